@@ -1,17 +1,48 @@
+use std::net::SocketAddr;
+
 use egui::Ui;
 use glam::{Mat4, Vec3, Vec4};
 use rfd::FileDialog;
+use engine::lib::assets::LoadState;
+use engine::lib::dependency_graph::AssetRef;
 use engine::lib::Dirtyable;
+use engine::lib::geometry::Geometry;
 
+use engine::lib::scene::bindings::{Axis, Binding, Property};
+use engine::lib::scene::character::CharacterController;
+use engine::lib::scene::joint::{Joint, JointKind};
+use engine::lib::scene::lighting::{Ambient, ShadowFilter};
+use engine::lib::scene::material::PbrMaterialDesc;
+use engine::lib::scene::mesh::{Mesh, Outline};
 use engine::lib::scene::model::Model;
+use engine::lib::scene::physics::{BodyKind, Collider, ColliderShape, PhysicsSettings, RigidBody};
+use engine::lib::scene::sky::{Background, Sky};
+use engine::lib::scene::water::Water;
 use engine::lib::scene::World;
-use engine::renderer::camera::Camera;
-use engine::renderer::{commands, Meta};
+use engine::renderer::calibration::Calibration;
+use engine::renderer::camera::{AxisView, Camera, CameraMode, DepthMode};
+use engine::renderer::drag::DragConstraint;
+use engine::renderer::gpu_memory::GpuMemory;
+use engine::renderer::gpu_timing::{PassBudgets, PassTiming};
+use engine::renderer::guides::AspectRatio;
+use engine::renderer::material_inspector::{material_inspector, TEXTURE_SLOTS};
+use engine::renderer::{commands, Meta, PresentMode};
 use engine::renderer::commands::Commands;
+use engine::renderer::pipelines::grid::Grid;
+use engine::renderer::pipelines::outlining::OutlineMode;
+use engine::renderer::pipelines::pbr::RenderLayer;
+use engine::renderer::window::WindowMode;
+use engine::renderer::pipelines::split_screen::{PlayerView, SplitScreen};
+use engine::renderer::pipelines::stereo::StereoMode;
+use engine::renderer::profiling::FrameGraph;
+use engine::renderer::time::TimeControl;
 
-use crate::util::{CameraModes, Editable, SparseModel, SparseScene};
+use crate::util::{Editable, SparseMaterial, SparseModel, SparseScene};
 use crate::{mutate_indirect, observe};
 
+// the port DCC plug-ins connect to
+const LIVE_LINK_PORT: u16 = 9787;
+
 pub(crate) fn update_ui(ctx: &egui::Context, world: &mut World, camera: &mut Camera, commands: Commands, meta: &mut Meta) {
     egui::Window::new("World").show(ctx, |ui| {
         ui.horizontal(|ui| {
@@ -27,9 +58,39 @@ pub(crate) fn update_ui(ctx: &egui::Context, world: &mut World, camera: &mut Cam
                     commands.send(commands::Command::ImportFile(file)).unwrap();
                 }
             }
+            if ui.button("New Scene").clicked() {
+                commands.send(commands::Command::CreateScene("Scene".into())).unwrap();
+            }
+            ui.menu_button("View", |ui| view_menu_ui(ui, meta, camera));
         });
         ui.label(format!("Frame time: {:.2} ms", meta.frame_time * 1000.0));
         ui.label(format!("FPS: {:.0}", 1.0 / meta.frame_time));
+        let adapter = meta.adapter();
+        ui.label(format!(
+            "Adapter: {} ({:?}, {:?})",
+            adapter.name, adapter.backend, adapter.device_type
+        ));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut meta.stats_overlay.enabled, "Stats overlay");
+            if meta.stats_overlay.enabled {
+                ui.checkbox(&mut meta.stats_overlay.graph, "Frame graph");
+            }
+        });
+        ui.collapsing("Profiler", |ui| {
+            ui.add(FrameGraph::new(&meta.frame_history));
+            ui.label("CPU");
+            timings_ui(ui, "cpu_timings", &meta.cpu_timings, None);
+            if !meta.gpu_timings.is_empty() {
+                ui.label("GPU passes");
+                timings_ui(ui, "gpu_timings", &meta.gpu_timings, Some(&mut meta.pass_budgets));
+                ui.add(egui::Slider::new(&mut meta.pass_budgets.frames, 1..=120).text("Frames over budget to warn"));
+            }
+            ui.label(format!(
+                "PBR draw calls: {}, bind group switches: {}",
+                meta.draw_stats.draw_calls, meta.draw_stats.bind_group_switches
+            ));
+        });
+        ui.collapsing("GPU memory", |ui| gpu_memory_ui(ui, &mut meta.gpu_memory));
         observe!(
             meta.vsync,
             {
@@ -39,35 +100,409 @@ pub(crate) fn update_ui(ctx: &egui::Context, world: &mut World, camera: &mut Cam
                 commands.send(commands::Command::SetVsync).unwrap();
             }
         );
+        let mut present_mode = meta.present_mode;
+        egui::ComboBox::from_label("Present mode")
+            .selected_text(format!("{:?}", present_mode))
+            .show_ui(ui, |ui| {
+                let auto = [PresentMode::AutoVsync, PresentMode::AutoNoVsync];
+                for mode in auto.iter().chain(meta.supported_present_modes()) {
+                    ui.selectable_value(&mut present_mode, *mode, format!("{:?}", mode));
+                }
+            });
+        if present_mode != meta.present_mode {
+            commands.send(commands::Command::SetPresentMode(present_mode)).unwrap();
+        }
+        let mut surface_format = meta.surface_format;
+        egui::ComboBox::from_label("Surface format")
+            .selected_text(format!("{:?}", surface_format))
+            .show_ui(ui, |ui| {
+                for format in meta.supported_surface_formats() {
+                    ui.selectable_value(&mut surface_format, *format, format!("{:?}", format));
+                }
+            });
+        if surface_format != meta.surface_format {
+            commands
+                .send(commands::Command::SetSurfaceFormat(surface_format))
+                .unwrap();
+        }
+        let mut window_mode = meta.window_mode;
+        egui::ComboBox::from_label("Window mode")
+            .selected_text(format!("{:?}", window_mode))
+            .show_ui(ui, |ui| {
+                for mode in [WindowMode::Windowed, WindowMode::Borderless, WindowMode::Fullscreen] {
+                    ui.selectable_value(&mut window_mode, mode, format!("{:?}", mode));
+                }
+            });
+        if window_mode != meta.window_mode {
+            commands.send(commands::Command::SetWindowMode(window_mode)).unwrap();
+        }
         ui.checkbox(&mut meta.show_grid, "Show Grid");
+        if meta.show_grid {
+            egui::CollapsingHeader::new("Grid").show(ui, |ui| grid_ui(ui, &mut meta.grid));
+        }
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut meta.axes_gizmo.corner, "Orientation gizmo");
+            ui.checkbox(&mut meta.axes_gizmo.selection, "Selection axes");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut meta.light_gizmos.icons, "Light icons");
+            ui.checkbox(&mut meta.light_gizmos.ranges, "Light ranges");
+        });
+        ui.horizontal(|ui| {
+            ui.label("View");
+            for view in AxisView::ALL {
+                if ui.button(format!("{:?}", view)).clicked() {
+                    commands.send(commands::Command::SetCameraView(view)).unwrap();
+                }
+            }
+        });
+        ui.checkbox(&mut meta.depth_prepass, "Depth pre-pass");
+        let mut reverse_z = camera.depth_mode() == DepthMode::ReverseZ;
+        if ui.checkbox(&mut reverse_z, "Reverse-Z").changed() {
+            let depth_mode = if reverse_z {
+                DepthMode::ReverseZ
+            } else {
+                DepthMode::Standard
+            };
+            commands.send(commands::Command::SetDepthMode(depth_mode)).unwrap();
+        }
+        egui::ComboBox::from_label("Render layer")
+            .selected_text(format!("{:?}", meta.debug_layer))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut meta.debug_layer, RenderLayer::Full, "Full");
+                ui.selectable_value(&mut meta.debug_layer, RenderLayer::Emissive, "Emissive");
+                ui.selectable_value(&mut meta.debug_layer, RenderLayer::DirectLighting, "Direct lighting");
+            });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut meta.motion_blur.enabled, "Motion Blur");
+            ui.add_enabled(
+                meta.motion_blur.enabled,
+                egui::Slider::new(&mut meta.motion_blur.intensity, 0.0..=2.0).text("Intensity"),
+            );
+            ui.add_enabled(
+                meta.motion_blur.enabled,
+                egui::Slider::new(&mut meta.motion_blur.samples, 1..=32).text("Samples"),
+            );
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Load LUT").clicked() {
+                let picked_file = FileDialog::new().add_filter("Cube LUTs", &["cube"]).pick_file();
+                if let Some(file) = picked_file {
+                    commands.send(commands::Command::LoadLut(file)).unwrap();
+                }
+            }
+            if ui.button("Clear LUT").clicked() {
+                commands.send(commands::Command::ClearLut).unwrap();
+            }
+        });
+        ui.horizontal(|ui| match meta.live_link() {
+            Some(address) => {
+                ui.label(format!("Live link on {}", address));
+                if ui.button("Stop").clicked() {
+                    commands.send(commands::Command::StopLiveLink).unwrap();
+                }
+            }
+            None => {
+                if ui.button("Start live link").clicked() {
+                    let address = SocketAddr::from(([127, 0, 0, 1], LIVE_LINK_PORT));
+                    commands
+                        .send(commands::Command::StartLiveLink {
+                            address,
+                            allow_remote: false,
+                        })
+                        .unwrap();
+                }
+            }
+        });
         egui::CollapsingHeader::new("Camera").show(ui, |ui| {
             if ui.button("Reset").clicked() {
                 camera.reset();
             }
             ui.horizontal(|ui| {
-                let mut mode = if camera.fps {
-                    CameraModes::FPS
-                } else {
-                    CameraModes::Arcball
-                };
-                ui.selectable_value(&mut mode, CameraModes::Arcball, "Arcball");
-                ui.selectable_value(&mut mode, CameraModes::FPS, "FPS");
-                camera.fps = mode == CameraModes::FPS;
+                ui.selectable_value(&mut camera.mode, CameraMode::Arcball, "Arcball");
+                ui.selectable_value(&mut camera.mode, CameraMode::Fly, "FPS");
+                ui.selectable_value(&mut camera.mode, CameraMode::Walk, "Walk");
+            });
+            ui.add(
+                egui::DragValue::new(&mut camera.znear)
+                    .speed(0.001)
+                    .clamp_range(0.0001..=camera.zfar)
+                    .prefix("Near plane: "),
+            );
+            if camera.mode == CameraMode::Walk {
+                let walk = &mut camera.walk;
+                ui.add(egui::Slider::new(&mut walk.eye_height, 0.1..=500.0).text("Eye height"));
+                ui.add(egui::Slider::new(&mut walk.radius, 0.01..=100.0).text("Radius"));
+                ui.add(egui::Slider::new(&mut walk.step_height, 0.0..=100.0).text("Step height"));
+                ui.add(egui::Slider::new(&mut walk.speed, 0.1..=1000.0).text("Speed"));
+                ui.add(egui::Slider::new(&mut walk.jump_speed, 0.0..=1000.0).text("Jump speed"));
+                ui.label(if walk.grounded() { "Grounded" } else { "Airborne" });
+            }
+        });
+
+        egui::CollapsingHeader::new("Time").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                let time = &meta.time;
+                if ui.button(if time.paused { "Resume" } else { "Pause" }).clicked() {
+                    commands.send(commands::Command::SetPaused(!time.paused)).unwrap();
+                }
+                if ui.add_enabled(time.paused, egui::Button::new("Step")).clicked() {
+                    commands.send(commands::Command::StepFrame).unwrap();
+                }
+                ui.label(format!("{:.2} s", time.elapsed()));
+            });
+            let mut scale = meta.time.scale();
+            let slider = egui::Slider::new(&mut scale, TimeControl::MIN_SCALE..=TimeControl::MAX_SCALE)
+                .logarithmic(true)
+                .text("Time scale");
+            if ui.add(slider).changed() {
+                commands.send(commands::Command::SetTimeScale(scale)).unwrap();
+            }
+        });
+
+        let selected = meta.selection().meshes().len() + meta.selection().direct_models().len();
+        egui::CollapsingHeader::new(format!("Selection ({})", selected)).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Duplicate").clicked() {
+                    commands.send(commands::Command::DuplicateSelection).unwrap();
+                }
+                if ui.button("Delete").clicked() {
+                    commands.send(commands::Command::DeleteSelection).unwrap();
+                }
+                if ui.button("Clear").clicked() {
+                    commands.send(commands::Command::ClearSelection).unwrap();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Move");
+                for (label, offset) in [
+                    ("-X", Vec3::NEG_X),
+                    ("+X", Vec3::X),
+                    ("-Y", Vec3::NEG_Y),
+                    ("+Y", Vec3::Y),
+                    ("-Z", Vec3::NEG_Z),
+                    ("+Z", Vec3::Z),
+                ] {
+                    if ui.small_button(label).clicked() {
+                        commands.send(commands::Command::MoveSelection(offset)).unwrap();
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut meta.snap.enabled, "Snap");
+                ui.add_enabled(
+                    meta.snap.enabled,
+                    egui::DragValue::new(&mut meta.snap.translation)
+                        .speed(0.05)
+                        .clamp_range(0.0..=100.0)
+                        .suffix(" m"),
+                );
+                ui.add_enabled(
+                    meta.snap.enabled,
+                    egui::DragValue::new(&mut meta.snap.rotation)
+                        .speed(1.0)
+                        .clamp_range(0.0..=180.0)
+                        .suffix("°"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut meta.drag.enabled, "Drag");
+                ui.checkbox(&mut meta.drag.marquee, "Marquee");
+                egui::ComboBox::from_id_source("drag_constraint")
+                    .selected_text(format!("{:?}", meta.drag.constraint))
+                    .show_ui(ui, |ui| {
+                        let constraint = &mut meta.drag.constraint;
+                        ui.selectable_value(constraint, DragConstraint::View, "View");
+                        for axis in [Axis::X, Axis::Y, Axis::Z] {
+                            ui.selectable_value(constraint, DragConstraint::Plane(axis), format!("Plane {:?}", axis));
+                            ui.selectable_value(constraint, DragConstraint::Axis(axis), format!("Axis {:?}", axis));
+                        }
+                    });
             });
         });
 
         if let Some(scene) = world.scenes.get_mut(&world.active_scene) {
             egui::CollapsingHeader::new("Outline").show(ui, |ui| {
+                egui::ComboBox::from_label("Mode")
+                    .selected_text(format!("{:?}", meta.outline_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [OutlineMode::Stencil, OutlineMode::JumpFlood] {
+                            ui.selectable_value(&mut meta.outline_mode, mode, format!("{:?}", mode));
+                        }
+                    });
+                ui.label("Selection");
+                outline_ui(ui, &mut scene.selection_outline);
+                let mut hover = scene.hover_outline.is_some();
+                ui.checkbox(&mut hover, "Outline hovered mesh");
+                if hover != scene.hover_outline.is_some() {
+                    scene.hover_outline = hover.then(|| Outline {
+                        color: [255, 200, 0],
+                        ..Outline::default()
+                    });
+                }
+                if let Some(outline) = &mut scene.hover_outline {
+                    outline_ui(ui, outline);
+                }
+            });
+            egui::CollapsingHeader::new("Background").show(ui, |ui| {
                 ui.horizontal(|ui| {
-                    ui.label("Width");
-                    ui.add(egui::DragValue::new(&mut scene.outline_width));
+                    if ui
+                        .selectable_label(matches!(scene.background, Background::Color(_)), "Color")
+                        .clicked()
+                    {
+                        scene.background = Background::default();
+                    }
+                    if ui
+                        .selectable_label(matches!(scene.background, Background::Sky(_)), "Sky")
+                        .clicked()
+                    {
+                        scene.background = Background::Sky(Sky::default());
+                    }
+                });
+                match &mut scene.background {
+                    Background::Color(color) => {
+                        ui.color_edit_button_rgb(color);
+                    }
+                    Background::Sky(sky) => {
+                        ui.add(egui::Slider::new(&mut sky.time_of_day, 0.0..=24.0).text("Time of day"));
+                        ui.add(egui::Slider::new(&mut sky.turbidity, 1.7..=10.0).text("Turbidity"));
+                        ui.add(egui::Slider::new(&mut sky.azimuth, 0.0..=std::f32::consts::TAU).text("Azimuth"));
+                        ui.add(egui::Slider::new(&mut sky.latitude, 0.0..=1.5).text("Latitude"));
+                        ui.add(egui::Slider::new(&mut sky.exposure, 0.001..=1.0).logarithmic(true).text("Exposure"));
+                    }
+                }
+            });
+            egui::CollapsingHeader::new("Water").show(ui, |ui| match &mut scene.water {
+                Some(water) => {
+                    ui.horizontal(|ui| {
+                        ui.label("Level");
+                        ui.add(egui::DragValue::new(&mut water.position.y).speed(0.1));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Size");
+                        ui.add(egui::DragValue::new(&mut water.size.x));
+                        ui.add(egui::DragValue::new(&mut water.size.y));
+                    });
+                    ui.color_edit_button_rgb(&mut water.color);
+                    ui.add(egui::Slider::new(&mut water.wave_strength, 0.0..=0.1).text("Wave strength"));
+                    ui.add(egui::Slider::new(&mut water.wave_speed, 0.0..=5.0).text("Wave speed"));
+                    ui.add(egui::Slider::new(&mut water.wave_scale, 1.0..=500.0).text("Wave scale"));
+                    if ui.button("Remove water").clicked() {
+                        commands.send(commands::Command::RemoveWater).unwrap();
+                    }
+                }
+                None => {
+                    if ui.button("Add water").clicked() {
+                        commands
+                            .send(commands::Command::CreateWater(Water::default()))
+                            .unwrap();
+                    }
+                }
+            });
+            egui::CollapsingHeader::new("Lighting").show(ui, |ui| {
+                let lighting = &mut scene.lighting;
+                let mut hemisphere = matches!(lighting.ambient, Ambient::Hemisphere { .. });
+                if ui.checkbox(&mut hemisphere, "Hemispheric ambient").changed() {
+                    let (sky, ground) = lighting.ambient.sky_and_ground();
+                    lighting.ambient = if hemisphere {
+                        Ambient::Hemisphere { sky, ground }
+                    } else {
+                        Ambient::Flat { color: sky }
+                    };
+                }
+                ui.horizontal(|ui| match &mut lighting.ambient {
+                    Ambient::Flat { color } => {
+                        ui.label("Ambient");
+                        ui.color_edit_button_rgb(color.as_mut());
+                    }
+                    Ambient::Hemisphere { sky, ground } => {
+                        ui.label("Sky");
+                        ui.color_edit_button_rgb(sky.as_mut());
+                        ui.label("Ground");
+                        ui.color_edit_button_rgb(ground.as_mut());
+                    }
                 });
+                ui.add(
+                    egui::Slider::new(&mut lighting.ambient_intensity, 0.0..=1.0)
+                        .logarithmic(true)
+                        .text("Ambient intensity"),
+                );
+                let filter = &mut lighting.shadow_filter;
+                let kernel_size = filter.kernel_size();
+                let selected = match filter {
+                    ShadowFilter::Hard => "Hard",
+                    ShadowFilter::Pcf { .. } => "PCF",
+                    ShadowFilter::Pcss { .. } => "PCSS",
+                };
+                let mut mode = selected;
+                egui::ComboBox::from_label("Shadow filter")
+                    .selected_text(mode)
+                    .show_ui(ui, |ui| {
+                        for option in ["Hard", "PCF", "PCSS"] {
+                            ui.selectable_value(&mut mode, option, option);
+                        }
+                    });
+                if mode != selected {
+                    *filter = match mode {
+                        "Hard" => ShadowFilter::Hard,
+                        "PCF" => ShadowFilter::Pcf { kernel_size },
+                        _ => ShadowFilter::Pcss {
+                            kernel_size,
+                            light_size: 10.0,
+                        },
+                    };
+                }
+                match filter {
+                    ShadowFilter::Hard => {}
+                    ShadowFilter::Pcf { kernel_size } => {
+                        ui.add(egui::Slider::new(kernel_size, 1..=ShadowFilter::MAX_KERNEL_SIZE).text("Kernel size"));
+                    }
+                    ShadowFilter::Pcss {
+                        kernel_size,
+                        light_size,
+                    } => {
+                        ui.add(egui::Slider::new(kernel_size, 1..=ShadowFilter::MAX_KERNEL_SIZE).text("Kernel size"));
+                        ui.add(egui::Slider::new(light_size, 0.0..=100.0).text("Light size"));
+                    }
+                }
+            });
+            egui::CollapsingHeader::new("Post Effects").show(ui, |ui| {
+                let effects = &mut scene.post_effects;
+                let ca = &mut effects.chromatic_aberration;
+                ui.checkbox(&mut ca.enabled, "Chromatic aberration");
+                ui.add_enabled(ca.enabled, egui::Slider::new(&mut ca.strength, 0.0..=0.02).text("Strength"));
+                let vignette = &mut effects.vignette;
+                ui.checkbox(&mut vignette.enabled, "Vignette");
+                ui.add_enabled_ui(vignette.enabled, |ui| {
+                    ui.add(egui::Slider::new(&mut vignette.intensity, 0.0..=1.0).text("Intensity"));
+                    ui.add(egui::Slider::new(&mut vignette.radius, 0.0..=1.0).text("Radius"));
+                    ui.add(egui::Slider::new(&mut vignette.softness, 0.0..=1.0).text("Softness"));
+                });
+                let grain = &mut effects.grain;
+                ui.checkbox(&mut grain.enabled, "Film grain");
+                ui.add_enabled_ui(grain.enabled, |ui| {
+                    ui.add(egui::Slider::new(&mut grain.intensity, 0.0..=0.3).text("Intensity"));
+                    ui.add(egui::Slider::new(&mut grain.size, 1.0..=4.0).text("Size"));
+                });
+            });
+            egui::CollapsingHeader::new("Physics").show(ui, |ui| {
+                let physics = &mut scene.physics;
                 ui.horizontal(|ui| {
-                    ui.label("Color");
-                    ui.add(egui::DragValue::new(&mut scene.outline_color[0]));
-                    ui.add(egui::DragValue::new(&mut scene.outline_color[1]));
-                    ui.add(egui::DragValue::new(&mut scene.outline_color[2]));
+                    ui.label("Gravity");
+                    ui.add(egui::DragValue::new(&mut physics.gravity.x).speed(0.1));
+                    ui.add(egui::DragValue::new(&mut physics.gravity.y).speed(0.1));
+                    ui.add(egui::DragValue::new(&mut physics.gravity.z).speed(0.1));
                 });
+                let mut rate = 1.0 / physics.timestep;
+                if ui.add(egui::Slider::new(&mut rate, 10.0..=240.0).text("Steps per second")).changed() {
+                    physics.timestep = 1.0 / rate;
+                }
+                ui.add(egui::Slider::new(&mut physics.max_steps_per_frame, 1..=16).text("Max steps per frame"));
+                ui.add(egui::Slider::new(&mut physics.solver_iterations, 1..=32).text("Solver iterations"));
+                ui.add(egui::Slider::new(&mut physics.friction_iterations, 0..=32).text("Friction iterations"));
+                if ui.button("Reset").clicked() {
+                    *physics = PhysicsSettings::default();
+                }
             });
         }
 
@@ -91,22 +526,90 @@ pub(crate) fn update_ui(ctx: &egui::Context, world: &mut World, camera: &mut Cam
             })
             .collect();
 
-        for (_, scene) in world.scenes.iter_mut() {
+        let sparse_materials: Vec<SparseMaterial> = world
+            .assets
+            .materials
+            .iter_with_ids()
+            .map(|(id, material)| SparseMaterial {
+                id,
+                name: material.name().clone(),
+            })
+            .collect();
+
+        let active_scene = world.active_scene;
+        for (id, scene) in world.scenes.iter_mut() {
             egui::CollapsingHeader::new(format!(
                 "Scene {}",
                 scene.name.clone().unwrap_or(format!("{}", scene.id).into())
             ))
             .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if *id == active_scene {
+                        ui.label("Active");
+                    } else if ui.button("Make active").clicked() {
+                        commands.send(commands::Command::SetActiveScene(*id)).unwrap();
+                    }
+                    ui.menu_button("Rename", |ui| {
+                        let name_id = ui.id().with(("scene_name", *id));
+                        let mut name = ui
+                            .data_mut(|data| data.get_temp::<String>(name_id))
+                            .unwrap_or_else(|| scene.name.as_deref().unwrap_or_default().to_string());
+                        ui.text_edit_singleline(&mut name);
+                        if ui.button("Rename").clicked() && !name.is_empty() {
+                            commands
+                                .send(commands::Command::RenameScene {
+                                    scene_id: *id,
+                                    name: name.clone(),
+                                })
+                                .unwrap();
+                            ui.close_menu();
+                        }
+                        ui.data_mut(|data| data.insert_temp(name_id, name));
+                    });
+                    if ui.button("Delete").clicked() {
+                        commands.send(commands::Command::DeleteScene(*id)).unwrap();
+                    }
+                });
                 add_model_menu(ui, &commands, None);
                 for model in scene.models.as_mut_slice().iter_mut() {
-                    draw_model_ui(model, scene.id, &sparse_scenes, &sparse_models, ui, &commands);
+                    draw_model_ui(
+                        model,
+                        scene.id,
+                        &sparse_scenes,
+                        &sparse_models,
+                        &sparse_materials,
+                        ui,
+                        &commands,
+                    );
                 }
+                bindings_ui(ui, scene.id, &scene.bindings, &sparse_models, &commands);
             });
         }
     });
 
+    egui::Window::new("Dependency Graph").default_open(false).show(ctx, |ui| {
+        dependency_graph_ui(ui, world);
+    });
+
+    egui::Window::new("Display Calibration").default_open(false).show(ctx, |ui| {
+        calibration_ui(ui, &mut meta.calibration);
+    });
+
     egui::Window::new("Textures & Materials").default_open(false).show(ctx, |ui| {
-        for (texid, texture) in world.textures.iter_with_ids() {
+        ui.menu_button("Import Texture", |ui| {
+            for kind in TEXTURE_SLOTS {
+                if ui.button(format!("{:?}", kind)).clicked() {
+                    ui.close_menu();
+                    let picked_file = FileDialog::new()
+                        .add_filter("Images", &["png", "jpg", "jpeg", "tga", "bmp"])
+                        .pick_file();
+                    if let Some(file) = picked_file {
+                        commands.send(commands::Command::ImportTexture(file, kind)).unwrap();
+                    }
+                }
+            }
+        });
+        for (texid, texture) in world.assets.textures.iter_with_ids() {
             egui::CollapsingHeader::new(format!(
                 "Texture {:?} {} {}",
                 texid,
@@ -115,31 +618,352 @@ pub(crate) fn update_ui(ctx: &egui::Context, world: &mut World, camera: &mut Cam
             ))
             .show(ui, |ui| {
                 ui.label(format!("Kind: {:?}", texture.kind));
+                meta.texture_thumbnails.show(ui, texid, texture, 128.0);
             });
         }
         ui.separator();
-        for (matid, material) in world.materials.iter_with_ids() {
+        if ui.button("New Material").clicked() {
+            let desc = PbrMaterialDesc {
+                name: Some("Material".into()),
+                ..Default::default()
+            };
+            commands.send(commands::Command::CreateMaterial(desc)).unwrap();
+        }
+        if ui
+            .button("Purge Unused")
+            .on_hover_text("Removes the materials no mesh uses and the textures no material uses")
+            .clicked()
+        {
+            commands.send(commands::Command::PurgeUnused).unwrap();
+        }
+        for (matid, material) in world.assets.materials.iter_with_ids() {
             egui::CollapsingHeader::new(format!("Material {:?} {:?}", matid, material.name())).show(ui, |ui| {
-                match material {
-                    engine::lib::Material::Pbr(pbr) => {
-                        ui.label(format!("Name: {:?}", pbr.name));
-                        ui.label(format!("Albedo: {:?}", pbr.albedo));
-                        ui.label(format!(
-                            "Metallic Roughness Factors: {:?}",
-                            pbr.metallic_roughness_factors
-                        ));
-                        ui.label(format!("Ambient Occlusion Factor: {:?}", pbr.occlusion_factor));
-                        ui.label(format!("Emissive Factors: {:?}", pbr.emissive_factors));
-                        ui.label(format!("Albedo Texture: {:?}", pbr.albedo_texture));
-                        ui.label(format!("Normal Texture: {:?}", pbr.normal_texture));
-                        ui.label(format!(
-                            "Metallic Roughness Texture: {:?}",
-                            pbr.metallic_roughness_texture
-                        ));
-                        ui.label(format!("Ambient Occlusion Texture: {:?}", pbr.occlusion_texture));
-                        ui.label(format!("Emissive Texture: {:?}", pbr.emissive_texture));
+                material_inspector(ui, matid, world, &commands, &mut meta.material_preview);
+            });
+        }
+        ui.separator();
+        egui::CollapsingHeader::new("Sources").show(ui, |ui| {
+            ui.checkbox(&mut meta.hot_reload, "Reload changed files");
+            for (path, load_state) in world.assets.sources() {
+                let file = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                match load_state {
+                    LoadState::Loading => ui.label(format!("{}: loading", file)),
+                    LoadState::Loaded => ui.label(format!("{}: loaded", file)),
+                    LoadState::Failed(error) => {
+                        ui.colored_label(ui.visuals().error_fg_color, format!("{}: {}", file, error))
                     }
                 }
+                .on_hover_text(path.to_string_lossy());
+            }
+        });
+    });
+}
+
+// per-category totals of the world's GPU memory, and its share of an optional budget
+fn gpu_memory_ui(ui: &mut Ui, memory: &mut GpuMemory) {
+    const MIB: f64 = 1024.0 * 1024.0;
+    let mib = |bytes: u64| format!("{:.1} MiB", bytes as f64 / MIB);
+    egui::Grid::new("gpu_memory").striped(true).show(ui, |ui| {
+        for (label, bytes) in [
+            ("Vertex buffers", memory.vertex_buffers),
+            ("Index buffers", memory.index_buffers),
+            ("Textures", memory.textures),
+            ("Buffer arrays", memory.buffer_arrays),
+        ] {
+            ui.label(label);
+            ui.label(mib(bytes));
+            ui.end_row();
+        }
+        ui.strong("Total");
+        ui.strong(mib(memory.total()));
+        ui.end_row();
+    });
+    let mut has_budget = memory.budget.is_some();
+    ui.checkbox(&mut has_budget, "Budget");
+    if !has_budget {
+        memory.budget = None;
+        return;
+    }
+    let mut budget = memory.budget.map_or(512.0, |budget| budget as f64 / MIB);
+    ui.add(
+        egui::DragValue::new(&mut budget)
+            .clamp_range(1.0..=65536.0)
+            .suffix(" MiB"),
+    );
+    memory.budget = Some((budget * MIB) as u64);
+    let fraction = memory.total() as f64 / (budget * MIB);
+    let mut bar = egui::ProgressBar::new(fraction.min(1.0) as f32).text(format!("{:.0}%", fraction * 100.0));
+    if memory.over_budget() {
+        bar = bar.fill(ui.visuals().error_fg_color);
+    }
+    ui.add(bar);
+}
+
+// passes that are over their budget are highlighted, and budgets can be edited if given
+fn timings_ui(ui: &mut Ui, id: &str, timings: &[PassTiming], mut budgets: Option<&mut PassBudgets>) {
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for timing in timings {
+            let color = match budgets.as_ref().is_some_and(|b| b.is_over_budget(timing.label)) {
+                true => ui.visuals().error_fg_color,
+                false => ui.visuals().text_color(),
+            };
+            ui.colored_label(color, timing.label);
+            ui.colored_label(color, format!("{:.3} ms", timing.milliseconds));
+            if let Some(budgets) = budgets.as_mut() {
+                let mut remove = false;
+                match budgets.budgets.get_mut(timing.label) {
+                    Some(budget) => {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::DragValue::new(budget)
+                                    .speed(0.01)
+                                    .clamp_range(0.0..=100.0)
+                                    .suffix(" ms"),
+                            );
+                            remove = ui.small_button("x").on_hover_text("Remove budget").clicked();
+                        });
+                    }
+                    None => {
+                        if ui.small_button("Set budget").clicked() {
+                            budgets.budgets.insert(timing.label, timing.milliseconds);
+                        }
+                    }
+                }
+                if remove {
+                    budgets.budgets.remove(timing.label);
+                }
+            }
+            ui.end_row();
+        }
+        ui.strong("Total");
+        ui.strong(format!("{:.3} ms", timings.iter().map(|t| t.milliseconds).sum::<f32>()));
+        ui.end_row();
+    });
+}
+
+fn view_menu_ui(ui: &mut Ui, meta: &mut Meta, camera: &Camera) {
+    ui.checkbox(&mut meta.show_grid, "Grid");
+    ui.separator();
+    ui.label("Guides");
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut meta.guides.frame, None, "No frame");
+        for aspect in [AspectRatio::SixteenByNine, AspectRatio::FourByThree] {
+            ui.selectable_value(&mut meta.guides.frame, Some(aspect), aspect.name());
+        }
+    });
+    ui.checkbox(&mut meta.guides.thirds, "Rule of thirds");
+    ui.checkbox(&mut meta.guides.action_safe, "Action safe");
+    ui.checkbox(&mut meta.guides.title_safe, "Title safe");
+    ui.separator();
+    ui.checkbox(&mut meta.stereo.enabled, "Stereo preview");
+    ui.add_enabled_ui(meta.stereo.enabled, |ui| {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut meta.stereo.mode, StereoMode::Anaglyph, "Anaglyph");
+            ui.selectable_value(&mut meta.stereo.mode, StereoMode::SideBySide, "Side by side");
+        });
+        ui.add(egui::Slider::new(&mut meta.stereo.eye_separation, 0.0..=0.2).text("Eye separation"));
+        ui.add(
+            egui::Slider::new(&mut meta.stereo.convergence, 0.1..=10.0)
+                .logarithmic(true)
+                .text("Convergence"),
+        );
+    });
+    ui.separator();
+    ui.checkbox(&mut meta.split_screen.enabled, "Split screen");
+    ui.add_enabled_ui(meta.split_screen.enabled, |ui| {
+        let mut players = meta.split_screen.players.len().max(SplitScreen::MIN_PLAYERS);
+        ui.add(egui::Slider::new(&mut players, SplitScreen::MIN_PLAYERS..=SplitScreen::MAX_PLAYERS).text("Players"));
+        if players != meta.split_screen.players.len() {
+            meta.split_screen.set_player_count(players, camera);
+        }
+        ui.horizontal(|ui| {
+            ui.label("Move to camera:");
+            for (i, player) in meta.split_screen.players.iter_mut().enumerate() {
+                if ui.button(format!("Player {}", i + 1)).clicked() {
+                    *player = PlayerView::from(camera);
+                }
+            }
+        });
+    });
+    ui.separator();
+    ui.label("Physics debug");
+    ui.checkbox(&mut meta.physics_debug.joints, "Joints");
+    ui.add_enabled_ui(!meta.physics_debug.is_empty(), |ui| {
+        ui.add(egui::Slider::new(&mut meta.physics_debug.marker_size, 0.5..=50.0).text("Marker size"));
+    });
+    ui.separator();
+    ui.checkbox(&mut meta.light_lod.enabled, "Light LOD");
+    ui.add_enabled_ui(meta.light_lod.enabled, |ui| {
+        let lod = &mut meta.light_lod;
+        ui.add(
+            egui::Slider::new(&mut lod.max_distance, 10.0..=10000.0)
+                .logarithmic(true)
+                .text("Max distance"),
+        );
+        ui.add(egui::Slider::new(&mut lod.fade_distance, 0.0..=lod.max_distance).text("Fade distance"));
+        ui.add(egui::Slider::new(&mut lod.min_screen_size, 0.0..=0.2).text("Min screen size"));
+    });
+    ui.separator();
+    ui.checkbox(&mut meta.imposter_lod.enabled, "Imposters");
+    ui.add_enabled(
+        meta.imposter_lod.enabled,
+        egui::Slider::new(&mut meta.imposter_lod.distance, 10.0..=20000.0)
+            .logarithmic(true)
+            .text("Imposter distance"),
+    );
+    ui.separator();
+    ui.checkbox(&mut meta.streaming.enabled, "Cell streaming");
+    ui.add_enabled_ui(meta.streaming.enabled, |ui| {
+        let streaming = &mut meta.streaming;
+        ui.add(
+            egui::Slider::new(&mut streaming.cell_size, 10.0..=10000.0)
+                .logarithmic(true)
+                .text("Cell size"),
+        );
+        ui.add(
+            egui::Slider::new(&mut streaming.load_distance, 10.0..=10000.0)
+                .logarithmic(true)
+                .text("Load distance"),
+        );
+        ui.add(
+            egui::Slider::new(&mut streaming.unload_distance, streaming.load_distance..=20000.0)
+                .logarithmic(true)
+                .text("Unload distance"),
+        );
+    });
+}
+
+fn calibration_ui(ui: &mut Ui, calibration: &mut Calibration) {
+    const PATCH_SIZE: f32 = 48.0;
+    // radiance levels from black up to the darkest mid-tones
+    const BRIGHTNESS_STEPS: [f32; 7] = [0.0, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1];
+
+    ui.add(egui::Slider::new(&mut calibration.brightness, 0.1..=4.0).logarithmic(true).text("Brightness"));
+    ui.label("Increase the brightness until the second square is just barely distinguishable from the first one.");
+    ui.horizontal(|ui| {
+        for radiance in BRIGHTNESS_STEPS {
+            let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(PATCH_SIZE), egui::Sense::hover());
+            // the pattern goes through the same calibration as the scene, the surface then encodes it to sRGB
+            let value = egui::ecolor::gamma_u8_from_linear_f32(calibration.apply(radiance));
+            ui.painter().rect_filled(rect, 0.0, egui::Color32::from_gray(value));
+        }
+    });
+
+    ui.separator();
+    ui.add(egui::Slider::new(&mut calibration.gamma, 1.0..=3.0).text("Gamma"));
+    ui.label("Adjust the gamma until the solid square blends in with the stripes around it when seen from a distance.");
+    let (rect, _) = ui.allocate_exact_size(egui::Vec2::new(3.0 * PATCH_SIZE, PATCH_SIZE), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    // alternating black and white lines, one physical pixel each, average out to half the display's luminance
+    let pixel = 1.0 / ui.ctx().pixels_per_point();
+    painter.rect_filled(rect, 0.0, egui::Color32::BLACK);
+    let mut y = rect.top();
+    while y < rect.bottom() {
+        painter.rect_filled(
+            egui::Rect::from_min_size(egui::pos2(rect.left(), y), egui::vec2(rect.width(), pixel)),
+            0.0,
+            egui::Color32::WHITE,
+        );
+        y += 2.0 * pixel;
+    }
+    let gray = (0.5f32.powf(1.0 / calibration.gamma) * 255.0).round() as u8;
+    painter.rect_filled(
+        egui::Rect::from_center_size(rect.center(), egui::Vec2::splat(PATCH_SIZE)),
+        0.0,
+        egui::Color32::from_gray(gray),
+    );
+
+    if ui.button("Reset").clicked() {
+        *calibration = Calibration::default();
+    }
+}
+
+fn dependency_graph_ui(ui: &mut Ui, world: &World) {
+    const NODE_SIZE: egui::Vec2 = egui::vec2(140.0, 18.0);
+    const COLUMN_SPACING: f32 = 180.0;
+    const ROW_SPACING: f32 = 24.0;
+
+    let graph = world.dependency_graph();
+    let column = |asset: &AssetRef| match asset {
+        AssetRef::Scene(_) => 0,
+        AssetRef::Model(_) => 1,
+        AssetRef::Mesh(_) => 2,
+        AssetRef::Material(_) => 3,
+        AssetRef::Texture(_) => 4,
+    };
+    let mut rows = [0usize; 5];
+    let positions = graph
+        .nodes
+        .iter()
+        .map(|node| {
+            let column = column(&node.asset);
+            rows[column] += 1;
+            egui::vec2(column as f32 * COLUMN_SPACING, (rows[column] - 1) as f32 * ROW_SPACING)
+        })
+        .collect::<Vec<_>>();
+    let height = *rows.iter().max().unwrap_or(&0) as f32 * ROW_SPACING;
+
+    egui::ScrollArea::both().show(ui, |ui| {
+        let (response, painter) =
+            ui.allocate_painter(egui::vec2(5.0 * COLUMN_SPACING, height), egui::Sense::hover());
+        let origin = response.rect.min;
+        let rect_of = |i: usize| egui::Rect::from_min_size(origin + positions[i], NODE_SIZE);
+        let hovered = response
+            .hover_pos()
+            .and_then(|pos| (0..graph.nodes.len()).find(|i| rect_of(*i).contains(pos)));
+
+        for (from, to) in graph.edges.iter() {
+            let highlighted = hovered == Some(*from) || hovered == Some(*to);
+            painter.line_segment(
+                [rect_of(*from).right_center(), rect_of(*to).left_center()],
+                egui::Stroke::new(
+                    if highlighted { 2.0 } else { 1.0 },
+                    if highlighted {
+                        egui::Color32::LIGHT_BLUE
+                    } else {
+                        egui::Color32::DARK_GRAY
+                    },
+                ),
+            );
+        }
+        for (i, node) in graph.nodes.iter().enumerate() {
+            let referenced = graph.is_referenced(node.asset) || matches!(node.asset, AssetRef::Scene(_));
+            painter.rect(
+                rect_of(i),
+                2.0,
+                if hovered == Some(i) {
+                    egui::Color32::from_gray(70)
+                } else {
+                    egui::Color32::from_gray(40)
+                },
+                egui::Stroke::new(
+                    1.0,
+                    if referenced {
+                        egui::Color32::GRAY
+                    } else {
+                        egui::Color32::YELLOW
+                    },
+                ),
+            );
+            painter.text(
+                rect_of(i).left_center() + egui::vec2(4.0, 0.0),
+                egui::Align2::LEFT_CENTER,
+                &node.name,
+                egui::FontId::proportional(11.0),
+                egui::Color32::WHITE,
+            );
+        }
+
+        if let Some(hovered) = hovered {
+            let asset = graph.nodes[hovered].asset;
+            response.on_hover_ui_at_pointer(|ui| {
+                ui.label(format!("{:?}", asset));
+                let dependents = graph.dependents(asset).map(|n| n.name.as_str()).collect::<Vec<_>>();
+                if dependents.is_empty() {
+                    ui.label("Not used by any asset");
+                } else {
+                    ui.label(format!("Used by: {}", dependents.join(", ")));
+                }
             });
         }
     });
@@ -150,6 +974,7 @@ fn draw_model_ui(
     scene_id: u32,
     sparse_scenes: &Vec<SparseScene>,
     sparse_models: &Vec<SparseModel>,
+    sparse_materials: &[SparseMaterial],
     ui: &mut Ui,
     commands: &Commands,
 ) {
@@ -159,9 +984,12 @@ fn draw_model_ui(
     ))
     .show(ui, |ui| {
         ui.horizontal(|ui| {
+            visibility_toggle(ui, model.visible, model.id, commands);
             ui.label(format!("ID: {}", model.id));
             model_actions(model, scene_id, sparse_scenes, sparse_models, &commands, ui);
         });
+        ui.checkbox(&mut model.is_static, "Static")
+            .on_hover_text("Merges the meshes with those of other static models into fewer draw calls");
 
         observe!(
             model.local_transform,
@@ -223,31 +1051,315 @@ fn draw_model_ui(
                     }
                 );
                 ui.label(format!("Range: {:?}", light.range));
+                ui.checkbox(&mut light.casts_shadows, "Casts shadows")
+                    .on_hover_text("Renders six shadow map faces per frame, only a few lights get one at a time");
             });
         }
+        joints_ui(ui, model.id, &mut model.joints, sparse_models);
+        character_ui(ui, model.id, &mut model.character);
+        rigid_body_ui(ui, model.id, &mut model.rigid_body, &mut model.collider);
         for mesh in model.meshes.as_mut_slice().iter_mut() {
             egui::CollapsingHeader::new(format!("Mesh {}", mesh.id)).show(ui, |ui| {
+                visibility_toggle(ui, mesh.visible, mesh.id, commands);
                 mutate_indirect!(
                     mesh.is_outline(),
                     |outline| {
                         ui.checkbox(&mut outline, "Outline");
                     },
                     |mesh, outline| {
-                        mesh.set_outline(outline);
+                        mesh.set_outline(outline.then(Outline::default));
                     }
                 );
-                ui.label(format!("Material: {:?}", mesh.material));
+                material_combo(ui, mesh, sparse_materials, commands);
                 ui.label(format!("Vertices: {}", mesh.vertices.len()));
                 ui.label(format!("Indices: {}", mesh.indices.len()));
+                mesh_params_ui(ui, mesh);
             });
         }
         ui.separator();
         for child in model.children.as_mut_slice().iter_mut() {
-            draw_model_ui(child, scene_id, sparse_scenes, sparse_models, ui, commands);
+            draw_model_ui(
+                child,
+                scene_id,
+                sparse_scenes,
+                sparse_models,
+                sparse_materials,
+                ui,
+                commands,
+            );
         }
     });
 }
 
+fn material_combo(ui: &mut Ui, mesh: &Mesh, sparse_materials: &[SparseMaterial], commands: &Commands) {
+    let material_name = |id| {
+        sparse_materials
+            .iter()
+            .find(|material| material.id == id)
+            .and_then(|material| material.name.as_deref().map(String::from))
+            .unwrap_or(format!("{:?}", id))
+    };
+    egui::ComboBox::from_id_source(("material", mesh.id))
+        .selected_text(format!("Material: {}", material_name(mesh.material)))
+        .show_ui(ui, |ui| {
+            for material in sparse_materials {
+                if ui
+                    .selectable_label(material.id == mesh.material, material_name(material.id))
+                    .clicked()
+                {
+                    commands
+                        .send(commands::Command::AssignMaterial {
+                            mesh_id: mesh.id,
+                            material: material.id,
+                        })
+                        .unwrap();
+                }
+            }
+        });
+}
+
+fn joints_ui(ui: &mut Ui, model_id: u32, joints: &mut Vec<Joint>, sparse_models: &[SparseModel]) {
+    let model_name = |id: u32| {
+        sparse_models
+            .iter()
+            .find(|m| m.id == id)
+            .and_then(|m| m.name.as_deref().map(String::from))
+            .unwrap_or(format!("{}", id))
+    };
+    egui::CollapsingHeader::new(format!("Joints ({})", joints.len()))
+        .id_source(("joints", model_id))
+        .show(ui, |ui| {
+            let mut removed = None;
+            for (i, joint) in joints.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("kind")
+                            .selected_text(joint.kind.name())
+                            .show_ui(ui, |ui| {
+                                for kind in [
+                                    JointKind::Fixed,
+                                    JointKind::Revolute {
+                                        axis: Vec3::Z,
+                                        limits: None,
+                                    },
+                                    JointKind::Prismatic {
+                                        axis: Vec3::X,
+                                        limits: None,
+                                    },
+                                ] {
+                                    if ui
+                                        .selectable_label(joint.kind.name() == kind.name(), kind.name())
+                                        .clicked()
+                                    {
+                                        joint.kind = kind;
+                                    }
+                                }
+                            });
+                        ui.label("to");
+                        egui::ComboBox::from_id_source("other")
+                            .selected_text(model_name(joint.other))
+                            .show_ui(ui, |ui| {
+                                for other in sparse_models.iter().filter(|m| m.id != model_id) {
+                                    ui.selectable_value(&mut joint.other, other.id, model_name(other.id));
+                                }
+                            });
+                        if ui.button("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                    joint.local_anchor.editable(
+                        Some("Anchor:".into()),
+                        ui,
+                        Vec3::splat(-100.0),
+                        Vec3::splat(100.0),
+                    );
+                    joint.other_anchor.editable(
+                        Some("Other anchor:".into()),
+                        ui,
+                        Vec3::splat(-100.0),
+                        Vec3::splat(100.0),
+                    );
+                    match &mut joint.kind {
+                        JointKind::Fixed => {}
+                        JointKind::Revolute { axis, limits } | JointKind::Prismatic { axis, limits } => {
+                            axis.editable(Some("Axis:".into()), ui, Vec3::splat(-1.0), Vec3::splat(1.0));
+                            ui.horizontal(|ui| {
+                                let mut limited = limits.is_some();
+                                ui.checkbox(&mut limited, "Limits");
+                                if limited != limits.is_some() {
+                                    *limits = limited.then_some([-1.0, 1.0]);
+                                }
+                                if let Some([min, max]) = limits {
+                                    ui.add(egui::DragValue::new(min).speed(0.01));
+                                    ui.add(egui::DragValue::new(max).speed(0.01));
+                                }
+                            });
+                        }
+                    }
+                    ui.separator();
+                });
+            }
+            if let Some(i) = removed {
+                joints.remove(i);
+            }
+            if let Some(other) = sparse_models.iter().find(|m| m.id != model_id) {
+                if ui.button("Add joint").clicked() {
+                    joints.push(Joint::new(other.id, JointKind::Fixed));
+                }
+            }
+        });
+}
+
+fn character_ui(ui: &mut Ui, model_id: u32, character: &mut Option<CharacterController>) {
+    egui::CollapsingHeader::new("Character controller")
+        .id_source(("character", model_id))
+        .show(ui, |ui| {
+            let mut enabled = character.is_some();
+            ui.checkbox(&mut enabled, "Enabled");
+            if enabled != character.is_some() {
+                *character = enabled.then(CharacterController::default);
+            }
+            if let Some(character) = character {
+                ui.add(egui::Slider::new(&mut character.radius, 0.05..=5.0).text("Radius"));
+                ui.add(egui::Slider::new(&mut character.height, 0.1..=10.0).text("Height"));
+                ui.add(egui::Slider::new(&mut character.step_height, 0.0..=2.0).text("Step height"));
+                ui.add(egui::Slider::new(&mut character.max_slope, 0.0..=1.5).text("Max slope (rad)"));
+                ui.add(egui::Slider::new(&mut character.gravity_scale, 0.0..=4.0).text("Gravity scale"));
+                ui.label(if character.grounded { "Grounded" } else { "Airborne" });
+            }
+        });
+}
+
+fn rigid_body_ui(ui: &mut Ui, model_id: u32, body: &mut Option<RigidBody>, collider: &mut Option<Collider>) {
+    egui::CollapsingHeader::new("Rigid body")
+        .id_source(("rigid_body", model_id))
+        .show(ui, |ui| {
+            let mut kind = body.map(|body| body.kind);
+            egui::ComboBox::from_id_source(("body_kind", model_id))
+                .selected_text(kind.map_or("None".to_string(), |kind| format!("{:?}", kind)))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut kind, None, "None");
+                    for option in [BodyKind::Dynamic, BodyKind::Kinematic, BodyKind::Fixed] {
+                        ui.selectable_value(&mut kind, Some(option), format!("{:?}", option));
+                    }
+                });
+            if kind != body.map(|body| body.kind) {
+                *body = kind.map(|kind| RigidBody {
+                    kind,
+                    ..body.unwrap_or(RigidBody::new(kind))
+                });
+            }
+            if let Some(body) = body {
+                ui.add(egui::Slider::new(&mut body.gravity_scale, 0.0..=4.0).text("Gravity scale"));
+                ui.add(egui::Slider::new(&mut body.linear_damping, 0.0..=10.0).text("Linear damping"));
+                ui.add(egui::Slider::new(&mut body.angular_damping, 0.0..=10.0).text("Angular damping"));
+                ui.label(format!("Velocity: {:.2}", body.linear_velocity));
+            }
+            let shape_name = |shape: &ColliderShape| match shape {
+                ColliderShape::Ball { .. } => "Ball",
+                ColliderShape::Cuboid { .. } => "Cuboid",
+                ColliderShape::Capsule { .. } => "Capsule",
+                ColliderShape::ConvexHull => "Convex hull",
+                ColliderShape::TriMesh => "Triangle mesh",
+                ColliderShape::ConvexDecomposition => "Simplified mesh",
+            };
+            let current = collider.as_ref().map_or("None", |collider| shape_name(&collider.shape));
+            egui::ComboBox::from_id_source(("collider_shape", model_id))
+                .selected_text(format!("Collider: {}", current))
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(collider.is_none(), "None").clicked() {
+                        *collider = None;
+                    }
+                    for shape in [
+                        ColliderShape::Ball { radius: 0.5 },
+                        ColliderShape::Cuboid {
+                            half_extents: Vec3::splat(0.5),
+                        },
+                        ColliderShape::Capsule {
+                            half_height: 0.5,
+                            radius: 0.25,
+                        },
+                        ColliderShape::ConvexHull,
+                        ColliderShape::TriMesh,
+                        ColliderShape::ConvexDecomposition,
+                    ] {
+                        let selected = current == shape_name(&shape);
+                        if ui.selectable_label(selected, shape_name(&shape)).clicked() && !selected {
+                            *collider = Some(Collider::new(shape));
+                        }
+                    }
+                });
+            if let Some(collider) = collider {
+                match &mut collider.shape {
+                    ColliderShape::Ball { radius } => {
+                        ui.add(egui::Slider::new(radius, 0.01..=10.0).text("Radius"));
+                    }
+                    ColliderShape::Cuboid { half_extents } => {
+                        half_extents.editable(Some("Half extents:".into()), ui, Vec3::splat(0.01), Vec3::splat(10.0));
+                    }
+                    ColliderShape::Capsule { half_height, radius } => {
+                        ui.add(egui::Slider::new(half_height, 0.0..=10.0).text("Half height"));
+                        ui.add(egui::Slider::new(radius, 0.01..=10.0).text("Radius"));
+                    }
+                    ColliderShape::ConvexHull | ColliderShape::TriMesh | ColliderShape::ConvexDecomposition => {}
+                }
+                ui.add(egui::Slider::new(&mut collider.density, 0.01..=10.0).text("Density"));
+                ui.add(egui::Slider::new(&mut collider.friction, 0.0..=2.0).text("Friction"));
+                ui.add(egui::Slider::new(&mut collider.restitution, 0.0..=1.0).text("Restitution"));
+            }
+        });
+}
+
+// an eye that shows or hides the model or mesh with the id
+fn grid_ui(ui: &mut Ui, grid: &mut Grid) {
+    ui.add(
+        egui::Slider::new(&mut grid.spacing, 0.01..=10.0)
+            .logarithmic(true)
+            .text("Spacing"),
+    );
+    ui.add(egui::Slider::new(&mut grid.major_every, 1..=20).text("Major line every"));
+    for (color, label) in [
+        (&mut grid.minor_color, "Minor lines"),
+        (&mut grid.major_color, "Major lines"),
+        (&mut grid.x_axis_color, "X axis"),
+        (&mut grid.z_axis_color, "Z axis"),
+    ] {
+        ui.horizontal(|ui| {
+            ui.color_edit_button_rgba_unmultiplied(color);
+            ui.label(label);
+        });
+    }
+    ui.add(egui::Slider::new(&mut grid.fade_start, 0.0..=500.0).text("Fade start"));
+    ui.add(egui::Slider::new(&mut grid.fade_end, 0.0..=500.0).text("Fade end"));
+    if ui.button("Reset").clicked() {
+        *grid = Grid::default();
+    }
+}
+
+fn outline_ui(ui: &mut Ui, outline: &mut Outline) {
+    ui.horizontal(|ui| {
+        ui.label("Width");
+        ui.add(egui::DragValue::new(&mut outline.width));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Color");
+        ui.add(egui::DragValue::new(&mut outline.color[0]));
+        ui.add(egui::DragValue::new(&mut outline.color[1]));
+        ui.add(egui::DragValue::new(&mut outline.color[2]));
+    });
+}
+
+fn visibility_toggle(ui: &mut Ui, visible: bool, id: u32, commands: &Commands) {
+    let toggle = ui
+        .selectable_label(visible, "👁")
+        .on_hover_text(if visible { "Hide" } else { "Show" });
+    if toggle.clicked() {
+        commands
+            .send(commands::Command::SetVisibility { id, visible: !visible })
+            .unwrap();
+    }
+}
+
 fn model_actions(
     model: &mut Model,
     scene_id: u32,
@@ -287,6 +1399,7 @@ fn model_actions(
                             model_id: model.id,
                             new_parent_id: None,
                             new_scene_id: other_scene.id,
+                            keep_world_transform: true,
                         })
                         .unwrap();
                 }
@@ -312,6 +1425,7 @@ fn model_actions(
                             model_id: model.id,
                             new_parent_id: Some(other_model.id),
                             new_scene_id: scene_id,
+                            keep_world_transform: true,
                         })
                         .unwrap();
                 }
@@ -323,6 +1437,57 @@ fn model_actions(
         if ui.button("Duplicate").on_hover_text("Duplicate this model").clicked() {
             commands.send(commands::Command::DuplicateModel(model.id)).unwrap();
         }
+        if ui
+            .button("Snap to grid")
+            .on_hover_text("Round this model's position to the snap step")
+            .clicked()
+        {
+            commands.send(commands::Command::SnapModelToGrid(model.id)).unwrap();
+        }
+        if ui
+            .button("Bake imposter")
+            .on_hover_text("Draw this model as a billboard when it is far away")
+            .clicked()
+        {
+            commands.send(commands::Command::BakeImposter(model.id)).unwrap();
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .button("Attach script")
+                .on_hover_text("Run a rhai script on this model")
+                .clicked()
+            {
+                let picked_file = FileDialog::new().add_filter("Rhai scripts", &["rhai"]).pick_file();
+                if let Some(path) = picked_file {
+                    commands
+                        .send(commands::Command::AttachScript {
+                            model_id: model.id,
+                            path,
+                        })
+                        .unwrap();
+                }
+            }
+            if ui.button("Detach script").clicked() {
+                commands.send(commands::Command::DetachScript(model.id)).unwrap();
+            }
+        });
+        ui.menu_button("Make solid", |ui| {
+            for (label, shape) in [
+                ("Triangle meshes", ColliderShape::TriMesh),
+                ("Convex hulls", ColliderShape::ConvexHull),
+                ("Simplified meshes", ColliderShape::ConvexDecomposition),
+            ] {
+                if ui.button(label).clicked() {
+                    ui.close_menu();
+                    commands
+                        .send(commands::Command::GenerateColliders {
+                            model_id: model.id,
+                            shape,
+                        })
+                        .unwrap();
+                }
+            }
+        });
         if ui.button("Print debug info").clicked() {
             println!("Model name={:?}, id={}", model.name.clone(), model.id);
             println!("| Local transform:");
@@ -342,8 +1507,32 @@ fn add_model_menu(ui: &mut Ui, commands: &Commands, parent_id: Option<u32>) {
         },
         |ui| {
             if ui.button("Cube model").on_hover_text("Add a cube model").clicked() {
-                println!("Adding cube TODO");
                 ui.close_menu();
+                commands
+                    .send(commands::Command::CreateModel(
+                        commands::CreateModel::Primitive {
+                            geometry: Geometry::Cube {
+                                width: 1.0,
+                                height: 1.0,
+                                depth: 1.0,
+                            },
+                            position: glam::Vec3::ZERO,
+                        },
+                        parent_id,
+                    ))
+                    .unwrap();
+            }
+            if ui.button("Sphere model").on_hover_text("Add a sphere model").clicked() {
+                ui.close_menu();
+                commands
+                    .send(commands::Command::CreateModel(
+                        commands::CreateModel::Primitive {
+                            geometry: Geometry::Sphere { radius: 0.5 },
+                            position: glam::Vec3::ZERO,
+                        },
+                        parent_id,
+                    ))
+                    .unwrap();
             }
             if ui
                 .button("Light model")
@@ -367,6 +1556,92 @@ fn add_model_menu(ui: &mut Ui, commands: &Commands, parent_id: Option<u32>) {
     );
 }
 
+fn mesh_params_ui(ui: &mut Ui, mesh: &mut Mesh) {
+    let params = mesh.params().iter().map(|(name, value)| (name.to_string(), value)).collect::<Vec<_>>();
+    for (name, mut value) in params {
+        ui.horizontal(|ui| {
+            ui.label(&name);
+            let mut changed = false;
+            for component in value.as_mut() {
+                changed |= ui.add(egui::DragValue::new(component).speed(0.01)).changed();
+            }
+            if changed {
+                mesh.set_param(&name, value);
+            }
+            if ui.small_button("x").on_hover_text("Remove this parameter").clicked() {
+                mesh.remove_param(&name);
+            }
+        });
+    }
+    let id = ui.id().with(("new_param", mesh.id));
+    let mut new_name = ui.data_mut(|data| data.get_temp::<String>(id).unwrap_or_default());
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut new_name);
+        if ui.button("Add parameter").clicked() && !new_name.is_empty() && mesh.set_param(&new_name, Vec4::ZERO) {
+            new_name.clear();
+        }
+    });
+    ui.data_mut(|data| data.insert_temp(id, new_name));
+}
+
+// bindings of the scene, and a row to bind a property of one of its models to a new expression
+fn bindings_ui(ui: &mut Ui, scene_id: u32, bindings: &[Binding], sparse_models: &[SparseModel], commands: &Commands) {
+    egui::CollapsingHeader::new("Bindings").show(ui, |ui| {
+        for binding in bindings {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "#{}.{} = {}",
+                    binding.model,
+                    binding.property,
+                    binding.expression.source()
+                ));
+                if ui.small_button("x").on_hover_text("Remove this binding").clicked() {
+                    commands
+                        .send(commands::Command::Unbind {
+                            model_id: binding.model,
+                            property: binding.property,
+                        })
+                        .unwrap();
+                }
+            });
+        }
+        let id = ui.id().with(("new_binding", scene_id));
+        let (mut model_id, mut property, mut expression) = ui.data_mut(|data| {
+            data.get_temp::<(u32, Property, String)>(id)
+                .unwrap_or((0, Property::LightIntensity, String::new()))
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source(id.with("model"))
+                .selected_text(format!("#{}", model_id))
+                .show_ui(ui, |ui| {
+                    for model in sparse_models {
+                        let name = model.name.clone().map(|s| s.to_string()).unwrap_or_default();
+                        ui.selectable_value(&mut model_id, model.id, format!("#{} {}", model.id, name));
+                    }
+                });
+            egui::ComboBox::from_id_source(id.with("property"))
+                .selected_text(property.to_string())
+                .show_ui(ui, |ui| {
+                    for option in Property::ALL {
+                        ui.selectable_value(&mut property, option, option.to_string());
+                    }
+                });
+            ui.text_edit_singleline(&mut expression)
+                .on_hover_text("e.g. 100 + 50 * sin(time), or #12.translation.y * 2");
+            if ui.button("Bind").clicked() && !expression.is_empty() {
+                commands
+                    .send(commands::Command::Bind {
+                        model_id,
+                        property,
+                        expression: expression.clone(),
+                    })
+                    .unwrap();
+            }
+        });
+        ui.data_mut(|data| data.insert_temp(id, (model_id, property, expression)));
+    });
+}
+
 fn add_mesh_menu(ui: &mut Ui, commands: &Commands, model_id: u32) {
     ui.menu_button("Add mesh", |ui| {
         if ui.button("Cube mesh").on_hover_text("Add a cube mesh").clicked() {
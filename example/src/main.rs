@@ -1,12 +1,16 @@
 use std::sync::mpsc;
 
-use log::debug;
+use log::{debug, error, info, warn};
 
 use engine::lib::scene::World;
 use engine::renderer::{commands, Hook, Meta};
+use engine::renderer::adapter::enumerate_adapters;
 use engine::renderer::camera::{Camera, KeyState};
 use engine::renderer::commands::{Command, CommandResult, Commands};
-use engine::renderer::events::{Event, MouseButton};
+use engine::renderer::events::{Event, KeyCode, MouseButton};
+use engine::renderer::selection::SelectionMode;
+use engine::renderer::window::RunConfig;
+use engine::renderer::world_builder::WorldBuilder;
 
 use crate::util::RainbowAnimation;
 
@@ -17,10 +21,12 @@ struct Game {
     event_receiver: Option<mpsc::Receiver<Event>>,
     command_sender: Option<Commands>,
     animation: RainbowAnimation,
+    // how the result of the last marquee is combined with the selection
+    marquee_mode: SelectionMode,
 }
 
 impl Hook for Game {
-    fn setup(&mut self, commands: Commands, event_receiver: mpsc::Receiver<Event>) {
+    fn setup(&mut self, commands: Commands, event_receiver: mpsc::Receiver<Event>, _world: &mut WorldBuilder) {
         self.event_receiver = Some(event_receiver);
         self.command_sender = Some(commands);
     }
@@ -28,40 +34,82 @@ impl Hook for Game {
     fn update(&mut self, keys: &KeyState, delta_time: f32, world: &mut World) {
         self.animation.update(delta_time as u32);
         if let Some(scene) = world.scenes.get_mut(&world.active_scene) {
-            scene.outline_color = self.animation.get_current_color();
+            scene.selection_outline.color = self.animation.get_current_color();
         }
         while let Ok(event) = self.event_receiver.as_ref().unwrap().try_recv() {
             match event {
-                Event::Click { x, y, mouse_button } => {
-                    if mouse_button == MouseButton::Left {
-                        self.command_sender
-                            .clone()
-                            .unwrap()
-                            .send(Command::QueryClick((x, y)))
-                            .unwrap();
-                    }
+                Event::Click {
+                    x,
+                    y,
+                    mouse_button,
+                    modifiers,
+                } => {
+                    let command = match mouse_button {
+                        MouseButton::Left => Command::SelectAt {
+                            x,
+                            y,
+                            mode: SelectionMode::from_modifiers(modifiers),
+                        },
+                        // shows where objects placed under the cursor would go
+                        MouseButton::Right => Command::QueryClick((x, y)),
+                        MouseButton::Middle => continue,
+                    };
+                    self.command_sender.clone().unwrap().send(command).unwrap();
+                }
+                Event::Marquee { min, max, modifiers } => {
+                    self.marquee_mode = SelectionMode::from_modifiers(modifiers);
+                    self.command_sender
+                        .clone()
+                        .unwrap()
+                        .send(Command::BoxSelect { min, max })
+                        .unwrap();
                 }
-                Event::CommandResult(command_result) => {
-                    debug!("Command result: {:?}", command_result);
-                    match command_result {
-                        CommandResult::ClickQuery(res) => {
-                            let Some(scene) = world.scenes.get_mut(&world.active_scene) else {
-                                return;
-                            };
-                            for model in scene.models.as_mut_slice() {
-                                for mesh in model.meshes.as_mut_slice() {
-                                    mesh.set_outline(false);
-                                }
-                            }
-                            if res == 0 {
-                                return;
-                            }
-                            self.animation.reset();
-                            scene.get_mesh_mut(res).unwrap().set_outline(true);
-                            debug!("Clicked on mesh: {}", res);
-                        }
+                Event::SelectionChanged(meshes) => {
+                    if !meshes.is_empty() {
+                        self.animation.reset();
                     }
+                    debug!("Selected meshes: {:?}", meshes);
+                }
+                Event::Hover { mesh_id, .. } => debug!("Hovering over mesh {:?}", mesh_id),
+                Event::KeyPressed { key, modifiers } => {
+                    let command = match key {
+                        KeyCode::Delete => Command::DeleteSelection,
+                        KeyCode::D if modifiers.ctrl => Command::DuplicateSelection,
+                        _ => continue,
+                    };
+                    self.command_sender.clone().unwrap().send(command).unwrap();
+                }
+                Event::MouseDown { .. }
+                | Event::MouseUp { .. }
+                | Event::DoubleClick { .. }
+                | Event::Drag { .. }
+                | Event::Scroll { .. } => {}
+                Event::PerformanceWarning { pass, milliseconds, budget } => {
+                    warn!("{} took {:.2} ms, more than its budget of {:.2} ms", pass, milliseconds, budget);
+                }
+                Event::CellLoaded(cell) => debug!("Cell {} streamed in", cell),
+                Event::CellUnloaded(cell) => debug!("Cell {} streamed out", cell),
+                Event::DeviceRecovered => warn!("The graphics device was lost and recreated"),
+                Event::AssetReloaded(path) => debug!("{:?} changed and was reloaded", path),
+                // the renderer logs the error already
+                Event::CommandFailed { .. } => {}
+                Event::CommandResult(CommandResult::ClickQuery(Some(hit))) => {
+                    info!(
+                        "Mesh {} hit at {} ({:.2} units away)",
+                        hit.mesh_id, hit.position, hit.depth
+                    );
+                }
+                Event::CommandResult(CommandResult::BoxSelect(meshes)) => {
+                    self.command_sender
+                        .clone()
+                        .unwrap()
+                        .send(Command::Select {
+                            meshes,
+                            mode: self.marquee_mode,
+                        })
+                        .unwrap();
                 }
+                Event::CommandResult(command_result) => debug!("Command result: {:?}", command_result),
             }
         }
     }
@@ -69,6 +117,10 @@ impl Hook for Game {
     fn update_ui(&mut self, ctx: &egui::Context, x: &mut World, x0: &mut Camera, sender: mpsc::Sender<commands::Command>, meta: &mut Meta) {
         gui::update_ui(ctx, x, x0, sender, meta);
     }
+
+    fn player_hud(&mut self, ui: &mut egui::Ui, player: usize) {
+        ui.label(format!("Player {}", player + 1));
+    }
 }
 
 fn main() {
@@ -78,6 +130,22 @@ fn main() {
         event_receiver: None,
         command_sender: None,
         animation: RainbowAnimation::new(),
+        marquee_mode: SelectionMode::Replace,
     };
-    pollster::block_on(engine::renderer::run(game));
+    // pick one of these with the ENGINE_ADAPTER environment variable, e.g. ENGINE_ADAPTER=1
+    for adapter in enumerate_adapters() {
+        info!(
+            "Adapter {}: {} ({:?}, {:?})",
+            adapter.index, adapter.name, adapter.backend, adapter.device_type
+        );
+    }
+    let config = RunConfig {
+        title: "engine-wgpu example".into(),
+        size: Some((1600, 900)),
+        min_size: Some((640, 360)),
+        ..Default::default()
+    };
+    if let Err(err) = pollster::block_on(engine::renderer::run(game, config)) {
+        error!("{:#}", err);
+    }
 }
@@ -1,6 +1,7 @@
 use std::f32::consts::PI;
 
 use egui::Ui;
+use engine::lib::managers::MatId;
 use glam::{Vec3, Vec4};
 
 pub(crate) struct SparseScene {
@@ -13,10 +14,9 @@ pub(crate) struct SparseModel {
     pub(crate) name: Option<Box<str>>,
 }
 
-#[derive(PartialEq)]
-pub(crate) enum CameraModes {
-    Arcball,
-    FPS,
+pub(crate) struct SparseMaterial {
+    pub(crate) id: MatId,
+    pub(crate) name: Option<Box<str>>,
 }
 
 pub(crate) trait Editable<T> {
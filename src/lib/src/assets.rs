@@ -0,0 +1,247 @@
+use std::fmt::{Debug, Formatter};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+
+use crate::managers::{MatId, MaterialManager, TexId, TextureManager};
+use crate::scene::mesh::Mesh;
+use crate::texture::Texture;
+use crate::Material;
+
+/**
+A kind of asset that `Handle`s can refer to.
+ */
+pub trait Asset {
+    type Id: Copy + Eq + Hash + Debug;
+
+    fn key(id: Self::Id) -> AssetKey;
+}
+
+impl Asset for Texture {
+    type Id = TexId;
+
+    fn key(id: TexId) -> AssetKey {
+        AssetKey::Texture(id)
+    }
+}
+
+impl Asset for Material {
+    type Id = MatId;
+
+    fn key(id: MatId) -> AssetKey {
+        AssetKey::Material(id)
+    }
+}
+
+impl Asset for Mesh {
+    type Id = u32;
+
+    fn key(id: u32) -> AssetKey {
+        AssetKey::Mesh(id)
+    }
+}
+
+/**
+Identifies an asset of any kind, e.g. to keep the reference counts of all kinds in one map.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetKey {
+    Texture(TexId),
+    Material(MatId),
+    /// the unique id of a mesh, which belongs to its model rather than to the `AssetServer`
+    Mesh(u32),
+}
+
+/**
+A typed, reference counted reference to an asset, see `AssetServer::handle`. Cloning a handle adds a reference and
+dropping it removes one; assets without references can be unloaded, see `AssetServer::unreferenced`.
+ */
+pub struct Handle<T: Asset> {
+    id: T::Id,
+    refs: Arc<()>,
+    // `fn() -> T` keeps the handle `Send` and `Sync` regardless of `T`
+    _asset: PhantomData<fn() -> T>,
+}
+
+impl<T: Asset> Handle<T> {
+    pub fn id(&self) -> T::Id {
+        self.id
+    }
+}
+
+impl<T: Asset> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            refs: self.refs.clone(),
+            _asset: PhantomData,
+        }
+    }
+}
+
+impl<T: Asset> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T: Asset> Eq for Handle<T> {}
+
+impl<T: Asset> Debug for Handle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({:?})", self.id)
+    }
+}
+
+/**
+How loading the assets of a source file went, see `AssetServer::load_state`.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    Failed(String),
+}
+
+/**
+The assets that `World::purge_unused` removed.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Purged {
+    pub materials: Vec<MatId>,
+    pub textures: Vec<TexId>,
+}
+
+impl Purged {
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty() && self.textures.is_empty()
+    }
+}
+
+/**
+Owns the world's textures and materials and hands out `Handle`s to them and to meshes, counting the references to each
+asset so that unused ones can be found and unloaded. Also keeps track of the files assets were loaded from and whether
+loading them succeeded, e.g. for streaming, which loads in the background.
+
+Meshes stay with the models they belong to, while their vertices and indices live in the scene's `MeshPool`, shared
+by all meshes of the scene; handles to meshes only count references.
+ */
+pub struct AssetServer {
+    pub textures: TextureManager,
+    pub materials: MaterialManager,
+    // the server's own reference to each asset that a handle was created for
+    refs: HashMap<AssetKey, Arc<()>>,
+    sources: HashMap<PathBuf, LoadState>,
+}
+
+impl AssetServer {
+    pub fn new(textures: TextureManager, materials: MaterialManager) -> Self {
+        Self {
+            textures,
+            materials,
+            refs: HashMap::new(),
+            sources: HashMap::new(),
+        }
+    }
+
+    /**
+    A new reference to an asset. The asset isn't checked for existence, handles to assets that were removed stay valid
+    but don't resolve anymore.
+     */
+    pub fn handle<T: Asset>(&mut self, id: T::Id) -> Handle<T> {
+        let refs = self.refs.entry(T::key(id)).or_default().clone();
+        Handle {
+            id,
+            refs,
+            _asset: PhantomData,
+        }
+    }
+
+    /**
+    The number of handles to an asset that exist, 0 if none were ever created.
+     */
+    pub fn ref_count<T: Asset>(&self, id: T::Id) -> usize {
+        self.refs.get(&T::key(id)).map_or(0, |refs| Arc::strong_count(refs) - 1)
+    }
+
+    /**
+    The assets that handles were created for, but whose handles have all been dropped since. These are the candidates
+    for unloading, after which `forget` should be called for them.
+     */
+    pub fn unreferenced(&self) -> impl Iterator<Item = AssetKey> + '_ {
+        self.refs
+            .iter()
+            .filter(|(_, refs)| Arc::strong_count(refs) == 1)
+            .map(|(key, _)| *key)
+    }
+
+    /**
+    Stops counting references to an asset, e.g. after it was unloaded. Existing handles keep working, but new handles
+    are counted separately from them.
+     */
+    pub fn forget(&mut self, key: AssetKey) {
+        self.refs.remove(&key);
+    }
+
+    pub fn texture(&self, handle: &Handle<Texture>) -> Option<&Texture> {
+        self.textures
+            .contains(handle.id)
+            .then(|| self.textures.get_texture(&handle.id))
+    }
+
+    pub fn material(&self, handle: &Handle<Material>) -> Option<&Material> {
+        self.materials
+            .contains(handle.id)
+            .then(|| self.materials.get_material(handle.id))
+    }
+
+    /**
+    Like `material`; mark the material as dirty after changing it, so that it is uploaded.
+     */
+    pub fn material_mut(&mut self, handle: &Handle<Material>) -> Option<&mut Material> {
+        if !self.materials.contains(handle.id) {
+            return None;
+        }
+        Some(self.materials.get_material_mut(handle.id))
+    }
+
+    /**
+    Adds a texture and returns a handle to it.
+     */
+    pub fn add_texture(&mut self, texture: Texture) -> Handle<Texture> {
+        let id = self.textures.add_texture(texture);
+        self.handle(id)
+    }
+
+    /**
+    How loading assets from the file went, `None` if nothing was loaded from it.
+     */
+    pub fn load_state(&self, path: &Path) -> Option<&LoadState> {
+        self.sources.get(path)
+    }
+
+    /**
+    The files that assets were loaded from, with how loading them went.
+     */
+    pub fn sources(&self) -> impl Iterator<Item = (&Path, &LoadState)> {
+        self.sources.iter().map(|(path, state)| (path.as_path(), state))
+    }
+
+    pub fn start_load(&mut self, path: &Path) {
+        self.sources.insert(path.to_path_buf(), LoadState::Loading);
+    }
+
+    /**
+    Records that loading from the file finished, and failed if there is an `error`.
+     */
+    pub fn finish_load(&mut self, path: &Path, error: Option<String>) {
+        let state = match error {
+            Some(error) => LoadState::Failed(error),
+            None => LoadState::Loaded,
+        };
+        self.sources.insert(path.to_path_buf(), state);
+    }
+}
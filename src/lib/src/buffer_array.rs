@@ -1,3 +1,4 @@
+use crate::staging::BufferWriter;
 use log::debug;
 use std::fmt::{Debug, Formatter};
 use std::hash::Hash;
@@ -5,6 +6,15 @@ use wgpu::{BindGroupLayout, Buffer, BufferAddress, Device, Queue};
 
 const PREALLOC_COUNT: usize = 16; // how many elements we want to have space for initially
 
+/**
+An element that moved from one index of a buffer to another, e.g. to close the hole left by a removed element.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Remap {
+    pub from: u64,
+    pub to: u64,
+}
+
 /**
 A dynamic buffer array on the GPU that auto-resizes and can be updated.
 */
@@ -55,8 +65,13 @@ impl<T: bytemuck::Pod> DynamicBufferArray<T> {
 
     pub fn push(&mut self, device: &Device, queue: &Queue, data: &[T], bind_group_layout: &BindGroupLayout) {
         debug!("Pushing {} elements to buffer (Count: {})", data.len(), self.count);
-        if self.count + data.len() as u64 > self.capacity {
-            self.resize(device, queue, bind_group_layout);
+        let required = self.count + data.len() as u64;
+        if required > self.capacity {
+            let mut capacity = self.capacity;
+            while capacity < required {
+                capacity *= 2;
+            }
+            self.reallocate(device, queue, bind_group_layout, capacity);
         }
         queue.write_buffer(
             &self.buffer,
@@ -66,7 +81,7 @@ impl<T: bytemuck::Pod> DynamicBufferArray<T> {
         self.count += data.len() as u64;
     }
 
-    pub fn update(&mut self, queue: &Queue, index: u64, data: T) {
+    pub fn update(&mut self, mut writer: impl BufferWriter, index: u64, data: T) {
         assert!(index < self.count);
         println!(
             "Updating buffer {:?} at index {} (offset {})",
@@ -74,19 +89,71 @@ impl<T: bytemuck::Pod> DynamicBufferArray<T> {
             index,
             index * std::mem::size_of::<T>() as u64
         );
-        queue.write_buffer(
+        writer.write_buffer(
             &self.buffer,
             index * std::mem::size_of::<T>() as u64,
             bytemuck::cast_slice(&[data]),
         );
     }
 
-    fn resize(&mut self, device: &Device, queue: &Queue, bind_group_layout: &BindGroupLayout) {
-        debug!("Resizing buffer {:?}", self.label);
+    /**
+    Overwrites the elements from `start` on with a single write.
+     */
+    pub fn update_range(&mut self, mut writer: impl BufferWriter, start: u64, data: &[T]) {
+        assert!(start + data.len() as u64 <= self.count);
+        writer.write_buffer(
+            &self.buffer,
+            start * std::mem::size_of::<T>() as u64,
+            bytemuck::cast_slice(data),
+        );
+    }
+
+    /**
+    Removes the element at `index` by moving the last element into its place, which is copied on the GPU and submitted
+    immediately. Returns where the last element moved from and to, so that whoever keeps its index can follow it, or
+    `None` if the removed element was the last one.
+     */
+    pub fn swap_remove(&mut self, device: &Device, queue: &Queue, index: u64) -> Option<Remap> {
+        assert!(index < self.count);
+        self.count -= 1;
+        if index == self.count {
+            return None;
+        }
+        let remap = Remap {
+            from: self.count,
+            to: index,
+        };
+        self.copy_within(device, queue, &[remap]);
+        Some(remap)
+    }
+
+    /**
+    Halves the capacity while at most a quarter of it is used, but not below the initial capacity, so that removing
+    many elements frees their memory without pushes and removals around a boundary reallocating every time. Returns
+    whether the buffer was reallocated, its bind group changes then.
+     */
+    pub fn shrink(&mut self, device: &Device, queue: &Queue, bind_group_layout: &BindGroupLayout) -> bool {
+        let mut capacity = self.capacity;
+        while capacity / 2 >= PREALLOC_COUNT as u64 && self.count * 4 <= capacity {
+            capacity /= 2;
+        }
+        if capacity == self.capacity {
+            return false;
+        }
+        self.reallocate(device, queue, bind_group_layout, capacity);
+        true
+    }
+
+    // moves the buffer's elements into a new buffer with room for `capacity` elements
+    fn reallocate(&mut self, device: &Device, queue: &Queue, bind_group_layout: &BindGroupLayout, capacity: u64) {
+        debug!(
+            "Reallocating buffer {:?} from {} to {} elements",
+            self.label, self.capacity, capacity
+        );
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Buffer resize encoder"),
         });
-        self.capacity *= 2;
+        self.capacity = capacity;
         let new_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: self.label.as_deref(),
             size: (self.capacity * std::mem::size_of::<T>() as u64) as BufferAddress,
@@ -102,6 +169,7 @@ impl<T: bytemuck::Pod> DynamicBufferArray<T> {
             (self.count * std::mem::size_of::<T>() as u64) as BufferAddress,
         );
         queue.submit(std::iter::once(encoder.finish()));
+        self.buffer = new_buffer;
 
         self.dirty = true;
         self.bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -114,24 +182,90 @@ impl<T: bytemuck::Pod> DynamicBufferArray<T> {
         });
     }
 
+    // copies elements to other indices of the buffer and submits the copies
+    fn copy_within(&self, device: &Device, queue: &Queue, moves: &[Remap]) {
+        let element_size = std::mem::size_of::<T>() as u64;
+        // a buffer can't be copied into itself, so the elements take a detour through a scratch buffer
+        let scratch = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Buffer move scratch buffer"),
+            size: moves.len() as u64 * element_size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Buffer move encoder"),
+        });
+        for (i, remap) in moves.iter().enumerate() {
+            encoder.copy_buffer_to_buffer(
+                &self.buffer,
+                remap.from * element_size,
+                &scratch,
+                i as u64 * element_size,
+                element_size,
+            );
+        }
+        for (i, remap) in moves.iter().enumerate() {
+            encoder.copy_buffer_to_buffer(
+                &scratch,
+                i as u64 * element_size,
+                &self.buffer,
+                remap.to * element_size,
+                element_size,
+            );
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /**
+    Recreates the buffer and its bind group on another device, e.g. after the old device was lost. The contents are
+    zeroed, the caller has to upload them again.
+     */
+    pub fn recreate(&mut self, device: &Device, bind_group_layout: &BindGroupLayout) {
+        self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: self.label.as_deref(),
+            size: (self.capacity * std::mem::size_of::<T>() as u64) as BufferAddress,
+            usage: self.usages | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        self.bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: self.label.as_deref(),
+            layout: bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: self.buffer.as_entire_binding(),
+            }],
+        });
+        self.dirty = false;
+    }
+
     #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> u64 {
         self.count
     }
+
+    /**
+    Size of the buffer on the GPU in bytes, including the space reserved for elements that weren't pushed yet.
+     */
+    pub fn byte_size(&self) -> u64 {
+        self.buffer.size()
+    }
 }
 
 /**
 A dynamic buffer array that also stores a map of keys to memory offsets within the buffer.
+Removing keys leaves holes in the buffer, which `defragment` closes over time.
 */
 pub struct DynamicBufferMap<T, K> {
     array: DynamicBufferArray<T>,
     map: std::collections::HashMap<K, u64>,
+    // the key stored at each index of the buffer, None for holes left by removed keys
+    slots: Vec<Option<K>>,
 }
 
 impl<T, K> DynamicBufferMap<T, K>
 where
     T: bytemuck::Pod,
-    K: Eq + Hash + Debug,
+    K: Eq + Hash + Debug + Clone,
 {
     pub fn new(
         device: &Device,
@@ -145,11 +279,15 @@ where
         Self {
             array: DynamicBufferArray::new(device, label, usages, bind_group_layout),
             map: std::collections::HashMap::new(),
+            slots: Vec::new(),
         }
     }
 
     pub fn push(&mut self, device: &Device, queue: &Queue, key: K, data: &[T], bind_group_layout: &BindGroupLayout) {
-        self.map.insert(key, self.array.len());
+        assert_eq!(data.len(), 1, "Each key maps to exactly one element");
+        self.remove(&key);
+        self.map.insert(key.clone(), self.array.len());
+        self.slots.push(Some(key));
         self.array.push(device, queue, data, bind_group_layout);
         println!(
             "Pushed to buffer, now length is {}; map: {:?}",
@@ -158,10 +296,140 @@ where
         );
     }
 
-    pub fn update(&mut self, queue: &Queue, key: &K, data: T) {
+    pub fn update(&mut self, writer: impl BufferWriter, key: &K, data: T) {
         let index = *self.map.get(key).unwrap();
         println!("Mesh {key:?} located at {index}, array len {}", self.array.len());
-        self.array.update(queue, index, data);
+        self.array.update(writer, index, data);
+    }
+
+    /**
+    Updates the elements of many keys at once. The updates are sorted by index and neighbouring ones are merged into a
+    single write, also across holes, which are zeroed. If a key is given more than once, its last element wins.
+    Returns the number of writes.
+     */
+    pub fn update_batch(&mut self, mut writer: impl BufferWriter, updates: impl IntoIterator<Item = (K, T)>) -> usize {
+        let mut updates: Vec<(u64, T)> = updates
+            .into_iter()
+            .map(|(key, data)| (*self.map.get(&key).unwrap(), data))
+            .collect();
+        // stable, so that later updates of the same key stay behind earlier ones
+        updates.sort_by_key(|(index, _)| *index);
+        let mut writes = 0;
+        let mut start = 0;
+        let mut run: Vec<T> = Vec::new();
+        for (index, data) in updates {
+            let end = start + run.len() as u64;
+            if !run.is_empty() && index < end {
+                run[(index - start) as usize] = data;
+                continue;
+            }
+            let joins = !run.is_empty() && (end..index).all(|hole| self.slots[hole as usize].is_none());
+            if !joins {
+                if !run.is_empty() {
+                    self.array.update_range(&mut writer, start, &run);
+                    writes += 1;
+                }
+                start = index;
+                run.clear();
+            }
+            run.extend((start + run.len() as u64..index).map(|_| T::zeroed()));
+            run.push(data);
+        }
+        if !run.is_empty() {
+            self.array.update_range(&mut writer, start, &run);
+            writes += 1;
+        }
+        writes
+    }
+
+    /**
+    Frees the key's slot. The buffer isn't shrunk until the slot is closed by `defragment` and the buffer is
+    `shrink`ed.
+     */
+    pub fn remove(&mut self, key: &K) -> Option<u64> {
+        let index = self.map.remove(key)?;
+        self.slots[index as usize] = None;
+        Some(index)
+    }
+
+    /**
+    Number of holes in the buffer that `defragment` still has to close.
+     */
+    pub fn holes(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_none()).count()
+    }
+
+    /**
+    Moves up to `max_moves` entries from the end of the buffer into holes and trims the buffer, so that it stays dense
+    and indices stay small. Meant to be called every frame with a small budget. Returns the moved keys with where they
+    moved from and to. The moves are copied on the GPU and submitted immediately, indices returned by `get` are valid
+    afterwards.
+     */
+    pub fn defragment(&mut self, device: &Device, queue: &Queue, max_moves: usize) -> Vec<(K, Remap)> {
+        self.trim();
+        let mut moves = Vec::new();
+        while moves.len() < max_moves {
+            let Some(hole) = self.slots.iter().position(Option::is_none) else {
+                break;
+            };
+            let last = self.slots.len() - 1;
+            let key = self.slots[last].take().expect("Trailing slots are trimmed");
+            self.map.insert(key.clone(), hole as u64);
+            self.slots[hole] = Some(key.clone());
+            let remap = Remap {
+                from: last as u64,
+                to: hole as u64,
+            };
+            moves.push((key, remap));
+            self.trim();
+        }
+        if moves.is_empty() {
+            return moves;
+        }
+        let remaps: Vec<Remap> = moves.iter().map(|(_, remap)| *remap).collect();
+        self.array.copy_within(device, queue, &remaps);
+        debug!(
+            "Defragmented buffer {:?}: moved {} entries, length is now {}",
+            self.array.label,
+            moves.len(),
+            self.array.len()
+        );
+        moves
+    }
+
+    /**
+    See `DynamicBufferArray::shrink`, only the holes at the end of the buffer are freed.
+     */
+    pub fn shrink(&mut self, device: &Device, queue: &Queue, bind_group_layout: &BindGroupLayout) -> bool {
+        self.trim();
+        self.array.shrink(device, queue, bind_group_layout)
+    }
+
+    // drops holes at the end of the buffer
+    fn trim(&mut self) {
+        while let Some(None) = self.slots.last() {
+            self.slots.pop();
+        }
+        self.array.count = self.slots.len() as u64;
+    }
+
+    /**
+    Like `DynamicBufferArray::recreate`, but uploads the element of each key again, as returned by `data`. Keys that
+    `data` returns nothing for keep zeroed elements.
+     */
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        bind_group_layout: &BindGroupLayout,
+        data: impl Fn(&K) -> Option<T>,
+    ) {
+        self.array.recreate(device, bind_group_layout);
+        for (key, index) in self.map.iter() {
+            if let Some(element) = data(key) {
+                self.array.update(queue, *index, element);
+            }
+        }
     }
 
     pub fn iter(&self) -> std::collections::hash_map::Iter<K, u64> {
@@ -175,6 +443,21 @@ where
     pub fn get(&self, key: &K) -> Option<&u64> {
         self.map.get(key)
     }
+
+    /**
+    Number of keys, not counting holes.
+     */
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /**
+    See `DynamicBufferArray::byte_size`, holes count as well.
+     */
+    pub fn byte_size(&self) -> u64 {
+        self.array.byte_size()
+    }
 }
 
 impl<T, K> Debug for DynamicBufferMap<T, K>
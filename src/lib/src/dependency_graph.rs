@@ -0,0 +1,150 @@
+use std::fmt::{Debug, Formatter};
+
+use hashbrown::HashMap;
+
+use crate::managers::{MatId, TexId};
+use crate::scene::model::Model;
+use crate::scene::World;
+
+/**
+Identifies a single asset within the world.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetRef {
+    Scene(usize),
+    Model(u32),
+    Mesh(u32),
+    Material(MatId),
+    Texture(TexId),
+}
+
+pub struct AssetNode {
+    pub asset: AssetRef,
+    pub name: String,
+}
+
+/**
+A snapshot of which assets use which other assets, e.g. scene -> model -> mesh -> material -> texture.
+Edges point from the dependent asset to its dependency.
+ */
+pub struct DependencyGraph {
+    pub nodes: Vec<AssetNode>,
+    pub edges: Vec<(usize, usize)>,
+    indices: HashMap<AssetRef, usize>,
+}
+
+impl DependencyGraph {
+    pub fn from_world(world: &World) -> Self {
+        let mut graph = Self {
+            nodes: vec![],
+            edges: vec![],
+            indices: HashMap::new(),
+        };
+        // managers first so that unused textures and materials show up as well
+        for (id, texture) in world.assets.textures.iter_with_ids() {
+            graph.add_node(
+                AssetRef::Texture(id),
+                texture.name.clone().unwrap_or_else(|| "untitled".into()),
+            );
+        }
+        for (id, material) in world.assets.materials.iter_with_ids() {
+            let node = graph.add_node(
+                AssetRef::Material(id),
+                material.name().as_deref().unwrap_or("untitled").to_string(),
+            );
+            for tex_id in material.texture_ids() {
+                graph.add_edge(node, AssetRef::Texture(tex_id));
+            }
+        }
+        for (id, scene) in world.scenes.iter() {
+            let node = graph.add_node(
+                AssetRef::Scene(*id),
+                scene
+                    .name
+                    .as_deref()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("{}", id)),
+            );
+            for model in scene.models.iter() {
+                let model_node = graph.add_model(model);
+                graph.edges.push((node, model_node));
+            }
+        }
+        graph
+    }
+
+    fn add_model(&mut self, model: &Model) -> usize {
+        let node = self.add_node(
+            AssetRef::Model(model.id),
+            model
+                .name
+                .as_deref()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{}", model.id)),
+        );
+        for mesh in model.meshes.iter() {
+            let mesh_node = self.add_node(AssetRef::Mesh(mesh.id), format!("{}", mesh.id));
+            self.edges.push((node, mesh_node));
+            self.add_edge(mesh_node, AssetRef::Material(mesh.material));
+        }
+        for child in model.children.iter() {
+            let child_node = self.add_model(child);
+            self.edges.push((node, child_node));
+        }
+        node
+    }
+
+    fn add_node(&mut self, asset: AssetRef, name: String) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(AssetNode { asset, name });
+        self.indices.insert(asset, index);
+        index
+    }
+
+    fn add_edge(&mut self, from: usize, to: AssetRef) {
+        if let Some(to) = self.indices.get(&to) {
+            self.edges.push((from, *to));
+        }
+    }
+
+    pub fn index_of(&self, asset: AssetRef) -> Option<usize> {
+        self.indices.get(&asset).copied()
+    }
+
+    /**
+    Assets that the given asset directly uses.
+     */
+    pub fn dependencies(&self, asset: AssetRef) -> impl Iterator<Item = &AssetNode> {
+        let index = self.index_of(asset);
+        self.edges
+            .iter()
+            .filter(move |(from, _)| Some(*from) == index)
+            .map(|(_, to)| &self.nodes[*to])
+    }
+
+    /**
+    Assets that directly use the given asset, i.e. the ones preventing it from being deleted.
+     */
+    pub fn dependents(&self, asset: AssetRef) -> impl Iterator<Item = &AssetNode> {
+        let index = self.index_of(asset);
+        self.edges
+            .iter()
+            .filter(move |(_, to)| Some(*to) == index)
+            .map(|(from, _)| &self.nodes[*from])
+    }
+
+    pub fn is_referenced(&self, asset: AssetRef) -> bool {
+        self.dependents(asset).next().is_some()
+    }
+}
+
+impl Debug for DependencyGraph {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{{DEPENDENCY GRAPH: # of nodes: {}, # of edges: {}}}",
+            self.nodes.len(),
+            self.edges.len()
+        )
+    }
+}
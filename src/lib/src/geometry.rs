@@ -1,44 +1,122 @@
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::managers::MatId;
+use crate::scene::mesh::Mesh;
+
+/**
+Simple primitive shapes that can be turned into meshes, centered around the origin. Spheres are UV spheres with their
+poles on the y axis.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Geometry {
     Cube { width: f32, height: f32, depth: f32 },
     Plane { width: f32, depth: f32 },
+    Sphere { radius: f32 },
+}
+
+impl Geometry {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Geometry::Cube { .. } => "Cube",
+            Geometry::Plane { .. } => "Plane",
+            Geometry::Sphere { .. } => "Sphere",
+        }
+    }
+
+    pub fn new_mesh(&self, material: MatId) -> Mesh {
+        let mut data = MeshData::default();
+        match *self {
+            Geometry::Cube { width, height, depth } => {
+                let half = Vec3::new(width, height, depth) / 2.0;
+                for (normal, tangent) in [
+                    (Vec3::X, Vec3::NEG_Z),
+                    (Vec3::NEG_X, Vec3::Z),
+                    (Vec3::Y, Vec3::X),
+                    (Vec3::NEG_Y, Vec3::X),
+                    (Vec3::Z, Vec3::X),
+                    (Vec3::NEG_Z, Vec3::NEG_X),
+                ] {
+                    data.add_face(normal * half, normal, tangent * half, normal.cross(tangent) * half);
+                }
+            }
+            Geometry::Plane { width, depth } => {
+                data.add_face(
+                    Vec3::ZERO,
+                    Vec3::Y,
+                    Vec3::X * width / 2.0,
+                    Vec3::Y.cross(Vec3::X) * depth / 2.0,
+                );
+            }
+            Geometry::Sphere { radius } => data.add_sphere(radius),
+        }
+        Mesh::from(
+            data.vertices,
+            data.indices,
+            data.normals,
+            data.tangents,
+            material,
+            data.uvs,
+            Mat4::IDENTITY,
+        )
+    }
 }
 
-// impl Geometry {
-//     pub fn new_mesh(&self) -> Mesh {
-//         let vertex_data = match self {
-//             Geometry::Cube { width, height, depth } => {
-//                 let width = *width;
-//                 let height = *height;
-//                 let depth = *depth;
-//                 vec![
-//                     Vec3::new(width, height, -depth),
-//                     Vec3::new(width, height, -depth),
-//                     Vec3::new(width, height, -depth),
-//                     Vec3::new(width, -height, -depth),
-//                     Vec3::new(width, -height, -depth),
-//                     Vec3::new(width, -height, -depth),
-//                     Vec3::new(width, height, depth),
-//                     Vec3::new(width, height, depth),
-//                     Vec3::new(width, height, depth),
-//                     Vec3::new(width, -height, depth),
-//                     Vec3::new(width, -height, depth),
-//                     Vec3::new(width, -height, depth),
-//                     Vec3::new(-width, height, -depth),
-//                     Vec3::new(-width, height, -depth),
-//                     Vec3::new(-width, height, -depth),
-//                     Vec3::new(-width, -height, -depth),
-//                     Vec3::new(-width, -height, -depth),
-//                     Vec3::new(-width, -height, -depth),
-//                     Vec3::new(-width, height, depth),
-//                     Vec3::new(-width, height, depth),
-//                     Vec3::new(-width, height, depth),
-//                     Vec3::new(-width, -height, depth),
-//                     Vec3::new(-width, -height, depth),
-//                     Vec3::new(-width, -height, depth),
-//                 ]
-//             }
-//             Geometry::Plane { .. } => {}
-//         }
-//         Mesh::from()
-//     }
-// }
+#[derive(Default)]
+struct MeshData {
+    vertices: Vec<Vec3>,
+    indices: Vec<u32>,
+    normals: Vec<Vec3>,
+    tangents: Vec<Vec4>,
+    uvs: Vec<Vec2>,
+}
+
+impl MeshData {
+    // adds a quad spanned by the (half-length) tangent and bitangent, wound counter-clockwise when seen from the front
+    fn add_face(&mut self, center: Vec3, normal: Vec3, tangent: Vec3, bitangent: Vec3) {
+        let first = self.vertices.len() as u32;
+        for (t, b, uv) in [
+            (-1.0, -1.0, Vec2::new(0.0, 1.0)),
+            (1.0, -1.0, Vec2::new(1.0, 1.0)),
+            (1.0, 1.0, Vec2::new(1.0, 0.0)),
+            (-1.0, 1.0, Vec2::new(0.0, 0.0)),
+        ] {
+            self.vertices.push(center + tangent * t + bitangent * b);
+            self.normals.push(normal);
+            self.tangents.push(Vec4::from((tangent.normalize(), 1.0)));
+            self.uvs.push(uv);
+        }
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    // rings from the top pole at +y to the bottom one, with a seam of duplicate vertices where u wraps around
+    fn add_sphere(&mut self, radius: f32) {
+        const RINGS: u32 = 32;
+        const SEGMENTS: u32 = 64;
+        let first = self.vertices.len() as u32;
+        for ring in 0..=RINGS {
+            let theta = ring as f32 / RINGS as f32 * std::f32::consts::PI;
+            for segment in 0..=SEGMENTS {
+                let phi = segment as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+                let normal = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+                self.vertices.push(normal * radius);
+                self.normals.push(normal);
+                // along increasing u, with v decreasing along the bitangent like the faces above
+                self.tangents.push(Vec4::new(-phi.sin(), 0.0, phi.cos(), 1.0));
+                self.uvs.push(Vec2::new(
+                    segment as f32 / SEGMENTS as f32,
+                    1.0 - ring as f32 / RINGS as f32,
+                ));
+            }
+        }
+        let row = SEGMENTS + 1;
+        for ring in 0..RINGS {
+            for segment in 0..SEGMENTS {
+                let a = first + ring * row + segment;
+                let b = a + row;
+                // counter-clockwise when seen from outside
+                self.indices.extend([a, a + 1, b + 1, a, b + 1, b]);
+            }
+        }
+    }
+}
@@ -1,12 +1,17 @@
+use crate::managers::TexId;
 use crate::scene::material::PbrMaterial;
 use wgpu::Buffer;
 
+pub mod assets;
 pub mod buffer_array;
-mod geometry;
+pub mod dependency_graph;
+pub mod geometry;
 pub mod managers;
+pub mod mesh_pool;
 pub mod scene;
 pub mod scene_serde;
 pub mod shader_types;
+pub mod staging;
 pub mod texture;
 pub mod util;
 
@@ -55,4 +60,10 @@ impl Material {
             Material::Pbr(pbr) => pbr.dirty(),
         }
     }
+
+    pub fn texture_ids(&self) -> impl Iterator<Item = TexId> + '_ {
+        match self {
+            Material::Pbr(pbr) => pbr.texture_ids(),
+        }
+    }
 }
@@ -1,12 +1,17 @@
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+use image::DynamicImage;
 use log::{debug, info, warn};
 use slotmap::basic::SlotMap;
 use slotmap::new_key_type;
 use wgpu::{BindGroupLayout, BufferUsages, Device, Queue};
 
 use crate::buffer_array::DynamicBufferArray;
-use crate::scene::material::PbrMaterial;
+use crate::scene::material::{PbrMaterial, PbrMaterialDesc};
 use crate::shader_types::MaterialInfo;
-use crate::texture::{Texture, TextureKind};
+use crate::staging::BufferWriter;
+use crate::texture::{image_hash, Texture, TextureKind};
 use crate::Material;
 
 new_key_type! { pub struct TexId; }
@@ -16,48 +21,183 @@ new_key_type! { pub struct MatId; }
 #[derive(Default)]
 pub struct TextureManager {
     textures: SlotMap<TexId, Texture>,
-    default_albedo: TexId,
-    default_normal: TexId,
+    defaults: HashMap<TextureKind, TexId>,
+    // position of each texture in bindless texture arrays; appended to so that slots stay valid as textures are added,
+    // only removing textures moves them
+    slots: Vec<TexId>,
+    slot_of: HashMap<TexId, u32>,
+    // set whenever a texture is added, so that texture arrays referencing all textures can be rebuilt
+    dirty: bool,
+    // textures created from images by the hash of their pixels, so that the same image is only uploaded once
+    by_hash: HashMap<(blake3::Hash, TextureKind), TexId>,
+    // id of the next added texture; only ever counts up, so that ids of removed textures aren't handed out again
+    next_id: u32,
 }
 
 impl TextureManager {
-    pub fn new(device: &Device, queue: &Queue) -> Self {
-        let mut textures = SlotMap::with_key();
-        let default_albedo = Texture::from_image(
-            device,
-            queue,
-            &image::load_from_memory(include_bytes!("../../../assets/textures/default.png")).unwrap(),
-            Some("Default Albedo Texture"),
-            TextureKind::Albedo,
-        )
-        .expect("Couldn't load default texture");
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        let default_normal = Texture::from_image(
-            device,
-            queue,
-            &image::load_from_memory(include_bytes!("../../../assets/textures/default_normal.png")).unwrap(),
-            Some("Default Normal Texture"),
-            TextureKind::Normal,
-        )
-        .expect("Couldn't load default normal texture");
+    /**
+    Registers the texture that gets bound whenever a material slot of the given kind is empty.
+    */
+    pub fn set_default(&mut self, texture_kind: TextureKind, texture: Texture) -> TexId {
+        let id = self.insert(texture);
+        self.defaults.insert(texture_kind, id);
+        id
+    }
 
-        Self {
-            default_albedo: textures.insert(default_albedo),
-            default_normal: textures.insert(default_normal),
-            textures,
+    /**
+    Adds a texture, unless a texture of the same kind was created from the same pixels already; the new texture is
+    dropped then and the existing texture's id returned. Use `add_image` to skip creating the texture in that case.
+     */
+    pub fn add_texture(&mut self, mut texture: Texture) -> TexId {
+        let hash = texture.content_hash().map(|hash| (hash, texture.kind));
+        if let Some(&id) = hash.as_ref().and_then(|hash| self.by_hash.get(hash)) {
+            debug!("Texture {:?} is a duplicate of texture {:?}", texture.name, id);
+            return id;
+        }
+        texture.id = Some(self.next_id);
+        self.next_id += 1;
+        let id = self.insert(texture);
+        if let Some(hash) = hash {
+            self.by_hash.insert(hash, id);
         }
+        id
     }
-    pub fn add_texture(&mut self, mut texture: Texture) -> TexId {
-        let id = self.textures.len();
-        texture.id = Some(id as u32);
+
+    /**
+    Creates a texture from the image and adds it, or returns the id of the texture of the same kind that was created
+    from the same pixels before.
+     */
+    pub fn add_image(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        img: &DynamicImage,
+        label: Option<&str>,
+        texture_kind: TextureKind,
+    ) -> TexId {
+        let rgba = Arc::new(img.to_rgba8());
+        if let Some(&id) = self.by_hash.get(&(image_hash(&rgba), texture_kind)) {
+            debug!("Image {:?} is a duplicate of texture {:?}", label, id);
+            return id;
+        }
+        self.add_texture(Texture::from_rgba(device, queue, rgba, label, texture_kind))
+    }
+
+    /**
+    Swaps the texture for another one, e.g. after its file changed, keeping its id and slot. Marks the manager dirty, so
+    that texture arrays are rebuilt; bind groups of materials that use the texture have to be recreated.
+     */
+    pub fn replace_texture(&mut self, id: TexId, mut texture: Texture) {
+        self.by_hash.retain(|_, other| *other != id);
+        if let Some(hash) = texture.content_hash() {
+            // another texture may have the new pixels already, both are kept
+            self.by_hash.entry((hash, texture.kind)).or_insert(id);
+        }
+        let old = &mut self.textures[id];
+        texture.id = old.id;
+        *old = texture;
+        self.dirty = true;
+    }
+
+    fn insert(&mut self, texture: Texture) -> TexId {
         let id = self.textures.insert(texture);
+        self.slot_of.insert(id, self.slots.len() as u32);
+        self.slots.push(id);
+        self.dirty = true;
         id
     }
 
+    /**
+    Removes the texture and returns it, the default textures are kept. The slots of the textures after it move up, so
+    material infos have to be uploaded again, see `MaterialManager::compact`.
+     */
+    pub fn remove_texture(&mut self, id: TexId) -> Option<Texture> {
+        if self.is_default(id) {
+            return None;
+        }
+        let texture = self.textures.remove(id)?;
+        self.by_hash.retain(|_, other| *other != id);
+        self.slots.retain(|other| *other != id);
+        self.slot_of = self
+            .slots
+            .iter()
+            .enumerate()
+            .map(|(slot, id)| (*id, slot as u32))
+            .collect();
+        self.dirty = true;
+        Some(texture)
+    }
+
+    /**
+    Whether the texture is the default of a texture kind, see `set_default`.
+     */
+    pub fn is_default(&self, id: TexId) -> bool {
+        self.defaults.values().any(|default| *default == id)
+    }
+
+    /**
+    Index of the texture in bindless texture arrays, see `iter_slots`.
+     */
+    pub fn slot(&self, id: TexId) -> u32 {
+        self.slot_of[&id]
+    }
+
+    /**
+    Like `unwrap_default`, but returns the slot of the texture.
+     */
+    pub fn slot_or_default(&self, tex_id: &Option<TexId>, texture_kind: TextureKind) -> u32 {
+        match tex_id {
+            Some(id) => self.slot(*id),
+            None => match self.defaults.get(&texture_kind) {
+                Some(id) => self.slot(*id),
+                None => self.slot(self.defaults[&TextureKind::Albedo]),
+            },
+        }
+    }
+
+    /**
+    All textures in slot order, i.e. the n-th texture has slot n.
+     */
+    pub fn iter_slots(&self) -> impl Iterator<Item = &Texture> {
+        self.slots.iter().map(|id| &self.textures[*id])
+    }
+
+    /**
+    Whether textures were added since the last call to `set_clean`.
+     */
+    pub fn dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn set_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    /**
+    Recreates all textures on another device, e.g. after the old device was lost. Marks the manager dirty, so that
+    texture arrays are rebuilt.
+     */
+    pub fn recreate(&mut self, device: &Device, queue: &Queue) {
+        for texture in self.textures.values_mut() {
+            if !texture.recreate(device, queue) {
+                warn!("Texture {:?} has no source pixels and can't be recreated", texture.name);
+            }
+        }
+        self.dirty = true;
+    }
+
     pub fn get_texture(&self, id: &TexId) -> &Texture {
         &self.textures[*id]
     }
 
+    pub fn contains(&self, id: TexId) -> bool {
+        self.textures.contains_key(id)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &Texture> {
         self.textures.values()
     }
@@ -67,16 +207,11 @@ impl TextureManager {
     }
 
     pub fn default_tex(&self, texture_kind: TextureKind) -> &Texture {
-        match texture_kind {
-            TextureKind::Albedo => &self.textures[self.default_albedo],
-            TextureKind::Normal => &self.textures[self.default_normal], // TODO support other default texture kinds
-            TextureKind::MetalRoughness => &self.textures[self.default_albedo],
-            TextureKind::Occlusion => &self.textures[self.default_albedo],
-            TextureKind::Emission => &self.textures[self.default_albedo],
-            TextureKind::Depth => &self.textures[self.default_albedo],
-            TextureKind::Other => {
+        match self.defaults.get(&texture_kind) {
+            Some(id) => &self.textures[*id],
+            None => {
                 warn!("No default texture for texture kind {:?}", texture_kind);
-                &self.textures[self.default_albedo]
+                &self.textures[self.defaults[&TextureKind::Albedo]]
             }
         }
     }
@@ -99,8 +234,25 @@ impl MaterialManager {
         queue: &Queue,
         mat_bind_group_layout: &BindGroupLayout,
         tex_bind_group_layout: &BindGroupLayout,
-        texture_manager: &TextureManager,
+        texture_manager: &mut TextureManager,
     ) -> Self {
+        // fallbacks for empty material slots, chosen so that they don't influence shading:
+        // white albedo, flat normal, neutral occlusion/roughness/metallic (only the factors apply), no emission. The
+        // other kinds, e.g. of textures imported from glTF files, default to white as well
+        for (kind, color, label) in [
+            (TextureKind::Albedo, [255, 255, 255, 255], "Default Albedo Texture"),
+            (TextureKind::Normal, [128, 128, 255, 255], "Default Normal Texture"),
+            (TextureKind::MetalRoughness, [255, 255, 255, 255], "Default Metal Roughness Texture"),
+            (TextureKind::Occlusion, [255, 255, 255, 255], "Default Occlusion Texture"),
+            (TextureKind::Emission, [0, 0, 0, 255], "Default Emission Texture"),
+            (TextureKind::Depth, [255, 255, 255, 255], "Default Depth Texture"),
+            (TextureKind::Other, [255, 255, 255, 255], "Default Texture"),
+        ] {
+            let texture = Texture::from_color(device, queue, color, Some(label), kind)
+                .unwrap_or_else(|_| panic!("Couldn't create {}", label));
+            texture_manager.set_default(kind, texture);
+        }
+
         let mut materials = SlotMap::with_key();
         let mut pbr_mat = PbrMaterial::from_default(None);
         pbr_mat.create_texture_bind_group(device, tex_bind_group_layout, texture_manager);
@@ -110,7 +262,12 @@ impl MaterialManager {
             BufferUsages::STORAGE | BufferUsages::COPY_DST,
             mat_bind_group_layout,
         );
-        buffer.push(device, queue, &[MaterialInfo::from(&pbr_mat)], mat_bind_group_layout);
+        buffer.push(
+            device,
+            queue,
+            &[MaterialInfo::new(&pbr_mat, texture_manager)],
+            mat_bind_group_layout,
+        );
         let default_material = materials.insert(Material::Pbr(pbr_mat));
 
         Self {
@@ -125,23 +282,112 @@ impl MaterialManager {
         device: &Device,
         queue: &Queue,
         bind_group_layout: &BindGroupLayout,
+        texture_manager: &TextureManager,
     ) -> MatId {
         debug!("Adding material: {:?}", material.name());
-        let shader_id = self.materials.len();
+        // the index the material info is pushed to, which differs from the number of materials after removing some
+        let shader_id = self.buffer.len();
         material.set_shader_id(shader_id as u32);
         match &material {
             Material::Pbr(pbr) => {
                 self.buffer
-                    .push(device, queue, &[MaterialInfo::from(pbr)], bind_group_layout);
+                    .push(device, queue, &[MaterialInfo::new(pbr, texture_manager)], bind_group_layout);
             }
         }
         self.materials.insert(material)
     }
 
+    /**
+    Creates a PBR material with its texture bind group and adds it, the textures of `desc` have to exist in
+    `texture_manager`.
+     */
+    pub fn create_material(
+        &mut self,
+        desc: PbrMaterialDesc,
+        device: &Device,
+        queue: &Queue,
+        mat_bind_group_layout: &BindGroupLayout,
+        tex_bind_group_layout: &BindGroupLayout,
+        texture_manager: &TextureManager,
+    ) -> MatId {
+        let mut material = PbrMaterial::from_desc(desc);
+        material.create_texture_bind_group(device, tex_bind_group_layout, texture_manager);
+        self.add_material(
+            Material::Pbr(material),
+            device,
+            queue,
+            mat_bind_group_layout,
+            texture_manager,
+        )
+    }
+
+    /**
+    A material that looks like the given one, see `PbrMaterial::looks_like`, e.g. to share it between imports of the
+    same asset. The default material is never returned, so that editing a shared material doesn't change it.
+     */
+    pub fn find_identical(&self, material: &PbrMaterial) -> Option<MatId> {
+        self.materials
+            .iter()
+            .filter(|(id, _)| *id != self.default_material)
+            .find(|(_, other)| match other {
+                Material::Pbr(other) => other.looks_like(material),
+            })
+            .map(|(id, _)| id)
+    }
+
+    /**
+    Removes the material and returns it, the default material is kept. Its material info stays in the buffer until
+    `compact` is called.
+     */
+    pub fn remove_material(&mut self, id: MatId) -> Option<Material> {
+        if id == self.default_material {
+            return None;
+        }
+        self.materials.remove(id)
+    }
+
+    /**
+    Rebuilds the material buffer with only the remaining materials, which changes their shader ids. The mesh infos of
+    all meshes have to be uploaded again afterwards, see `Scene::update_all_meshes`.
+     */
+    pub fn compact(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        mat_bind_group_layout: &BindGroupLayout,
+        texture_manager: &TextureManager,
+    ) {
+        let mut infos = Vec::with_capacity(self.materials.len());
+        for (shader_id, material) in self.materials.values_mut().enumerate() {
+            let Material::Pbr(material) = material;
+            material.shader_id = shader_id as u32;
+            material.dirty = false;
+            infos.push(MaterialInfo::new(material, texture_manager));
+        }
+        // replacing the buffer frees the old one, which may be much larger
+        self.buffer = DynamicBufferArray::new(
+            device,
+            Some("Material Buffer".to_string()),
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mat_bind_group_layout,
+        );
+        self.buffer.push(device, queue, &infos, mat_bind_group_layout);
+        info!("Compacted the material buffer to {} materials", infos.len());
+    }
+
     pub fn get_material(&self, id: MatId) -> &Material {
         &self.materials[id]
     }
 
+    pub fn contains(&self, id: MatId) -> bool {
+        self.materials.contains_key(id)
+    }
+
+    /// mark the material as dirty after changing it, so that `update_dirty` uploads it
+    pub fn get_material_mut(&mut self, id: MatId) -> &mut Material {
+        &mut self.materials[id]
+    }
+
     pub fn get_default_material(&self) -> &Material {
         &self.materials[self.default_material]
     }
@@ -154,14 +400,36 @@ impl MaterialManager {
         self.materials.iter()
     }
 
-    pub fn update_dirty(&mut self, queue: &Queue) {
+    /**
+    Recreates the material buffer and the materials' texture bind groups on another device, e.g. after the old device
+    was lost. The textures have to be recreated first.
+     */
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        mat_bind_group_layout: &BindGroupLayout,
+        tex_bind_group_layout: &BindGroupLayout,
+        texture_manager: &TextureManager,
+    ) {
+        self.buffer.recreate(device, mat_bind_group_layout);
+        for material in self.materials.values_mut() {
+            let Material::Pbr(material) = material;
+            material.create_texture_bind_group(device, tex_bind_group_layout, texture_manager);
+            self.buffer
+                .update(queue, material.shader_id as u64, MaterialInfo::new(material, texture_manager));
+            material.dirty = false;
+        }
+    }
+
+    pub fn update_dirty(&mut self, mut writer: impl BufferWriter, texture_manager: &TextureManager) {
         for (_, mat) in self.materials.iter_mut().filter(|(_, m)| m.dirty()) {
             debug!("Updating material {:?}...", mat.name());
             let Material::Pbr(mat) = mat;
             mat.dirty = false;
             let mat_id = mat.shader_id;
-            let uniform = MaterialInfo::from(mat);
-            self.buffer.update(queue, mat_id as u64, uniform);
+            let uniform = MaterialInfo::new(mat, texture_manager);
+            self.buffer.update(&mut writer, mat_id as u64, uniform);
             info!("Updated material #{}", mat_id);
         }
     }
@@ -0,0 +1,225 @@
+use std::ops::Range;
+
+use itertools::izip;
+use log::debug;
+use wgpu::{Buffer, BufferUsages, Device, IndexFormat, Queue, RenderPass};
+
+use crate::scene::mesh::Mesh;
+use crate::scene::VertexInputs;
+use crate::shader_types::PbrVertex;
+
+// how many vertices and indices a pool has space for initially
+const INITIAL_VERTICES: u64 = 4096;
+const INITIAL_INDICES: u64 = 16384;
+
+/**
+One vertex buffer and one index buffer shared by all meshes of a scene. Each mesh takes up a range of vertices and a
+range of indices, which are bound once per render pass and drawn with `draw_indexed` using the mesh's `VertexInputs`.
+Ranges of removed meshes are reused by meshes added later. The buffers double in size whenever a mesh doesn't fit.
+ */
+pub struct MeshPool {
+    label: String,
+    vertices: Buffer,
+    indices: Buffer,
+    vertex_ranges: RangeAllocator,
+    index_ranges: RangeAllocator,
+}
+
+impl MeshPool {
+    pub fn new(device: &Device, label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            vertices: create_buffer(device, label, vertex_bytes(INITIAL_VERTICES), BufferUsages::VERTEX),
+            indices: create_buffer(device, label, index_bytes(INITIAL_INDICES), BufferUsages::INDEX),
+            vertex_ranges: RangeAllocator::new(INITIAL_VERTICES),
+            index_ranges: RangeAllocator::new(INITIAL_INDICES),
+        }
+    }
+
+    /**
+    Uploads the mesh's vertices and indices and returns where they are. The result belongs into the mesh's
+    `vertex_inputs`, and has to be passed to `remove` once the mesh is removed from the pool.
+     */
+    pub fn insert(&mut self, device: &Device, queue: &Queue, mesh: &Mesh) -> VertexInputs {
+        let vertices = izip!(&mesh.vertices, &mesh.normals, &mesh.tangents, &mesh.uvs)
+            .map(|(position, normal, tangent, uv)| PbrVertex {
+                position: (*position).into(),
+                normal: (*normal).into(),
+                tangent: (*tangent).into(),
+                uv: (*uv).into(),
+            })
+            .collect::<Vec<_>>();
+        let mut indices = mesh.indices.iter().map(|i| *i as u16).collect::<Vec<u16>>();
+        let index_count = indices.len() as u32;
+        // writes have to be 4 byte aligned, so every range takes up an even number of indices
+        if indices.len() % 2 == 1 {
+            indices.push(0);
+        }
+
+        let base_vertex = match self.vertex_ranges.allocate(vertices.len() as u64) {
+            Some(start) => start,
+            None => {
+                let capacity = grown_capacity(&self.vertex_ranges, vertices.len() as u64);
+                self.vertices = self.grow(
+                    device,
+                    queue,
+                    &self.vertices,
+                    vertex_bytes(capacity),
+                    BufferUsages::VERTEX,
+                );
+                self.vertex_ranges.grow(capacity);
+                self.vertex_ranges.allocate(vertices.len() as u64).unwrap()
+            }
+        };
+        let first_index = match self.index_ranges.allocate(indices.len() as u64) {
+            Some(start) => start,
+            None => {
+                let capacity = grown_capacity(&self.index_ranges, indices.len() as u64);
+                self.indices = self.grow(device, queue, &self.indices, index_bytes(capacity), BufferUsages::INDEX);
+                self.index_ranges.grow(capacity);
+                self.index_ranges.allocate(indices.len() as u64).unwrap()
+            }
+        };
+        queue.write_buffer(
+            &self.vertices,
+            vertex_bytes(base_vertex),
+            bytemuck::cast_slice(&vertices),
+        );
+        queue.write_buffer(&self.indices, index_bytes(first_index), bytemuck::cast_slice(&indices));
+
+        VertexInputs {
+            mesh_id: mesh.id,
+            base_vertex: base_vertex as u32,
+            vertex_count: vertices.len() as u32,
+            first_index: first_index as u32,
+            index_count,
+        }
+    }
+
+    /**
+    Frees the ranges of a mesh that was inserted into this pool.
+     */
+    pub fn remove(&mut self, inputs: &VertexInputs) {
+        let base_vertex = inputs.base_vertex as u64;
+        let first_index = inputs.first_index as u64;
+        self.vertex_ranges
+            .free(base_vertex..base_vertex + inputs.vertex_count as u64);
+        self.index_ranges
+            .free(first_index..first_index + inputs.index_count.next_multiple_of(2) as u64);
+    }
+
+    /**
+    Binds the vertex and index buffer, after which meshes in this pool can be drawn.
+     */
+    pub fn bind<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.set_index_buffer(self.indices.slice(..), IndexFormat::Uint16);
+    }
+
+    /**
+    Size of the vertex buffer on the GPU in bytes, including unused space.
+     */
+    pub fn vertex_buffer_size(&self) -> u64 {
+        self.vertices.size()
+    }
+
+    /**
+    Size of the index buffer on the GPU in bytes, including unused space.
+     */
+    pub fn index_buffer_size(&self) -> u64 {
+        self.indices.size()
+    }
+
+    // creates a larger buffer with the contents of `buffer` and submits the copy immediately
+    fn grow(&self, device: &Device, queue: &Queue, buffer: &Buffer, size: u64, usage: BufferUsages) -> Buffer {
+        debug!(
+            "Growing buffer of mesh pool {:?} from {} to {} bytes",
+            self.label,
+            buffer.size(),
+            size
+        );
+        let new_buffer = create_buffer(device, &self.label, size, usage);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mesh pool resize encoder"),
+        });
+        encoder.copy_buffer_to_buffer(buffer, 0, &new_buffer, 0, buffer.size());
+        queue.submit(std::iter::once(encoder.finish()));
+        new_buffer
+    }
+}
+
+fn create_buffer(device: &Device, label: &str, size: u64, usage: BufferUsages) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: usage | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+fn vertex_bytes(count: u64) -> u64 {
+    count * std::mem::size_of::<PbrVertex>() as u64
+}
+
+fn index_bytes(count: u64) -> u64 {
+    count * std::mem::size_of::<u16>() as u64
+}
+
+// doubles the capacity until `len` more elements fit at its end
+fn grown_capacity(ranges: &RangeAllocator, len: u64) -> u64 {
+    let mut capacity = ranges.capacity;
+    while capacity < ranges.capacity + len {
+        capacity *= 2;
+    }
+    capacity
+}
+
+// hands out ranges of elements, first fit
+struct RangeAllocator {
+    capacity: u64,
+    // sorted and never adjacent to each other
+    free: Vec<Range<u64>>,
+}
+
+impl RangeAllocator {
+    fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            free: std::iter::once(0..capacity).collect(),
+        }
+    }
+
+    fn allocate(&mut self, len: u64) -> Option<u64> {
+        if len == 0 {
+            return Some(0);
+        }
+        let i = self.free.iter().position(|range| range.end - range.start >= len)?;
+        let start = self.free[i].start;
+        self.free[i].start += len;
+        if self.free[i].is_empty() {
+            self.free.remove(i);
+        }
+        Some(start)
+    }
+
+    fn free(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        let i = self.free.partition_point(|free| free.start < range.start);
+        self.free.insert(i, range);
+        // merge with the next range, then with the previous one
+        if i + 1 < self.free.len() && self.free[i].end == self.free[i + 1].start {
+            self.free[i].end = self.free.remove(i + 1).end;
+        }
+        if i > 0 && self.free[i - 1].end == self.free[i].start {
+            self.free[i - 1].end = self.free.remove(i).end;
+        }
+    }
+
+    fn grow(&mut self, capacity: u64) {
+        let added = self.capacity..capacity;
+        self.capacity = capacity;
+        self.free(added);
+    }
+}
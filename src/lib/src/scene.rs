@@ -1,40 +1,82 @@
 use std::fmt::{Debug, Formatter};
 
-use glam::{Vec2, Vec3, Vec4};
+use anyhow::{bail, Result};
+use glam::{Mat4, Vec3};
 use hashbrown::HashMap;
-use itertools::izip;
-use log::debug;
-use rand::Rng;
-use wgpu::{BindGroupLayout, Buffer, BufferUsages, Device, Queue};
-use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use log::{debug, info};
+use wgpu::{BindGroupLayout, BufferUsages, Device, Queue};
 
-use crate::{Dirtyable, Material, SizedBuffer};
+use crate::{Dirtyable, Material};
 use crate::buffer_array::{DynamicBufferArray, DynamicBufferMap};
-use crate::managers::{MaterialManager, TextureManager};
-use crate::scene::mesh::Mesh;
+use crate::assets::{AssetKey, AssetServer, Purged};
+use crate::dependency_graph::DependencyGraph;
+use crate::managers::{MatId, MaterialManager, TexId};
+use crate::mesh_pool::MeshPool;
+use crate::scene::bindings::{world_position, Binding, BindingInputs, Property};
+use crate::scene::character::CharacterController;
+use crate::scene::light::PointLight;
+use crate::scene::lighting::Lighting;
+use crate::scene::mesh::{Mesh, Outline};
 use crate::scene::model::{DeepIter, Model};
-use crate::shader_types::{LightInfo, MeshInfo, PbrVertex};
+use crate::scene::physics::PhysicsSettings;
+use crate::scene::post_effects::PostEffects;
+use crate::scene::sky::Background;
+use crate::scene::water::Water;
+use crate::shader_types::{LightInfo, MeshInfo};
+use crate::staging::BufferWriter;
+use crate::texture::Texture;
+use crate::util::ids::next_id;
 
+// once at least one in this many meshes is dirty, `Scene::update_meshes` uploads all of them
+const FULL_UPLOAD_DIVISOR: usize = 4;
+
+pub mod bindings;
+pub mod character;
+#[cfg(feature = "ecs")]
+pub mod ecs;
+pub mod joint;
 pub mod light;
+pub mod lighting;
 pub mod material;
 pub mod mesh;
 pub mod model;
+pub mod params;
+pub mod physics;
+pub mod post_effects;
+pub mod sky;
+pub mod static_batch;
+pub mod water;
 
 pub struct Scene {
     pub id: u32,
     pub models: Vec<Model>,
     pub name: Option<Box<str>>,
     pub mesh_buffer: DynamicBufferMap<MeshInfo, u32>,
+    /// the vertices and indices of the meshes in `mesh_buffer`
+    pub mesh_pool: MeshPool,
+    /// merged meshes of static models, see `Scene::update_static_batches`
+    static_batches: Vec<Mesh>,
+    // the transform and material of each mesh that is merged into `static_batches`, as of when they were merged
+    batched: HashMap<u32, (Mat4, MatId)>,
     pub light_buffer: DynamicBufferArray<LightInfo>,
-    pub outline_width: u8,
-    pub outline_color: [u8; 3],
+    /// the outline of selected meshes
+    pub selection_outline: Outline,
+    /// the outline of the mesh under the cursor, if hovered meshes are outlined
+    pub hover_outline: Option<Outline>,
+    pub background: Background,
+    pub water: Option<Water>,
+    pub lighting: Lighting,
+    pub post_effects: PostEffects,
+    pub physics: PhysicsSettings,
+    /// properties of models driven by expressions, see `Scene::evaluate_bindings`
+    pub bindings: Vec<Binding>,
 }
 
 impl Scene {
     pub fn from(
         device: &Device,
         queue: &Queue,
-        models: Vec<Model>,
+        mut models: Vec<Model>,
         material_manager: &MaterialManager,
         name: Option<Box<str>>,
         mesh_bind_group_layout: &BindGroupLayout,
@@ -46,15 +88,17 @@ impl Scene {
             BufferUsages::STORAGE | BufferUsages::COPY_DST,
             mesh_bind_group_layout,
         );
+        let mut mesh_pool = MeshPool::new(device, "Scene Mesh Pool");
         let mut light_buffer = DynamicBufferArray::new(
             device,
             Some("Light Buffer".to_string()),
             BufferUsages::STORAGE | BufferUsages::COPY_DST,
             light_bind_group_layout,
         );
-        for model in models.iter() {
-            for mesh in model.meshes.iter() {
+        for model in models.iter_mut() {
+            for mesh in model.meshes.iter_mut() {
                 debug!("Adding mesh {} to meshbuffer", mesh.id);
+                mesh.vertex_inputs = Some(mesh_pool.insert(device, queue, mesh));
                 mesh_buffer.push(
                     device,
                     queue,
@@ -63,19 +107,28 @@ impl Scene {
                     mesh_bind_group_layout,
                 );
             }
-            if let Some(light) = &model.light {
-                light_buffer.push(device, queue, &[LightInfo::from(light)], light_bind_group_layout);
+            if let Some(light) = &mut model.light {
+                push_light(light, &mut light_buffer, device, queue, light_bind_group_layout);
             }
         }
 
         Self {
-            id: rand::thread_rng().gen_range(0u32..1u32 << 31),
+            id: next_id(),
             models,
             name,
             mesh_buffer,
+            mesh_pool,
+            static_batches: vec![],
+            batched: HashMap::new(),
             light_buffer,
-            outline_width: 6,
-            outline_color: [255, 255, 255],
+            selection_outline: Outline::default(),
+            hover_outline: None,
+            background: Background::default(),
+            water: None,
+            lighting: Lighting::default(),
+            post_effects: PostEffects::default(),
+            physics: PhysicsSettings::default(),
+            bindings: vec![],
         }
     }
 
@@ -84,19 +137,21 @@ impl Scene {
      */
     pub fn join(
         &mut self,
-        other: Scene,
+        mut other: Scene,
         device: &Device,
         queue: &Queue,
         material_manager: &MaterialManager,
         mesh_bind_group_layout: &BindGroupLayout,
         light_bind_group_layout: &BindGroupLayout,
     ) {
-        for model in other.models.iter() {
-            for mesh in model.meshes.iter() {
+        for model in other.models.iter_mut() {
+            for mesh in model.meshes.iter_mut() {
                 debug!(
                     "Inserting mesh {} with material {:?} into meshbuffer",
                     mesh.id, mesh.material
                 );
+                // the vertices are in the other scene's pool
+                mesh.vertex_inputs = Some(self.mesh_pool.insert(device, queue, mesh));
                 self.mesh_buffer.push(
                     device,
                     queue,
@@ -105,9 +160,9 @@ impl Scene {
                     mesh_bind_group_layout,
                 );
             }
-            if let Some(light) = &model.light {
-                self.light_buffer
-                    .push(device, queue, &[LightInfo::from(light)], light_bind_group_layout);
+            // the lights' indices point into the other scene's light buffer
+            if let Some(light) = &mut model.light {
+                push_light(light, &mut self.light_buffer, device, queue, light_bind_group_layout);
             }
         }
         self.models.extend(other.models);
@@ -117,19 +172,24 @@ impl Scene {
 
     /*
     Add a model to the scene, and update the mesh and light buffers accordingly. Note: you probably need to also update the light count in the camera.
+    Fails without uploading anything if there is no model `parent_id`.
      */
     pub fn add_model(
         &mut self,
-        model: Model,
+        mut model: Model,
         parent_id: Option<u32>,
         device: &Device,
         queue: &Queue,
         material_manager: &MaterialManager,
         mesh_bind_group_layout: &BindGroupLayout,
         light_bind_group_layout: &BindGroupLayout,
-    ) {
-        for mesh in model.meshes.iter() {
+    ) -> Result<()> {
+        if let Some(parent_id) = parent_id.filter(|parent_id| self.get_model(*parent_id).is_none()) {
+            bail!("Model {} not found", parent_id);
+        }
+        for mesh in model.meshes.iter_mut() {
             debug!("Adding mesh {} to meshbuffer", mesh.id);
+            mesh.vertex_inputs = Some(self.mesh_pool.insert(device, queue, mesh));
             self.mesh_buffer.push(
                 device,
                 queue,
@@ -138,23 +198,116 @@ impl Scene {
                 mesh_bind_group_layout,
             );
         }
-        if let Some(light) = &model.light {
-            self.light_buffer
-                .push(device, queue, &[LightInfo::from(light)], light_bind_group_layout);
-        }
-        if let Some(parent_id) = parent_id {
-            self.models
-                .iter_mut()
-                .find(|m| m.id == parent_id)
-                .unwrap()
-                .children
-                .push(model);
-        } else {
-            self.models.push(model);
+        if let Some(light) = &mut model.light {
+            push_light(light, &mut self.light_buffer, device, queue, light_bind_group_layout);
+        }
+        model.update_visibility(parent_id.is_none_or(|parent_id| self.is_model_visible(parent_id)));
+        match parent_id.and_then(|parent_id| self.get_model_mut(parent_id)) {
+            Some(parent) => parent.children.push(model),
+            None => self.models.push(model),
         }
         self.update_meshes(queue, material_manager);
         self.update_lights(queue);
+        Ok(())
+    }
+
+    /**
+    Replaces the meshes of the model `model_id`, e.g. after its geometry was edited elsewhere. The new meshes are placed
+    at the model's transform. Returns false if there is no such model.
+     */
+    pub fn replace_meshes(
+        &mut self,
+        model_id: u32,
+        meshes: Vec<Mesh>,
+        device: &Device,
+        queue: &Queue,
+        material_manager: &MaterialManager,
+        mesh_bind_group_layout: &BindGroupLayout,
+    ) -> bool {
+        if self.get_model(model_id).is_none() {
+            return false;
+        }
+        let mut meshes = meshes;
+        for mesh in meshes.iter_mut() {
+            mesh.vertex_inputs = Some(self.mesh_pool.insert(device, queue, mesh));
+        }
+        let model = self.get_model_mut(model_id).unwrap();
+        // the old meshes carry the transform of the model's parent
+        let parent = model
+            .meshes
+            .first()
+            .map_or(Mat4::IDENTITY, |mesh| mesh.global_transform * model.local_transform.inverse());
+        let old_meshes = std::mem::replace(&mut model.meshes, meshes);
+        model.update_transforms(parent);
+        let new_meshes = model
+            .meshes
+            .iter()
+            .map(|mesh| (mesh.id, MeshInfo::from_mesh(mesh, material_manager)))
+            .collect::<Vec<_>>();
+        for mesh in old_meshes {
+            self.mesh_buffer.remove(&mesh.id);
+            if let Some(inputs) = &mesh.vertex_inputs {
+                self.mesh_pool.remove(inputs);
+            }
+        }
+        for (id, info) in new_meshes {
+            debug!("Adding mesh {} to meshbuffer", id);
+            self.mesh_buffer.push(device, queue, id, &[info], mesh_bind_group_layout);
+        }
+        self.update_meshes(queue, material_manager);
+        true
+    }
+
+    /**
+    Recreates the scene's GPU resources on another device, e.g. after the old device was lost: the mesh pool, the light
+    buffers and the mesh and light buffers of the scene. The materials have to be recreated
+    first.
+     */
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        material_manager: &MaterialManager,
+        mesh_bind_group_layout: &BindGroupLayout,
+        light_bind_group_layout: &BindGroupLayout,
+    ) {
+        fn recreate_models(models: &mut [Model], pool: &mut MeshPool, device: &Device, queue: &Queue) {
+            for model in models.iter_mut() {
+                for mesh in model.meshes.iter_mut().filter(|mesh| mesh.vertex_inputs.is_some()) {
+                    mesh.vertex_inputs = Some(pool.insert(device, queue, mesh));
+                }
+                if let Some(light) = &mut model.light {
+                    light.recreate_buffer(device);
+                }
+                recreate_models(&mut model.children, pool, device, queue);
+            }
+        }
+        self.mesh_pool = MeshPool::new(device, "Scene Mesh Pool");
+        recreate_models(&mut self.models, &mut self.mesh_pool, device, queue);
+        // merged again by the next `update_static_batches`
+        for batch in self.static_batches.drain(..) {
+            self.mesh_buffer.remove(&batch.id);
+        }
+        self.batched.clear();
+
+        let mesh_infos = self
+            .iter_models_deep()
+            .flat_map(|model| model.meshes.iter())
+            .map(|mesh| (mesh.id, MeshInfo::from_mesh(mesh, material_manager)))
+            .collect::<HashMap<_, _>>();
+        self.mesh_buffer
+            .recreate(device, queue, mesh_bind_group_layout, |id| mesh_infos.get(id).copied());
+        self.light_buffer.recreate(device, light_bind_group_layout);
+        let lights = self
+            .iter_models_deep()
+            .filter_map(|model| model.light.as_ref())
+            .map(|light| (light.index as u64, LightInfo::from(light)))
+            .collect::<Vec<_>>();
+        for (index, info) in lights {
+            self.light_buffer.update(queue, index, info);
+        }
     }
+
     fn remove_model_deep(models: &mut Vec<Model>, model_id: u32) -> Option<Model> {
         let mut found_model = None;
         for (i, model) in models.iter_mut().enumerate() {
@@ -172,54 +325,379 @@ impl Scene {
         }
         Some(models.remove(found_model?))
     }
-    pub fn remove_model(&mut self, model_id: u32, queue: &Queue, material_manager: &MaterialManager) -> Option<Model> {
+    /**
+    Removes the model and the models below it, and frees their meshes' and lights' elements of the scene's buffers. The
+    last lights move into the freed light elements; the camera's light count has to be updated afterwards.
+     */
+    pub fn remove_model(
+        &mut self,
+        model_id: u32,
+        device: &Device,
+        queue: &Queue,
+        material_manager: &MaterialManager,
+    ) -> Option<Model> {
         let model = Self::remove_model_deep(&mut self.models, model_id);
+        if let Some(model) = &model {
+            for mesh in model.meshes.iter().chain(model.children.iter_deep().flat_map(|m| m.meshes.iter())) {
+                self.mesh_buffer.remove(&mesh.id);
+                if let Some(inputs) = &mesh.vertex_inputs {
+                    self.mesh_pool.remove(inputs);
+                }
+            }
+            let mut light_indices: Vec<usize> = std::iter::once(model)
+                .chain(model.children.iter_deep())
+                .filter_map(|model| model.light.as_ref().map(|light| light.index))
+                .collect();
+            // from the back, so that the last light is never one that is removed later
+            light_indices.sort_unstable_by(|a, b| b.cmp(a));
+            for index in light_indices {
+                let Some(remap) = self.light_buffer.swap_remove(device, queue, index as u64) else {
+                    continue;
+                };
+                // the copy on the GPU lacks changes that weren't uploaded yet, so the moved light is uploaded as well
+                if let Some(light) = find_light_mut(&mut self.models, remap.from as usize) {
+                    light.index = remap.to as usize;
+                    light.set_dirty(false);
+                    self.light_buffer.update(queue, remap.to, LightInfo::from(&*light));
+                }
+            }
+            let removed = std::iter::once(model.id)
+                .chain(model.children.iter_deep().map(|m| m.id))
+                .collect::<Vec<_>>();
+            self.bindings.retain(|binding| !removed.contains(&binding.model));
+        }
         self.update_meshes(queue, material_manager);
         self.update_lights(queue);
         model
     }
 
+    /**
+    Removes all joints that attach to the given model, e.g. after it was deleted.
+     */
+    pub fn remove_joints_to(&mut self, model_id: u32) {
+        fn remove_rec(models: &mut [Model], model_id: u32) {
+            for model in models {
+                model.joints.retain(|joint| joint.other != model_id);
+                remove_rec(&mut model.children, model_id);
+            }
+        }
+        remove_rec(&mut self.models, model_id);
+    }
+
+    /**
+    Moves the top-level model `model_id` with its character controller by the horizontal part of `velocity` (in world
+    units per second), applies the scene's gravity and resolves collisions with the meshes of all other models.
+    Returns the updated controller, or `None` if there is no such model or it has no controller.
+     */
+    pub fn move_character(&mut self, model_id: u32, velocity: Vec3, delta_time: f32) -> Option<&CharacterController> {
+        let model = self.models.iter().find(|model| model.id == model_id)?;
+        let mut controller = model.character?;
+        let (scale, rotation, translation) = model.local_transform.to_scale_rotation_translation();
+        let own_meshes = model
+            .meshes
+            .iter()
+            .chain(model.children.iter_deep().flat_map(|child| child.meshes.iter()))
+            .map(|mesh| mesh.id)
+            .collect::<Vec<_>>();
+        let position = self.move_controller(&mut controller, translation, velocity, delta_time, &own_meshes);
+
+        let model = self.models.iter_mut().find(|model| model.id == model_id)?;
+        model.local_transform = Mat4::from_scale_rotation_translation(scale, rotation, position);
+        model.update_transforms(Mat4::IDENTITY);
+        model.character = Some(controller);
+        model.character.as_ref()
+    }
+
+    /**
+    Moves a character controller that isn't attached to a model, like `move_character` does, from `position` (the
+    bottom of its capsule) and returns the new position. The meshes `exclude` aren't collided with.
+     */
+    pub fn move_controller(
+        &self,
+        controller: &mut CharacterController,
+        position: Vec3,
+        velocity: Vec3,
+        delta_time: f32,
+        exclude: &[u32],
+    ) -> Vec3 {
+        let obstacles = self
+            .iter_models_deep()
+            .flat_map(|model| model.meshes.iter())
+            .filter(|mesh| !exclude.contains(&mesh.id))
+            .collect::<Vec<_>>();
+        controller.step(position, velocity, self.physics.gravity, delta_time, &obstacles)
+    }
+
     pub fn iter_meshes(&self) -> impl Iterator<Item = &Mesh> {
         self.models.iter().flat_map(|model| model.meshes.iter())
     }
 
+    /**
+    Like `iter_meshes`, but skips hidden meshes, see `Mesh::is_visible`.
+     */
+    pub fn iter_visible_meshes(&self) -> impl Iterator<Item = &Mesh> {
+        self.iter_meshes().filter(|mesh| mesh.is_visible())
+    }
+
+    /**
+    The meshes to draw: the visible meshes that aren't merged into a static batch, and the static batches. Picking and
+    outlines need the meshes themselves and use `iter_visible_meshes` instead.
+     */
+    pub fn iter_drawn_meshes(&self) -> impl Iterator<Item = &Mesh> {
+        self.iter_visible_meshes()
+            .filter(|mesh| !self.batched.contains_key(&mesh.id))
+            .chain(self.static_batches.iter())
+    }
+
+    // the meshes that `update_static_batches` merges
+    fn iter_static_meshes(&self) -> impl Iterator<Item = &Mesh> {
+        self.models
+            .iter()
+            .filter(|model| model.is_static)
+            .flat_map(|model| model.meshes.iter())
+            .filter(|mesh| mesh.is_visible() && static_batch::is_batchable(mesh))
+    }
+
+    /**
+    Merges the visible meshes of static models that share a material into batches, which are drawn in their place, see
+    `iter_drawn_meshes`. The batches are only merged again once a static mesh was moved, hidden, shown, given another
+    material or removed, or a model became static or not, so this can be called every frame. Returns whether the batches
+    were merged again.
+     */
+    pub fn update_static_batches(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        material_manager: &MaterialManager,
+        mesh_bind_group_layout: &BindGroupLayout,
+    ) -> bool {
+        let current = self
+            .iter_static_meshes()
+            .map(|mesh| (mesh.id, (mesh.transform(), mesh.material)))
+            .collect::<HashMap<_, _>>();
+        if current == self.batched {
+            return false;
+        }
+        for batch in self.static_batches.drain(..) {
+            self.mesh_buffer.remove(&batch.id);
+            if let Some(inputs) = &batch.vertex_inputs {
+                self.mesh_pool.remove(inputs);
+            }
+        }
+        let mut batches = static_batch::merge(self.iter_static_meshes());
+        for batch in batches.iter_mut() {
+            batch.vertex_inputs = Some(self.mesh_pool.insert(device, queue, batch));
+            let info = MeshInfo::from_mesh(batch, material_manager);
+            self.mesh_buffer
+                .push(device, queue, batch.id, &[info], mesh_bind_group_layout);
+            batch.set_dirty(false);
+        }
+        info!("Merged {} static meshes into {} batches", current.len(), batches.len());
+        self.static_batches = batches;
+        self.batched = current;
+        true
+    }
+
+    /**
+    Whether the model is drawn, i.e. neither it nor a model above it is hidden. False if there is no such model.
+     */
+    pub fn is_model_visible(&self, id: u32) -> bool {
+        fn find(models: &[Model], id: u32) -> Option<bool> {
+            for model in models {
+                if model.id == id {
+                    return Some(model.visible);
+                }
+                if let Some(visible) = find(&model.children, id) {
+                    return Some(visible && model.visible);
+                }
+            }
+            None
+        }
+        find(&self.models, id).unwrap_or(false)
+    }
+
+    /**
+    Shows or hides the model or mesh with the id. Hiding a model hides its meshes and all models below it. Returns false
+    if there is no such model or mesh.
+     */
+    pub fn set_visibility(&mut self, id: u32, visible: bool) -> bool {
+        fn set(models: &mut [Model], parent_visible: bool, id: u32, visible: bool) -> bool {
+            for model in models.iter_mut() {
+                if model.id == id {
+                    model.visible = visible;
+                    model.update_visibility(parent_visible);
+                    return true;
+                }
+                if let Some(mesh) = model.meshes.iter_mut().find(|mesh| mesh.id == id) {
+                    mesh.visible = visible;
+                    return true;
+                }
+                if set(&mut model.children, parent_visible && model.visible, id, visible) {
+                    return true;
+                }
+            }
+            false
+        }
+        set(&mut self.models, true, id, visible)
+    }
+
     pub fn iter_models_deep(&self) -> impl Iterator<Item = &Model> {
         self.models
             .iter()
             .chain(self.models.iter().flat_map(|model| model.children.iter_deep()))
     }
 
-    pub fn update_meshes(&mut self, queue: &Queue, material_manager: &MaterialManager) {
-        for mesh in self
-            .models
-            .iter_mut()
-            .flat_map(|model| model.meshes.iter_mut())
-            .filter(|mesh| mesh.dirty())
-        {
-            debug!(
-                "Updating mesh {} with mesh info {:?}",
-                mesh.id,
-                MeshInfo::from_mesh(mesh, material_manager)
-            );
-            self.mesh_buffer
-                .update(queue, &mesh.id, MeshInfo::from_mesh(mesh, material_manager));
-            mesh.set_dirty(false);
+    /**
+    Uploads the mesh infos of dirty meshes, including the ones below other models, merging neighbouring ones into
+    single writes. Once a good part of the meshes is dirty, e.g. while animating, all meshes are uploaded instead, which
+    mostly takes a single write.
+     */
+    pub fn update_meshes(&mut self, writer: impl BufferWriter, material_manager: &MaterialManager) {
+        let mut dirty = vec![];
+        for_each_mesh_mut(&mut self.models, &mut |mesh| {
+            if mesh.dirty() {
+                mesh.set_dirty(false);
+                dirty.push((mesh.id, MeshInfo::from_mesh(mesh, material_manager)));
+            }
+        });
+        if dirty.is_empty() {
+            return;
         }
+        let count = dirty.len();
+        let writes = if count * FULL_UPLOAD_DIVISOR >= self.mesh_buffer.len() {
+            self.update_all_meshes(writer, material_manager)
+        } else {
+            self.mesh_buffer.update_batch(writer, dirty)
+        };
+        debug!("Updated {} dirty meshes with {} writes", count, writes);
     }
 
-    pub fn update_lights(&mut self, queue: &Queue) {
-        for model in self
-            .models
-            .iter_mut()
-            .filter(|model| model.light.is_some() && model.light.as_ref().unwrap().dirty)
-        {
-            let light = model.light.as_mut().unwrap();
-            light.set_dirty(false);
-            self.light_buffer
-                .update(queue, light.index as u64, LightInfo::from(light)); // TODO is light.index what we want here?
+    /**
+    Uploads the mesh infos of all meshes, including the ones below other models and the ones that aren't dirty, e.g.
+    after the materials' shader ids changed. Returns the number of writes.
+     */
+    pub fn update_all_meshes(&mut self, writer: impl BufferWriter, material_manager: &MaterialManager) -> usize {
+        let mesh_infos = self
+            .iter_models_deep()
+            .flat_map(|model| model.meshes.iter())
+            .chain(self.static_batches.iter())
+            .map(|mesh| (mesh.id, MeshInfo::from_mesh(mesh, material_manager)))
+            .collect::<Vec<_>>();
+        self.mesh_buffer.update_batch(writer, mesh_infos)
+    }
+
+    /**
+    Uploads each mesh's transform of the previous frame, including the meshes below other models, which the GPU needs
+    for motion vectors. Must be called exactly once per frame, after all transforms of the frame were updated.
+     */
+    pub fn update_motion(&mut self, writer: impl BufferWriter, material_manager: &MaterialManager) {
+        fn advance(models: &mut [Model], material_manager: &MaterialManager, moved: &mut Vec<(u32, MeshInfo)>) {
+            for model in models.iter_mut() {
+                for mesh in model.meshes.iter_mut() {
+                    let last_frame_transform = mesh.last_frame_transform;
+                    mesh.last_frame_transform = mesh.transform();
+                    if mesh.previous_transform != last_frame_transform {
+                        mesh.previous_transform = last_frame_transform;
+                        moved.push((mesh.id, MeshInfo::from_mesh(mesh, material_manager)));
+                    }
+                }
+                advance(&mut model.children, material_manager, moved);
+            }
+        }
+        let mut moved = vec![];
+        advance(&mut self.models, material_manager, &mut moved);
+        if !moved.is_empty() {
+            self.mesh_buffer.update_batch(writer, moved);
+        }
+    }
+
+    /**
+    Uploads the dirty lights, including the ones below other models.
+     */
+    pub fn update_lights(&mut self, mut writer: impl BufferWriter) {
+        let light_buffer = &mut self.light_buffer;
+        for_each_light_mut(&mut self.models, &mut |light| {
+            if light.dirty {
+                light.set_dirty(false);
+                light_buffer.update(&mut writer, light.index as u64, LightInfo::from(&*light));
+            }
+        });
+    }
+
+    /**
+    Sets the fade of every light to the result of `fade`, marking the ones that changed as dirty so that the next
+    `update_lights` uploads them.
+     */
+    pub fn fade_lights(&mut self, fade: impl Fn(&PointLight) -> f32) {
+        for_each_light_mut(&mut self.models, &mut |light| {
+            let new_fade = fade(light);
+            if light.fade != new_fade {
+                light.fade = new_fade;
+                light.set_dirty(true);
+            }
+        });
+    }
+
+    /**
+    Gives the first `slots` lights that cast shadows and aren't faded out a shadow map slot, in the order of the
+    models with parents before their children, and takes it from all others. Lights whose slot changed are marked as
+    dirty.
+     */
+    pub fn assign_shadow_maps(&mut self, slots: u32) {
+        let mut next = 0;
+        for_each_light_mut(&mut self.models, &mut |light| {
+            let slot = (light.casts_shadows && light.fade > 0.0 && next < slots).then_some(next);
+            if slot.is_some() {
+                next += 1;
+            }
+            if light.shadow_map != slot {
+                light.shadow_map = slot;
+                light.set_dirty(true);
+            }
+        });
+    }
+
+    /**
+    Adds a binding, replacing the one driving the same property of the same model if there is one.
+     */
+    pub fn bind(&mut self, binding: Binding) {
+        self.unbind(binding.model, binding.property);
+        self.bindings.push(binding);
+    }
+
+    pub fn unbind(&mut self, model_id: u32, property: Property) {
+        self.bindings
+            .retain(|binding| binding.model != model_id || binding.property != property);
+    }
+
+    /**
+    Evaluates all bindings and applies their results, marking the changed lights, meshes and materials as dirty.
+    All expressions are evaluated before any result is applied, so that bindings referencing each other's properties
+    don't depend on their order. Bindings whose model or referenced properties don't exist are skipped.
+     */
+    pub fn evaluate_bindings(&mut self, materials: &mut MaterialManager, inputs: &BindingInputs) {
+        let read = |model_id, property: Property| property.read(self.get_model(model_id)?, materials);
+        let results = self
+            .bindings
+            .iter()
+            .filter_map(|binding| {
+                let model = self.get_model(binding.model)?;
+                let distance = world_position(model).distance(inputs.camera_position);
+                let value = binding.expression.evaluate(inputs, distance, &read)?;
+                value.is_finite().then_some((binding.model, binding.property, value))
+            })
+            .collect::<Vec<_>>();
+        for (model_id, property, value) in results {
+            if let Some(model) = self.get_model_mut(model_id) {
+                property.write(model, materials, value);
+            }
         }
     }
 
+    pub fn get_model(&self, id: u32) -> Option<&Model> {
+        self.iter_models_deep().find(|model| model.id == id)
+    }
+
     fn get_model_rec_mut(parent: &mut Model, id: u32) -> Option<&mut Model> {
         if parent.id == id {
             return Some(parent);
@@ -274,11 +752,54 @@ impl Debug for Scene {
     }
 }
 
+// appends the light to the buffer and points it at its element
+fn push_light(
+    light: &mut PointLight,
+    light_buffer: &mut DynamicBufferArray<LightInfo>,
+    device: &Device,
+    queue: &Queue,
+    light_bind_group_layout: &BindGroupLayout,
+) {
+    light.index = light_buffer.len() as usize;
+    light_buffer.push(device, queue, &[LightInfo::from(&*light)], light_bind_group_layout);
+}
+
+// calls `f` with the meshes of every model, parents before their children
+fn for_each_mesh_mut(models: &mut [Model], f: &mut impl FnMut(&mut Mesh)) {
+    for model in models {
+        for mesh in model.meshes.iter_mut() {
+            f(mesh);
+        }
+        for_each_mesh_mut(&mut model.children, f);
+    }
+}
+
+// calls `f` with the light of every model, parents before their children
+fn for_each_light_mut(models: &mut [Model], f: &mut impl FnMut(&mut PointLight)) {
+    for model in models {
+        if let Some(light) = &mut model.light {
+            f(light);
+        }
+        for_each_light_mut(&mut model.children, f);
+    }
+}
+
+fn find_light_mut(models: &mut [Model], index: usize) -> Option<&mut PointLight> {
+    for model in models {
+        if model.light.as_ref().is_some_and(|light| light.index == index) {
+            return model.light.as_mut();
+        }
+        if let Some(light) = find_light_mut(&mut model.children, index) {
+            return Some(light);
+        }
+    }
+    None
+}
+
 pub struct World {
     pub scenes: HashMap<usize, Scene>,
     pub active_scene: usize,
-    pub materials: MaterialManager,
-    pub textures: TextureManager,
+    pub assets: AssetServer,
 }
 
 impl World {
@@ -286,74 +807,131 @@ impl World {
         self.scenes.get(&self.active_scene)
     }
 
+    /**
+    The visible meshes of the active scene with PBR materials.
+     */
     // TODO Optimization: the performance of this must be terrible!
     pub fn pbr_meshes(&self) -> Option<impl Iterator<Item = &Mesh>> {
         self.get_active_scene().map(|scene| {
             scene
-                .iter_meshes()
-                .filter(|mesh| match *self.materials.get_material(mesh.material) {
+                .iter_drawn_meshes()
+                .filter(|mesh| match *self.assets.materials.get_material(mesh.material) {
                     Material::Pbr(_) => true,
                 })
         })
     }
 
-    pub fn update_active_scene(&mut self, queue: &Queue) {
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph::from_world(self)
+    }
+
+    /**
+    How many meshes of all scenes use each material. Materials that no mesh uses are missing.
+     */
+    pub fn material_ref_counts(&self) -> HashMap<MatId, usize> {
+        let mut counts = HashMap::new();
+        for mesh in self
+            .scenes
+            .values()
+            .flat_map(|scene| scene.iter_models_deep())
+            .flat_map(|model| model.meshes.iter())
+        {
+            *counts.entry(mesh.material).or_default() += 1;
+        }
+        counts
+    }
+
+    /**
+    How many materials use each texture. Textures that no material uses are missing.
+     */
+    pub fn texture_ref_counts(&self) -> HashMap<TexId, usize> {
+        let mut counts = HashMap::new();
+        for texture in self.assets.materials.iter().flat_map(|material| material.texture_ids()) {
+            *counts.entry(texture).or_default() += 1;
+        }
+        counts
+    }
+
+    /**
+    Removes the materials that no mesh uses and then the textures that no remaining material uses, except for defaults
+    and assets that `Handle`s refer to. Frees their GPU memory and compacts the material buffer and texture slots, and
+    uploads the mesh infos of all scenes again. Texture arrays have to be rebuilt, see `TextureManager::dirty`.
+     */
+    pub fn purge_unused(&mut self, device: &Device, queue: &Queue, mat_bind_group_layout: &BindGroupLayout) -> Purged {
+        let material_refs = self.material_ref_counts();
+        let materials: Vec<MatId> = self
+            .assets
+            .materials
+            .iter_with_ids()
+            .map(|(id, _)| id)
+            .filter(|id| *id != self.assets.materials.default_material)
+            .filter(|id| !material_refs.contains_key(id) && self.assets.ref_count::<Material>(*id) == 0)
+            .collect();
+        for id in materials.iter() {
+            self.assets.materials.remove_material(*id);
+            self.assets.forget(AssetKey::Material(*id));
+        }
+
+        let texture_refs = self.texture_ref_counts();
+        let textures: Vec<TexId> = self
+            .assets
+            .textures
+            .iter_with_ids()
+            .map(|(id, _)| id)
+            .filter(|id| !self.assets.textures.is_default(*id))
+            .filter(|id| !texture_refs.contains_key(id) && self.assets.ref_count::<Texture>(*id) == 0)
+            .collect();
+        for id in textures.iter() {
+            self.assets.textures.remove_texture(*id);
+            self.assets.forget(AssetKey::Texture(*id));
+        }
+
+        let purged = Purged { materials, textures };
+        if purged.is_empty() {
+            return purged;
+        }
+        // removing textures moves the slots that material infos refer to, so the materials are uploaded either way
+        self.assets
+            .materials
+            .compact(device, queue, mat_bind_group_layout, &self.assets.textures);
+        for scene in self.scenes.values_mut() {
+            scene.update_all_meshes(queue, &self.assets.materials);
+        }
+        info!(
+            "Purged {} unused materials and {} unused textures",
+            purged.materials.len(),
+            purged.textures.len()
+        );
+        purged
+    }
+
+    pub fn update_active_scene(&mut self, mut writer: impl BufferWriter) {
         let Some(scene) = &mut self.scenes.get_mut(&self.active_scene) else {
             return;
         };
-        scene.update_meshes(queue, &self.materials);
-        scene.update_lights(queue);
+        scene.update_meshes(&mut writer, &self.assets.materials);
+        scene.update_lights(writer);
     }
 }
 
-// Data passed to the vertex shader as vertex inputs, contains the vertex positions, normals, tangents, UVs and indices for a mesh
+/**
+Where a mesh's vertices and indices are in the `MeshPool` of its scene, which all meshes of the scene share. The
+vertex shader gets the vertex positions, normals, tangents and UVs from there.
+ */
+#[derive(Debug, Clone, Copy)]
 pub struct VertexInputs {
     pub mesh_id: u32,
-    pub vertex_buffer: SizedBuffer,
-    pub index_buffer: SizedBuffer,
+    pub base_vertex: u32,
+    pub vertex_count: u32,
+    pub first_index: u32,
+    pub index_count: u32,
 }
 
 impl VertexInputs {
-    pub fn from_mesh(
-        mesh_id: u32,
-        vertices: &Vec<Vec3>,
-        normals: &Vec<Vec3>,
-        tangents: &Vec<Vec4>,
-        uvs: &Vec<Vec2>,
-        indices: &[u32],
-        device: &Device,
-    ) -> Self {
-        let mut buffers = vec![];
-        for (position, normal, tangent, uv) in izip!(vertices, normals, tangents, uvs) {
-            buffers.push(PbrVertex {
-                position: (*position).into(),
-                normal: (*normal).into(),
-                tangent: (*tangent).into(),
-                uv: (*uv).into(),
-            });
-        }
-        let vertex_buffer: Buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&buffers),
-            usage: BufferUsages::VERTEX,
-        });
-
-        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices.iter().map(|i| *i as u16).collect::<Vec<u16>>()),
-            usage: BufferUsages::INDEX,
-        });
-
-        Self {
-            mesh_id,
-            vertex_buffer: SizedBuffer {
-                buffer: vertex_buffer,
-                count: vertices.len() as u32,
-            },
-            index_buffer: SizedBuffer {
-                buffer: index_buffer,
-                count: indices.len() as u32,
-            },
-        }
+    /**
+    The mesh's indices in the pool's index buffer, as passed to `draw_indexed` along with `base_vertex`.
+     */
+    pub fn indices(&self) -> std::ops::Range<u32> {
+        self.first_index..self.first_index + self.index_count
     }
 }
@@ -0,0 +1,622 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::managers::MaterialManager;
+use crate::scene::model::Model;
+use crate::{Dirtyable, Material};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/**
+A numeric property of a model that a `Binding` can drive. Material properties apply to the materials of all of the
+model's meshes, which are shared with every other mesh using them.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Property {
+    LightIntensity,
+    LightRange,
+    Translation(Axis),
+    /// euler angle in radians, in XYZ order
+    Rotation(Axis),
+    Scale(Axis),
+    Metallic,
+    Roughness,
+    Occlusion,
+}
+
+impl Property {
+    pub const ALL: [Property; 14] = [
+        Property::LightIntensity,
+        Property::LightRange,
+        Property::Translation(Axis::X),
+        Property::Translation(Axis::Y),
+        Property::Translation(Axis::Z),
+        Property::Rotation(Axis::X),
+        Property::Rotation(Axis::Y),
+        Property::Rotation(Axis::Z),
+        Property::Scale(Axis::X),
+        Property::Scale(Axis::Y),
+        Property::Scale(Axis::Z),
+        Property::Metallic,
+        Property::Roughness,
+        Property::Occlusion,
+    ];
+
+    /**
+    The current value of the property, `None` if the model doesn't have it, e.g. a light property of a model without a
+    light.
+     */
+    pub fn read(&self, model: &Model, materials: &MaterialManager) -> Option<f32> {
+        let (_, rotation, translation) = model.local_transform.to_scale_rotation_translation();
+        match self {
+            Property::LightIntensity => model.light.as_ref().map(|light| light.intensity),
+            Property::LightRange => model.light.as_ref().and_then(|light| light.range),
+            Property::Translation(axis) => Some(translation[axis.index()]),
+            Property::Rotation(axis) => {
+                let (x, y, z) = rotation.to_euler(EulerRot::XYZ);
+                Some([x, y, z][axis.index()])
+            }
+            Property::Scale(axis) => Some(model.scale[axis.index()]),
+            Property::Metallic | Property::Roughness | Property::Occlusion => {
+                let Material::Pbr(pbr) = materials.get_material(model.meshes.first()?.material);
+                Some(match self {
+                    Property::Metallic => pbr.metallic_roughness_factors.x,
+                    Property::Roughness => pbr.metallic_roughness_factors.y,
+                    _ => pbr.occlusion_factor,
+                })
+            }
+        }
+    }
+
+    /**
+    Sets the property, marking whatever it belongs to as dirty if the value changed. Does nothing if the model doesn't
+    have the property.
+     */
+    pub fn write(&self, model: &mut Model, materials: &mut MaterialManager, value: f32) {
+        match self {
+            Property::LightIntensity | Property::LightRange => {
+                let Some(light) = model.light.as_mut() else {
+                    return;
+                };
+                if *self == Property::LightIntensity && light.intensity != value {
+                    light.intensity = value;
+                    light.set_dirty(true);
+                } else if *self == Property::LightRange && light.range != Some(value) {
+                    light.range = Some(value);
+                    light.set_dirty(true);
+                }
+            }
+            Property::Translation(_) | Property::Rotation(_) | Property::Scale(_) => {
+                let parent = model.global_transform() * model.local_transform.inverse();
+                let (scale, rotation, mut translation) = model.local_transform.to_scale_rotation_translation();
+                let (x, y, z) = rotation.to_euler(EulerRot::XYZ);
+                let mut euler = Vec3::new(x, y, z);
+                let channel = match self {
+                    Property::Translation(axis) => &mut translation[axis.index()],
+                    Property::Rotation(axis) => &mut euler[axis.index()],
+                    Property::Scale(axis) => &mut model.scale[axis.index()],
+                    _ => unreachable!(),
+                };
+                if *channel == value {
+                    return;
+                }
+                *channel = value;
+                let rotation = Quat::from_euler(EulerRot::XYZ, euler.x, euler.y, euler.z);
+                model.local_transform = Mat4::from_scale_rotation_translation(scale, rotation, translation);
+                model.update_transforms(parent);
+            }
+            Property::Metallic | Property::Roughness | Property::Occlusion => {
+                for mesh in model.meshes.iter() {
+                    let Material::Pbr(pbr) = materials.get_material_mut(mesh.material);
+                    let field = match self {
+                        Property::Metallic => &mut pbr.metallic_roughness_factors.x,
+                        Property::Roughness => &mut pbr.metallic_roughness_factors.y,
+                        _ => &mut pbr.occlusion_factor,
+                    };
+                    if *field != value {
+                        *field = value;
+                        pbr.dirty = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Display for Property {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let axis = |axis: &Axis| match axis {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        };
+        match self {
+            Property::LightIntensity => write!(f, "intensity"),
+            Property::LightRange => write!(f, "range"),
+            Property::Translation(a) => write!(f, "translation.{}", axis(a)),
+            Property::Rotation(a) => write!(f, "rotation.{}", axis(a)),
+            Property::Scale(a) => write!(f, "scale.{}", axis(a)),
+            Property::Metallic => write!(f, "metallic"),
+            Property::Roughness => write!(f, "roughness"),
+            Property::Occlusion => write!(f, "occlusion"),
+        }
+    }
+}
+
+impl FromStr for Property {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Property::ALL
+            .into_iter()
+            .find(|property| property.to_string() == s)
+            .ok_or(())
+    }
+}
+
+/**
+The position of the model in world space, which the `distance` of its bindings is measured from.
+ */
+pub fn world_position(model: &Model) -> Vec3 {
+    model.global_transform().w_axis.truncate()
+}
+
+/**
+Values that expressions can read besides the properties of models.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct BindingInputs {
+    /// seconds since the start of the renderer
+    pub time: f32,
+    pub delta_time: f32,
+    /// position of the camera in world space
+    pub camera_position: Vec3,
+}
+
+/**
+Drives `property` of the model with the id `model` by evaluating `expression` every frame.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub model: u32,
+    pub property: Property,
+    pub expression: Expression,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// byte offset into the source
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Variable {
+    Time,
+    DeltaTime,
+    Distance,
+    Pi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Abs,
+    Sqrt,
+    Floor,
+    Fract,
+    Min,
+    Max,
+    Clamp,
+    Mix,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "abs" => Function::Abs,
+            "sqrt" => Function::Sqrt,
+            "floor" => Function::Floor,
+            "fract" => Function::Fract,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "clamp" => Function::Clamp,
+            "mix" => Function::Mix,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Function::Min | Function::Max => 2,
+            Function::Clamp | Function::Mix => 3,
+            _ => 1,
+        }
+    }
+
+    fn apply(self, args: &[f32]) -> f32 {
+        match self {
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Abs => args[0].abs(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Floor => args[0].floor(),
+            Function::Fract => args[0].fract(),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+            Function::Clamp => args[0].max(args[1]).min(args[2]),
+            Function::Mix => args[0] + (args[1] - args[0]) * args[2],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f32),
+    Variable(Variable),
+    /// a property of another model
+    Reference(u32, Property),
+    Negate(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+impl Node {
+    fn evaluate(
+        &self,
+        inputs: &BindingInputs,
+        distance: f32,
+        read: &dyn Fn(u32, Property) -> Option<f32>,
+    ) -> Option<f32> {
+        Some(match self {
+            Node::Number(value) => *value,
+            Node::Variable(Variable::Time) => inputs.time,
+            Node::Variable(Variable::DeltaTime) => inputs.delta_time,
+            Node::Variable(Variable::Distance) => distance,
+            Node::Variable(Variable::Pi) => std::f32::consts::PI,
+            Node::Reference(model, property) => read(*model, *property)?,
+            Node::Negate(node) => -node.evaluate(inputs, distance, read)?,
+            Node::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(inputs, distance, read)?;
+                let rhs = rhs.evaluate(inputs, distance, read)?;
+                match op {
+                    '+' => lhs + rhs,
+                    '-' => lhs - rhs,
+                    '*' => lhs * rhs,
+                    '/' => lhs / rhs,
+                    '%' => lhs.rem_euclid(rhs),
+                    _ => lhs.powf(rhs),
+                }
+            }
+            Node::Call(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(inputs, distance, read))
+                    .collect::<Option<Vec<_>>>()?;
+                function.apply(&args)
+            }
+        })
+    }
+}
+
+/**
+An arithmetic expression over numbers, the variables `time`, `dt` (the frame's delta time), `distance` (from the
+camera to the bound model) and `pi`, and properties of models written as `#<model id>.<property>`, e.g.
+`#12.translation.y`. Supports `+ - * / % ^`, parentheses and the functions `sin cos abs sqrt floor fract`, `min max`
+with two and `clamp mix` with three arguments.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            source,
+            position: 0,
+            depth: 0,
+        };
+        let root = parser.expression()?;
+        parser.skip_whitespace();
+        if parser.position < source.len() {
+            return Err(parser.error("Unexpected input"));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /**
+    Evaluates the expression, `read` gives the value of a referenced property. Returns `None` if a referenced property
+    doesn't exist.
+     */
+    pub fn evaluate(
+        &self,
+        inputs: &BindingInputs,
+        distance: f32,
+        read: &dyn Fn(u32, Property) -> Option<f32>,
+    ) -> Option<f32> {
+        self.root.evaluate(inputs, distance, read)
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = ParseError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Self::parse(&source)
+    }
+}
+
+impl From<Expression> for String {
+    fn from(expression: Expression) -> Self {
+        expression.source
+    }
+}
+
+// the deepest nesting of parentheses, negations, powers and function arguments that `Expression::parse` accepts, so
+// that parsing, evaluating and dropping an expression can't overflow the stack
+const MAX_DEPTH: usize = 64;
+
+// recursive descent, from the lowest to the highest precedence
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+    // the number of `unary` calls the parser is currently in, every nested operand goes through it
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.source[self.position..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    // consumes characters while `predicate` holds and returns them
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        let rest = &self.source[start..];
+        self.position += rest.find(|c| !predicate(c)).unwrap_or(rest.len());
+        &self.source[start..self.position]
+    }
+
+    fn expression(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.term()?));
+        }
+        Ok(node)
+    }
+
+    fn term(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.unary()?;
+        while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+            self.position += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("Expression is nested too deeply"));
+        }
+        self.depth += 1;
+        let node = if self.eat('-') {
+            Node::Negate(Box::new(self.unary()?))
+        } else {
+            self.power()?
+        };
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    // right associative and binding tighter than negation, so `-2^2` is -4
+    fn power(&mut self) -> Result<Node, ParseError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Ok(Node::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node, ParseError> {
+        let start = self.position;
+        match self.peek() {
+            Some('(') => {
+                self.position += 1;
+                let node = self.expression()?;
+                if !self.eat(')') {
+                    return Err(self.error("Expected ')'"));
+                }
+                Ok(node)
+            }
+            Some('#') => {
+                self.position += 1;
+                let id = self.take_while(|c| c.is_ascii_digit());
+                let id = id.parse().map_err(|_| self.error("Expected a model id"))?;
+                if !self.eat('.') {
+                    return Err(self.error("Expected '.' followed by a property"));
+                }
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+                let property = name
+                    .parse()
+                    .map_err(|_| self.error(&format!("Unknown property '{}'", name)))?;
+                Ok(Node::Reference(id, property))
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                number.parse().map(Node::Number).map_err(|_| ParseError {
+                    position: start,
+                    message: format!("Invalid number '{}'", number),
+                })
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_').to_string();
+                if let Some(function) = Function::from_name(&name) {
+                    return self.call(function);
+                }
+                Ok(Node::Variable(match name.as_str() {
+                    "time" => Variable::Time,
+                    "dt" => Variable::DeltaTime,
+                    "distance" => Variable::Distance,
+                    "pi" => Variable::Pi,
+                    _ => {
+                        return Err(ParseError {
+                            position: start,
+                            message: format!("Unknown variable '{}'", name),
+                        })
+                    }
+                }))
+            }
+            Some(_) => Err(self.error("Expected a number, variable, function or property")),
+            None => Err(self.error("Unexpected end of expression")),
+        }
+    }
+
+    fn call(&mut self, function: Function) -> Result<Node, ParseError> {
+        if !self.eat('(') {
+            return Err(self.error("Expected '(' after function name"));
+        }
+        let mut args = vec![self.expression()?];
+        while self.eat(',') {
+            args.push(self.expression()?);
+        }
+        if !self.eat(')') {
+            return Err(self.error("Expected ')'"));
+        }
+        if args.len() != function.arity() {
+            return Err(self.error(&format!(
+                "{:?} takes {} argument(s), got {}",
+                function,
+                function.arity(),
+                args.len()
+            )));
+        }
+        Ok(Node::Call(function, args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(source: &str) -> f32 {
+        let inputs = BindingInputs {
+            time: 2.0,
+            delta_time: 0.5,
+            camera_position: Vec3::ZERO,
+        };
+        Expression::parse(source).unwrap().evaluate(&inputs, 10.0, &|_, _| Some(3.0)).unwrap()
+    }
+
+    fn error(source: &str) -> ParseError {
+        Expression::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("-2^2"), -4.0);
+        assert_eq!(evaluate("2^-1"), 0.5);
+        assert_eq!(evaluate("2^3^2"), 512.0);
+        assert_eq!(evaluate("1 + 2 * 3"), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9.0);
+        assert_eq!(evaluate("10 - 4 - 3"), 3.0);
+        assert_eq!(evaluate("-7 % 3"), 2.0);
+    }
+
+    #[test]
+    fn variables_and_references() {
+        assert_eq!(evaluate("time * dt + distance"), 11.0);
+        assert_eq!(evaluate("#4.translation.y * 2"), 6.0);
+        assert_eq!(evaluate("clamp(time, 0, 1) + mix(0, 10, 0.5)"), 6.0);
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(error("min(1)").message, "Min takes 2 argument(s), got 1");
+        assert_eq!(error("sin(1, 2)").message, "Sin takes 1 argument(s), got 2");
+        assert_eq!(error("clamp(1, 2)").position, 11);
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error("1 + foo").position, 4);
+        assert_eq!(error("1 +").position, 3);
+        assert_eq!(error("(1 + 2").position, 6);
+        assert_eq!(error("1 2").position, 2);
+        assert_eq!(error("#x.translation.y").position, 1);
+        assert_eq!(error("#1.colour").message, "Unknown property 'colour'");
+        assert_eq!(error("1..2").position, 0);
+    }
+
+    #[test]
+    fn nesting_depth() {
+        let parentheses = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        let negations = |depth: usize| format!("{}1", "-".repeat(depth));
+        assert!(Expression::parse(&parentheses(MAX_DEPTH - 1)).is_ok());
+        assert!(Expression::parse(&negations(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(error(&parentheses(100_000)).message, "Expression is nested too deeply");
+        assert_eq!(error(&negations(100_000)).message, "Expression is nested too deeply");
+        assert_eq!(error(&"2^".repeat(100_000)).message, "Expression is nested too deeply");
+    }
+}
@@ -0,0 +1,190 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::scene::mesh::Mesh;
+
+/**
+Kinematic controller for player-like models: moves its model by the hook's movement input, pulled down by the scene's
+gravity, and keeps it on top of and outside of the scene's meshes. The model's origin is the bottom of the capsule.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CharacterController {
+    pub radius: f32,
+    /// total height of the capsule, including both caps
+    pub height: f32,
+    /// ledges up to this height are stepped onto instead of blocking
+    pub step_height: f32,
+    /// steepest walkable ground in radians, steeper slopes can't be climbed
+    pub max_slope: f32,
+    /// multiplies the scene's gravity
+    pub gravity_scale: f32,
+    /// velocity along the up axis, from gravity and jumps
+    pub vertical_speed: f32,
+    /// whether the character stood on walkable ground after the last move
+    pub grounded: bool,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            radius: 0.3,
+            height: 1.8,
+            step_height: 0.3,
+            max_slope: 45f32.to_radians(),
+            gravity_scale: 1.0,
+            vertical_speed: 0.0,
+            grounded: false,
+        }
+    }
+}
+
+// iterations of moving and sliding along walls per move
+const SLIDE_ITERATIONS: usize = 3;
+// distance kept to walls and ground, so that the rays of the next move don't start inside them
+const SKIN: f32 = 0.01;
+
+impl CharacterController {
+    /**
+    Launches the character upwards with the given speed, if it stands on the ground.
+     */
+    pub fn jump(&mut self, speed: f32) {
+        if self.grounded {
+            self.vertical_speed = speed;
+            self.grounded = false;
+        }
+    }
+
+    /**
+    Returns the new position of the character after moving it from `position` with the horizontal part of `velocity`
+    for `delta_time` seconds and applying `gravity`. `meshes` are the obstacles, in world space.
+     */
+    pub(crate) fn step(
+        &mut self,
+        position: Vec3,
+        velocity: Vec3,
+        gravity: Vec3,
+        delta_time: f32,
+        meshes: &[&Mesh],
+    ) -> Vec3 {
+        let up = (-gravity).try_normalize().unwrap_or(Vec3::NEG_Y);
+        let mut position = position;
+
+        // horizontal movement, sliding along walls that are hit at knee or head height
+        let mut displacement = (velocity - up * velocity.dot(up)) * delta_time;
+        for _ in 0..SLIDE_ITERATIONS {
+            let distance = displacement.length();
+            let Some(direction) = displacement.try_normalize() else {
+                break;
+            };
+            let heights = [
+                self.step_height + SKIN,
+                (self.height - self.radius).max(self.step_height + SKIN),
+            ];
+            let hit = heights
+                .iter()
+                .filter_map(|height| raycast(meshes, position + up * *height, direction, distance + self.radius))
+                // walkable slopes are climbed by the ground snapping below instead
+                .filter(|hit| hit.normal.dot(up) < self.max_slope.cos())
+                .min_by(|a, b| a.distance.total_cmp(&b.distance));
+            match hit {
+                Some(hit) => {
+                    let allowed = (hit.distance - self.radius - SKIN).max(0.0);
+                    position += direction * allowed;
+                    // continue with what's left of the movement, along the wall
+                    let remaining = direction * (distance - allowed);
+                    let wall = (hit.normal - up * hit.normal.dot(up)).normalize_or_zero();
+                    displacement = remaining - wall * remaining.dot(wall).min(0.0);
+                }
+                None => {
+                    position += displacement;
+                    break;
+                }
+            }
+        }
+
+        // vertical movement
+        if !self.grounded {
+            self.vertical_speed -= gravity.length() * self.gravity_scale * delta_time;
+        }
+        let rise = self.vertical_speed * delta_time;
+        position += up * rise;
+
+        // snap onto walkable ground below the feet (or up to a step above them), and down steps while grounded
+        let probe_start = position + up * self.step_height;
+        let probe_length = self.step_height
+            + if self.grounded {
+                self.step_height
+            } else {
+                (-rise).max(0.0)
+            }
+            + SKIN;
+        let ground =
+            raycast(meshes, probe_start, -up, probe_length).filter(|hit| hit.normal.dot(up) >= self.max_slope.cos());
+        match ground {
+            Some(hit) if self.vertical_speed <= 0.0 => {
+                position = probe_start - up * hit.distance;
+                self.vertical_speed = 0.0;
+                self.grounded = true;
+            }
+            _ => self.grounded = false,
+        }
+        position
+    }
+}
+
+struct RayHit {
+    distance: f32,
+    // world space normal of the hit triangle, facing against the ray
+    normal: Vec3,
+}
+
+// closest intersection of the ray with the triangles of the meshes within max_distance; direction must be normalized
+fn raycast(meshes: &[&Mesh], origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RayHit> {
+    let mut closest: Option<RayHit> = None;
+    for mesh in meshes {
+        // intersect in the mesh's local space; the direction isn't renormalized, so distances stay in world units
+        let inverse = mesh.transform().inverse();
+        let normal_matrix = inverse.transpose();
+        let local_origin = inverse.transform_point3(origin);
+        let local_direction = inverse.transform_vector3(direction);
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+            let Some(distance) = intersect_triangle(local_origin, local_direction, a, b, c) else {
+                continue;
+            };
+            if distance > max_distance || closest.as_ref().is_some_and(|hit| hit.distance <= distance) {
+                continue;
+            }
+            let normal = normal_matrix
+                .transform_vector3((b - a).cross(c - a))
+                .normalize_or_zero();
+            let normal = if normal.dot(direction) > 0.0 { -normal } else { normal };
+            closest = Some(RayHit { distance, normal });
+        }
+    }
+    closest
+}
+
+// Möller–Trumbore, returns the ray parameter of the intersection if it lies in front of the origin
+fn intersect_triangle(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(edge1);
+    let v = direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inverse_determinant;
+    (t >= 0.0).then_some(t)
+}
@@ -0,0 +1,261 @@
+use glam::{Mat4, Vec3};
+use hashbrown::{HashMap, HashSet};
+pub use hecs;
+use hecs::Entity;
+
+use crate::managers::MatId;
+use crate::scene::model::Model;
+use crate::scene::Scene;
+use crate::Dirtyable;
+
+/**
+The id of the model or mesh that an entity mirrors.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SceneId(pub u32);
+
+/**
+Marks the entities of models.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelEntity;
+
+/**
+Marks the entities of meshes, whose `Parent` is their model.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshEntity;
+
+/**
+The model above the entity: the parent model of a model, or the model that a mesh belongs to. Top-level models have
+none. Read only.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+
+/**
+The name of a model. Read only.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name(pub Box<str>);
+
+/**
+The transform of a model relative to its parent. Written back by `push`, which moves the meshes and lights of the
+model and of all models below it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalTransform(pub Mat4);
+
+/**
+The transform of a model or mesh in world space as of the last `pull`. Read only.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlobalTransform(pub Mat4);
+
+/**
+Whether the model or mesh itself is shown, see `Model::visible` and `Mesh::visible`. Written back by `push`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visible(pub bool);
+
+/**
+The material of a mesh. Written back by `push`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshMaterial(pub MatId);
+
+/**
+The point light of a model. Written back by `push`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: Option<f32>,
+}
+
+/**
+An entity component system mirroring a scene, for gameplay systems that query components instead of walking the model
+trees. Every model and every mesh of the scene is an entity; `pull` copies the scene into the components and `push`
+writes the edited components back, marking what changed dirty so that the renderer updates the mesh and light buffers
+in the same frame, e.g. when called from `Hook::update`.
+
+The scene stays the source of truth: models and meshes are still created and deleted with commands, entities spawned
+in the ECS are ignored by `push`, and the next `pull` despawns the entities of deleted models.
+ */
+#[derive(Default)]
+pub struct SceneEcs {
+    pub world: hecs::World,
+    entities: HashMap<u32, Entity>,
+}
+
+impl SceneEcs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_scene(scene: &Scene) -> Self {
+        let mut ecs = Self::new();
+        ecs.pull(scene);
+        ecs
+    }
+
+    /**
+    The entity of the model or mesh with the id, as of the last `pull`.
+     */
+    pub fn entity(&self, id: u32) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    /**
+    Copies the scene into the components, spawning entities for new models and meshes and despawning the ones of
+    removed models and meshes. Call this before running systems whenever the scene may have changed otherwise, e.g.
+    through commands.
+     */
+    pub fn pull(&mut self, scene: &Scene) {
+        let mut seen = HashSet::new();
+        self.pull_models(&scene.models, None, Mat4::IDENTITY, &mut seen);
+        let removed = self
+            .entities
+            .iter()
+            .filter(|(id, _)| !seen.contains(*id))
+            .map(|(id, entity)| (*id, *entity))
+            .collect::<Vec<_>>();
+        for (id, entity) in removed {
+            self.entities.remove(&id);
+            let _ = self.world.despawn(entity);
+        }
+    }
+
+    fn pull_models(
+        &mut self,
+        models: &[Model],
+        parent: Option<Entity>,
+        parent_transform: Mat4,
+        seen: &mut HashSet<u32>,
+    ) {
+        for model in models {
+            seen.insert(model.id);
+            let entity = self.get_or_spawn(model.id);
+            let global_transform = parent_transform * model.local_transform;
+            self.world
+                .insert(
+                    entity,
+                    (
+                        SceneId(model.id),
+                        ModelEntity,
+                        LocalTransform(model.local_transform),
+                        GlobalTransform(global_transform),
+                        Visible(model.visible),
+                    ),
+                )
+                .unwrap();
+            self.set_optional(entity, parent.map(Parent));
+            self.set_optional(entity, model.name.clone().map(Name));
+            self.set_optional(
+                entity,
+                model.light.as_ref().map(|light| Light {
+                    color: light.color,
+                    intensity: light.intensity,
+                    range: light.range,
+                }),
+            );
+            for mesh in model.meshes.iter() {
+                seen.insert(mesh.id);
+                let mesh_entity = self.get_or_spawn(mesh.id);
+                self.world
+                    .insert(
+                        mesh_entity,
+                        (
+                            SceneId(mesh.id),
+                            MeshEntity,
+                            Parent(entity),
+                            GlobalTransform(mesh.global_transform),
+                            Visible(mesh.visible),
+                            MeshMaterial(mesh.material),
+                        ),
+                    )
+                    .unwrap();
+            }
+            self.pull_models(&model.children, Some(entity), global_transform, seen);
+        }
+    }
+
+    /**
+    Writes the components back into the scene, see the components for which ones. Models and meshes without an entity,
+    e.g. ones added since the last `pull`, are left alone.
+     */
+    pub fn push(&self, scene: &mut Scene) {
+        self.push_models(&mut scene.models, Mat4::IDENTITY, true);
+    }
+
+    fn push_models(&self, models: &mut [Model], parent_transform: Mat4, parent_visible: bool) {
+        for model in models.iter_mut() {
+            let Some(entity) = self.entity(model.id) else {
+                continue;
+            };
+            if let Ok(local) = self.world.get::<&LocalTransform>(entity) {
+                if local.0 != model.local_transform {
+                    model.local_transform = local.0;
+                    model.update_transforms(parent_transform);
+                }
+            }
+            if let Ok(visible) = self.world.get::<&Visible>(entity) {
+                if visible.0 != model.visible {
+                    model.visible = visible.0;
+                    model.update_visibility(parent_visible);
+                }
+            }
+            if let (Some(light), Ok(component)) = (&mut model.light, self.world.get::<&Light>(entity)) {
+                if light.color != component.color
+                    || light.intensity != component.intensity
+                    || light.range != component.range
+                {
+                    light.color = component.color;
+                    light.intensity = component.intensity;
+                    light.range = component.range;
+                    light.set_dirty(true);
+                }
+            }
+            for mesh in model.meshes.iter_mut() {
+                let Some(mesh_entity) = self.entity(mesh.id) else {
+                    continue;
+                };
+                if let Ok(material) = self.world.get::<&MeshMaterial>(mesh_entity) {
+                    if material.0 != mesh.material {
+                        mesh.set_material(material.0);
+                    }
+                }
+                if let Ok(visible) = self.world.get::<&Visible>(mesh_entity) {
+                    mesh.visible = visible.0;
+                }
+            }
+            self.push_models(
+                &mut model.children,
+                parent_transform * model.local_transform,
+                parent_visible && model.visible,
+            );
+        }
+    }
+
+    fn get_or_spawn(&mut self, id: u32) -> Entity {
+        match self.entities.get(&id) {
+            Some(entity) if self.world.contains(*entity) => *entity,
+            _ => {
+                let entity = self.world.spawn(());
+                self.entities.insert(id, entity);
+                entity
+            }
+        }
+    }
+
+    // inserts the component, or removes the entity's component of the type if there is none
+    fn set_optional<T: hecs::Component>(&mut self, entity: Entity, component: Option<T>) {
+        match component {
+            Some(component) => self.world.insert_one(entity, component).unwrap(),
+            None => {
+                let _ = self.world.remove_one::<T>(entity);
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum JointKind {
+    /// locks all relative motion, e.g. to weld two bodies together
+    Fixed,
+    /// rotation around `axis` only, e.g. a door hinge. `limits` are the min and max angles in radians
+    Revolute { axis: Vec3, limits: Option<[f32; 2]> },
+    /// translation along `axis` only, e.g. a slider. `limits` are the min and max distances
+    Prismatic { axis: Vec3, limits: Option<[f32; 2]> },
+}
+
+impl JointKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JointKind::Fixed => "Fixed",
+            JointKind::Revolute { .. } => "Revolute",
+            JointKind::Prismatic { .. } => "Prismatic",
+        }
+    }
+}
+
+/**
+A constraint between the model that owns it and another model.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Joint {
+    /// id of the model the owner is attached to
+    pub other: u32,
+    /// attachment point in the owner's local space
+    pub local_anchor: Vec3,
+    /// attachment point in the other model's local space
+    pub other_anchor: Vec3,
+    pub kind: JointKind,
+}
+
+impl Joint {
+    pub fn new(other: u32, kind: JointKind) -> Self {
+        Self {
+            other,
+            local_anchor: Vec3::ZERO,
+            other_anchor: Vec3::ZERO,
+            kind,
+        }
+    }
+}
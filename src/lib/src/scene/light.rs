@@ -12,8 +12,13 @@ pub struct PointLight {
     pub color: Vec3,
     pub intensity: f32,
     pub range: Option<f32>,
+    /// multiplies the intensity, set by the renderer to fade out distant lights
+    pub fade: f32,
+    /// whether the light renders a shadow map, which takes six passes over the scene per frame
+    pub casts_shadows: bool,
+    /// the slot of the light's shadow map, set by the renderer to the lights that get one
+    pub shadow_map: Option<u32>,
     pub buffer: Buffer,
-    // pub shadow_view: Option<Texture>,
 }
 
 impl PointLight {
@@ -25,17 +30,7 @@ impl PointLight {
         range: Option<f32>,
         device: &Device,
     ) -> Self {
-        let buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Point Light Buffer"),
-            contents: bytemuck::cast_slice(&[LightInfo {
-                transform: global_transform.to_cols_array_2d(),
-                color: color.to_array(),
-                intensity,
-                range: range.unwrap_or(10.0),
-                ..Default::default()
-            }]),
-            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-        });
+        let buffer = Self::create_buffer(global_transform, color, intensity, range, device);
         Self {
             dirty: true,
             global_transform,
@@ -43,9 +38,40 @@ impl PointLight {
             color,
             intensity,
             range,
+            fade: 1.0,
+            casts_shadows: false,
+            shadow_map: None,
             buffer,
         }
     }
+
+    /**
+    Recreates the light's buffer, e.g. after the old device was lost, and marks the light dirty.
+     */
+    pub fn recreate_buffer(&mut self, device: &Device) {
+        self.buffer = Self::create_buffer(self.global_transform, self.color, self.intensity, self.range, device);
+        self.dirty = true;
+    }
+
+    fn create_buffer(
+        global_transform: Mat4,
+        color: Vec3,
+        intensity: f32,
+        range: Option<f32>,
+        device: &Device,
+    ) -> Buffer {
+        device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Point Light Buffer"),
+            contents: bytemuck::cast_slice(&[LightInfo {
+                transform: global_transform.to_cols_array_2d(),
+                color: color.to_array(),
+                intensity,
+                range: range.unwrap_or(10.0),
+                ..Default::default()
+            }]),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        })
+    }
 }
 
 impl Dirtyable for PointLight {
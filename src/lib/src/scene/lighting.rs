@@ -0,0 +1,88 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/**
+How the edges of shadows are smoothed. The kernel size is the number of samples per axis, the renderer clamps it to
+`ShadowFilter::MAX_KERNEL_SIZE`, so a fragment takes at most its square of samples per shadow casting light.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ShadowFilter {
+    /// a single sample, hard edges that show the texels of the shadow map
+    Hard,
+    /// percentage-closer filtering: blurs the edges over `kernel_size` texels
+    Pcf { kernel_size: u32 },
+    /// percentage-closer soft shadows: the edges get softer with the distance to the shadow caster, like the shadows
+    /// of a light that is `light_size` world units large
+    Pcss { kernel_size: u32, light_size: f32 },
+}
+
+impl Default for ShadowFilter {
+    fn default() -> Self {
+        Self::Pcf { kernel_size: 3 }
+    }
+}
+
+impl ShadowFilter {
+    pub const MAX_KERNEL_SIZE: u32 = 7;
+
+    pub fn kernel_size(&self) -> u32 {
+        match *self {
+            ShadowFilter::Hard => 1,
+            ShadowFilter::Pcf { kernel_size } | ShadowFilter::Pcss { kernel_size, .. } => {
+                kernel_size.clamp(1, Self::MAX_KERNEL_SIZE)
+            }
+        }
+    }
+}
+
+/**
+Light that reaches surfaces from all around, standing in for light bounced off the surroundings, so that faces no
+light shines on aren't completely black.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Ambient {
+    /// the same color from all directions
+    Flat { color: Vec3 },
+    /// blends from `ground` on surfaces facing down to `sky` on surfaces facing up
+    Hemisphere { sky: Vec3, ground: Vec3 },
+}
+
+impl Default for Ambient {
+    fn default() -> Self {
+        Self::Flat { color: Vec3::ONE }
+    }
+}
+
+impl Ambient {
+    /**
+    The colors of surfaces facing up and down, which are the same for a flat ambient.
+     */
+    pub fn sky_and_ground(&self) -> (Vec3, Vec3) {
+        match *self {
+            Ambient::Flat { color } => (color, color),
+            Ambient::Hemisphere { sky, ground } => (sky, ground),
+        }
+    }
+}
+
+/**
+Per-scene settings of how the scene's lights are rendered.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lighting {
+    pub ambient: Ambient,
+    /// multiplies the ambient colors
+    pub ambient_intensity: f32,
+    pub shadow_filter: ShadowFilter,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            ambient: Ambient::default(),
+            ambient_intensity: 0.001,
+            shadow_filter: ShadowFilter::default(),
+        }
+    }
+}
@@ -41,6 +41,24 @@ impl PbrMaterial {
         }
     }
 
+    pub fn from_desc(desc: PbrMaterialDesc) -> Self {
+        Self {
+            dirty: true,
+            shader_id: 0,
+            name: desc.name.map(String::into_boxed_str),
+            albedo_texture: desc.albedo_texture,
+            albedo: desc.albedo,
+            metallic_roughness_texture: desc.metallic_roughness_texture,
+            metallic_roughness_factors: desc.metallic_roughness_factors,
+            normal_texture: desc.normal_texture,
+            occlusion_texture: desc.occlusion_texture,
+            occlusion_factor: desc.occlusion_factor,
+            emissive_texture: desc.emissive_texture,
+            emissive_factors: desc.emissive_factors,
+            texture_bind_group: None,
+        }
+    }
+
     pub fn create_texture_bind_group(&mut self, device: &Device, layout: &BindGroupLayout, tex_mgr: &TextureManager) {
         let mut entries = vec![];
         for Texture { view, sampler, .. } in [
@@ -69,6 +87,139 @@ impl PbrMaterial {
     pub fn dirty(&self) -> bool {
         self.dirty
     }
+
+    /**
+    Puts the texture into the material's slot of the kind, or empties the slot. Returns false if the material has no
+    slot of the kind. The texture bind group has to be recreated afterwards.
+     */
+    pub fn set_texture(&mut self, slot: TextureKind, texture: Option<TexId>) -> bool {
+        let slot = match slot {
+            TextureKind::Albedo => &mut self.albedo_texture,
+            TextureKind::Normal => &mut self.normal_texture,
+            TextureKind::MetalRoughness => &mut self.metallic_roughness_texture,
+            TextureKind::Occlusion => &mut self.occlusion_texture,
+            TextureKind::Emission => &mut self.emissive_texture,
+            TextureKind::Depth | TextureKind::Other => return false,
+        };
+        *slot = texture;
+        self.dirty = true;
+        true
+    }
+
+    /**
+    Whether both materials have the same factors and textures, i.e. render the same. Names are ignored.
+     */
+    pub fn looks_like(&self, other: &PbrMaterial) -> bool {
+        self.albedo == other.albedo
+            && self.metallic_roughness_factors == other.metallic_roughness_factors
+            && self.occlusion_factor == other.occlusion_factor
+            && self.emissive_factors == other.emissive_factors
+            && self.albedo_texture == other.albedo_texture
+            && self.metallic_roughness_texture == other.metallic_roughness_texture
+            && self.normal_texture == other.normal_texture
+            && self.occlusion_texture == other.occlusion_texture
+            && self.emissive_texture == other.emissive_texture
+    }
+
+    /**
+    The textures assigned to this material's slots, without defaults for empty slots.
+     */
+    pub fn texture_ids(&self) -> impl Iterator<Item = TexId> + '_ {
+        [
+            self.albedo_texture,
+            self.normal_texture,
+            self.metallic_roughness_texture,
+            self.occlusion_texture,
+            self.emissive_texture,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/**
+The properties of a material that is created at runtime, see `MaterialManager::create_material`. Textures that are
+`None` fall back to the default texture of their kind.
+ */
+#[derive(Debug, Clone)]
+pub struct PbrMaterialDesc {
+    pub name: Option<String>,
+    pub albedo: Vec4,
+    pub albedo_texture: Option<TexId>,
+    pub metallic_roughness_factors: Vec2,
+    pub metallic_roughness_texture: Option<TexId>,
+    pub normal_texture: Option<TexId>,
+    pub occlusion_factor: f32,
+    pub occlusion_texture: Option<TexId>,
+    pub emissive_factors: Vec3,
+    pub emissive_texture: Option<TexId>,
+}
+
+impl Default for PbrMaterialDesc {
+    fn default() -> Self {
+        Self {
+            name: None,
+            albedo: Vec4::ONE,
+            albedo_texture: None,
+            metallic_roughness_factors: Vec2::from((0.5, 0.5)),
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_factor: 1.0,
+            occlusion_texture: None,
+            emissive_factors: Vec3::ZERO,
+            emissive_texture: None,
+        }
+    }
+}
+
+impl PbrMaterialDesc {
+    pub fn texture_ids(&self) -> impl Iterator<Item = TexId> + '_ {
+        [
+            self.albedo_texture,
+            self.normal_texture,
+            self.metallic_roughness_texture,
+            self.occlusion_texture,
+            self.emissive_texture,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/**
+Changes to the factors of an existing material, fields that are `None` are left as they are.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct PbrMaterialChanges {
+    pub name: Option<String>,
+    pub albedo: Option<Vec4>,
+    pub metallic_roughness_factors: Option<Vec2>,
+    pub occlusion_factor: Option<f32>,
+    pub emissive_factors: Option<Vec3>,
+}
+
+impl PbrMaterialChanges {
+    /**
+    Applies the changes and marks the material dirty, so that `MaterialManager::update_dirty` uploads them.
+     */
+    pub fn apply(self, material: &mut PbrMaterial) {
+        if let Some(name) = self.name {
+            material.name = Some(name.into_boxed_str());
+        }
+        if let Some(albedo) = self.albedo {
+            material.albedo = albedo;
+        }
+        if let Some(factors) = self.metallic_roughness_factors {
+            material.metallic_roughness_factors = factors;
+        }
+        if let Some(factor) = self.occlusion_factor {
+            material.occlusion_factor = factor;
+        }
+        if let Some(factors) = self.emissive_factors {
+            material.emissive_factors = factors;
+        }
+        material.dirty = true;
+    }
 }
 
 impl Debug for PbrMaterial {
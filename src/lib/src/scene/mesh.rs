@@ -1,12 +1,40 @@
 use std::fmt::{Debug, Formatter};
 
 use glam::{Mat4, Vec2, Vec3, Vec4};
-use rand::Rng;
-use wgpu::Device;
 
 use crate::Dirtyable;
 use crate::managers::MatId;
+use crate::scene::params::ParamBlock;
 use crate::scene::VertexInputs;
+use crate::util::ids::next_id;
+
+/**
+How a mesh is outlined. The width is relative to the mesh's size with the stencil outline and in pixels with the jump
+flooding outline, see `OutlineMode`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outline {
+    pub width: u8,
+    pub color: [u8; 3],
+}
+
+impl Outline {
+    /**
+    The color in the upper three bytes and the width in the lowest byte, as the outline shaders read it.
+     */
+    pub fn packed(&self) -> u32 {
+        (self.color[0] as u32) << 24 | (self.color[1] as u32) << 16 | (self.color[2] as u32) << 8 | self.width as u32
+    }
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self {
+            width: 6,
+            color: [255, 255, 255],
+        }
+    }
+}
 
 pub struct Mesh {
     dirty: bool,
@@ -23,8 +51,19 @@ pub struct Mesh {
     // computed as product of the parent models' local transforms
     pub normal_matrix: Mat4,
     // computed as inverse transpose of the global transform
+    /// where the vertices are in the scene's `MeshPool`, None until the mesh was added to a scene
     pub vertex_inputs: Option<VertexInputs>,
-    outline: bool,
+    // the mesh's own outline, selected and hovered meshes are outlined with the scene's outlines instead
+    outline: Option<Outline>,
+    // the transform (including scale) of the previous frame as uploaded to the GPU, for motion vectors
+    pub previous_transform: Mat4,
+    // the transform of the frame that is currently rendered, becomes the previous transform in the next frame
+    pub(crate) last_frame_transform: Mat4,
+    params: ParamBlock,
+    /// hides the mesh from all render passes and picking when false, see `is_visible`
+    pub visible: bool,
+    // false if a model above the mesh is hidden, see `Model::update_visibility`
+    pub(crate) parent_visible: bool,
 }
 
 impl Mesh {
@@ -36,13 +75,9 @@ impl Mesh {
         material: MatId,
         uvs: Vec<Vec2>,
         global_transform: Mat4,
-        device: &Device,
     ) -> Self {
-        let id = rand::thread_rng().gen_range(0u32..1u32 << 31);
-        let vertex_inputs = VertexInputs::from_mesh(id, &vertices, &normals, &tangents, &uvs, &indices, device);
-
         Self {
-            id,
+            id: next_id(),
             dirty: true,
             vertices,
             indices,
@@ -52,46 +87,109 @@ impl Mesh {
             uvs,
             global_transform,
             normal_matrix: global_transform.inverse().transpose(),
-            vertex_inputs: Some(vertex_inputs),
-            outline: false,
+            vertex_inputs: None,
+            outline: None,
             scale: Vec3::new(1.0, 1.0, 1.0),
+            previous_transform: global_transform,
+            last_frame_transform: global_transform,
+            params: ParamBlock::default(),
+            visible: true,
+            parent_visible: true,
         }
     }
 
-    pub fn clone(&self, device: &Device) -> Self {
-        let vertices = self.vertices.clone();
-        let indices = self.indices.clone();
-        let normals = self.normals.clone();
-        let tangents = self.tangents.clone();
-        let uvs = self.uvs.clone();
-        let id = rand::thread_rng().gen_range(0u32..1u32 << 31);
-        let vertex_inputs = VertexInputs::from_mesh(id, &vertices, &normals, &tangents, &uvs, &indices, device);
+    /**
+    The full transform applied to the mesh's vertices, i.e. the global transform including the mesh's scale.
+     */
+    pub fn transform(&self) -> Mat4 {
+        self.global_transform * Mat4::from_scale(self.scale)
+    }
 
+    /**
+    A copy of the mesh with a new id, which isn't in any scene yet.
+     */
+    pub fn duplicate(&self) -> Self {
         Self {
-            id,
+            id: next_id(),
             dirty: true,
-            vertices,
-            indices,
-            normals,
-            tangents,
-            uvs,
+            vertices: self.vertices.clone(),
+            indices: self.indices.clone(),
+            normals: self.normals.clone(),
+            tangents: self.tangents.clone(),
+            uvs: self.uvs.clone(),
             material: self.material,
             global_transform: self.global_transform,
             normal_matrix: self.normal_matrix,
-            vertex_inputs: Some(vertex_inputs),
-            outline: false,
+            vertex_inputs: None,
+            outline: None,
             scale: self.scale,
+            previous_transform: self.transform(),
+            last_frame_transform: self.transform(),
+            params: self.params.clone(),
+            visible: self.visible,
+            parent_visible: self.parent_visible,
         }
     }
 
-    pub fn set_outline(&mut self, outline: bool) {
+    /**
+    Whether the mesh is drawn, i.e. neither the mesh nor a model above it is hidden.
+     */
+    pub fn is_visible(&self) -> bool {
+        self.visible && self.parent_visible
+    }
+
+    /**
+    Outlines the mesh regardless of the selection, e.g. to mark it as invalid, or stops outlining it with None.
+     */
+    pub fn set_outline(&mut self, outline: Option<Outline>) {
         self.outline = outline;
         self.set_dirty(true);
     }
 
-    pub fn is_outline(&self) -> bool {
+    pub fn outline(&self) -> Option<Outline> {
         self.outline
     }
+
+    pub fn is_outline(&self) -> bool {
+        self.outline.is_some()
+    }
+
+    /**
+    Renders the mesh with another material from now on, once the scene's mesh infos were updated.
+     */
+    pub fn set_material(&mut self, material: MatId) {
+        self.material = material;
+        self.set_dirty(true);
+    }
+
+    pub fn params(&self) -> &ParamBlock {
+        &self.params
+    }
+
+    /**
+    Sets the value of the named shader parameter, defining it if it doesn't exist yet. Returns false if all parameter
+    slots are taken by other names.
+     */
+    pub fn set_param(&mut self, name: &str, value: Vec4) -> bool {
+        let Some(slot) = self.params.define(name) else {
+            return false;
+        };
+        self.set_param_slot(slot, value);
+        true
+    }
+
+    /**
+    Sets the value of a shader parameter slot by its index, regardless of its name.
+     */
+    pub fn set_param_slot(&mut self, slot: usize, value: Vec4) {
+        self.params.set_slot(slot, value);
+        self.set_dirty(true);
+    }
+
+    pub fn remove_param(&mut self, name: &str) {
+        self.params.undefine(name);
+        self.set_dirty(true);
+    }
 }
 
 impl Dirtyable for Mesh {
@@ -1,8 +1,11 @@
+use crate::scene::character::CharacterController;
+use crate::scene::joint::Joint;
 use crate::scene::light::PointLight;
 use crate::scene::mesh::Mesh;
+use crate::scene::physics::{Collider, RigidBody};
 use crate::Dirtyable;
+use crate::util::ids::next_id;
 use glam::{Mat4, Vec3};
-use rand::Rng;
 use std::fmt::{Debug, Formatter};
 
 pub struct Model {
@@ -13,6 +16,20 @@ pub struct Model {
     pub local_transform: Mat4,
     pub scale: Vec3,
     pub light: Option<PointLight>,
+    /// constraints attaching this model to others
+    pub joints: Vec<Joint>,
+    /// makes the model movable by `Scene::move_character`
+    pub character: Option<CharacterController>,
+    /// simulated by the renderer's physics module if it is enabled
+    pub rigid_body: Option<RigidBody>,
+    pub collider: Option<Collider>,
+    /// hides the model's meshes and all models below it when false, see `update_visibility`
+    pub visible: bool,
+    /// promises that the model's meshes rarely move, so that they can be merged with the meshes of other static models
+    /// into fewer draw calls, see `Scene::update_static_batches`. Models below it aren't affected
+    pub is_static: bool,
+    // the model's transform in world space, as computed by the last `update_transforms`
+    world_transform: Mat4,
 }
 
 impl Model {
@@ -24,13 +41,20 @@ impl Model {
         light: Option<PointLight>,
     ) -> Self {
         Self {
-            id: rand::thread_rng().gen_range(0u32..1u32 << 31),
+            id: next_id(),
             meshes,
             name,
             children,
             local_transform,
             scale: Vec3::new(1.0, 1.0, 1.0),
             light,
+            joints: vec![],
+            character: None,
+            rigid_body: None,
+            collider: None,
+            visible: true,
+            is_static: false,
+            world_transform: local_transform,
         }
     }
 
@@ -39,6 +63,7 @@ impl Model {
     Sets dirty to true.
      */
     pub fn update_transforms(&mut self, parent: Mat4) {
+        self.world_transform = parent * self.local_transform;
         for mesh in self.meshes.as_mut_slice() {
             mesh.global_transform = parent * self.local_transform;
             mesh.scale = self.scale;
@@ -46,13 +71,34 @@ impl Model {
             mesh.set_dirty(true);
         }
         for child in self.children.as_mut_slice() {
-            child.update_transforms(self.local_transform);
+            child.update_transforms(parent * self.local_transform);
         }
         if let Some(ref mut light) = self.light {
             light.global_transform = parent * self.local_transform;
             light.set_dirty(true);
         }
     }
+
+    /**
+    The model's transform in world space, as computed by the last `update_transforms`.
+     */
+    pub fn global_transform(&self) -> Mat4 {
+        self.world_transform
+    }
+
+    /**
+    Call this after changing `visible` of this model or a model above it, or after moving the model to another parent.
+    `parent_visible` is whether the parent and all models above it are visible.
+     */
+    pub fn update_visibility(&mut self, parent_visible: bool) {
+        let visible = parent_visible && self.visible;
+        for mesh in self.meshes.iter_mut() {
+            mesh.parent_visible = visible;
+        }
+        for child in self.children.iter_mut() {
+            child.update_visibility(visible);
+        }
+    }
 }
 pub trait DeepIter<T> {
     fn iter_deep(&self) -> Box<dyn Iterator<Item = &T> + '_>;
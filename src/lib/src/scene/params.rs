@@ -0,0 +1,72 @@
+use glam::Vec4;
+
+/**
+A small block of user-defined parameters of a mesh, uploaded with the mesh's `MeshInfo` so that shaders can read them
+as `mesh_infos[index].params`. Each of the `ParamBlock::SLOTS` slots holds a `Vec4` and can be given a name, so that
+hooks and scripts don't need to know the slot indices that a shader uses.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParamBlock {
+    names: [Option<Box<str>>; ParamBlock::SLOTS],
+    values: [Vec4; ParamBlock::SLOTS],
+}
+
+impl ParamBlock {
+    /// number of slots, has to match the `params` array of `MeshInfo` in the shaders
+    pub const SLOTS: usize = 4;
+
+    /**
+    The slot with the given name, or the first unnamed slot which is then given the name. Returns `None` if all slots
+    are named differently.
+     */
+    pub fn define(&mut self, name: &str) -> Option<usize> {
+        if let Some(slot) = self.slot(name) {
+            return Some(slot);
+        }
+        let slot = self.names.iter().position(Option::is_none)?;
+        self.names[slot] = Some(name.into());
+        Some(slot)
+    }
+
+    /**
+    Removes the name of the slot and resets its value.
+     */
+    pub fn undefine(&mut self, name: &str) {
+        if let Some(slot) = self.slot(name) {
+            self.names[slot] = None;
+            self.values[slot] = Vec4::ZERO;
+        }
+    }
+
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .position(|slot_name| slot_name.as_deref() == Some(name))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Vec4> {
+        self.slot(name).map(|slot| self.values[slot])
+    }
+
+    pub fn get_slot(&self, slot: usize) -> Vec4 {
+        self.values[slot]
+    }
+
+    pub(crate) fn set_slot(&mut self, slot: usize, value: Vec4) {
+        self.values[slot] = value;
+    }
+
+    /**
+    The named slots with their values.
+     */
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Vec4)> {
+        self.names
+            .iter()
+            .zip(self.values)
+            .filter_map(|(name, value)| name.as_deref().map(|name| (name, value)))
+    }
+
+    pub(crate) fn to_array(&self) -> [[f32; 4]; ParamBlock::SLOTS] {
+        self.values.map(|value| value.to_array())
+    }
+}
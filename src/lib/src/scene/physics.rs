@@ -0,0 +1,142 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/**
+Per-scene settings of the physics simulation, so that levels can tune it independently.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhysicsSettings {
+    /// acceleration applied to all dynamic bodies; note that the engine's up axis is -Y
+    pub gravity: Vec3,
+    /// duration of a single simulation step in seconds, independent of the frame rate
+    pub timestep: f32,
+    /// upper bound of steps per frame, so that a slow frame doesn't cause even slower frames
+    pub max_steps_per_frame: u32,
+    /// iterations of the constraint solver per step, more are more accurate but slower
+    pub solver_iterations: u32,
+    /// additional solver iterations for friction only
+    pub friction_iterations: u32,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            gravity: Vec3::new(0.0, 9.81, 0.0),
+            timestep: 1.0 / 60.0,
+            max_steps_per_frame: 4,
+            solver_iterations: 4,
+            friction_iterations: 4,
+        }
+    }
+}
+
+impl PhysicsSettings {
+    /**
+    Adds `delta_time` to `accumulator` and returns how many fixed steps to simulate this frame, removing their
+    duration from the accumulator. Time beyond `max_steps_per_frame` steps is dropped.
+     */
+    pub fn steps(&self, accumulator: &mut f32, delta_time: f32) -> u32 {
+        *accumulator += delta_time;
+        let steps = (*accumulator / self.timestep) as u32;
+        let steps_taken = steps.min(self.max_steps_per_frame);
+        *accumulator = if steps > self.max_steps_per_frame {
+            0.0
+        } else {
+            *accumulator - steps_taken as f32 * self.timestep
+        };
+        steps_taken
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BodyKind {
+    /// moved by forces, gravity and collisions, the simulation moves its model
+    Dynamic,
+    /// follows its model, e.g. moved by the hook or an animation, and pushes dynamic bodies out of the way
+    Kinematic,
+    /// never moves, e.g. the ground
+    Fixed,
+}
+
+/**
+Makes a model part of the physics simulation. The simulation moves the models of dynamic bodies, with everything
+attached to them, and keeps the velocities up to date.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RigidBody {
+    pub kind: BodyKind,
+    /// in world units per second
+    pub linear_velocity: Vec3,
+    /// in radians per second around each axis
+    pub angular_velocity: Vec3,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    /// multiplies the scene's gravity
+    pub gravity_scale: f32,
+}
+
+impl RigidBody {
+    pub fn new(kind: BodyKind) -> Self {
+        Self {
+            kind,
+            linear_velocity: Vec3::ZERO,
+            angular_velocity: Vec3::ZERO,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            gravity_scale: 1.0,
+        }
+    }
+
+    /**
+    Whether the bodies behave the same, i.e. only their velocities differ.
+     */
+    pub fn same_settings(&self, other: &RigidBody) -> bool {
+        self.kind == other.kind
+            && self.linear_damping == other.linear_damping
+            && self.angular_damping == other.angular_damping
+            && self.gravity_scale == other.gravity_scale
+    }
+}
+
+/**
+Shapes of colliders in the model's local space. They are scaled along with the model. Capsules are aligned with the y
+axis, their `half_height` excludes the caps.
+
+The other shapes are generated from the model's own meshes, not those of its children, when the collider is created:
+`ConvexHull` wraps all vertices, `TriMesh` is exactly the triangles and best for fixed obstacles like imported levels,
+and `ConvexDecomposition` is a simplified version of the meshes made of a few convex parts, which is slow to generate
+but suits dynamic bodies with concave meshes.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ColliderShape {
+    Ball { radius: f32 },
+    Cuboid { half_extents: Vec3 },
+    Capsule { half_height: f32, radius: f32 },
+    ConvexHull,
+    TriMesh,
+    ConvexDecomposition,
+}
+
+/**
+The shape that a model collides with. It belongs to the model's rigid body, models without one are fixed obstacles.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    /// mass per volume, determines the mass of dynamic bodies
+    pub density: f32,
+    pub friction: f32,
+    /// bounciness from 0 to 1
+    pub restitution: f32,
+}
+
+impl Collider {
+    pub fn new(shape: ColliderShape) -> Self {
+        Self {
+            shape,
+            density: 1.0,
+            friction: 0.5,
+            restitution: 0.0,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+/**
+Darkens the image towards its corners.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Vignette {
+    pub enabled: bool,
+    /// how dark the corners get, 0 to 1
+    pub intensity: f32,
+    /// distance from the center (relative to the half diagonal) at which the darkening starts
+    pub radius: f32,
+    /// width of the transition from the unaffected center to the corners
+    pub softness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 0.5,
+            radius: 0.75,
+            softness: 0.45,
+        }
+    }
+}
+
+/**
+Animated noise resembling film grain.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FilmGrain {
+    pub enabled: bool,
+    pub intensity: f32,
+    /// size of a grain in pixels
+    pub size: f32,
+}
+
+impl Default for FilmGrain {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 0.05,
+            size: 1.5,
+        }
+    }
+}
+
+/**
+Splits the color channels towards the edges of the image, like a cheap lens would.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChromaticAberration {
+    pub enabled: bool,
+    /// offset of the red and blue channels at the corners, relative to the image size
+    pub strength: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.005,
+        }
+    }
+}
+
+/**
+Screen-space effects emulating a physical camera, applied in the order chromatic aberration, vignette, grain.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct PostEffects {
+    pub chromatic_aberration: ChromaticAberration,
+    pub vignette: Vignette,
+    pub grain: FilmGrain,
+}
+
+impl PostEffects {
+    pub fn is_active(&self) -> bool {
+        self.chromatic_aberration.enabled || self.vignette.enabled || self.grain.enabled
+    }
+}
@@ -0,0 +1,74 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use glam::{Mat3, Vec3};
+use serde::{Deserialize, Serialize};
+
+/**
+What is visible behind the scene's geometry.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Background {
+    /// A solid (linear) color
+    Color([f32; 3]),
+    /// A procedural, physically-based sky
+    Sky(Sky),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color([0.0; 3])
+    }
+}
+
+/**
+Settings of the procedural sky (Preetham et al. 1999). The sun position is derived from the time of day.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sky {
+    /// in hours, 0.0..24.0; the sun rises at 6 and sets at 18
+    pub time_of_day: f32,
+    /// haziness of the atmosphere, sensible values are 2.0 (clear) to 10.0 (hazy)
+    pub turbidity: f32,
+    /// rotation of the sun's path around the up axis, in radians
+    pub azimuth: f32,
+    /// tilt of the sun's path away from the zenith, in radians
+    pub latitude: f32,
+    /// scales the sky luminance before tone mapping
+    pub exposure: f32,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self {
+            time_of_day: 10.0,
+            turbidity: 3.0,
+            azimuth: 0.0,
+            latitude: 0.5,
+            exposure: 0.05,
+        }
+    }
+}
+
+impl Sky {
+    /**
+    Normalized world-space direction pointing towards the sun. Note that the engine's up axis is -Y.
+    Once directional lights exist, this should drive the sun light.
+     */
+    pub fn sun_direction(&self) -> Vec3 {
+        let day = self.time_of_day / 24.0 * TAU - FRAC_PI_2;
+        let y_up = Vec3::new(
+            day.cos(),
+            day.sin() * self.latitude.cos(),
+            day.sin() * self.latitude.sin(),
+        );
+        let rotated = Mat3::from_rotation_y(self.azimuth) * y_up;
+        Vec3::new(rotated.x, -rotated.y, rotated.z).normalize()
+    }
+
+    /**
+    Whether the sun is above the horizon.
+     */
+    pub fn is_day(&self) -> bool {
+        self.sun_direction().y < 0.0
+    }
+}
@@ -0,0 +1,90 @@
+use glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use hashbrown::HashMap;
+
+use crate::managers::MatId;
+use crate::scene::mesh::Mesh;
+use crate::scene::params::ParamBlock;
+
+// indices are uploaded as u16, so a batch can't address more vertices than this
+const MAX_BATCH_VERTICES: usize = u16::MAX as usize + 1;
+
+/**
+Whether the mesh can be merged with others: it has to fit into a batch, and its shader parameters are lost when it's
+merged, so it mustn't have any.
+ */
+pub(crate) fn is_batchable(mesh: &Mesh) -> bool {
+    mesh.vertices.len() <= MAX_BATCH_VERTICES && *mesh.params() == ParamBlock::default()
+}
+
+/**
+Merges batchable meshes into as few meshes as possible, one or more per material. The vertices are transformed into
+world space, so the merged meshes are drawn with the identity transform.
+ */
+pub(crate) fn merge<'a>(meshes: impl Iterator<Item = &'a Mesh>) -> Vec<Mesh> {
+    let mut by_material: HashMap<MatId, Vec<&Mesh>> = HashMap::new();
+    for mesh in meshes {
+        by_material.entry(mesh.material).or_default().push(mesh);
+    }
+    let mut batches = vec![];
+    for (material, meshes) in by_material {
+        let mut batch = Batch::default();
+        for mesh in meshes {
+            if batch.vertices.len() + mesh.vertices.len() > MAX_BATCH_VERTICES {
+                batches.push(std::mem::take(&mut batch).into_mesh(material));
+            }
+            batch.append(mesh);
+        }
+        batches.push(batch.into_mesh(material));
+    }
+    batches
+}
+
+#[derive(Default)]
+struct Batch {
+    vertices: Vec<Vec3>,
+    indices: Vec<u32>,
+    normals: Vec<Vec3>,
+    tangents: Vec<Vec4>,
+    uvs: Vec<Vec2>,
+}
+
+impl Batch {
+    fn append(&mut self, mesh: &Mesh) {
+        let transform = mesh.transform();
+        let normal_matrix = transform.inverse().transpose();
+        let first = self.vertices.len() as u32;
+        self.vertices
+            .extend(mesh.vertices.iter().map(|vertex| transform.transform_point3(*vertex)));
+        self.normals.extend(
+            mesh.normals
+                .iter()
+                .map(|normal| normal_matrix.transform_vector3(*normal).normalize_or_zero()),
+        );
+        // the sign in w is kept, it only depends on the UVs
+        self.tangents.extend(mesh.tangents.iter().map(|tangent| {
+            transform
+                .transform_vector3(tangent.xyz())
+                .normalize_or_zero()
+                .extend(tangent.w)
+        }));
+        self.uvs.extend(mesh.uvs.iter().copied());
+        // the attributes of all meshes have to line up, even if a mesh lacks some of them
+        let len = self.vertices.len();
+        self.normals.resize(len, Vec3::Z);
+        self.tangents.resize(len, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        self.uvs.resize(len, Vec2::ZERO);
+        self.indices.extend(mesh.indices.iter().map(|index| first + index));
+    }
+
+    fn into_mesh(self, material: MatId) -> Mesh {
+        Mesh::from(
+            self.vertices,
+            self.indices,
+            self.normals,
+            self.tangents,
+            material,
+            self.uvs,
+            Mat4::IDENTITY,
+        )
+    }
+}
@@ -0,0 +1,53 @@
+use glam::{Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+
+/**
+A flat, animated water surface that reflects and refracts the scene's geometry.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Water {
+    /// center of the surface; its y coordinate is the water level
+    pub position: Vec3,
+    /// extent of the surface along the x and z axes
+    pub size: Vec2,
+    /// (linear) color that the refracted scene is tinted with
+    pub color: [f32; 3],
+    /// how much the waves distort the reflection and refraction
+    pub wave_strength: f32,
+    pub wave_speed: f32,
+    /// size of the waves, larger values mean fewer, wider waves
+    pub wave_scale: f32,
+}
+
+impl Default for Water {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            size: Vec2::new(1000.0, 1000.0),
+            color: [0.1, 0.3, 0.4],
+            wave_strength: 0.02,
+            wave_speed: 1.0,
+            wave_scale: 50.0,
+        }
+    }
+}
+
+impl Water {
+    pub fn level(&self) -> f32 {
+        self.position.y
+    }
+
+    /**
+    Plane `(n, d)` with `dot(n, p) + d >= 0` for all points `p` above the water. Note that the engine's up axis is -Y.
+     */
+    pub fn clip_plane_above(&self) -> Vec4 {
+        Vec4::new(0.0, -1.0, 0.0, self.level())
+    }
+
+    /**
+    Plane `(n, d)` with `dot(n, p) + d >= 0` for all points `p` below the water.
+     */
+    pub fn clip_plane_below(&self) -> Vec4 {
+        -self.clip_plane_above()
+    }
+}
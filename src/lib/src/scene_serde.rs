@@ -7,7 +7,7 @@ use std::sync::Arc;
 
 use crate::scene::{PbrMaterial, MaterialManager, Mesh, Model, Scene, Texture, TextureManager, World};
 use crate::shader_types::{LightInfo, MaterialInfo, MeshInfo};
-use crate::util::extract_image_to_file;
+use crate::util::cache::AssetCache;
 use glam::{Mat4, Vec2, Vec3, Vec4};
 use image::{DynamicImage, ImageFormat};
 use serde::{Deserialize, Serialize};
@@ -16,7 +16,7 @@ use serde::{Deserialize, Serialize};
 pub struct TextureSerde {
     pub id: u32,
     pub name: Option<Box<str>>,
-    pub img_path: PathBuf, // relative to run directory
+    pub img_path: PathBuf, // relative to the asset cache's root
 }
 
 impl From<Rc<Texture>> for TextureSerde {
@@ -35,6 +35,7 @@ impl Texture {
         allocator: Arc<StandardMemoryAllocator>,
         cmd_buf_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
         root_dir: &Path,
+        cache: &mut AssetCache,
     ) -> Texture {
         let img = DynamicImage::from(
             image::open(root_dir.join(value.img_path.as_path()))
@@ -46,11 +47,9 @@ impl Texture {
                 })
                 .to_rgba8(),
         );
-        let path = extract_image_to_file(
-            value.img_path.file_stem().unwrap().to_str().unwrap(),
-            &img,
-            ImageFormat::from_path(value.img_path.as_path()).unwrap(),
-        );
+        let path = cache
+            .insert_image(&img, ImageFormat::from_path(value.img_path.as_path()).unwrap())
+            .expect("Couldn't cache image");
 
         let (width, height) = (img.width(), img.height());
         let texture = create_texture(
@@ -1,7 +1,9 @@
-use crate::managers::MaterialManager;
+use crate::managers::{MaterialManager, TextureManager};
 use crate::scene::light::PointLight;
 use crate::scene::material::PbrMaterial;
 use crate::scene::mesh::Mesh;
+use crate::scene::params::ParamBlock;
+use crate::texture::TextureKind;
 use glam::Mat4;
 
 pub trait Vertex<const ATTRIB_COUNT: usize> {
@@ -25,6 +27,23 @@ impl Vertex<1> for BasicVertex {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColoredVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+impl Vertex<2> for ColoredVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ColoredVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct PbrVertex {
@@ -54,7 +73,10 @@ pub struct CameraUniform {
     pub unproj_view: [[f32; 4]; 4], // s64 o0
     pub view_position: [f32; 4],  // s16 o64
     pub num_lights: u32,          // s4 o80
-    pub padding: [u32; 3],        // total size: 96
+    pub gamma: f32,               // s4 o84
+    pub brightness: f32,          // s4 o88
+    pub near_depth: f32,          // s4 o92, depth of the near plane, 1 for reverse-Z
+    pub prev_proj_view: [[f32; 4]; 4], // s64 o96, total size: 160
 }
 
 #[repr(C)]
@@ -64,28 +86,25 @@ pub struct MaterialInfo {
     pub emission_factors: [f32; 3],        // s12 o16
     pub occlusion_factor: f32,             // s4 o28
     pub metal_roughness_factors: [f32; 2], // s8 o32
-    padding3: [f32; 2],                    // total size: 48
-}
-
-impl From<&PbrMaterial> for MaterialInfo {
-    fn from(material: &PbrMaterial) -> Self {
-        Self {
-            albedo: material.albedo.to_array(),
-            emission_factors: material.emissive_factors.into(),
-            occlusion_factor: material.occlusion_factor,
-            metal_roughness_factors: material.metallic_roughness_factors.into(),
-            ..Default::default()
-        }
-    }
+    // slots of the albedo, normal, metal roughness, occlusion and emission textures in bindless texture arrays
+    pub texture_slots: [u32; 5],           // s20 o40
+    padding3: u32,                         // total size: 64
 }
 
-impl From<&mut PbrMaterial> for MaterialInfo {
-    fn from(material: &mut PbrMaterial) -> Self {
+impl MaterialInfo {
+    pub fn new(material: &PbrMaterial, textures: &TextureManager) -> Self {
         Self {
             albedo: material.albedo.to_array(),
             emission_factors: material.emissive_factors.into(),
             occlusion_factor: material.occlusion_factor,
             metal_roughness_factors: material.metallic_roughness_factors.into(),
+            texture_slots: [
+                textures.slot_or_default(&material.albedo_texture, TextureKind::Albedo),
+                textures.slot_or_default(&material.normal_texture, TextureKind::Normal),
+                textures.slot_or_default(&material.metallic_roughness_texture, TextureKind::MetalRoughness),
+                textures.slot_or_default(&material.occlusion_texture, TextureKind::Occlusion),
+                textures.slot_or_default(&material.emissive_texture, TextureKind::Emission),
+            ],
             ..Default::default()
         }
     }
@@ -98,7 +117,8 @@ impl Default for MaterialInfo {
             metal_roughness_factors: [0.5; 2],
             emission_factors: [0.0; 3],
             occlusion_factor: 1.0,
-            padding3: [0.0; 2],
+            texture_slots: [0; 5],
+            padding3: 0,
         }
     }
 }
@@ -112,6 +132,8 @@ pub struct MeshInfo {
     pub normal_matrix: [[f32; 4]; 4],   // s36 o80
     pub scale: [f32; 3],                 // s12 o80
     _align2: u32,
+    pub prev_model_transform: [[f32; 4]; 4], // s64 o160, including the scale
+    pub params: [[f32; 4]; ParamBlock::SLOTS], // s64 o224, total size: 288
 }
 impl MeshInfo {
     pub fn from_mesh(mesh: &Mesh, material_manager: &MaterialManager) -> Self {
@@ -122,18 +144,34 @@ impl MeshInfo {
             scale: mesh.scale.to_array(),
             _align2: 0,
             normal_matrix: mesh.normal_matrix.to_cols_array_2d(),
+            prev_model_transform: mesh.previous_transform.to_cols_array_2d(),
+            params: mesh.params().to_array(),
         }
     }
 }
 
 #[repr(C)]
-#[derive(Default, Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct LightInfo {
     pub transform: [[f32; 4]; 4], // s64 o0
     pub color: [f32; 3],          // s12 o64
     pub intensity: f32,           // s4 o76
     pub range: f32,               // s4 o80
-    pub padding4: [f32; 3],       // total size: 96
+    pub shadow_map: i32,          // s4 o84, -1 without a shadow map
+    pub padding4: [f32; 2],       // total size: 96
+}
+
+impl Default for LightInfo {
+    fn default() -> Self {
+        Self {
+            transform: Default::default(),
+            color: Default::default(),
+            intensity: 0.0,
+            range: 0.0,
+            shadow_map: -1,
+            padding4: Default::default(),
+        }
+    }
 }
 
 impl From<&PointLight> for LightInfo {
@@ -141,8 +179,9 @@ impl From<&PointLight> for LightInfo {
         Self {
             transform: light.global_transform.to_cols_array_2d(),
             color: light.color.to_array(),
-            intensity: light.intensity,
+            intensity: light.intensity * light.fade,
             range: light.range.unwrap_or(1.0),
+            shadow_map: light.shadow_map.map_or(-1, |slot| slot as i32),
             ..Default::default()
         }
     }
@@ -153,8 +192,9 @@ impl From<&mut PointLight> for LightInfo {
         Self {
             transform: light.global_transform.to_cols_array_2d(),
             color: light.color.to_array(),
-            intensity: light.intensity,
+            intensity: light.intensity * light.fade,
             range: light.range.unwrap_or(1.0),
+            shadow_map: light.shadow_map.map_or(-1, |slot| slot as i32),
             ..Default::default()
         }
     }
@@ -0,0 +1,102 @@
+use std::num::NonZeroU64;
+
+use wgpu::util::StagingBelt;
+use wgpu::{Buffer, BufferAddress, CommandEncoder, Device, Queue};
+
+// the smallest staging buffer the belt allocates, larger writes get buffers of their own
+const CHUNK_SIZE: BufferAddress = 256 * 1024;
+
+/**
+Something that buffer updates can be written to, either the queue directly or a `Staging` belt. Functions that update
+buffers take `impl BufferWriter`, so that `&Queue` can be passed for one-off updates and a `StagedWriter` for the many
+small updates of a frame.
+ */
+pub trait BufferWriter {
+    fn write_buffer(&mut self, buffer: &Buffer, offset: BufferAddress, data: &[u8]);
+}
+
+impl BufferWriter for &Queue {
+    fn write_buffer(&mut self, buffer: &Buffer, offset: BufferAddress, data: &[u8]) {
+        Queue::write_buffer(self, buffer, offset, data);
+    }
+}
+
+impl<W: BufferWriter> BufferWriter for &mut W {
+    fn write_buffer(&mut self, buffer: &Buffer, offset: BufferAddress, data: &[u8]) {
+        (**self).write_buffer(buffer, offset, data);
+    }
+}
+
+/**
+Collects buffer updates in mapped staging buffers that are reused across frames, and copies them into their buffers
+with a single command buffer on `submit`. Unlike `Queue::write_buffer`, this doesn't allocate for every update.
+
+The copies run after everything written with `Queue::write_buffer` before the same submission, so writing a range both
+ways between submits leaves the staged data. Buffers that were written to mustn't be reallocated or have their
+elements moved before `submit`, as the copies go to the old buffer or index.
+ */
+pub struct Staging {
+    belt: StagingBelt,
+    encoder: Option<CommandEncoder>,
+}
+
+impl Staging {
+    pub fn new() -> Self {
+        Self {
+            belt: StagingBelt::new(CHUNK_SIZE),
+            encoder: None,
+        }
+    }
+
+    /**
+    A writer that stages updates in this belt until the next `submit`.
+     */
+    pub fn writer<'a>(&'a mut self, device: &'a Device) -> StagedWriter<'a> {
+        StagedWriter { staging: self, device }
+    }
+
+    /**
+    Copies the staged updates into their buffers. Does nothing if nothing was staged since the last submit.
+     */
+    pub fn submit(&mut self, queue: &Queue) {
+        let Some(encoder) = self.encoder.take() else {
+            return;
+        };
+        self.belt.finish();
+        queue.submit(std::iter::once(encoder.finish()));
+        // the staging buffers are mapped again once the GPU is done with the copies
+        self.belt.recall();
+    }
+}
+
+impl Default for Staging {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+See `Staging::writer`.
+ */
+pub struct StagedWriter<'a> {
+    staging: &'a mut Staging,
+    device: &'a Device,
+}
+
+impl BufferWriter for StagedWriter<'_> {
+    fn write_buffer(&mut self, buffer: &Buffer, offset: BufferAddress, data: &[u8]) {
+        let Some(size) = NonZeroU64::new(data.len() as u64) else {
+            return;
+        };
+        // copies between buffers have to be 4 byte aligned, which all shader types are
+        debug_assert_eq!(size.get() % wgpu::COPY_BUFFER_ALIGNMENT, 0);
+        let Staging { belt, encoder } = &mut *self.staging;
+        let encoder = encoder.get_or_insert_with(|| {
+            self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Staged buffer updates"),
+            })
+        });
+        belt.write_buffer(encoder, buffer, offset, size, self.device)
+            .copy_from_slice(data);
+    }
+}
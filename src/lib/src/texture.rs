@@ -1,7 +1,8 @@
+use std::sync::Arc;
+
 use anyhow::*;
-use image::GenericImageView;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TextureKind {
     Albedo,
     Normal,
@@ -19,6 +20,9 @@ pub struct Texture {
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
     pub kind: TextureKind,
+    /// the pixels the texture was created from, kept to recreate it on another device. `None` for render targets,
+    /// which their owners recreate
+    pub source: Option<Arc<image::RgbaImage>>,
 }
 
 impl Texture {
@@ -29,8 +33,18 @@ impl Texture {
         label: Option<&str>,
         texture_kind: TextureKind,
     ) -> Result<Self> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        let rgba = Arc::new(img.to_rgba8());
+        Ok(Self::from_rgba(device, queue, rgba, label, texture_kind))
+    }
+
+    pub(crate) fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: Arc<image::RgbaImage>,
+        label: Option<&str>,
+        texture_kind: TextureKind,
+    ) -> Self {
+        let dimensions = rgba.dimensions();
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -45,7 +59,8 @@ impl Texture {
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8Unorm,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
+            // for `srgb_view`
+            view_formats: &[wgpu::TextureFormat::Rgba8UnormSrgb],
         });
 
         queue.write_texture(
@@ -75,15 +90,115 @@ impl Texture {
             ..Default::default()
         });
 
-        Ok(Self {
+        Self {
             id: None,
             name: label.map(|s| s.to_string()),
             texture,
             view,
             sampler,
             kind: texture_kind,
-        })
+            source: Some(rgba),
+        }
+    }
+
+    /**
+    Hash of the source pixels and their dimensions, identical images have the same hash. `None` for textures that
+    weren't created from an image.
+    */
+    pub fn content_hash(&self) -> Option<blake3::Hash> {
+        self.source.as_deref().map(image_hash)
+    }
+
+    /**
+    Recreates the texture on another device from its source pixels, e.g. after the old device was lost. Returns false
+    if the texture has no source pixels.
+    */
+    pub fn recreate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        let Some(source) = self.source.clone() else {
+            return false;
+        };
+        let recreated = Self::from_rgba(device, queue, source, self.name.as_deref(), self.kind);
+        self.texture = recreated.texture;
+        self.view = recreated.view;
+        self.sampler = recreated.sampler;
+        true
     }
+
+    /**
+    A view that decodes the pixels as sRGB, e.g. to show the texture the way image viewers do. `None` for textures that
+    weren't created from an image.
+    */
+    pub fn srgb_view(&self) -> Option<wgpu::TextureView> {
+        self.source.as_ref()?;
+        Some(self.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(wgpu::TextureFormat::Rgba8UnormSrgb),
+            ..Default::default()
+        }))
+    }
+
+    /**
+    Creates a 1x1 texture filled with a single color, e.g. for use as a fallback when a material slot is empty.
+    */
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: [u8; 4],
+        label: Option<&str>,
+        texture_kind: TextureKind,
+    ) -> Result<Self> {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)));
+        Self::from_image(device, queue, &img, label, texture_kind)
+    }
+
+    /**
+    Creates a texture that can be rendered into and sampled from afterwards, e.g. for offscreen passes.
+    */
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            id: None,
+            name: Some(label.into()),
+            texture,
+            view,
+            sampler,
+            kind: TextureKind::Other,
+            source: None,
+        }
+    }
+
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
     pub fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32, label: &str) -> Self {
         let size = wgpu::Extent3d {
@@ -124,6 +239,16 @@ impl Texture {
             view,
             sampler,
             kind: TextureKind::Depth,
+            source: None,
         }
     }
 }
+
+pub(crate) fn image_hash(rgba: &image::RgbaImage) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+    // the same pixels can make up images of different dimensions
+    hasher.update(&rgba.width().to_le_bytes());
+    hasher.update(&rgba.height().to_le_bytes());
+    hasher.update(rgba.as_raw());
+    hasher.finalize()
+}
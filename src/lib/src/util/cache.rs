@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageFormat};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+const INDEX_FILE: &str = "index.json";
+/// default for `AssetCache::max_size`
+pub const DEFAULT_MAX_SIZE: u64 = 1 << 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// relative to the cache's root
+    path: PathBuf,
+    size: u64,
+    /// seconds since the Unix epoch
+    last_used: u64,
+}
+
+/**
+Files kept on disk by the hash of their contents, e.g. images extracted from imported glTF files. The same contents
+always end up at the same path relative to the cache's root, so that serialized scenes can refer to them and importing
+the same file twice doesn't store it twice.
+
+An index file in the root records the size and last use of each file. Once the files take up more than `max_size`
+bytes, the least recently used ones are evicted. An evicted file is stored at the same path again when its contents are
+inserted the next time.
+ */
+pub struct AssetCache {
+    root: PathBuf,
+    /// by the hex encoded hash of the contents
+    entries: HashMap<String, Entry>,
+    pub max_size: u64,
+}
+
+impl AssetCache {
+    /**
+    The cache in `cache/` in the working directory, or in the directory in the `WORKING_DIR` environment variable.
+     */
+    pub fn default_root() -> PathBuf {
+        match std::env::var("WORKING_DIR") {
+            Ok(cwd) => PathBuf::from(cwd).join("cache"),
+            Err(_) => PathBuf::from("cache"),
+        }
+    }
+
+    /**
+    Opens the cache in `root`, creating the directory if it doesn't exist. Entries of the index whose files were
+    deleted in the meantime are dropped.
+     */
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root).with_context(|| format!("Couldn't create the asset cache in {:?}", root))?;
+        let entries: HashMap<String, Entry> = match fs::read(root.join(INDEX_FILE)) {
+            Ok(index) => serde_json::from_slice(&index).unwrap_or_else(|e| {
+                warn!(
+                    "The index of the asset cache in {:?} is corrupt, starting over: {}",
+                    root, e
+                );
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        let mut cache = Self {
+            root,
+            entries,
+            max_size: DEFAULT_MAX_SIZE,
+        };
+        cache.entries.retain(|_, entry| cache.root.join(&entry.path).is_file());
+        Ok(cache)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /**
+    The combined size of the cached files in bytes.
+     */
+    pub fn size(&self) -> u64 {
+        self.entries.values().map(|entry| entry.size).sum()
+    }
+
+    /**
+    Stores the bytes unless the same bytes are stored already, and returns their path relative to the root. The
+    extension is appended to the file name, e.g. `png`.
+     */
+    pub fn insert(&mut self, bytes: &[u8], extension: &str) -> Result<PathBuf> {
+        let hash = blake3::hash(bytes).to_hex().to_string();
+        let now = now();
+        if let Some(entry) = self.entries.get_mut(&hash) {
+            entry.last_used = now;
+            return Ok(entry.path.clone());
+        }
+        // two levels, so that no directory gets too large
+        let path = Path::new(&hash[..2]).join(format!("{}.{}", hash, extension));
+        let absolute = self.root.join(&path);
+        fs::create_dir_all(absolute.parent().unwrap())?;
+        fs::write(&absolute, bytes).with_context(|| format!("Couldn't write {:?} into the asset cache", path))?;
+        debug!("Cached {} bytes at {:?}", bytes.len(), path);
+        self.entries.insert(
+            hash.clone(),
+            Entry {
+                path: path.clone(),
+                size: bytes.len() as u64,
+                last_used: now,
+            },
+        );
+        self.evict(Some(&hash));
+        self.save()?;
+        Ok(path)
+    }
+
+    /**
+    Encodes the image in the format and stores it, see `insert`.
+     */
+    pub fn insert_image(&mut self, image: &DynamicImage, format: ImageFormat) -> Result<PathBuf> {
+        let mut bytes = Cursor::new(vec![]);
+        image
+            .write_to(&mut bytes, format)
+            .context("Couldn't encode the image")?;
+        self.insert(bytes.get_ref(), format.extensions_str()[0])
+    }
+
+    /**
+    The absolute path of a file in the cache, which doesn't exist if the file was evicted.
+     */
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /**
+    Copies the cached files to the same relative paths below `destination`, e.g. to save a scene along with its
+    images.
+     */
+    pub fn copy_to(&self, paths: &[PathBuf], destination: &Path) -> Result<()> {
+        for path in paths {
+            let target = destination.join(path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(self.root.join(path), &target).with_context(|| format!("Couldn't copy {:?}", path))?;
+        }
+        Ok(())
+    }
+
+    /**
+    Deletes all cached files.
+     */
+    pub fn clear(&mut self) -> Result<()> {
+        for entry in self.entries.values() {
+            remove_file(&self.root.join(&entry.path));
+        }
+        self.entries.clear();
+        self.save()
+    }
+
+    /**
+    Writes the index, which `insert` and `clear` do themselves.
+     */
+    pub fn save(&self) -> Result<()> {
+        let index = serde_json::to_vec(&self.entries)?;
+        fs::write(self.root.join(INDEX_FILE), index).context("Couldn't write the index of the asset cache")
+    }
+
+    // deletes the least recently used files other than `keep` until the cache fits into `max_size`
+    fn evict(&mut self, keep: Option<&str>) {
+        let mut size = self.size();
+        if size <= self.max_size {
+            return;
+        }
+        let mut entries: Vec<(String, u64)> = self
+            .entries
+            .iter()
+            .filter(|(hash, _)| Some(hash.as_str()) != keep)
+            .map(|(hash, entry)| (hash.clone(), entry.last_used))
+            .collect();
+        entries.sort_by_key(|(_, last_used)| *last_used);
+        for (hash, _) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            let entry = self.entries.remove(&hash).unwrap();
+            remove_file(&self.root.join(&entry.path));
+            size -= entry.size;
+            info!("Evicted {:?} from the asset cache", entry.path);
+        }
+    }
+}
+
+fn remove_file(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        warn!("Couldn't delete {:?} from the asset cache: {}", path, e);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
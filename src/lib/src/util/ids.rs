@@ -0,0 +1,39 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use rand::Rng;
+
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+static NEXT_ID: AtomicU32 = AtomicU32::new(FIRST_ID);
+
+/** 0 is reserved for "nothing", e.g. when object picking doesn't hit any mesh. */
+const FIRST_ID: u32 = 1;
+const ID_LIMIT: u32 = 1 << 31;
+
+/**
+Switches scene, model and mesh IDs to a deterministic sequence, so that repeating the same imports and commands
+produces identical worlds. The sequence restarts every time this is called.
+Material and texture IDs are slotmap keys and are deterministic either way.
+ */
+pub fn use_deterministic_ids() {
+    NEXT_ID.store(FIRST_ID, Ordering::SeqCst);
+    DETERMINISTIC.store(true, Ordering::SeqCst);
+}
+
+/** Switches back to randomly generated IDs (the default). */
+pub fn use_random_ids() {
+    DETERMINISTIC.store(false, Ordering::SeqCst);
+}
+
+pub fn is_deterministic() -> bool {
+    DETERMINISTIC.load(Ordering::SeqCst)
+}
+
+pub fn next_id() -> u32 {
+    if is_deterministic() {
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+        assert!(id < ID_LIMIT, "Ran out of deterministic IDs");
+        id
+    } else {
+        rand::thread_rng().gen_range(FIRST_ID..ID_LIMIT)
+    }
+}
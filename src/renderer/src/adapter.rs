@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use wgpu::{Adapter, Backend, DeviceType, Instance, Limits, Surface};
+
+/**
+Environment variable that overrides `RunConfig::adapter`, parsed by `AdapterSelection::parse`.
+ */
+pub const ADAPTER_ENV: &str = "ENGINE_ADAPTER";
+
+/**
+Which graphics adapter the renderer uses, see `RunConfig::adapter`. Adapters that can't render to the window are never
+selected, if the selected one can't, the renderer falls back to `HighPerformance`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AdapterSelection {
+    /// the adapter the platform considers fastest, usually a discrete GPU
+    #[default]
+    HighPerformance,
+    /// the adapter the platform considers most power-efficient, usually an integrated GPU
+    LowPower,
+    /// the adapter with this index in `enumerate_adapters`
+    Index(usize),
+    /// the first adapter whose name contains this, ignoring case
+    Name(String),
+}
+
+impl AdapterSelection {
+    /**
+    Parses a selection as given in `ADAPTER_ENV`: `high-performance`, `low-power`, an index into `enumerate_adapters`,
+    or part of an adapter's name.
+     */
+    pub fn parse(selection: &str) -> Self {
+        let selection = selection.trim();
+        match selection.to_lowercase().as_str() {
+            "high-performance" => Self::HighPerformance,
+            "low-power" => Self::LowPower,
+            _ => match selection.parse() {
+                Ok(index) => Self::Index(index),
+                Err(_) => Self::Name(selection.to_string()),
+            },
+        }
+    }
+
+    /**
+    The selection in `ADAPTER_ENV`, if it is set.
+     */
+    pub fn from_env() -> Option<Self> {
+        std::env::var(ADAPTER_ENV).ok().map(|selection| Self::parse(&selection))
+    }
+}
+
+/**
+An adapter that the renderer can select, see `enumerate_adapters`.
+ */
+#[derive(Debug, Clone)]
+pub struct AdapterDescription {
+    pub index: usize,
+    pub name: String,
+    pub backend: Backend,
+    pub device_type: DeviceType,
+}
+
+/**
+Lists the graphics adapters of this machine, e.g. to let users pick one before the engine is started. The list is
+empty on the web, where the browser picks the adapter.
+ */
+pub fn enumerate_adapters() -> Vec<AdapterDescription> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        create_instance()
+            .enumerate_adapters(wgpu::Backends::all())
+            .enumerate()
+            .map(|(index, adapter)| {
+                let info = adapter.get_info();
+                AdapterDescription {
+                    index,
+                    name: info.name,
+                    backend: info.backend,
+                    device_type: info.device_type,
+                }
+            })
+            .collect()
+    }
+    #[cfg(target_arch = "wasm32")]
+    vec![]
+}
+
+pub(crate) fn create_instance() -> Instance {
+    #[cfg(not(target_arch = "wasm32"))]
+    let descriptor = wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    };
+    // in the browser, WebGPU is the only backend, and it validates and labels objects on its own
+    #[cfg(target_arch = "wasm32")]
+    let descriptor = wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::BROWSER_WEBGPU,
+        flags: wgpu::InstanceFlags::empty(),
+        ..Default::default()
+    };
+    Instance::new(descriptor)
+}
+
+/**
+The adapter that `selection` picks among those that can render to `surface`.
+ */
+pub(crate) async fn request_adapter(
+    instance: &Instance,
+    surface: &Surface,
+    selection: &AdapterSelection,
+) -> Result<Adapter> {
+    let selected = match selection {
+        AdapterSelection::Index(_) | AdapterSelection::Name(_) => {
+            let adapter = find_adapter(instance, surface, selection);
+            if adapter.is_none() {
+                warn!(
+                    "No adapter matching {:?} can render to the window, using the default one",
+                    selection
+                );
+            }
+            adapter
+        }
+        _ => None,
+    };
+    let adapter = match selected {
+        Some(adapter) => adapter,
+        None => instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: match selection {
+                    AdapterSelection::LowPower => wgpu::PowerPreference::LowPower,
+                    _ => wgpu::PowerPreference::HighPerformance,
+                },
+                compatible_surface: Some(surface),
+                force_fallback_adapter: false,
+            })
+            .await
+            .context("No graphics adapter compatible with the window's surface found")?,
+    };
+    let info = adapter.get_info();
+    info!(
+        "Using adapter {} ({:?}, {:?})",
+        info.name, info.backend, info.device_type
+    );
+    Ok(adapter)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn find_adapter(instance: &Instance, surface: &Surface, selection: &AdapterSelection) -> Option<Adapter> {
+    let adapter = instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .enumerate()
+        .find(|(index, adapter)| match selection {
+            AdapterSelection::Index(selected) => index == selected,
+            AdapterSelection::Name(name) => adapter.get_info().name.to_lowercase().contains(&name.to_lowercase()),
+            _ => false,
+        })
+        .map(|(_, adapter)| adapter)?;
+    if !adapter.is_surface_supported(surface) {
+        warn!("Adapter {} can't render to the window", adapter.get_info().name);
+        return None;
+    }
+    Some(adapter)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn find_adapter(_instance: &Instance, _surface: &Surface, _selection: &AdapterSelection) -> Option<Adapter> {
+    None
+}
+
+/**
+Lowers the `requested` limits to what the adapter `allowed`, so that requesting a device with them can't fail. Limits
+that are minimums, like offset alignments, are raised instead.
+ */
+pub(crate) fn clamp_limits(requested: Limits, allowed: &Limits) -> Limits {
+    let mut limits = requested;
+    macro_rules! clamp {
+        (max: $($name:ident),*; min: $($min_name:ident),*) => {
+            $(limits.$name = limits.$name.min(allowed.$name);)*
+            $(limits.$min_name = limits.$min_name.max(allowed.$min_name);)*
+        };
+    }
+    clamp!(
+        max:
+            max_texture_dimension_1d,
+            max_texture_dimension_2d,
+            max_texture_dimension_3d,
+            max_texture_array_layers,
+            max_bind_groups,
+            max_bindings_per_bind_group,
+            max_dynamic_uniform_buffers_per_pipeline_layout,
+            max_dynamic_storage_buffers_per_pipeline_layout,
+            max_sampled_textures_per_shader_stage,
+            max_samplers_per_shader_stage,
+            max_storage_buffers_per_shader_stage,
+            max_storage_textures_per_shader_stage,
+            max_uniform_buffers_per_shader_stage,
+            max_uniform_buffer_binding_size,
+            max_storage_buffer_binding_size,
+            max_vertex_buffers,
+            max_buffer_size,
+            max_vertex_attributes,
+            max_vertex_buffer_array_stride,
+            max_push_constant_size,
+            max_inter_stage_shader_components,
+            max_compute_workgroup_storage_size,
+            max_compute_invocations_per_workgroup,
+            max_compute_workgroup_size_x,
+            max_compute_workgroup_size_y,
+            max_compute_workgroup_size_z,
+            max_compute_workgroups_per_dimension,
+            max_non_sampler_bindings;
+        min:
+            min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment
+    );
+    limits
+}
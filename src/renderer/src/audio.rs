@@ -0,0 +1,370 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use glam::{Mat4, Vec3};
+use hashbrown::HashMap;
+use lib::scene::model::Model;
+use lib::util::ids::next_id;
+use log::{info, warn};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source, SpatialSink};
+
+use crate::camera::Camera;
+use crate::events::Event;
+use crate::RenderState;
+
+// distance between the listener's ears in world units
+const EAR_DISTANCE: f32 = 0.2;
+
+type SoundSource = Decoder<Cursor<Arc<[u8]>>>;
+
+/**
+Sounds played through the default output device, see `Command::LoadSound` and `Command::PlaySound`. A sound is read into
+memory once and decoded anew by each playback, so the same sound can play several times at once. Playbacks pause while
+the scene's time is paused, and `Event::SoundFinished` is sent when one ends.
+
+Sounds played on a model (see `Command::PlaySoundOnModel`) follow it around: the camera is the listener, and such sounds
+are panned between its ears and attenuated with their distance to it, see `Falloff`.
+
+Without an output device, e.g. on a server, sounds still load but playing them fails.
+ */
+pub(crate) struct Audio {
+    // dropping the stream stops every sink that plays on it, so it lives as long as its handle
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    sounds: HashMap<u32, Sound>,
+    playbacks: HashMap<u32, Playback>,
+    master_volume: f32,
+    paused: bool,
+}
+
+struct Sound {
+    path: PathBuf,
+    data: Arc<[u8]>,
+}
+
+struct Playback {
+    sound_id: u32,
+    output: PlaybackOutput,
+    // before the master volume and attenuation are applied
+    volume: f32,
+}
+
+enum PlaybackOutput {
+    Flat(Sink),
+    Spatial {
+        sink: SpatialSink,
+        model_id: u32,
+        falloff: Falloff,
+        // whether the model is within the falloff's `max_distance`
+        audible: bool,
+        // whether the emitter and ears have been positioned, the sink is paused until then
+        placed: bool,
+    },
+}
+
+/**
+How the volume of a sound played on a model falls off with the model's distance to the camera, in world units: full
+volume within `reference_distance`, then with the inverse square of the distance, and silence beyond `max_distance`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Falloff {
+    pub reference_distance: f32,
+    pub max_distance: f32,
+}
+
+impl Default for Falloff {
+    fn default() -> Self {
+        Self {
+            reference_distance: 1.0,
+            max_distance: 100.0,
+        }
+    }
+}
+
+// the camera's ears in world space
+struct Listener {
+    left: Vec3,
+    right: Vec3,
+}
+
+impl Listener {
+    fn new(camera: &Camera) -> Self {
+        let position = camera.world_eye();
+        // the view's x axis points to the right of the screen
+        let right = camera.view.inverse().x_axis.truncate().normalize_or_zero();
+        Self {
+            left: position - right * EAR_DISTANCE / 2.0,
+            right: position + right * EAR_DISTANCE / 2.0,
+        }
+    }
+}
+
+impl Playback {
+    fn pause(&self) {
+        match &self.output {
+            PlaybackOutput::Flat(sink) => sink.pause(),
+            PlaybackOutput::Spatial { sink, .. } => sink.pause(),
+        }
+    }
+
+    fn play(&self) {
+        match &self.output {
+            PlaybackOutput::Flat(sink) => sink.play(),
+            PlaybackOutput::Spatial { sink, placed, .. } => {
+                if *placed {
+                    sink.play();
+                }
+            }
+        }
+    }
+
+    fn ended(&self) -> bool {
+        match &self.output {
+            PlaybackOutput::Flat(sink) => sink.empty(),
+            PlaybackOutput::Spatial { sink, .. } => sink.empty(),
+        }
+    }
+
+    fn apply_volume(&self, master_volume: f32) {
+        let volume = self.volume * master_volume;
+        match &self.output {
+            PlaybackOutput::Flat(sink) => sink.set_volume(volume),
+            PlaybackOutput::Spatial { sink, audible, .. } => sink.set_volume(if *audible { volume } else { 0.0 }),
+        }
+    }
+
+    // moves a spatial playback's emitter to `position`
+    fn place(&mut self, position: Vec3, listener: &Listener) {
+        let PlaybackOutput::Spatial {
+            sink,
+            falloff,
+            audible,
+            placed,
+            ..
+        } = &mut self.output
+        else {
+            return;
+        };
+        // rodio attenuates with the inverse square of the distance, clamped to full volume within 1 unit, so
+        // scaling all positions makes that unit the reference distance. Panning only depends on the ratio of distances
+        let scale = 1.0 / falloff.reference_distance.max(f32::EPSILON);
+        sink.set_emitter_position((position * scale).into());
+        sink.set_left_ear_position((listener.left * scale).into());
+        sink.set_right_ear_position((listener.right * scale).into());
+        *audible = position.distance((listener.left + listener.right) / 2.0) <= falloff.max_distance;
+        *placed = true;
+    }
+}
+
+impl Audio {
+    pub(crate) fn new() -> Self {
+        let output = match OutputStream::try_default() {
+            Ok(output) => Some(output),
+            Err(e) => {
+                warn!("No audio output device, sounds won't play: {}", e);
+                None
+            }
+        };
+        Self {
+            output,
+            sounds: HashMap::new(),
+            playbacks: HashMap::new(),
+            master_volume: 1.0,
+            paused: false,
+        }
+    }
+
+    /**
+    Reads a WAV, FLAC, Vorbis or MP3 file and returns the id of the new sound.
+     */
+    pub(crate) fn load(&mut self, path: &Path) -> Result<u32> {
+        let data: Arc<[u8]> = std::fs::read(path)
+            .with_context(|| format!("Couldn't read sound {:?}", path))?
+            .into();
+        // rejects unsupported files now rather than when they are played
+        Decoder::new(Cursor::new(data.clone())).with_context(|| format!("Couldn't decode sound {:?}", path))?;
+        let sound_id = next_id();
+        info!("Loaded sound {} from {:?}", sound_id, path);
+        self.sounds.insert(
+            sound_id,
+            Sound {
+                path: path.to_path_buf(),
+                data,
+            },
+        );
+        Ok(sound_id)
+    }
+
+    /**
+    Removes a sound and stops its playbacks.
+     */
+    pub(crate) fn unload(&mut self, sound_id: u32) -> Result<()> {
+        let sound = self
+            .sounds
+            .remove(&sound_id)
+            .with_context(|| format!("Sound {} not found", sound_id))?;
+        self.playbacks.retain(|_, playback| playback.sound_id != sound_id);
+        info!("Unloaded sound {} from {:?}", sound_id, sound.path);
+        Ok(())
+    }
+
+    /**
+    Starts playing a sound and returns the id of the playback, which stays valid until it is stopped or has ended.
+     */
+    pub(crate) fn play(&mut self, sound_id: u32, volume: f32, looping: bool) -> Result<u32> {
+        let (source, handle) = self.source(sound_id)?;
+        let sink = Sink::try_new(handle).context("Couldn't create an audio sink")?;
+        if looping {
+            sink.append(source.repeat_infinite());
+        } else {
+            sink.append(source);
+        }
+        Ok(self.start(sound_id, volume, PlaybackOutput::Flat(sink)))
+    }
+
+    /**
+    Like `play`, but the sound is emitted from a model of the active scene and follows it until it is removed. It starts
+    with the next `update`, which places it.
+     */
+    pub(crate) fn play_on_model(
+        &mut self,
+        sound_id: u32,
+        model_id: u32,
+        volume: f32,
+        looping: bool,
+        falloff: Falloff,
+    ) -> Result<u32> {
+        let (source, handle) = self.source(sound_id)?;
+        let sink = SpatialSink::try_new(handle, [0.0; 3], [-1.0, 0.0, 0.0], [1.0, 0.0, 0.0])
+            .context("Couldn't create an audio sink")?;
+        sink.pause();
+        if looping {
+            sink.append(source.repeat_infinite());
+        } else {
+            sink.append(source);
+        }
+        let output = PlaybackOutput::Spatial {
+            sink,
+            model_id,
+            falloff,
+            audible: true,
+            placed: false,
+        };
+        Ok(self.start(sound_id, volume, output))
+    }
+
+    // a new decoder of a sound, and the output to play it on
+    fn source(&self, sound_id: u32) -> Result<(SoundSource, &OutputStreamHandle)> {
+        let sound = self
+            .sounds
+            .get(&sound_id)
+            .with_context(|| format!("Sound {} not found", sound_id))?;
+        let Some((_, handle)) = &self.output else {
+            bail!("There is no audio output device to play sound {} on", sound_id);
+        };
+        Ok((Decoder::new(Cursor::new(sound.data.clone()))?, handle))
+    }
+
+    fn start(&mut self, sound_id: u32, volume: f32, output: PlaybackOutput) -> u32 {
+        let playback = Playback {
+            sound_id,
+            output,
+            volume,
+        };
+        playback.apply_volume(self.master_volume);
+        if self.paused {
+            playback.pause();
+        }
+        let playback_id = next_id();
+        self.playbacks.insert(playback_id, playback);
+        playback_id
+    }
+
+    pub(crate) fn stop(&mut self, playback_id: u32) -> Result<()> {
+        // dropping the sink stops it
+        self.playbacks
+            .remove(&playback_id)
+            .with_context(|| format!("Playback {} not found, it may have ended", playback_id))?;
+        Ok(())
+    }
+
+    pub(crate) fn set_volume(&mut self, playback_id: u32, volume: f32) -> Result<()> {
+        let playback = self
+            .playbacks
+            .get_mut(&playback_id)
+            .with_context(|| format!("Playback {} not found, it may have ended", playback_id))?;
+        playback.volume = volume;
+        playback.apply_volume(self.master_volume);
+        Ok(())
+    }
+
+    /**
+    Scales the volume of all playbacks, including ones started later.
+     */
+    pub(crate) fn set_master_volume(&mut self, master_volume: f32) {
+        self.master_volume = master_volume;
+        for playback in self.playbacks.values() {
+            playback.apply_volume(master_volume);
+        }
+    }
+}
+
+/**
+Pauses or resumes the playbacks along with the scene's time, moves the sounds played on models along with them and the
+camera, and removes the playbacks that have ended or whose model is gone.
+ */
+pub(crate) fn update(state: &mut RenderState) {
+    let audio = &mut state.audio;
+    let paused = state.meta.time.paused;
+    if paused != audio.paused {
+        for playback in audio.playbacks.values() {
+            if paused {
+                playback.pause();
+            } else {
+                playback.play();
+            }
+        }
+        audio.paused = paused;
+    }
+    let mut positions = HashMap::new();
+    let spatial = audio
+        .playbacks
+        .values()
+        .any(|playback| matches!(playback.output, PlaybackOutput::Spatial { .. }));
+    if let (true, Some(scene)) = (spatial, state.world.get_active_scene()) {
+        collect_positions(&scene.models, Mat4::IDENTITY, &mut positions);
+    }
+    let listener = Listener::new(&state.camera);
+    let master_volume = audio.master_volume;
+    audio.playbacks.retain(|&playback_id, playback| {
+        let mut ended = playback.ended();
+        if let PlaybackOutput::Spatial { model_id, placed, .. } = playback.output {
+            match positions.get(&model_id) {
+                Some(&position) => {
+                    playback.place(position, &listener);
+                    playback.apply_volume(master_volume);
+                    if !placed && !paused {
+                        playback.play();
+                    }
+                }
+                None => ended = true,
+            }
+        }
+        if !ended {
+            return true;
+        }
+        // the hook may have dropped its receiver, in which case nobody is interested in the event
+        let _ = state.event_channel.0.send(Event::SoundFinished(playback_id));
+        false
+    });
+}
+
+// world space positions of the models and the models below them
+fn collect_positions(models: &[Model], parent_transform: Mat4, positions: &mut HashMap<u32, Vec3>) {
+    for model in models {
+        let transform = parent_transform * model.local_transform;
+        positions.insert(model.id, transform.w_axis.truncate());
+        collect_positions(&model.children, transform, positions);
+    }
+}
@@ -0,0 +1,30 @@
+/**
+Display calibration that is applied when tone mapping, so that output can be adjusted for uncalibrated displays.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    /// the display's gamma that the output gets encoded for
+    pub gamma: f32,
+    /// linear scale applied to the scene's radiance before tone mapping
+    pub brightness: f32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            gamma: 2.2,
+            brightness: 1.0,
+        }
+    }
+}
+
+impl Calibration {
+    /**
+    Maps a linear radiance value to the value written to the surface, same as the `tonemap` function in the shaders.
+    Useful for drawing test patterns that go through the same calibration as the rendered scene.
+     */
+    pub fn apply(&self, radiance: f32) -> f32 {
+        let color = radiance * self.brightness;
+        (color / (color + 1.0)).powf(1.0 / self.gamma)
+    }
+}
@@ -1,13 +1,22 @@
 use glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
 use log::debug;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
-use wgpu::{BindGroup, BindGroupLayoutDescriptor, Buffer, Device, Queue};
+use wgpu::{BindGroupLayoutDescriptor, Buffer, CompareFunction, Device, ShaderModule};
 use winit::event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode};
 
+use lib::scene::character::CharacterController;
+use lib::scene::Scene;
 use lib::shader_types::CameraUniform;
+use lib::staging::BufferWriter;
+
+use crate::calibration::Calibration;
+use crate::events::Modifiers;
 
 const GLOBAL_Y: [f32; 4] = [0.0, -1.0, 0.0, 1.0];
 
+/// WGSL declaration of the camera uniform and the `tonemap` function, prepended to the shaders that tone map
+pub(crate) const CAMERA_SHADER: &str = include_str!("shaders/camera.wgsl");
+
 #[derive(Debug)]
 enum InputDevice {
     Mouse { middle_pressed: bool },
@@ -34,8 +43,10 @@ pub struct KeyState {
     pub left_pressed: bool,
     pub right_pressed: bool,
     pub shift_pressed: bool,
+    pub jump_pressed: bool,
     input_device: InputDevice,
     pub cmd_pressed: bool,
+    pub ctrl_pressed: bool,
 }
 
 impl KeyState {
@@ -46,7 +57,7 @@ impl KeyState {
             VirtualKeyCode::S => self.down_pressed = pressed,
             VirtualKeyCode::A => self.left_pressed = pressed,
             VirtualKeyCode::D => self.right_pressed = pressed,
-            // VirtualKeyCode::Space => self.middle_pressed = pressed,
+            VirtualKeyCode::Space => self.jump_pressed = pressed,
             _ => (),
         }
     }
@@ -60,7 +71,8 @@ impl KeyState {
                     middle_pressed: pressed,
                 }
             }
-            MouseButton::Left if self.cmd_pressed || self.shift_pressed => {
+            // shift-clicks add to the selection, panning with shift needs the middle button
+            MouseButton::Left if self.cmd_pressed => {
                 self.input_device = InputDevice::Mouse {
                     middle_pressed: pressed,
                 };
@@ -74,6 +86,174 @@ impl KeyState {
     pub(crate) fn set_modifiers(&mut self, state: &ModifiersState) {
         self.shift_pressed = state.shift();
         self.cmd_pressed = state.logo();
+        self.ctrl_pressed = state.ctrl();
+    }
+
+    pub(crate) fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            shift: self.shift_pressed,
+            ctrl: self.ctrl_pressed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// orbits around `target` while the middle mouse button is held
+    Arcball,
+    /// flies along `direction` and looks around with the mouse
+    Fly,
+    /// walks on the active scene's meshes and looks around with the mouse, see `Walk`
+    Walk,
+}
+
+/**
+Walk mode: the camera is the eye of a capsule that the active scene's gravity pulls down and its meshes stop, moved
+horizontally with WASD and launched upwards with space. Distances are in world units.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Walk {
+    /// height of the eye above the feet, the capsule is a radius taller
+    pub eye_height: f32,
+    pub radius: f32,
+    /// ledges up to this height are stepped onto
+    pub step_height: f32,
+    /// per second
+    pub speed: f32,
+    /// upwards speed at the start of a jump, per second
+    pub jump_speed: f32,
+    controller: CharacterController,
+    // horizontal velocity from this frame's input
+    velocity: Vec3,
+    jump: bool,
+}
+
+impl Default for Walk {
+    fn default() -> Self {
+        Self {
+            eye_height: 1.7,
+            radius: 0.3,
+            step_height: 0.3,
+            speed: 1.5,
+            jump_speed: 4.0,
+            controller: CharacterController::default(),
+            velocity: Vec3::ZERO,
+            jump: false,
+        }
+    }
+}
+
+impl Walk {
+    /**
+    Whether the feet stood on walkable ground after the last step.
+     */
+    pub fn grounded(&self) -> bool {
+        self.controller.grounded
+    }
+}
+
+/**
+Views along the world axes, named after the side of the scene that they look at. Set with `Command::SetCameraView` or
+by clicking the axes gizmo, see `AxesGizmo`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisView {
+    /// looks along +Z
+    Front,
+    /// looks along -Z
+    Back,
+    /// looks along -X
+    Right,
+    /// looks along +X
+    Left,
+    /// looks down
+    Top,
+    /// looks up
+    Bottom,
+}
+
+impl AxisView {
+    pub const ALL: [AxisView; 6] = [
+        AxisView::Front,
+        AxisView::Back,
+        AxisView::Right,
+        AxisView::Left,
+        AxisView::Top,
+        AxisView::Bottom,
+    ];
+
+    /**
+    The direction from the scene towards the camera.
+     */
+    pub fn side(self) -> Vec3 {
+        let up = Vec4::from(GLOBAL_Y).xyz();
+        match self {
+            AxisView::Front => Vec3::NEG_Z,
+            AxisView::Back => Vec3::Z,
+            AxisView::Right => Vec3::X,
+            AxisView::Left => Vec3::NEG_X,
+            AxisView::Top => up,
+            AxisView::Bottom => -up,
+        }
+    }
+}
+
+/**
+How the projection maps view depth to the depth buffer. With `ReverseZ`, the near plane is at depth 1 and the far plane
+at depth 0, which spreads the precision of the float depth buffer much more evenly over the view distance and avoids
+z-fighting in the distance. Set it with `Command::SetDepthMode`, which recreates the pipelines that test depth.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DepthMode {
+    #[default]
+    Standard,
+    ReverseZ,
+}
+
+impl DepthMode {
+    /**
+    Compare function for the depth test, passing fragments that are closer than the stored depth.
+     */
+    pub fn compare(self) -> CompareFunction {
+        match self {
+            DepthMode::Standard => CompareFunction::Less,
+            DepthMode::ReverseZ => CompareFunction::Greater,
+        }
+    }
+
+    /**
+    Like `compare`, but also passes fragments at the stored depth, e.g. after a depth pre-pass.
+     */
+    pub fn compare_equal(self) -> CompareFunction {
+        match self {
+            DepthMode::Standard => CompareFunction::LessEqual,
+            DepthMode::ReverseZ => CompareFunction::GreaterEqual,
+        }
+    }
+
+    /**
+    Depth of the far plane, which depth buffers are cleared to.
+     */
+    pub fn far_depth(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReverseZ => 0.0,
+        }
+    }
+
+    /**
+    Depth of the near plane, e.g. to unproject a point on it.
+     */
+    pub fn near_depth(self) -> f32 {
+        1.0 - self.far_depth()
+    }
+
+    // a perspective projection for this mode, the clipping planes are swapped for reverse-Z
+    pub(crate) fn perspective(self, fovy: f32, aspect: f32, znear: f32, zfar: f32) -> Mat4 {
+        match self {
+            DepthMode::Standard => Mat4::perspective_lh(fovy, aspect, znear, zfar),
+            DepthMode::ReverseZ => Mat4::perspective_lh(fovy, aspect, zfar, znear),
+        }
     }
 }
 
@@ -90,11 +270,18 @@ pub struct Camera {
     pub znear: f32,
     pub zfar: f32,
     pub speed: f32,
-    pub fps: bool,
+    pub mode: CameraMode,
+    pub walk: Walk,
     /// the camera's transform matrix / world to view matrix
     pub view: Mat4,
     dirty: bool,
+    depth_mode: DepthMode,
     light_count: u32,
+    calibration: Calibration,
+    // the projection of the previous frame as uploaded to the GPU, for motion vectors
+    previous_proj_view: Mat4,
+    // the projection of the frame that is currently rendered
+    last_frame_proj_view: Mat4,
     pub buffer: Buffer,
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
@@ -118,6 +305,8 @@ impl Camera {
         debug!("Creating view proj: {:?}", proj * view * scale);
         data.proj_view = (proj * view * scale).to_cols_array_2d();
         data.view_position = (Vec4::from((eye, 1.0))).into();
+        data.gamma = Calibration::default().gamma;
+        data.brightness = Calibration::default().brightness;
 
         let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Camera Buffer"),
@@ -128,7 +317,8 @@ impl Camera {
             label: Some("Camera Bindgroup Layout"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                // the fragment stage only needs the display calibration
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -147,7 +337,7 @@ impl Camera {
             }],
         });
 
-        Camera {
+        let mut camera = Camera {
             eye,
             target,
             direction: target - eye,
@@ -157,14 +347,34 @@ impl Camera {
             znear,
             zfar,
             speed: 0.5,
-            fps: false,
+            mode: CameraMode::Arcball,
+            walk: Walk::default(),
             view,
             dirty: true,
+            depth_mode: DepthMode::default(),
             light_count: 0,
+            calibration: Calibration::default(),
+            previous_proj_view: Mat4::IDENTITY,
+            last_frame_proj_view: Mat4::IDENTITY,
             buffer: camera_buffer,
             bind_group_layout,
             bind_group,
-        }
+        };
+        // no motion in the first frame
+        camera.previous_proj_view = camera.build_projection();
+        camera.last_frame_proj_view = camera.previous_proj_view;
+        camera
+    }
+
+    /**
+    Recreates the camera's buffer and bind group on another device, e.g. after the old device was lost.
+     */
+    pub fn recreate(&mut self, device: &Device) {
+        let fresh = Self::new_default(1.0, 1.0, device);
+        self.buffer = fresh.buffer;
+        self.bind_group_layout = fresh.bind_group_layout;
+        self.bind_group = fresh.bind_group;
+        self.dirty = true;
     }
 
     /**
@@ -183,6 +393,50 @@ impl Camera {
         self.light_count
     }
 
+    /**
+    Turns the camera to look along a world axis. The arcball camera keeps its target and distance, the other modes
+    keep their position.
+     */
+    pub fn set_axis_view(&mut self, view: AxisView) {
+        let global_up = Vec4::from(GLOBAL_Y).xyz();
+        let mut direction = -view.side();
+        if matches!(view, AxisView::Top | AxisView::Bottom) {
+            // looking straight along the up axis leaves the view's orientation undefined, so tilt it a little
+            direction = (direction + Vec3::Z * 0.001).normalize();
+        }
+        if self.mode == CameraMode::Arcball {
+            let distance = (self.target - self.eye).length();
+            self.eye = self.target - direction * distance;
+        }
+        self.direction = direction;
+        self.view = Mat4::look_to_lh(self.eye, direction, global_up);
+        self.dirty = true;
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    // the pipelines that test depth have to be recreated with the new mode, see `Command::SetDepthMode`
+    pub(crate) fn set_depth_mode(&mut self, depth_mode: DepthMode) {
+        self.depth_mode = depth_mode;
+        // the previous frame's projection is in the old mode, which would show up as motion
+        self.last_frame_proj_view = self.build_projection();
+        self.previous_proj_view = self.last_frame_proj_view;
+        self.dirty = true;
+    }
+
+    /**
+    Sets the display calibration that's passed to the tone mapping in the fragment shaders.
+     */
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        if self.calibration == calibration {
+            return;
+        }
+        self.calibration = calibration;
+        self.dirty = true;
+    }
+
     pub fn reset(&mut self) {
         self.eye = (0.3, 0.3, 1.0).into();
         self.target = (0.0, 0.0, 0.0).into();
@@ -192,47 +446,130 @@ impl Camera {
         self.znear = 0.1;
         self.zfar = 100.0;
         self.speed = 0.5;
-        self.fps = false;
+        self.mode = CameraMode::Arcball;
+        self.walk = Walk::default();
         self.view = Mat4::look_at_lh(self.eye, self.target, self.up);
         self.dirty = true;
     }
 
+    /**
+    Keeps track of the previous frame's projection for motion vectors. Must be called exactly once per frame, after
+    the camera was moved and before `update_view`.
+     */
+    pub fn update_motion(&mut self) {
+        let last_frame_proj_view = self.last_frame_proj_view;
+        self.last_frame_proj_view = self.build_projection();
+        if self.previous_proj_view != last_frame_proj_view {
+            self.previous_proj_view = last_frame_proj_view;
+            self.dirty = true;
+        }
+    }
+
     pub(crate) fn build_projection(&self) -> Mat4 {
         let view = self.view;
-        let proj = Mat4::perspective_lh(self.fovy.to_radians(), self.aspect, self.znear, self.zfar);
+        let proj = self.projection(self.fovy, self.aspect);
         let scale = Mat4::from_scale((0.01, 0.01, 0.01).into());
         proj * view * scale
     }
 
+    // the perspective projection with this camera's clipping planes and depth mode, `fovy` in degrees
+    fn projection(&self, fovy: f32, aspect: f32) -> Mat4 {
+        self.depth_mode
+            .perspective(fovy.to_radians(), aspect, self.znear, self.zfar)
+    }
+
+    /**
+    The camera's position in world space; `eye` is in camera space, which is world space scaled by 0.01.
+     */
+    pub fn world_eye(&self) -> Vec3 {
+        self.eye * 100.0
+    }
+
     pub fn update_aspect(&mut self, width: f32, height: f32) {
         self.aspect = width / height;
         self.dirty = true;
     }
 
-    pub fn update_view(&mut self, queue: &Queue) {
+    pub fn update_view(&mut self, mut writer: impl BufferWriter) {
         if !self.dirty {
             return;
         }
         self.dirty = false;
+        writer.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform()]))
+    }
+
+    /**
+    Creates a shader from `source` with `CAMERA_SHADER` prepended. The source still declares the binding of `camera`.
+     */
+    pub(crate) fn create_shader(device: &Device, label: &str, source: &str) -> ShaderModule {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", CAMERA_SHADER, source).into()),
+        })
+    }
+
+    // the data for the camera uniform buffer
+    pub(crate) fn uniform(&self) -> CameraUniform {
         let new_proj = self.build_projection();
         let view_inv = self.view.inverse();
-        let proj_inv = Mat4::perspective_lh(self.fovy.to_radians(), self.aspect, self.znear, self.zfar).inverse();
-        let uniform = CameraUniform {
+        let proj_inv = self.projection(self.fovy, self.aspect).inverse();
+        CameraUniform {
             proj_view: new_proj.to_cols_array_2d(),
             unproj_view: (view_inv * proj_inv).to_cols_array_2d(),
             view_position: Vec4::from((self.eye, 1.0)).into(),
             num_lights: self.light_count,
-            ..Default::default()
-        };
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]))
+            gamma: self.calibration.gamma,
+            brightness: self.calibration.brightness,
+            near_depth: self.depth_mode.near_depth(),
+            prev_proj_view: self.previous_proj_view.to_cols_array_2d(),
+        }
+    }
+
+    /**
+    Uniform for one eye of a stereo pair: the camera shifted by `offset` along its right axis, with the frustum skewed
+    so that both eyes converge at `convergence` distance in front of the camera. Distances are in camera space, the
+    same as `eye`. `aspect` overrides the camera's aspect ratio, e.g. for side-by-side views.
+     */
+    pub(crate) fn eye_uniform(&self, offset: f32, convergence: f32, aspect: f32) -> CameraUniform {
+        let proj = self.projection(self.fovy, aspect);
+        // shifts clip space x by a constant amount in NDC, which cancels out the eye offset at the convergence distance
+        let mut skew = Mat4::IDENTITY;
+        skew.w_axis.x = proj.x_axis.x * offset / convergence;
+        let proj = skew * proj;
+        let view = Mat4::from_translation(Vec3::new(-offset, 0.0, 0.0)) * self.view;
+        let scale = Mat4::from_scale((0.01, 0.01, 0.01).into());
+        let eye = view.inverse().w_axis;
+        CameraUniform {
+            proj_view: (proj * view * scale).to_cols_array_2d(),
+            unproj_view: (view.inverse() * proj.inverse()).to_cols_array_2d(),
+            view_position: eye.into(),
+            ..self.uniform()
+        }
+    }
+
+    /**
+    Uniform for another viewpoint into the same scene, e.g. a split-screen player: looks from `eye` at `target` (both
+    in camera space, the same as `eye`) with a vertical field of view of `fovy` degrees. Everything else, like the
+    clipping planes and calibration, is taken from this camera.
+     */
+    pub(crate) fn view_uniform(&self, eye: Vec3, target: Vec3, fovy: f32, aspect: f32) -> CameraUniform {
+        let proj = self.projection(fovy, aspect);
+        let view = Mat4::look_at_lh(eye, target, self.up);
+        let scale = Mat4::from_scale((0.01, 0.01, 0.01).into());
+        CameraUniform {
+            proj_view: (proj * view * scale).to_cols_array_2d(),
+            unproj_view: (view.inverse() * proj.inverse()).to_cols_array_2d(),
+            view_position: Vec4::from((eye, 1.0)).into(),
+            ..self.uniform()
+        }
     }
 
     pub fn recv_input(&mut self, keys: &KeyState, change: Vec2, delta_time: f32) {
         // TODO clamp right rotation between 90° and -90° to avoid the jittering at the top
-        if self.fps {
-            self.update_fps(keys, change, delta_time)
-        } else {
-            self.update_arcball(keys, change, delta_time)
+        match self.mode {
+            CameraMode::Arcball => self.update_arcball(keys, change, delta_time),
+            CameraMode::Fly => self.update_fps(keys, change, delta_time),
+            CameraMode::Walk => self.update_walk_input(keys, change, delta_time),
         }
     }
 
@@ -267,15 +604,75 @@ impl Camera {
             self.eye += translation;
             self.dirty = true;
         }
+        self.look(cursor_delta, delta_time);
+        if self.dirty {
+            self.view = Mat4::look_at_lh(self.eye, self.eye + self.direction.normalize(), global_up.xyz());
+        }
+    }
+
+    // turns `direction` by the mouse movement
+    fn look(&mut self, cursor_delta: Vec2, delta_time: f32) {
         if cursor_delta.length() != 0.0 {
+            let global_up = Vec4::from(GLOBAL_Y);
+            let right = self.direction.cross(global_up.xyz()).normalize();
             let rotation_up = Mat4::from_axis_angle(global_up.xyz(), cursor_delta.x.to_degrees() * delta_time);
             let rotation_right = Mat4::from_axis_angle(right, -cursor_delta.y.to_degrees() * delta_time);
 
             self.direction = (rotation_right * rotation_up * as_4(self.direction)).xyz();
             self.dirty = true;
         }
+    }
+
+    // looks around and remembers the movement for `update_walk`, which moves the camera once the scene is known
+    fn update_walk_input(&mut self, keys: &KeyState, cursor_delta: Vec2, delta_time: f32) {
+        self.look(cursor_delta, delta_time);
+        let up = Vec4::from(GLOBAL_Y).xyz();
+        let forward = (self.direction - up * self.direction.dot(up)).normalize_or_zero();
+        let right = forward.cross(up);
+        let mut movement = Vec3::ZERO;
+        if keys.up_pressed {
+            movement += forward;
+        }
+        if keys.down_pressed {
+            movement -= forward;
+        }
+        if keys.left_pressed {
+            movement -= right;
+        }
+        if keys.right_pressed {
+            movement += right;
+        }
+        self.walk.velocity = movement.normalize_or_zero() * self.walk.speed;
+        self.walk.jump = keys.jump_pressed;
+    }
+
+    /**
+    Moves the camera in walk mode with the input of `recv_input`, colliding with the scene's meshes. Does nothing in
+    the other modes.
+     */
+    pub(crate) fn update_walk(&mut self, scene: &Scene, delta_time: f32) {
+        if self.mode != CameraMode::Walk {
+            return;
+        }
+        let up = Vec4::from(GLOBAL_Y).xyz();
+        let eye = self.world_eye();
+        let walk = &mut self.walk;
+        let controller = &mut walk.controller;
+        controller.radius = walk.radius;
+        controller.height = walk.eye_height + walk.radius;
+        controller.step_height = walk.step_height;
+        if walk.jump {
+            controller.jump(walk.jump_speed);
+        }
+        let feet = eye - up * walk.eye_height;
+        let feet = scene.move_controller(controller, feet, walk.velocity, delta_time, &[]);
+        let eye = (feet + up * walk.eye_height) / 100.0;
+        if eye != self.eye {
+            self.eye = eye;
+            self.dirty = true;
+        }
         if self.dirty {
-            self.view = Mat4::look_at_lh(self.eye, self.eye + self.direction.normalize(), global_up.xyz());
+            self.view = Mat4::look_at_lh(self.eye, self.eye + self.direction.normalize(), up);
         }
     }
 
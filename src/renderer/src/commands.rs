@@ -1,22 +1,89 @@
+use std::ffi::OsStr;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 
-use glam::Mat4;
-use hashbrown::HashMap;
+use anyhow::{anyhow, bail, Context, Result};
+#[cfg(feature = "physics")]
+use glam::Quat;
+use glam::{IVec2, Mat4, Vec3, Vec4};
 use log::{debug, error, info};
 
-use lib::managers::{MaterialManager, TextureManager};
+use lib::assets::Purged;
+use lib::geometry::Geometry;
+use lib::managers::{MatId, TexId};
+use lib::scene::bindings::{Binding, Expression, Property};
 use lib::scene::light::PointLight;
-use lib::scene::model::Model;
-use lib::scene::World;
+use lib::scene::material::{PbrMaterialChanges, PbrMaterialDesc};
+use lib::scene::mesh::{Mesh, Outline};
+use lib::scene::model::{DeepIter, Model};
+use lib::scene::physics::{Collider, ColliderShape, RigidBody};
+use lib::scene::water::Water;
+use lib::scene::Scene;
+use lib::texture::TextureKind;
+use lib::Material;
 use systems::io::gltf_loader::load_gltf;
 
+#[cfg(feature = "audio")]
+use crate::audio::Falloff;
+use crate::camera::{AxisView, DepthMode};
 use crate::events::Event;
-use crate::RenderState;
+use crate::live_link::LiveLink;
+use crate::lut::Lut;
+#[cfg(feature = "physics")]
+use crate::physics::PhysicsHit;
+use crate::pipelines::object_picking::{PickHit, PickRequest};
+use crate::pipelines::pbr::DrawInputs;
+use crate::selection::{Selection, SelectionMode};
+use crate::streaming;
+use crate::window::{self, WindowMode};
+use crate::{PresentMode, RenderState, TextureFormat};
 
+/**
+The outcome of a command that was processed successfully, sent as `Event::CommandResult`. Every command results in
+exactly one of these or in an `Event::CommandFailed`, in the order the commands were sent, except for the picking
+commands, whose results follow a few frames later.
+ */
 #[derive(Debug)]
 pub enum CommandResult {
-    ClickQuery(u32),
+    /// The command was carried out and has nothing else to report
+    Done,
+    /// The id of the first scene of the loaded file, which is the new active scene
+    SceneLoaded(u32),
+    /// The id of the scene that `CreateScene` added
+    SceneCreated(u32),
+    /// The id of the material that `CreateMaterial` added
+    MaterialCreated(MatId),
+    /// The id of the texture that `ImportTexture` added
+    TextureImported(TexId),
+    /// The materials and textures that `PurgeUnused` removed
+    Purged(Purged),
+    /// The id of the model that `CreateModel` or `DuplicateModel` added
+    ModelCreated(u32),
+    /// The ids of the top-level models that `ImportFile` or `DuplicateSelection` added to the active scene
+    ModelsCreated(Vec<u32>),
+    /// The model and all models below it were deleted
+    ModelDeleted(u32),
+    /// The results of the commands of a `Command::Batch` in order. `None` stands for a result that is sent once the
+    /// command completes in a later frame, which none of the commands allowed in batches does, see
+    /// `Command::is_revertible`
+    Batch(Vec<Option<CommandResult>>),
+    /// The mesh at the queried pixel and the point where it was hit, `None` if the pixel is empty
+    ClickQuery(Option<PickHit>),
+    /// The unique ids of the meshes visible in the queried rectangle
+    BoxSelect(Vec<u32>),
+    /// The closest hit of a `PhysicsRaycast` or `PhysicsShapeCast`, `None` if nothing was hit
+    #[cfg(feature = "physics")]
+    PhysicsHit(Option<PhysicsHit>),
+    /// The models whose colliders overlap the shape of a `PhysicsOverlap`
+    #[cfg(feature = "physics")]
+    PhysicsOverlap(Vec<u32>),
+    /// The id of the sound that `LoadSound` loaded
+    #[cfg(feature = "audio")]
+    SoundLoaded(u32),
+    /// The id of the playback that `PlaySound` or `PlaySoundOnModel` started, see `Event::SoundFinished`
+    #[cfg(feature = "audio")]
+    SoundPlaying(u32),
 }
 
 #[derive(Debug)]
@@ -26,118 +93,428 @@ pub enum CreateModel {
         color: glam::Vec3,
         intensity: f32,
     },
+    Primitive {
+        geometry: Geometry,
+        position: glam::Vec3,
+    },
 }
 
 pub type Commands = mpsc::Sender<Command>;
 
 #[derive(Debug)]
 pub enum Command {
+    /// Loads the scenes of a glTF file into the world, next to the scenes that are loaded already, and makes the first
+    /// one the active scene
     LoadSceneFile(PathBuf),
+    /// Renders another scene of the world. The other scenes stay loaded, and the selection is cleared
+    SetActiveScene(usize),
+    /// Adds an empty scene to the world, without making it the active scene
+    CreateScene(String),
+    /// Removes a scene and its models from the world. If it was the active scene, the scene with the lowest id becomes
+    /// the active one, or no scene is active if it was the last one. Streaming cells that were loaded into it count as
+    /// unloaded
+    DeleteScene(usize),
+    RenameScene {
+        scene_id: usize,
+        name: String,
+    },
     ImportFile(PathBuf),
     CreateModel(CreateModel, Option<u32>),
+    /// Moves a model below another model, or to the top level if `new_parent_id` is None. With
+    /// `keep_world_transform`, which is what editors usually want, the model's local transform is recomputed so that it
+    /// stays where it is in the world, otherwise it keeps its local transform and moves along with its new parent
     ChangeModelParent {
         model_id: u32,
         new_parent_id: Option<u32>,
         new_scene_id: u32,
+        keep_world_transform: bool,
     },
     DeleteModel(u32),
+    /// Shows or hides a model or mesh in the active scene. Hiding a model hides its meshes and all models below it from
+    /// rendering and picking
+    SetVisibility {
+        id: u32,
+        visible: bool,
+    },
+    /// Outlines a mesh in the active scene regardless of the selection, e.g. to mark it as invalid, or stops outlining
+    /// it with None. Selected and hovered meshes are outlined with the scene's outlines instead
+    SetOutline {
+        mesh_id: u32,
+        outline: Option<Outline>,
+    },
     DuplicateModel(u32),
+    /// Finds the mesh at the pixel of the viewport and where it was hit. Picking doesn't stall the frame, the result is
+    /// sent as `CommandResult::ClickQuery` a few frames later
     QueryClick((u32, u32)),
+    /// Changes the selection of the active scene, whose meshes are outlined together, see `Meta::selection`
+    Select {
+        meshes: Vec<u32>,
+        mode: SelectionMode,
+    },
+    /// Selects the mesh at the pixel like `Select` once picking it finished, a click on nothing clears the selection
+    /// with `SelectionMode::Replace`. A click on a light's icon selects the light's model instead, see
+    /// `Meta::light_gizmos`
+    SelectAt {
+        x: u32,
+        y: u32,
+        mode: SelectionMode,
+    },
+    /// Finds the visible meshes in the rectangle between the corners `min` and `max` of the viewport. Like
+    /// `QueryClick`, the result is sent as `CommandResult::BoxSelect` a few frames later
+    BoxSelect {
+        min: (u32, u32),
+        max: (u32, u32),
+    },
+    ClearSelection,
+    /// Deletes the models of the selected meshes, see `Selection::models`
+    DeleteSelection,
+    /// Duplicates the models of the selected meshes, see `Selection::models`
+    DuplicateSelection,
+    /// Moves the models of the selected meshes by the offset in world space, see `Selection::models`. The offset is
+    /// rounded to the translation step while `Meta::snap` is enabled
+    MoveSelection(Vec3),
+    /// Moves a model in the active scene to the closest point on the grid of `Meta::snap`'s translation step, even if
+    /// snapping is disabled
+    SnapModelToGrid(u32),
+    /// Switches between `PresentMode::AutoVsync` and `PresentMode::AutoNoVsync` according to `Meta::vsync`
     SetVsync,
+    /// Reconfigures the surface with the present mode, which has to be in `Meta::supported_present_modes` or one of
+    /// the `Auto` modes
+    SetPresentMode(PresentMode),
+    /// Reconfigures the surface with the color format, which has to be in `Meta::supported_surface_formats`, and
+    /// recreates the pipelines that render into it. The GUI's renderer is recreated as well, so hooks get
+    /// `Event::DeviceRecovered` to register their textures again
+    SetSurfaceFormat(TextureFormat),
+    /// Switches the camera between the standard and the reverse-Z projection, and recreates the pipelines that test
+    /// depth for it
+    SetDepthMode(DepthMode),
+    /// Turns the camera to look along a world axis, see `Camera::set_axis_view`
+    SetCameraView(AxisView),
+    /// Adds a water surface to the active scene, replacing its existing one
+    CreateWater(Water),
+    RemoveWater,
+    /// Loads a `.cube` 3D LUT and grades the output with it, replacing the current LUT
+    LoadLut(PathBuf),
+    /// Disables color grading
+    ClearLut,
+    /// Registers a glTF file as the contents of a streaming cell, see `Meta::streaming`
+    AddStreamingCell { cell: IVec2, path: PathBuf },
+    /// Unregisters a streaming cell, removing its models from the active scene if it is loaded
+    RemoveStreamingCell(IVec2),
+    /// Adds a PBR material to the world, whose textures have to exist in the world already
+    CreateMaterial(PbrMaterialDesc),
+    UpdateMaterial {
+        id: MatId,
+        changes: PbrMaterialChanges,
+    },
+    /// Loads an image file as a texture of the world, e.g. to put it into a material with `SetMaterialTexture`
+    ImportTexture(PathBuf, TextureKind),
+    /// Puts a texture into the slot of a material, or empties the slot so that the default texture of its kind is used
+    SetMaterialTexture {
+        material: MatId,
+        slot: TextureKind,
+        texture: Option<TexId>,
+    },
+    /// Renders a mesh of any scene with another material of the world
+    AssignMaterial {
+        mesh_id: u32,
+        material: MatId,
+    },
+    /// Removes the materials that no mesh uses and the textures that no material uses, e.g. after deleting models, see
+    /// `World::purge_unused`
+    PurgeUnused,
+    /// Sets a shader parameter of a mesh in the active scene, see `ParamBlock`
+    SetMeshParam { mesh_id: u32, name: String, value: Vec4 },
+    /// Drives a property of a model in the active scene by an expression, replacing the property's existing binding,
+    /// see `Expression` for the syntax
+    Bind {
+        model_id: u32,
+        property: Property,
+        expression: String,
+    },
+    Unbind { model_id: u32, property: Property },
+    /// Makes a model in the active scene a rigid body with the collider, replacing its body and collider. Simulated
+    /// with the renderer's `physics` feature
+    AddRigidBody {
+        model_id: u32,
+        body: RigidBody,
+        collider: Option<Collider>,
+    },
+    /// Removes the rigid body and the collider of a model in the active scene
+    RemoveRigidBody(u32),
+    /// Gives every model with meshes at and below a model in the active scene a collider of the shape, replacing their
+    /// colliders, e.g. `ColliderShape::TriMesh` to make an imported level solid. Models without a rigid body become
+    /// fixed obstacles
+    GenerateColliders {
+        model_id: u32,
+        shape: ColliderShape,
+    },
+    /// Sets the velocities of the rigid body of a model in the active scene, in world units and radians per second
+    SetVelocity {
+        model_id: u32,
+        linear: Vec3,
+        angular: Vec3,
+    },
+    /// Casts a ray through the colliders of the active scene's physics simulation, ignoring the collider of the model
+    /// `exclude`. Results in `CommandResult::PhysicsHit`. Needs the renderer's `physics` feature
+    #[cfg(feature = "physics")]
+    PhysicsRaycast {
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        exclude: Option<u32>,
+    },
+    /// Finds the colliders that overlap the shape, placed at the position with the rotation. Results in
+    /// `CommandResult::PhysicsOverlap`
+    #[cfg(feature = "physics")]
+    PhysicsOverlap {
+        shape: ColliderShape,
+        position: Vec3,
+        rotation: Quat,
+        exclude: Option<u32>,
+    },
+    /// Moves the shape along the direction and finds the first collider it touches. Results in
+    /// `CommandResult::PhysicsHit`
+    #[cfg(feature = "physics")]
+    PhysicsShapeCast {
+        shape: ColliderShape,
+        position: Vec3,
+        rotation: Quat,
+        direction: Vec3,
+        max_distance: f32,
+        exclude: Option<u32>,
+    },
+    /// Bakes an imposter of the model, which replaces it beyond `Meta::imposter_lod`'s distance
+    BakeImposter(u32),
+    RemoveImposter(u32),
+    SetWindowTitle(String),
+    /// Switches the window between windowed, borderless and exclusive fullscreen on its current monitor
+    SetWindowMode(WindowMode),
+    /// Starts a server on the address that DCC plug-ins connect to in order to push edits into the active scene, see
+    /// `LiveLinkMessage`. Replaces a running live link on another address. The protocol has no authentication, anyone
+    /// who can connect can edit the scene, so only loopback addresses are accepted unless `allow_remote` is set. Set it
+    /// only on trusted networks
+    StartLiveLink {
+        address: SocketAddr,
+        allow_remote: bool,
+    },
+    StopLiveLink,
+    /// Compiles the rhai script and runs it on the model every frame, replacing the model's script, see `Scripts`
+    #[cfg(feature = "scripting")]
+    AttachScript {
+        model_id: u32,
+        path: PathBuf,
+    },
+    #[cfg(feature = "scripting")]
+    DetachScript(u32),
+    /// Makes the scene's time run this many times as fast as real time, from 0.1 to 4, see `TimeControl`
+    SetTimeScale(f32),
+    /// Stops or resumes the scene's time
+    SetPaused(bool),
+    /// Advances the paused scene's time by a single frame
+    StepFrame,
+    /// Stops or resumes rendering while commands, the hook, scripts and physics keep being updated. Resuming shows
+    /// the window if it was hidden, see `RunConfig::simulation_only`
+    SetSimulationOnly(bool),
+    /// Reads a WAV, FLAC, Vorbis or MP3 file into memory so that it can be played
+    #[cfg(feature = "audio")]
+    LoadSound(PathBuf),
+    /// Removes a loaded sound and stops its playbacks
+    #[cfg(feature = "audio")]
+    UnloadSound(u32),
+    /// Plays a loaded sound with a volume between 0 and 1 (or above, to amplify it), over and over if `looping`.
+    /// Playbacks pause while the scene's time is paused
+    #[cfg(feature = "audio")]
+    PlaySound {
+        sound_id: u32,
+        volume: f32,
+        looping: bool,
+    },
+    /// Plays a loaded sound from a model of the active scene, panned and attenuated relative to the camera as the
+    /// model and the camera move. Stops when the model is removed
+    #[cfg(feature = "audio")]
+    PlaySoundOnModel {
+        sound_id: u32,
+        model_id: u32,
+        volume: f32,
+        looping: bool,
+        falloff: Falloff,
+    },
+    /// Stops a playback of `PlaySound` or `PlaySoundOnModel`
+    #[cfg(feature = "audio")]
+    StopSound(u32),
+    #[cfg(feature = "audio")]
+    SetSoundVolume {
+        playback_id: u32,
+        volume: f32,
+    },
+    /// Scales the volume of every playback
+    #[cfg(feature = "audio")]
+    SetMasterVolume(f32),
+    /// Processes the commands in order within the same frame, so that the hook and other commands can't observe or
+    /// interleave with a partially applied edit. The batch is applied completely or not at all: if a command fails,
+    /// the commands before it are reverted and the batch fails as a whole. Only commands that can be reverted are
+    /// accepted, see `Command::is_revertible`; a batch with any other command fails without applying anything.
+    /// There is no undo history yet; once there is one, a batch is meant to become a single step of it
+    Batch(Vec<Command>),
 }
 
 impl Command {
     pub(crate) fn process(self, state: &mut RenderState, event_sender: mpsc::Sender<Event>) {
         debug!("Processing command: {:?}", self);
-        match self {
-            Command::LoadSceneFile(path) => {
-                let textures = TextureManager::new(&state.device, &state.queue);
-                let materials = MaterialManager::new(
-                    &state.device,
-                    &state.queue,
-                    &state.pbr_pipeline.mat_bind_group_layout,
-                    &state.pbr_pipeline.tex_bind_group_layout,
-                    &textures,
-                );
-                state.world = World {
-                    scenes: HashMap::new(),
-                    active_scene: 0,
-                    materials,
-                    textures,
-                };
+        let command = format!("{:?}", self);
+        if let Some(crash_reporter) = &state.crash_reporter {
+            crash_reporter.record_command(&command);
+        }
+        match self.try_process(state, &event_sender) {
+            Ok(result) => {
+                debug!("Finished processing command.");
+                if let Some(result) = result {
+                    let _ = event_sender.send(Event::CommandResult(result));
+                }
+            }
+            Err(err) => {
+                error!("Command {} failed: {:#}", command, err);
+                // the hook may have dropped its receiver, in which case nobody is interested in the event
+                let _ = event_sender.send(Event::CommandFailed {
+                    command,
+                    error: format!("{:#}", err),
+                });
+            }
+        }
+    }
 
-                let mut scenes = load_gltf(
+    /**
+    Returns the result to send, or `None` if it is sent once the command completes in a later frame.
+     */
+    pub(crate) fn try_process(
+        self,
+        state: &mut RenderState,
+        event_sender: &mpsc::Sender<Event>,
+    ) -> Result<Option<CommandResult>> {
+        let result = match self {
+            Command::LoadSceneFile(path) => {
+                // into the world's managers, so that the scenes that are already loaded stay resident
+                let scenes = load_gltf(
                     &path,
                     &state.device,
                     &state.queue,
-                    &state.pbr_pipeline.tex_bind_group_layout,
-                    &state.pbr_pipeline.mat_bind_group_layout,
-                    &state.pbr_pipeline.mesh_bind_group_layout,
-                    &state.pbr_pipeline.light_bind_group_layout,
-                    &mut state.world.textures,
-                    &mut state.world.materials,
+                    state.pbr_pipeline.gltf_layouts(),
+                    &mut state.world.assets.textures,
+                    &mut state.world.assets.materials,
                 );
-                let mut first = scenes.remove(0);
-                let id = state.world.scenes.keys().max().unwrap_or(&0) + 1;
-                state.world.active_scene = id;
-                first.id = id as u32;
-                state.world.scenes.insert(first.id as usize, first);
-
-                state.camera.update_light_count(
-                    state
-                        .world
-                        .get_active_scene()
-                        .expect("No active scene")
-                        .light_buffer
-                        .len() as u32,
-                );
-                state.camera.update_view(&state.queue);
-                state.world.materials.update_dirty(&state.queue);
+                let error = scenes.as_ref().err().map(|e| format!("{:#}", e));
+                state.world.assets.finish_load(&path, error);
+                let scenes = scenes?;
+                if scenes.is_empty() {
+                    bail!("{:?} doesn't contain a scene", path);
+                }
+                let first_id = state.world.scenes.keys().max().unwrap_or(&0) + 1;
+                let scene_ids = (first_id..first_id + scenes.len()).collect();
+                for (index, mut scene) in scenes.into_iter().enumerate() {
+                    scene.id = (first_id + index) as u32;
+                    state.world.scenes.insert(first_id + index, scene);
+                }
+                state.hot_reload.watch_scenes(&path, scene_ids);
+                state
+                    .world
+                    .assets
+                    .materials
+                    .update_dirty(&state.queue, &state.world.assets.textures);
+                Command::SetActiveScene(first_id).try_process(state, event_sender)?;
+                CommandResult::SceneLoaded(first_id as u32)
+            }
+            Command::SetActiveScene(scene_id) => {
+                if !state.world.scenes.contains_key(&scene_id) {
+                    bail!("Scene {} not found", scene_id);
+                }
+                if state.world.active_scene != scene_id {
+                    state.world.active_scene = scene_id;
+                    // the selection only ever contains meshes of the active scene
+                    if !state.meta.selection.is_empty() {
+                        state.meta.selection.clear();
+                        state.selection_changed();
+                    }
+                }
+                state.update_light_count();
                 state.world.update_active_scene(&state.queue); // updates lights and mesh info buffers
+                CommandResult::Done
+            }
+            Command::CreateScene(name) => {
+                let id = state.world.scenes.keys().max().unwrap_or(&0) + 1;
+                let scene = state.empty_scene(id, name.into_boxed_str());
+                state.world.scenes.insert(id, scene);
+                CommandResult::SceneCreated(id as u32)
+            }
+            Command::DeleteScene(scene_id) => {
+                let scene = state
+                    .world
+                    .scenes
+                    .remove(&scene_id)
+                    .with_context(|| format!("Scene {} not found", scene_id))?;
+                let removed_ids: Vec<u32> = scene.iter_models_deep().map(|model| model.id).collect();
+                for id in removed_ids.iter() {
+                    state.imposter_pipeline.remove(*id);
+                    for other_scene in state.world.scenes.values_mut() {
+                        other_scene.remove_joints_to(*id);
+                    }
+                }
+                state.hot_reload.forget_scene(scene_id, &removed_ids);
+                for coordinates in state.streamer.forget_models(&removed_ids) {
+                    let _ = state.event_channel.0.send(Event::CellUnloaded(coordinates));
+                }
+                #[cfg(feature = "physics")]
+                state.physics.forget_scene(&scene);
+                if state.world.active_scene == scene_id {
+                    if let Some(&next) = state.world.scenes.keys().min() {
+                        return Command::SetActiveScene(next).try_process(state, event_sender);
+                    }
+                    // scene ids start at 1, so no scene is active until one is created or loaded
+                    state.world.active_scene = 0;
+                    state.update_light_count();
+                    // pruning needs an active scene
+                    if !state.meta.selection.is_empty() {
+                        state.meta.selection.clear();
+                        state.selection_changed();
+                    }
+                }
+                CommandResult::Done
+            }
+            Command::RenameScene { scene_id, name } => {
+                let scene = state
+                    .world
+                    .scenes
+                    .get_mut(&scene_id)
+                    .with_context(|| format!("Scene {} not found", scene_id))?;
+                scene.name = Some(name.into_boxed_str());
+                CommandResult::Done
             }
             Command::ImportFile(path) => {
                 info!("Importing file: {:?}", path);
-                if path.extension().unwrap() == "glb" || path.extension().unwrap() == "gltf" {
-                    let mut scenes = load_gltf(
-                        &path,
-                        &state.device,
-                        &state.queue,
-                        &state.pbr_pipeline.tex_bind_group_layout,
-                        &state.pbr_pipeline.mat_bind_group_layout,
-                        &state.pbr_pipeline.mesh_bind_group_layout,
-                        &state.pbr_pipeline.light_bind_group_layout,
-                        &mut state.world.textures,
-                        &mut state.world.materials,
-                    );
-
-                    let first = scenes.remove(0);
-                    state
-                        .world
-                        .scenes
-                        .get_mut(&state.world.active_scene)
-                        .expect("Scene does not exist")
-                        .join(
-                            first,
-                            &state.device,
-                            &state.queue,
-                            &state.world.materials,
-                            &state.pbr_pipeline.mesh_bind_group_layout,
-                            &state.pbr_pipeline.light_bind_group_layout,
-                        );
-                    state.world.materials.update_dirty(&state.queue);
-                    state.camera.update_light_count(
-                        state
-                            .world
-                            .get_active_scene()
-                            .expect("No active scene")
-                            .light_buffer
-                            .len() as u32,
-                    );
-                    state.camera.update_view(&state.queue);
-                    state.world.update_active_scene(&state.queue); // updates lights and mesh info buffers
-                } else {
-                    error!("Unsupported file type: {:?}", path);
+                let extension = path.extension().and_then(OsStr::to_str);
+                if !matches!(extension, Some("glb") | Some("gltf")) {
+                    bail!("Unsupported file type: {:?}", path);
                 }
+                let scenes = load_gltf(
+                    &path,
+                    &state.device,
+                    &state.queue,
+                    state.pbr_pipeline.gltf_layouts(),
+                    &mut state.world.assets.textures,
+                    &mut state.world.assets.materials,
+                );
+                let error = scenes.as_ref().err().map(|e| format!("{:#}", e));
+                state.world.assets.finish_load(&path, error);
+                let mut scenes = scenes?;
+                if scenes.is_empty() {
+                    bail!("{:?} doesn't contain a scene", path);
+                }
+
+                let scene = scenes.remove(0);
+                let model_ids: Vec<u32> = scene.models.iter().map(|model| model.id).collect();
+                state.join_into_active_scene(scene);
+                state.hot_reload.watch_models(&path, model_ids.clone());
+                CommandResult::ModelsCreated(model_ids)
             }
             Command::CreateModel(info, parent_id) => match info {
                 CreateModel::Light {
@@ -161,89 +538,176 @@ impl Command {
                         )),
                     );
                     model.update_transforms(Mat4::IDENTITY);
+                    let model_id = model.id;
+                    state.ensure_active_scene();
                     state
                         .world
                         .scenes
                         .get_mut(&state.world.active_scene)
-                        .expect("Scene does not exist")
+                        .context("No active scene")?
                         .add_model(
                             model,
                             parent_id,
                             &state.device,
                             &state.queue,
-                            &state.world.materials,
+                            &state.world.assets.materials,
                             &state.pbr_pipeline.mesh_bind_group_layout,
                             &state.pbr_pipeline.light_bind_group_layout,
-                        );
-                    state.camera.update_light_count(
-                        state
-                            .world
-                            .get_active_scene()
-                            .expect("No active scene")
-                            .light_buffer
-                            .len() as u32,
+                        )?;
+                    state.update_light_count();
+                    state.world.update_active_scene(&state.queue); // updates lights and mesh info buffers
+                    CommandResult::ModelCreated(model_id)
+                }
+                CreateModel::Primitive { geometry, position } => {
+                    // same flip as the neutral transform of glTF imports, so that primitives are the same way up
+                    let mut neutral = Mat4::IDENTITY;
+                    neutral.y_axis *= -1.0;
+                    let mesh = geometry.new_mesh(state.world.assets.materials.default_material);
+                    let mut model = Model::from(
+                        vec![mesh],
+                        Some(geometry.name().into()),
+                        vec![],
+                        Mat4::from_translation(position) * neutral,
+                        None,
                     );
-                    state.camera.update_view(&state.queue);
+                    model.update_transforms(Mat4::IDENTITY);
+                    let model_id = model.id;
+                    state.ensure_active_scene();
+                    state
+                        .world
+                        .scenes
+                        .get_mut(&state.world.active_scene)
+                        .context("No active scene")?
+                        .add_model(
+                            model,
+                            parent_id,
+                            &state.device,
+                            &state.queue,
+                            &state.world.assets.materials,
+                            &state.pbr_pipeline.mesh_bind_group_layout,
+                            &state.pbr_pipeline.light_bind_group_layout,
+                        )?;
                     state.world.update_active_scene(&state.queue); // updates lights and mesh info buffers
+                    CommandResult::ModelCreated(model_id)
                 }
             },
             Command::ChangeModelParent {
                 model_id,
                 new_parent_id,
                 new_scene_id,
+                keep_world_transform,
             } => {
+                // checked up front, so that the model isn't lost when it can't be added to the new scene
+                let new_scene = state
+                    .world
+                    .scenes
+                    .get(&(new_scene_id as usize))
+                    .with_context(|| format!("Scene {} not found", new_scene_id))?;
+                let new_parent_transform = match new_parent_id {
+                    Some(parent_id) => {
+                        let moved_model = state
+                            .world
+                            .scenes
+                            .values()
+                            .find_map(|scene| scene.get_model(model_id))
+                            .with_context(|| format!("Model {} not found", model_id))?;
+                        if parent_id == model_id || moved_model.children.iter_deep().any(|m| m.id == parent_id) {
+                            bail!("Model {} can't be moved below itself", model_id);
+                        }
+                        new_scene
+                            .get_model(parent_id)
+                            .with_context(|| format!("Model {} not found in scene {}", parent_id, new_scene_id))?
+                            .global_transform()
+                    }
+                    None => Mat4::IDENTITY,
+                };
                 let mut model = None;
-                for (_, scene) in state.world.scenes.iter_mut() {
-                    if let Some(found_model) = scene.remove_model(model_id, &state.queue, &state.world.materials) {
-                        model = Some(found_model);
+                for (scene_id, scene) in state.world.scenes.iter_mut() {
+                    if let Some(found_model) =
+                        scene.remove_model(model_id, &state.device, &state.queue, &state.world.assets.materials)
+                    {
+                        model = Some((*scene_id, found_model));
                         break;
                     }
                 }
-                if let Some(model) = model {
-                    state
-                        .world
-                        .scenes
-                        .get_mut(&(new_scene_id as usize))
-                        .expect("Scene does not exist")
-                        .add_model(
-                            model,
-                            new_parent_id,
-                            &state.device,
-                            &state.queue,
-                            &state.world.materials,
-                            &state.pbr_pipeline.mesh_bind_group_layout,
-                            &state.pbr_pipeline.light_bind_group_layout,
-                        );
-                } else {
-                    error!("Model not found: {}", model_id);
+                let (old_scene_id, mut model) = model.with_context(|| format!("Model {} not found", model_id))?;
+                if keep_world_transform {
+                    model.local_transform = new_parent_transform.inverse() * model.global_transform();
                 }
+                model.update_transforms(new_parent_transform);
+                state
+                    .world
+                    .scenes
+                    .get_mut(&(new_scene_id as usize))
+                    .with_context(|| format!("Scene {} not found", new_scene_id))?
+                    .add_model(
+                        model,
+                        new_parent_id,
+                        &state.device,
+                        &state.queue,
+                        &state.world.assets.materials,
+                        &state.pbr_pipeline.mesh_bind_group_layout,
+                        &state.pbr_pipeline.light_bind_group_layout,
+                    )?;
+                if old_scene_id != new_scene_id as usize {
+                    // the lights of both scenes changed, one of them may be the active one
+                    state.update_light_count();
+                }
+                CommandResult::Done
             }
             Command::DeleteModel(model_id) => {
+                let mut removed = None;
                 for (_, scene) in state.world.scenes.iter_mut() {
-                    if scene
-                        .remove_model(model_id, &state.queue, &state.world.materials)
-                        .is_some()
-                    {
-                        state.camera.update_light_count(
-                            state
-                                .world
-                                .get_active_scene()
-                                .expect("No active scene")
-                                .light_buffer
-                                .len() as u32,
-                        );
-                        state.camera.update_view(&state.queue);
+                    removed = scene.remove_model(model_id, &state.device, &state.queue, &state.world.assets.materials);
+                    if removed.is_some() {
                         break;
                     }
                 }
+                let removed = removed.with_context(|| format!("Model {} not found", model_id))?;
+                state.imposter_pipeline.remove(removed.id);
+                // joints of other models must not dangle
+                let removed_ids: Vec<u32> = std::iter::once(removed.id)
+                    .chain(removed.children.iter_deep().map(|child| child.id))
+                    .collect();
+                for scene in state.world.scenes.values_mut() {
+                    for id in removed_ids.iter() {
+                        scene.remove_joints_to(*id);
+                    }
+                }
+                state.update_light_count();
+                CommandResult::ModelDeleted(model_id)
+            }
+            Command::SetVisibility { id, visible } => {
+                let scene = state
+                    .world
+                    .scenes
+                    .get_mut(&state.world.active_scene)
+                    .context("No active scene")?;
+                if !scene.set_visibility(id, visible) {
+                    bail!("No model or mesh {} in the active scene", id);
+                }
+                CommandResult::Done
+            }
+            Command::SetOutline { mesh_id, outline } => {
+                let scene = state
+                    .world
+                    .scenes
+                    .get_mut(&state.world.active_scene)
+                    .context("No active scene")?;
+                scene
+                    .get_mesh_mut(mesh_id)
+                    .with_context(|| format!("No mesh {} in the active scene", mesh_id))?
+                    .set_outline(outline);
+                CommandResult::Done
             }
             Command::DuplicateModel(model_id) => {
+                let mut new_model_id = None;
                 for (_, scene) in state.world.scenes.iter_mut() {
                     let mut new_model = None;
                     for model in scene.iter_models_deep() {
                         if model.id == model_id {
                             new_model = Some(Model::from(
-                                model.meshes.iter().map(|mesh| mesh.clone(&state.device)).collect(),
+                                model.meshes.iter().map(|mesh| mesh.duplicate()).collect(),
                                 Some(format!("{} duplicate", model.name.clone().unwrap_or("".into())).into_boxed_str()),
                                 vec![],
                                 model.local_transform,
@@ -253,58 +717,816 @@ impl Command {
                         }
                     }
                     if let Some(new_model) = new_model {
+                        new_model_id = Some(new_model.id);
                         scene.add_model(
                             new_model,
                             None,
                             &state.device,
                             &state.queue,
-                            &state.world.materials,
+                            &state.world.assets.materials,
                             &state.pbr_pipeline.mesh_bind_group_layout,
                             &state.pbr_pipeline.light_bind_group_layout,
-                        );
+                        )?;
                     }
                 }
-                state.camera.update_light_count(
-                    state
-                        .world
-                        .get_active_scene()
-                        .expect("No active scene")
-                        .light_buffer
-                        .len() as u32,
-                );
-                state.camera.update_view(&state.queue);
+                let new_model_id = new_model_id.with_context(|| format!("Model {} not found", model_id))?;
+                state.update_light_count();
+                CommandResult::ModelCreated(new_model_id)
             }
             Command::QueryClick((x, y)) => {
-                let Some(scene) = state.world.get_active_scene() else {
-                    event_sender
-                        .send(Event::CommandResult(CommandResult::ClickQuery(0)))
-                        .unwrap();
-                    return;
+                if state.world.get_active_scene().is_none() {
+                    return Ok(Some(CommandResult::ClickQuery(None)));
+                }
+                state.object_picking_pipeline.request(PickRequest::Query, x, y);
+                return Ok(None);
+            }
+            Command::Select { meshes, mode } => {
+                let scene = state.world.get_active_scene().context("No active scene")?;
+                for mesh_id in meshes.iter() {
+                    let found = scene
+                        .iter_models_deep()
+                        .any(|model| model.meshes.iter().any(|mesh| mesh.id == *mesh_id));
+                    if !found {
+                        bail!("No mesh {} in the active scene", mesh_id);
+                    }
+                }
+                state.meta.selection.select(&meshes, mode);
+                state.selection_changed();
+                CommandResult::Done
+            }
+            Command::SelectAt { x, y, mode } => {
+                let scene = state.world.get_active_scene().context("No active scene")?;
+                let size = (state.surface_config.width, state.surface_config.height);
+                // light icons are drawn over the meshes, so they are hit first
+                if let Some(model_id) = state.meta.light_gizmos.hit((x, y), size, &state.camera, scene) {
+                    state.meta.selection.select_models(&[model_id], mode);
+                    state.selection_changed();
+                } else {
+                    state.object_picking_pipeline.request(PickRequest::Select(mode), x, y);
+                }
+                CommandResult::Done
+            }
+            Command::BoxSelect { min, max } => {
+                let min_corner = (min.0.min(max.0), min.1.min(max.1));
+                let max_corner = (min.0.max(max.0), min.1.max(max.1));
+                if state.world.get_active_scene().is_none() {
+                    return Ok(Some(CommandResult::BoxSelect(vec![])));
+                }
+                state.object_picking_pipeline.request_region(min_corner, max_corner);
+                return Ok(None);
+            }
+            Command::ClearSelection => {
+                state.meta.selection.clear();
+                state.selection_changed();
+                CommandResult::Done
+            }
+            Command::DeleteSelection => {
+                let scene = state.world.get_active_scene().context("No active scene")?;
+                for model_id in state.meta.selection.models(scene) {
+                    Command::DeleteModel(model_id).try_process(state, event_sender)?;
+                }
+                CommandResult::Done
+            }
+            Command::DuplicateSelection => {
+                let scene = state.world.get_active_scene().context("No active scene")?;
+                let mut model_ids = vec![];
+                for model_id in state.meta.selection.models(scene) {
+                    if let Some(CommandResult::ModelCreated(id)) =
+                        Command::DuplicateModel(model_id).try_process(state, event_sender)?
+                    {
+                        model_ids.push(id);
+                    }
+                }
+                CommandResult::ModelsCreated(model_ids)
+            }
+            Command::MoveSelection(offset) => {
+                let scene = state
+                    .world
+                    .scenes
+                    .get_mut(&state.world.active_scene)
+                    .context("No active scene")?;
+                let offset = state.meta.snap.offset(offset);
+                for model_id in state.meta.selection.models(scene) {
+                    translate_model(scene.get_model_mut(model_id).unwrap(), offset);
+                }
+                CommandResult::Done
+            }
+            Command::SnapModelToGrid(model_id) => {
+                let snap = state.meta.snap;
+                let model = active_model_mut(state, model_id)?;
+                let position = model.global_transform().w_axis.truncate();
+                translate_model(model, snap.position(position) - position);
+                CommandResult::Done
+            }
+            Command::SetVsync => {
+                let present_mode = if state.meta.vsync {
+                    PresentMode::AutoVsync
+                } else {
+                    PresentMode::AutoNoVsync
                 };
-
-                let query_result = state.object_picking_pipeline.query_click(
+                return Command::SetPresentMode(present_mode).try_process(state, event_sender);
+            }
+            Command::SetPresentMode(present_mode) => {
+                let auto = matches!(present_mode, PresentMode::AutoVsync | PresentMode::AutoNoVsync);
+                if !auto && !state.meta.supported_present_modes().contains(&present_mode) {
+                    bail!("Present mode {:?} isn't supported by the surface", present_mode);
+                }
+                state.surface_config.present_mode = present_mode;
+                state.surface.configure(&state.device, &state.surface_config);
+                state.meta.present_mode = present_mode;
+                state.meta.vsync = matches!(
+                    present_mode,
+                    PresentMode::AutoVsync | PresentMode::Fifo | PresentMode::FifoRelaxed
+                );
+                CommandResult::Done
+            }
+            Command::SetSurfaceFormat(format) => {
+                if !state.meta.supported_surface_formats().contains(&format) {
+                    bail!("Surface format {:?} isn't supported by the surface", format);
+                }
+                if format != state.surface_config.format {
+                    state.set_surface_format(format);
+                }
+                CommandResult::Done
+            }
+            Command::SetCameraView(view) => {
+                state.camera.set_axis_view(view);
+                CommandResult::Done
+            }
+            Command::SetDepthMode(depth_mode) => {
+                if depth_mode != state.camera.depth_mode() {
+                    state.set_depth_mode(depth_mode);
+                }
+                CommandResult::Done
+            }
+            Command::CreateWater(water) => {
+                state.ensure_active_scene();
+                if let Some(scene) = state.world.scenes.get_mut(&state.world.active_scene) {
+                    scene.water = Some(water);
+                }
+                CommandResult::Done
+            }
+            Command::RemoveWater => {
+                if let Some(scene) = state.world.scenes.get_mut(&state.world.active_scene) {
+                    scene.water = None;
+                }
+                CommandResult::Done
+            }
+            Command::LoadLut(path) => {
+                let lut = Lut::load_cube(&path)?;
+                let max_size = state.device.limits().max_texture_dimension_3d;
+                if lut.size > max_size {
+                    bail!("LUT_3D_SIZE {} exceeds the device's maximum 3D texture size of {}", lut.size, max_size);
+                }
+                info!("Loaded LUT {:?} of size {}", lut.title.as_deref().unwrap_or("untitled"), lut.size);
+                state.grading_pipeline.set_lut(&state.device, &state.queue, &lut);
+                CommandResult::Done
+            }
+            Command::ClearLut => {
+                state.grading_pipeline.clear_lut();
+                CommandResult::Done
+            }
+            Command::AddStreamingCell { cell, path } => {
+                state.streamer.add_cell(cell, path);
+                CommandResult::Done
+            }
+            Command::RemoveStreamingCell(cell) => {
+                let model_ids = state.streamer.remove_cell(cell);
+                if !model_ids.is_empty() {
+                    streaming::remove_models(state, &model_ids);
+                    let _ = event_sender.send(Event::CellUnloaded(cell));
+                }
+                CommandResult::Done
+            }
+            Command::CreateMaterial(desc) => {
+                if let Some(texture) = desc.texture_ids().find(|id| !state.world.assets.textures.contains(*id)) {
+                    bail!("Texture {:?} not found", texture);
+                }
+                let id = state.world.assets.materials.create_material(
+                    desc,
+                    &state.device,
+                    &state.queue,
+                    &state.pbr_pipeline.mat_bind_group_layout,
+                    &state.pbr_pipeline.tex_bind_group_layout,
+                    &state.world.assets.textures,
+                );
+                CommandResult::MaterialCreated(id)
+            }
+            Command::UpdateMaterial { id, changes } => {
+                if !state.world.assets.materials.contains(id) {
+                    bail!("Material {:?} not found", id);
+                }
+                // uploaded with the other dirty materials
+                let Material::Pbr(material) = state.world.assets.materials.get_material_mut(id);
+                changes.apply(material);
+                CommandResult::Done
+            }
+            Command::ImportTexture(path, kind) => {
+                let image = image::open(&path).with_context(|| format!("Couldn't load texture {:?}", path));
+                let error = image.as_ref().err().map(|e| format!("{:#}", e));
+                state.world.assets.finish_load(&path, error);
+                let image = image?;
+                let name = path.file_name().and_then(OsStr::to_str);
+                // the texture arrays are rebuilt once the commands are processed. Importing an image that was imported
+                // before returns the existing texture
+                let id = state
+                    .world
+                    .assets
+                    .textures
+                    .add_image(&state.device, &state.queue, &image, name, kind);
+                state.hot_reload.watch_texture(&path, id, kind);
+                CommandResult::TextureImported(id)
+            }
+            Command::SetMaterialTexture {
+                material,
+                slot,
+                texture,
+            } => {
+                if !state.world.assets.materials.contains(material) {
+                    bail!("Material {:?} not found", material);
+                }
+                if let Some(texture) = texture.filter(|texture| !state.world.assets.textures.contains(*texture)) {
+                    bail!("Texture {:?} not found", texture);
+                }
+                let Material::Pbr(pbr) = state.world.assets.materials.get_material_mut(material);
+                if !pbr.set_texture(slot, texture) {
+                    bail!("Materials have no {:?} texture", slot);
+                }
+                pbr.create_texture_bind_group(
+                    &state.device,
+                    &state.pbr_pipeline.tex_bind_group_layout,
+                    &state.world.assets.textures,
+                );
+                CommandResult::Done
+            }
+            Command::AssignMaterial { mesh_id, material } => {
+                if !state.world.assets.materials.contains(material) {
+                    bail!("Material {:?} not found", material);
+                }
+                let mesh = state
+                    .world
+                    .scenes
+                    .values_mut()
+                    .find_map(|scene| scene.get_mesh_mut(mesh_id))
+                    .with_context(|| format!("Mesh {} not found", mesh_id))?;
+                mesh.set_material(material);
+                state.world.update_active_scene(&state.queue); // updates lights and mesh info buffers
+                CommandResult::Done
+            }
+            Command::PurgeUnused => {
+                let layout = &state.pbr_pipeline.mat_bind_group_layout;
+                let purged = state.world.purge_unused(&state.device, &state.queue, layout);
+                // the texture arrays are rebuilt once the commands are processed, thumbnails of removed textures are
+                // unregistered before the next frame
+                CommandResult::Purged(purged)
+            }
+            Command::SetMeshParam { mesh_id, name, value } => {
+                let mesh = state
+                    .world
+                    .scenes
+                    .get_mut(&state.world.active_scene)
+                    .and_then(|scene| scene.get_mesh_mut(mesh_id))
+                    .with_context(|| format!("Mesh {} not found in the active scene", mesh_id))?;
+                if !mesh.set_param(&name, value) {
+                    bail!("All parameter slots of mesh {} are taken, can't set {}", mesh_id, name);
+                }
+                CommandResult::Done
+            }
+            Command::Bind {
+                model_id,
+                property,
+                expression,
+            } => {
+                let scene = state
+                    .world
+                    .scenes
+                    .get_mut(&state.world.active_scene)
+                    .with_context(|| format!("No active scene to bind model {} in", model_id))?;
+                if scene.get_model(model_id).is_none() {
+                    bail!("Model {} not found in the active scene", model_id);
+                }
+                let parsed = Expression::parse(&expression)
+                    .map_err(|e| anyhow!("Couldn't parse binding expression '{}': {}", expression, e))?;
+                scene.bind(Binding {
+                    model: model_id,
+                    property,
+                    expression: parsed,
+                });
+                CommandResult::Done
+            }
+            Command::Unbind { model_id, property } => {
+                if let Some(scene) = state.world.scenes.get_mut(&state.world.active_scene) {
+                    scene.unbind(model_id, property);
+                }
+                CommandResult::Done
+            }
+            Command::AddRigidBody {
+                model_id,
+                body,
+                collider,
+            } => {
+                let model = active_model_mut(state, model_id)?;
+                model.rigid_body = Some(body);
+                model.collider = collider;
+                CommandResult::Done
+            }
+            Command::RemoveRigidBody(model_id) => {
+                let model = active_model_mut(state, model_id)?;
+                model.rigid_body = None;
+                model.collider = None;
+                CommandResult::Done
+            }
+            Command::GenerateColliders { model_id, shape } => {
+                fn generate(model: &mut Model, shape: ColliderShape) {
+                    if !model.meshes.is_empty() {
+                        model.collider = Some(Collider::new(shape));
+                    }
+                    for child in model.children.iter_mut() {
+                        generate(child, shape);
+                    }
+                }
+                generate(active_model_mut(state, model_id)?, shape);
+                CommandResult::Done
+            }
+            Command::SetVelocity {
+                model_id,
+                linear,
+                angular,
+            } => {
+                let body = active_model_mut(state, model_id)?
+                    .rigid_body
+                    .as_mut()
+                    .with_context(|| format!("Model {} has no rigid body", model_id))?;
+                body.linear_velocity = linear;
+                body.angular_velocity = angular;
+                CommandResult::Done
+            }
+            #[cfg(feature = "physics")]
+            Command::PhysicsRaycast {
+                origin,
+                direction,
+                max_distance,
+                exclude,
+            } => CommandResult::PhysicsHit(state.physics.raycast(origin, direction, max_distance, exclude)?),
+            #[cfg(feature = "physics")]
+            Command::PhysicsOverlap {
+                shape,
+                position,
+                rotation,
+                exclude,
+            } => CommandResult::PhysicsOverlap(state.physics.overlap(&shape, position, rotation, exclude)?),
+            #[cfg(feature = "physics")]
+            Command::PhysicsShapeCast {
+                shape,
+                position,
+                rotation,
+                direction,
+                max_distance,
+                exclude,
+            } => {
+                let hit = state
+                    .physics
+                    .shape_cast(&shape, position, rotation, direction, max_distance, exclude)?;
+                CommandResult::PhysicsHit(hit)
+            }
+            Command::BakeImposter(model_id) => {
+                let scene = state
+                    .world
+                    .get_active_scene()
+                    .context("No active scene to bake an imposter in")?;
+                let model = scene
+                    .iter_models_deep()
+                    .find(|model| model.id == model_id)
+                    .with_context(|| format!("Model {} not found in the active scene", model_id))?;
+                let meshes = imposter_meshes(scene, model);
+                state.imposter_pipeline.bake(
                     &state.device,
                     &state.queue,
-                    x,
-                    y,
-                    &scene.iter_meshes().collect::<Vec<_>>(),
-                    &scene.mesh_buffer,
+                    model_id,
+                    &state.pbr_pipeline,
+                    &DrawInputs::scene(scene, &meshes, &state.world.assets.materials, &state.camera),
                     &state.camera,
                 );
-                debug!("Query result: {}", query_result);
-                event_sender
-                    .send(Event::CommandResult(CommandResult::ClickQuery(query_result)))
-                    .unwrap();
+                CommandResult::Done
             }
-            Command::SetVsync => {
-                state.surface_config.present_mode = if state.meta.vsync {
-                    wgpu::PresentMode::AutoVsync
-                } else {
-                    wgpu::PresentMode::AutoNoVsync
+            Command::RemoveImposter(model_id) => {
+                state.imposter_pipeline.remove(model_id);
+                CommandResult::Done
+            }
+            Command::SetWindowTitle(title) => {
+                state.window.set_title(&title);
+                CommandResult::Done
+            }
+            Command::SetWindowMode(window_mode) => {
+                let monitor = state.window.current_monitor();
+                state.window.set_fullscreen(window::fullscreen(window_mode, monitor));
+                state.meta.window_mode = window_mode;
+                CommandResult::Done
+            }
+            Command::StartLiveLink { address, allow_remote } => {
+                if !allow_remote && !address.ip().is_loopback() {
+                    bail!("Live link address {} isn't a loopback address, and remote clients aren't allowed", address);
+                }
+                if state.meta.live_link == Some(address) {
+                    return Ok(Some(CommandResult::Done));
+                }
+                // a running live link keeps running if the new one can't be started
+                let live_link = LiveLink::start(address)?;
+                state.meta.live_link = Some(live_link.address());
+                state.live_link = Some(live_link);
+                CommandResult::Done
+            }
+            Command::StopLiveLink => {
+                state.live_link = None;
+                state.meta.live_link = None;
+                CommandResult::Done
+            }
+            #[cfg(feature = "scripting")]
+            Command::AttachScript { model_id, path } => {
+                let scene = state
+                    .world
+                    .get_active_scene()
+                    .with_context(|| format!("No active scene to attach a script to model {} in", model_id))?;
+                if scene.get_model(model_id).is_none() {
+                    bail!("Model {} not found in the active scene", model_id);
+                }
+                state.scripts.attach(model_id, &path)?;
+                info!("Attached script {} to model {}", path.display(), model_id);
+                CommandResult::Done
+            }
+            #[cfg(feature = "scripting")]
+            Command::DetachScript(model_id) => {
+                if !state.scripts.detach(model_id) {
+                    bail!("Model {} has no script", model_id);
+                }
+                CommandResult::Done
+            }
+            Command::SetTimeScale(scale) => {
+                if !scale.is_finite() || scale <= 0.0 {
+                    bail!("Invalid time scale {}", scale);
+                }
+                state.meta.time.set_scale(scale);
+                CommandResult::Done
+            }
+            Command::SetPaused(paused) => {
+                state.meta.time.paused = paused;
+                CommandResult::Done
+            }
+            Command::StepFrame => {
+                if !state.meta.time.paused {
+                    bail!("Only a paused scene can be stepped");
+                }
+                state.meta.time.step();
+                CommandResult::Done
+            }
+            Command::SetSimulationOnly(simulation_only) => {
+                if !simulation_only {
+                    state.window.set_visible(true);
+                }
+                state.meta.simulation_only = simulation_only;
+                CommandResult::Done
+            }
+            #[cfg(feature = "audio")]
+            Command::LoadSound(path) => CommandResult::SoundLoaded(state.audio.load(&path)?),
+            #[cfg(feature = "audio")]
+            Command::UnloadSound(sound_id) => {
+                state.audio.unload(sound_id)?;
+                CommandResult::Done
+            }
+            #[cfg(feature = "audio")]
+            Command::PlaySound {
+                sound_id,
+                volume,
+                looping,
+            } => CommandResult::SoundPlaying(state.audio.play(sound_id, volume, looping)?),
+            #[cfg(feature = "audio")]
+            Command::PlaySoundOnModel {
+                sound_id,
+                model_id,
+                volume,
+                looping,
+                falloff,
+            } => {
+                let scene = state.world.get_active_scene().context("No active scene")?;
+                if !scene.iter_models_deep().any(|model| model.id == model_id) {
+                    bail!("Model {} not found in the active scene", model_id);
+                }
+                let playback_id = state
+                    .audio
+                    .play_on_model(sound_id, model_id, volume, looping, falloff)?;
+                CommandResult::SoundPlaying(playback_id)
+            }
+            #[cfg(feature = "audio")]
+            Command::StopSound(playback_id) => {
+                state.audio.stop(playback_id)?;
+                CommandResult::Done
+            }
+            #[cfg(feature = "audio")]
+            Command::SetSoundVolume { playback_id, volume } => {
+                state.audio.set_volume(playback_id, volume)?;
+                CommandResult::Done
+            }
+            #[cfg(feature = "audio")]
+            Command::SetMasterVolume(master_volume) => {
+                state.audio.set_master_volume(master_volume);
+                CommandResult::Done
+            }
+            Command::Batch(commands) => {
+                let (results, _) = process_batch(commands, state, event_sender)?;
+                CommandResult::Batch(results)
+            }
+        };
+        Ok(Some(result))
+    }
+
+    /**
+    Whether the command can be part of a `Command::Batch`, i.e. what it changes can be reverted if a later command of
+    the batch fails.
+     */
+    pub fn is_revertible(&self) -> bool {
+        match self {
+            Command::CreateModel(..)
+            | Command::DuplicateModel(_)
+            | Command::ChangeModelParent { .. }
+            | Command::SetVisibility { .. }
+            | Command::SetOutline { .. }
+            | Command::AssignMaterial { .. }
+            | Command::Select { .. }
+            | Command::ClearSelection
+            | Command::MoveSelection(_)
+            | Command::SnapModelToGrid(_) => true,
+            Command::Batch(commands) => commands.iter().all(Command::is_revertible),
+            _ => false,
+        }
+    }
+}
+
+/**
+The meshes that an imposter of `model`, a model of `scene`, is baked from, i.e. the rendered meshes of the model and
+its children.
+ */
+pub(crate) fn imposter_meshes<'a>(scene: &'a Scene, model: &'a Model) -> Vec<&'a Mesh> {
+    std::iter::once(model)
+        .chain(model.children.iter_deep())
+        .flat_map(|model| model.meshes.iter())
+        // only meshes that are rendered, i.e. that are in the scene's mesh buffer
+        .filter(|mesh| scene.mesh_buffer.get(&mesh.id).is_some())
+        .collect()
+}
+
+/**
+Processes the commands of a batch in order, reverting the applied ones if one fails. Returns the results of the
+commands, and how to revert them in reverse order, e.g. when the batch is part of another batch that fails later.
+ */
+fn process_batch(
+    commands: Vec<Command>,
+    state: &mut RenderState,
+    event_sender: &mpsc::Sender<Event>,
+) -> Result<(Vec<Option<CommandResult>>, Vec<Revert>)> {
+    // checked up front, so that nothing is applied that can't be reverted
+    if let Some((index, command)) = commands.iter().enumerate().find(|(_, command)| !command.is_revertible()) {
+        bail!("Command {} of the batch can't be reverted and isn't allowed in batches: {:?}", index, command);
+    }
+    let mut results = Vec::with_capacity(commands.len());
+    let mut reverts = Vec::with_capacity(commands.len());
+    for (index, command) in commands.into_iter().enumerate() {
+        let revert = Revert::capture(&command, state);
+        let result = match command {
+            Command::Batch(commands) => process_batch(commands, state, event_sender).map(|(results, reverts)| {
+                (Some(CommandResult::Batch(results)), Revert::Batch(reverts))
+            }),
+            command => command.try_process(state, event_sender).map(|result| (result, revert)),
+        };
+        match result {
+            Ok((result, revert)) => {
+                let revert = match (revert, &result) {
+                    (Revert::Created(_), Some(result)) => {
+                        let mut created = vec![];
+                        created_models(result, &mut created);
+                        Revert::Created(created)
+                    }
+                    (revert, _) => revert,
                 };
-                state.surface.configure(&state.device, &state.surface_config);
+                results.push(result);
+                reverts.push(revert);
             }
+            Err(err) => {
+                for revert in reverts.into_iter().rev() {
+                    revert.apply(state, event_sender);
+                }
+                return Err(err.context(format!("Command {} of the batch failed", index)));
+            }
+        }
+    }
+    Ok((results, reverts))
+}
+
+/**
+What a command of a batch changed, captured before it is applied so that it can be reverted.
+ */
+enum Revert {
+    /// the ids of the models the command created, which are only known once it is applied
+    Created(Vec<u32>),
+    Parent {
+        model_id: u32,
+        parent_id: Option<u32>,
+        scene_id: u32,
+        local_transform: Mat4,
+    },
+    Visibility {
+        id: u32,
+        visible: bool,
+    },
+    Outline {
+        mesh_id: u32,
+        outline: Option<Outline>,
+    },
+    Material {
+        mesh_id: u32,
+        material: MatId,
+    },
+    Selection(Selection),
+    /// local transforms of models in the active scene
+    Transforms(Vec<(u32, Mat4)>),
+    /// the reverts of a nested batch's commands
+    Batch(Vec<Revert>),
+    /// the command failed to find what it changes, so it fails before changing anything
+    Nothing,
+}
+
+impl Revert {
+    fn capture(command: &Command, state: &RenderState) -> Self {
+        let active_scene = state.world.get_active_scene();
+        match command {
+            Command::CreateModel(..) | Command::DuplicateModel(_) => Revert::Created(vec![]),
+            Command::ChangeModelParent { model_id, .. } => state
+                .world
+                .scenes
+                .iter()
+                .find_map(|(scene_id, scene)| {
+                    let model = scene.get_model(*model_id)?;
+                    let parent_id = scene
+                        .iter_models_deep()
+                        .find(|parent| parent.children.iter().any(|child| child.id == *model_id))
+                        .map(|parent| parent.id);
+                    Some(Revert::Parent {
+                        model_id: *model_id,
+                        parent_id,
+                        scene_id: *scene_id as u32,
+                        local_transform: model.local_transform,
+                    })
+                })
+                .unwrap_or(Revert::Nothing),
+            Command::SetVisibility { id, .. } => active_scene
+                .and_then(|scene| {
+                    scene.iter_models_deep().find_map(|model| {
+                        if model.id == *id {
+                            return Some(model.visible);
+                        }
+                        model.meshes.iter().find(|mesh| mesh.id == *id).map(|mesh| mesh.visible)
+                    })
+                })
+                .map_or(Revert::Nothing, |visible| Revert::Visibility { id: *id, visible }),
+            Command::SetOutline { mesh_id, .. } => active_scene
+                .and_then(|scene| find_mesh(scene, *mesh_id))
+                .map_or(Revert::Nothing, |mesh| Revert::Outline {
+                    mesh_id: *mesh_id,
+                    outline: mesh.outline(),
+                }),
+            Command::AssignMaterial { mesh_id, .. } => state
+                .world
+                .scenes
+                .values()
+                .find_map(|scene| find_mesh(scene, *mesh_id))
+                .map_or(Revert::Nothing, |mesh| Revert::Material {
+                    mesh_id: *mesh_id,
+                    material: mesh.material,
+                }),
+            Command::Select { .. } | Command::ClearSelection => Revert::Selection(state.meta.selection.clone()),
+            Command::MoveSelection(_) | Command::SnapModelToGrid(_) => {
+                let Some(scene) = active_scene else {
+                    return Revert::Nothing;
+                };
+                let model_ids = match command {
+                    Command::SnapModelToGrid(model_id) => vec![*model_id],
+                    _ => state.meta.selection.models(scene),
+                };
+                Revert::Transforms(
+                    model_ids
+                        .into_iter()
+                        .filter_map(|model_id| Some((model_id, scene.get_model(model_id)?.local_transform)))
+                        .collect(),
+                )
+            }
+            // processed by `process_batch`, which collects the reverts of its commands
+            Command::Batch(_) => Revert::Batch(vec![]),
+            _ => unreachable!("Batches only contain revertible commands"),
+        }
+    }
+
+    // reverting only uses what the batch's commands touched before, so it is expected to succeed
+    fn apply(self, state: &mut RenderState, event_sender: &mpsc::Sender<Event>) {
+        let result = match self {
+            // in reverse, so that models created below other created models are removed first
+            Revert::Created(model_ids) => model_ids
+                .into_iter()
+                .rev()
+                .try_for_each(|model_id| Command::DeleteModel(model_id).try_process(state, event_sender).map(|_| ())),
+            Revert::Parent {
+                model_id,
+                parent_id,
+                scene_id,
+                local_transform,
+            } => match state
+                .world
+                .scenes
+                .values_mut()
+                .find_map(|scene| scene.get_model_mut(model_id))
+            {
+                Some(model) => {
+                    model.local_transform = local_transform;
+                    Command::ChangeModelParent {
+                        model_id,
+                        new_parent_id: parent_id,
+                        new_scene_id: scene_id,
+                        keep_world_transform: false,
+                    }
+                    .try_process(state, event_sender)
+                    .map(|_| ())
+                }
+                None => Err(anyhow!("Model {} not found", model_id)),
+            },
+            Revert::Visibility { id, visible } => Command::SetVisibility { id, visible }
+                .try_process(state, event_sender)
+                .map(|_| ()),
+            Revert::Outline { mesh_id, outline } => Command::SetOutline { mesh_id, outline }
+                .try_process(state, event_sender)
+                .map(|_| ()),
+            Revert::Material { mesh_id, material } => Command::AssignMaterial { mesh_id, material }
+                .try_process(state, event_sender)
+                .map(|_| ()),
+            Revert::Selection(selection) => {
+                state.meta.selection = selection;
+                state.selection_changed();
+                Ok(())
+            }
+            Revert::Transforms(transforms) => transforms.into_iter().try_for_each(|(model_id, local_transform)| {
+                let model = active_model_mut(state, model_id)?;
+                let parent = model.global_transform() * model.local_transform.inverse();
+                model.local_transform = local_transform;
+                model.update_transforms(parent);
+                Ok(())
+            }),
+            Revert::Batch(reverts) => {
+                for revert in reverts.into_iter().rev() {
+                    revert.apply(state, event_sender);
+                }
+                Ok(())
+            }
+            Revert::Nothing => Ok(()),
+        };
+        if let Err(err) = result {
+            error!("Couldn't revert a command of the failed batch: {:#}", err);
         }
-        debug!("Finished processing command.");
     }
 }
+
+fn find_mesh(scene: &Scene, mesh_id: u32) -> Option<&Mesh> {
+    scene
+        .iter_models_deep()
+        .flat_map(|model| model.meshes.iter())
+        .find(|mesh| mesh.id == mesh_id)
+}
+
+/** Collects the models that a command created and that still exist, from its result. */
+fn created_models(result: &CommandResult, created: &mut Vec<u32>) {
+    match result {
+        CommandResult::ModelCreated(model_id) => created.push(*model_id),
+        CommandResult::ModelsCreated(model_ids) => created.extend(model_ids),
+        CommandResult::ModelDeleted(model_id) => created.retain(|id| id != model_id),
+        CommandResult::Batch(results) => {
+            for result in results.iter().flatten() {
+                created_models(result, created);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn active_model_mut(state: &mut RenderState, model_id: u32) -> Result<&mut Model> {
+    state
+        .world
+        .scenes
+        .get_mut(&state.world.active_scene)
+        .context("No active scene")?
+        .get_model_mut(model_id)
+        .with_context(|| format!("Model {} not found in the active scene", model_id))
+}
+
+/** Moves a model by the offset in world space, along with the models below it. */
+fn translate_model(model: &mut Model, offset: Vec3) {
+    let global_transform = model.global_transform();
+    let parent = global_transform * model.local_transform.inverse();
+    model.local_transform = parent.inverse() * Mat4::from_translation(offset) * global_transform;
+    model.update_transforms(parent);
+}
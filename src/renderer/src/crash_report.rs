@@ -0,0 +1,230 @@
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+
+use log::{error, info};
+use web_time::{SystemTime, UNIX_EPOCH};
+use wgpu::{Adapter, AdapterInfo, Device, Features, Limits};
+
+use crate::gpu_timing::PassTiming;
+
+// how many of the most recently processed commands a report lists
+const RECENT_COMMANDS: usize = 32;
+// commands with large payloads, like whole water surfaces, are cut off after this many characters
+const MAX_COMMAND_LENGTH: usize = 200;
+// file in the report directory that holds the path of the newest report, until it was offered to the user
+const PENDING_FILE: &str = "pending";
+
+/**
+Stats of the last rendered frame, included in crash reports.
+ */
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FrameStats {
+    pub(crate) frame_time: f32,
+    pub(crate) models: usize,
+    pub(crate) meshes: usize,
+    pub(crate) lights: usize,
+    pub(crate) materials: usize,
+    pub(crate) textures: usize,
+    pub(crate) gpu_timings: Vec<PassTiming>,
+}
+
+#[derive(Default)]
+struct CrashContext {
+    adapter: Option<AdapterInfo>,
+    features: Features,
+    limits: Limits,
+    commands: VecDeque<String>,
+    frames: u64,
+    stats: FrameStats,
+    // set by the uncaptured error handler right before it panics, see `CrashReporter::record_device_error`
+    device_error: Option<String>,
+}
+
+/**
+Writes a crash report when the engine panics, including panics caused by device errors, with the context needed to
+make sense of it: the adapter and driver, the device's features and limits, the last processed commands and the stats
+of the last frame. See `RunConfig::crash_reports`.
+ */
+#[derive(Clone)]
+pub(crate) struct CrashReporter {
+    directory: PathBuf,
+    context: Arc<Mutex<CrashContext>>,
+}
+
+impl CrashReporter {
+    /**
+    Installs a panic hook that writes reports into `directory`, after running the previous hook.
+     */
+    pub(crate) fn install(directory: PathBuf) -> Self {
+        let reporter = Self {
+            directory,
+            context: Arc::default(),
+        };
+        let previous = panic::take_hook();
+        let hook_reporter = reporter.clone();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            hook_reporter.write(&info.to_string());
+        }));
+        reporter
+    }
+
+    /**
+    Records the device that reports are about.
+     */
+    pub(crate) fn set_device(&self, adapter: &Adapter, device: &Device) {
+        let mut context = lock(&self.context);
+        context.adapter = Some(adapter.get_info());
+        context.features = device.features();
+        context.limits = device.limits();
+    }
+
+    /**
+    Records a device error right before the engine panics because of it, see `recovery::watch_device`.
+     */
+    pub(crate) fn record_device_error(&self, error: &str) {
+        lock(&self.context).device_error = Some(error.to_string());
+    }
+
+    pub(crate) fn record_command(&self, command: &str) {
+        let mut context = lock(&self.context);
+        if context.commands.len() == RECENT_COMMANDS {
+            context.commands.pop_front();
+        }
+        let command = command.chars().take(MAX_COMMAND_LENGTH).collect();
+        context.commands.push_back(command);
+    }
+
+    pub(crate) fn update_stats(&self, stats: FrameStats) {
+        let mut context = lock(&self.context);
+        context.frames += 1;
+        context.stats = stats;
+    }
+
+    fn write(&self, panic: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let mut report = String::new();
+        let _ = writeln!(report, "engine-wgpu crash report");
+        let _ = writeln!(report, "time: {} (unix)", timestamp);
+        let _ = writeln!(report, "renderer version: {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(report, "\n{}", panic);
+        // the panicking thread may hold the lock, waiting for it would never end
+        match self.context.try_lock() {
+            Ok(context) => context.describe(&mut report),
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner().describe(&mut report),
+            Err(TryLockError::WouldBlock) => {
+                let _ = writeln!(report, "\n(the context was locked when the panic happened)");
+            }
+        }
+        let _ = writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture());
+
+        let path = self.directory.join(format!("crash-{}.txt", timestamp));
+        let written = fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(&path, report))
+            .and_then(|_| fs::write(self.directory.join(PENDING_FILE), path.to_string_lossy().as_bytes()));
+        match written {
+            Ok(()) => error!("Wrote a crash report to {:?}", path),
+            Err(e) => error!("Couldn't write a crash report to {:?}: {}", path, e),
+        }
+    }
+}
+
+impl CrashContext {
+    fn describe(&self, report: &mut String) {
+        if let Some(device_error) = &self.device_error {
+            let _ = writeln!(report, "\ndevice error: {}", device_error);
+        }
+        match &self.adapter {
+            Some(adapter) => {
+                let _ = writeln!(report, "\nadapter: {} ({:?})", adapter.name, adapter.device_type);
+                let _ = writeln!(report, "backend: {:?}", adapter.backend);
+                let _ = writeln!(report, "vendor: {:#06x}", adapter.vendor);
+                let _ = writeln!(report, "device: {:#06x}", adapter.device);
+                let _ = writeln!(report, "driver: {} {}", adapter.driver, adapter.driver_info);
+                let _ = writeln!(report, "features: {:?}", self.features);
+                let _ = writeln!(report, "limits: {:#?}", self.limits);
+            }
+            None => {
+                let _ = writeln!(report, "\nno device was created yet");
+            }
+        }
+
+        let stats = &self.stats;
+        let _ = writeln!(report, "\nframes rendered: {}", self.frames);
+        let _ = writeln!(report, "last frame time: {:.2} ms", stats.frame_time * 1000.0);
+        let _ = writeln!(
+            report,
+            "models: {}, meshes: {}, lights: {}, materials: {}, textures: {}",
+            stats.models, stats.meshes, stats.lights, stats.materials, stats.textures
+        );
+        for timing in stats.gpu_timings.iter() {
+            let _ = writeln!(report, "  {}: {:.2} ms", timing.label, timing.milliseconds);
+        }
+
+        let _ = writeln!(report, "\nlast commands, oldest first:");
+        for command in self.commands.iter() {
+            let _ = writeln!(report, "  {}", command);
+        }
+    }
+}
+
+// a panic while the context was locked must not keep later reports from using it
+fn lock(context: &Mutex<CrashContext>) -> MutexGuard<'_, CrashContext> {
+    context.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/**
+The newest report in `directory` if it wasn't offered to the user yet, e.g. because the engine crashed during the last
+run. Afterwards, the report doesn't count as pending anymore.
+ */
+pub(crate) fn take_pending(directory: &Path) -> Option<PathBuf> {
+    let pending = directory.join(PENDING_FILE);
+    let path = PathBuf::from(fs::read_to_string(&pending).ok()?);
+    let _ = fs::remove_file(&pending);
+    path.exists().then_some(path)
+}
+
+/**
+Offers to open the report of a crash during the last run. Returns false once the dialog was closed.
+ */
+pub(crate) fn dialog(ctx: &egui::Context, report: &Path) -> bool {
+    let mut open = true;
+    egui::Window::new("Crash report")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("The engine crashed during the last run. A report with details was written to");
+            ui.monospace(report.display().to_string());
+            ui.horizontal(|ui| {
+                if ui.button("Open").clicked() {
+                    open_file(report);
+                    open = false;
+                }
+                if ui.button("Dismiss").clicked() {
+                    open = false;
+                }
+            });
+        });
+    open
+}
+
+fn open_file(path: &Path) {
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    match std::process::Command::new(program).arg(path).spawn() {
+        Ok(_) => info!("Opened crash report {:?}", path),
+        Err(e) => error!("Couldn't open crash report {:?}: {}", path, e),
+    }
+}
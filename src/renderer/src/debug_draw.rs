@@ -0,0 +1,112 @@
+use std::f32::consts::TAU;
+
+use glam::Vec3;
+
+use lib::shader_types::ColoredVertex;
+
+// number of segments of the circles that make up a sphere
+const CIRCLE_SEGMENTS: usize = 24;
+// length of an arrow's head relative to the arrow
+const ARROW_HEAD: f32 = 0.2;
+
+/**
+Lines in world space that the hook draws each frame, see `Hook::debug_draw`. Everything is drawn on top of the scene
+after the meshes, together with the physics visualizations of `Meta::physics_debug`. Lines that are depth tested are
+hidden behind meshes, the others are always visible. The lines are cleared before every frame.
+ */
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    depth_tested: Vec<ColoredVertex>,
+    overlay: Vec<ColoredVertex>,
+}
+
+impl DebugDraw {
+    pub fn draw_line(&mut self, from: Vec3, to: Vec3, color: [f32; 3], depth_test: bool) {
+        let vertices = if depth_test {
+            &mut self.depth_tested
+        } else {
+            &mut self.overlay
+        };
+        vertices.extend([
+            ColoredVertex {
+                position: from.into(),
+                color,
+            },
+            ColoredVertex {
+                position: to.into(),
+                color,
+            },
+        ]);
+    }
+
+    /**
+    The edges of the axis-aligned box between the corners `min` and `max`.
+     */
+    pub fn draw_aabb(&mut self, min: Vec3, max: Vec3, color: [f32; 3], depth_test: bool) {
+        let corner = |i: usize| {
+            Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        // the corners whose index differs in a single bit share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.draw_line(corner(i), corner(i | bit), color, depth_test);
+                }
+            }
+        }
+    }
+
+    /**
+    Three circles around the axes through `center`.
+     */
+    pub fn draw_sphere(&mut self, center: Vec3, radius: f32, color: [f32; 3], depth_test: bool) {
+        for (a, b) in [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)] {
+            let point = |i: usize| {
+                let (sin, cos) = (i as f32 / CIRCLE_SEGMENTS as f32 * TAU).sin_cos();
+                center + (a * cos + b * sin) * radius
+            };
+            for i in 0..CIRCLE_SEGMENTS {
+                self.draw_line(point(i), point(i + 1), color, depth_test);
+            }
+        }
+    }
+
+    /**
+    A line from `from` to `to` with a head at `to`.
+     */
+    pub fn draw_arrow(&mut self, from: Vec3, to: Vec3, color: [f32; 3], depth_test: bool) {
+        self.draw_line(from, to, color, depth_test);
+        let direction = to - from;
+        let length = direction.length();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let (u, v) = (direction / length).any_orthonormal_pair();
+        let base = to - direction * ARROW_HEAD;
+        let radius = length * ARROW_HEAD / 2.0;
+        for side in [u, -u, v, -v] {
+            self.draw_line(to, base + side * radius, color, depth_test);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth_tested.is_empty() && self.overlay.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.depth_tested.clear();
+        self.overlay.clear();
+    }
+
+    pub(crate) fn depth_tested(&self) -> &[ColoredVertex] {
+        &self.depth_tested
+    }
+
+    pub(crate) fn overlay(&self) -> &[ColoredVertex] {
+        &self.overlay
+    }
+}
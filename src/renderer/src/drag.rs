@@ -0,0 +1,225 @@
+use egui::{Color32, Context, LayerId, Pos2, Rect, Stroke};
+use glam::{Vec2, Vec3};
+
+use lib::scene::bindings::Axis;
+
+use crate::camera::Camera;
+use crate::pipelines::object_picking::PickHit;
+use crate::selection::Selection;
+use crate::snap::Snap;
+
+/**
+How far the cursor may move between pressing and releasing a button for it to still count as a click, in pixels.
+Anything further is a drag.
+ */
+pub const CLICK_DISTANCE: f32 = 4.0;
+
+/**
+Moving the selection by dragging one of its meshes with the left mouse button. The selection moves along a plane
+through the point where the mesh was grabbed, which is found by picking, and the moves are sent as
+`Command::MoveSelection`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Drag {
+    pub enabled: bool,
+    pub constraint: DragConstraint,
+    /// dragging anywhere else spans a rectangle, which is sent as `Event::Marquee` once the button is released
+    pub marquee: bool,
+}
+
+impl Default for Drag {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            constraint: DragConstraint::View,
+            marquee: true,
+        }
+    }
+}
+
+/**
+The plane that dragged objects move along.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DragConstraint {
+    /// the plane facing the camera
+    #[default]
+    View,
+    /// the plane perpendicular to the axis, e.g. the ground for `Y`
+    Plane(Axis),
+    /// only along the axis
+    Axis(Axis),
+}
+
+#[derive(Debug, Default)]
+enum DragState {
+    #[default]
+    Idle,
+    // the button is down and the pixel where it was pressed is being picked
+    Picking {
+        start: (u32, u32),
+        cursor: (u32, u32),
+    },
+    Marquee {
+        start: (u32, u32),
+        cursor: (u32, u32),
+    },
+    Dragging {
+        // where the mesh was grabbed
+        origin: Vec3,
+        normal: Vec3,
+        // how far the selection was moved so far
+        moved: Vec3,
+    },
+}
+
+/**
+Tracks a drag from pressing the button to releasing it.
+ */
+#[derive(Debug, Default)]
+pub(crate) struct Dragger {
+    state: DragState,
+}
+
+impl Dragger {
+    /**
+    Call this when the left button was pressed at `cursor`, the pixel then has to be picked with `PickRequest::Drag`.
+     */
+    pub(crate) fn press(&mut self, cursor: (u32, u32)) {
+        self.state = DragState::Picking { start: cursor, cursor };
+    }
+
+    /**
+    Ends the drag. Returns the corners of the marquee if one was spanned.
+     */
+    pub(crate) fn release(&mut self) -> Option<((u32, u32), (u32, u32))> {
+        let marquee = self.marquee();
+        self.state = DragState::Idle;
+        marquee
+    }
+
+    /**
+    The corners of the marquee that is being spanned, once it is larger than a click.
+     */
+    pub(crate) fn marquee(&self) -> Option<((u32, u32), (u32, u32))> {
+        let DragState::Marquee { start, cursor } = self.state else {
+            return None;
+        };
+        let size = Vec2::new(cursor.0 as f32 - start.0 as f32, cursor.1 as f32 - start.1 as f32);
+        if size.length() <= CLICK_DISTANCE {
+            return None;
+        }
+        let min = (start.0.min(cursor.0), start.1.min(cursor.1));
+        let max = (start.0.max(cursor.0), start.1.max(cursor.1));
+        Some((min, max))
+    }
+
+    /**
+    Draws the marquee over the viewport.
+     */
+    pub(crate) fn paint(&self, ctx: &Context) {
+        let Some((min, max)) = self.marquee() else {
+            return;
+        };
+        // the cursor is in physical pixels, egui in points
+        let scale = 1.0 / ctx.pixels_per_point();
+        let rect = Rect::from_min_max(
+            Pos2::new(min.0 as f32 * scale, min.1 as f32 * scale),
+            Pos2::new(max.0 as f32 * scale, max.1 as f32 * scale),
+        );
+        let painter = ctx.layer_painter(LayerId::background());
+        painter.rect(
+            rect,
+            0.0,
+            Color32::from_rgba_unmultiplied(120, 170, 255, 40),
+            Stroke::new(1.0, Color32::from_rgb(120, 170, 255)),
+        );
+    }
+
+    /**
+    Starts dragging if the button is still down and a selected mesh was hit.
+     */
+    pub(crate) fn picked(&mut self, hit: Option<PickHit>, selection: &Selection, drag: &Drag, camera: &Camera) {
+        let DragState::Picking { start, cursor } = self.state else {
+            return;
+        };
+        self.state = match hit {
+            Some(hit) if drag.enabled && selection.contains(hit.mesh_id) => {
+                let view_direction = (hit.position - camera.world_eye()).normalize_or_zero();
+                let normal = match drag.constraint {
+                    DragConstraint::View => view_direction,
+                    DragConstraint::Plane(axis) => axis_vector(axis),
+                    // the plane containing the axis that faces the camera the most
+                    DragConstraint::Axis(axis) => {
+                        let axis = axis_vector(axis);
+                        axis.cross(view_direction.cross(axis)).normalize_or_zero()
+                    }
+                };
+                DragState::Dragging {
+                    origin: hit.position,
+                    normal,
+                    moved: Vec3::ZERO,
+                }
+            }
+            _ if drag.marquee => DragState::Marquee { start, cursor },
+            _ => DragState::Idle,
+        };
+    }
+
+    /**
+    The offset to move the selection by since the last call, for the cursor at `cursor` of a viewport of `size`. Also
+    spans the marquee. The total offset since grabbing snaps to the translation step if snapping is enabled.
+     */
+    pub(crate) fn cursor_moved(
+        &mut self,
+        cursor: (u32, u32),
+        size: (u32, u32),
+        drag: &Drag,
+        snap: &Snap,
+        camera: &Camera,
+    ) -> Option<Vec3> {
+        let (origin, normal, moved) = match &mut self.state {
+            DragState::Picking { cursor: current, .. } | DragState::Marquee { cursor: current, .. } => {
+                *current = cursor;
+                return None;
+            }
+            DragState::Dragging { origin, normal, moved } => (origin, normal, moved),
+            DragState::Idle => return None,
+        };
+        let ndc = Vec2::new(
+            (cursor.0 as f32 + 0.5) / size.0 as f32 * 2.0 - 1.0,
+            1.0 - (cursor.1 as f32 + 0.5) / size.1 as f32 * 2.0,
+        );
+        let unprojection = camera.build_projection().inverse();
+        let depth_mode = camera.depth_mode();
+        let near = unprojection.project_point3(ndc.extend(depth_mode.near_depth()));
+        let far = unprojection.project_point3(ndc.extend(depth_mode.far_depth()));
+        let direction = far - near;
+        let denominator = direction.dot(*normal);
+        // the plane is seen edge-on
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+        let t = (*origin - near).dot(*normal) / denominator;
+        if t < 0.0 {
+            return None;
+        }
+        let mut total = near + direction * t - *origin;
+        if let DragConstraint::Axis(axis) = drag.constraint {
+            let axis = axis_vector(axis);
+            total = axis * total.dot(axis);
+        }
+        let total = snap.offset(total);
+        let offset = total - *moved;
+        *moved = total;
+        (offset != Vec3::ZERO).then_some(offset)
+    }
+}
+
+fn axis_vector(axis: Axis) -> Vec3 {
+    match axis {
+        Axis::X => Vec3::X,
+        Axis::Y => Vec3::Y,
+        Axis::Z => Vec3::Z,
+    }
+}
@@ -1,5 +1,12 @@
+use std::path::PathBuf;
+
+use glam::{IVec2, Vec2};
+
 use crate::commands::CommandResult;
 
+/// the keys of `Event::KeyPressed`
+pub use winit::event::VirtualKeyCode as KeyCode;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MouseButton {
     Left,
@@ -7,8 +14,99 @@ pub enum MouseButton {
     Middle,
 }
 
+/**
+Modifier keys held during a click, see `SelectionMode::from_modifiers`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+}
+
 #[derive(Debug)]
 pub enum Event {
-    Click { x: u32, y: u32, mouse_button: MouseButton },
+    MouseDown {
+        x: u32,
+        y: u32,
+        mouse_button: MouseButton,
+        modifiers: Modifiers,
+    },
+    MouseUp {
+        x: u32,
+        y: u32,
+        mouse_button: MouseButton,
+        modifiers: Modifiers,
+    },
+    /// A mouse button was pressed and released without moving the cursor further than `drag::CLICK_DISTANCE`, sent
+    /// after its `MouseUp`
+    Click {
+        x: u32,
+        y: u32,
+        mouse_button: MouseButton,
+        modifiers: Modifiers,
+    },
+    /// A second `Click` of the same button in the same place within `input::DOUBLE_CLICK_TIME`, sent after it
+    DoubleClick {
+        x: u32,
+        y: u32,
+        mouse_button: MouseButton,
+        modifiers: Modifiers,
+    },
+    /// The cursor moved by `delta` pixels to `x`, `y` while `mouse_button` was held
+    Drag {
+        x: u32,
+        y: u32,
+        delta: IVec2,
+        mouse_button: MouseButton,
+        modifiers: Modifiers,
+    },
+    /// The mouse wheel or touchpad was scrolled, in lines. Positive y scrolls up
+    Scroll {
+        delta: Vec2,
+        modifiers: Modifiers,
+    },
+    /// A key was pressed, repeatedly while it is held. Escape closes the window instead
+    KeyPressed {
+        key: KeyCode,
+        modifiers: Modifiers,
+    },
+    /// The left button was dragged from outside the selection and released, spanning a rectangle between the corners
+    /// `min` and `max`, see `Drag::marquee`
+    Marquee {
+        min: (u32, u32),
+        max: (u32, u32),
+        modifiers: Modifiers,
+    },
+    /// The cursor came to rest over another mesh at `x`, `y`, or over none, see `hover::HOVER_DELAY`
+    Hover {
+        mesh_id: Option<u32>,
+        x: u32,
+        y: u32,
+    },
     CommandResult(CommandResult),
+    /// A command couldn't be processed, e.g. because a file was malformed or a model didn't exist. The error was
+    /// logged as well and the world is left as it was before the command where possible
+    CommandFailed { command: String, error: String },
+    /// A GPU pass exceeded its budget in `Meta::pass_budgets` for the configured number of consecutive frames
+    PerformanceWarning { pass: &'static str, milliseconds: f32, budget: f32 },
+    /// A streaming cell finished loading and its models were added to the active scene
+    CellLoaded(IVec2),
+    /// A streaming cell's models were removed from the active scene
+    CellUnloaded(IVec2),
+    /// The graphics device was lost, e.g. because the driver was updated or reset, and the renderer recreated it along
+    /// with the world's textures, materials and meshes and the loaded LUT. Resources that only existed on the GPU are
+    /// gone: baked imposters have to be baked again, and the textures that the hook registered with `UiResources` have
+    /// to be registered again, as the GUI's renderer was recreated. `Hook::post_processes` was called again.
+    /// Also sent after `Command::SetSurfaceFormat` recreated the GUI's renderer, which only loses the registered
+    /// textures
+    DeviceRecovered,
+    /// The selection changed, by a command or because selected meshes were removed. Contains the selected meshes, the
+    /// models that were selected on their own, like lights, are in `Selection::direct_models` of `Meta::selection`
+    SelectionChanged(Vec<u32>),
+    /// A file that assets were imported from changed on disk and was imported again in their place, see
+    /// `Meta::hot_reload`. If importing failed, the old assets stay and the error is logged
+    AssetReloaded(PathBuf),
+    /// A playback of a sound ended on its own or because the model it played on was removed, see `Command::PlaySound`
+    #[cfg(feature = "audio")]
+    SoundFinished(u32),
 }
@@ -0,0 +1,76 @@
+use crate::RenderState;
+
+/**
+GPU memory taken up by the world's resources, in bytes, see `Meta::gpu_memory`. The sizes are the ones the resources
+were created with; drivers may allocate more for alignment or bookkeeping.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GpuMemory {
+    /// the vertex buffers of the mesh pools of all scenes, including their unused space
+    pub vertex_buffers: u64,
+    /// the index buffers of the mesh pools of all scenes
+    pub index_buffers: u64,
+    /// the world's textures, including the default textures
+    pub textures: u64,
+    /// the material buffer and the mesh and light buffers of all scenes, which grow as elements are added
+    pub buffer_arrays: u64,
+    /// how much memory the world may take up, for the GUI to compare `total` with. Not enforced
+    pub budget: Option<u64>,
+}
+
+impl GpuMemory {
+    pub fn total(&self) -> u64 {
+        self.vertex_buffers + self.index_buffers + self.textures + self.buffer_arrays
+    }
+
+    /**
+    Whether the total exceeds the budget, false without a budget.
+     */
+    pub fn over_budget(&self) -> bool {
+        self.budget.is_some_and(|budget| self.total() > budget)
+    }
+}
+
+/**
+Size of the texture's mip levels and array layers in bytes, zero for formats whose size depends on the aspect, like
+combined depth stencil formats.
+ */
+pub fn texture_bytes(texture: &wgpu::Texture) -> u64 {
+    let format = texture.format();
+    let Some(block_size) = format.block_size(None) else {
+        return 0;
+    };
+    let (block_width, block_height) = format.block_dimensions();
+    let size = texture.size();
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let width = (size.width >> level).max(1).div_ceil(block_width) as u64;
+            let height = (size.height >> level).max(1).div_ceil(block_height) as u64;
+            width * height * size.depth_or_array_layers as u64 * block_size as u64
+        })
+        .sum()
+}
+
+/**
+Measures the memory taken up by the world's resources, keeping the budget.
+ */
+pub(crate) fn update(state: &mut RenderState) {
+    let world = &state.world;
+    let mut memory = GpuMemory {
+        budget: state.meta.gpu_memory.budget,
+        textures: world
+            .assets
+            .textures
+            .iter()
+            .map(|texture| texture_bytes(&texture.texture))
+            .sum(),
+        buffer_arrays: world.assets.materials.buffer.byte_size(),
+        ..Default::default()
+    };
+    for scene in world.scenes.values() {
+        memory.vertex_buffers += scene.mesh_pool.vertex_buffer_size();
+        memory.index_buffers += scene.mesh_pool.index_buffer_size();
+        memory.buffer_arrays += scene.mesh_buffer.byte_size() + scene.light_buffer.byte_size();
+    }
+    state.meta.gpu_memory = memory;
+}
@@ -0,0 +1,220 @@
+use hashbrown::HashMap;
+use log::warn;
+use wgpu::{Buffer, CommandEncoder, Device, Features, QuerySet, Queue};
+
+use crate::readback::ReadbackBuffer;
+
+/**
+How long the GPU took for one scope of the frame, e.g. a render pass.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PassTiming {
+    pub label: &'static str,
+    pub milliseconds: f32,
+}
+
+/**
+Millisecond budgets for the scopes measured by the `GpuTimer`, by label. A pass that exceeds its budget in `frames`
+consecutive measured frames is reported with `Event::PerformanceWarning` and counts as over budget until it is
+within its budget again. Not every frame is measured, see `GpuTimer`.
+ */
+#[derive(Debug, Clone)]
+pub struct PassBudgets {
+    pub budgets: HashMap<&'static str, f32>,
+    pub frames: u32,
+    // consecutive measured frames in which each pass exceeded its budget
+    streaks: HashMap<&'static str, u32>,
+}
+
+impl Default for PassBudgets {
+    fn default() -> Self {
+        Self {
+            budgets: HashMap::new(),
+            frames: 10,
+            streaks: HashMap::new(),
+        }
+    }
+}
+
+impl PassBudgets {
+    pub fn is_over_budget(&self, label: &str) -> bool {
+        self.streaks
+            .get(label)
+            .is_some_and(|streak| *streak >= self.frames.max(1))
+    }
+
+    /**
+    Updates the streaks with a frame's timings and returns the passes that just went over budget.
+     */
+    pub(crate) fn check(&mut self, timings: &[PassTiming]) -> Vec<PassTiming> {
+        let mut exceeded = vec![];
+        for timing in timings {
+            match self.budgets.get(timing.label) {
+                Some(budget) if timing.milliseconds > *budget => {
+                    let streak = self.streaks.entry(timing.label).or_insert(0);
+                    *streak += 1;
+                    if *streak == self.frames.max(1) {
+                        exceeded.push(*timing);
+                    }
+                }
+                _ => {
+                    self.streaks.remove(timing.label);
+                }
+            }
+        }
+        exceeded
+    }
+}
+
+struct Queries {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback: ReadbackBuffer,
+    // nanoseconds per timestamp tick
+    period: f32,
+    // labels of the scopes in the readback buffer, while it is pending
+    pending: Option<Vec<&'static str>>,
+}
+
+/**
+Measures the GPU time of scopes in a frame with timestamp queries. Scopes are delimited with `begin` and `end` on the
+frame's encoder and can't be nested. The results are read back asynchronously, so they arrive a few frames later;
+frames recorded while the previous results are still being read back are not measured.
+
+Does nothing if the device doesn't support `Features::TIMESTAMP_QUERY`.
+ */
+pub struct GpuTimer {
+    queries: Option<Queries>,
+    // labels of the scopes recorded in the current frame
+    scopes: Vec<&'static str>,
+    recording: bool,
+}
+
+impl GpuTimer {
+    pub const MAX_SCOPES: usize = 16;
+    const QUERY_SIZE: u64 = std::mem::size_of::<u64>() as u64;
+
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let queries = device.features().contains(Features::TIMESTAMP_QUERY).then(|| {
+            let count = 2 * Self::MAX_SCOPES as u32;
+            let size = count as u64 * Self::QUERY_SIZE;
+            Queries {
+                query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("GPU Timer Query Set"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count,
+                }),
+                resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("GPU Timer Resolve Buffer"),
+                    size,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback: ReadbackBuffer::new(device, "GPU Timer Readback Buffer", size),
+                period: queue.get_timestamp_period(),
+                pending: None,
+            }
+        });
+        Self {
+            queries,
+            scopes: Vec::with_capacity(Self::MAX_SCOPES),
+            recording: false,
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    /**
+    Starts recording a frame, call this before the first scope.
+     */
+    pub fn begin_frame(&mut self) {
+        self.scopes.clear();
+        self.recording = self.queries.as_ref().is_some_and(|queries| queries.pending.is_none());
+    }
+
+    pub fn begin(&mut self, encoder: &mut CommandEncoder, label: &'static str) {
+        let Some(queries) = self.queries.as_ref().filter(|_| self.recording) else {
+            return;
+        };
+        if self.scopes.len() == Self::MAX_SCOPES {
+            warn!("Too many GPU timer scopes, not measuring {}", label);
+            return;
+        }
+        encoder.write_timestamp(&queries.query_set, 2 * self.scopes.len() as u32);
+        self.scopes.push(label);
+    }
+
+    /**
+    Ends the scope that was begun last.
+     */
+    pub fn end(&mut self, encoder: &mut CommandEncoder) {
+        let Some(queries) = self.queries.as_ref().filter(|_| self.recording) else {
+            return;
+        };
+        if self.scopes.is_empty() {
+            return;
+        }
+        encoder.write_timestamp(&queries.query_set, 2 * self.scopes.len() as u32 - 1);
+    }
+
+    /**
+    Copies the frame's timestamps into the readback buffer, call this after the last scope.
+     */
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        let Some(queries) = self.queries.as_ref().filter(|_| self.recording) else {
+            return;
+        };
+        if self.scopes.is_empty() {
+            return;
+        }
+        let count = 2 * self.scopes.len() as u32;
+        encoder.resolve_query_set(&queries.query_set, 0..count, &queries.resolve_buffer, 0);
+        queries
+            .readback
+            .copy_from_buffer(encoder, &queries.resolve_buffer, 0, count as u64 * Self::QUERY_SIZE);
+    }
+
+    /**
+    Starts reading back the frame's timestamps, call this after the frame's encoder was submitted.
+     */
+    pub fn end_frame(&mut self) {
+        let Some(queries) = self.queries.as_mut().filter(|_| self.recording) else {
+            return;
+        };
+        if self.scopes.is_empty() {
+            return;
+        }
+        queries.readback.map();
+        queries.pending = Some(self.scopes.clone());
+        self.recording = false;
+    }
+
+    /**
+    Returns the timings of a previous frame once they were read back. Doesn't block.
+     */
+    pub fn poll(&mut self, device: &Device) -> Option<Vec<PassTiming>> {
+        let queries = self.queries.as_mut()?;
+        let size = 2 * queries.pending.as_ref()?.len() * Self::QUERY_SIZE as usize;
+        let timestamps: Option<Vec<u64>> = queries
+            .readback
+            .try_read(device, |data| bytemuck::pod_collect_to_vec(&data[..size]));
+        if queries.readback.is_pending() {
+            return None;
+        }
+        // the readback finished, successfully or not
+        let labels = queries.pending.take()?;
+        let timestamps = timestamps?;
+        Some(
+            labels
+                .into_iter()
+                .zip(timestamps.chunks_exact(2))
+                .map(|(label, ticks)| PassTiming {
+                    label,
+                    milliseconds: ticks[1].saturating_sub(ticks[0]) as f32 * queries.period / 1_000_000.0,
+                })
+                .collect(),
+        )
+    }
+}
@@ -4,10 +4,12 @@ use egui_wgpu::renderer::ScreenDescriptor;
 use egui_wgpu::Renderer;
 
 use egui_winit::State;
-use wgpu::{CommandEncoder, Device, Queue, TextureFormat, TextureView};
+use wgpu::{CommandBuffer, CommandEncoder, Device, Queue, TextureFormat, TextureView};
 use winit::event::WindowEvent;
 use winit::window::Window;
 
+use crate::ui_resources::UiResources;
+
 pub struct EguiRenderer {
     pub context: Context,
     state: State,
@@ -49,6 +51,13 @@ impl EguiRenderer {
         }
     }
 
+    /**
+    The GUI's textures and paint callback resources, see `UiResources`.
+     */
+    pub(crate) fn resources(&mut self) -> UiResources<'_> {
+        UiResources::new(&mut self.renderer)
+    }
+
     pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) -> bool {
         let res = self.state.on_window_event(&self.context, event);
         res.consumed
@@ -63,7 +72,7 @@ impl EguiRenderer {
         window_surface_view: &TextureView,
         screen_descriptor: ScreenDescriptor,
         run_ui: impl FnOnce(&Context),
-    ) {
+    ) -> Vec<CommandBuffer> {
         // self.state.set_pixels_per_point(window.scale_factor() as f32);
         let raw_input = self.state.take_egui_input(&window);
         let full_output = self.context.run(raw_input, |ui| {
@@ -79,7 +88,9 @@ impl EguiRenderer {
         for (id, image_delta) in &full_output.textures_delta.set {
             self.renderer.update_texture(&device, &queue, *id, &image_delta);
         }
-        self.renderer
+        // paint callbacks may encode their own work, which has to be submitted before the encoder
+        let callback_commands = self
+            .renderer
             .update_buffers(&device, &queue, encoder, &tris, &screen_descriptor);
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -100,5 +111,6 @@ impl EguiRenderer {
         for x in &full_output.textures_delta.free {
             self.renderer.free_texture(x)
         }
+        callback_commands
     }
 }
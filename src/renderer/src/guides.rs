@@ -0,0 +1,110 @@
+use egui::{Color32, Context, LayerId, Painter, Pos2, Rect, Stroke};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AspectRatio {
+    SixteenByNine,
+    FourByThree,
+}
+
+impl AspectRatio {
+    pub fn ratio(&self) -> f32 {
+        match self {
+            AspectRatio::SixteenByNine => 16.0 / 9.0,
+            AspectRatio::FourByThree => 4.0 / 3.0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AspectRatio::SixteenByNine => "16:9",
+            AspectRatio::FourByThree => "4:3",
+        }
+    }
+}
+
+/**
+Composition guides drawn over the viewport. They are painted by the GUI on top of the final image, so they never end up
+in render targets or captures of the scene.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Guides {
+    /// frames the viewport to the given aspect ratio and dims the area outside of it
+    pub frame: Option<AspectRatio>,
+    pub thirds: bool,
+    /// 90% of the frame, within which action should stay
+    pub action_safe: bool,
+    /// 80% of the frame, within which text should stay
+    pub title_safe: bool,
+}
+
+impl Guides {
+    const COLOR: Color32 = Color32::from_rgba_premultiplied(200, 200, 200, 200);
+    const MASK: Color32 = Color32::from_rgba_premultiplied(0, 0, 0, 160);
+
+    pub fn is_empty(&self) -> bool {
+        self.frame.is_none() && !self.thirds && !self.action_safe && !self.title_safe
+    }
+
+    pub(crate) fn paint(&self, ctx: &Context) {
+        if self.is_empty() {
+            return;
+        }
+        // background layer, so that the guides are below all windows
+        let painter = ctx.layer_painter(LayerId::background());
+        let viewport = ctx.screen_rect();
+        let stroke = Stroke::new(1.0, Self::COLOR);
+
+        let frame = match self.frame {
+            Some(aspect) => {
+                let frame = Self::fit(viewport, aspect.ratio());
+                Self::mask_outside(&painter, viewport, frame);
+                painter.rect_stroke(frame, 0.0, stroke);
+                frame
+            }
+            None => viewport,
+        };
+        if self.thirds {
+            for i in 1..3 {
+                let t = i as f32 / 3.0;
+                let x = frame.left() + frame.width() * t;
+                let y = frame.top() + frame.height() * t;
+                painter.line_segment([Pos2::new(x, frame.top()), Pos2::new(x, frame.bottom())], stroke);
+                painter.line_segment([Pos2::new(frame.left(), y), Pos2::new(frame.right(), y)], stroke);
+            }
+        }
+        if self.action_safe {
+            painter.rect_stroke(frame.shrink2(frame.size() * 0.05), 0.0, stroke);
+        }
+        if self.title_safe {
+            painter.rect_stroke(frame.shrink2(frame.size() * 0.1), 0.0, stroke);
+        }
+    }
+
+    // largest rect of the given aspect ratio centered in `viewport`
+    fn fit(viewport: Rect, ratio: f32) -> Rect {
+        let size = if viewport.aspect_ratio() > ratio {
+            egui::vec2(viewport.height() * ratio, viewport.height())
+        } else {
+            egui::vec2(viewport.width(), viewport.width() / ratio)
+        };
+        Rect::from_center_size(viewport.center(), size)
+    }
+
+    fn mask_outside(painter: &Painter, viewport: Rect, frame: Rect) {
+        let bars = [
+            Rect::from_min_max(viewport.min, Pos2::new(viewport.right(), frame.top())),
+            Rect::from_min_max(Pos2::new(viewport.left(), frame.bottom()), viewport.max),
+            Rect::from_min_max(
+                Pos2::new(viewport.left(), frame.top()),
+                Pos2::new(frame.left(), frame.bottom()),
+            ),
+            Rect::from_min_max(
+                Pos2::new(frame.right(), frame.top()),
+                Pos2::new(viewport.right(), frame.bottom()),
+            ),
+        ];
+        for bar in bars.into_iter().filter(|bar| bar.is_positive()) {
+            painter.rect_filled(bar, 0.0, Self::MASK);
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use glam::Mat4;
+use hashbrown::HashMap;
+use log::{error, info, warn};
+use web_time::{Duration, Instant};
+
+use lib::managers::TexId;
+use lib::scene::Scene;
+use lib::texture::{Texture, TextureKind};
+use lib::Material;
+use systems::io::gltf_loader::load_gltf;
+
+use crate::commands::Command;
+use crate::events::Event;
+use crate::RenderState;
+
+// editors often write a file in several steps, so it is reloaded once it has been left alone for this long
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/**
+What was imported from a watched file, and what reloading it replaces.
+ */
+#[derive(Debug, Clone)]
+enum Import {
+    /// `Command::ImportTexture`, the texture is replaced in place
+    Texture { id: TexId, kind: TextureKind },
+    /// `Command::ImportFile`, the top-level models are replaced by the file's new models, keeping their transforms
+    Models(Vec<u32>),
+    /// `Command::LoadSceneFile`, the scenes are replaced by the file's new scenes, keeping their ids
+    Scenes(Vec<usize>),
+}
+
+/**
+Watches the files that textures, models and scenes were imported from, and imports them again when they change on disk,
+see `Meta::hot_reload`. Each file's directory is watched rather than the file itself, as many editors save by replacing
+the file.
+ */
+pub(crate) struct HotReload {
+    #[cfg(not(target_arch = "wasm32"))]
+    watcher: Option<notify::RecommendedWatcher>,
+    changes: flume::Receiver<PathBuf>,
+    imports: HashMap<PathBuf, Vec<Import>>,
+    // when each changed file last changed
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl HotReload {
+    pub(crate) fn new() -> Self {
+        let (sender, changes) = flume::unbounded();
+        #[cfg(not(target_arch = "wasm32"))]
+        let watcher = {
+            use notify::EventKind;
+
+            let handler = move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    for path in event.paths {
+                        // the receiver is only dropped along with the watcher
+                        let _ = sender.send(path);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Error while watching files: {}", e),
+            };
+            notify::recommended_watcher(handler)
+                .map_err(|e| warn!("Couldn't watch files, imports won't be reloaded: {}", e))
+                .ok()
+        };
+        #[cfg(target_arch = "wasm32")]
+        drop(sender);
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            watcher,
+            changes,
+            imports: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    fn watch(&mut self, path: &Path, import: Import) {
+        let Ok(path) = path.canonicalize() else {
+            return;
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (Some(watcher), Some(directory)) = (&mut self.watcher, path.parent()) {
+            use notify::{RecursiveMode, Watcher};
+
+            let watched = self.imports.keys().any(|other| other.parent() == Some(directory));
+            if !watched {
+                if let Err(e) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+                    warn!("Couldn't watch {:?}, it won't be reloaded: {}", directory, e);
+                    return;
+                }
+            }
+        }
+        self.imports.entry(path).or_default().push(import);
+    }
+
+    pub(crate) fn watch_texture(&mut self, path: &Path, id: TexId, kind: TextureKind) {
+        self.watch(path, Import::Texture { id, kind });
+    }
+
+    pub(crate) fn watch_models(&mut self, path: &Path, model_ids: Vec<u32>) {
+        self.watch(path, Import::Models(model_ids));
+    }
+
+    pub(crate) fn watch_scenes(&mut self, path: &Path, scene_ids: Vec<usize>) {
+        self.watch(path, Import::Scenes(scene_ids));
+    }
+
+    /**
+    Stops reloading a deleted scene and the models that were imported into it. Like imports that are gone after a
+    reload, their directories stay watched.
+     */
+    pub(crate) fn forget_scene(&mut self, scene_id: usize, model_ids: &[u32]) {
+        for imports in self.imports.values_mut() {
+            for import in imports.iter_mut() {
+                match import {
+                    Import::Models(ids) => ids.retain(|id| !model_ids.contains(id)),
+                    Import::Scenes(ids) => ids.retain(|id| *id != scene_id),
+                    Import::Texture { .. } => {}
+                }
+            }
+            imports.retain(|import| match import {
+                Import::Models(ids) => !ids.is_empty(),
+                Import::Scenes(ids) => !ids.is_empty(),
+                Import::Texture { .. } => true,
+            });
+        }
+        self.imports.retain(|_, imports| !imports.is_empty());
+        self.pending.retain(|path, _| self.imports.contains_key(path));
+    }
+}
+
+/**
+Reloads the watched files that changed and have settled since, and sends `Event::AssetReloaded` for each of them.
+Changes are ignored while `Meta::hot_reload` is disabled.
+ */
+pub(crate) fn update(state: &mut RenderState) {
+    let hot_reload = &mut state.hot_reload;
+    let now = Instant::now();
+    for path in hot_reload.changes.try_iter() {
+        if state.meta.hot_reload && hot_reload.imports.contains_key(&path) {
+            hot_reload.pending.insert(path, now);
+        }
+    }
+    let settled: Vec<PathBuf> = hot_reload
+        .pending
+        .iter()
+        .filter(|(_, changed)| now.duration_since(**changed) >= SETTLE_TIME)
+        .map(|(path, _)| path.clone())
+        .collect();
+    for path in settled {
+        state.hot_reload.pending.remove(&path);
+        let imports = state.hot_reload.imports.remove(&path).unwrap_or_default();
+        let mut reloaded = vec![];
+        let mut failed = false;
+        for import in imports {
+            match reload(state, &path, &import) {
+                Ok(Some(import)) => reloaded.push(import),
+                // everything that was imported from the file is gone
+                Ok(None) => {}
+                Err(e) => {
+                    error!("Couldn't reload {:?}: {:#}", path, e);
+                    failed = true;
+                    // tried again when the file changes the next time
+                    reloaded.push(import);
+                }
+            }
+        }
+        if reloaded.is_empty() {
+            continue;
+        }
+        state.hot_reload.imports.insert(path.clone(), reloaded);
+        if !failed {
+            info!("Reloaded {:?}", path);
+            // the hook may have dropped its receiver, in which case nobody is interested in the event
+            let _ = state.event_channel.0.send(Event::AssetReloaded(path));
+        }
+    }
+}
+
+// imports the file again in place of `import`, returns what it imported
+fn reload(state: &mut RenderState, path: &Path, import: &Import) -> Result<Option<Import>> {
+    match import {
+        Import::Texture { id, kind } => {
+            if !state.world.assets.textures.contains(*id) {
+                return Ok(None);
+            }
+            let image = image::open(path).context("Couldn't load the texture")?;
+            let name = path.file_name().and_then(OsStr::to_str);
+            let texture = Texture::from_image(&state.device, &state.queue, &image, name, *kind)?;
+            state.world.assets.textures.replace_texture(*id, texture);
+            state.meta.texture_thumbnails.replace(*id);
+            // materials without bindless textures bind the old texture's view
+            let materials: Vec<_> = state
+                .world
+                .assets
+                .materials
+                .iter_with_ids()
+                .filter(|(_, material)| match material {
+                    Material::Pbr(pbr) => pbr.texture_ids().any(|texture| texture == *id),
+                })
+                .map(|(material_id, _)| material_id)
+                .collect();
+            for material_id in materials {
+                let Material::Pbr(material) = state.world.assets.materials.get_material_mut(material_id);
+                material.create_texture_bind_group(
+                    &state.device,
+                    &state.pbr_pipeline.tex_bind_group_layout,
+                    &state.world.assets.textures,
+                );
+            }
+            state.world.assets.finish_load(path, None);
+            Ok(Some(import.clone()))
+        }
+        Import::Models(model_ids) => {
+            // the models may have been moved to another scene
+            let Some(scene_id) = state
+                .world
+                .scenes
+                .iter()
+                .find(|(_, scene)| model_ids.iter().any(|id| scene.get_model(*id).is_some()))
+                .map(|(id, _)| *id)
+            else {
+                return Ok(None);
+            };
+            let mut scene = load_file(state, path)?.remove(0);
+            let old_transforms: Vec<Option<Mat4>> = model_ids
+                .iter()
+                .map(|id| {
+                    state.world.scenes[&scene_id]
+                        .get_model(*id)
+                        .map(|model| model.local_transform)
+                })
+                .collect();
+            for (model, old_transform) in scene.models.iter_mut().zip(&old_transforms) {
+                if let Some(transform) = old_transform {
+                    model.local_transform = *transform;
+                    model.update_transforms(Mat4::IDENTITY);
+                }
+            }
+            for (model_id, old_transform) in model_ids.iter().zip(&old_transforms) {
+                if old_transform.is_some() {
+                    let event_sender = state.event_channel.0.clone();
+                    Command::DeleteModel(*model_id).try_process(state, &event_sender)?;
+                }
+            }
+            let new_ids = scene.models.iter().map(|model| model.id).collect();
+            if scene_id == state.world.active_scene {
+                state.join_into_active_scene(scene);
+            } else if let Some(target) = state.world.scenes.get_mut(&scene_id) {
+                target.join(
+                    scene,
+                    &state.device,
+                    &state.queue,
+                    &state.world.assets.materials,
+                    &state.pbr_pipeline.mesh_bind_group_layout,
+                    &state.pbr_pipeline.light_bind_group_layout,
+                );
+            }
+            Ok(Some(Import::Models(new_ids)))
+        }
+        Import::Scenes(scene_ids) => {
+            let scene_ids: Vec<usize> = scene_ids
+                .iter()
+                .copied()
+                .filter(|id| state.world.scenes.contains_key(id))
+                .collect();
+            if scene_ids.is_empty() {
+                return Ok(None);
+            }
+            let scenes = load_file(state, path)?;
+            for (&scene_id, mut scene) in scene_ids.iter().zip(scenes) {
+                for model in state.world.scenes[&scene_id].iter_models_deep() {
+                    state.imposter_pipeline.remove(model.id);
+                }
+                scene.id = scene_id as u32;
+                state.world.scenes.insert(scene_id, scene);
+            }
+            if scene_ids.contains(&state.world.active_scene) {
+                if !state.meta.selection.is_empty() {
+                    state.meta.selection.clear();
+                    state.selection_changed();
+                }
+                state.update_light_count();
+                state.world.update_active_scene(&state.queue); // updates lights and mesh info buffers
+            }
+            Ok(Some(Import::Scenes(scene_ids)))
+        }
+    }
+}
+
+fn load_file(state: &mut RenderState, path: &Path) -> Result<Vec<Scene>> {
+    let scenes = load_gltf(
+        path,
+        &state.device,
+        &state.queue,
+        state.pbr_pipeline.gltf_layouts(),
+        &mut state.world.assets.textures,
+        &mut state.world.assets.materials,
+    );
+    let error = scenes.as_ref().err().map(|e| format!("{:#}", e));
+    state.world.assets.finish_load(path, error);
+    let scenes = scenes?;
+    if scenes.is_empty() {
+        bail!("The file doesn't contain a scene anymore");
+    }
+    state
+        .world
+        .assets
+        .materials
+        .update_dirty(&state.queue, &state.world.assets.textures);
+    Ok(scenes)
+}
@@ -0,0 +1,55 @@
+/**
+How long the cursor has to rest before the mesh under it is picked, in seconds. Picking on every cursor move would
+render the picking pass far more often than hovering needs.
+ */
+pub const HOVER_DELAY: f32 = 0.15;
+
+/**
+Tracks the mesh under the cursor for `Event::Hover`.
+ */
+#[derive(Debug, Default)]
+pub(crate) struct Hover {
+    cursor: (u32, u32),
+    // seconds since the cursor last moved
+    resting: f32,
+    // whether the pixel under the resting cursor was picked already
+    requested: bool,
+    mesh_id: Option<u32>,
+}
+
+impl Hover {
+    pub(crate) fn cursor_moved(&mut self, cursor: (u32, u32)) {
+        self.cursor = cursor;
+        self.resting = 0.0;
+        self.requested = false;
+    }
+
+    /**
+    The pixel to pick, once the cursor rested on it for `HOVER_DELAY`.
+     */
+    pub(crate) fn update(&mut self, delta_time: f32) -> Option<(u32, u32)> {
+        self.resting += delta_time;
+        if self.requested || self.resting < HOVER_DELAY {
+            return None;
+        }
+        self.requested = true;
+        Some(self.cursor)
+    }
+
+    /**
+    Returns whether the hovered mesh changed.
+     */
+    pub(crate) fn picked(&mut self, mesh_id: Option<u32>) -> bool {
+        let changed = self.mesh_id != mesh_id;
+        self.mesh_id = mesh_id;
+        changed
+    }
+
+    pub(crate) fn mesh_id(&self) -> Option<u32> {
+        self.mesh_id
+    }
+
+    pub(crate) fn cursor(&self) -> (u32, u32) {
+        self.cursor
+    }
+}
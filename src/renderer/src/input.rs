@@ -0,0 +1,130 @@
+use glam::{IVec2, Vec2};
+use web_time::{Duration, Instant};
+use winit::event::MouseScrollDelta;
+
+use crate::drag::CLICK_DISTANCE;
+use crate::events::{Event, Modifiers, MouseButton};
+
+/**
+The longest time between two clicks of the same button that makes them a double click.
+ */
+pub const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+/**
+Pixels per line of scroll deltas that touchpads report in pixels, see `Event::Scroll`.
+ */
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/**
+Turns the mouse input of the window into `Event`s for the hook.
+ */
+#[derive(Debug, Default)]
+pub(crate) struct Mouse {
+    cursor: (u32, u32),
+    // the button that is held and where it was pressed, to tell clicks from drags
+    pressed: Option<(MouseButton, (u32, u32))>,
+    last_click: Option<(MouseButton, (u32, u32), Instant)>,
+}
+
+impl Mouse {
+    pub(crate) fn cursor(&self) -> (u32, u32) {
+        self.cursor
+    }
+
+    pub(crate) fn press(&mut self, button: MouseButton, modifiers: Modifiers) -> Event {
+        self.pressed = Some((button, self.cursor));
+        let (x, y) = self.cursor;
+        Event::MouseDown {
+            x,
+            y,
+            mouse_button: button,
+            modifiers,
+        }
+    }
+
+    /**
+    The `MouseUp` for the button, followed by a `Click` and `DoubleClick` if the cursor stayed in place.
+     */
+    pub(crate) fn release(&mut self, button: MouseButton, modifiers: Modifiers) -> Vec<Event> {
+        let (x, y) = self.cursor;
+        let mut events = vec![Event::MouseUp {
+            x,
+            y,
+            mouse_button: button,
+            modifiers,
+        }];
+        let pressed = match self.pressed {
+            Some((pressed_button, position)) if pressed_button == button => {
+                self.pressed = None;
+                position
+            }
+            _ => return events,
+        };
+        // moving the cursor in between makes it a drag rather than a click
+        if distance(pressed, self.cursor) > CLICK_DISTANCE {
+            return events;
+        }
+        events.push(Event::Click {
+            x,
+            y,
+            mouse_button: button,
+            modifiers,
+        });
+        let now = Instant::now();
+        let double_click = self.last_click.is_some_and(|(last_button, position, time)| {
+            last_button == button
+                && distance(position, self.cursor) <= CLICK_DISTANCE
+                && now.duration_since(time) <= DOUBLE_CLICK_TIME
+        });
+        if double_click {
+            events.push(Event::DoubleClick {
+                x,
+                y,
+                mouse_button: button,
+                modifiers,
+            });
+            // a third click starts over
+            self.last_click = None;
+        } else {
+            self.last_click = Some((button, self.cursor, now));
+        }
+        events
+    }
+
+    /**
+    A `Drag` if a button is held.
+     */
+    pub(crate) fn cursor_moved(&mut self, cursor: (u32, u32), modifiers: Modifiers) -> Option<Event> {
+        let previous = self.cursor;
+        self.cursor = cursor;
+        let (mouse_button, _) = self.pressed?;
+        Some(Event::Drag {
+            x: cursor.0,
+            y: cursor.1,
+            delta: IVec2::new(cursor.0 as i32 - previous.0 as i32, cursor.1 as i32 - previous.1 as i32),
+            mouse_button,
+            modifiers,
+        })
+    }
+}
+
+pub(crate) fn mouse_button(button: winit::event::MouseButton) -> Option<MouseButton> {
+    match button {
+        winit::event::MouseButton::Left => Some(MouseButton::Left),
+        winit::event::MouseButton::Right => Some(MouseButton::Right),
+        winit::event::MouseButton::Middle => Some(MouseButton::Middle),
+        _ => None,
+    }
+}
+
+pub(crate) fn scroll(delta: MouseScrollDelta, modifiers: Modifiers) -> Event {
+    let delta = match delta {
+        MouseScrollDelta::LineDelta(x, y) => Vec2::new(x, y),
+        MouseScrollDelta::PixelDelta(delta) => Vec2::new(delta.x as f32, delta.y as f32) / PIXELS_PER_LINE,
+    };
+    Event::Scroll { delta, modifiers }
+}
+
+fn distance(a: (u32, u32), b: (u32, u32)) -> f32 {
+    Vec2::new(a.0 as f32, a.1 as f32).distance(Vec2::new(b.0 as f32, b.1 as f32))
+}
@@ -1,34 +1,131 @@
-use std::sync::mpsc;
-use std::time::Instant;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use bumpalo::Bump;
+use bumpalo::collections::Vec as BumpVec;
 use egui_wgpu::renderer::ScreenDescriptor;
 use glam::Vec2;
 use hashbrown::HashMap;
-use wgpu::{Device, Features, Limits, PresentMode, Queue, Surface, SurfaceConfiguration, SurfaceError};
+use log::{error, warn};
+use web_time::Instant;
+use wgpu::{
+    AdapterInfo, CommandEncoder, Device, Features, Limits, Queue, Surface, SurfaceCapabilities, SurfaceConfiguration,
+    SurfaceError,
+};
 use wgpu::PresentMode::AutoVsync;
 use winit::event::{DeviceEvent, ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::{Window, WindowBuilder};
+use winit::window::Window;
 
+use lib::assets::AssetServer;
 use lib::managers::{MaterialManager, TextureManager};
-use lib::scene::World;
+use lib::scene::bindings::BindingInputs;
+use lib::scene::mesh::{Mesh, Outline};
+use lib::scene::sky::Background;
+use lib::scene::{Scene, World};
+use lib::staging::Staging;
 
-use crate::camera::{Camera, KeyState};
+use crate::adapter::AdapterSelection;
+use crate::calibration::Calibration;
+use crate::camera::{Camera, DepthMode, KeyState};
+use crate::commands::CommandResult;
+use crate::crash_report::{CrashReporter, FrameStats};
+use crate::debug_draw::DebugDraw;
+use crate::drag::{Drag, Dragger};
 use crate::events::{Event, MouseButton};
-use crate::pipelines::grid::GridPipeline;
-use crate::pipelines::object_picking::ObjectPickingPipeline;
-use crate::pipelines::outlining::OutliningPipeline;
-use crate::pipelines::pbr::PBRPipeline;
+use crate::gpu_memory::GpuMemory;
+use crate::gpu_timing::{GpuTimer, PassBudgets, PassTiming};
+use crate::guides::Guides;
+use crate::hover::Hover;
+use crate::light_lod::LightLod;
+use crate::live_link::LiveLink;
+use crate::material_inspector::MaterialPreview;
+use crate::physics_debug::PhysicsDebug;
+use crate::pipelines::axes_gizmo::{AxesGizmo, AxesGizmoPipeline};
+use crate::pipelines::depth_prepass::DepthPrepassPipeline;
+use crate::pipelines::grading::GradingPipeline;
+use crate::pipelines::grid::{Grid, GridPipeline};
+use crate::pipelines::imposter::{ImposterLod, ImposterPipeline};
+use crate::pipelines::jump_flood::JumpFloodPipeline;
+use crate::pipelines::light_gizmos::{LightGizmoPipeline, LightGizmos};
+use crate::pipelines::lines::LinePipeline;
+use crate::pipelines::material_preview::MaterialPreviewPipeline;
+use crate::pipelines::motion_blur::{MotionBlur, MotionBlurPipeline};
+use crate::pipelines::object_picking::{ObjectPickingPipeline, PickRequest, PickResult};
+use crate::pipelines::outlining::{OutlineMode, OutliningPipeline};
+use crate::pipelines::pbr::{DrawInputs, DrawStats, PBRPipeline, RenderLayer, ViewPass};
+use crate::pipelines::post_effects::PostEffectsPipeline;
+use crate::pipelines::post_process::{PostProcess, PostProcessChain, PostProcessContext};
+use crate::pipelines::shadows::{ShadowMaps, ShadowPipeline};
+use crate::pipelines::sky::SkyPipeline;
+use crate::pipelines::split_screen::{SplitScreen, SplitScreenPipeline};
+use crate::pipelines::stereo::{Stereo, StereoPipeline};
+use crate::pipelines::water::WaterPipeline;
+use crate::profiling::{CpuProfiler, FrameHistory, FrameSample};
+use crate::selection::Selection;
+use crate::settings::Settings;
+use crate::snap::Snap;
+use crate::stats_overlay::StatsOverlay;
+use crate::streaming::{CellStreamer, Streaming};
+use crate::thumbnails::TextureThumbnails;
+use crate::time::TimeControl;
+use crate::ui_resources::UiResources;
+use crate::window::{RunConfig, WindowMode};
+use crate::world_builder::WorldBuilder;
 
+pub mod adapter;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod calibration;
 pub mod camera;
 pub mod commands;
+mod crash_report;
+pub mod debug_draw;
+pub mod drag;
 pub mod events;
+pub mod gpu_memory;
+pub mod gpu_timing;
 mod gui;
+pub mod guides;
+mod hot_reload;
+pub mod hover;
+pub mod input;
+pub mod light_lod;
+pub mod live_link;
+pub mod lut;
+pub mod material_inspector;
+#[cfg(feature = "physics")]
+pub mod physics;
+pub mod physics_debug;
 pub mod pipelines;
+pub mod profiling;
+pub mod readback;
+mod recovery;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod selection;
+mod settings;
+pub mod snap;
+pub mod stats_overlay;
+pub mod streaming;
+pub mod thumbnails;
+pub mod time;
+pub mod ui_resources;
+pub mod window;
+pub mod world_builder;
+
+pub use wgpu::{PresentMode, TextureFormat};
 
 pub trait Hook {
-    fn setup<'a>(&mut self, commands: mpsc::Sender<commands::Command>, event_receiver: mpsc::Receiver<Event>);
+    fn setup<'a>(
+        &mut self,
+        commands: mpsc::Sender<commands::Command>,
+        event_receiver: mpsc::Receiver<Event>,
+        world: &mut WorldBuilder,
+    );
 
     fn update(&mut self, keys: &KeyState, delta_time: f32, world: &mut World);
 
@@ -40,19 +137,76 @@ pub trait Hook {
         sender: mpsc::Sender<commands::Command>,
         meta: &mut Meta,
     );
+
+    /**
+    Draws the HUD of one player while split-screen rendering is enabled. `ui` covers the player's partition of the
+    screen, and is drawn below the regular UI of `update_ui`.
+     */
+    fn player_hud(&mut self, _ui: &mut egui::Ui, _player: usize) {}
+
+    /**
+    Custom post-processing passes, which run after the built-in ones in the returned order. Called once before `setup`,
+    and again after the graphics device was recreated, see `Event::DeviceRecovered`.
+     */
+    fn post_processes(&mut self, _device: &Device, _chain: &PostProcessChain) -> Vec<Box<dyn PostProcess>> {
+        vec![]
+    }
+
+    /**
+    Called every frame before `update_ui` while the GUI is shown, to prepare what the UI draws: e.g. rendering into
+    the hook's own textures with `encoder` and registering them as egui images, or storing the resources of paint
+    callbacks. See `UiResources`.
+     */
+    fn prepare_ui(
+        &mut self,
+        _device: &Device,
+        _queue: &Queue,
+        _encoder: &mut CommandEncoder,
+        _world: &World,
+        _resources: &mut UiResources,
+    ) {
+    }
+
+    /**
+    Called every frame after `update` to draw debug lines into `draw`, which starts out empty each frame.
+     */
+    fn debug_draw(&mut self, _world: &World, _draw: &mut DebugDraw) {}
 }
 
 pub struct RenderState {
     pub device: Device,
+    // kept to request a new device if the current one is lost
+    instance: wgpu::Instance,
+    adapter_selection: AdapterSelection,
+    // set when the device was lost, see `recovery::recover`
+    device_lost: Arc<AtomicBool>,
+    // consecutive frames whose surface texture was lost, see `recovery::MAX_SURFACE_LOSSES`
+    surface_losses: u32,
     surface: Surface,
     surface_config: SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     pub window: Window,
     queue: Queue,
     pbr_pipeline: PBRPipeline,
+    depth_prepass_pipeline: DepthPrepassPipeline,
+    shadow_pipeline: ShadowPipeline,
     object_picking_pipeline: ObjectPickingPipeline,
     outlining_pipeline: OutliningPipeline,
+    jump_flood_pipeline: JumpFloodPipeline,
     grid_pipeline: GridPipeline,
+    line_pipeline: LinePipeline,
+    axes_gizmo_pipeline: AxesGizmoPipeline,
+    light_gizmo_pipeline: LightGizmoPipeline,
+    sky_pipeline: SkyPipeline,
+    imposter_pipeline: ImposterPipeline,
+    water_pipeline: WaterPipeline,
+    stereo_pipeline: StereoPipeline,
+    split_screen_pipeline: SplitScreenPipeline,
+    material_preview_pipeline: MaterialPreviewPipeline,
+    post_process_chain: PostProcessChain,
+    motion_blur_pipeline: MotionBlurPipeline,
+    post_effects_pipeline: PostEffectsPipeline,
+    grading_pipeline: GradingPipeline,
     camera: Camera,
     world: World,
     hook: Box<dyn Hook>,
@@ -61,64 +215,355 @@ pub struct RenderState {
     command_channel: (mpsc::Sender<commands::Command>, mpsc::Receiver<commands::Command>), // Commands: impl -> renderer
     event_channel: (mpsc::Sender<Event>, Option<mpsc::Receiver<Event>>),                   // Events: renderer -> impl
     meta: Meta,
+    gpu_timer: GpuTimer,
+    cpu_profiler: CpuProfiler,
+    streamer: CellStreamer,
+    live_link: Option<LiveLink>,
+    hot_reload: hot_reload::HotReload,
+    staging: Staging,
+    #[cfg(feature = "scripting")]
+    scripts: scripting::Scripts,
+    #[cfg(feature = "physics")]
+    physics: physics::Physics,
+    #[cfg(feature = "audio")]
+    audio: audio::Audio,
+    crash_reporter: Option<CrashReporter>,
+    // a report of a crash during the last run, offered to the user until they close the dialog
+    crash_dialog: Option<PathBuf>,
+    // scratch memory for transient per-frame data like mesh lists, reset at the start of every frame
+    frame_arena: Bump,
+    dragger: Dragger,
+    hover: Hover,
+    debug_draw: DebugDraw,
 }
 const FRAME_TIME_WINDOW: usize = 1000;
+// how many mesh buffer entries may be moved per frame to close holes left by removed meshes
+const MESH_DEFRAG_MOVES_PER_FRAME: usize = 8;
 pub struct Meta {
     pub frame_time: f32,
     frame_times : [f32; FRAME_TIME_WINDOW],
     index: usize,
     pub show_grid: bool,
+    /// the grid's spacing, colors and fade, see `RunConfig::settings` to keep it across runs
+    pub grid: Grid,
+    /// the orientation widget in the corner and the axes at the selection
+    pub axes_gizmo: AxesGizmo,
+    /// icons at the lights, which select the light's model when clicked, and spheres of their range
+    pub light_gizmos: LightGizmos,
+    /// whether the present mode waits for vertical blanks, see `Command::SetVsync`
     pub vsync: bool,
+    /// the surface's present mode, see `Command::SetPresentMode`
+    pub present_mode: PresentMode,
+    supported_present_modes: Vec<PresentMode>,
+    /// the surface's color format, which everything rendering to the screen renders in, see
+    /// `Command::SetSurfaceFormat`
+    pub surface_format: TextureFormat,
+    supported_surface_formats: Vec<TextureFormat>,
+    /// whether the window is fullscreen, see `Command::SetWindowMode`
+    pub window_mode: WindowMode,
+    /// restricts the on-screen PBR pass to a single lighting layer, for debugging
+    pub debug_layer: RenderLayer,
+    /// renders the depth of the meshes before shading them, which pays off in scenes with a lot of overdraw. Compare
+    /// the "Depth pre-pass" and "PBR" GPU timings to decide
+    pub depth_prepass: bool,
+    /// how outlined meshes are outlined, the scene's outline width is in pixels with `OutlineMode::JumpFlood`
+    pub outline_mode: OutlineMode,
+    /// display calibration applied when tone mapping
+    pub calibration: Calibration,
+    pub motion_blur: MotionBlur,
+    /// composition guides drawn over the viewport
+    pub guides: Guides,
+    /// stereoscopic preview, replaces the regular view while enabled
+    pub stereo: Stereo,
+    /// renders one view per player into partitions of the screen, takes precedence over the stereo preview
+    pub split_screen: SplitScreen,
+    /// physics visualizations drawn over the scene
+    pub physics_debug: PhysicsDebug,
+    /// fades out distant and small lights
+    pub light_lod: LightLod,
+    /// draws distant models that have an imposter as their imposter
+    pub imposter_lod: ImposterLod,
+    /// loads and unloads cells of the world around the camera
+    pub streaming: Streaming,
+    /// GPU time of each pass of a recent frame, empty if the device doesn't support timestamp queries
+    pub gpu_timings: Vec<PassTiming>,
+    /// limits for the GPU time of passes, see `PassBudgets`
+    pub pass_budgets: PassBudgets,
+    /// CPU time of each part of the last frame: update, scene buffer updates, command processing, encoding and submit
+    pub cpu_timings: Vec<PassTiming>,
+    /// timings of the most recent frames, see `profiling::FrameGraph`
+    pub frame_history: FrameHistory,
+    live_link: Option<SocketAddr>,
+    adapter: AdapterInfo,
+    selection: Selection,
+    /// moving the selection with the mouse
+    pub drag: Drag,
+    /// the steps that moves and rotations of models snap to
+    pub snap: Snap,
+    /// the sphere that `material_inspector` renders materials onto
+    pub material_preview: MaterialPreview,
+    /// the world's textures as egui images
+    pub texture_thumbnails: TextureThumbnails,
+    /// pauses and scales the scene's time, see `Command::SetTimeScale`
+    pub time: TimeControl,
+    /// skips rendering while the update loop, commands and the hook keep running, see `Command::SetSimulationOnly`
+    pub simulation_only: bool,
+    /// imports textures, models and scenes again when the files they were imported from change
+    pub hot_reload: bool,
+    /// GPU memory taken up by the world, measured every frame. The budget can be set, e.g. from the GUI
+    pub gpu_memory: GpuMemory,
+    /// draw calls, bind group switches and triangles of the last frame's main PBR pass
+    pub draw_stats: DrawStats,
+    /// frame rate, draw stats and GPU memory drawn over the viewport
+    pub stats_overlay: StatsOverlay,
+}
+
+impl Meta {
+    /**
+    The present modes that the surface supports, besides `AutoVsync` and `AutoNoVsync` which pick one of them.
+     */
+    pub fn supported_present_modes(&self) -> &[PresentMode] {
+        &self.supported_present_modes
+    }
+
+    /**
+    The color formats that the surface supports, the preferred one first.
+     */
+    pub fn supported_surface_formats(&self) -> &[TextureFormat] {
+        &self.supported_surface_formats
+    }
+
+    /**
+    The graphics adapter the renderer uses, see `RunConfig::adapter`.
+     */
+    pub fn adapter(&self) -> &AdapterInfo {
+        &self.adapter
+    }
+
+    /**
+    The address the live link listens on, if it is running, see `Command::StartLiveLink`.
+     */
+    pub fn live_link(&self) -> Option<SocketAddr> {
+        self.live_link
+    }
+
+    /**
+    The selected meshes of the active scene, see `Command::Select`.
+     */
+    pub fn selection(&self) -> &Selection {
+        &self.selection
+    }
+}
+
+/**
+Requests the adapter that `selection` picks and a device that renders to `surface`, with the optional features the
+renderer makes use of.
+ */
+async fn request_device(
+    instance: &wgpu::Instance,
+    surface: &Surface,
+    selection: &AdapterSelection,
+) -> Result<(wgpu::Adapter, Device, Queue)> {
+    // adapter is handle to the graphics card (to get its name, backend etc.)
+    let adapter = adapter::request_adapter(instance, surface, selection).await?;
+
+    // per-draw data like mesh indices is passed as push constants where supported, otherwise through a uniform
+    // buffer bound next to the shadow maps or in a bind group of its own, see `DrawConstants`
+    let adapter_limits = adapter.limits();
+    let push_constants =
+        adapter.features().contains(Features::PUSH_CONSTANTS) && adapter_limits.max_push_constant_size >= 32;
+    if !push_constants {
+        warn!("Push constants are unsupported, passing per-draw data through uniform buffers instead");
+    }
+    // the defaults exceed what e.g. WebGPU adapters offer, the pipelines only rely on the bind groups
+    let mut limits = adapter::clamp_limits(
+        Limits {
+            max_bind_groups: PBRPipeline::BIND_GROUPS,
+            max_push_constant_size: if push_constants { 32 } else { 0 },
+            ..Default::default()
+        },
+        &adapter_limits,
+    );
+    if limits.max_bind_groups < PBRPipeline::BIND_GROUPS {
+        bail!(
+            "The graphics adapter {:?} supports {} bind groups, but the PBR pipeline needs {}",
+            adapter.get_info().name,
+            adapter_limits.max_bind_groups,
+            PBRPipeline::BIND_GROUPS
+        );
+    }
+    let mut features = if push_constants {
+        Features::PUSH_CONSTANTS
+    } else {
+        Features::empty()
+    };
+    // bindless textures for the PBR pass where supported, otherwise materials bind their textures per draw
+    if adapter.features().contains(PBRPipeline::BINDLESS_FEATURES) {
+        features |= PBRPipeline::BINDLESS_FEATURES;
+        limits.max_sampled_textures_per_shader_stage = adapter_limits
+            .max_sampled_textures_per_shader_stage
+            .min(PBRPipeline::MAX_BINDLESS_TEXTURES);
+        limits.max_samplers_per_shader_stage = adapter_limits
+            .max_samplers_per_shader_stage
+            .min(PBRPipeline::MAX_BINDLESS_TEXTURES);
+    }
+    // per-pass GPU timings where supported
+    features |= adapter.features() & Features::TIMESTAMP_QUERY;
+
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                limits,
+                features,
+            },
+            None,
+        )
+        .await
+        .context("Couldn't open the graphics device")?;
+    Ok((adapter, device, queue))
+}
+
+// sRGB, so that shaders can output linear colors
+fn preferred_surface_format(caps: &SurfaceCapabilities) -> TextureFormat {
+    caps.formats
+        .iter()
+        .copied()
+        .find(|f| f.is_srgb())
+        .unwrap_or(caps.formats[0])
 }
 
+// all render pipelines of the renderer, created in one place so that they can be recreated together on a new device
+struct Pipelines {
+    pbr_pipeline: PBRPipeline,
+    depth_prepass_pipeline: DepthPrepassPipeline,
+    shadow_pipeline: ShadowPipeline,
+    object_picking_pipeline: ObjectPickingPipeline,
+    outlining_pipeline: OutliningPipeline,
+    jump_flood_pipeline: JumpFloodPipeline,
+    grid_pipeline: GridPipeline,
+    line_pipeline: LinePipeline,
+    axes_gizmo_pipeline: AxesGizmoPipeline,
+    light_gizmo_pipeline: LightGizmoPipeline,
+    sky_pipeline: SkyPipeline,
+    imposter_pipeline: ImposterPipeline,
+    water_pipeline: WaterPipeline,
+    stereo_pipeline: StereoPipeline,
+    split_screen_pipeline: SplitScreenPipeline,
+    material_preview_pipeline: MaterialPreviewPipeline,
+    post_process_chain: PostProcessChain,
+    motion_blur_pipeline: MotionBlurPipeline,
+    post_effects_pipeline: PostEffectsPipeline,
+    grading_pipeline: GradingPipeline,
+}
+
+impl Pipelines {
+    fn new(device: &Device, surface_config: &SurfaceConfiguration, camera: &Camera) -> Self {
+        let depth_mode = camera.depth_mode();
+        let mut pbr_pipeline = PBRPipeline::new(device, surface_config, camera);
+        pbr_pipeline.create_pipeline(device, surface_config.format, depth_mode);
+
+        let mut depth_prepass_pipeline = DepthPrepassPipeline::new(device, camera);
+        depth_prepass_pipeline.create_pipeline(device, depth_mode);
+
+        let shadow_pipeline =
+            ShadowPipeline::new(device, &pbr_pipeline.shadow_maps, &pbr_pipeline.mesh_bind_group_layout);
+
+        let mut object_picking_pipeline = ObjectPickingPipeline::new(device, surface_config, camera);
+        object_picking_pipeline.create_pipeline(device, depth_mode);
+
+        let mut outlining_pipeline = OutliningPipeline::new(device, surface_config, camera);
+        outlining_pipeline.create_pipelines(device, surface_config.format);
+        let mut jump_flood_pipeline = JumpFloodPipeline::new(device, surface_config, camera);
+        jump_flood_pipeline.create_pipelines(device, surface_config.format);
+
+        let mut grid_pipeline = GridPipeline::new(device, surface_config, camera);
+        grid_pipeline.create_pipeline(device, surface_config.format, depth_mode);
+
+        let mut line_pipeline = LinePipeline::new(device, camera);
+        line_pipeline.create_pipelines(device, surface_config.format, depth_mode);
+        let mut axes_gizmo_pipeline = AxesGizmoPipeline::new(device);
+        axes_gizmo_pipeline.create_pipeline(device, surface_config.format);
+        let mut light_gizmo_pipeline = LightGizmoPipeline::new(device, camera);
+        light_gizmo_pipeline.create_pipeline(device, surface_config.format);
+
+        let mut sky_pipeline = SkyPipeline::new(device, camera);
+        sky_pipeline.create_pipeline(device, surface_config.format);
+
+        let mut imposter_pipeline = ImposterPipeline::new(device, camera);
+        imposter_pipeline.create_pipeline(device, surface_config.format, depth_mode);
+
+        let mut water_pipeline = WaterPipeline::new(device, surface_config, camera);
+        water_pipeline.create_pipeline(device, surface_config.format, depth_mode);
+
+        let mut stereo_pipeline = StereoPipeline::new(device, surface_config, camera);
+        stereo_pipeline.create_pipeline(device, surface_config.format);
+
+        let mut split_screen_pipeline = SplitScreenPipeline::new(device, surface_config, camera);
+        split_screen_pipeline.create_pipeline(device, surface_config.format);
+
+        let mut material_preview_pipeline = MaterialPreviewPipeline::new(device, camera, &pbr_pipeline);
+        material_preview_pipeline.create_pipeline(device);
+
+        let post_process_chain = PostProcessChain::new(device, surface_config);
+
+        let mut motion_blur_pipeline = MotionBlurPipeline::new(device, surface_config, camera, &post_process_chain);
+        motion_blur_pipeline.create_pipelines(device, &post_process_chain, depth_mode);
+
+        let mut post_effects_pipeline = PostEffectsPipeline::new(device, &post_process_chain);
+        post_effects_pipeline.create_pipeline(device, &post_process_chain);
+
+        let mut grading_pipeline = GradingPipeline::new(device, &post_process_chain);
+        grading_pipeline.create_pipeline(device, &post_process_chain);
+
+        Self {
+            pbr_pipeline,
+            depth_prepass_pipeline,
+            shadow_pipeline,
+            object_picking_pipeline,
+            outlining_pipeline,
+            jump_flood_pipeline,
+            grid_pipeline,
+            line_pipeline,
+            axes_gizmo_pipeline,
+            light_gizmo_pipeline,
+            sky_pipeline,
+            imposter_pipeline,
+            water_pipeline,
+            stereo_pipeline,
+            split_screen_pipeline,
+            material_preview_pipeline,
+            post_process_chain,
+            motion_blur_pipeline,
+            post_effects_pipeline,
+            grading_pipeline,
+        }
+    }
+}
 
 impl RenderState {
-    async fn new(window: Window, hook: impl Hook + 'static) -> Self {
+    async fn new(
+        window: Window,
+        hook: impl Hook + 'static,
+        adapter_selection: AdapterSelection,
+        crash_reporter: Option<CrashReporter>,
+    ) -> Result<Self> {
         let size = window.inner_size();
         assert_ne!(size.width, 0);
         assert_ne!(size.height, 0);
 
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
+        let instance = adapter::create_instance();
         // Safety: Surface needs to live as long as the window that created it. State owns window, so this is safe.
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
-        // adapter is handle to the graphics card (to get its name, backend etc.)
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let limits = Limits {
-            max_bind_groups: 5,
-            max_push_constant_size: 32,
-            ..Default::default()
-        };
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    limits,
-                    features: Features::PUSH_CONSTANTS,
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let surface =
+            unsafe { instance.create_surface(&window) }.context("Couldn't create a surface for the window")?;
+        let (adapter, device, queue) = request_device(&instance, &surface, &adapter_selection).await?;
+        if let Some(crash_reporter) = &crash_reporter {
+            crash_reporter.set_device(&adapter, &device);
+        }
+        let device_lost = recovery::watch_device(&device, crash_reporter.clone());
 
         let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
+        let surface_format = preferred_surface_format(&surface_caps);
         let surface_config = SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -131,49 +576,86 @@ impl RenderState {
         surface.configure(&device, &surface_config);
 
         let camera = Camera::new_default(size.width as f32, size.height as f32, &device);
-        let mut pbr_pipeline = PBRPipeline::new(&device, &surface_config, &camera);
-        pbr_pipeline.create_pipeline(&device);
+        let Pipelines {
+            pbr_pipeline,
+            depth_prepass_pipeline,
+            shadow_pipeline,
+            object_picking_pipeline,
+            outlining_pipeline,
+            jump_flood_pipeline,
+            grid_pipeline,
+            line_pipeline,
+            axes_gizmo_pipeline,
+            light_gizmo_pipeline,
+            sky_pipeline,
+            imposter_pipeline,
+            water_pipeline,
+            stereo_pipeline,
+            split_screen_pipeline,
+            material_preview_pipeline,
+            post_process_chain,
+            motion_blur_pipeline,
+            post_effects_pipeline,
+            grading_pipeline,
+        } = Pipelines::new(&device, &surface_config, &camera);
 
-        let textures = TextureManager::new(&device, &queue);
+        let mut textures = TextureManager::new();
         let materials = MaterialManager::new(
             &device,
             &queue,
             &pbr_pipeline.mat_bind_group_layout,
             &pbr_pipeline.tex_bind_group_layout,
-            &textures,
+            &mut textures,
         );
 
         let world = World {
             scenes: HashMap::new(),
             active_scene: 0,
-            materials,
-            textures,
+            assets: AssetServer::new(textures, materials),
         };
 
-        let mut object_picking_pipeline = ObjectPickingPipeline::new(&device, &surface_config, &camera);
-        object_picking_pipeline.create_pipeline(&device);
-
-        let mut outlining_pipeline = OutliningPipeline::new(&device, &surface_config, &camera);
-        outlining_pipeline.create_pipelines(&device);
-
-        let mut grid_pipeline = GridPipeline::new(&device, &surface_config, &camera);
-        grid_pipeline.create_pipeline(&device);
+        let gpu_timer = GpuTimer::new(&device, &queue);
 
-        let egui = gui::EguiRenderer::new(&device, surface_config.format, None, 1, &window);
+        let mut egui = gui::EguiRenderer::new(&device, surface_config.format, None, 1, &window);
+        let material_preview = MaterialPreview::new(egui.resources().register_texture(
+            &device,
+            material_preview_pipeline.output(),
+            wgpu::FilterMode::Linear,
+        ));
         let event_channel = mpsc::channel();
         let event_channel = (event_channel.0, Some(event_channel.1));
 
-        Self {
+        Ok(Self {
             window,
+            instance,
+            adapter_selection,
+            device_lost,
+            surface_losses: 0,
             surface,
             device,
             queue,
             surface_config,
             size,
             pbr_pipeline,
+            depth_prepass_pipeline,
+            shadow_pipeline,
             object_picking_pipeline,
             outlining_pipeline,
+            jump_flood_pipeline,
             grid_pipeline,
+            line_pipeline,
+            axes_gizmo_pipeline,
+            light_gizmo_pipeline,
+            sky_pipeline,
+            imposter_pipeline,
+            water_pipeline,
+            stereo_pipeline,
+            split_screen_pipeline,
+            material_preview_pipeline,
+            post_process_chain,
+            motion_blur_pipeline,
+            post_effects_pipeline,
+            grading_pipeline,
             camera,
             world,
             show_gui: true,
@@ -186,22 +668,275 @@ impl RenderState {
                 frame_times: [0.0; FRAME_TIME_WINDOW],
                 index: 0,
                 show_grid: false,
+                grid: Grid::default(),
+                axes_gizmo: AxesGizmo::default(),
+                light_gizmos: LightGizmos::default(),
                 vsync: true,
+                present_mode: AutoVsync,
+                supported_present_modes: surface_caps.present_modes.clone(),
+                surface_format,
+                supported_surface_formats: surface_caps.formats.clone(),
+                window_mode: WindowMode::Windowed,
+                debug_layer: RenderLayer::Full,
+                depth_prepass: false,
+                outline_mode: OutlineMode::default(),
+                calibration: Calibration::default(),
+                motion_blur: MotionBlur::default(),
+                guides: Guides::default(),
+                stereo: Stereo::default(),
+                split_screen: SplitScreen::default(),
+                physics_debug: PhysicsDebug::default(),
+                light_lod: LightLod::default(),
+                imposter_lod: ImposterLod::default(),
+                streaming: Streaming::default(),
+                gpu_timings: vec![],
+                pass_budgets: PassBudgets::default(),
+                cpu_timings: vec![],
+                frame_history: FrameHistory::default(),
+                live_link: None,
+                adapter: adapter.get_info(),
+                selection: Selection::default(),
+                drag: Drag::default(),
+                snap: Snap::default(),
+                material_preview,
+                texture_thumbnails: TextureThumbnails::default(),
+                time: TimeControl::default(),
+                simulation_only: false,
+                hot_reload: true,
+                gpu_memory: GpuMemory::default(),
+                draw_stats: DrawStats::default(),
+                stats_overlay: StatsOverlay::default(),
             },
-        }
+            gpu_timer,
+            cpu_profiler: CpuProfiler::default(),
+            streamer: CellStreamer::default(),
+            live_link: None,
+            hot_reload: hot_reload::HotReload::new(),
+            staging: Staging::new(),
+            #[cfg(feature = "scripting")]
+            scripts: scripting::Scripts::new(),
+            #[cfg(feature = "physics")]
+            physics: physics::Physics::new(),
+            #[cfg(feature = "audio")]
+            audio: audio::Audio::new(),
+            crash_reporter,
+            crash_dialog: None,
+            frame_arena: Bump::new(),
+            dragger: Dragger::default(),
+            hover: Hover::default(),
+            debug_draw: DebugDraw::default(),
+        })
     }
 
     fn setup(&mut self) {
-        self.hook
-            .setup(self.command_channel.0.clone(), self.event_channel.1.take().unwrap());
+        for pass in self.hook.post_processes(&self.device, &self.post_process_chain) {
+            self.post_process_chain.push(pass);
+        }
+        let mut world_builder = WorldBuilder::new();
+        self.hook.setup(
+            self.command_channel.0.clone(),
+            self.event_channel.1.take().unwrap(),
+            &mut world_builder,
+        );
+        world_builder.build(self, self.event_channel.0.clone());
         while let Ok(command) = self.command_channel.1.try_recv() {
             command.process(self, self.event_channel.0.clone());
         }
     }
+
+    /** Inserts an empty scene under the active scene id if the world doesn't contain one yet. */
+    fn ensure_active_scene(&mut self) {
+        let id = self.world.active_scene;
+        if self.world.scenes.contains_key(&id) {
+            return;
+        }
+        let scene = self.empty_scene(id, "Scene".into());
+        self.world.scenes.insert(id, scene);
+    }
+
+    fn empty_scene(&self, id: usize, name: Box<str>) -> Scene {
+        let mut scene = Scene::from(
+            &self.device,
+            &self.queue,
+            vec![],
+            &self.world.assets.materials,
+            Some(name),
+            &self.pbr_pipeline.mesh_bind_group_layout,
+            &self.pbr_pipeline.light_bind_group_layout,
+        );
+        scene.id = id as u32;
+        scene
+    }
+
+    /** Adds the models of `scene` to the active scene, creating it if necessary. */
+    fn join_into_active_scene(&mut self, scene: Scene) {
+        self.ensure_active_scene();
+        if let Some(active_scene) = self.world.scenes.get_mut(&self.world.active_scene) {
+            active_scene.join(
+                scene,
+                &self.device,
+                &self.queue,
+                &self.world.assets.materials,
+                &self.pbr_pipeline.mesh_bind_group_layout,
+                &self.pbr_pipeline.light_bind_group_layout,
+            );
+        }
+        self.world
+            .assets
+            .materials
+            .update_dirty(&self.queue, &self.world.assets.textures);
+        self.update_light_count();
+        self.world.update_active_scene(&self.queue); // updates lights and mesh info buffers
+    }
+
+    /** Resizes the camera's light data to the lights of the active scene, if there is one. */
+    fn update_light_count(&mut self) {
+        // without an active scene there is nothing to light
+        let count = self
+            .world
+            .get_active_scene()
+            .map_or(0, |scene| scene.light_buffer.len() as u32);
+        self.camera.update_light_count(count);
+        self.camera.update_view(&self.queue);
+    }
+
+    /** Renders the next queued picking query and handles the result of the last one once it was read back. */
+    fn update_picking(&mut self) {
+        match self.object_picking_pipeline.poll(&self.device) {
+            Some(PickResult::Point(PickRequest::Query, hit)) => {
+                let _ = self
+                    .event_channel
+                    .0
+                    .send(Event::CommandResult(CommandResult::ClickQuery(hit)));
+            }
+            Some(PickResult::Point(PickRequest::Select(mode), hit)) => {
+                let meshes = hit.map(|hit| vec![hit.mesh_id]).unwrap_or_default();
+                self.meta.selection.select(&meshes, mode);
+                self.selection_changed();
+            }
+            Some(PickResult::Point(PickRequest::Drag, hit)) => {
+                self.dragger
+                    .picked(hit, &self.meta.selection, &self.meta.drag, &self.camera);
+            }
+            Some(PickResult::Point(PickRequest::Hover, hit)) => {
+                let mesh_id = hit.map(|hit| hit.mesh_id);
+                if self.hover.picked(mesh_id) {
+                    let (x, y) = self.hover.cursor();
+                    let _ = self.event_channel.0.send(Event::Hover { mesh_id, x, y });
+                }
+            }
+            Some(PickResult::Region(meshes)) => {
+                let _ = self
+                    .event_channel
+                    .0
+                    .send(Event::CommandResult(CommandResult::BoxSelect(meshes)));
+            }
+            None => {}
+        }
+        if let Some(scene) = self.world.get_active_scene() {
+            self.object_picking_pipeline.render_next(
+                &self.device,
+                &self.queue,
+                &scene.iter_visible_meshes().collect::<Vec<_>>(),
+                &scene.mesh_pool,
+                &scene.mesh_buffer,
+                &self.camera,
+            );
+        }
+    }
+
+    /** Notifies the hook, call this after changing the selection. */
+    fn selection_changed(&mut self) {
+        let selected = self.meta.selection.meshes().to_vec();
+        let _ = self.event_channel.0.send(Event::SelectionChanged(selected));
+    }
+
+    /**
+    The outline of a mesh in the active scene: the scene's selection outline if it's selected, else its hover outline if
+    the cursor is on it, else the mesh's own.
+     */
+    fn outline(&self, scene: &Scene, mesh: &Mesh) -> Option<Outline> {
+        if self.meta.selection.contains(mesh.id) {
+            Some(scene.selection_outline)
+        } else if let Some(outline) = scene.hover_outline.filter(|_| self.hover.mesh_id() == Some(mesh.id)) {
+            Some(outline)
+        } else {
+            mesh.outline()
+        }
+    }
+
     pub fn window(&self) -> &Window {
         &self.window
     }
 
+    /**
+    Reconfigures the surface with `format` and recreates everything that renders into it in the surface's format.
+    As after recovering the device, the hook is notified with `Event::DeviceRecovered` to register its GUI textures
+    again.
+     */
+    fn set_surface_format(&mut self, format: TextureFormat) {
+        self.surface_config.format = format;
+        self.meta.surface_format = format;
+        self.surface.configure(&self.device, &self.surface_config);
+
+        let depth_mode = self.camera.depth_mode();
+        self.pbr_pipeline.create_pipeline(&self.device, format, depth_mode);
+        self.outlining_pipeline.create_pipelines(&self.device, format);
+        self.jump_flood_pipeline.create_pipelines(&self.device, format);
+        self.grid_pipeline.create_pipeline(&self.device, format, depth_mode);
+        self.line_pipeline.create_pipelines(&self.device, format, depth_mode);
+        self.axes_gizmo_pipeline.create_pipeline(&self.device, format);
+        self.light_gizmo_pipeline.create_pipeline(&self.device, format);
+        self.sky_pipeline.create_pipeline(&self.device, format);
+        self.imposter_pipeline.create_pipeline(&self.device, format, depth_mode);
+        self.water_pipeline.create_pipeline(&self.device, format, depth_mode);
+        self.stereo_pipeline.create_pipeline(&self.device, format);
+        self.split_screen_pipeline.create_pipeline(&self.device, format);
+        // recreates the chain's targets and custom passes in the new format
+        self.post_process_chain.resize(&self.device, &self.surface_config);
+        self.motion_blur_pipeline
+            .create_pipelines(&self.device, &self.post_process_chain, depth_mode);
+        self.post_effects_pipeline
+            .create_pipeline(&self.device, &self.post_process_chain);
+        self.grading_pipeline
+            .create_pipeline(&self.device, &self.post_process_chain);
+        // egui's renderer can't change its format
+        self.recreate_egui();
+        let _ = self.event_channel.0.send(Event::DeviceRecovered);
+    }
+
+    /**
+    Replaces the GUI's renderer with a new one for the current device and surface format. The new renderer lacks the
+    textures that were registered with the old one, so the material preview is registered again and the texture
+    thumbnails are recreated when they are shown next.
+     */
+    fn recreate_egui(&mut self) {
+        self.egui = gui::EguiRenderer::new(&self.device, self.surface_config.format, None, 1, &self.window);
+        self.meta.material_preview.texture = self.egui.resources().register_texture(
+            &self.device,
+            self.material_preview_pipeline.output(),
+            wgpu::FilterMode::Linear,
+        );
+        self.meta.texture_thumbnails.clear();
+    }
+
+    /**
+    Switches the camera's projection to `depth_mode` and recreates the pipelines that test depth for it.
+     */
+    fn set_depth_mode(&mut self, depth_mode: DepthMode) {
+        self.camera.set_depth_mode(depth_mode);
+        let format = self.surface_config.format;
+        self.pbr_pipeline.create_pipeline(&self.device, format, depth_mode);
+        self.depth_prepass_pipeline.create_pipeline(&self.device, depth_mode);
+        self.object_picking_pipeline.create_pipeline(&self.device, depth_mode);
+        self.grid_pipeline.create_pipeline(&self.device, format, depth_mode);
+        self.line_pipeline.create_pipelines(&self.device, format, depth_mode);
+        self.imposter_pipeline.create_pipeline(&self.device, format, depth_mode);
+        self.water_pipeline.create_pipeline(&self.device, format, depth_mode);
+        self.motion_blur_pipeline
+            .create_pipelines(&self.device, &self.post_process_chain, depth_mode);
+    }
+
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size = new_size;
         self.surface_config.width = new_size.width.max(1);
@@ -211,12 +946,26 @@ impl RenderState {
         self.pbr_pipeline.resize(&self.device, &self.surface_config);
         self.object_picking_pipeline.resize(&self.device, &self.surface_config);
         self.outlining_pipeline.resize(&self.device, &self.surface_config);
+        self.jump_flood_pipeline.resize(&self.device, &self.surface_config);
         self.grid_pipeline.resize(&self.device, &self.surface_config);
+        self.water_pipeline.resize(&self.device, &self.surface_config);
+        self.stereo_pipeline.resize(&self.device, &self.surface_config);
+        self.split_screen_pipeline.resize(&self.device, &self.surface_config);
+        self.post_process_chain.resize(&self.device, &self.surface_config);
+        self.motion_blur_pipeline.resize(&self.device, &self.surface_config);
 
         self.camera.update_aspect(new_size.width as f32, new_size.height as f32);
         self.window.request_redraw();
     }
 
+    /**
+    Whether frames are rendered and presented, which they aren't in simulation-only mode or while the window is
+    minimized and there is nothing to present to.
+     */
+    fn rendering(&self) -> bool {
+        !self.meta.simulation_only && self.size.width > 0 && self.size.height > 0
+    }
+
     fn input(&mut self, event: &WindowEvent) -> bool {
         if !self.show_gui {
             false
@@ -226,77 +975,514 @@ impl RenderState {
     }
 
     fn update(&mut self, keys: &KeyState, delta_time: f32, cursor_delta: Vec2) {
-        self.hook.update(keys, delta_time, &mut self.world);
+        self.cpu_profiler.begin_frame();
+        self.cpu_profiler.begin("Update");
+        // the camera moves in real time, the scene in scaled time
+        let scene_delta_time = self.meta.time.advance(delta_time);
+        self.hook.update(keys, scene_delta_time, &mut self.world);
+        self.debug_draw.clear();
+        self.hook.debug_draw(&self.world, &mut self.debug_draw);
         self.camera.recv_input(keys, cursor_delta, delta_time);
-        self.camera.update_view(&self.queue);
-        self.world.update_active_scene(&self.queue); // updates lights and mesh info buffers
+        if let Some(scene) = self.world.get_active_scene() {
+            self.camera.update_walk(scene, delta_time);
+        }
+        self.camera.set_calibration(self.meta.calibration);
+        self.camera.update_motion();
+        if self.meta.stereo.enabled {
+            self.stereo_pipeline.update(&self.queue, &self.meta.stereo, &self.camera);
+        }
+        if self.meta.split_screen.enabled {
+            if self.meta.split_screen.players.len() < SplitScreen::MIN_PLAYERS {
+                self.meta.split_screen.set_player_count(SplitScreen::MIN_PLAYERS, &self.camera);
+            }
+            self.split_screen_pipeline
+                .update(&self.device, &self.queue, &self.meta.split_screen, &self.camera);
+        }
+        self.cpu_profiler.end();
+        self.cpu_profiler.begin("Streaming");
+        streaming::update(self);
+        self.cpu_profiler.end();
+        self.cpu_profiler.begin("Live link");
+        live_link::update(self);
+        self.cpu_profiler.end();
+        self.cpu_profiler.begin("Hot reload");
+        hot_reload::update(self);
+        self.cpu_profiler.end();
+        #[cfg(feature = "scripting")]
+        {
+            self.cpu_profiler.begin("Scripts");
+            scripting::update(self, scene_delta_time);
+            self.cpu_profiler.end();
+        }
+        #[cfg(feature = "physics")]
+        {
+            self.cpu_profiler.begin("Physics");
+            physics::update(self, scene_delta_time);
+            self.cpu_profiler.end();
+        }
+        #[cfg(feature = "audio")]
+        {
+            self.cpu_profiler.begin("Audio");
+            audio::update(self);
+            self.cpu_profiler.end();
+        }
+        self.cpu_profiler.begin("Scene buffers");
+        if let Some(scene) = self.world.scenes.get_mut(&self.world.active_scene) {
+            let inputs = BindingInputs {
+                time: self.meta.time.elapsed(),
+                delta_time: scene_delta_time,
+                camera_position: self.camera.world_eye(),
+            };
+            scene.evaluate_bindings(&mut self.world.assets.materials, &inputs);
+            scene.fade_lights(|light| self.meta.light_lod.fade(light, &self.camera));
+            scene.assign_shadow_maps(ShadowMaps::MAX_LIGHTS);
+            scene.update_static_batches(
+                &self.device,
+                &self.queue,
+                &self.world.assets.materials,
+                &self.pbr_pipeline.mesh_bind_group_layout,
+            );
+        }
+        // the many small updates of a frame go through the staging belt, and only after everything else in this frame
+        // that writes to the camera with the queue, as the staged copies run after those writes
+        let mut writer = self.staging.writer(&self.device);
+        self.camera.update_view(&mut writer);
+        self.world
+            .assets
+            .materials
+            .update_dirty(&mut writer, &self.world.assets.textures);
+        self.world.update_active_scene(&mut writer); // updates lights and mesh info buffers
+        if let Some(scene) = self.world.scenes.get_mut(&self.world.active_scene) {
+            scene.update_motion(&mut writer, &self.world.assets.materials);
+        }
+        // before moving or reallocating elements, which the staged copies would otherwise miss
+        self.staging.submit(&self.queue);
+        if let Some(scene) = self.world.scenes.get_mut(&self.world.active_scene) {
+            scene
+                .mesh_buffer
+                .defragment(&self.device, &self.queue, MESH_DEFRAG_MOVES_PER_FRAME);
+            // frees the memory of deleted models once enough of them were removed
+            scene
+                .mesh_buffer
+                .shrink(&self.device, &self.queue, &self.pbr_pipeline.mesh_bind_group_layout);
+            scene
+                .light_buffer
+                .shrink(&self.device, &self.queue, &self.pbr_pipeline.light_bind_group_layout);
+            self.imposter_pipeline.update(
+                &self.queue,
+                scene.iter_visible_meshes(),
+                &self.camera,
+                &self.meta.imposter_lod,
+            );
+        }
+        self.cpu_profiler.end();
+        self.cpu_profiler.begin("Commands");
+        if let Some((x, y)) = self.hover.update(delta_time) {
+            // without a scene, the request would wait for one
+            if self.world.get_active_scene().is_some() {
+                self.object_picking_pipeline.request(PickRequest::Hover, x, y);
+            }
+        }
         while let Ok(command) = self.command_channel.1.try_recv() {
             command.process(self, self.event_channel.0.clone());
         }
+        self.update_picking();
+        // commands and the hook may have removed selected meshes
+        let pruned = match self.world.get_active_scene() {
+            Some(scene) => self.meta.selection.prune(scene),
+            None => false,
+        };
+        if pruned {
+            self.selection_changed();
+        }
+        // after processing commands, which may have added textures
+        if self.world.assets.textures.dirty() {
+            self.pbr_pipeline
+                .update_textures(&self.device, &self.world.assets.textures);
+            self.world.assets.textures.set_clean();
+        }
+        self.cpu_profiler.end();
+        self.cpu_profiler.begin("GPU memory");
+        gpu_memory::update(self);
+        self.cpu_profiler.end();
     }
 
     fn render(&mut self) -> Result<(), SurfaceError> {
+        self.frame_arena.reset();
+        if let Some(timings) = self.gpu_timer.poll(&self.device) {
+            for timing in self.meta.pass_budgets.check(&timings) {
+                // the hook may have dropped its receiver, in which case nobody is interested in the event
+                let _ = self.event_channel.0.send(Event::PerformanceWarning {
+                    pass: timing.label,
+                    milliseconds: timing.milliseconds,
+                    budget: self.meta.pass_budgets.budgets[timing.label],
+                });
+            }
+            self.meta.gpu_timings = timings;
+        }
+        // waits for the next surface texture, e.g. for vsync
+        self.cpu_profiler.begin("Acquire");
         let output = self.surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.cpu_profiler.end();
+        self.cpu_profiler.begin("Encode");
+        let surface_view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let context = PostProcessContext {
+            device: &self.device,
+            queue: &self.queue,
+            camera: &self.camera,
+            meta: &self.meta,
+            scene: self.world.get_active_scene(),
+        };
+        // with post-processing, the scene is rendered offscreen first and then passed through the enabled passes
+        let mut post_processes = BumpVec::new_in(&self.frame_arena);
+        if context.scene.is_some() {
+            let built_in: [&dyn PostProcess; 3] =
+                [&self.motion_blur_pipeline, &self.post_effects_pipeline, &self.grading_pipeline];
+            post_processes.extend(
+                built_in
+                    .into_iter()
+                    .chain(self.post_process_chain.custom_passes())
+                    .filter(|pass| pass.is_enabled(&context)),
+            );
+        }
+        let split_screen = self.meta.split_screen.enabled && context.scene.is_some();
+        let stereo = self.meta.stereo.enabled && context.scene.is_some() && !split_screen;
+        let motion_blur = context.scene.is_some() && self.motion_blur_pipeline.is_enabled(&context);
+        let view = if post_processes.is_empty() {
+            &surface_view
+        } else {
+            self.post_process_chain.scene_target()
+        };
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
+        self.gpu_timer.begin_frame();
 
+        let mut draw_stats = DrawStats::default();
         {
             if let Some(scene) = self.world.get_active_scene() {
+                // the shadow maps don't depend on the view, all of the views below share them
                 if let Some(meshes) = self.world.pbr_meshes() {
-                    let meshes = meshes.collect::<Vec<_>>();
-                    self.pbr_pipeline.render_meshes(
+                    let meshes = BumpVec::from_iter_in(meshes, &self.frame_arena);
+                    self.gpu_timer.begin(&mut encoder, "Shadows");
+                    self.shadow_pipeline.render(
+                        &self.device,
+                        &self.queue,
                         &mut encoder,
-                        &view,
+                        &self.pbr_pipeline.shadow_maps,
+                        scene,
                         &meshes,
-                        &self.world.materials,
-                        &self.world.materials.buffer,
-                        &scene.mesh_buffer,
-                        &scene.light_buffer,
-                        &self.camera,
                     );
+                    self.gpu_timer.end(&mut encoder);
+                }
+                if split_screen || stereo {
+                    if let Some(meshes) = self.world.pbr_meshes() {
+                        let mut meshes = BumpVec::from_iter_in(meshes, &self.frame_arena);
+                        meshes.sort_by_key(|mesh| mesh.material);
+                        let clear_color = match scene.background {
+                            Background::Color([r, g, b]) => wgpu::Color {
+                                r: r as f64,
+                                g: g as f64,
+                                b: b as f64,
+                                a: 1.0,
+                            },
+                            Background::Sky(_) => wgpu::Color::BLACK,
+                        };
+                        self.gpu_timer
+                            .begin(&mut encoder, if split_screen { "Split screen" } else { "Stereo" });
+                        let inputs = DrawInputs::scene(scene, &meshes, &self.world.assets.materials, &self.camera);
+                        if split_screen {
+                            self.split_screen_pipeline.render(
+                                &self.device,
+                                &mut encoder,
+                                view,
+                                clear_color,
+                                &self.pbr_pipeline,
+                                &inputs,
+                            );
+                        } else {
+                            self.stereo_pipeline.render(
+                                &self.device,
+                                &mut encoder,
+                                view,
+                                clear_color,
+                                &self.pbr_pipeline,
+                                &inputs,
+                            );
+                        }
+                        self.gpu_timer.end(&mut encoder);
+                    }
+                } else {
+                    let clear_color = match scene.background {
+                        Background::Color([r, g, b]) => Some(wgpu::Color {
+                            r: r as f64,
+                            g: g as f64,
+                            b: b as f64,
+                            a: 1.0,
+                        }),
+                        Background::Sky(ref sky) => {
+                            self.sky_pipeline.update(&self.queue, sky);
+                            self.gpu_timer.begin(&mut encoder, "Sky");
+                            self.sky_pipeline.render(&mut encoder, view, &self.camera);
+                            self.gpu_timer.end(&mut encoder);
+                            None
+                        }
+                    };
+                    if let Some(meshes) = self.world.pbr_meshes() {
+                        let mut meshes = BumpVec::from_iter_in(
+                            meshes.filter(|mesh| !self.imposter_pipeline.is_hidden(mesh)),
+                            &self.frame_arena,
+                        );
+                        // meshes of the same material share a bind group, drawing them in a row saves switches
+                        meshes.sort_by_key(|mesh| mesh.material);
+                        if self.meta.depth_prepass {
+                            self.gpu_timer.begin(&mut encoder, "Depth pre-pass");
+                            self.depth_prepass_pipeline.render(
+                                &self.device,
+                                &mut encoder,
+                                &self.pbr_pipeline.depth_texture.view,
+                                &meshes,
+                                scene,
+                                &self.camera,
+                            );
+                            self.gpu_timer.end(&mut encoder);
+                        }
+                        self.gpu_timer.begin(&mut encoder, "PBR");
+                        let inputs = DrawInputs::scene(scene, &meshes, &self.world.assets.materials, &self.camera);
+                        let pass = ViewPass {
+                            clear_color,
+                            layer: self.meta.debug_layer,
+                            depth_prepass: self.meta.depth_prepass,
+                        };
+                        draw_stats = self.pbr_pipeline.render_meshes(&self.device, &mut encoder, view, pass, &inputs);
+                        self.imposter_pipeline.render(
+                            &mut encoder,
+                            view,
+                            &self.pbr_pipeline.depth_texture.view,
+                            &self.camera,
+                        );
+                        self.gpu_timer.end(&mut encoder);
+
+                        if let Some(ref water) = scene.water {
+                            self.water_pipeline
+                                .update(&self.queue, water, &self.camera, self.meta.time.elapsed());
+                            self.gpu_timer.begin(&mut encoder, "Water");
+                            self.water_pipeline.render(
+                                &self.device,
+                                &mut encoder,
+                                view,
+                                clear_color.unwrap_or(wgpu::Color::BLACK),
+                                &self.pbr_pipeline,
+                                &inputs,
+                            );
+                            self.gpu_timer.end(&mut encoder);
+                        }
+
+                        if motion_blur {
+                            self.gpu_timer.begin(&mut encoder, "Velocity");
+                            self.motion_blur_pipeline.render_velocity(
+                                &self.device,
+                                &mut encoder,
+                                &meshes,
+                                &scene.mesh_pool,
+                                &scene.mesh_buffer,
+                                &self.camera,
+                            );
+                            self.gpu_timer.end(&mut encoder);
+                        }
 
-                    let outlined_meshes = meshes.iter().filter(|m| m.is_outline()).copied().collect::<Vec<_>>();
-                    if !outlined_meshes.is_empty() {
-                        self.outlining_pipeline.render_outline(
+                        // static meshes are only drawn as part of their batch, which isn't outlined
+                        let outlined_meshes = BumpVec::from_iter_in(
+                            scene
+                                .iter_visible_meshes()
+                                .filter(|m| !self.imposter_pipeline.is_hidden(m))
+                                .filter_map(|m| Some((m, self.outline(scene, m)?))),
+                            &self.frame_arena,
+                        );
+                        if !outlined_meshes.is_empty() {
+                            self.gpu_timer.begin(&mut encoder, "Outline");
+                            match self.meta.outline_mode {
+                                OutlineMode::Stencil => self.outlining_pipeline.render_outline(
+                                    &self.device,
+                                    &mut encoder,
+                                    view,
+                                    &outlined_meshes[..],
+                                    scene,
+                                    &self.camera,
+                                ),
+                                OutlineMode::JumpFlood => self.jump_flood_pipeline.render_outline(
+                                    &self.device,
+                                    &mut encoder,
+                                    view,
+                                    &outlined_meshes[..],
+                                    scene,
+                                    &self.camera,
+                                ),
+                            }
+                            self.gpu_timer.end(&mut encoder);
+                        }
+                    }
+
+                    self.meta.light_gizmos.ranges(scene, &mut self.debug_draw);
+                    if !self.meta.physics_debug.is_empty() || !self.debug_draw.is_empty() {
+                        let mut overlay =
+                            BumpVec::from_iter_in(self.debug_draw.overlay().iter().copied(), &self.frame_arena);
+                        self.meta.physics_debug.lines(scene, &mut overlay);
+                        self.gpu_timer.begin(&mut encoder, "Debug lines");
+                        self.line_pipeline.update(
+                            &self.device,
+                            &self.queue,
+                            self.debug_draw.depth_tested(),
+                            &overlay,
+                        );
+                        self.line_pipeline.render(
                             &mut encoder,
-                            &view,
-                            &outlined_meshes[..],
-                            &scene.mesh_buffer,
+                            view,
+                            &self.pbr_pipeline.depth_texture.view,
                             &self.camera,
-                            scene.outline_width,
-                            scene.outline_color,
                         );
+                        self.gpu_timer.end(&mut encoder);
                     }
                 }
             }
-            if self.meta.show_grid {
-                self.grid_pipeline.render(&mut encoder, &view, &self.camera);
+            if self.meta.show_grid && !stereo && !split_screen {
+                self.grid_pipeline.update(&self.queue, &self.meta.grid);
+                self.gpu_timer.begin(&mut encoder, "Grid");
+                self.grid_pipeline.render(&mut encoder, view, &self.camera);
+                self.gpu_timer.end(&mut encoder);
+            }
+            if !post_processes.is_empty() {
+                self.gpu_timer.begin(&mut encoder, "Post processing");
+                self.post_process_chain
+                    .run(&mut encoder, &context, &post_processes, &surface_view);
+                self.gpu_timer.end(&mut encoder);
+            }
+            // gizmos are drawn after post-processing, so that they keep their colors and are drawn over everything
+            if self.meta.light_gizmos.icons && !stereo && !split_screen {
+                if let Some(scene) = self.world.get_active_scene() {
+                    let instances = self.meta.light_gizmos.instances(scene);
+                    self.gpu_timer.begin(&mut encoder, "Light gizmos");
+                    self.light_gizmo_pipeline.update(
+                        &self.device,
+                        &self.queue,
+                        &instances,
+                        (self.surface_config.width, self.surface_config.height),
+                        self.meta.light_gizmos.size,
+                    );
+                    self.light_gizmo_pipeline
+                        .render(&mut encoder, &surface_view, &self.camera);
+                    self.gpu_timer.end(&mut encoder);
+                }
+            }
+            if self.meta.axes_gizmo.is_visible() && !stereo && !split_screen {
+                let selected = match self.world.get_active_scene() {
+                    Some(scene) if self.meta.axes_gizmo.selection => self
+                        .meta
+                        .selection
+                        .models(scene)
+                        .into_iter()
+                        .filter_map(|id| scene.get_model(id))
+                        .map(|model| model.global_transform().w_axis.truncate())
+                        .collect(),
+                    _ => vec![],
+                };
+                let size = (self.surface_config.width, self.surface_config.height);
+                let vertices = self.meta.axes_gizmo.vertices(size, &self.camera, &selected);
+                self.gpu_timer.begin(&mut encoder, "Axes gizmo");
+                self.axes_gizmo_pipeline
+                    .render(&self.device, &self.queue, &mut encoder, &surface_view, &vertices);
+                self.gpu_timer.end(&mut encoder);
             }
         }
+        self.meta.draw_stats = draw_stats;
         let screen_descriptor = ScreenDescriptor {
             size_in_pixels: [self.surface_config.width, self.surface_config.height],
             pixels_per_point: self.window.scale_factor() as f32,
         };
 
+        self.cpu_profiler.end();
+
+        let mut callback_commands = vec![];
         if self.show_gui {
-            self.egui.draw(
+            self.cpu_profiler.begin("UI");
+            let mut resources = self.egui.resources();
+            self.meta
+                .texture_thumbnails
+                .update(&mut resources, &self.device, &self.world.assets.textures);
+            self.hook
+                .prepare_ui(&self.device, &self.queue, &mut encoder, &self.world, &mut resources);
+            // the material that the UI of the last frame requested, the preview shows it in this frame's UI
+            let preview = self.meta.material_preview.take_request();
+            self.meta.material_preview.shown =
+                preview.filter(|material| self.world.assets.materials.contains(*material));
+            if let Some(material) = self.meta.material_preview.shown {
+                self.gpu_timer.begin(&mut encoder, "Material preview");
+                self.material_preview_pipeline.update(
+                    &self.device,
+                    &self.queue,
+                    material,
+                    &self.pbr_pipeline,
+                    &self.world.assets.materials,
+                    &self.camera,
+                );
+                self.material_preview_pipeline.render(
+                    &self.device,
+                    &mut encoder,
+                    &self.pbr_pipeline,
+                    &self.world.assets.materials,
+                );
+                self.gpu_timer.end(&mut encoder);
+            }
+            self.gpu_timer.begin(&mut encoder, "Egui");
+            callback_commands = self.egui.draw(
                 &self.device,
                 &self.queue,
                 &mut encoder,
                 &self.window,
-                &view,
+                &surface_view,
                 screen_descriptor,
                 |ui| {
+                    self.meta.guides.paint(ui);
+                    self.dragger.paint(ui);
+                    if split_screen {
+                        let screen = ui.screen_rect();
+                        for (player, [x, y, w, h]) in self.meta.split_screen.partitions().iter().enumerate() {
+                            let rect = egui::Rect::from_min_size(
+                                screen.min + egui::vec2(x * screen.width(), y * screen.height()),
+                                egui::vec2(w * screen.width(), h * screen.height()),
+                            );
+                            egui::Area::new(egui::Id::new(("player_hud", player)))
+                                .fixed_pos(rect.min)
+                                .order(egui::Order::Background)
+                                .show(ui, |ui| {
+                                    ui.set_clip_rect(rect);
+                                    ui.set_max_size(rect.size());
+                                    self.hook.player_hud(ui, player);
+                                });
+                        }
+                    }
                     self.hook
                         .update_ui(ui, &mut self.world, &mut self.camera, self.command_channel.0.clone(), &mut self.meta);
+                    self.meta.stats_overlay.show(ui, &self.meta);
+                    if let Some(report) = &self.crash_dialog {
+                        if !crash_report::dialog(ui, report) {
+                            self.crash_dialog = None;
+                        }
+                    }
                 },
             );
+            self.gpu_timer.end(&mut encoder);
+            self.cpu_profiler.end();
         }
+        self.gpu_timer.resolve(&mut encoder);
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+        self.cpu_profiler.begin("Submit");
+        self.queue
+            .submit(callback_commands.into_iter().chain(std::iter::once(encoder.finish())));
+        self.gpu_timer.end_frame();
         output.present();
+        self.cpu_profiler.end();
+        self.meta.cpu_timings = self.cpu_profiler.scopes().to_vec();
         Ok(())
     }
 
@@ -304,22 +1490,60 @@ impl RenderState {
         self.meta.frame_times[self.meta.index] = frame_time;
         self.meta.index = (self.meta.index + 1) % FRAME_TIME_WINDOW;
         self.meta.frame_time = self.meta.frame_times.iter().sum::<f32>() / FRAME_TIME_WINDOW as f32;
+        self.meta.frame_history.push(FrameSample {
+            frame: frame_time * 1000.0,
+            cpu: self.meta.cpu_timings.iter().map(|timing| timing.milliseconds).sum(),
+            gpu: self.meta.gpu_timings.iter().map(|timing| timing.milliseconds).sum(),
+        });
+        if let Some(crash_reporter) = &self.crash_reporter {
+            let scene = self.world.get_active_scene();
+            crash_reporter.update_stats(FrameStats {
+                frame_time,
+                models: scene.map_or(0, |scene| scene.iter_models_deep().count()),
+                meshes: scene.map_or(0, |scene| {
+                    scene.iter_models_deep().map(|model| model.meshes.len()).sum()
+                }),
+                lights: scene.map_or(0, |scene| scene.light_buffer.len() as usize),
+                materials: self.world.assets.materials.iter().count(),
+                textures: self.world.assets.textures.iter().count(),
+                gpu_timings: self.meta.gpu_timings.clone(),
+            });
+        }
     }
 }
 
-pub async fn run(hook: impl Hook + 'static) {
+/**
+Opens the window and runs the engine until it is closed. Natively, block on it e.g. with `pollster::block_on`; on the
+web (wasm32), spawn it on the browser's event loop with `wasm_bindgen_futures::spawn_local` instead.
+
+Only returns if the window or the graphics device couldn't be created; afterwards, the event loop takes over the thread.
+ */
+pub async fn run(hook: impl Hook + 'static, config: RunConfig) -> Result<()> {
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let window = config.build(&event_loop).context("Couldn't create the window")?;
 
-    let mut state = RenderState::new(window, hook).await;
+    let crash_reporter = config.crash_reports.clone().map(CrashReporter::install);
+    let adapter_selection = AdapterSelection::from_env().unwrap_or_else(|| config.adapter.clone());
+    let mut state = RenderState::new(window, hook, adapter_selection, crash_reporter).await?;
+    state.meta.window_mode = config.window_mode;
+    state.meta.simulation_only = config.simulation_only;
+    if let Some(path) = &config.settings {
+        Settings::load(path).apply(&mut state.meta);
+    }
+    if config.crash_dialog {
+        state.crash_dialog = config.crash_reports.as_deref().and_then(crash_report::take_pending);
+    }
     let mut keys = KeyState::default();
     let mut cursor_delta = Vec2::default();
-    let mut cursor_position = (0, 0);
+    let mut mouse = input::Mouse::default();
     let mut delta_time = 0.0;
     let sender = state.command_channel.0.clone();
     let mut time = Instant::now();
     state.setup();
     event_loop.run(move |event, _, control_flow| {
+        // in the browser, frames are driven by request_redraw, which waits for the next animation frame
+        #[cfg(target_arch = "wasm32")]
+        control_flow.set_wait();
         match event {
             winit::event::Event::WindowEvent { ref event, window_id } if window_id == state.window().id() => {
                 if !state.input(event) {
@@ -337,13 +1561,19 @@ pub async fn run(hook: impl Hook + 'static) {
                         WindowEvent::KeyboardInput {
                             input:
                             KeyboardInput {
-                                state,
+                                state: key_state,
                                 virtual_keycode: Some(keycode),
                                 ..
                             },
                             ..
                         } => {
-                            keys.update_keys(*keycode, *state);
+                            keys.update_keys(*keycode, *key_state);
+                            if *key_state == ElementState::Pressed {
+                                let _ = state.event_channel.0.send(Event::KeyPressed {
+                                    key: *keycode,
+                                    modifiers: keys.modifiers(),
+                                });
+                            }
                         }
                         WindowEvent::ModifiersChanged(state) => keys.set_modifiers(state),
                         WindowEvent::Resized(physical_size) => {
@@ -357,28 +1587,60 @@ pub async fn run(hook: impl Hook + 'static) {
                             button,
                             ..
                         } => {
-                            if !keys.update_mouse(element_state, button) && element_state == &ElementState::Pressed {
-                                let button = match button {
-                                    winit::event::MouseButton::Left => MouseButton::Left,
-                                    winit::event::MouseButton::Right => MouseButton::Right,
-                                    winit::event::MouseButton::Middle => MouseButton::Middle,
-                                    _ => return,
-                                };
-                                let (x, y): (u32, u32) = cursor_position;
-                                state
-                                    .event_channel
-                                    .0
-                                    .clone()
-                                    .send(Event::Click {
-                                        x,
-                                        y,
-                                        mouse_button: button,
-                                    })
-                                    .unwrap();
+                            if keys.update_mouse(element_state, button) {
+                                return;
+                            }
+                            let Some(button) = input::mouse_button(*button) else {
+                                return;
+                            };
+                            let cursor = mouse.cursor();
+                            if element_state == &ElementState::Pressed {
+                                let size = (state.surface_config.width, state.surface_config.height);
+                                if button == MouseButton::Left {
+                                    // clicks on the gizmo don't reach the application, they would select what's
+                                    // behind it
+                                    if let Some(view) = state.meta.axes_gizmo.hit(cursor, size, &state.camera) {
+                                        let _ = sender.send(commands::Command::SetCameraView(view));
+                                        return;
+                                    }
+                                }
+                                let _ = state.event_channel.0.send(mouse.press(button, keys.modifiers()));
+                                if button == MouseButton::Left && (state.meta.drag.enabled || state.meta.drag.marquee) {
+                                    state.dragger.press(cursor);
+                                    state
+                                        .object_picking_pipeline
+                                        .request(PickRequest::Drag, cursor.0, cursor.1);
+                                }
+                                return;
+                            }
+                            if button == MouseButton::Left {
+                                if let Some((min, max)) = state.dragger.release() {
+                                    let _ = state.event_channel.0.send(Event::Marquee {
+                                        min,
+                                        max,
+                                        modifiers: keys.modifiers(),
+                                    });
+                                }
+                            }
+                            for event in mouse.release(button, keys.modifiers()) {
+                                let _ = state.event_channel.0.send(event);
                             }
                         }
+                        WindowEvent::MouseWheel { delta, .. } => {
+                            let _ = state.event_channel.0.send(input::scroll(*delta, keys.modifiers()));
+                        }
                         WindowEvent::CursorMoved { position, .. } => {
-                            cursor_position = (*position).into();
+                            let cursor_position = (*position).into();
+                            if let Some(event) = mouse.cursor_moved(cursor_position, keys.modifiers()) {
+                                let _ = state.event_channel.0.send(event);
+                            }
+                            state.hover.cursor_moved(cursor_position);
+                            let size = (state.surface_config.width, state.surface_config.height);
+                            let (drag, snap, camera) = (&state.meta.drag, &state.meta.snap, &state.camera);
+                            if let Some(offset) = state.dragger.cursor_moved(cursor_position, size, drag, snap, camera)
+                            {
+                                let _ = sender.send(commands::Command::MoveSelection(offset));
+                            }
                         }
 
                         _ => {}
@@ -386,20 +1648,53 @@ pub async fn run(hook: impl Hook + 'static) {
                 }
             }
             winit::event::Event::MainEventsCleared => {
+                if recovery::device_lost(&state) {
+                    if let Err(e) = recovery::recover(&mut state) {
+                        error!("Couldn't recover from the loss of the graphics device: {:#}", e);
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                }
+                if !state.rendering() {
+                    // without redraws, the loop has to keep polling on its own and measure the frame time here
+                    control_flow.set_poll();
+                    state.update(&keys, delta_time, cursor_delta);
+                    cursor_delta = Vec2::default();
+                    state.update_frame_time(time.elapsed().as_secs_f32());
+                    delta_time = time.elapsed().as_secs_f32();
+                    return;
+                }
                 state.window().request_redraw();
                 state.update(&keys, delta_time, cursor_delta);
                 cursor_delta = Vec2::default();
             }
-            winit::event::Event::RedrawRequested(window_id) if window_id == state.window().id() => {
+            winit::event::Event::RedrawRequested(window_id)
+                if window_id == state.window().id() && state.rendering() =>
+            {
                 match state.render() {
-                    Ok(_) => {}
-                    Err(SurfaceError::Lost) => state.resize(state.size),
+                    Ok(_) => state.surface_losses = 0,
+                    Err(SurfaceError::Lost) => {
+                        state.surface_losses += 1;
+                        if state.surface_losses >= recovery::MAX_SURFACE_LOSSES {
+                            warn!("The surface keeps getting lost, recreating the graphics device");
+                            state.device_lost.store(true, Ordering::Relaxed);
+                        } else {
+                            state.resize(state.size);
+                        }
+                    }
                     Err(SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
                     Err(e) => eprintln!("{:?}", e),
                 }
                 state.update_frame_time(time.elapsed().as_secs_f32());
                 delta_time = time.elapsed().as_secs_f32();
             }
+            winit::event::Event::LoopDestroyed => {
+                if let Some(path) = &config.settings {
+                    if let Err(e) = Settings::from_meta(&state.meta).save(path) {
+                        error!("{:#}", e);
+                    }
+                }
+            }
             winit::event::Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
                 ..
@@ -0,0 +1,61 @@
+use lib::scene::light::PointLight;
+
+use crate::camera::Camera;
+
+/**
+Level of detail for lights: lights beyond `max_distance` from the camera, or whose range covers less than
+`min_screen_size` of the view, are faded out and don't contribute to the lighting anymore.
+
+Faded out lights also give up their shadow map, see `Scene::assign_shadow_maps`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightLod {
+    pub enabled: bool,
+    /// in world units
+    pub max_distance: f32,
+    /// distance over which lights fade out before reaching `max_distance`, in world units
+    pub fade_distance: f32,
+    /// fraction of the view's height, only applies to lights with a range
+    pub min_screen_size: f32,
+}
+
+impl Default for LightLod {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_distance: 2000.0,
+            fade_distance: 500.0,
+            min_screen_size: 0.02,
+        }
+    }
+}
+
+impl LightLod {
+    /**
+    How much of `light`'s intensity remains when seen from `camera`, between 0 and 1.
+     */
+    pub fn fade(&self, light: &PointLight, camera: &Camera) -> f32 {
+        if !self.enabled {
+            return 1.0;
+        }
+        let distance = camera.world_eye().distance(light.global_transform.w_axis.truncate());
+        let distance_fade = 1.0 - smoothstep(self.max_distance - self.fade_distance, self.max_distance, distance);
+        let size_fade = match light.range {
+            Some(range) if self.min_screen_size > 0.0 => {
+                // angle covered by the light's range, relative to the vertical field of view
+                let size = 2.0 * range.atan2(distance) / camera.fovy.to_radians();
+                smoothstep(0.5 * self.min_screen_size, self.min_screen_size, size)
+            }
+            _ => 1.0,
+        };
+        distance_fade * size_fade
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
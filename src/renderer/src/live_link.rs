@@ -0,0 +1,322 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use hashbrown::HashMap;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use lib::managers::MatId;
+use lib::scene::material::PbrMaterial;
+use lib::scene::mesh::Mesh;
+use lib::scene::model::Model;
+use lib::{Dirtyable, Material};
+
+use crate::{streaming, RenderState};
+
+// how often the server threads check whether the live link was stopped
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// longest message a client may send, clients that exceed it are disconnected so that they can't exhaust the memory
+const MAX_LINE_LENGTH: u64 = 64 * 1024 * 1024;
+
+/**
+A message of the live-link protocol, which lets a plug-in of a DCC tool like Blender push edits into the running
+engine. Clients connect via TCP and send one JSON object per line, tagged by its `type`, e.g.
+`{"type": "transform", "object": "Cube", "matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 2, 0, 1]}`.
+
+Objects are identified by their name in the DCC. Positions, normals and matrices use glTF's conventions (+Y up,
+column-major matrices), as the DCC's glTF exporter would write them.
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveLinkMessage {
+    /// Creates the object or replaces its geometry. `uvs` may be empty, indices must fit into 16 bits
+    Mesh {
+        object: String,
+        positions: Vec<[f32; 3]>,
+        normals: Vec<[f32; 3]>,
+        #[serde(default)]
+        uvs: Vec<[f32; 2]>,
+        indices: Vec<u32>,
+    },
+    /// Sets the object's transform relative to the world
+    Transform { object: String, matrix: [f32; 16] },
+    /// Sets the factors of the object's material, which is created for the object by the first such message
+    Material {
+        object: String,
+        albedo: [f32; 4],
+        metallic: f32,
+        roughness: f32,
+        #[serde(default)]
+        emissive: [f32; 3],
+    },
+    /// Removes the object from the active scene
+    Remove { object: String },
+}
+
+struct LinkedObject {
+    // the top-level model that shows the object in the active scene
+    model: u32,
+    material: Option<MatId>,
+}
+
+/**
+A running live-link server, see `LiveLinkMessage`. Messages are received on background threads and applied to the
+active scene at the start of the next frame. The server stops once the live link is dropped.
+ */
+pub(crate) struct LiveLink {
+    address: SocketAddr,
+    receiver: flume::Receiver<LiveLinkMessage>,
+    objects: HashMap<String, LinkedObject>,
+}
+
+impl LiveLink {
+    pub(crate) fn start(address: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(address).with_context(|| format!("Couldn't listen on {}", address))?;
+        listener.set_nonblocking(true)?;
+        // resolves port 0 to the port that was picked
+        let address = listener.local_addr()?;
+        let (sender, receiver) = flume::unbounded();
+        thread::spawn(move || accept(listener, sender));
+        info!("Live link listening on {}", address);
+        Ok(Self {
+            address,
+            receiver,
+            objects: HashMap::new(),
+        })
+    }
+
+    pub(crate) fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    fn apply(&mut self, state: &mut RenderState, message: LiveLinkMessage) {
+        let Some(scene) = state.world.scenes.get_mut(&state.world.active_scene) else {
+            return;
+        };
+        match message {
+            LiveLinkMessage::Mesh {
+                object,
+                positions,
+                normals,
+                uvs,
+                indices,
+            } => {
+                let vertex_count = positions.len();
+                if normals.len() != vertex_count || !(uvs.is_empty() || uvs.len() == vertex_count) {
+                    warn!("Live link mesh of {} has mismatching vertex attributes", object);
+                    return;
+                }
+                // index buffers are 16 bit
+                if vertex_count > u16::MAX as usize + 1 || indices.iter().any(|i| *i as usize >= vertex_count) {
+                    warn!("Live link mesh of {} has invalid indices", object);
+                    return;
+                }
+                let uvs = if uvs.is_empty() {
+                    vec![Vec2::ZERO; vertex_count]
+                } else {
+                    uvs.into_iter().map(Vec2::from).collect()
+                };
+                let linked = self.objects.get(&object);
+                let material = linked
+                    .and_then(|linked| linked.material)
+                    .filter(|material| state.world.assets.materials.contains(*material))
+                    .unwrap_or(state.world.assets.materials.default_material);
+                let mesh = Mesh::from(
+                    positions.into_iter().map(Vec3::from).collect(),
+                    indices,
+                    normals.into_iter().map(Vec3::from).collect(),
+                    // live-linked materials have no normal maps, so any tangent will do
+                    vec![Vec4::new(1.0, 0.0, 0.0, 1.0); vertex_count],
+                    material,
+                    uvs,
+                    Mat4::IDENTITY,
+                );
+                if let Some(linked) = linked {
+                    if scene.get_model(linked.model).is_some() {
+                        scene.replace_meshes(
+                            linked.model,
+                            vec![mesh],
+                            &state.device,
+                            &state.queue,
+                            &state.world.assets.materials,
+                            &state.pbr_pipeline.mesh_bind_group_layout,
+                        );
+                        return;
+                    }
+                }
+                // the object is new, or its model was deleted in the engine or belongs to another scene
+                let mut model = Model::from(vec![mesh], Some(object.as_str().into()), vec![], neutral(), None);
+                model.update_transforms(Mat4::IDENTITY);
+                let model_id = model.id;
+                // top-level models always have a place in the scene
+                if let Err(e) = scene.add_model(
+                    model,
+                    None,
+                    &state.device,
+                    &state.queue,
+                    &state.world.assets.materials,
+                    &state.pbr_pipeline.mesh_bind_group_layout,
+                    &state.pbr_pipeline.light_bind_group_layout,
+                ) {
+                    warn!("Couldn't add the live link model of {}: {}", object, e);
+                    return;
+                }
+                self.objects
+                    .entry(object)
+                    .or_insert(LinkedObject {
+                        model: model_id,
+                        material: None,
+                    })
+                    .model = model_id;
+            }
+            LiveLinkMessage::Transform { object, matrix } => {
+                let Some(model) = self
+                    .objects
+                    .get(&object)
+                    .and_then(|linked| scene.get_model_mut(linked.model))
+                else {
+                    warn!("Live link object {} has no mesh in the active scene", object);
+                    return;
+                };
+                model.local_transform = neutral() * Mat4::from_cols_array(&matrix);
+                model.update_transforms(Mat4::IDENTITY);
+            }
+            LiveLinkMessage::Material {
+                object,
+                albedo,
+                metallic,
+                roughness,
+                emissive,
+            } => {
+                let Some(linked) = self.objects.get_mut(&object) else {
+                    warn!("Live link object {} has no mesh in the active scene", object);
+                    return;
+                };
+                // the material is purged along with the object's meshes, see `World::purge_unused`
+                linked.material = linked
+                    .material
+                    .filter(|material| state.world.assets.materials.contains(*material));
+                let material_id = *linked.material.get_or_insert_with(|| {
+                    let mut material = PbrMaterial::from_default(None);
+                    material.name = Some(object.as_str().into());
+                    material.create_texture_bind_group(
+                        &state.device,
+                        &state.pbr_pipeline.tex_bind_group_layout,
+                        &state.world.assets.textures,
+                    );
+                    state.world.assets.materials.add_material(
+                        Material::Pbr(material),
+                        &state.device,
+                        &state.queue,
+                        &state.pbr_pipeline.mat_bind_group_layout,
+                        &state.world.assets.textures,
+                    )
+                });
+                let Material::Pbr(material) = state.world.assets.materials.get_material_mut(material_id);
+                material.albedo = Vec4::from(albedo);
+                material.metallic_roughness_factors = Vec2::new(metallic, roughness);
+                material.emissive_factors = Vec3::from(emissive);
+                material.dirty = true;
+                if let Some(model) = scene.get_model_mut(linked.model) {
+                    for mesh in model.meshes.iter_mut().filter(|mesh| mesh.material != material_id) {
+                        mesh.material = material_id;
+                        mesh.set_dirty(true);
+                    }
+                }
+            }
+            LiveLinkMessage::Remove { object } => {
+                if let Some(linked) = self.objects.remove(&object) {
+                    streaming::remove_models(state, &[linked.model]);
+                }
+            }
+        }
+    }
+}
+
+// the same flip as the neutral transform of glTF imports, so that objects are the same way up as in exported files
+fn neutral() -> Mat4 {
+    let mut neutral = Mat4::IDENTITY;
+    neutral.y_axis *= -1.0;
+    neutral
+}
+
+fn accept(listener: TcpListener, sender: flume::Sender<LiveLinkMessage>) {
+    // the receiver is dropped when the live link is stopped
+    while !sender.is_disconnected() {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                info!("Live link client {} connected", peer);
+                let sender = sender.clone();
+                thread::spawn(move || receive(stream, peer, sender));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                warn!("Live link couldn't accept a connection: {}", e);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+fn receive(stream: TcpStream, peer: SocketAddr, sender: flume::Sender<LiveLinkMessage>) {
+    // accepted streams may inherit the listener's non-blocking mode, reads time out instead to notice a stop
+    if let Err(e) = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(POLL_INTERVAL)))
+    {
+        warn!("Couldn't set up live link connection to {}: {}", peer, e);
+        return;
+    }
+    let mut reader = BufReader::new(stream);
+    let mut line = vec![];
+    while !sender.is_disconnected() {
+        // reads at most up to the limit, so that a line without an end can't grow without bounds
+        let remaining = MAX_LINE_LENGTH.saturating_sub(line.len() as u64);
+        match (&mut reader).take(remaining).read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) if line.last() != Some(&b'\n') && line.len() as u64 >= MAX_LINE_LENGTH => {
+                warn!("Live link client {} sent a message longer than {} bytes", peer, MAX_LINE_LENGTH);
+                break;
+            }
+            Ok(_) => {
+                if !line.trim_ascii().is_empty() {
+                    match serde_json::from_slice(&line) {
+                        Ok(message) => {
+                            let _ = sender.send(message);
+                        }
+                        Err(e) => warn!("Ignoring malformed live link message from {}: {}", peer, e),
+                    }
+                }
+                line.clear();
+            }
+            // a partially received line stays in `line` and is completed by the next read
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => {
+                warn!("Live link connection to {} failed: {}", peer, e);
+                break;
+            }
+        }
+    }
+    info!("Live link client {} disconnected", peer);
+}
+
+/**
+Applies the messages that the live link received since the last frame to the active scene.
+ */
+pub(crate) fn update(state: &mut RenderState) {
+    let Some(mut live_link) = state.live_link.take() else {
+        return;
+    };
+    let messages = live_link.receiver.try_iter().collect::<Vec<_>>();
+    if !messages.is_empty() {
+        state.ensure_active_scene();
+        for message in messages {
+            live_link.apply(state, message);
+        }
+    }
+    state.live_link = Some(live_link);
+}
@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/**
+A 3D color lookup table that maps a display color to a graded display color.
+ */
+#[derive(Debug, Clone)]
+pub struct Lut {
+    pub title: Option<String>,
+    /// number of entries along each axis
+    pub size: u32,
+    /// `size`³ entries, red changes fastest, then green, then blue
+    pub data: Vec<[f32; 3]>,
+    /// input colors are remapped from `domain_min..domain_max` to `0..1` before the lookup
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+}
+
+impl Lut {
+    /**
+    Loads a LUT in the Adobe/Resolve `.cube` format. Only 3D LUTs are supported.
+     */
+    pub fn load_cube(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read LUT {:?}", path))?;
+        Self::parse_cube(&text).with_context(|| format!("Failed to parse LUT {:?}", path))
+    }
+
+    pub fn parse_cube(text: &str) -> Result<Self> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut data = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let keyword = parts.next().unwrap();
+            match keyword {
+                "TITLE" => title = Some(line["TITLE".len()..].trim().trim_matches('"').to_string()),
+                "LUT_3D_SIZE" => {
+                    let n: u32 = parts
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing LUT_3D_SIZE value in line {}", number + 1))?
+                        .parse()?;
+                    if n < 2 {
+                        bail!("LUT_3D_SIZE must be at least 2, got {}", n);
+                    }
+                    size = Some(n);
+                }
+                "LUT_1D_SIZE" => bail!("1D LUTs are not supported"),
+                "DOMAIN_MIN" => domain_min = Self::parse_triple(keyword, parts, number)?,
+                "DOMAIN_MAX" => domain_max = Self::parse_triple(keyword, parts, number)?,
+                _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => {
+                    let rest = std::iter::once(keyword).chain(parts);
+                    data.push(Self::parse_triple("entry", rest, number)?);
+                }
+                // other keywords, e.g. LUT_IN_VIDEO_RANGE, don't affect the lookup
+                _ => {}
+            }
+        }
+
+        let size = size.ok_or_else(|| anyhow!("Missing LUT_3D_SIZE"))?;
+        // in 64 bits, so that huge sizes don't overflow, and checked, so that they don't on 32-bit targets either
+        let expected = (size as u64)
+            .checked_mul(size as u64)
+            .and_then(|count| count.checked_mul(size as u64))
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or_else(|| anyhow!("LUT_3D_SIZE {} is too large", size))?;
+        if data.len() != expected {
+            bail!(
+                "Expected {} entries for LUT_3D_SIZE {}, got {}",
+                expected,
+                size,
+                data.len()
+            );
+        }
+        Ok(Self {
+            title,
+            size,
+            data,
+            domain_min,
+            domain_max,
+        })
+    }
+
+    fn parse_triple<'a>(what: &str, parts: impl Iterator<Item = &'a str>, number: usize) -> Result<[f32; 3]> {
+        let values = parts.map(str::parse::<f32>).collect::<Result<Vec<_>, _>>()?;
+        match values[..] {
+            [r, g, b] => Ok([r, g, b]),
+            _ => bail!(
+                "Expected 3 values for {} in line {}, got {}",
+                what,
+                number + 1,
+                values.len()
+            ),
+        }
+    }
+
+    /** The entries as RGBA8, in the layout of a 3D texture of `size`³ texels. */
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|[r, g, b]| {
+                let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+                [to_u8(*r), to_u8(*g), to_u8(*b), 255]
+            })
+            .collect()
+    }
+}
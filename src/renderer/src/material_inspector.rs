@@ -0,0 +1,193 @@
+use egui::load::SizedTexture;
+use egui::{TextureId, Ui};
+use glam::{Vec3, Vec4};
+
+use lib::managers::{MatId, TexId, TextureManager};
+use lib::scene::material::{PbrMaterial, PbrMaterialChanges};
+use lib::scene::World;
+use lib::texture::TextureKind;
+use lib::Material;
+
+use crate::commands::{Command, Commands};
+
+/**
+The texture slots of PBR materials, in the order the inspector lists them.
+ */
+pub const TEXTURE_SLOTS: [TextureKind; 5] = [
+    TextureKind::Albedo,
+    TextureKind::Normal,
+    TextureKind::MetalRoughness,
+    TextureKind::Occlusion,
+    TextureKind::Emission,
+];
+
+/**
+Size of the preview sphere in the inspector, in points.
+ */
+pub const PREVIEW_SIZE: f32 = 128.0;
+
+/**
+The rendered sphere that `material_inspector` shows. There is a single preview, so only one material is previewed at a
+time: the renderer renders the material requested during a frame's UI before drawing the next frame's UI, and stops
+once no material is requested anymore.
+ */
+#[derive(Debug)]
+pub struct MaterialPreview {
+    requested: Option<MatId>,
+    pub(crate) shown: Option<MatId>,
+    pub(crate) texture: TextureId,
+}
+
+impl MaterialPreview {
+    pub(crate) fn new(texture: TextureId) -> Self {
+        Self {
+            requested: None,
+            shown: None,
+            texture,
+        }
+    }
+
+    /**
+    Previews the material from the next frame on, unless another material was requested during this frame already.
+     */
+    pub fn request(&mut self, material: MatId) {
+        self.requested.get_or_insert(material);
+    }
+
+    /**
+    Previews the material from the next frame on, replacing any other request.
+     */
+    pub fn switch_to(&mut self, material: MatId) {
+        self.requested = Some(material);
+    }
+
+    /**
+    The material that the preview shows in this frame.
+     */
+    pub fn shown(&self) -> Option<MatId> {
+        self.shown
+    }
+
+    /**
+    The preview as a texture for egui, see `MaterialPreviewPipeline::SIZE` for its size in pixels.
+     */
+    pub fn texture(&self) -> TextureId {
+        self.texture
+    }
+
+    pub(crate) fn take_request(&mut self) -> Option<MatId> {
+        self.requested.take()
+    }
+}
+
+/**
+Editor for a PBR material with a preview sphere, color pickers and sliders for the factors, and a texture picker per
+slot. Meant for `Hook::update_ui`: the material is only borrowed, edits are sent as `Command::UpdateMaterial` and
+`Command::SetMaterialTexture` and show up in the next frame.
+ */
+pub fn material_inspector(ui: &mut Ui, id: MatId, world: &World, commands: &Commands, preview: &mut MaterialPreview) {
+    let Material::Pbr(material) = world.assets.materials.get_material(id);
+    preview_ui(ui, id, preview);
+    factors_ui(ui, id, material, commands);
+    for (slot, texture) in TEXTURE_SLOTS.into_iter().zip([
+        material.albedo_texture,
+        material.normal_texture,
+        material.metallic_roughness_texture,
+        material.occlusion_texture,
+        material.emissive_texture,
+    ]) {
+        texture_slot_ui(ui, id, slot, texture, &world.assets.textures, commands);
+    }
+}
+
+// keeps the preview while this material has it, other inspectors offer to take it over
+fn preview_ui(ui: &mut Ui, id: MatId, preview: &mut MaterialPreview) {
+    let size = egui::Vec2::splat(PREVIEW_SIZE);
+    match preview.shown() {
+        Some(shown) if shown != id => {
+            if ui.button("Show Preview").clicked() {
+                preview.switch_to(id);
+            }
+        }
+        shown => {
+            preview.request(id);
+            if shown.is_some() {
+                ui.image(SizedTexture::new(preview.texture(), size));
+            } else {
+                // the first frame, until the preview is rendered
+                ui.allocate_space(size);
+            }
+        }
+    }
+}
+
+fn factors_ui(ui: &mut Ui, id: MatId, material: &PbrMaterial, commands: &Commands) {
+    let mut changes = PbrMaterialChanges::default();
+    let mut albedo = material.albedo.to_array();
+    ui.horizontal(|ui| {
+        ui.label("Albedo");
+        if ui.color_edit_button_rgba_unmultiplied(&mut albedo).changed() {
+            changes.albedo = Some(Vec4::from_array(albedo));
+        }
+    });
+    let mut metallic_roughness = material.metallic_roughness_factors;
+    let metallic = ui.add(egui::Slider::new(&mut metallic_roughness.x, 0.0..=1.0).text("Metallic"));
+    let roughness = ui.add(egui::Slider::new(&mut metallic_roughness.y, 0.0..=1.0).text("Roughness"));
+    if metallic.changed() || roughness.changed() {
+        changes.metallic_roughness_factors = Some(metallic_roughness);
+    }
+    let mut occlusion = material.occlusion_factor;
+    if ui
+        .add(egui::Slider::new(&mut occlusion, 0.0..=1.0).text("Ambient Occlusion"))
+        .changed()
+    {
+        changes.occlusion_factor = Some(occlusion);
+    }
+    let mut emissive = material.emissive_factors.to_array();
+    ui.horizontal(|ui| {
+        ui.label("Emissive");
+        if ui.color_edit_button_rgb(&mut emissive).changed() {
+            changes.emissive_factors = Some(Vec3::from_array(emissive));
+        }
+    });
+    let changed = changes.albedo.is_some()
+        || changes.metallic_roughness_factors.is_some()
+        || changes.occlusion_factor.is_some()
+        || changes.emissive_factors.is_some();
+    if changed {
+        let _ = commands.send(Command::UpdateMaterial { id, changes });
+    }
+}
+
+fn texture_slot_ui(
+    ui: &mut Ui,
+    material: MatId,
+    slot: TextureKind,
+    texture: Option<TexId>,
+    textures: &TextureManager,
+    commands: &Commands,
+) {
+    let texture_name = |id: Option<TexId>| match id {
+        Some(id) => textures.get_texture(&id).name.clone().unwrap_or(format!("{:?}", id)),
+        None => "Default".to_string(),
+    };
+    let mut selected = texture;
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source(("texture_slot", material, slot))
+            .selected_text(texture_name(texture))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut selected, None, texture_name(None));
+                for (id, _) in textures.iter_with_ids() {
+                    ui.selectable_value(&mut selected, Some(id), texture_name(Some(id)));
+                }
+            });
+        ui.label(format!("{:?} Texture", slot));
+    });
+    if selected != texture {
+        let _ = commands.send(Command::SetMaterialTexture {
+            material,
+            slot,
+            texture: selected,
+        });
+    }
+}
@@ -0,0 +1,588 @@
+use std::mem::{self, Discriminant};
+use std::num::NonZeroUsize;
+
+use anyhow::{Context, Result};
+use glam::{Mat4, Quat, Vec3};
+use hashbrown::{HashMap, HashSet};
+use log::warn;
+use rapier3d::na;
+use rapier3d::prelude::*;
+
+use lib::scene::joint::{Joint, JointKind};
+use lib::scene::model::Model;
+use lib::scene::physics::{BodyKind, Collider as ColliderDesc, ColliderShape, PhysicsSettings, RigidBody as BodyDesc};
+use lib::scene::Scene;
+
+use crate::RenderState;
+
+/**
+Rapier simulation of the active scene's rigid bodies, colliders and joints, see `Model::rigid_body`. It is advanced in
+fixed steps of `Scene::physics` each frame, after `Hook::update`, and moves the models of dynamic bodies.
+
+Changes of the models are picked up by comparing them with the state of the last frame: moving a model teleports its
+body, changing the body's velocities or settings or the collider applies them. Switching to another scene starts its
+simulation from scratch.
+ */
+pub(crate) struct Physics {
+    scene: Option<u32>,
+    accumulator: f32,
+    pipeline: PhysicsPipeline,
+    islands: IslandManager,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+    query_pipeline: QueryPipeline,
+    models: HashMap<u32, SimulatedModel>,
+    joints: Vec<(u32, Joint)>,
+    joint_handles: Vec<ImpulseJointHandle>,
+    shapes: ShapeCache,
+}
+
+/**
+Where a physics query hit a collider, see `Command::PhysicsRaycast`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsHit {
+    pub model_id: u32,
+    /// in world space
+    pub position: Vec3,
+    /// of the collider's surface at the hit
+    pub normal: Vec3,
+    /// from the start of the ray or shape
+    pub distance: f32,
+}
+
+// what a model looked like when it was last synchronized with the simulation
+struct SimulatedModel {
+    body: Option<(RigidBodyHandle, BodyDesc)>,
+    collider: Option<(ColliderHandle, ColliderDesc)>,
+    global_transform: Mat4,
+}
+
+impl Physics {
+    pub(crate) fn new() -> Self {
+        Self {
+            scene: None,
+            accumulator: 0.0,
+            pipeline: PhysicsPipeline::new(),
+            islands: IslandManager::new(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            impulse_joints: ImpulseJointSet::new(),
+            multibody_joints: MultibodyJointSet::new(),
+            ccd_solver: CCDSolver::new(),
+            query_pipeline: QueryPipeline::new(),
+            models: HashMap::new(),
+            joints: vec![],
+            joint_handles: vec![],
+            shapes: HashMap::new(),
+        }
+    }
+
+    // creates, updates and removes bodies and colliders to match the model and the models below it
+    fn sync_models(&mut self, models: &[Model], parent_transform: Mat4, seen: &mut HashSet<u32>, rebuilt: &mut bool) {
+        for model in models {
+            let global_transform = parent_transform * model.local_transform;
+            if model.rigid_body.is_some() || model.collider.is_some() {
+                seen.insert(model.id);
+                *rebuilt |= self.sync_model(model, global_transform);
+            }
+            self.sync_models(&model.children, global_transform, seen, rebuilt);
+        }
+    }
+
+    // returns whether the body was recreated, which removes its joints
+    fn sync_model(&mut self, model: &Model, global_transform: Mat4) -> bool {
+        let (scale, rotation, translation) = global_transform.to_scale_rotation_translation();
+        let position = isometry(rotation, translation);
+        let mut rebuilt = false;
+        let entry = self.models.entry(model.id).or_insert(SimulatedModel {
+            body: None,
+            collider: None,
+            global_transform,
+        });
+        let scaled = entry.global_transform.to_scale_rotation_translation().0 != scale;
+
+        let body_changed = match (&entry.body, &model.rigid_body) {
+            (Some((_, old)), Some(new)) => !old.same_settings(new),
+            (None, None) => false,
+            _ => true,
+        };
+        if body_changed {
+            if let Some((handle, _)) = entry.body.take() {
+                // the collider goes with it and is created again below
+                self.bodies.remove(
+                    handle,
+                    &mut self.islands,
+                    &mut self.colliders,
+                    &mut self.impulse_joints,
+                    &mut self.multibody_joints,
+                    true,
+                );
+                entry.collider = None;
+            }
+            if let Some(desc) = model.rigid_body {
+                let body = match desc.kind {
+                    BodyKind::Dynamic => RigidBodyBuilder::dynamic(),
+                    BodyKind::Kinematic => RigidBodyBuilder::kinematic_position_based(),
+                    BodyKind::Fixed => RigidBodyBuilder::fixed(),
+                }
+                .position(position)
+                .linvel(vector(desc.linear_velocity))
+                .angvel(vector(desc.angular_velocity))
+                .linear_damping(desc.linear_damping)
+                .angular_damping(desc.angular_damping)
+                .gravity_scale(desc.gravity_scale);
+                entry.body = Some((self.bodies.insert(body), desc));
+            }
+            rebuilt = true;
+        } else if let (Some((handle, old)), Some(desc)) = (&mut entry.body, model.rigid_body) {
+            let body = &mut self.bodies[*handle];
+            if entry.global_transform != global_transform {
+                match desc.kind {
+                    BodyKind::Kinematic => body.set_next_kinematic_position(position),
+                    BodyKind::Dynamic | BodyKind::Fixed => body.set_position(position, true),
+                }
+            }
+            if old.linear_velocity != desc.linear_velocity || old.angular_velocity != desc.angular_velocity {
+                body.set_linvel(vector(desc.linear_velocity), true);
+                body.set_angvel(vector(desc.angular_velocity), true);
+            }
+            *old = desc;
+        }
+
+        let collider_changed =
+            entry.collider.map(|(_, desc)| desc) != model.collider || (scaled && model.collider.is_some());
+        if collider_changed {
+            if let Some((handle, _)) = entry.collider.take() {
+                self.colliders.remove(handle, &mut self.islands, &mut self.bodies, true);
+            }
+            let shape = model
+                .collider
+                .and_then(|desc| Some((desc, collider_shape(&mut self.shapes, model, &desc, scale)?)));
+            if let Some((desc, shape)) = shape {
+                let collider = ColliderBuilder::new(shape)
+                    .user_data(model.id as u128)
+                    .density(desc.density)
+                    .friction(desc.friction)
+                    .restitution(desc.restitution);
+                let handle = match &entry.body {
+                    Some((body, _)) => self.colliders.insert_with_parent(collider, *body, &mut self.bodies),
+                    None => self.colliders.insert(collider.position(position)),
+                };
+                entry.collider = Some((handle, desc));
+            }
+        } else if let (None, Some((handle, _))) = (&entry.body, &entry.collider) {
+            // fixed obstacles follow their model
+            if entry.global_transform != global_transform {
+                self.colliders[*handle].set_position(position);
+            }
+        }
+        entry.global_transform = global_transform;
+        rebuilt
+    }
+
+    /**
+    Drops the simulation of a deleted scene if it is the simulated one, and the cached shapes of its meshes.
+     */
+    pub(crate) fn forget_scene(&mut self, scene: &Scene) {
+        if self.scene == Some(scene.id) {
+            let shapes = mem::take(&mut self.shapes);
+            *self = Physics::new();
+            self.shapes = shapes;
+        }
+        let meshes = scene
+            .iter_models_deep()
+            .flat_map(|model| model.meshes.iter())
+            .map(|mesh| mesh.id)
+            .collect::<HashSet<_>>();
+        self.shapes
+            .retain(|key, _| key.meshes.iter().all(|mesh| !meshes.contains(mesh)));
+    }
+
+    fn remove_model(&mut self, id: u32) {
+        let Some(entry) = self.models.remove(&id) else {
+            return;
+        };
+        if let Some((handle, _)) = entry.body {
+            self.bodies.remove(
+                handle,
+                &mut self.islands,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                true,
+            );
+        } else if let Some((handle, _)) = entry.collider {
+            self.colliders.remove(handle, &mut self.islands, &mut self.bodies, true);
+        }
+    }
+
+    // joints only connect models that both have a body
+    fn sync_joints(&mut self, joints: Vec<(u32, Joint)>, rebuilt: bool) {
+        if !rebuilt && joints == self.joints {
+            return;
+        }
+        for handle in self.joint_handles.drain(..) {
+            self.impulse_joints.remove(handle, true);
+        }
+        for (owner, joint) in joints.iter() {
+            let body = |id: u32| {
+                self.models
+                    .get(&id)
+                    .and_then(|model| model.body.map(|(handle, _)| handle))
+            };
+            let (Some(body1), Some(body2)) = (body(*owner), body(joint.other)) else {
+                continue;
+            };
+            // anchors are in the models' local space, which includes their scale unlike the bodies' local space
+            let scale = |id: u32| self.models[&id].global_transform.to_scale_rotation_translation().0;
+            let anchor1 = point(joint.local_anchor * scale(*owner));
+            let anchor2 = point(joint.other_anchor * scale(joint.other));
+            let data: GenericJoint = match joint.kind {
+                JointKind::Fixed => FixedJointBuilder::new()
+                    .local_anchor1(anchor1)
+                    .local_anchor2(anchor2)
+                    .into(),
+                JointKind::Revolute { axis, limits } => {
+                    let mut builder = RevoluteJointBuilder::new(unit_vector(axis))
+                        .local_anchor1(anchor1)
+                        .local_anchor2(anchor2);
+                    if let Some(limits) = limits {
+                        builder = builder.limits(limits);
+                    }
+                    builder.into()
+                }
+                JointKind::Prismatic { axis, limits } => {
+                    let mut builder = PrismaticJointBuilder::new(unit_vector(axis))
+                        .local_anchor1(anchor1)
+                        .local_anchor2(anchor2);
+                    if let Some(limits) = limits {
+                        builder = builder.limits(limits);
+                    }
+                    builder.into()
+                }
+            };
+            self.joint_handles
+                .push(self.impulse_joints.insert(body1, body2, data, true));
+        }
+        self.joints = joints;
+    }
+
+    /**
+    The closest collider along the ray from `origin` within `max_distance`, ignoring the collider of the model
+    `exclude`, e.g. the one casting the ray.
+     */
+    pub(crate) fn raycast(
+        &mut self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        exclude: Option<u32>,
+    ) -> Result<Option<PhysicsHit>> {
+        let direction = direction.try_normalize().context("The ray has no direction")?;
+        self.query_pipeline.update(&self.bodies, &self.colliders);
+        let ray = Ray::new(point(origin), vector(direction));
+        let predicate = exclude_model(exclude);
+        let filter = QueryFilter::new().predicate(&predicate);
+        let hit = self.query_pipeline.cast_ray_and_get_normal(
+            &self.bodies,
+            &self.colliders,
+            &ray,
+            max_distance,
+            true,
+            filter,
+        );
+        Ok(hit.map(|(handle, intersection)| PhysicsHit {
+            model_id: self.colliders[handle].user_data as u32,
+            position: vec3(ray.point_at(intersection.toi).coords),
+            normal: vec3(intersection.normal),
+            distance: intersection.toi,
+        }))
+    }
+
+    /**
+    The models whose colliders overlap the shape at the position, except for the model `exclude`. Mesh shapes can't be
+    used for queries.
+     */
+    pub(crate) fn overlap(
+        &mut self,
+        shape: &ColliderShape,
+        position: Vec3,
+        rotation: Quat,
+        exclude: Option<u32>,
+    ) -> Result<Vec<u32>> {
+        let shape = primitive_shape(shape, Vec3::ONE).context("Mesh shapes can't be used for queries")?;
+        self.query_pipeline.update(&self.bodies, &self.colliders);
+        let predicate = exclude_model(exclude);
+        let mut models = vec![];
+        self.query_pipeline.intersections_with_shape(
+            &self.bodies,
+            &self.colliders,
+            &isometry(rotation, position),
+            shape.as_ref(),
+            QueryFilter::new().predicate(&predicate),
+            |handle| {
+                models.push(self.colliders[handle].user_data as u32);
+                true
+            },
+        );
+        Ok(models)
+    }
+
+    /**
+    The first collider that the shape touches when moved from the position along `direction` for up to
+    `max_distance`, except for the collider of the model `exclude`. The hit's position and normal are where the
+    collider is touched. Shapes that overlap a collider at the start hit it at a distance of 0.
+     */
+    pub(crate) fn shape_cast(
+        &mut self,
+        shape: &ColliderShape,
+        position: Vec3,
+        rotation: Quat,
+        direction: Vec3,
+        max_distance: f32,
+        exclude: Option<u32>,
+    ) -> Result<Option<PhysicsHit>> {
+        let shape = primitive_shape(shape, Vec3::ONE).context("Mesh shapes can't be used for queries")?;
+        let direction = direction.try_normalize().context("The shape cast has no direction")?;
+        self.query_pipeline.update(&self.bodies, &self.colliders);
+        let predicate = exclude_model(exclude);
+        let hit = self.query_pipeline.cast_shape(
+            &self.bodies,
+            &self.colliders,
+            &isometry(rotation, position),
+            &vector(direction),
+            shape.as_ref(),
+            max_distance,
+            true,
+            QueryFilter::new().predicate(&predicate),
+        );
+        Ok(hit.map(|(handle, toi)| {
+            let collider = &self.colliders[handle];
+            PhysicsHit {
+                model_id: collider.user_data as u32,
+                position: vec3(collider.position().transform_point(&toi.witness1).coords),
+                normal: vec3(collider.position().transform_vector(&toi.normal1)),
+                distance: toi.toi,
+            }
+        }))
+    }
+
+    fn step(&mut self, settings: &PhysicsSettings, delta_time: f32) -> u32 {
+        let steps = settings.steps(&mut self.accumulator, delta_time);
+        let parameters = IntegrationParameters {
+            dt: settings.timestep,
+            num_solver_iterations: NonZeroUsize::new(settings.solver_iterations.max(1) as usize).unwrap(),
+            num_additional_friction_iterations: settings.friction_iterations as usize,
+            ..Default::default()
+        };
+        for _ in 0..steps {
+            self.pipeline.step(
+                &vector(settings.gravity),
+                &parameters,
+                &mut self.islands,
+                &mut self.broad_phase,
+                &mut self.narrow_phase,
+                &mut self.bodies,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                &mut self.ccd_solver,
+                Some(&mut self.query_pipeline),
+                &(),
+                &(),
+            );
+        }
+        steps
+    }
+
+    // moves the models of dynamic bodies to where the simulation moved the bodies
+    fn write_back(&mut self, models: &mut [Model], parent_transform: Mat4) {
+        for model in models.iter_mut() {
+            let simulated = self.models.get_mut(&model.id).and_then(|entry| match entry.body {
+                Some((handle, desc)) if desc.kind == BodyKind::Dynamic => Some((entry, handle)),
+                _ => None,
+            });
+            if let Some((entry, handle)) = simulated {
+                let body = &self.bodies[handle];
+                let position = body.position();
+                let rotation = position.rotation.coords;
+                let global_transform = Mat4::from_scale_rotation_translation(
+                    entry.global_transform.to_scale_rotation_translation().0,
+                    Quat::from_xyzw(rotation.x, rotation.y, rotation.z, rotation.w),
+                    vec3(position.translation.vector),
+                );
+                if global_transform != entry.global_transform {
+                    model.local_transform = parent_transform.inverse() * global_transform;
+                    model.update_transforms(parent_transform);
+                    entry.global_transform = global_transform;
+                }
+                if let (Some(desc), Some((_, synced))) = (&mut model.rigid_body, &mut entry.body) {
+                    desc.linear_velocity = vec3(*body.linvel());
+                    desc.angular_velocity = vec3(*body.angvel());
+                    *synced = *desc;
+                }
+            }
+            self.write_back(&mut model.children, parent_transform * model.local_transform);
+        }
+    }
+}
+
+/**
+Synchronizes the simulation with the active scene, advances it and moves the models of dynamic bodies, see `Physics`.
+ */
+pub(crate) fn update(state: &mut RenderState, delta_time: f32) {
+    let Some(scene) = state.world.scenes.get(&state.world.active_scene) else {
+        return;
+    };
+    let physics = &mut state.physics;
+    if physics.scene != Some(scene.id) {
+        // generated shapes stay cached, the scene may be switched back to
+        let shapes = mem::take(&mut physics.shapes);
+        *physics = Physics::new();
+        physics.scene = Some(scene.id);
+        physics.shapes = shapes;
+    }
+    let mut seen = HashSet::new();
+    let mut rebuilt = false;
+    physics.sync_models(&scene.models, Mat4::IDENTITY, &mut seen, &mut rebuilt);
+    let removed = physics
+        .models
+        .keys()
+        .filter(|id| !seen.contains(*id))
+        .copied()
+        .collect::<Vec<_>>();
+    if !removed.is_empty() {
+        for id in removed {
+            physics.remove_model(id);
+        }
+        rebuilt = true;
+        // shapes of deleted meshes won't be needed again
+        let meshes = state
+            .world
+            .scenes
+            .values()
+            .flat_map(|scene| scene.iter_models_deep().flat_map(|model| model.meshes.iter()))
+            .map(|mesh| mesh.id)
+            .collect::<HashSet<_>>();
+        physics
+            .shapes
+            .retain(|key, _| key.meshes.iter().all(|mesh| meshes.contains(mesh)));
+    }
+    let joints = scene
+        .iter_models_deep()
+        .flat_map(|model| model.joints.iter().map(|joint| (model.id, *joint)))
+        .collect();
+    physics.sync_joints(joints, rebuilt);
+
+    let scene = state.world.scenes.get_mut(&state.world.active_scene).unwrap();
+    if physics.step(&scene.physics, delta_time) > 0 {
+        physics.write_back(&mut scene.models, Mat4::IDENTITY);
+    }
+}
+
+// the shapes that don't need a model, i.e. all but the mesh shapes
+fn primitive_shape(shape: &ColliderShape, scale: Vec3) -> Option<SharedShape> {
+    let scale = scale.abs();
+    match *shape {
+        ColliderShape::Ball { radius } => Some(SharedShape::ball(radius * scale.max_element())),
+        ColliderShape::Cuboid { half_extents } => {
+            let half_extents = half_extents * scale;
+            Some(SharedShape::cuboid(half_extents.x, half_extents.y, half_extents.z))
+        }
+        ColliderShape::Capsule { half_height, radius } => Some(SharedShape::capsule_y(
+            half_height * scale.y,
+            radius * scale.x.max(scale.z),
+        )),
+        ColliderShape::ConvexHull | ColliderShape::TriMesh | ColliderShape::ConvexDecomposition => None,
+    }
+}
+
+// mesh shapes generated for the models' meshes at a scale, since generating them is slow for large meshes. Failed
+// generations are remembered too, so that they aren't retried every frame
+type ShapeCache = HashMap<ShapeKey, Option<SharedShape>>;
+
+#[derive(PartialEq, Eq, Hash)]
+struct ShapeKey {
+    meshes: Vec<u32>,
+    shape: Discriminant<ColliderShape>,
+    scale: [u32; 3],
+}
+
+fn collider_shape(cache: &mut ShapeCache, model: &Model, desc: &ColliderDesc, scale: Vec3) -> Option<SharedShape> {
+    if let Some(shape) = primitive_shape(&desc.shape, scale) {
+        return Some(shape);
+    }
+    let key = ShapeKey {
+        meshes: model.meshes.iter().map(|mesh| mesh.id).collect(),
+        shape: mem::discriminant(&desc.shape),
+        scale: scale.to_array().map(f32::to_bits),
+    };
+    cache
+        .entry(key)
+        .or_insert_with(|| {
+            let mut vertices = vec![];
+            let mut triangles = vec![];
+            for mesh in model.meshes.iter() {
+                let offset = vertices.len() as u32;
+                vertices.extend(mesh.vertices.iter().map(|vertex| point(*vertex * scale)));
+                triangles.extend(
+                    mesh.indices
+                        .chunks_exact(3)
+                        .map(|triangle| [triangle[0], triangle[1], triangle[2]].map(|index| index + offset)),
+                );
+            }
+            let shape = if triangles.is_empty() {
+                None
+            } else {
+                match desc.shape {
+                    ColliderShape::ConvexHull => SharedShape::convex_hull(&vertices),
+                    ColliderShape::TriMesh => Some(SharedShape::trimesh(vertices, triangles)),
+                    _ => Some(SharedShape::convex_decomposition(&vertices, &triangles)),
+                }
+            };
+            if shape.is_none() {
+                warn!(
+                    "Couldn't generate a {:?} collider for model {}, its meshes have no volume",
+                    desc.shape, model.id
+                );
+            }
+            shape
+        })
+        .clone()
+}
+
+// query filter predicate that skips the colliders of the model
+fn exclude_model(exclude: Option<u32>) -> impl Fn(ColliderHandle, &Collider) -> bool {
+    move |_, collider| exclude.is_none_or(|model_id| collider.user_data != model_id as u128)
+}
+
+fn vector(v: Vec3) -> Vector<Real> {
+    Vector::new(v.x, v.y, v.z)
+}
+
+fn vec3(v: Vector<Real>) -> Vec3 {
+    Vec3::new(v.x, v.y, v.z)
+}
+
+fn point(v: Vec3) -> Point<Real> {
+    Point::new(v.x, v.y, v.z)
+}
+
+fn unit_vector(v: Vec3) -> UnitVector<Real> {
+    UnitVector::new_normalize(vector(v.try_normalize().unwrap_or(Vec3::X)))
+}
+
+fn isometry(rotation: Quat, translation: Vec3) -> Isometry<Real> {
+    Isometry::from_parts(
+        vector(translation).into(),
+        Rotation::from_quaternion(na::Quaternion::new(rotation.w, rotation.x, rotation.y, rotation.z)),
+    )
+}
@@ -0,0 +1,99 @@
+use glam::{Mat4, Vec3};
+
+use lib::scene::joint::JointKind;
+use lib::scene::model::Model;
+use lib::scene::Scene;
+use lib::shader_types::ColoredVertex;
+
+/**
+Which parts of the physics setup are visualized on top of the scene.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsDebug {
+    /// anchors of joints, the line between them and the axes of revolute and prismatic joints
+    pub joints: bool,
+    /// size of the anchor markers and axes, in world units
+    pub marker_size: f32,
+}
+
+impl Default for PhysicsDebug {
+    fn default() -> Self {
+        Self {
+            joints: false,
+            marker_size: 5.0,
+        }
+    }
+}
+
+const OWNER_ANCHOR_COLOR: [f32; 3] = [1.0, 0.8, 0.0];
+const OTHER_ANCHOR_COLOR: [f32; 3] = [0.0, 0.8, 1.0];
+const CONNECTION_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+const AXIS_COLOR: [f32; 3] = [1.0, 0.2, 0.2];
+
+impl PhysicsDebug {
+    pub fn is_empty(&self) -> bool {
+        !self.joints
+    }
+
+    /**
+    Appends the enabled visualizations of `scene` as line list vertices.
+     */
+    pub(crate) fn lines(&self, scene: &Scene, out: &mut impl Extend<ColoredVertex>) {
+        if self.joints {
+            let mut transforms = Vec::new();
+            collect_transforms(&scene.models, Mat4::IDENTITY, &mut transforms);
+            let transform_of = |id: u32| transforms.iter().find(|(model, _)| *model == id).map(|(_, t)| *t);
+            for model in scene.iter_models_deep() {
+                let Some(owner) = transform_of(model.id) else { continue };
+                for joint in model.joints.iter() {
+                    let Some(other) = transform_of(joint.other) else {
+                        continue;
+                    };
+                    let a = owner.transform_point3(joint.local_anchor);
+                    let b = other.transform_point3(joint.other_anchor);
+                    self.cross(a, OWNER_ANCHOR_COLOR, out);
+                    self.cross(b, OTHER_ANCHOR_COLOR, out);
+                    line(a, b, CONNECTION_COLOR, out);
+                    if let JointKind::Revolute { axis, .. } | JointKind::Prismatic { axis, .. } = joint.kind {
+                        let axis = owner.transform_vector3(axis).normalize_or_zero() * self.marker_size * 2.0;
+                        line(a - axis, a + axis, AXIS_COLOR, out);
+                    }
+                }
+            }
+        }
+    }
+
+    fn cross(&self, center: Vec3, color: [f32; 3], out: &mut impl Extend<ColoredVertex>) {
+        let half = self.marker_size / 2.0;
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            line(center - axis * half, center + axis * half, color, out);
+        }
+    }
+}
+
+fn line(from: Vec3, to: Vec3, color: [f32; 3], out: &mut impl Extend<ColoredVertex>) {
+    out.extend([
+        ColoredVertex {
+            position: from.into(),
+            color,
+        },
+        ColoredVertex {
+            position: to.into(),
+            color,
+        },
+    ]);
+}
+
+// world transforms of all models, taken from what is rendered where possible
+fn collect_transforms(models: &[Model], parent: Mat4, out: &mut Vec<(u32, Mat4)>) {
+    for model in models {
+        let transform = model
+            .meshes
+            .first()
+            .map(|mesh| mesh.global_transform)
+            .or(model.light.as_ref().map(|light| light.global_transform))
+            .unwrap_or(parent * model.local_transform);
+        out.push((model.id, transform));
+        collect_transforms(&model.children, transform, out);
+    }
+}
@@ -0,0 +1,305 @@
+use std::f32::consts::TAU;
+
+use glam::{Vec2, Vec3};
+use wgpu::{
+    include_wgsl, Buffer, CommandEncoder, Device, PipelineLayout, Queue, RenderPipeline, ShaderModule, TextureView,
+};
+
+use lib::shader_types::{ColoredVertex, Vertex};
+
+use crate::camera::{AxisView, Camera};
+
+// distance of the corner widget from the viewport's edges, in pixels
+const MARGIN: f32 = 10.0;
+const LINE_WIDTH: f32 = 2.0;
+// the selection's axes are this long relative to their distance from the camera, so that their size on screen is fixed
+const SELECTION_AXIS_LENGTH: f32 = 0.1;
+
+/**
+Axes drawn on top of everything: an orientation widget in the top right corner of the viewport, which turns the camera
+to look along an axis when one of its tips is clicked, and the world axes at the models of the selected meshes. The X,
+Y and Z axes are red, green and blue, the tips of the negative axes are darker.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxesGizmo {
+    /// shows the orientation widget in the corner
+    pub corner: bool,
+    /// shows the axes at the selection
+    pub selection: bool,
+    /// size of the corner widget in pixels
+    pub size: f32,
+}
+
+impl Default for AxesGizmo {
+    fn default() -> Self {
+        Self {
+            corner: false,
+            selection: false,
+            size: 80.0,
+        }
+    }
+}
+
+impl AxesGizmo {
+    pub fn is_visible(&self) -> bool {
+        self.corner || self.selection
+    }
+
+    /**
+    The view whose tip in the corner widget is at the cursor, for a viewport of `size` pixels.
+     */
+    pub fn hit(&self, cursor: (u32, u32), size: (u32, u32), camera: &Camera) -> Option<AxisView> {
+        if !self.corner {
+            return None;
+        }
+        let cursor = Vec2::new(cursor.0 as f32, cursor.1 as f32);
+        // the closest tips are drawn last, on top of the others
+        self.tips(size, camera)
+            .into_iter()
+            .rev()
+            .find(|(_, tip, _)| tip.distance(cursor) <= self.tip_radius())
+            .map(|(view, _, _)| view)
+    }
+
+    fn tip_radius(&self) -> f32 {
+        self.size * 0.1
+    }
+
+    // the center of the corner widget and the length of its axes in pixels
+    fn corner_layout(&self, size: (u32, u32)) -> (Vec2, f32) {
+        let center = Vec2::new(size.0 as f32 - MARGIN - self.size / 2.0, MARGIN + self.size / 2.0);
+        (center, self.size / 2.0 - self.tip_radius())
+    }
+
+    // each view's tip in pixels and its distance from the viewer, the furthest first
+    fn tips(&self, size: (u32, u32), camera: &Camera) -> [(AxisView, Vec2, f32); 6] {
+        let (center, length) = self.corner_layout(size);
+        let mut tips = AxisView::ALL.map(|view| {
+            let direction = camera.view.transform_vector3(view.side());
+            (
+                view,
+                center + Vec2::new(direction.x, -direction.y) * length,
+                direction.z,
+            )
+        });
+        tips.sort_by(|a, b| b.2.total_cmp(&a.2));
+        tips
+    }
+
+    /**
+    The triangles of the gizmo in normalized device coordinates, for a viewport of `size` pixels. `selected` are the
+    positions of the selected models in world space.
+     */
+    pub(crate) fn vertices(&self, size: (u32, u32), camera: &Camera, selected: &[Vec3]) -> Vec<ColoredVertex> {
+        let mut triangles = Triangles::default();
+        if self.selection {
+            let projection = camera.build_projection();
+            let to_pixels = |position: Vec3| {
+                let clip = projection * position.extend(1.0);
+                (clip.w > 0.0).then(|| {
+                    let ndc = clip.truncate().truncate() / clip.w;
+                    Vec2::new((ndc.x + 1.0) / 2.0 * size.0 as f32, (1.0 - ndc.y) / 2.0 * size.1 as f32)
+                })
+            };
+            for position in selected {
+                let length = camera.world_eye().distance(*position) * SELECTION_AXIS_LENGTH;
+                // the positive X, Y and Z axes
+                for view in [AxisView::Right, AxisView::Bottom, AxisView::Back] {
+                    let end = *position + view.side() * length;
+                    if let (Some(from), Some(to)) = (to_pixels(*position), to_pixels(end)) {
+                        triangles.line(from, to, axis_color(view));
+                    }
+                }
+            }
+        }
+        if self.corner {
+            let (center, _) = self.corner_layout(size);
+            for (view, tip, _) in self.tips(size, camera) {
+                let color = axis_color(view);
+                if positive(view) {
+                    triangles.line(center, tip, color);
+                    triangles.disc(tip, self.tip_radius(), color);
+                } else {
+                    triangles.disc(tip, self.tip_radius() * 0.8, color.map(|channel| channel * 0.5));
+                }
+            }
+        }
+        triangles.to_ndc(size)
+    }
+}
+
+// whether the side is on the positive half of its world axis
+fn positive(view: AxisView) -> bool {
+    view.side().max_element() > 0.0
+}
+
+fn axis_color(view: AxisView) -> [f32; 3] {
+    let axis = view.side().abs();
+    if axis.x > 0.5 {
+        [0.9, 0.2, 0.2]
+    } else if axis.y > 0.5 {
+        [0.3, 0.8, 0.3]
+    } else {
+        [0.2, 0.4, 0.9]
+    }
+}
+
+// triangles in pixel coordinates
+#[derive(Default)]
+struct Triangles {
+    vertices: Vec<(Vec2, [f32; 3])>,
+}
+
+impl Triangles {
+    fn line(&mut self, from: Vec2, to: Vec2, color: [f32; 3]) {
+        let normal = (to - from).perp().normalize_or_zero() * LINE_WIDTH / 2.0;
+        for corner in [
+            from - normal,
+            to - normal,
+            to + normal,
+            from - normal,
+            to + normal,
+            from + normal,
+        ] {
+            self.vertices.push((corner, color));
+        }
+    }
+
+    fn disc(&mut self, center: Vec2, radius: f32, color: [f32; 3]) {
+        const SEGMENTS: usize = 12;
+        let point = |i: usize| center + Vec2::from_angle(i as f32 / SEGMENTS as f32 * TAU) * radius;
+        for i in 0..SEGMENTS {
+            self.vertices
+                .extend([(center, color), (point(i), color), (point(i + 1), color)]);
+        }
+    }
+
+    fn to_ndc(&self, size: (u32, u32)) -> Vec<ColoredVertex> {
+        self.vertices
+            .iter()
+            .map(|(position, color)| ColoredVertex {
+                position: [
+                    position.x / size.0 as f32 * 2.0 - 1.0,
+                    1.0 - position.y / size.1 as f32 * 2.0,
+                    0.0,
+                ],
+                color: *color,
+            })
+            .collect()
+    }
+}
+
+/**
+Draws the triangles of `AxesGizmo::vertices`.
+ */
+pub struct AxesGizmoPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pipeline_layout: PipelineLayout,
+    vertex_buffer: Buffer,
+    // number of vertices the vertex buffer can hold
+    capacity: usize,
+}
+
+impl AxesGizmoPipeline {
+    const INITIAL_CAPACITY: usize = 1024;
+
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("../shaders/axes_gizmo.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Axes Gizmo Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            vertex_buffer: Self::create_vertex_buffer(device, Self::INITIAL_CAPACITY),
+            capacity: Self::INITIAL_CAPACITY,
+        }
+    }
+
+    fn create_vertex_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Axes Gizmo Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<ColoredVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // (re-)creates the pipeline for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Axes Gizmo Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[ColoredVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+    }
+
+    /**
+    Draws `vertices` as a triangle list over `view`, in the order they are given. The vertex buffer grows as needed.
+     */
+    pub fn render(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        vertices: &[ColoredVertex],
+    ) {
+        if vertices.is_empty() {
+            return;
+        }
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Axes Gizmo Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+    }
+}
@@ -0,0 +1,182 @@
+use bytemuck::Pod;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutEntry, Buffer, CommandEncoder, Device, PipelineLayout, Queue,
+    ShaderModule, ShaderModuleDescriptor,
+};
+
+use crate::readback::ReadbackBuffer;
+
+/**
+A compute shader entry point together with its pipeline layout. This is the common base for GPU work that doesn't
+draw anything, e.g. prefiltering environment maps, simulating particles, skinning or culling.
+
+Bind group layouts for compute shaders can be assembled from the `*_entry` functions of this module.
+ */
+pub struct ComputePipeline {
+    label: String,
+    shader: ShaderModule,
+    entry_point: String,
+    pub pipeline_layout: PipelineLayout,
+    pipeline: Option<wgpu::ComputePipeline>,
+}
+
+impl ComputePipeline {
+    pub fn new(
+        device: &Device,
+        label: &str,
+        shader: ShaderModuleDescriptor,
+        entry_point: &str,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader = device.create_shader_module(shader);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{} Layout", label)),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            label: label.to_string(),
+            shader,
+            entry_point: entry_point.to_string(),
+            pipeline_layout,
+            pipeline: None,
+        }
+    }
+
+    // (re-)creates the pipeline
+    pub fn create_pipeline(&mut self, device: &Device) {
+        self.pipeline = Some(device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&self.label),
+            layout: Some(&self.pipeline_layout),
+            module: &self.shader,
+            entry_point: &self.entry_point,
+        }));
+    }
+
+    /**
+    Runs the shader with the given number of workgroups in each dimension. `bind_groups` are bound in order,
+    starting at group 0.
+     */
+    pub fn dispatch(&self, encoder: &mut CommandEncoder, bind_groups: &[&BindGroup], workgroups: [u32; 3]) {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(&self.label),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            compute_pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        let [x, y, z] = workgroups;
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+
+    /**
+    Like `dispatch`, but with the number of invocations instead of workgroups. `workgroup_size` has to match the
+    shader's `@workgroup_size`; the shader needs to skip invocations beyond the actual size of the work.
+     */
+    pub fn dispatch_invocations(
+        &self,
+        encoder: &mut CommandEncoder,
+        bind_groups: &[&BindGroup],
+        invocations: [u32; 3],
+        workgroup_size: [u32; 3],
+    ) {
+        self.dispatch(encoder, bind_groups, workgroup_count(invocations, workgroup_size));
+    }
+}
+
+/**
+The number of workgroups needed to cover all invocations.
+ */
+pub fn workgroup_count(invocations: [u32; 3], workgroup_size: [u32; 3]) -> [u32; 3] {
+    [0, 1, 2].map(|i| invocations[i].div_ceil(workgroup_size[i]))
+}
+
+pub fn storage_buffer_entry(binding: u32, read_only: bool) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub fn uniform_buffer_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/**
+A sampled float texture; compute shaders have no implicit derivatives, so sample it with `textureSampleLevel`
+or `textureLoad`.
+ */
+pub fn texture_entry(binding: u32, view_dimension: wgpu::TextureViewDimension) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    }
+}
+
+pub fn storage_texture_entry(
+    binding: u32,
+    format: wgpu::TextureFormat,
+    access: wgpu::StorageTextureAccess,
+    view_dimension: wgpu::TextureViewDimension,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access,
+            format,
+            view_dimension,
+        },
+        count: None,
+    }
+}
+
+pub fn sampler_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    }
+}
+
+/**
+Copies the contents of `buffer` (which needs `COPY_SRC` usage) back to the CPU, e.g. the results of a compute
+shader. Blocks until all previously submitted work is done, so this is meant for tools and one-off computations
+rather than per-frame use.
+ */
+pub fn read_buffer<T: Pod>(device: &Device, queue: &Queue, buffer: &Buffer) -> Vec<T> {
+    let mut readback = ReadbackBuffer::new(device, "Compute Readback Buffer", buffer.size());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Compute Readback Encoder"),
+    });
+    readback.copy_from_buffer(&mut encoder, buffer, 0, buffer.size());
+    queue.submit(Some(encoder.finish()));
+    readback.map();
+    readback
+        .read(device, bytemuck::pod_collect_to_vec)
+        .expect("Couldn't read back compute buffer")
+}
@@ -0,0 +1,165 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    BindGroupLayoutDescriptor, CommandEncoder, DepthStencilState, Device, PipelineLayout,
+    RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule, TextureView,
+};
+
+use lib::scene::mesh::Mesh;
+use lib::scene::Scene;
+use lib::shader_types::{PbrVertex, Vertex};
+use lib::texture::Texture;
+
+use crate::camera::{Camera, DepthMode};
+use crate::pipelines::draw_constants::DrawConstants;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct PushConstants {
+    mesh_index: u32,
+    padding: [u32; 3],
+}
+
+/**
+Renders only the depth of meshes, before the PBR pass shades them. The PBR pass then only shades the closest fragment
+of each pixel, instead of every fragment that passes the depth test at the time it's drawn. Whether that's worth the
+additional pass depends on how much overdraw the scene has, see `Meta::depth_prepass`.
+ */
+pub struct DepthPrepassPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pipeline_layout: PipelineLayout,
+    draw_constants: DrawConstants<PushConstants>,
+}
+
+impl DepthPrepassPipeline {
+    pub fn new(device: &Device, camera: &Camera) -> Self {
+        let draw_constants = DrawConstants::new(
+            device,
+            "Depth Pre-pass Draw Constants Bindgroup Layout",
+            wgpu::ShaderStages::VERTEX,
+            2,
+        );
+        let shader = draw_constants.create_shader(
+            device,
+            "Depth Pre-pass Shader",
+            include_str!("../shaders/depth_prepass.wgsl"),
+        );
+
+        let mesh_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Depth Pre-pass Mesh Bindgroup Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                // the same visibility as in the PBR pipeline, so that the mesh buffer's bind group fits both
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Depth Pre-pass Pipeline Layout"),
+            bind_group_layouts: &draw_constants
+                .bind_group_layouts(&[&mesh_bind_group_layout, &camera.bind_group_layout]),
+            push_constant_ranges: &draw_constants.push_constant_ranges(),
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            draw_constants,
+        }
+    }
+
+    // (re-)creates the pipeline
+    pub(crate) fn create_pipeline(&mut self, device: &Device, depth_mode: DepthMode) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth Pre-pass Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[PbrVertex::desc()],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                polygon_mode: wgpu::PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: depth_mode.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        }));
+    }
+
+    /**
+    Clears `depth` and renders the depth of the meshes into it. The meshes have to be drawn by the PBR pass with the
+    same camera afterwards, with `depth_prepass` set.
+     */
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        depth: &TextureView,
+        meshes: &[&Mesh],
+        scene: &Scene,
+        camera: &Camera,
+    ) {
+        let constants = meshes
+            .iter()
+            .map(|mesh| PushConstants {
+                mesh_index: *scene.mesh_buffer.get(&mesh.id).expect("Mesh not found in mesh_info_map") as u32,
+                padding: [0; 3],
+            })
+            .collect::<Vec<_>>();
+        let uploaded = self.draw_constants.upload(device, &constants);
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Depth Pre-pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(camera.depth_mode().far_depth()),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+
+        render_pass.set_bind_group(0, scene.mesh_buffer.bind_group(), &[]);
+        render_pass.set_bind_group(1, &camera.bind_group, &[]);
+        scene.mesh_pool.bind(&mut render_pass);
+
+        for (draw, mesh) in meshes.iter().enumerate() {
+            self.draw_constants
+                .set(&mut render_pass, uploaded.as_ref(), draw, &constants[draw]);
+            let inputs = mesh.vertex_inputs.as_ref().unwrap();
+            render_pass.draw_indexed(inputs.indices(), inputs.base_vertex as i32, 0..1);
+        }
+    }
+}
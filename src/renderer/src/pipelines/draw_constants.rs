@@ -0,0 +1,195 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::num::NonZeroU64;
+
+use bytemuck::Pod;
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, Device, Features,
+    PushConstantRange, RenderPass, ShaderModule, ShaderStages,
+};
+
+/**
+Per-draw data of a pipeline, like the index of the drawn mesh in the mesh buffer. It is passed as push constants if the
+device supports them. Otherwise the data of all draws of a pass is uploaded into a small uniform buffer, which is bound
+at a different dynamic offset for each draw. The buffer either gets a bind group of its own, appended to the pipeline's
+other groups, or shares one of the pipeline's groups for pipelines that already use as many groups as devices offer.
+
+Shaders declare the data as `var<push_constant> push: PushConstants;`, which `create_shader` rewrites into a uniform
+binding when push constants are unsupported.
+ */
+pub(crate) struct DrawConstants<T> {
+    stages: ShaderStages,
+    // the bind group index of the uniform buffer, following the pipeline's other bind groups unless it is shared
+    group: u32,
+    // the binding of the uniform buffer in its group, after the bindings of the pipeline's group if it is shared
+    binding: u32,
+    // whether the uniform buffer shares the pipeline's group `group` instead of having a group of its own
+    shared: bool,
+    // `None` if push constants are supported
+    bind_group_layout: Option<BindGroupLayout>,
+    // distance between the data of two draws in the uniform buffer, respecting the device's offset alignment
+    stride: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> DrawConstants<T> {
+    pub(crate) fn new(device: &Device, label: &str, stages: ShaderStages, group: u32) -> Self {
+        Self::create(device, label, stages, group, &[], false)
+    }
+
+    /**
+    Like `new`, but the uniform buffer is bound next to the `entries` of the pipeline's group `group`, so that the
+    pipeline needs no additional group. Use `upload_shared` to create the shared bind group.
+     */
+    pub(crate) fn sharing_group(
+        device: &Device,
+        label: &str,
+        stages: ShaderStages,
+        group: u32,
+        entries: &[BindGroupLayoutEntry],
+    ) -> Self {
+        Self::create(device, label, stages, group, entries, true)
+    }
+
+    fn create(
+        device: &Device,
+        label: &str,
+        stages: ShaderStages,
+        group: u32,
+        entries: &[BindGroupLayoutEntry],
+        shared: bool,
+    ) -> Self {
+        let binding = entries.iter().map(|entry| entry.binding + 1).max().unwrap_or(0);
+        let bind_group_layout = (!device.features().contains(Features::PUSH_CONSTANTS)).then(|| {
+            let uniform = BindGroupLayoutEntry {
+                binding,
+                visibility: stages,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: None,
+                },
+                count: None,
+            };
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &entries.iter().cloned().chain([uniform]).collect::<Vec<_>>(),
+            })
+        });
+        let stride = wgpu::util::align_to(
+            std::mem::size_of::<T>() as u64,
+            device.limits().min_uniform_buffer_offset_alignment as u64,
+        );
+        Self {
+            stages,
+            group,
+            binding,
+            shared,
+            bind_group_layout,
+            stride,
+            _marker: PhantomData,
+        }
+    }
+
+    /**
+    The pipeline's bind group layouts, with the one of the uniform buffer appended if push constants are unsupported,
+    or replacing the layout of the shared group.
+     */
+    pub(crate) fn bind_group_layouts<'a>(&'a self, layouts: &[&'a BindGroupLayout]) -> Vec<&'a BindGroupLayout> {
+        match &self.bind_group_layout {
+            Some(layout) if self.shared => {
+                let mut layouts = layouts.to_vec();
+                layouts[self.group as usize] = layout;
+                layouts
+            }
+            layout => layouts.iter().copied().chain(layout.as_ref()).collect(),
+        }
+    }
+
+    pub(crate) fn push_constant_ranges(&self) -> Vec<PushConstantRange> {
+        match self.bind_group_layout {
+            Some(_) => vec![],
+            None => vec![PushConstantRange {
+                stages: self.stages,
+                range: 0..std::mem::size_of::<T>() as u32,
+            }],
+        }
+    }
+
+    pub(crate) fn create_shader(&self, device: &Device, label: &str, source: &str) -> ShaderModule {
+        let source = match self.bind_group_layout {
+            Some(_) => Cow::Owned(source.replace(
+                "var<push_constant>",
+                &format!("@group({}) @binding({}) var<uniform>", self.group, self.binding),
+            )),
+            None => Cow::Borrowed(source),
+        };
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source),
+        })
+    }
+
+    /**
+    Uploads the data of all draws of a pass, if push constants are unsupported. The returned bind group has to be
+    passed to `set` for each draw.
+     */
+    pub(crate) fn upload(&self, device: &Device, constants: &[T]) -> Option<BindGroup> {
+        self.upload_shared(device, constants, &[])
+    }
+
+    /**
+    Like `upload`, but the returned bind group also binds the `entries` of the shared group, see `sharing_group`. It
+    takes the place of the pipeline's bind group of that group.
+     */
+    pub(crate) fn upload_shared(
+        &self,
+        device: &Device,
+        constants: &[T],
+        entries: &[BindGroupEntry],
+    ) -> Option<BindGroup> {
+        let layout = self.bind_group_layout.as_ref()?;
+        let mut contents = vec![0; self.stride as usize * constants.len().max(1)];
+        for (chunk, constants) in contents.chunks_exact_mut(self.stride as usize).zip(constants) {
+            chunk[..std::mem::size_of::<T>()].copy_from_slice(bytemuck::bytes_of(constants));
+        }
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Draw Constants Buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let uniform = BindGroupEntry {
+            binding: self.binding,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &buffer,
+                offset: 0,
+                size: NonZeroU64::new(self.stride),
+            }),
+        };
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Draw Constants Bindgroup"),
+            layout,
+            entries: &entries.iter().cloned().chain([uniform]).collect::<Vec<_>>(),
+        }))
+    }
+
+    /**
+    Sets the data of the `draw`-th draw of the pass, either as push constants or by binding the uploaded buffer at its
+    offset.
+     */
+    pub(crate) fn set<'a>(
+        &self,
+        render_pass: &mut RenderPass<'a>,
+        uploaded: Option<&'a BindGroup>,
+        draw: usize,
+        constants: &T,
+    ) {
+        match uploaded {
+            Some(bind_group) => {
+                render_pass.set_bind_group(self.group, bind_group, &[(draw as u64 * self.stride) as u32]);
+            }
+            None => render_pass.set_push_constants(self.stages, 0, bytemuck::bytes_of(constants)),
+        }
+    }
+}
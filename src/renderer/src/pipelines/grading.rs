@@ -0,0 +1,233 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    include_wgsl, BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, CommandEncoder, Device,
+    PipelineLayout, Queue, RenderPipeline, Sampler, ShaderModule, TextureView,
+};
+
+use crate::lut::Lut;
+use crate::pipelines::post_process::{fullscreen_pass, PostProcess, PostProcessChain, PostProcessContext};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct LutUniform {
+    domain_min: [f32; 3],
+    size: f32,
+    domain_max: [f32; 3],
+    padding: f32,
+}
+
+struct LoadedLut {
+    // the CPU-side copy, to upload the LUT again on a new device
+    lut: Lut,
+    // kept alive for the view in the bind group
+    _texture: wgpu::Texture,
+    view: TextureView,
+    buffer: Buffer,
+}
+
+/**
+Color grading pass, maps the tone mapped scene through a 3D LUT. Enabled while a LUT is loaded.
+ */
+pub struct GradingPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    bind_group_layout: BindGroupLayout,
+    lut_sampler: Sampler,
+    lut: Option<LoadedLut>,
+    bind_group: Option<BindGroup>,
+}
+
+impl GradingPipeline {
+    pub fn new(device: &Device, chain: &PostProcessChain) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("../shaders/grading.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Grading Bindgroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grading Pipeline Layout"),
+            bind_group_layouts: &[chain.input_bind_group_layout(), &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let lut_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("LUT Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            bind_group_layout,
+            lut_sampler,
+            lut: None,
+            bind_group: None,
+        }
+    }
+
+    fn update_bind_group(&mut self, device: &Device) {
+        self.bind_group = self.lut.as_ref().map(|lut| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Grading Bindgroup"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&lut.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.lut_sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: lut.buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+    }
+
+    // (re-)creates the pipeline
+    pub(crate) fn create_pipeline(&mut self, device: &Device, chain: &PostProcessChain) {
+        self.pipeline = Some(chain.create_pipeline(
+            device,
+            "Grading Pipeline",
+            &self.pipeline_layout,
+            &self.shader,
+            "fs_main",
+        ));
+    }
+
+    /**
+    Uploads `lut` as a 3D texture and enables grading with it, replacing the current LUT.
+     */
+    pub fn set_lut(&mut self, device: &Device, queue: &Queue, lut: &Lut) {
+        let size = wgpu::Extent3d {
+            width: lut.size,
+            height: lut.size,
+            depth_or_array_layers: lut.size,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("LUT Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &lut.to_rgba8(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * lut.size),
+                rows_per_image: Some(lut.size),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("LUT Buffer"),
+            contents: bytemuck::cast_slice(&[LutUniform {
+                domain_min: lut.domain_min,
+                size: lut.size as f32,
+                domain_max: lut.domain_max,
+                padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        self.lut = Some(LoadedLut {
+            lut: lut.clone(),
+            _texture: texture,
+            view,
+            buffer,
+        });
+        self.update_bind_group(device);
+    }
+
+    pub fn clear_lut(&mut self) {
+        self.lut = None;
+        self.bind_group = None;
+    }
+
+    pub fn has_lut(&self) -> bool {
+        self.lut.is_some()
+    }
+
+    /**
+    The LUT that the output is graded with, see `set_lut`.
+     */
+    pub fn lut(&self) -> Option<&Lut> {
+        self.lut.as_ref().map(|loaded| &loaded.lut)
+    }
+}
+
+impl PostProcess for GradingPipeline {
+    fn is_enabled(&self, _context: &PostProcessContext) -> bool {
+        self.has_lut()
+    }
+
+    fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        _context: &PostProcessContext,
+        input: &BindGroup,
+        output: &TextureView,
+    ) {
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        fullscreen_pass(
+            encoder,
+            "Grading Render Pass",
+            output,
+            self.pipeline.as_ref().unwrap(),
+            &[input, bind_group],
+        );
+    }
+}
@@ -1,6 +1,8 @@
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
 use wgpu::{
-    BindGroup, CommandEncoder, DepthStencilState,
-    Device, include_wgsl, PipelineLayout, RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule,
+    BindGroup, Buffer, CommandEncoder, DepthStencilState,
+    Device, include_wgsl, PipelineLayout, Queue, RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule,
     SurfaceConfiguration, TextureView,
 };
 use wgpu::util::DeviceExt;
@@ -9,7 +11,73 @@ use lib::shader_types::{BasicVertex, Vertex};
 use lib::SizedBuffer;
 use lib::texture::Texture;
 
-use crate::camera::Camera;
+use crate::camera::{Camera, DepthMode};
+
+/**
+How the grid looks, see `Meta::grid`. Colors are RGBA, where alpha is the opacity of the lines.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Grid {
+    /// distance between two minor lines in world units
+    pub spacing: f32,
+    /// every this many minor lines is a major line
+    pub major_every: u32,
+    pub minor_color: [f32; 4],
+    pub major_color: [f32; 4],
+    /// color of the x axis, i.e. the line where z = 0
+    pub x_axis_color: [f32; 4],
+    /// color of the z axis, i.e. the line where x = 0
+    pub z_axis_color: [f32; 4],
+    /// distance from the camera at which the grid starts to fade out
+    pub fade_start: f32,
+    /// distance from the camera at which the grid is faded out completely
+    pub fade_end: f32,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            spacing: 0.05,
+            major_every: 10,
+            minor_color: [0.2, 0.2, 0.2, 1.0],
+            major_color: [0.4, 0.4, 0.4, 1.0],
+            x_axis_color: [1.0, 0.2, 0.2, 1.0],
+            z_axis_color: [0.2, 0.2, 1.0, 1.0],
+            fade_start: 20.0,
+            fade_end: 60.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct GridUniform {
+    minor_color: [f32; 4],
+    major_color: [f32; 4],
+    x_axis_color: [f32; 4],
+    z_axis_color: [f32; 4],
+    spacing: f32,
+    major_every: f32,
+    fade_start: f32,
+    fade_end: f32,
+}
+
+impl From<&Grid> for GridUniform {
+    fn from(grid: &Grid) -> Self {
+        Self {
+            minor_color: grid.minor_color,
+            major_color: grid.major_color,
+            x_axis_color: grid.x_axis_color,
+            z_axis_color: grid.z_axis_color,
+            spacing: grid.spacing.max(f32::EPSILON),
+            major_every: grid.major_every.max(1) as f32,
+            fade_start: grid.fade_start,
+            // smoothstep is undefined for an empty range
+            fade_end: grid.fade_end.max(grid.fade_start + f32::EPSILON),
+        }
+    }
+}
 
 pub struct GridPipeline {
     shader: ShaderModule,
@@ -17,6 +85,8 @@ pub struct GridPipeline {
     pub pipeline_layout: PipelineLayout,
     pub depth_texture: Texture,
     vertices: SizedBuffer,
+    grid_buffer: Buffer,
+    grid_bind_group: BindGroup,
 }
 
 impl GridPipeline {
@@ -24,9 +94,36 @@ impl GridPipeline {
         let shader = device.create_shader_module(include_wgsl!("../shaders/grid.wgsl"));
         let depth_texture = Texture::create_depth_texture(device, config.width, config.height, "depth_texture");
 
+        let grid_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Grid Bindgroup Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let grid_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Grid Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[GridUniform::from(&Grid::default())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let grid_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Grid Bindgroup"),
+            layout: &grid_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: grid_buffer.as_entire_binding(),
+            }],
+        });
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Grid Pipeline Layout"),
-            bind_group_layouts: &[&camera.bind_group_layout],
+            bind_group_layouts: &[&camera.bind_group_layout, &grid_bind_group_layout],
             push_constant_ranges: &[],
         });
 
@@ -55,14 +152,20 @@ impl GridPipeline {
             pipeline_layout,
             depth_texture,
             vertices,
+            grid_buffer,
+            grid_bind_group,
         }
     }
+
+    pub(crate) fn update(&self, queue: &Queue, grid: &Grid) {
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::cast_slice(&[GridUniform::from(grid)]));
+    }
     pub(crate) fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
         self.depth_texture = Texture::create_depth_texture(device, config.width, config.height, "depth_texture");
     }
 
-    // (re-)creates the pipeline
-    pub(crate) fn create_pipeline(&mut self, device: &Device) {
+    // (re-)creates the pipeline for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat, depth_mode: DepthMode) {
         self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Grid Pipeline"),
             layout: Some(&self.pipeline_layout),
@@ -75,7 +178,7 @@ impl GridPipeline {
                 module: &self.shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    format,
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -92,7 +195,7 @@ impl GridPipeline {
             depth_stencil: Some(DepthStencilState {
                 format: Texture::DEPTH_FORMAT,
                 depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare: depth_mode.compare(),
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
@@ -109,7 +212,7 @@ impl GridPipeline {
         &self,
         view: &TextureView,
         encoder: &mut CommandEncoder,
-        camera_bind_group: &BindGroup,
+        camera: &Camera,
     ) {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Grid Render Pass"),
@@ -124,7 +227,7 @@ impl GridPipeline {
             depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
                 view: &self.depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: wgpu::LoadOp::Clear(camera.depth_mode().far_depth()),
                     store: wgpu::StoreOp::Discard,
                 }),
                 stencil_ops: None,
@@ -134,7 +237,8 @@ impl GridPipeline {
         });
         render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
 
-        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.grid_bind_group, &[]);
 
         render_pass.set_vertex_buffer(0, self.vertices.buffer.slice(..));
         // render_pass.set_index_buffer(index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint16);
@@ -149,6 +253,6 @@ impl GridPipeline {
         view: &TextureView,
         camera: &Camera,
     ) {
-        self.render_pass(view, encoder, &camera.bind_group);
+        self.render_pass(view, encoder, camera);
     }
 }
@@ -0,0 +1,511 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4};
+use hashbrown::{HashMap, HashSet};
+use log::warn;
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, Color, CommandEncoder, Device, PipelineLayout,
+    Queue, RenderPipeline, ShaderModule, TextureView,
+};
+
+use lib::scene::mesh::Mesh;
+use lib::shader_types::CameraUniform;
+use lib::texture::Texture;
+
+use crate::camera::{Camera, DepthMode};
+use crate::pipelines::pbr::{ClippedPass, DrawInputs, PBRPipeline, RenderLayerTarget};
+
+/**
+Settings for replacing models with their imposters, see `Command::BakeImposter`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImposterLod {
+    pub enabled: bool,
+    /// models farther from the camera than this are drawn as their imposter, in world units
+    pub distance: f32,
+}
+
+impl Default for ImposterLod {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            distance: 3000.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable, Default)]
+struct ImposterUniform {
+    corners: [[f32; 4]; 4],
+    tile_offset: [f32; 2],
+    tile_size: f32,
+    padding: f32,
+}
+
+/**
+A model baked into views from all around it, which are arranged in an atlas by octahedral mapping of their
+directions. Drawn as a quad showing the view closest to the direction of the camera.
+ */
+pub struct Imposter {
+    // meshes of the model and its children, which the imposter replaces
+    mesh_ids: Vec<u32>,
+    // the imposter follows this mesh, whose transform (including scale) was `anchor_transform` when baking
+    anchor: u32,
+    anchor_transform: Mat4,
+    // bounding sphere in world space when baking
+    center: Vec3,
+    radius: f32,
+    // whether the imposter is drawn instead of the meshes this frame
+    active: bool,
+    _atlas: wgpu::Texture,
+    buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+/**
+Pipeline that bakes imposters for distant models and draws them in place of the models' meshes.
+ */
+pub struct ImposterPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    bind_group_layout: BindGroupLayout,
+    // imposters by the id of the model they were baked from
+    imposters: HashMap<u32, Imposter>,
+    // meshes of active imposters, which are skipped in the PBR pass
+    hidden_meshes: HashSet<u32>,
+}
+
+impl ImposterPipeline {
+    /// number of views along each side of the atlas
+    pub const VIEWS: u32 = 8;
+    /// size of each view in pixels
+    pub const VIEW_SIZE: u32 = 128;
+
+    pub fn new(device: &Device, camera: &Camera) -> Self {
+        let shader = Camera::create_shader(device, "Imposter Shader", include_str!("../shaders/imposter.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Imposter Bindgroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Imposter Pipeline Layout"),
+            bind_group_layouts: &[&camera.bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            bind_group_layout,
+            imposters: HashMap::new(),
+            hidden_meshes: HashSet::new(),
+        }
+    }
+
+    // (re-)creates the pipeline for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat, depth_mode: DepthMode) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Imposter Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: depth_mode.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+    }
+
+    /**
+    Renders the meshes of the inputs, usually those of the model `model_id` and its children, from all directions into
+    a new imposter of the model, replacing its existing one. The views are lit by the inputs' lights and drawn with the
+    depth mode and calibration of `camera`. Submits its own commands.
+     */
+    pub fn bake(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        model_id: u32,
+        pbr_pipeline: &PBRPipeline,
+        inputs: &DrawInputs,
+        camera: &Camera,
+    ) {
+        let Some((center, radius)) = bounding_sphere(inputs.meshes) else {
+            warn!("Model {} has no meshes to bake an imposter from", model_id);
+            return;
+        };
+
+        let atlas_size = Self::VIEWS * Self::VIEW_SIZE;
+        let atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Imposter Atlas"),
+            size: wgpu::Extent3d {
+                width: atlas_size,
+                height: atlas_size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: RenderLayerTarget::FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let target = RenderLayerTarget::new(device, Self::VIEW_SIZE, Self::VIEW_SIZE);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Imposter Bake Encoder"),
+        });
+        // the views keep the transparent background, which marks where the model doesn't cover them
+        let pass = ClippedPass {
+            clear_color: Color::TRANSPARENT,
+            clip_plane: Vec4::ZERO,
+            mirrored: false,
+        };
+        // each view needs its own camera buffer, as all of them are written before the passes are submitted
+        let mut camera_buffers = vec![];
+        for y in 0..Self::VIEWS {
+            for x in 0..Self::VIEWS {
+                let direction = view_direction(UVec2::new(x, y));
+                let (_, up) = view_basis(direction);
+                let eye = center + direction * radius * 2.0;
+                let view = Mat4::look_to_lh(eye, -direction, up);
+                let (near, far) = (radius * 0.5, radius * 3.5);
+                // the views are rendered by the PBR pipeline, whose depth test expects the camera's depth mode
+                let proj = match camera.depth_mode() {
+                    DepthMode::Standard => Mat4::orthographic_lh(-radius, radius, -radius, radius, near, far),
+                    DepthMode::ReverseZ => Mat4::orthographic_lh(-radius, radius, -radius, radius, far, near),
+                };
+                let uniform = CameraUniform {
+                    proj_view: (proj * view).to_cols_array_2d(),
+                    unproj_view: (proj * view).inverse().to_cols_array_2d(),
+                    view_position: eye.extend(1.0).into(),
+                    ..camera.uniform()
+                };
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Imposter Bake Camera Buffer"),
+                    contents: bytemuck::cast_slice(&[uniform]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Imposter Bake Camera Bindgroup"),
+                    layout: &camera.bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+                let inputs = DrawInputs {
+                    camera_bind_group: &bind_group,
+                    ..*inputs
+                };
+                pbr_pipeline.render_clipped(device, &mut encoder, &target, pass, &inputs);
+                encoder.copy_texture_to_texture(
+                    target.color.texture.as_image_copy(),
+                    wgpu::ImageCopyTexture {
+                        texture: &atlas,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d {
+                            x: x * Self::VIEW_SIZE,
+                            y: y * Self::VIEW_SIZE,
+                            z: 0,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::Extent3d {
+                        width: Self::VIEW_SIZE,
+                        height: Self::VIEW_SIZE,
+                        depth_or_array_layers: 1,
+                    },
+                );
+                camera_buffers.push(buffer);
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let atlas_view = atlas.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Imposter Buffer"),
+            contents: bytemuck::cast_slice(&[ImposterUniform::default()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Imposter Bindgroup"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        self.imposters.insert(
+            model_id,
+            Imposter {
+                mesh_ids: inputs.meshes.iter().map(|mesh| mesh.id).collect(),
+                anchor: inputs.meshes[0].id,
+                anchor_transform: inputs.meshes[0].transform(),
+                center,
+                radius,
+                active: false,
+                _atlas: atlas,
+                buffer,
+                bind_group,
+            },
+        );
+    }
+
+    /**
+    Removes the imposter of the model, if it has one.
+     */
+    pub fn remove(&mut self, model_id: u32) {
+        self.imposters.remove(&model_id);
+    }
+
+    pub fn has_imposter(&self, model_id: u32) -> bool {
+        self.imposters.contains_key(&model_id)
+    }
+
+    /**
+    The ids of the models that have an imposter.
+     */
+    pub fn model_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.imposters.keys().copied()
+    }
+
+    /**
+    Decides which imposters replace their models and uploads the views they show. Call this every frame before
+    rendering, `meshes` are the meshes of the active scene.
+     */
+    pub fn update<'a>(
+        &mut self,
+        queue: &Queue,
+        meshes: impl Iterator<Item = &'a Mesh>,
+        camera: &Camera,
+        lod: &ImposterLod,
+    ) {
+        self.hidden_meshes.clear();
+        if self.imposters.is_empty() {
+            return;
+        }
+        let anchors = self
+            .imposters
+            .values()
+            .map(|imposter| imposter.anchor)
+            .collect::<HashSet<_>>();
+        let transforms = meshes
+            .filter(|mesh| anchors.contains(&mesh.id))
+            .map(|mesh| (mesh.id, mesh.transform()))
+            .collect::<HashMap<_, _>>();
+        let eye = camera.world_eye();
+        for imposter in self.imposters.values_mut() {
+            // imposters of models that aren't in the active scene are never drawn
+            let Some(transform) = transforms.get(&imposter.anchor) else {
+                imposter.active = false;
+                continue;
+            };
+            // from the imposter's space when baking to its current world space
+            let relative = *transform * imposter.anchor_transform.inverse();
+            let center = relative.transform_point3(imposter.center);
+            imposter.active = lod.enabled && eye.distance(center) > lod.distance;
+            if !imposter.active {
+                continue;
+            }
+            self.hidden_meshes.extend(imposter.mesh_ids.iter().copied());
+
+            let direction = relative.inverse().transform_vector3(eye - center).normalize_or_zero();
+            let tile = nearest_view(direction);
+            let (right, up) = view_basis(view_direction(tile));
+            let right = relative.transform_vector3(right * imposter.radius);
+            let up = relative.transform_vector3(up * imposter.radius);
+            let corners = [
+                center - right - up,
+                center + right - up,
+                center - right + up,
+                center + right + up,
+            ];
+            let uniform = ImposterUniform {
+                corners: corners.map(|corner| corner.extend(1.0).to_array()),
+                tile_offset: (tile.as_vec2() / Self::VIEWS as f32).to_array(),
+                tile_size: 1.0 / Self::VIEWS as f32,
+                padding: 0.0,
+            };
+            queue.write_buffer(&imposter.buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+    }
+
+    /**
+    Whether the mesh is replaced by an imposter in the current frame.
+     */
+    pub fn is_hidden(&self, mesh: &Mesh) -> bool {
+        self.hidden_meshes.contains(&mesh.id)
+    }
+
+    /**
+    Draws the active imposters on top of the view, depth tested against the meshes rendered before.
+     */
+    pub fn render(&self, encoder: &mut CommandEncoder, view: &TextureView, depth: &TextureView, camera: &Camera) {
+        if self.hidden_meshes.is_empty() {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Imposter Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        for imposter in self.imposters.values().filter(|imposter| imposter.active) {
+            render_pass.set_bind_group(1, &imposter.bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
+
+// world space bounding sphere of the meshes' vertices, around the center of their bounding box
+fn bounding_sphere(meshes: &[&Mesh]) -> Option<(Vec3, f32)> {
+    let points = || {
+        meshes.iter().flat_map(|mesh| {
+            let transform = mesh.transform();
+            mesh.vertices
+                .iter()
+                .map(move |vertex| transform.transform_point3(*vertex))
+        })
+    };
+    let (min, max) = points().fold(None, |bounds: Option<(Vec3, Vec3)>, point| match bounds {
+        Some((min, max)) => Some((min.min(point), max.max(point))),
+        None => Some((point, point)),
+    })?;
+    let center = (min + max) / 2.0;
+    let radius = points().map(|point| point.distance(center)).fold(0.0, f32::max);
+    // a small margin, so that the model doesn't touch the edges of its views
+    Some((center, radius.max(f32::EPSILON) * 1.05))
+}
+
+// the direction from the model towards the camera of the view in the given atlas tile
+fn view_direction(tile: UVec2) -> Vec3 {
+    let uv = (tile.as_vec2() + 0.5) / ImposterPipeline::VIEWS as f32;
+    octahedral_decode(uv * 2.0 - 1.0)
+}
+
+// the atlas tile of the view closest to the direction
+fn nearest_view(direction: Vec3) -> UVec2 {
+    let uv = octahedral_encode(direction) * 0.5 + 0.5;
+    (uv * ImposterPipeline::VIEWS as f32)
+        .as_uvec2()
+        .min(UVec2::splat(ImposterPipeline::VIEWS - 1))
+}
+
+// right and up axes of a view looking along -direction, the same ones `Mat4::look_to_lh` derives
+fn view_basis(direction: Vec3) -> (Vec3, Vec3) {
+    // world up is -y, views from straight above or below use z instead
+    let up = if direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::NEG_Y };
+    let forward = -direction;
+    let right = up.cross(forward).normalize();
+    (right, forward.cross(right))
+}
+
+fn octahedral_encode(direction: Vec3) -> Vec2 {
+    let n = direction / (direction.x.abs() + direction.y.abs() + direction.z.abs()).max(f32::EPSILON);
+    if n.z >= 0.0 {
+        n.truncate()
+    } else {
+        let sign = Vec2::new(if n.x >= 0.0 { 1.0 } else { -1.0 }, if n.y >= 0.0 { 1.0 } else { -1.0 });
+        (Vec2::ONE - Vec2::new(n.y.abs(), n.x.abs())) * sign
+    }
+}
+
+fn octahedral_decode(f: Vec2) -> Vec3 {
+    let mut n = Vec3::new(f.x, f.y, 1.0 - f.x.abs() - f.y.abs());
+    let t = (-n.z).max(0.0);
+    n.x += if n.x >= 0.0 { -t } else { t };
+    n.y += if n.y >= 0.0 { -t } else { t };
+    n.normalize()
+}
@@ -0,0 +1,381 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, CommandEncoder, Device, PipelineLayout, RenderPass,
+    RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
+};
+
+use lib::scene::mesh::{Mesh, Outline};
+use lib::scene::Scene;
+use lib::shader_types::{PbrVertex, Vertex};
+use lib::texture::Texture;
+
+use crate::camera::Camera;
+use crate::pipelines::draw_constants::DrawConstants;
+
+// the color and width of the outline of the mesh drawn at each pixel
+const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+// the pixel coordinates of the closest mask pixel, negative if none was found
+const SEED_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct PushConstants {
+    mesh_index: u32,
+    step: i32,
+    outline: u32,
+}
+
+/**
+Outlines meshes in screen space, see `OutlineMode::JumpFlood`. The meshes are drawn into a mask, then the jump flooding
+algorithm finds the closest mask pixel for every pixel in a few fullscreen passes, and the pixels within the width of
+the closest mask pixel's outline are blended onto the view. Unlike the stencil outline, the width is the same all around
+the meshes, regardless of their shape.
+ */
+pub struct JumpFloodPipeline {
+    shader: ShaderModule,
+    mask_pipeline: Option<RenderPipeline>,
+    seed_pipeline: Option<RenderPipeline>,
+    flood_pipeline: Option<RenderPipeline>,
+    composite_pipeline: Option<RenderPipeline>,
+    mask_pipeline_layout: PipelineLayout,
+    flood_pipeline_layout: PipelineLayout,
+    seeds_bind_group_layout: BindGroupLayout,
+    mask_bind_group_layout: BindGroupLayout,
+    targets: Targets,
+    draw_constants: DrawConstants<PushConstants>,
+}
+
+// the mask and two seed textures that the flood passes alternate between, with bind groups to read them
+struct Targets {
+    mask: Texture,
+    mask_bind_group: BindGroup,
+    seeds: [Texture; 2],
+    seeds_bind_groups: [BindGroup; 2],
+}
+
+impl JumpFloodPipeline {
+    pub fn new(device: &Device, config: &SurfaceConfiguration, camera: &Camera) -> Self {
+        let draw_constants = DrawConstants::new(
+            device,
+            "Jump Flood Draw Constants Bindgroup Layout",
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            2,
+        );
+        let source = format!(
+            "{}\n{}",
+            include_str!("../shaders/fullscreen.wgsl"),
+            include_str!("../shaders/jump_flood.wgsl"),
+        );
+        let shader = draw_constants.create_shader(device, "Jump Flood Shader", &source);
+
+        let mesh_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Jump Flood Mesh Bindgroup Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let mask_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Jump Flood Mask Pipeline Layout"),
+            bind_group_layouts: &draw_constants
+                .bind_group_layouts(&[&mesh_bind_group_layout, &camera.bind_group_layout]),
+            push_constant_ranges: &draw_constants.push_constant_ranges(),
+        });
+
+        let seeds_bind_group_layout = texture_bind_group_layout(device, "Jump Flood Seeds Bindgroup Layout");
+        let mask_bind_group_layout = texture_bind_group_layout(device, "Jump Flood Mask Bindgroup Layout");
+        let flood_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Jump Flood Pipeline Layout"),
+            bind_group_layouts: &draw_constants
+                .bind_group_layouts(&[&seeds_bind_group_layout, &mask_bind_group_layout]),
+            push_constant_ranges: &draw_constants.push_constant_ranges(),
+        });
+
+        let targets = Targets::new(device, config, &seeds_bind_group_layout, &mask_bind_group_layout);
+
+        Self {
+            shader,
+            mask_pipeline: None,
+            seed_pipeline: None,
+            flood_pipeline: None,
+            composite_pipeline: None,
+            mask_pipeline_layout,
+            flood_pipeline_layout,
+            seeds_bind_group_layout,
+            mask_bind_group_layout,
+            targets,
+            draw_constants,
+        }
+    }
+
+    pub(crate) fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        self.targets = Targets::new(
+            device,
+            config,
+            &self.seeds_bind_group_layout,
+            &self.mask_bind_group_layout,
+        );
+    }
+
+    // (re-)creates the pipelines, the outline is blended onto color targets of `format`
+    pub(crate) fn create_pipelines(&mut self, device: &Device, format: wgpu::TextureFormat) {
+        self.mask_pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Jump Flood Mask Pipeline"),
+            layout: Some(&self.mask_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_mask",
+                buffers: &[PbrVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_mask",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: MASK_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+        self.seed_pipeline =
+            Some(self.fullscreen_pipeline(device, "Jump Flood Seed Pipeline", "fs_seed", SEED_FORMAT, None));
+        self.flood_pipeline =
+            Some(self.fullscreen_pipeline(device, "Jump Flood Pipeline", "fs_flood", SEED_FORMAT, None));
+        self.composite_pipeline = Some(self.fullscreen_pipeline(
+            device,
+            "Jump Flood Composite Pipeline",
+            "fs_composite",
+            format,
+            Some(wgpu::BlendState::ALPHA_BLENDING),
+        ));
+    }
+
+    fn fullscreen_pipeline(
+        &self,
+        device: &Device,
+        label: &str,
+        fragment_entry_point: &str,
+        format: wgpu::TextureFormat,
+        blend: Option<wgpu::BlendState>,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&self.flood_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: fragment_entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /**
+    Outlines each mesh on `view` with its outline, whose width is in pixels.
+     */
+    pub fn render_outline(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        meshes: &[(&Mesh, Outline)],
+        scene: &Scene,
+        camera: &Camera,
+    ) {
+        let width = meshes
+            .iter()
+            .map(|(_, outline)| outline.width)
+            .max()
+            .unwrap_or(0)
+            .max(1) as u32;
+        // the seeds travel up to twice the first step minus one pixel, which has to cover the widest outline
+        let steps = std::iter::successors(Some(width.next_power_of_two()), |step| (*step > 1).then_some(step / 2))
+            .collect::<Vec<_>>();
+        let pass_constants = |step: u32| PushConstants {
+            mesh_index: 0,
+            step: step as i32,
+            outline: 0,
+        };
+        // the mask draws come first, followed by the seed pass, the flood passes and the composite pass
+        let constants = meshes
+            .iter()
+            .map(|(mesh, outline)| PushConstants {
+                mesh_index: *scene.mesh_buffer.get(&mesh.id).expect("Mesh not found in mesh_info_map") as u32,
+                step: 0,
+                outline: outline.packed(),
+            })
+            .chain(std::iter::once(pass_constants(0)))
+            .chain(steps.iter().map(|step| pass_constants(*step)))
+            .chain(std::iter::once(pass_constants(0)))
+            .collect::<Vec<_>>();
+        let uploaded = self.draw_constants.upload(device, &constants);
+        let mut draw = 0;
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Jump Flood Mask Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.targets.mask.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(self.mask_pipeline.as_ref().unwrap());
+            render_pass.set_bind_group(0, scene.mesh_buffer.bind_group(), &[]);
+            render_pass.set_bind_group(1, &camera.bind_group, &[]);
+            scene.mesh_pool.bind(&mut render_pass);
+            for (mesh, _) in meshes {
+                self.draw_constants
+                    .set(&mut render_pass, uploaded.as_ref(), draw, &constants[draw]);
+                let inputs = mesh.vertex_inputs.as_ref().unwrap();
+                render_pass.draw_indexed(inputs.indices(), inputs.base_vertex as i32, 0..1);
+                draw += 1;
+            }
+        }
+
+        // the seed pass doesn't read any seeds, but the layout needs a texture other than its output bound
+        let mut output = 0;
+        let passes = std::iter::once(("Jump Flood Seed Pass", &self.seed_pipeline))
+            .chain(steps.iter().map(|_| ("Jump Flood Pass", &self.flood_pipeline)));
+        for (label, pipeline) in passes {
+            let mut render_pass = self.fullscreen_pass(
+                encoder,
+                label,
+                &self.targets.seeds[output].view,
+                wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                pipeline.as_ref().unwrap(),
+                &self.targets.seeds_bind_groups[1 - output],
+            );
+            self.draw_constants
+                .set(&mut render_pass, uploaded.as_ref(), draw, &constants[draw]);
+            render_pass.draw(0..3, 0..1);
+            draw += 1;
+            output = 1 - output;
+        }
+        let mut render_pass = self.fullscreen_pass(
+            encoder,
+            "Jump Flood Composite Pass",
+            view,
+            wgpu::LoadOp::Load,
+            self.composite_pipeline.as_ref().unwrap(),
+            &self.targets.seeds_bind_groups[1 - output],
+        );
+        self.draw_constants
+            .set(&mut render_pass, uploaded.as_ref(), draw, &constants[draw]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    // begins a pass drawing a fullscreen triangle into `output`, which reads the seeds of `seeds_bind_group`
+    fn fullscreen_pass<'a>(
+        &'a self,
+        encoder: &'a mut CommandEncoder,
+        label: &str,
+        output: &'a TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        pipeline: &'a RenderPipeline,
+        seeds_bind_group: &'a BindGroup,
+    ) -> RenderPass<'a> {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, seeds_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.targets.mask_bind_group, &[]);
+        render_pass
+    }
+}
+
+impl Targets {
+    fn new(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        seeds_layout: &BindGroupLayout,
+        mask_layout: &BindGroupLayout,
+    ) -> Self {
+        let mask = Texture::create_render_target(device, config.width, config.height, MASK_FORMAT, "Jump Flood Mask");
+        let seeds = [0, 1].map(|_| {
+            Texture::create_render_target(device, config.width, config.height, SEED_FORMAT, "Jump Flood Seeds")
+        });
+        Self {
+            mask_bind_group: texture_bind_group(device, mask_layout, &mask),
+            seeds_bind_groups: [0, 1].map(|i| texture_bind_group(device, seeds_layout, &seeds[i])),
+            mask,
+            seeds,
+        }
+    }
+}
+
+// the textures are read with textureLoad at binding 1, see jump_flood.wgsl
+fn texture_bind_group_layout(device: &Device, label: &str) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        }],
+    })
+}
+
+fn texture_bind_group(device: &Device, layout: &BindGroupLayout, texture: &Texture) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Jump Flood Texture Bindgroup"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 1,
+            resource: wgpu::BindingResource::TextureView(&texture.view),
+        }],
+    })
+}
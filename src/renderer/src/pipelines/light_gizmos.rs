@@ -0,0 +1,273 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec2;
+use wgpu::util::DeviceExt;
+use wgpu::{
+    include_wgsl, BindGroup, Buffer, CommandEncoder, Device, PipelineLayout, Queue, RenderPipeline, ShaderModule,
+    TextureView,
+};
+
+use lib::scene::Scene;
+use lib::shader_types::ColoredVertex;
+
+use crate::camera::Camera;
+use crate::debug_draw::DebugDraw;
+
+const RANGE_COLOR: [f32; 3] = [1.0, 0.9, 0.4];
+
+/**
+Makes the lights of the active scene visible, see `Meta::light_gizmos`: an icon in the light's color at each light,
+which selects the light's model when clicked with `Command::SelectAt`, and a wireframe sphere of each light's range.
+Icons are drawn on top of everything, so that lights inside of meshes can be found too.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightGizmos {
+    pub icons: bool,
+    /// draws spheres for lights that have a range, hidden behind meshes
+    pub ranges: bool,
+    /// size of the icons in pixels
+    pub size: f32,
+}
+
+impl Default for LightGizmos {
+    fn default() -> Self {
+        Self {
+            icons: false,
+            ranges: false,
+            size: 32.0,
+        }
+    }
+}
+
+impl LightGizmos {
+    /**
+    The model of the light whose icon is at the cursor, the closest one to the camera if icons overlap, for a viewport
+    of `size` pixels.
+     */
+    pub fn hit(&self, cursor: (u32, u32), size: (u32, u32), camera: &Camera, scene: &Scene) -> Option<u32> {
+        if !self.icons {
+            return None;
+        }
+        let cursor = Vec2::new(cursor.0 as f32, cursor.1 as f32);
+        let projection = camera.build_projection();
+        scene
+            .iter_models_deep()
+            .filter_map(|model| {
+                let light = model.light.as_ref()?;
+                let clip = projection * light.global_transform.w_axis.truncate().extend(1.0);
+                if clip.w <= 0.0 {
+                    return None;
+                }
+                let ndc = clip.truncate().truncate() / clip.w;
+                let pixel = Vec2::new((ndc.x + 1.0) / 2.0 * size.0 as f32, (1.0 - ndc.y) / 2.0 * size.1 as f32);
+                (pixel.distance(cursor) <= self.size / 2.0).then_some((model.id, clip.w))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(model_id, _)| model_id)
+    }
+
+    /**
+    Adds the range spheres of the lights in `scene` to `draw`, if they are enabled.
+     */
+    pub(crate) fn ranges(&self, scene: &Scene, draw: &mut DebugDraw) {
+        if !self.ranges {
+            return;
+        }
+        for light in scene.iter_models_deep().filter_map(|model| model.light.as_ref()) {
+            if let Some(range) = light.range {
+                draw.draw_sphere(light.global_transform.w_axis.truncate(), range, RANGE_COLOR, true);
+            }
+        }
+    }
+
+    /**
+    One instance per light in `scene` for `LightGizmoPipeline::render`, with the light's color brightened so that its
+    largest component is one, as dark icons would be hard to make out.
+     */
+    pub(crate) fn instances(&self, scene: &Scene) -> Vec<ColoredVertex> {
+        scene
+            .iter_models_deep()
+            .filter_map(|model| model.light.as_ref())
+            .map(|light| ColoredVertex {
+                position: light.global_transform.w_axis.truncate().into(),
+                color: (light.color / light.color.max_element().max(f32::EPSILON)).into(),
+            })
+            .collect()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct IconUniform {
+    extent: [f32; 2],
+    padding: [f32; 2],
+}
+
+/**
+Draws the light icons of `LightGizmos` as billboards that keep their size on screen.
+ */
+pub struct LightGizmoPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pipeline_layout: PipelineLayout,
+    icon_buffer: Buffer,
+    icon_bind_group: BindGroup,
+    instance_buffer: Buffer,
+    // number of instances the instance buffer can hold
+    capacity: usize,
+    // number of instances uploaded in the last update
+    count: u32,
+}
+
+impl LightGizmoPipeline {
+    const INITIAL_CAPACITY: usize = 64;
+
+    pub fn new(device: &Device, camera: &Camera) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("../shaders/light_gizmos.wgsl"));
+        let icon_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Light Gizmo Bindgroup Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let icon_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Gizmo Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[IconUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let icon_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Light Gizmo Bindgroup"),
+            layout: &icon_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: icon_buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Light Gizmo Pipeline Layout"),
+            bind_group_layouts: &[&camera.bind_group_layout, &icon_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            icon_buffer,
+            icon_bind_group,
+            instance_buffer: Self::create_instance_buffer(device, Self::INITIAL_CAPACITY),
+            capacity: Self::INITIAL_CAPACITY,
+            count: 0,
+        }
+    }
+
+    fn create_instance_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Gizmo Instance Buffer"),
+            size: (capacity * std::mem::size_of::<ColoredVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // (re-)creates the pipeline for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Light Gizmo Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                // the corners of the icons come from the vertex index
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ColoredVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+    }
+
+    /**
+    Uploads an icon of `size` pixels for each of `instances`, for a view that is `viewport` pixels large. The instance
+    buffer grows as needed.
+     */
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        instances: &[ColoredVertex],
+        viewport: (u32, u32),
+        size: f32,
+    ) {
+        self.count = instances.len() as u32;
+        if instances.is_empty() {
+            return;
+        }
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(instances));
+        let icon = IconUniform {
+            extent: [size / viewport.0.max(1) as f32, size / viewport.1.max(1) as f32],
+            padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.icon_buffer, 0, bytemuck::cast_slice(&[icon]));
+    }
+
+    /**
+    Draws the icons of the last `update` over `view`.
+     */
+    pub fn render(&self, encoder: &mut CommandEncoder, view: &TextureView, camera: &Camera) {
+        if self.count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Light Gizmo Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.icon_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.count);
+    }
+}
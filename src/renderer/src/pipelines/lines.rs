@@ -0,0 +1,180 @@
+use wgpu::{
+    include_wgsl, Buffer, CommandEncoder, DepthStencilState, Device, PipelineLayout, Queue,
+    RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule, TextureView,
+};
+
+use lib::shader_types::{ColoredVertex, Vertex};
+use lib::texture::Texture;
+
+use crate::camera::{Camera, DepthMode};
+
+/**
+Draws colored line segments in world space on top of the scene, for debug visualizations. Lines are either depth tested
+against the scene's depth buffer or drawn over everything.
+ */
+pub struct LinePipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    depth_tested_pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    vertex_buffer: Buffer,
+    // number of vertices the vertex buffer can hold
+    capacity: usize,
+    // number of depth tested vertices at the start of the vertex buffer, and of all vertices
+    split: u32,
+    count: u32,
+}
+
+impl LinePipeline {
+    const INITIAL_CAPACITY: usize = 1024;
+
+    pub fn new(device: &Device, camera: &Camera) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("../shaders/lines.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Line Pipeline Layout"),
+            bind_group_layouts: &[&camera.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            depth_tested_pipeline: None,
+            pipeline_layout,
+            vertex_buffer: Self::create_vertex_buffer(device, Self::INITIAL_CAPACITY),
+            capacity: Self::INITIAL_CAPACITY,
+            split: 0,
+            count: 0,
+        }
+    }
+
+    fn create_vertex_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Line Vertex Buffer"),
+            size: (capacity * std::mem::size_of::<ColoredVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // (re-)creates the pipelines for color targets of `format`
+    pub(crate) fn create_pipelines(&mut self, device: &Device, format: wgpu::TextureFormat, depth_mode: DepthMode) {
+        self.pipeline = Some(self.create_pipeline(device, format, wgpu::CompareFunction::Always));
+        self.depth_tested_pipeline = Some(self.create_pipeline(device, format, depth_mode.compare()));
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &Device,
+        format: wgpu::TextureFormat,
+        depth_compare: wgpu::CompareFunction,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Line Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[ColoredVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // both pipelines draw in the same pass, so both have the depth attachment, but neither writes to it
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /**
+    Uploads line lists, i.e. every two vertices form a segment: `depth_tested` ones are drawn behind the scene, and
+    `overlay` ones over everything. Both go into one vertex buffer, which grows as needed.
+     */
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        depth_tested: &[ColoredVertex],
+        overlay: &[ColoredVertex],
+    ) {
+        let count = depth_tested.len() + overlay.len();
+        self.split = depth_tested.len() as u32;
+        self.count = count as u32;
+        if count == 0 {
+            return;
+        }
+        if count > self.capacity {
+            self.capacity = count.next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.capacity);
+        }
+        let vertex_size = std::mem::size_of::<ColoredVertex>() as u64;
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(depth_tested));
+        queue.write_buffer(
+            &self.vertex_buffer,
+            depth_tested.len() as u64 * vertex_size,
+            bytemuck::cast_slice(overlay),
+        );
+    }
+
+    /**
+    Draws the lines of the last `update`, testing the depth tested ones against `depth`.
+     */
+    pub fn render(&self, encoder: &mut CommandEncoder, view: &TextureView, depth: &TextureView, camera: &Camera) {
+        if self.count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Line Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if self.split > 0 {
+            render_pass.set_pipeline(self.depth_tested_pipeline.as_ref().unwrap());
+            render_pass.draw(0..self.split, 0..1);
+        }
+        if self.count > self.split {
+            render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+            render_pass.draw(self.split..self.count, 0..1);
+        }
+    }
+}
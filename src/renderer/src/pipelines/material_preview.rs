@@ -0,0 +1,304 @@
+use glam::{Mat4, Vec3, Vec4};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayoutDescriptor, Buffer, BufferUsages, Color, CommandEncoder, Device, PipelineLayout, Queue,
+    RenderPipeline, ShaderModule, TextureView,
+};
+
+use lib::buffer_array::{DynamicBufferArray, DynamicBufferMap};
+use lib::geometry::Geometry;
+use lib::managers::{MatId, MaterialManager};
+use lib::mesh_pool::MeshPool;
+use lib::scene::mesh::Mesh;
+use lib::shader_types::{CameraUniform, LightInfo, MeshInfo};
+use lib::texture::Texture;
+
+use crate::camera::Camera;
+use crate::pipelines::pbr::{ClippedPass, DrawInputs, PBRPipeline, RenderLayerTarget};
+
+// in world units, the camera below is placed for a sphere of this size
+const SPHERE_RADIUS: f32 = 100.0;
+// camera space, a bit above the sphere
+const EYE: Vec3 = Vec3::new(0.0, -0.8, -3.0);
+const FOVY: f32 = 45.0;
+const CLEAR_COLOR: Color = Color {
+    r: 0.02,
+    g: 0.02,
+    b: 0.025,
+    a: 1.0,
+};
+// world positions of a key light at the top left and a dimmer fill light at the right, with the radiance they should
+// have where they hit the sphere
+const LIGHTS: [(Vec3, f32); 2] = [
+    (Vec3::new(-300.0, -350.0, -300.0), 3.0),
+    (Vec3::new(350.0, -100.0, -150.0), 0.6),
+];
+
+/**
+Renders a material onto a lit sphere, for previews in the GUI. The sphere is rendered with the PBR pipeline into an
+offscreen HDR target, which is then tone mapped with the display calibration into `output`. The sphere has its own
+mesh and light buffers, so the preview doesn't depend on the active scene.
+ */
+pub struct MaterialPreviewPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    sphere: Mesh,
+    mesh_pool: MeshPool,
+    mesh_buffer: DynamicBufferMap<MeshInfo, u32>,
+    light_buffer: DynamicBufferArray<LightInfo>,
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    target: RenderLayerTarget,
+    target_bind_group: BindGroup,
+    output: Texture,
+}
+
+impl MaterialPreviewPipeline {
+    /// width and height of the preview in pixels
+    pub const SIZE: u32 = 256;
+    // sRGB like the surface, so that egui draws the preview with the same colors as the viewport
+    const OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub fn new(device: &Device, camera: &Camera, pbr_pipeline: &PBRPipeline) -> Self {
+        // tone mapping is the same as for the split-screen views, a fullscreen triangle sampling the HDR target
+        let shader = Camera::create_shader(
+            device,
+            "Material Preview Shader",
+            include_str!("../shaders/split_screen.wgsl"),
+        );
+        let target_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Material Preview Target Bindgroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Material Preview Pipeline Layout"),
+            bind_group_layouts: &[&camera.bind_group_layout, &target_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // the material is set before each render
+        let mut sphere = Geometry::Sphere { radius: SPHERE_RADIUS }.new_mesh(MatId::default());
+        // same flip as primitives get, so that the sphere's UVs are the same way up as theirs
+        let mut neutral = Mat4::IDENTITY;
+        neutral.y_axis *= -1.0;
+        sphere.global_transform = neutral;
+        sphere.normal_matrix = neutral.inverse().transpose();
+        sphere.previous_transform = neutral;
+
+        let mesh_pool = MeshPool::new(device, "Material Preview Mesh Pool");
+        let mesh_buffer = DynamicBufferMap::new(
+            device,
+            Some("Material Preview Mesh Buffer".to_string()),
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            &pbr_pipeline.mesh_bind_group_layout,
+        );
+        let light_buffer = DynamicBufferArray::new(
+            device,
+            Some("Material Preview Light Buffer".to_string()),
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            &pbr_pipeline.light_bind_group_layout,
+        );
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Preview Camera"),
+            contents: bytemuck::cast_slice(&[camera.uniform()]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Material Preview Camera"),
+            layout: &camera.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let target = RenderLayerTarget::new(device, Self::SIZE, Self::SIZE);
+        let target_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Material Preview Target Bindgroup"),
+            layout: &target_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&target.color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&target.color.sampler),
+                },
+            ],
+        });
+
+        let output =
+            Texture::create_render_target(device, Self::SIZE, Self::SIZE, Self::OUTPUT_FORMAT, "Material Preview");
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            sphere,
+            mesh_pool,
+            mesh_buffer,
+            light_buffer,
+            camera_buffer,
+            camera_bind_group,
+            target,
+            target_bind_group,
+            output,
+        }
+    }
+
+    // the preview always renders into its own output, so unlike the other pipelines it doesn't depend on the surface
+    pub(crate) fn create_pipeline(&mut self, device: &Device) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Material Preview Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: Self::OUTPUT_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+    }
+
+    /**
+    The rendered preview, to be registered with egui.
+     */
+    pub fn output(&self) -> &TextureView {
+        &self.output.view
+    }
+
+    /**
+    Puts `material` onto the sphere and uploads the sphere, its lights and its view with the calibration of `camera`.
+    The material has to exist in `material_manager`. Call this before each `render`.
+     */
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        material: MatId,
+        pbr_pipeline: &PBRPipeline,
+        material_manager: &MaterialManager,
+        camera: &Camera,
+    ) {
+        self.sphere.set_material(material);
+        if self.sphere.vertex_inputs.is_none() {
+            self.sphere.vertex_inputs = Some(self.mesh_pool.insert(device, queue, &self.sphere));
+        }
+        let mesh_info = MeshInfo::from_mesh(&self.sphere, material_manager);
+        if self.mesh_buffer.get(&self.sphere.id).is_some() {
+            self.mesh_buffer.update(queue, &self.sphere.id, mesh_info);
+        } else {
+            self.mesh_buffer.push(
+                device,
+                queue,
+                self.sphere.id,
+                &[mesh_info],
+                &pbr_pipeline.mesh_bind_group_layout,
+            );
+        }
+        if self.light_buffer.len() == 0 {
+            let lights = LIGHTS.map(|(position, radiance)| LightInfo {
+                transform: Mat4::from_translation(position).to_cols_array_2d(),
+                color: [1.0; 3],
+                // the PBR shader divides intensities by 10 and by the squared distance to the light
+                intensity: radiance * 10.0 * (position.length() - SPHERE_RADIUS).powi(2),
+                range: 1.0,
+                ..Default::default()
+            });
+            self.light_buffer
+                .push(device, queue, &lights, &pbr_pipeline.light_bind_group_layout);
+        }
+        let uniform = camera.view_uniform(EYE, Vec3::ZERO, FOVY, 1.0);
+        let uniform = CameraUniform {
+            num_lights: self.light_buffer.len() as u32,
+            ..uniform
+        };
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /**
+    Renders the sphere with the material of the last `update`.
+     */
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        pbr_pipeline: &PBRPipeline,
+        material_manager: &MaterialManager,
+    ) {
+        let pass = ClippedPass {
+            clear_color: CLEAR_COLOR,
+            // a zero plane doesn't clip anything
+            clip_plane: Vec4::ZERO,
+            mirrored: false,
+        };
+        let inputs = DrawInputs {
+            meshes: &[&self.sphere],
+            mesh_pool: &self.mesh_pool,
+            material_manager,
+            mesh_buffer: &self.mesh_buffer,
+            light_buffer: &self.light_buffer,
+            camera_bind_group: &self.camera_bind_group,
+        };
+        pbr_pipeline.render_clipped(device, encoder, &self.target, pass, &inputs);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Material Preview Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.output.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.target_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
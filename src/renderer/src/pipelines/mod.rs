@@ -1,4 +1,22 @@
 pub mod object_picking;
 pub mod outlining;
 pub mod pbr;
+pub mod post_effects;
+pub mod post_process;
+pub mod motion_blur;
 pub mod grid;
+pub mod lines;
+pub mod compute;
+pub(crate) mod draw_constants;
+pub mod grading;
+pub mod imposter;
+pub mod material_preview;
+pub mod sky;
+pub mod split_screen;
+pub mod stereo;
+pub mod water;
+pub mod depth_prepass;
+pub mod jump_flood;
+pub mod axes_gizmo;
+pub mod light_gizmos;
+pub mod shadows;
@@ -0,0 +1,348 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, CommandEncoder, DepthStencilState, Device,
+    PipelineLayout, RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
+};
+
+use lib::buffer_array::DynamicBufferMap;
+use lib::mesh_pool::MeshPool;
+use lib::scene::mesh::Mesh;
+use lib::shader_types::{MeshInfo, PbrVertex, Vertex};
+use lib::texture::Texture;
+
+use crate::camera::{Camera, DepthMode};
+use crate::pipelines::draw_constants::DrawConstants;
+use crate::pipelines::post_process::{fullscreen_pass, PostProcess, PostProcessChain, PostProcessContext};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct PushConstants {
+    mesh_index: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct BlurUniform {
+    intensity: f32,
+    samples: u32,
+    padding: [u32; 2],
+}
+
+/**
+Runtime settings of the motion blur post pass.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionBlur {
+    pub enabled: bool,
+    /// scales the motion of the last frame, 1.0 blurs along the full distance a pixel moved
+    pub intensity: f32,
+    /// color samples per pixel along its motion
+    pub samples: u32,
+}
+
+impl Default for MotionBlur {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 1.0,
+            samples: 8,
+        }
+    }
+}
+
+/**
+Velocity-based motion blur. The meshes' screen-space motion since the previous frame (from the previous transforms in
+`MeshInfo` and the camera) is rendered into a velocity buffer, and the blur pass then averages the scene color along
+each pixel's motion.
+ */
+pub struct MotionBlurPipeline {
+    shader: ShaderModule,
+    velocity_pipeline: Option<RenderPipeline>,
+    blur_pipeline: Option<RenderPipeline>,
+    pub velocity_pipeline_layout: PipelineLayout,
+    pub blur_pipeline_layout: PipelineLayout,
+    velocity_target: Texture,
+    depth_texture: Texture,
+    blur_buffer: Buffer,
+    blur_bind_group_layout: BindGroupLayout,
+    blur_bind_group: BindGroup,
+    draw_constants: DrawConstants<PushConstants>,
+}
+
+impl MotionBlurPipeline {
+    pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+    pub fn new(device: &Device, config: &SurfaceConfiguration, camera: &Camera, chain: &PostProcessChain) -> Self {
+        let draw_constants = DrawConstants::new(
+            device,
+            "Motion Blur Draw Constants Bindgroup Layout",
+            wgpu::ShaderStages::VERTEX,
+            2,
+        );
+        let shader = draw_constants.create_shader(
+            device,
+            "Motion Blur Shader",
+            include_str!("../shaders/motion_blur.wgsl"),
+        );
+
+        let mesh_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Motion Blur Mesh Bindgroup Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let velocity_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Motion Blur Velocity Pipeline Layout"),
+            bind_group_layouts: &draw_constants
+                .bind_group_layouts(&[&mesh_bind_group_layout, &camera.bind_group_layout]),
+            push_constant_ranges: &draw_constants.push_constant_ranges(),
+        });
+
+        let blur_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Motion Blur Bindgroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let blur_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Motion Blur Pipeline Layout"),
+            bind_group_layouts: &[chain.input_bind_group_layout(), &blur_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let blur_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Motion Blur Buffer"),
+            contents: bytemuck::cast_slice(&[BlurUniform::from(&MotionBlur::default())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let (velocity_target, depth_texture) = Self::create_targets(device, config);
+        let blur_bind_group =
+            Self::create_blur_bind_group(device, &blur_bind_group_layout, &velocity_target, &blur_buffer);
+
+        Self {
+            shader,
+            velocity_pipeline: None,
+            blur_pipeline: None,
+            velocity_pipeline_layout,
+            blur_pipeline_layout,
+            velocity_target,
+            depth_texture,
+            blur_buffer,
+            blur_bind_group_layout,
+            blur_bind_group,
+            draw_constants,
+        }
+    }
+
+    fn create_targets(device: &Device, config: &SurfaceConfiguration) -> (Texture, Texture) {
+        (
+            Texture::create_render_target(
+                device,
+                config.width,
+                config.height,
+                Self::VELOCITY_FORMAT,
+                "Motion Blur Velocity Target",
+            ),
+            Texture::create_depth_texture(device, config.width, config.height, "Motion Blur Depth Texture"),
+        )
+    }
+
+    fn create_blur_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        velocity_target: &Texture,
+        blur_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Motion Blur Bindgroup"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&velocity_target.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: blur_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    // (re-)creates the pipelines
+    pub(crate) fn create_pipelines(&mut self, device: &Device, chain: &PostProcessChain, depth_mode: DepthMode) {
+        self.velocity_pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Motion Blur Velocity Pipeline"),
+            layout: Some(&self.velocity_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_velocity",
+                buffers: &[PbrVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_velocity",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: Self::VELOCITY_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // separate depth buffer, so that the velocity of occluded surfaces is discarded
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: depth_mode.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+        self.blur_pipeline = Some(chain.create_pipeline(
+            device,
+            "Motion Blur Pipeline",
+            &self.blur_pipeline_layout,
+            &self.shader,
+            "fs_blur",
+        ));
+    }
+
+    /**
+    Renders the screen-space motion of the meshes since the previous frame into the velocity buffer.
+     */
+    pub fn render_velocity(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        meshes: &[&Mesh],
+        mesh_pool: &MeshPool,
+        mesh_buffer: &DynamicBufferMap<MeshInfo, u32>,
+        camera: &Camera,
+    ) {
+        let constants = meshes
+            .iter()
+            .map(|mesh| PushConstants {
+                mesh_index: *mesh_buffer.get(&mesh.id).expect("Mesh not found in mesh_info_map") as u32,
+            })
+            .collect::<Vec<_>>();
+        let uploaded = self.draw_constants.upload(device, &constants);
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Motion Blur Velocity Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.velocity_target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(camera.depth_mode().far_depth()),
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.velocity_pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, mesh_buffer.bind_group(), &[]);
+        render_pass.set_bind_group(1, &camera.bind_group, &[]);
+        mesh_pool.bind(&mut render_pass);
+        for (draw, inputs) in meshes.iter().map(|m| m.vertex_inputs.as_ref().unwrap()).enumerate() {
+            self.draw_constants
+                .set(&mut render_pass, uploaded.as_ref(), draw, &constants[draw]);
+            render_pass.draw_indexed(inputs.indices(), inputs.base_vertex as i32, 0..1);
+        }
+    }
+}
+
+impl PostProcess for MotionBlurPipeline {
+    // the velocity buffer is rendered from the mono camera, which doesn't match the eyes of the stereo preview
+    fn is_enabled(&self, context: &PostProcessContext) -> bool {
+        context.meta.motion_blur.enabled && !context.meta.stereo.enabled && !context.meta.split_screen.enabled
+    }
+
+    fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        (self.velocity_target, self.depth_texture) = Self::create_targets(device, config);
+        self.blur_bind_group = Self::create_blur_bind_group(
+            device,
+            &self.blur_bind_group_layout,
+            &self.velocity_target,
+            &self.blur_buffer,
+        );
+    }
+
+    /**
+    Blurs the input along the velocity buffer, which has to be rendered with `render_velocity` beforehand.
+     */
+    fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        context: &PostProcessContext,
+        input: &BindGroup,
+        output: &TextureView,
+    ) {
+        let uniform = BlurUniform::from(&context.meta.motion_blur);
+        context
+            .queue
+            .write_buffer(&self.blur_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        fullscreen_pass(
+            encoder,
+            "Motion Blur Render Pass",
+            output,
+            self.blur_pipeline.as_ref().unwrap(),
+            &[input, &self.blur_bind_group],
+        );
+    }
+}
+
+impl From<&MotionBlur> for BlurUniform {
+    fn from(settings: &MotionBlur) -> Self {
+        Self {
+            intensity: settings.intensity,
+            samples: settings.samples,
+            padding: [0; 2],
+        }
+    }
+}
@@ -1,17 +1,93 @@
+use std::collections::VecDeque;
+
 use bytemuck::{Pod, Zeroable};
+use glam::{Vec2, Vec3};
 use wgpu::{
-    BindGroup, BindGroupLayoutDescriptor, Buffer, BufferAddress, Color, CommandEncoder, DepthStencilState,
-    Device, include_wgsl, PipelineLayout, Queue, RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule,
-    SurfaceConfiguration, TextureView,
+    BindGroup, BindGroupLayoutDescriptor, Color, CommandEncoder, DepthStencilState, Device, PipelineLayout, Queue,
+    RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
 };
 
 use lib::buffer_array::DynamicBufferMap;
+use lib::mesh_pool::MeshPool;
 use lib::scene::mesh::Mesh;
 use lib::scene::VertexInputs;
 use lib::shader_types::{MeshInfo, PbrVertex, Vertex};
 use lib::texture::Texture;
 
-use crate::camera::Camera;
+use crate::camera::{Camera, DepthMode};
+use crate::commands::Command;
+use crate::pipelines::draw_constants::DrawConstants;
+use crate::readback::ReadbackBuffer;
+use crate::selection::SelectionMode;
+
+// mesh ids are written as their four bytes
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+// the view depth of the mesh, which the hit point is reconstructed from
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+/**
+A mesh under the cursor, see `Command::QueryClick`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickHit {
+    pub mesh_id: u32,
+    /// the point that was hit, in world space
+    pub position: Vec3,
+    /// the distance of `position` from the camera, in world units
+    pub depth: f32,
+}
+
+/**
+What a picking query is for, which decides what happens with its result.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PickRequest {
+    /// sends the result as `CommandResult::ClickQuery`
+    Query,
+    /// selects the hit mesh, see `Command::SelectAt`
+    Select(SelectionMode),
+    /// starts dragging the selection if a selected mesh was hit, see `Meta::drag`
+    Drag,
+    /// sends `Event::Hover` if another mesh was hit than before
+    Hover,
+}
+
+/**
+The result of a picking query, see `ObjectPickingPipeline::poll`.
+ */
+#[derive(Debug)]
+pub(crate) enum PickResult {
+    /// the mesh at a pixel, `None` if there is none
+    Point(PickRequest, Option<PickHit>),
+    /// the unique ids of the meshes in a region, see `Command::BoxSelect`
+    Region(Vec<u32>),
+}
+
+enum PickQuery {
+    Point(PickRequest, u32, u32),
+    // the minimum and maximum corner
+    Region((u32, u32), (u32, u32)),
+}
+
+// a query that was rendered and is being read back
+enum PendingPick {
+    Point(PendingPoint),
+    // read back into a buffer of the region's size, along with the region's corners in the viewport
+    Region(ReadbackBuffer, (u32, u32), (u32, u32)),
+}
+
+struct PendingPoint {
+    request: PickRequest,
+    // the pixel of the viewport
+    x: u32,
+    y: u32,
+    // where the camera was when the query was rendered, to reconstruct the hit point from the view depth
+    eye: Vec3,
+    near: Vec3,
+    znear: f32,
+    mesh_id: Option<u32>,
+    view_depth: Option<f32>,
+}
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
@@ -28,15 +104,35 @@ pub struct ObjectPickingPipeline {
     pub depth_texture: Texture,
     pub render_target: wgpu::Texture,
     render_target_view: TextureView,
-    staging_buffer: Buffer,
+    // the view depth of the picked pixels
+    depth_target: wgpu::Texture,
+    depth_target_view: TextureView,
+    // the pixel under the cursor
+    readback: ReadbackBuffer,
+    depth_readback: ReadbackBuffer,
+    queued: VecDeque<PickQuery>,
+    pending: Option<PendingPick>,
     target_size: (u32, u32),
     viewport_size: (u32, u32),
+    // the depth mode the pipeline was created for
+    depth_mode: DepthMode,
+    draw_constants: DrawConstants<PushConstants>,
 }
 
 impl ObjectPickingPipeline {
     // Creates all necessary bind groups and layouts for the pipeline
     pub fn new(device: &Device, config: &SurfaceConfiguration, camera: &Camera) -> Self {
-        let shader = device.create_shader_module(include_wgsl!("../shaders/object_picking.wgsl"));
+        let draw_constants = DrawConstants::new(
+            device,
+            "Object Picking Draw Constants Bindgroup Layout",
+            wgpu::ShaderStages::VERTEX,
+            2,
+        );
+        let shader = draw_constants.create_shader(
+            device,
+            "Object Picking Shader",
+            include_str!("../shaders/object_picking.wgsl"),
+        );
         let target_size = (Self::round_to_next_multiple_of_256(config.width), config.height);
         let depth_texture = Texture::create_depth_texture(device, target_size.0, target_size.1, "depth_texture");
 
@@ -56,36 +152,18 @@ impl ObjectPickingPipeline {
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Object Picking Pipeline Layout"),
-            bind_group_layouts: &[&mesh_bind_group_layout, &camera.bind_group_layout],
-            push_constant_ranges: &[wgpu::PushConstantRange {
-                stages: wgpu::ShaderStages::VERTEX,
-                range: 0..std::mem::size_of::<PushConstants>() as u32,
-            }],
-        });
-
-        let render_target = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Object Picking Render Target"),
-            size: wgpu::Extent3d {
-                width: target_size.0,
-                height: target_size.1,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+            bind_group_layouts: &draw_constants
+                .bind_group_layouts(&[&mesh_bind_group_layout, &camera.bind_group_layout]),
+            push_constant_ranges: &draw_constants.push_constant_ranges(),
         });
 
+        let render_target = Self::create_target(device, target_size, ID_FORMAT, "Object Picking Render Target");
         let render_target_view = render_target.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_target = Self::create_target(device, target_size, DEPTH_FORMAT, "Object Picking Depth Target");
+        let depth_target_view = depth_target.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Object Picking Staging Buffer"),
-            size: (target_size.0 * target_size.1 * 4) as BufferAddress,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
+        let readback = ReadbackBuffer::for_texture(device, "Object Picking Readback Buffer", 1, 1, 4);
+        let depth_readback = ReadbackBuffer::for_texture(device, "Object Picking Depth Readback Buffer", 1, 1, 4);
 
         Self {
             shader,
@@ -94,9 +172,16 @@ impl ObjectPickingPipeline {
             depth_texture,
             render_target,
             render_target_view,
-            staging_buffer,
+            depth_target,
+            depth_target_view,
+            readback,
+            depth_readback,
+            queued: VecDeque::new(),
+            pending: None,
             target_size,
             viewport_size: (config.width, config.height),
+            depth_mode: DepthMode::default(),
+            draw_constants,
         }
     }
     fn round_to_next_multiple_of_256(n: u32) -> u32 {
@@ -105,33 +190,34 @@ impl ObjectPickingPipeline {
     pub(crate) fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
         let target_size = (Self::round_to_next_multiple_of_256(config.width), config.height);
         self.depth_texture = Texture::create_depth_texture(device, target_size.0, target_size.1, "depth_texture");
-        self.staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Object Picking Staging Buffer"),
-            size: (target_size.0 * target_size.1 * 4) as BufferAddress,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
         self.target_size = target_size;
         self.viewport_size = (config.width, config.height);
-        self.render_target = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Object Picking Render Target"),
+        self.render_target = Self::create_target(device, target_size, ID_FORMAT, "Object Picking Render Target");
+        self.render_target_view = self.render_target.create_view(&wgpu::TextureViewDescriptor::default());
+        self.depth_target = Self::create_target(device, target_size, DEPTH_FORMAT, "Object Picking Depth Target");
+        self.depth_target_view = self.depth_target.create_view(&wgpu::TextureViewDescriptor::default());
+    }
+
+    fn create_target(device: &Device, size: (u32, u32), format: wgpu::TextureFormat, label: &str) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
             size: wgpu::Extent3d {
-                width: target_size.0,
-                height: target_size.1,
+                width: size.0,
+                height: size.1,
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            format,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
-            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
-        });
-        self.render_target_view = self.render_target.create_view(&wgpu::TextureViewDescriptor::default());
+            view_formats: &[format],
+        })
     }
 
     // (re-)creates the pipeline
-    pub(crate) fn create_pipeline(&mut self, device: &Device) {
+    pub(crate) fn create_pipeline(&mut self, device: &Device, depth_mode: DepthMode) {
+        self.depth_mode = depth_mode;
         self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Object Picking Pipeline"),
             layout: Some(&self.pipeline_layout),
@@ -143,11 +229,19 @@ impl ObjectPickingPipeline {
             fragment: Some(wgpu::FragmentState {
                 module: &self.shader,
                 entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: wgpu::TextureFormat::Rgba8Unorm,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: ID_FORMAT,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    // float targets can't be blended
+                    Some(wgpu::ColorTargetState {
+                        format: DEPTH_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                ],
             }),
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
@@ -164,7 +258,7 @@ impl ObjectPickingPipeline {
             depth_stencil: Some(DepthStencilState {
                 format: Texture::DEPTH_FORMAT,
                 depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare: depth_mode.compare(),
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
@@ -179,26 +273,55 @@ impl ObjectPickingPipeline {
 
     fn render_pass<'a>(
         &self,
+        device: &Device,
         encoder: &mut CommandEncoder,
         vertex_inputs: impl Iterator<Item = &'a VertexInputs>,
+        mesh_pool: &MeshPool,
         mesh_info_map: &DynamicBufferMap<MeshInfo, u32>,
         camera_bind_group: &BindGroup,
     ) {
         let vertex_inputs = vertex_inputs.collect::<Vec<_>>();
+        let constants = vertex_inputs
+            .iter()
+            .map(|VertexInputs { mesh_id, .. }| {
+                let mesh_index = mesh_info_map.get(mesh_id).expect("Mesh not found in mesh_info_map");
+                PushConstants {
+                    mesh_index: *mesh_index as u32,
+                    padding: [0; 3],
+                    color: [
+                        (mesh_id & 0xff) as f32 / 255.0,
+                        ((mesh_id >> 8) & 0xff) as f32 / 255.0,
+                        ((mesh_id >> 16) & 0xff) as f32 / 255.0,
+                        ((mesh_id >> 24) & 0xff) as f32 / 255.0,
+                    ],
+                }
+            })
+            .collect::<Vec<_>>();
+        let uploaded = self.draw_constants.upload(device, &constants);
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Object Picking Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.render_target_view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(Color::TRANSPARENT),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.render_target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &self.depth_target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+            ],
             depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
                 view: &self.depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: wgpu::LoadOp::Clear(self.depth_mode.far_depth()),
                     store: wgpu::StoreOp::Discard,
                 }),
                 stencil_ops: None,
@@ -210,89 +333,205 @@ impl ObjectPickingPipeline {
 
         render_pass.set_bind_group(0, mesh_info_map.bind_group(), &[]);
         render_pass.set_bind_group(1, camera_bind_group, &[]);
+        mesh_pool.bind(&mut render_pass);
 
-        for VertexInputs {
-            mesh_id,
-            vertex_buffer,
-            index_buffer,
-        } in vertex_inputs.iter()
-        {
-            let mesh_index = mesh_info_map.get(mesh_id).expect("Mesh not found in mesh_info_map");
-            let push_constants = PushConstants {
-                mesh_index: *mesh_index as u32,
-                padding: [0; 3],
-                color: [
-                    (mesh_id & 0xff) as f32 / 255.0,
-                    ((mesh_id >> 8) & 0xff) as f32 / 255.0,
-                    ((mesh_id >> 16) & 0xff) as f32 / 255.0,
-                    ((mesh_id >> 24) & 0xff) as f32 / 255.0,
-                ],
-            };
-            render_pass.set_push_constants(wgpu::ShaderStages::VERTEX, 0, bytemuck::bytes_of(&push_constants));
-            render_pass.set_vertex_buffer(0, vertex_buffer.buffer.slice(..));
-            render_pass.set_index_buffer(index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint16);
-
-            render_pass.draw_indexed(0..index_buffer.count, 0, 0..1);
+        for (draw, inputs) in vertex_inputs.iter().enumerate() {
+            self.draw_constants
+                .set(&mut render_pass, uploaded.as_ref(), draw, &constants[draw]);
+            render_pass.draw_indexed(inputs.indices(), inputs.base_vertex as i32, 0..1);
         }
     }
 
-    pub fn query_click(
-        &self,
+    /**
+    Queues a query for the mesh at the pixel `x`, `y` of the viewport. Queries are rendered one after another by
+    `render_next` and their results are returned by `poll` some frames later, so that picking doesn't stall the frame.
+     */
+    pub(crate) fn request(&mut self, request: PickRequest, x: u32, y: u32) {
+        self.queued.push_back(PickQuery::Point(request, x, y));
+    }
+
+    /**
+    Like `request`, but queries all meshes in the rectangle between the corners `min` and `max` of the viewport.
+     */
+    pub(crate) fn request_region(&mut self, min: (u32, u32), max: (u32, u32)) {
+        self.queued.push_back(PickQuery::Region(min, max));
+    }
+
+    /**
+    Renders the next queued query, unless one is still being read back.
+     */
+    pub(crate) fn render_next(
+        &mut self,
         device: &Device,
         queue: &Queue,
-        x: u32,
-        y: u32,
         meshes: &[&Mesh],
+        mesh_pool: &MeshPool,
         mesh_buffer: &DynamicBufferMap<MeshInfo, u32>,
         camera: &Camera,
-    ) -> u32 {
+    ) {
+        if self.pending.is_some() {
+            return;
+        }
+        let Some(query) = self.queued.pop_front() else {
+            return;
+        };
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Object Picking Query Encoder"),
         });
         let vertex_inputs = meshes.iter().map(|m| m.vertex_inputs.as_ref().unwrap());
 
-        self.render_pass(&mut encoder, vertex_inputs, mesh_buffer, &camera.bind_group);
-
-        encoder.copy_texture_to_buffer(
-            wgpu::ImageCopyTexture {
-                texture: &self.render_target,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            wgpu::ImageCopyBuffer {
-                buffer: &self.staging_buffer,
-                layout: wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(self.target_size.0 * 4),
-                    rows_per_image: Some(self.target_size.1),
-                },
-            },
-            wgpu::Extent3d {
-                width: self.target_size.0,
-                height: self.target_size.1,
-                depth_or_array_layers: 1,
-            },
+        self.render_pass(
+            device,
+            &mut encoder,
+            vertex_inputs,
+            mesh_pool,
+            mesh_buffer,
+            &camera.bind_group,
         );
-        queue.submit(Some(encoder.finish()));
-        let res = {
-            let buffer_slice = self.staging_buffer.slice(..);
-            let (sender, receiver) = flume::unbounded();
-            buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
-            device.poll(wgpu::Maintain::Wait);
-            receiver.recv().unwrap().unwrap();
-            let view = buffer_slice.get_mapped_range();
-            // save buffer contents as image
-            let ratio = self.viewport_size.0 as f32 / self.target_size.0 as f32;
-            let x = (x as f32 / ratio) as u32;
-            // get red pixel value at x, y from buffer; buffer is in RGBA format and image has width of target_size.0 and height of target_size.1
-            let r = view[(x * 4 + y * self.target_size.0 * 4) as usize];
-            let g = view[(x * 4 + y * self.target_size.0 * 4 + 1) as usize];
-            let b = view[(x * 4 + y * self.target_size.0 * 4 + 2) as usize];
-            let a = view[(x * 4 + y * self.target_size.0 * 4 + 3) as usize];
-            u32::from_le_bytes([r, g, b, a])
-        };
-        self.staging_buffer.unmap();
-        res
+
+        match query {
+            PickQuery::Point(request, x, y) => {
+                let (target_x, target_y) = self.to_target(x, y);
+                let origin = wgpu::Origin3d {
+                    x: target_x,
+                    y: target_y,
+                    z: 0,
+                };
+                self.readback
+                    .copy_from_texture(&mut encoder, &self.render_target, origin, 1, 1);
+                self.depth_readback
+                    .copy_from_texture(&mut encoder, &self.depth_target, origin, 1, 1);
+                queue.submit(Some(encoder.finish()));
+                self.readback.map();
+                self.depth_readback.map();
+
+                // the hit point is on the ray through the pixel, the view depth grows linearly along it starting
+                // with znear at the near plane. The camera may have moved by the time the query was read back
+                let ndc = Vec2::new(
+                    (x as f32 + 0.5) / self.viewport_size.0 as f32 * 2.0 - 1.0,
+                    1.0 - (y as f32 + 0.5) / self.viewport_size.1 as f32 * 2.0,
+                );
+                self.pending = Some(PendingPick::Point(PendingPoint {
+                    request,
+                    x,
+                    y,
+                    eye: camera.world_eye(),
+                    near: camera
+                        .build_projection()
+                        .inverse()
+                        .project_point3(ndc.extend(camera.depth_mode().near_depth())),
+                    znear: camera.znear,
+                    mesh_id: None,
+                    view_depth: None,
+                }));
+            }
+            PickQuery::Region(corner_min, corner_max) => {
+                let min = self.to_target(corner_min.0, corner_min.1);
+                let max = self.to_target(corner_max.0, corner_max.1);
+                let width = max.0.saturating_sub(min.0) + 1;
+                let height = max.1.saturating_sub(min.1) + 1;
+                let mut readback =
+                    ReadbackBuffer::for_texture(device, "Object Picking Region Readback Buffer", width, height, 4);
+                let origin = wgpu::Origin3d {
+                    x: min.0,
+                    y: min.1,
+                    z: 0,
+                };
+                readback.copy_from_texture(&mut encoder, &self.render_target, origin, width, height);
+                queue.submit(Some(encoder.finish()));
+                readback.map();
+                self.pending = Some(PendingPick::Region(readback, corner_min, corner_max));
+            }
+        }
+    }
+
+    // the pixel of the render target that the pixel of the viewport was rendered to, as the render target is
+    // stretched horizontally to a width that is a multiple of 256
+    fn to_target(&self, x: u32, y: u32) -> (u32, u32) {
+        let ratio = self.viewport_size.0 as f32 / self.target_size.0 as f32;
+        let x = ((x as f32 / ratio) as u32).min(self.target_size.0 - 1);
+        (x, y.min(self.target_size.1 - 1))
     }
+
+    /**
+    Drops the queued queries and the one being read back, e.g. when the pipeline is replaced after the device was lost.
+    Returns the `Command::QueryClick` and `Command::BoxSelect` commands whose results weren't sent yet.
+     */
+    pub(crate) fn cancel(&mut self) -> Vec<Command> {
+        let pending = self.pending.take().map(|pending| match pending {
+            PendingPick::Point(point) => PickQuery::Point(point.request, point.x, point.y),
+            PendingPick::Region(_, min, max) => PickQuery::Region(min, max),
+        });
+        pending
+            .into_iter()
+            .chain(self.queued.drain(..))
+            .filter_map(|query| match query {
+                PickQuery::Point(PickRequest::Query, x, y) => Some(Command::QueryClick((x, y))),
+                // the others are handled by the renderer itself
+                PickQuery::Point(..) => None,
+                PickQuery::Region(min, max) => Some(Command::BoxSelect { min, max }),
+            })
+            .collect()
+    }
+
+    /**
+    The result of the rendered query, once it was read back.
+     */
+    pub(crate) fn poll(&mut self, device: &Device) -> Option<PickResult> {
+        match self.pending.as_mut()? {
+            PendingPick::Point(pending) => {
+                if pending.mesh_id.is_none() {
+                    // the pixel's RGBA bytes are the mesh id
+                    pending.mesh_id = self.readback.try_read(device, read_id);
+                }
+                if pending.view_depth.is_none() {
+                    pending.view_depth = self
+                        .depth_readback
+                        .try_read(device, |data| f32::from_le_bytes([data[0], data[1], data[2], data[3]]));
+                }
+                // a readback whose mapping failed isn't pending anymore but has no value, the query then hit nothing
+                let done = (pending.mesh_id.is_some() || !self.readback.is_pending())
+                    && (pending.view_depth.is_some() || !self.depth_readback.is_pending());
+                if !done {
+                    return None;
+                }
+                let hit = match (pending.mesh_id, pending.view_depth) {
+                    (Some(mesh_id), Some(view_depth)) if mesh_id != 0 => {
+                        let position = pending.eye + (pending.near - pending.eye) * (view_depth / pending.znear);
+                        Some(PickHit {
+                            mesh_id,
+                            position,
+                            depth: pending.eye.distance(position),
+                        })
+                    }
+                    _ => None,
+                };
+                let request = pending.request;
+                self.pending = None;
+                Some(PickResult::Point(request, hit))
+            }
+            PendingPick::Region(readback, _, _) => {
+                let Some(data) = readback.try_read(device, |data| data.to_vec()) else {
+                    if readback.is_pending() {
+                        return None;
+                    }
+                    self.pending = None;
+                    return Some(PickResult::Region(vec![]));
+                };
+                let mut ids = readback
+                    .rows(&data)
+                    .flat_map(|row| row.chunks_exact(4).map(read_id))
+                    .filter(|id| *id != 0)
+                    .collect::<Vec<_>>();
+                ids.sort_unstable();
+                ids.dedup();
+                self.pending = None;
+                Some(PickResult::Region(ids))
+            }
+        }
+    }
+}
+
+fn read_id(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
 }
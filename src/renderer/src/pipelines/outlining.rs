@@ -1,15 +1,15 @@
 use bytemuck::{Pod, Zeroable};
 use wgpu::{
-    BindGroup, BindGroupLayoutDescriptor, CommandEncoder, DepthStencilState, Device, include_wgsl, PipelineLayout,
-    RenderPass, RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
+    BindGroup, BindGroupLayoutDescriptor, CommandEncoder, DepthStencilState, Device, PipelineLayout, RenderPass,
+    RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
 };
 
-use lib::buffer_array::DynamicBufferMap;
-use lib::scene::mesh::Mesh;
-use lib::scene::VertexInputs;
-use lib::shader_types::{MeshInfo, PbrVertex, Vertex};
+use lib::scene::mesh::{Mesh, Outline};
+use lib::scene::{Scene, VertexInputs};
+use lib::shader_types::{PbrVertex, Vertex};
 
 use crate::camera::Camera;
+use crate::pipelines::draw_constants::DrawConstants;
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
@@ -18,18 +18,38 @@ struct PushConstants {
     outline_config: u32,
 }
 
+/**
+How outlined meshes are outlined. `Stencil` draws the meshes again, scaled up around their origin, wherever they didn't
+cover the stencil, which is cheap but uneven for meshes that aren't roughly convex around their origin. `JumpFlood`
+outlines in screen space, see `JumpFloodPipeline`, with the outline width in pixels.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutlineMode {
+    #[default]
+    Stencil,
+    JumpFlood,
+}
+
 pub struct OutliningPipeline {
     shader: ShaderModule,
     mask_pipeline: Option<RenderPipeline>,
     outline_pipeline: Option<RenderPipeline>,
     pipeline_layout: PipelineLayout,
     stencil_view: wgpu::TextureView,
+    draw_constants: DrawConstants<PushConstants>,
 }
 
 impl OutliningPipeline {
     // Creates all necessary bind groups and layouts for the pipeline
     pub fn new(device: &Device, config: &SurfaceConfiguration, camera: &Camera) -> Self {
-        let shader = device.create_shader_module(include_wgsl!("../shaders/outlining.wgsl"));
+        let draw_constants = DrawConstants::new(
+            device,
+            "Outlining Draw Constants Bindgroup Layout",
+            wgpu::ShaderStages::VERTEX,
+            2,
+        );
+        let shader =
+            draw_constants.create_shader(device, "Outlining Shader", include_str!("../shaders/outlining.wgsl"));
 
         let mesh_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("Outlining Mesh Bindgroup Layout"),
@@ -47,11 +67,9 @@ impl OutliningPipeline {
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Outlining Mask Pipeline Layout"),
-            bind_group_layouts: &[&mesh_bind_group_layout, &camera.bind_group_layout],
-            push_constant_ranges: &[wgpu::PushConstantRange {
-                stages: wgpu::ShaderStages::VERTEX,
-                range: 0..std::mem::size_of::<PushConstants>() as u32,
-            }],
+            bind_group_layouts: &draw_constants
+                .bind_group_layouts(&[&mesh_bind_group_layout, &camera.bind_group_layout]),
+            push_constant_ranges: &draw_constants.push_constant_ranges(),
         });
 
         let stencil_buffer = device.create_texture(&wgpu::TextureDescriptor {
@@ -77,6 +95,7 @@ impl OutliningPipeline {
             outline_pipeline: None,
             pipeline_layout,
             stencil_view,
+            draw_constants,
         }
     }
 
@@ -98,8 +117,8 @@ impl OutliningPipeline {
         self.stencil_view = stencil_buffer.create_view(&wgpu::TextureViewDescriptor::default());
     }
 
-    // (re-)creates the pipeline
-    pub(crate) fn create_pipelines(&mut self, device: &Device) {
+    // (re-)creates the pipelines for color targets of `format`
+    pub(crate) fn create_pipelines(&mut self, device: &Device, format: wgpu::TextureFormat) {
         self.mask_pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Outlining Mask Pipeline"),
             layout: Some(&self.pipeline_layout),
@@ -112,7 +131,7 @@ impl OutliningPipeline {
                 module: &self.shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    format,
                     blend: None,
                     write_mask: wgpu::ColorWrites::empty(),
                 })],
@@ -158,7 +177,7 @@ impl OutliningPipeline {
                 module: &self.shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    format,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -192,16 +211,51 @@ impl OutliningPipeline {
         }));
     }
 
-    fn render_pass<'a>(
+    // `first_draw` is the index of the first draw's constants in the uploaded draw constants
+    fn draw<'a>(
         &self,
+        render_pass: &mut RenderPass<'a>,
+        vertex_inputs: &[&VertexInputs],
+        uploaded: Option<&'a BindGroup>,
+        first_draw: usize,
+        constants: &[PushConstants],
+    ) {
+        for (draw, inputs) in vertex_inputs.iter().enumerate() {
+            self.draw_constants
+                .set(render_pass, uploaded, first_draw + draw, &constants[draw]);
+            render_pass.draw_indexed(inputs.indices(), inputs.base_vertex as i32, 0..1);
+        }
+    }
+
+    /**
+    Outlines each mesh with its outline.
+     */
+    pub fn render_outline(
+        &self,
+        device: &Device,
         encoder: &mut CommandEncoder,
         view: &TextureView,
-        vertex_inputs: impl Iterator<Item = &'a VertexInputs>,
-        mesh_info_map: &DynamicBufferMap<MeshInfo, u32>,
-        camera_bind_group: &BindGroup,
-        outline_value: u32,
+        meshes: &[(&Mesh, Outline)],
+        scene: &Scene,
+        camera: &Camera,
     ) {
-        let vertex_inputs = vertex_inputs.collect::<Vec<_>>();
+        let vertex_inputs = meshes
+            .iter()
+            .map(|(mesh, _)| mesh.vertex_inputs.as_ref().unwrap())
+            .collect::<Vec<_>>();
+        // the meshes are drawn twice, first into the stencil mask and then as the outline
+        let constants = meshes
+            .iter()
+            .map(|(mesh, _)| (mesh, 0))
+            .chain(meshes.iter().map(|(mesh, outline)| (mesh, outline.packed())))
+            .map(|(mesh, outline_config)| PushConstants {
+                mesh_index: *scene.mesh_buffer.get(&mesh.id).expect("Mesh not found in mesh_info_map") as u32,
+                outline_config,
+            })
+            .collect::<Vec<_>>();
+        let uploaded = self.draw_constants.upload(device, &constants);
+        let (mask_constants, outline_constants) = constants.split_at(meshes.len());
+
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Outlining Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -224,72 +278,21 @@ impl OutliningPipeline {
             occlusion_query_set: None,
         });
         render_pass.set_stencil_reference(1);
-        render_pass.set_pipeline(self.mask_pipeline.as_ref().unwrap());
+        scene.mesh_pool.bind(&mut render_pass);
+        // both pipelines share the layout, so the bind groups stay bound across them
+        render_pass.set_bind_group(0, scene.mesh_buffer.bind_group(), &[]);
+        render_pass.set_bind_group(1, &camera.bind_group, &[]);
 
-        Self::draw(mesh_info_map, camera_bind_group, &vertex_inputs, &mut render_pass, 0);
+        render_pass.set_pipeline(self.mask_pipeline.as_ref().unwrap());
+        self.draw(&mut render_pass, &vertex_inputs, uploaded.as_ref(), 0, mask_constants);
 
         render_pass.set_pipeline(self.outline_pipeline.as_ref().unwrap());
-
-        Self::draw(
-            mesh_info_map,
-            camera_bind_group,
-            &vertex_inputs,
+        self.draw(
             &mut render_pass,
-            outline_value,
-        );
-    }
-
-    fn draw<'a, 'b: 'a>(
-        mesh_info_map: &'b DynamicBufferMap<MeshInfo, u32>,
-        camera_bind_group: &'b BindGroup,
-        vertex_inputs: &[&'a VertexInputs],
-        render_pass: &mut RenderPass<'a>,
-        outline_value: u32,
-    ) {
-        render_pass.set_bind_group(0, mesh_info_map.bind_group(), &[]);
-        render_pass.set_bind_group(1, camera_bind_group, &[]);
-        for VertexInputs {
-            mesh_id,
-            vertex_buffer,
-            index_buffer,
-        } in vertex_inputs.iter()
-        {
-            let mesh_index = mesh_info_map.get(mesh_id).expect("Mesh not found in mesh_info_map");
-            let push_constants = PushConstants {
-                mesh_index: *mesh_index as u32,
-                outline_config: outline_value,
-            };
-            render_pass.set_push_constants(wgpu::ShaderStages::VERTEX, 0, bytemuck::bytes_of(&push_constants));
-            render_pass.set_vertex_buffer(0, vertex_buffer.buffer.slice(..));
-            render_pass.set_index_buffer(index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint16);
-
-            render_pass.draw_indexed(0..index_buffer.count, 0, 0..1);
-        }
-    }
-
-    pub fn render_outline(
-        &self,
-        encoder: &mut CommandEncoder,
-        view: &TextureView,
-        meshes: &[&Mesh],
-        mesh_buffer: &DynamicBufferMap<MeshInfo, u32>,
-        camera: &Camera,
-        outline_width: u8,
-        outline_color: [u8; 3],
-    ) {
-        let vertex_inputs = meshes.iter().map(|m| m.vertex_inputs.as_ref().unwrap());
-        let outline_value = (outline_color[0] as u32) << 24
-            | (outline_color[1] as u32) << 16
-            | (outline_color[2] as u32) << 8
-            | outline_width as u32;
-
-        self.render_pass(
-            encoder,
-            view,
-            vertex_inputs,
-            mesh_buffer,
-            &camera.bind_group,
-            outline_value,
+            &vertex_inputs,
+            uploaded.as_ref(),
+            mask_constants.len(),
+            outline_constants,
         );
     }
 }
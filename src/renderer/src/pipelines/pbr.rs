@@ -1,24 +1,172 @@
+use std::num::NonZeroU32;
+
 use bytemuck::{Pod, Zeroable};
+use glam::Vec4;
+use log::warn;
 use wgpu::{
-    BindGroup, BindGroupLayoutDescriptor, Color, CommandEncoder, DepthStencilState, Device, include_wgsl,
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Color, CommandEncoder, DepthStencilState, Device, Features,
     PipelineLayout, RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
 };
 use wgpu::SamplerBindingType::Filtering;
 
 use lib::buffer_array::{DynamicBufferArray, DynamicBufferMap};
-use lib::managers::MaterialManager;
+use lib::managers::{MaterialManager, TextureManager};
+use lib::mesh_pool::MeshPool;
 use lib::Material;
 use lib::scene::mesh::Mesh;
-use lib::scene::VertexInputs;
-use lib::shader_types::{LightInfo, MaterialInfo, MeshInfo, PbrVertex, Vertex};
+use lib::scene::{Scene, VertexInputs};
+use lib::shader_types::{LightInfo, MeshInfo, PbrVertex, Vertex};
 use lib::texture::Texture;
+use systems::io::gltf_loader::GltfLayouts;
 
-use crate::camera::Camera;
+use crate::camera::{Camera, DepthMode, CAMERA_SHADER};
+use crate::pipelines::draw_constants::DrawConstants;
+use crate::pipelines::shadows::ShadowMaps;
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
 struct PushConstants {
     mesh_index: u32,
+    layer: u32,
+    padding: [u32; 2],
+    // fragments on the negative side of the plane get discarded, a zero plane disables clipping
+    clip_plane: [f32; 4],
+}
+
+/**
+Restricts which lighting contributions the PBR pass outputs. Everything but `Full` is meant for offscreen targets
+(e.g. for baking lightmaps) or as a debug view.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderLayer {
+    #[default]
+    Full,
+    /// Only the emission of materials
+    Emissive,
+    /// Only the direct contribution of the scene's lights, without ambient and emissive terms
+    DirectLighting,
+}
+
+impl RenderLayer {
+    fn shader_value(self) -> u32 {
+        match self {
+            RenderLayer::Full => 0,
+            RenderLayer::Emissive => 1,
+            RenderLayer::DirectLighting => 2,
+        }
+    }
+}
+
+/**
+Counters of a PBR pass, to check how well the draws were sorted and how much was drawn.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub draw_calls: u32,
+    /// How often the material textures were rebound. Zero with bindless textures, where they are bound once.
+    pub bind_group_switches: u32,
+    pub triangles: u64,
+}
+
+/**
+An offscreen color target (with matching depth buffer) that a single render layer can be rendered into.
+ */
+pub struct RenderLayerTarget {
+    pub color: Texture,
+    depth: Texture,
+}
+
+impl RenderLayerTarget {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        Self {
+            color: Texture::create_render_target(device, width, height, Self::FORMAT, "Render Layer Target"),
+            depth: Texture::create_depth_texture(device, width, height, "Render Layer Depth Texture"),
+        }
+    }
+}
+
+// the attachments and pipeline variant a PBR render pass draws with
+struct PassTarget<'a> {
+    pipeline: &'a RenderPipeline,
+    color: &'a TextureView,
+    depth: &'a TextureView,
+    load: wgpu::LoadOp<Color>,
+    // `Load` if the depth pre-pass filled the depth buffer
+    depth_load: wgpu::LoadOp<f32>,
+    layer: RenderLayer,
+    clip_plane: Vec4,
+}
+
+/**
+The meshes a PBR pass draws and everything it draws them with: the pool holding their vertices, their mesh infos and
+materials, the lights and the camera. These are usually the active scene's, see `scene`, but e.g. the material
+preview draws from buffers of its own.
+ */
+#[derive(Clone, Copy)]
+pub struct DrawInputs<'a> {
+    pub meshes: &'a [&'a Mesh],
+    pub mesh_pool: &'a MeshPool,
+    pub material_manager: &'a MaterialManager,
+    pub mesh_buffer: &'a DynamicBufferMap<MeshInfo, u32>,
+    pub light_buffer: &'a DynamicBufferArray<LightInfo>,
+    /// the view the meshes are drawn from, replaced e.g. for each eye of a stereo pair
+    pub camera_bind_group: &'a BindGroup,
+}
+
+impl<'a> DrawInputs<'a> {
+    /**
+    Inputs drawing `meshes` of `scene` as seen by `camera`.
+     */
+    pub fn scene(
+        scene: &'a Scene,
+        meshes: &'a [&'a Mesh],
+        material_manager: &'a MaterialManager,
+        camera: &'a Camera,
+    ) -> Self {
+        Self {
+            meshes,
+            mesh_pool: &scene.mesh_pool,
+            material_manager,
+            mesh_buffer: &scene.mesh_buffer,
+            light_buffer: &scene.light_buffer,
+            camera_bind_group: &camera.bind_group,
+        }
+    }
+}
+
+/**
+How `PBRPipeline::render_meshes` draws into the view.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ViewPass {
+    /// `None` draws the meshes on top of the view's contents, e.g. a sky
+    pub clear_color: Option<Color>,
+    /// usually `RenderLayer::Full`, other layers can be used as debug views
+    pub layer: RenderLayer,
+    /// set if the depth pre-pass just rendered the same meshes into the depth texture
+    pub depth_prepass: bool,
+}
+
+/**
+How `PBRPipeline::render_clipped` draws into its offscreen target.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct ClippedPass {
+    pub clear_color: Color,
+    /// plane `(n, d)` in world space, fragments with `dot(n, p) + d < 0` are discarded; a zero plane clips nothing
+    pub clip_plane: Vec4,
+    /// set if the camera's view is mirrored, as is the case for planar reflections
+    pub mirrored: bool,
+}
+
+// all textures bound at once as arrays, which materials index by the textures' slots
+struct BindlessTextures {
+    // number of elements of the texture and sampler arrays
+    capacity: u32,
+    bind_group_layout: BindGroupLayout,
+    bind_group: Option<BindGroup>,
 }
 
 /**
@@ -27,18 +175,95 @@ Pipeline for physically-based rendering
 pub struct PBRPipeline {
     shader: ShaderModule,
     pipeline: Option<RenderPipeline>,
+    // variant of `pipeline` for depth buffers filled by the depth pre-pass, which only tests the depth
+    prepassed_pipeline: Option<RenderPipeline>,
+    layer_pipeline: Option<RenderPipeline>,
+    // layer pipeline for geometry that is mirrored by the camera, which flips the winding order
+    mirrored_layer_pipeline: Option<RenderPipeline>,
     pub pipeline_layout: PipelineLayout,
+    /// layout of the per-material texture bind groups, which are only bound if bindless textures are unsupported
     pub tex_bind_group_layout: wgpu::BindGroupLayout,
+    bindless: Option<BindlessTextures>,
     pub(crate) mat_bind_group_layout: wgpu::BindGroupLayout,
     pub(crate) mesh_bind_group_layout: wgpu::BindGroupLayout,
     pub light_bind_group_layout: wgpu::BindGroupLayout,
     pub depth_texture: Texture,
+    /// the shadow maps of the lights, which the shadow pass renders before the PBR pass samples them
+    pub shadow_maps: ShadowMaps,
+    // the depth mode the pipelines were created for, which decides what depth buffers are cleared to
+    depth_mode: DepthMode,
+    draw_constants: DrawConstants<PushConstants>,
 }
 
 impl PBRPipeline {
+    /// device features required for bindless textures; without them, each material's textures are bound per draw
+    pub const BINDLESS_FEATURES: Features = Features::TEXTURE_BINDING_ARRAY
+        .union(Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+    /// upper bound for the size of the bindless texture arrays, the device limits may lower it further
+    pub const MAX_BINDLESS_TEXTURES: u32 = 4096;
+    /// number of bind groups of the pipeline layout, which is the most of all pipelines
+    pub const BIND_GROUPS: u32 = 6;
+
     // Creates all necessary bind groups and layouts for the pipeline
     pub fn new(device: &Device, config: &SurfaceConfiguration, camera: &Camera) -> Self {
-        let shader = device.create_shader_module(include_wgsl!("../shaders/pbr.wgsl"));
+        let bindless = device.features().contains(Self::BINDLESS_FEATURES).then(|| {
+            let limits = device.limits();
+            let capacity = Self::MAX_BINDLESS_TEXTURES
+                .min(limits.max_sampled_textures_per_shader_stage)
+                .min(limits.max_samplers_per_shader_stage);
+            let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("PBR Bindless Texture Bindgroup Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: NonZeroU32::new(capacity),
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(Filtering),
+                        count: NonZeroU32::new(capacity),
+                    },
+                ],
+            });
+            BindlessTextures {
+                capacity,
+                bind_group_layout,
+                bind_group: None,
+            }
+        });
+
+        // the texture access functions of pbr.wgsl are defined by one of two variants, depending on bindless support
+        let source = match bindless {
+            Some(BindlessTextures { capacity, .. }) => format!(
+                "{}\n{}\nconst TEXTURE_CAPACITY = {}u;\n{}",
+                CAMERA_SHADER,
+                include_str!("../shaders/pbr.wgsl"),
+                capacity,
+                include_str!("../shaders/pbr_bindless_textures.wgsl"),
+            ),
+            None => format!(
+                "{}\n{}\n{}",
+                CAMERA_SHADER,
+                include_str!("../shaders/pbr.wgsl"),
+                include_str!("../shaders/pbr_bound_textures.wgsl"),
+            ),
+        };
+        // shares the group of the shadow maps, as the pipeline already uses as many groups as many devices offer
+        let draw_constants = DrawConstants::sharing_group(
+            device,
+            "PBR Draw Constants Bindgroup Layout",
+            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            5,
+            &ShadowMaps::layout_entries(),
+        );
+        let shader = draw_constants.create_shader(device, "PBR Shader", &source);
         let depth_texture = Texture::create_depth_texture(device, config.width, config.height, "depth_texture");
 
         let tex_bind_group_layout = {
@@ -110,41 +335,159 @@ impl PBRPipeline {
             }],
         });
 
+        let shadow_maps = ShadowMaps::new(device);
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("PBR Pipeline Layout"),
-            bind_group_layouts: &[
-                &tex_bind_group_layout,
+            bind_group_layouts: &draw_constants.bind_group_layouts(&[
+                bindless
+                    .as_ref()
+                    .map_or(&tex_bind_group_layout, |bindless| &bindless.bind_group_layout),
                 &mat_bind_group_layout,
                 &mesh_bind_group_layout,
                 &camera.bind_group_layout,
                 &light_bind_group_layout,
-            ],
-            push_constant_ranges: &[wgpu::PushConstantRange {
-                stages: wgpu::ShaderStages::VERTEX,
-                range: 0..std::mem::size_of::<PushConstants>() as u32,
-            }],
+                &shadow_maps.bind_group_layout,
+            ]),
+            push_constant_ranges: &draw_constants.push_constant_ranges(),
         });
 
         Self {
             shader,
             pipeline: None,
+            prepassed_pipeline: None,
+            layer_pipeline: None,
+            mirrored_layer_pipeline: None,
             pipeline_layout,
             tex_bind_group_layout,
+            bindless,
             mat_bind_group_layout,
             mesh_bind_group_layout,
             light_bind_group_layout,
             depth_texture,
+            shadow_maps,
+            depth_mode: DepthMode::default(),
+            draw_constants,
         }
     }
 
+    /**
+    Whether all textures are bound at once, instead of binding each material's textures before its draws.
+     */
+    pub fn is_bindless(&self) -> bool {
+        self.bindless.is_some()
+    }
+
+    /**
+    The layouts that glTF files are loaded with, for materials and meshes that this pipeline can draw.
+     */
+    pub fn gltf_layouts(&self) -> GltfLayouts<'_> {
+        GltfLayouts {
+            texture: &self.tex_bind_group_layout,
+            material: &self.mat_bind_group_layout,
+            mesh: &self.mesh_bind_group_layout,
+            light: &self.light_bind_group_layout,
+        }
+    }
+
+    /**
+    Rebuilds the bindless texture arrays from the textures' slots. Needs to be called whenever textures were added,
+    does nothing if bindless textures are unsupported.
+     */
+    pub fn update_textures(&mut self, device: &Device, textures: &TextureManager) {
+        let Some(bindless) = self.bindless.as_mut() else {
+            return;
+        };
+        let capacity = bindless.capacity as usize;
+        let mut slots = textures.iter_slots();
+        let Some(first) = slots.next() else {
+            return;
+        };
+        let count = textures.iter_slots().count();
+        if count > capacity {
+            warn!(
+                "{} textures exceed the bindless texture capacity of {}, the remaining ones render as the first \
+                 texture",
+                count, capacity
+            );
+        }
+        // unused elements are filled with the first texture, as all elements of the arrays need to be bound
+        let textures = std::iter::once(first)
+            .chain(slots)
+            .chain(std::iter::repeat(first))
+            .take(capacity)
+            .collect::<Vec<_>>();
+        let views = textures.iter().map(|texture| &texture.view).collect::<Vec<_>>();
+        let samplers = textures.iter().map(|texture| &texture.sampler).collect::<Vec<_>>();
+        bindless.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("PBR Bindless Texture Bind Group"),
+            layout: &bindless.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureViewArray(&views),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::SamplerArray(&samplers),
+                },
+            ],
+        }));
+    }
+
     pub(crate) fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
         self.depth_texture = Texture::create_depth_texture(device, config.width, config.height, "depth_texture");
     }
 
-    // (re-)creates the pipeline
-    pub(crate) fn create_pipeline(&mut self, device: &Device) {
-        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("PBR Pipeline"),
+    // (re-)creates the pipelines, the on-screen one for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat, depth_mode: DepthMode) {
+        self.depth_mode = depth_mode;
+        self.pipeline = Some(self.build_pipeline(
+            device,
+            "PBR Pipeline",
+            "fs_main",
+            format,
+            wgpu::FrontFace::Ccw,
+            false,
+        ));
+        self.prepassed_pipeline = Some(self.build_pipeline(
+            device,
+            "PBR Pre-passed Pipeline",
+            "fs_main",
+            format,
+            wgpu::FrontFace::Ccw,
+            true,
+        ));
+        self.layer_pipeline = Some(self.build_pipeline(
+            device,
+            "PBR Layer Pipeline",
+            "fs_layer",
+            RenderLayerTarget::FORMAT,
+            wgpu::FrontFace::Ccw,
+            false,
+        ));
+        self.mirrored_layer_pipeline = Some(self.build_pipeline(
+            device,
+            "PBR Mirrored Layer Pipeline",
+            "fs_layer",
+            RenderLayerTarget::FORMAT,
+            wgpu::FrontFace::Cw,
+            false,
+        ));
+    }
+
+    fn build_pipeline(
+        &self,
+        device: &Device,
+        label: &str,
+        fragment_entry_point: &str,
+        format: wgpu::TextureFormat,
+        front_face: wgpu::FrontFace,
+        // whether the depth buffer already holds the depth of the drawn meshes, see `DepthPrepassPipeline`
+        prepassed: bool,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
             layout: Some(&self.pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &self.shader,
@@ -153,9 +496,9 @@ impl PBRPipeline {
             },
             fragment: Some(wgpu::FragmentState {
                 module: &self.shader,
-                entry_point: "fs_main",
+                entry_point: fragment_entry_point,
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                    format,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -163,7 +506,7 @@ impl PBRPipeline {
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
+                front_face,
                 cull_mode: Some(wgpu::Face::Back),
                 // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
                 polygon_mode: wgpu::PolygonMode::Fill,
@@ -174,8 +517,12 @@ impl PBRPipeline {
             },
             depth_stencil: Some(DepthStencilState {
                 format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_write_enabled: !prepassed,
+                depth_compare: if prepassed {
+                    self.depth_mode.compare_equal()
+                } else {
+                    self.depth_mode.compare()
+                },
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
@@ -185,104 +532,188 @@ impl PBRPipeline {
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-        }));
+        })
     }
 
-    fn render_pass<'a>(
+    fn render_pass(
         &self,
+        device: &Device,
         encoder: &mut CommandEncoder,
-        vertex_inputs: impl Iterator<Item = &'a VertexInputs>,
-        view: &TextureView,
-        textures_bind_groups: &[&BindGroup],
-        material_info_bind_group: &BindGroup,
-        mesh_info_map: &DynamicBufferMap<MeshInfo, u32>,
-        camera_bind_group: &BindGroup,
-        light_bind_group: &BindGroup,
-    ) {
-        let vertex_inputs = vertex_inputs.collect::<Vec<_>>();
+        target: PassTarget,
+        inputs: &DrawInputs,
+    ) -> DrawStats {
+        // bindless textures are bound once for all meshes, otherwise each material's textures are bound per draw
+        let textures_bind_group = self.bindless.as_ref().map(|bindless| {
+            bindless
+                .bind_group
+                .as_ref()
+                .expect("Bindless textures must be updated before rendering")
+        });
+        let meshes = inputs
+            .meshes
+            .iter()
+            .map(|mesh| {
+                let material_textures_bind_group = match textures_bind_group {
+                    Some(_) => None,
+                    None => match inputs.material_manager.get_material(mesh.material) {
+                        Material::Pbr(ref mat) => Some(
+                            mat.texture_bind_group
+                                .as_ref()
+                                .expect("PBR material must have a texture bind group"),
+                        ),
+                    },
+                };
+                (mesh.vertex_inputs.as_ref().unwrap(), material_textures_bind_group)
+            })
+            .collect::<Vec<_>>();
+        let constants = meshes
+            .iter()
+            .map(|(VertexInputs { mesh_id, .. }, _)| PushConstants {
+                mesh_index: *inputs.mesh_buffer.get(mesh_id).expect("Mesh not found in mesh_info_map") as u32,
+                layer: target.layer.shader_value(),
+                padding: [0; 2],
+                clip_plane: target.clip_plane.to_array(),
+            })
+            .collect::<Vec<_>>();
+        let uploaded = self
+            .draw_constants
+            .upload_shared(device, &constants, &self.shadow_maps.entries());
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("PBR Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view,
+                view: target.color,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(Color::BLACK),
+                    load: target.load,
                     store: wgpu::StoreOp::Store,
                 },
             })],
             depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.view,
+                view: target.depth,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Discard,
+                    load: target.depth_load,
+                    // kept for passes that draw on top of the meshes, e.g. water
+                    store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
             }),
             timestamp_writes: None,
             occlusion_query_set: None,
         });
-        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
-
-        render_pass.set_bind_group(1, material_info_bind_group, &[]);
-        render_pass.set_bind_group(2, mesh_info_map.bind_group(), &[]);
-        render_pass.set_bind_group(3, camera_bind_group, &[]);
-        render_pass.set_bind_group(4, light_bind_group, &[]);
-
-        for (
-            i,
-            VertexInputs {
-                mesh_id,
-                vertex_buffer,
-                index_buffer,
-            },
-        ) in vertex_inputs.iter().enumerate()
-        {
-            let mesh_index = mesh_info_map.get(mesh_id).expect("Mesh not found in mesh_info_map");
-            let push_constants = PushConstants {
-                mesh_index: *mesh_index as u32,
-            };
-            render_pass.set_push_constants(wgpu::ShaderStages::VERTEX, 0, bytemuck::bytes_of(&push_constants));
-            render_pass.set_bind_group(0, textures_bind_groups[i], &[]);
-
-            render_pass.set_vertex_buffer(0, vertex_buffer.buffer.slice(..));
-            render_pass.set_index_buffer(index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint16);
-
-            render_pass.draw_indexed(0..index_buffer.count, 0, 0..1);
+        render_pass.set_pipeline(target.pipeline);
+
+        if let Some(textures_bind_group) = textures_bind_group {
+            render_pass.set_bind_group(0, textures_bind_group, &[]);
+        }
+        render_pass.set_bind_group(1, &inputs.material_manager.buffer.bind_group, &[]);
+        render_pass.set_bind_group(2, inputs.mesh_buffer.bind_group(), &[]);
+        render_pass.set_bind_group(3, inputs.camera_bind_group, &[]);
+        render_pass.set_bind_group(4, &inputs.light_buffer.bind_group, &[]);
+        if uploaded.is_none() {
+            // otherwise the draw constants' bind group binds the shadow maps
+            render_pass.set_bind_group(5, &self.shadow_maps.bind_group, &[]);
+        }
+        inputs.mesh_pool.bind(&mut render_pass);
+
+        let mut stats = DrawStats::default();
+        let mut bound: Option<&BindGroup> = None;
+        for (draw, (vertex_inputs, material_textures_bind_group)) in meshes.into_iter().enumerate() {
+            self.draw_constants
+                .set(&mut render_pass, uploaded.as_ref(), draw, &constants[draw]);
+            if let Some(material_textures_bind_group) = material_textures_bind_group {
+                // consecutive meshes of the same material share the bind group, it only has to be set once
+                if !bound.is_some_and(|bound| std::ptr::eq(bound, material_textures_bind_group)) {
+                    render_pass.set_bind_group(0, material_textures_bind_group, &[]);
+                    bound = Some(material_textures_bind_group);
+                    stats.bind_group_switches += 1;
+                }
+            }
+
+            render_pass.draw_indexed(vertex_inputs.indices(), vertex_inputs.base_vertex as i32, 0..1);
+            stats.draw_calls += 1;
+            stats.triangles += vertex_inputs.index_count as u64 / 3;
         }
+        stats
     }
 
+    /**
+    Renders the meshes to the (tonemapped) surface view. The meshes are drawn in the given order, so sort them by
+    material to save bind group switches.
+     */
     pub fn render_meshes(
         &self,
+        device: &Device,
         encoder: &mut CommandEncoder,
         view: &TextureView,
-        meshes: &[&Mesh],
-        material_manager: &MaterialManager,
-        mat_buffer: &DynamicBufferArray<MaterialInfo>,
-        mesh_buffer: &DynamicBufferMap<MeshInfo, u32>,
-        light_buffer: &DynamicBufferArray<LightInfo>,
-        camera: &Camera,
+        pass: ViewPass,
+        inputs: &DrawInputs,
+    ) -> DrawStats {
+        let (pipeline, depth_load) = if pass.depth_prepass {
+            (&self.prepassed_pipeline, wgpu::LoadOp::Load)
+        } else {
+            (&self.pipeline, wgpu::LoadOp::Clear(self.depth_mode.far_depth()))
+        };
+        let target = PassTarget {
+            pipeline: pipeline.as_ref().unwrap(),
+            color: view,
+            depth: &self.depth_texture.view,
+            load: pass.clear_color.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+            depth_load,
+            layer: pass.layer,
+            clip_plane: Vec4::ZERO,
+        };
+        self.render_pass(device, encoder, target, inputs)
+    }
+
+    /**
+    Renders the untonemapped radiance of a single layer into an offscreen HDR target, e.g. for light baking.
+     */
+    pub fn render_layer(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        target: &RenderLayerTarget,
+        layer: RenderLayer,
+        inputs: &DrawInputs,
     ) {
-        let vertex_inputs = meshes.iter().map(|m| m.vertex_inputs.as_ref().unwrap());
-        let textures_bind_groups = meshes
-            .iter()
-            .map(|m| match material_manager.get_material(m.material) {
-                Material::Pbr(ref mat) => mat
-                    .texture_bind_group
-                    .as_ref()
-                    .expect("PBR material must have a texture bind group"),
-                _ => panic!("Unsupported material type for PBR pipeline"),
-            })
-            .collect::<Vec<_>>();
+        let target = PassTarget {
+            pipeline: self.layer_pipeline.as_ref().unwrap(),
+            color: &target.color.view,
+            depth: &target.depth.view,
+            load: wgpu::LoadOp::Clear(Color::BLACK),
+            depth_load: wgpu::LoadOp::Clear(self.depth_mode.far_depth()),
+            layer,
+            clip_plane: Vec4::ZERO,
+        };
+        self.render_pass(device, encoder, target, inputs);
+    }
 
-        self.render_pass(
-            encoder,
-            vertex_inputs,
-            view,
-            &textures_bind_groups,
-            &mat_buffer.bind_group,
-            mesh_buffer,
-            &camera.bind_group,
-            &light_buffer.bind_group,
-        )
+    /**
+    Renders the untonemapped radiance of the meshes on the positive side of the pass's clip plane into an offscreen
+    HDR target. The inputs' camera bind group usually differs from the main camera's, e.g. for reflections.
+     */
+    pub fn render_clipped(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        target: &RenderLayerTarget,
+        pass: ClippedPass,
+        inputs: &DrawInputs,
+    ) {
+        let pipeline = if pass.mirrored {
+            &self.mirrored_layer_pipeline
+        } else {
+            &self.layer_pipeline
+        };
+        let target = PassTarget {
+            pipeline: pipeline.as_ref().unwrap(),
+            color: &target.color.view,
+            depth: &target.depth.view,
+            load: wgpu::LoadOp::Clear(pass.clear_color),
+            depth_load: wgpu::LoadOp::Clear(self.depth_mode.far_depth()),
+            layer: RenderLayer::Full,
+            clip_plane: pass.clip_plane,
+        };
+        self.render_pass(device, encoder, target, inputs);
     }
 }
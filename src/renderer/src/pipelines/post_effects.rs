@@ -0,0 +1,146 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    include_wgsl, BindGroup, BindGroupLayoutDescriptor, Buffer, CommandEncoder, Device, PipelineLayout, RenderPipeline,
+    ShaderModule, TextureView,
+};
+
+use lib::scene::post_effects::PostEffects;
+
+use crate::pipelines::post_process::{fullscreen_pass, PostProcess, PostProcessChain, PostProcessContext};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct PostEffectsUniform {
+    ca_strength: f32,
+    vignette_intensity: f32,
+    vignette_radius: f32,
+    vignette_softness: f32,
+    grain_intensity: f32,
+    grain_size: f32,
+    time: f32,
+    padding: f32,
+}
+
+impl PostEffectsUniform {
+    // disabled effects are passed with zero strength, so that they don't need their own shader variants
+    fn new(effects: &PostEffects, time: f32) -> Self {
+        let PostEffects {
+            chromatic_aberration,
+            vignette,
+            grain,
+        } = effects;
+        Self {
+            ca_strength: if chromatic_aberration.enabled {
+                chromatic_aberration.strength
+            } else {
+                0.0
+            },
+            vignette_intensity: if vignette.enabled { vignette.intensity } else { 0.0 },
+            vignette_radius: vignette.radius,
+            vignette_softness: vignette.softness.max(0.001),
+            grain_intensity: if grain.enabled { grain.intensity } else { 0.0 },
+            grain_size: grain.size,
+            time,
+            padding: 0.0,
+        }
+    }
+}
+
+/**
+Applies the active scene's `PostEffects`, enabled while any of them is.
+ */
+pub struct PostEffectsPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    bind_group: BindGroup,
+    buffer: Buffer,
+}
+
+impl PostEffectsPipeline {
+    pub fn new(device: &Device, chain: &PostProcessChain) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("../shaders/post_effects.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Post Effects Bindgroup Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Effects Pipeline Layout"),
+            bind_group_layouts: &[chain.input_bind_group_layout(), &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Effects Buffer"),
+            contents: bytemuck::cast_slice(&[PostEffectsUniform::new(&PostEffects::default(), 0.0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post Effects Bindgroup"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            bind_group,
+            buffer,
+        }
+    }
+
+    // (re-)creates the pipeline
+    pub(crate) fn create_pipeline(&mut self, device: &Device, chain: &PostProcessChain) {
+        self.pipeline = Some(chain.create_pipeline(
+            device,
+            "Post Effects Pipeline",
+            &self.pipeline_layout,
+            &self.shader,
+            "fs_main",
+        ));
+    }
+}
+
+impl PostProcess for PostEffectsPipeline {
+    fn is_enabled(&self, context: &PostProcessContext) -> bool {
+        context.scene.is_some_and(|scene| scene.post_effects.is_active())
+    }
+
+    fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        context: &PostProcessContext,
+        input: &BindGroup,
+        output: &TextureView,
+    ) {
+        let Some(scene) = context.scene else {
+            return;
+        };
+        let uniform = PostEffectsUniform::new(&scene.post_effects, context.meta.time.elapsed());
+        context
+            .queue
+            .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+        fullscreen_pass(
+            encoder,
+            "Post Effects Render Pass",
+            output,
+            self.pipeline.as_ref().unwrap(),
+            &[input, &self.bind_group],
+        );
+    }
+}
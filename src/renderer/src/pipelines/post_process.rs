@@ -0,0 +1,261 @@
+use wgpu::{
+    include_wgsl, BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, CommandEncoder, Device, PipelineLayout, Queue,
+    RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
+};
+
+use lib::scene::Scene;
+use lib::texture::Texture;
+
+use crate::camera::Camera;
+use crate::Meta;
+
+/**
+Everything a post-processing pass may depend on in the current frame.
+ */
+pub struct PostProcessContext<'a> {
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+    pub camera: &'a Camera,
+    pub meta: &'a Meta,
+    /// the active scene, if there is one
+    pub scene: Option<&'a Scene>,
+}
+
+/**
+A fullscreen pass in the post-processing chain. Passes operate on the tone mapped image: each one samples the output
+of the previous pass (or the rendered scene) from bind group 0 and writes into the next pass' input (or the surface).
+
+Pipelines of a pass are created with `PostProcessChain::create_pipeline`, which provides the shared fullscreen vertex
+shader, and a pipeline layout that starts with `PostProcessChain::input_bind_group_layout`.
+ */
+pub trait PostProcess {
+    /**
+    Whether the pass runs this frame. Disabled passes are skipped entirely, so they cost nothing.
+     */
+    fn is_enabled(&self, _context: &PostProcessContext) -> bool {
+        true
+    }
+
+    /**
+    Called when the surface was resized or its format changed, to recreate size- and format-dependent resources,
+    e.g. pipelines created by `PostProcessChain::create_pipeline`.
+     */
+    fn resize(&mut self, _device: &Device, _config: &SurfaceConfiguration) {}
+
+    /**
+    Renders the pass into `output`, e.g. with `fullscreen_pass`. `input` is the bind group of the previous output and
+    has to be bound to group 0.
+     */
+    fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        context: &PostProcessContext,
+        input: &BindGroup,
+        output: &TextureView,
+    );
+}
+
+/**
+Runs post-processing passes in order, ping-ponging between two offscreen targets and writing the last pass into the
+surface. The scene has to be rendered into `scene_target` whenever a pass is enabled.
+ */
+pub struct PostProcessChain {
+    fullscreen_shader: ShaderModule,
+    input_bind_group_layout: BindGroupLayout,
+    // the surface's format, which the targets and all passes render in
+    format: wgpu::TextureFormat,
+    targets: [Texture; 2],
+    input_bind_groups: [BindGroup; 2],
+    // passes added by users of the engine, they run after the built-in ones
+    custom: Vec<Box<dyn PostProcess>>,
+}
+
+impl PostProcessChain {
+    pub fn new(device: &Device, config: &SurfaceConfiguration) -> Self {
+        let fullscreen_shader = device.create_shader_module(include_wgsl!("../shaders/fullscreen.wgsl"));
+        let input_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Post Process Input Bindgroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let (targets, input_bind_groups) = Self::create_targets(device, config, &input_bind_group_layout);
+        Self {
+            fullscreen_shader,
+            input_bind_group_layout,
+            format: config.format,
+            targets,
+            input_bind_groups,
+            custom: vec![],
+        }
+    }
+
+    fn create_targets(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        layout: &BindGroupLayout,
+    ) -> ([Texture; 2], [BindGroup; 2]) {
+        let targets = ["Post Process Target A", "Post Process Target B"]
+            .map(|label| Texture::create_render_target(device, config.width, config.height, config.format, label));
+        let input_bind_groups = [&targets[0], &targets[1]].map(|target| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Process Input Bindgroup"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&target.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&target.sampler),
+                    },
+                ],
+            })
+        });
+        (targets, input_bind_groups)
+    }
+
+    /** The format of the targets, which passes created by `create_pipeline` render in. */
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn input_bind_group_layout(&self) -> &BindGroupLayout {
+        &self.input_bind_group_layout
+    }
+
+    /** The target the scene is rendered into while any pass is enabled. */
+    pub fn scene_target(&self) -> &TextureView {
+        &self.targets[0].view
+    }
+
+    /**
+    Appends a pass that runs after all built-in passes and the custom passes added before it.
+     */
+    pub fn push(&mut self, pass: Box<dyn PostProcess>) {
+        self.custom.push(pass);
+    }
+
+    pub(crate) fn custom_passes(&self) -> impl Iterator<Item = &dyn PostProcess> {
+        self.custom.iter().map(|pass| pass.as_ref())
+    }
+
+    pub(crate) fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        self.format = config.format;
+        (self.targets, self.input_bind_groups) = Self::create_targets(device, config, &self.input_bind_group_layout);
+        for pass in self.custom.iter_mut() {
+            pass.resize(device, config);
+        }
+    }
+
+    /**
+    Creates a pipeline for a pass that runs the shared fullscreen vertex shader and `fragment_entry` in `fragment`.
+     */
+    pub fn create_pipeline(
+        &self,
+        device: &Device,
+        label: &str,
+        layout: &PipelineLayout,
+        fragment: &ShaderModule,
+        fragment_entry: &str,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &self.fullscreen_shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: fragment,
+                entry_point: fragment_entry,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /**
+    Runs `passes` (already filtered to the enabled ones) on the contents of `scene_target` and writes the result into
+    `surface`. Does nothing if there are no passes.
+     */
+    pub(crate) fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        context: &PostProcessContext,
+        passes: &[&dyn PostProcess],
+        surface: &TextureView,
+    ) {
+        for (i, pass) in passes.iter().enumerate() {
+            let output = if i == passes.len() - 1 {
+                surface
+            } else {
+                &self.targets[(i + 1) % 2].view
+            };
+            pass.render(encoder, context, &self.input_bind_groups[i % 2], output);
+        }
+    }
+}
+
+/**
+Records a render pass that draws `pipeline` over all of `output`, with `bind_groups` bound in order starting at group 0.
+ */
+pub fn fullscreen_pass(
+    encoder: &mut CommandEncoder,
+    label: &str,
+    output: &TextureView,
+    pipeline: &RenderPipeline,
+    bind_groups: &[&BindGroup],
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    for (i, bind_group) in bind_groups.iter().enumerate() {
+        render_pass.set_bind_group(i as u32, bind_group, &[]);
+    }
+    render_pass.draw(0..3, 0..1);
+}
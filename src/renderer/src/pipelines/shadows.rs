@@ -0,0 +1,433 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, Buffer,
+    CommandEncoder, DepthStencilState, Device, Queue, RenderPassDepthStencilAttachment, RenderPipeline, Sampler,
+    TextureView,
+};
+
+use lib::scene::lighting::{Lighting, ShadowFilter};
+use lib::scene::mesh::Mesh;
+use lib::scene::Scene;
+use lib::shader_types::{PbrVertex, Vertex};
+use lib::texture::Texture;
+
+use crate::pipelines::draw_constants::DrawConstants;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct PushConstants {
+    mesh_index: u32,
+    // index of the rendered face in the face matrices, which is also its layer in the shadow map texture
+    face: u32,
+    padding: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct LightingUniform {
+    // ambient light on surfaces facing up and down, including the intensity
+    ambient_sky: [f32; 3],
+    // 0 for hard shadows, 1 for PCF, 2 for PCSS
+    shadow_filter: u32,
+    ambient_ground: [f32; 3],
+    kernel_size: u32,
+    light_size: f32,
+    near: f32,
+    padding: [f32; 2],
+    // far plane of the faces of each slot
+    far: [f32; ShadowMaps::MAX_LIGHTS as usize],
+}
+
+impl LightingUniform {
+    // without shadow casting lights, which `ShadowMaps::update` adds the far planes of
+    fn new(lighting: &Lighting) -> Self {
+        let (sky, ground) = lighting.ambient.sky_and_ground();
+        let shadow_filter = lighting.shadow_filter;
+        Self {
+            ambient_sky: (sky * lighting.ambient_intensity).to_array(),
+            shadow_filter: match shadow_filter {
+                ShadowFilter::Hard => 0,
+                ShadowFilter::Pcf { .. } => 1,
+                ShadowFilter::Pcss { .. } => 2,
+            },
+            ambient_ground: (ground * lighting.ambient_intensity).to_array(),
+            kernel_size: shadow_filter.kernel_size(),
+            light_size: match shadow_filter {
+                ShadowFilter::Pcss { light_size, .. } => light_size.max(0.0),
+                _ => 0.0,
+            },
+            near: ShadowMaps::NEAR,
+            padding: [0.0; 2],
+            far: [0.0; ShadowMaps::MAX_LIGHTS as usize],
+        }
+    }
+}
+
+// view direction and up vector of the six faces of a light's shadow map, in the order pbr.wgsl picks them in
+const FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::Z),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y),
+];
+
+/**
+The depth maps of the lights that cast shadows, six square faces around each light that together cover all directions.
+The faces of all lights are layers of one texture, so that the PBR pass can sample any of them through a single
+binding. Lights get one of the `MAX_LIGHTS` slots from `Scene::assign_shadow_maps`.
+
+The bind group of the shadow maps also carries the scene's `Lighting`, as the PBR pass reads both per light.
+ */
+pub struct ShadowMaps {
+    // one view per face, for rendering into
+    face_views: Vec<TextureView>,
+    // the projection and view matrix of each face
+    faces: Buffer,
+    // the scene's `Lighting`, and what the shadow filters need to know about the faces
+    lighting: Buffer,
+    // binds the face matrices to the shadow pass
+    faces_bind_group_layout: BindGroupLayout,
+    faces_bind_group: BindGroup,
+    pub(crate) bind_group_layout: BindGroupLayout,
+    pub(crate) bind_group: BindGroup,
+    view: TextureView,
+    sampler: Sampler,
+}
+
+impl ShadowMaps {
+    /// number of lights that can cast shadows at the same time
+    pub const MAX_LIGHTS: u32 = 4;
+    /// width and height of each face in pixels
+    pub const SIZE: u32 = 512;
+    // distance from the light at which faces start, in world units
+    const NEAR: f32 = 1.0;
+    // distance at which faces end for lights without a range, the camera's far plane in world units
+    const DEFAULT_FAR: f32 = 10_000.0;
+
+    pub fn new(device: &Device) -> Self {
+        let layers = Self::MAX_LIGHTS * 6;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Maps"),
+            size: wgpu::Extent3d {
+                width: Self::SIZE,
+                height: Self::SIZE,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let face_views = (0..layers)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow Map Face View"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Maps View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Map Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let faces = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Map Faces Buffer"),
+            size: (layers as usize * std::mem::size_of::<Mat4>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // passes that draw before the first update, like material previews, get the default ambient
+        let lighting = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Lighting Buffer"),
+            contents: bytemuck::bytes_of(&LightingUniform::new(&Lighting::default())),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let faces_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Shadow Map Faces Bindgroup Layout"),
+            entries: &[BindGroupLayoutEntry {
+                visibility: wgpu::ShaderStages::VERTEX,
+                ..Self::layout_entries()[0]
+            }],
+        });
+        let faces_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Map Faces Bindgroup"),
+            layout: &faces_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: faces.as_entire_binding(),
+            }],
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Shadow Maps Bindgroup Layout"),
+            entries: &Self::layout_entries(),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Maps Bindgroup"),
+            layout: &bind_group_layout,
+            entries: &Self::bind_group_entries(&faces, &view, &sampler, &lighting),
+        });
+
+        Self {
+            face_views,
+            faces,
+            lighting,
+            faces_bind_group_layout,
+            faces_bind_group,
+            bind_group_layout,
+            bind_group,
+            view,
+            sampler,
+        }
+    }
+
+    /**
+    The entries of `bind_group_layout`, for layouts of groups that bind the shadow maps next to other resources.
+     */
+    pub(crate) fn layout_entries() -> [BindGroupLayoutEntry; 4] {
+        [
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Depth,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ]
+    }
+
+    /**
+    The entries of `bind_group`, see `layout_entries`.
+     */
+    pub(crate) fn entries(&self) -> [BindGroupEntry<'_>; 4] {
+        Self::bind_group_entries(&self.faces, &self.view, &self.sampler, &self.lighting)
+    }
+
+    fn bind_group_entries<'a>(
+        faces: &'a Buffer,
+        view: &'a TextureView,
+        sampler: &'a Sampler,
+        lighting: &'a Buffer,
+    ) -> [BindGroupEntry<'a>; 4] {
+        [
+            BindGroupEntry {
+                binding: 0,
+                resource: faces.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: lighting.as_entire_binding(),
+            },
+        ]
+    }
+
+    /**
+    Uploads the face matrices of the lights in `scene` that have a slot and the scene's lighting settings, and returns
+    the slots.
+     */
+    fn update(&self, queue: &Queue, scene: &Scene) -> Vec<u32> {
+        let mut lighting = LightingUniform::new(&scene.lighting);
+        let mut slots = vec![];
+        for light in scene.iter_models_deep().filter_map(|model| model.light.as_ref()) {
+            let Some(slot) = light.shadow_map.filter(|slot| *slot < Self::MAX_LIGHTS) else {
+                continue;
+            };
+            let position = light.global_transform.w_axis.truncate();
+            let far = light.range.unwrap_or(Self::DEFAULT_FAR).max(Self::NEAR * 2.0);
+            let projection = Mat4::perspective_lh(std::f32::consts::FRAC_PI_2, 1.0, Self::NEAR, far);
+            let matrices = FACES
+                .map(|(direction, up)| (projection * Mat4::look_to_lh(position, direction, up)).to_cols_array_2d());
+            queue.write_buffer(
+                &self.faces,
+                (slot as usize * 6 * std::mem::size_of::<Mat4>()) as u64,
+                bytemuck::cast_slice(&matrices),
+            );
+            lighting.far[slot as usize] = far;
+            slots.push(slot);
+        }
+        queue.write_buffer(&self.lighting, 0, bytemuck::bytes_of(&lighting));
+        slots
+    }
+}
+
+/**
+Renders the depth of meshes into the faces of the shadow maps, see `ShadowMaps`. Each shadow casting light takes six
+passes over all meshes, which is why lights only cast shadows if `PointLight::casts_shadows` is set.
+ */
+pub struct ShadowPipeline {
+    pipeline: RenderPipeline,
+    draw_constants: DrawConstants<PushConstants>,
+}
+
+impl ShadowPipeline {
+    pub fn new(device: &Device, shadow_maps: &ShadowMaps, mesh_bind_group_layout: &BindGroupLayout) -> Self {
+        let draw_constants = DrawConstants::new(
+            device,
+            "Shadow Draw Constants Bindgroup Layout",
+            wgpu::ShaderStages::VERTEX,
+            2,
+        );
+        let shader = draw_constants.create_shader(device, "Shadow Shader", include_str!("../shaders/shadows.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &draw_constants
+                .bind_group_layouts(&[mesh_bind_group_layout, &shadow_maps.faces_bind_group_layout]),
+            push_constant_ranges: &draw_constants.push_constant_ranges(),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[PbrVertex::desc()],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // the faces look at the meshes from all sides, both sides of thin geometry have to cast shadows
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                // against shadow acne, together with the normal offset in pbr.wgsl
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            draw_constants,
+        }
+    }
+
+    /**
+    Renders `meshes` into the six faces of each light in `scene` that has a shadow map slot. Does nothing if no light
+    has one.
+     */
+    pub fn render(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        shadow_maps: &ShadowMaps,
+        scene: &Scene,
+        meshes: &[&Mesh],
+    ) {
+        let slots = shadow_maps.update(queue, scene);
+        if slots.is_empty() {
+            return;
+        }
+        let faces = slots.iter().flat_map(|slot| slot * 6..slot * 6 + 6).collect::<Vec<_>>();
+        let constants = faces
+            .iter()
+            .flat_map(|&face| {
+                meshes.iter().map(move |mesh| PushConstants {
+                    mesh_index: *scene
+                        .mesh_buffer
+                        .get(&mesh.id)
+                        .expect("Mesh not found in mesh_info_map") as u32,
+                    face,
+                    padding: [0; 2],
+                })
+            })
+            .collect::<Vec<_>>();
+        let uploaded = self.draw_constants.upload(device, &constants);
+        for (i, &face) in faces.iter().enumerate() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &shadow_maps.face_views[face as usize],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, scene.mesh_buffer.bind_group(), &[]);
+            render_pass.set_bind_group(1, &shadow_maps.faces_bind_group, &[]);
+            scene.mesh_pool.bind(&mut render_pass);
+
+            for (j, mesh) in meshes.iter().enumerate() {
+                let draw = i * meshes.len() + j;
+                self.draw_constants
+                    .set(&mut render_pass, uploaded.as_ref(), draw, &constants[draw]);
+                let inputs = mesh.vertex_inputs.as_ref().unwrap();
+                render_pass.draw_indexed(inputs.indices(), inputs.base_vertex as i32, 0..1);
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayoutDescriptor, Buffer, CommandEncoder, Device, PipelineLayout, Queue, RenderPipeline,
+    ShaderModule, TextureView,
+};
+
+use lib::scene::sky::Sky;
+
+use crate::camera::Camera;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct SkyUniform {
+    sun_direction: [f32; 3],
+    turbidity: f32,
+    exposure: f32,
+    padding: [f32; 3],
+}
+
+impl From<&Sky> for SkyUniform {
+    fn from(sky: &Sky) -> Self {
+        Self {
+            sun_direction: sky.sun_direction().to_array(),
+            turbidity: sky.turbidity,
+            exposure: sky.exposure,
+            padding: [0.0; 3],
+        }
+    }
+}
+
+/**
+Pipeline that draws a procedural sky as the background, before any geometry is rendered.
+ */
+pub struct SkyPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    buffer: Buffer,
+    bind_group: BindGroup,
+    // the settings currently in the uniform buffer
+    uploaded: Option<Sky>,
+}
+
+impl SkyPipeline {
+    pub fn new(device: &Device, camera: &Camera) -> Self {
+        let shader = Camera::create_shader(device, "Sky Shader", include_str!("../shaders/sky.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Sky Bindgroup Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Buffer"),
+            contents: bytemuck::cast_slice(&[SkyUniform::from(&Sky::default())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sky Bindgroup"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            bind_group_layouts: &[&camera.bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            buffer,
+            bind_group,
+            uploaded: None,
+        }
+    }
+
+    // (re-)creates the pipeline for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+    }
+
+    /**
+    Uploads the sky settings if they changed since the last call.
+     */
+    pub fn update(&mut self, queue: &Queue, sky: &Sky) {
+        if self.uploaded.as_ref() == Some(sky) {
+            return;
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[SkyUniform::from(sky)]));
+        self.uploaded = Some(*sky);
+    }
+
+    pub fn render(&self, encoder: &mut CommandEncoder, view: &TextureView, camera: &Camera) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sky Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
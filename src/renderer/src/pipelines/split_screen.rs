@@ -0,0 +1,305 @@
+use glam::{Vec3, Vec4};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, Color, CommandEncoder, Device, PipelineLayout,
+    Queue, RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
+};
+
+use crate::camera::Camera;
+use crate::pipelines::pbr::{ClippedPass, DrawInputs, PBRPipeline, RenderLayerTarget};
+
+/**
+The viewpoint of one split-screen player. Positions are in camera space, the same as `Camera::eye`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerView {
+    pub eye: Vec3,
+    pub target: Vec3,
+    /// vertical field of view in degrees
+    pub fovy: f32,
+}
+
+impl From<&Camera> for PlayerView {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            eye: camera.eye,
+            target: camera.target,
+            fovy: camera.fovy,
+        }
+    }
+}
+
+/**
+Settings of the split-screen mode, which renders the world once per player into a partition of the screen. Hooks move
+the players by updating their views, and draw each player's HUD with `Hook::player_hud`.
+ */
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SplitScreen {
+    pub enabled: bool,
+    /// between `MIN_PLAYERS` and `MAX_PLAYERS` views, see `set_player_count`
+    pub players: Vec<PlayerView>,
+}
+
+impl SplitScreen {
+    pub const MIN_PLAYERS: usize = 2;
+    pub const MAX_PLAYERS: usize = 4;
+
+    /**
+    Adds or removes players, new ones start out with the view of `camera`.
+     */
+    pub fn set_player_count(&mut self, count: usize, camera: &Camera) {
+        let count = count.clamp(Self::MIN_PLAYERS, Self::MAX_PLAYERS);
+        self.players.resize(count, PlayerView::from(camera));
+    }
+
+    /**
+    The screen partition of each player as x, y, width and height in fractions of the screen: two players share the
+    screen side by side, three or four players get a quarter each, except for the third of three who gets the whole
+    bottom half.
+     */
+    pub fn partitions(&self) -> &'static [[f32; 4]] {
+        match self.players.len() {
+            0 | 1 => &[[0.0, 0.0, 1.0, 1.0]],
+            2 => &[[0.0, 0.0, 0.5, 1.0], [0.5, 0.0, 0.5, 1.0]],
+            3 => &[[0.0, 0.0, 0.5, 0.5], [0.5, 0.0, 0.5, 0.5], [0.0, 0.5, 1.0, 0.5]],
+            _ => &[
+                [0.0, 0.0, 0.5, 0.5],
+                [0.5, 0.0, 0.5, 0.5],
+                [0.0, 0.5, 0.5, 0.5],
+                [0.5, 0.5, 0.5, 0.5],
+            ],
+        }
+    }
+}
+
+// the offscreen target and camera of one player, sized to the player's partition
+struct PlayerTarget {
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    target: RenderLayerTarget,
+    target_bind_group: BindGroup,
+    // x, y, width and height of the partition in pixels
+    viewport: [u32; 4],
+}
+
+/**
+Split-screen rendering. Each player's view of the scene is rendered into an offscreen HDR target the size of their
+partition and then tone mapped into that partition of the screen. Like the stereo preview, the players' views only
+contain the scene's meshes: the sky, water, outlines and grid are skipped.
+ */
+pub struct SplitScreenPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    target_bind_group_layout: BindGroupLayout,
+    players: Vec<PlayerTarget>,
+    surface_size: (u32, u32),
+}
+
+impl SplitScreenPipeline {
+    pub fn new(device: &Device, config: &SurfaceConfiguration, camera: &Camera) -> Self {
+        let shader = Camera::create_shader(
+            device,
+            "Split Screen Shader",
+            include_str!("../shaders/split_screen.wgsl"),
+        );
+        let target_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Split Screen Target Bindgroup Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Split Screen Pipeline Layout"),
+            bind_group_layouts: &[&camera.bind_group_layout, &target_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            target_bind_group_layout,
+            players: vec![],
+            surface_size: (config.width, config.height),
+        }
+    }
+
+    pub(crate) fn resize(&mut self, _device: &Device, config: &SurfaceConfiguration) {
+        self.surface_size = (config.width, config.height);
+        // recreated in the next update, with the new partition sizes
+        self.players.clear();
+    }
+
+    // (re-)creates the pipeline for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Split Screen Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+    }
+
+    fn create_player(&self, device: &Device, camera: &Camera, viewport: [u32; 4]) -> PlayerTarget {
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Split Screen Player Camera"),
+            contents: bytemuck::cast_slice(&[camera.uniform()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Split Screen Player Camera"),
+            layout: &camera.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+        let target = RenderLayerTarget::new(device, viewport[2], viewport[3]);
+        let target_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Split Screen Target Bindgroup"),
+            layout: &self.target_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&target.color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&target.color.sampler),
+                },
+            ],
+        });
+        PlayerTarget {
+            camera_buffer,
+            camera_bind_group,
+            target,
+            target_bind_group,
+            viewport,
+        }
+    }
+
+    /**
+    Fits the targets to the players' partitions and uploads their cameras. Call this every frame before rendering.
+     */
+    pub fn update(&mut self, device: &Device, queue: &Queue, split_screen: &SplitScreen, camera: &Camera) {
+        let (width, height) = self.surface_size;
+        let viewports = split_screen
+            .partitions()
+            .iter()
+            .map(|[x, y, w, h]| {
+                let x0 = (x * width as f32).round() as u32;
+                let y0 = (y * height as f32).round() as u32;
+                let x1 = ((x + w) * width as f32).round() as u32;
+                let y1 = ((y + h) * height as f32).round() as u32;
+                [x0, y0, (x1 - x0).max(1), (y1 - y0).max(1)]
+            })
+            .take(split_screen.players.len())
+            .collect::<Vec<_>>();
+        if self
+            .players
+            .iter()
+            .map(|player| player.viewport)
+            .ne(viewports.iter().copied())
+        {
+            self.players = viewports
+                .iter()
+                .map(|viewport| self.create_player(device, camera, *viewport))
+                .collect();
+        }
+        for (player, view) in self.players.iter().zip(split_screen.players.iter()) {
+            let aspect = player.viewport[2] as f32 / player.viewport[3] as f32;
+            let uniform = camera.view_uniform(view.eye, view.target, view.fovy, aspect);
+            queue.write_buffer(&player.camera_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+    }
+
+    /**
+    Renders the view of every player and composites them into their partitions of `view`.
+     */
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        clear_color: Color,
+        pbr_pipeline: &PBRPipeline,
+        inputs: &DrawInputs,
+    ) {
+        let pass = ClippedPass {
+            clear_color,
+            // a zero plane doesn't clip anything
+            clip_plane: Vec4::ZERO,
+            mirrored: false,
+        };
+        for player in self.players.iter() {
+            let inputs = DrawInputs {
+                camera_bind_group: &player.camera_bind_group,
+                ..*inputs
+            };
+            pbr_pipeline.render_clipped(device, encoder, &player.target, pass, &inputs);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Split Screen Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, inputs.camera_bind_group, &[]);
+        for player in self.players.iter() {
+            let [x, y, width, height] = player.viewport.map(|v| v as f32);
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            render_pass.set_bind_group(1, &player.target_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec4;
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, Color, CommandEncoder, Device, PipelineLayout,
+    Queue, RenderPipeline, ShaderModule, SurfaceConfiguration, TextureView,
+};
+
+use crate::camera::Camera;
+use crate::pipelines::pbr::{ClippedPass, DrawInputs, PBRPipeline, RenderLayerTarget};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoMode {
+    /// red (left eye) / cyan (right eye), for anaglyph glasses
+    Anaglyph,
+    /// left eye on the left half of the screen, right eye on the right half
+    SideBySide,
+}
+
+/**
+Settings of the stereoscopic preview.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stereo {
+    pub enabled: bool,
+    pub mode: StereoMode,
+    /// distance between the eyes, in camera space
+    pub eye_separation: f32,
+    /// distance in front of the camera at which both eyes converge, i.e. that appears at screen depth
+    pub convergence: f32,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: StereoMode::Anaglyph,
+            eye_separation: 0.03,
+            convergence: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct StereoUniform {
+    mode: u32,
+    padding: [u32; 3],
+}
+
+/**
+Stereoscopic preview. The scene is rendered once per eye into offscreen HDR targets, with the camera offset along its
+right axis, and then composited as an anaglyph or side by side. The eyes only contain the scene's meshes: the sky,
+water, outlines and grid are rendered for the mono camera only and are therefore skipped.
+ */
+pub struct StereoPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    left_camera_buffer: Buffer,
+    left_camera_bind_group: BindGroup,
+    right_camera_buffer: Buffer,
+    right_camera_bind_group: BindGroup,
+    stereo_buffer: Buffer,
+    targets_bind_group_layout: BindGroupLayout,
+    targets_bind_group: BindGroup,
+    left_target: RenderLayerTarget,
+    right_target: RenderLayerTarget,
+    aspect: f32,
+}
+
+impl StereoPipeline {
+    pub fn new(device: &Device, config: &SurfaceConfiguration, camera: &Camera) -> Self {
+        let shader = Camera::create_shader(device, "Stereo Shader", include_str!("../shaders/stereo.wgsl"));
+
+        let create_camera = |label| {
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: bytemuck::cast_slice(&[camera.uniform()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &camera.bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+            (buffer, bind_group)
+        };
+        let (left_camera_buffer, left_camera_bind_group) = create_camera("Stereo Left Camera");
+        let (right_camera_buffer, right_camera_bind_group) = create_camera("Stereo Right Camera");
+
+        let stereo_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Stereo Buffer"),
+            contents: bytemuck::cast_slice(&[StereoUniform::from(&Stereo::default())]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let targets_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Stereo Targets Bindgroup Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let left_target = RenderLayerTarget::new(device, config.width, config.height);
+        let right_target = RenderLayerTarget::new(device, config.width, config.height);
+        let targets_bind_group = Self::create_targets_bind_group(
+            device,
+            &targets_bind_group_layout,
+            &left_target,
+            &right_target,
+            &stereo_buffer,
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Stereo Pipeline Layout"),
+            bind_group_layouts: &[&camera.bind_group_layout, &targets_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            left_camera_buffer,
+            left_camera_bind_group,
+            right_camera_buffer,
+            right_camera_bind_group,
+            stereo_buffer,
+            targets_bind_group_layout,
+            targets_bind_group,
+            left_target,
+            right_target,
+            aspect: config.width as f32 / config.height as f32,
+        }
+    }
+
+    fn create_targets_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        left_target: &RenderLayerTarget,
+        right_target: &RenderLayerTarget,
+        stereo_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Stereo Targets Bindgroup"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&left_target.color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&right_target.color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&left_target.color.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: stereo_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub(crate) fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        self.left_target = RenderLayerTarget::new(device, config.width, config.height);
+        self.right_target = RenderLayerTarget::new(device, config.width, config.height);
+        self.targets_bind_group = Self::create_targets_bind_group(
+            device,
+            &self.targets_bind_group_layout,
+            &self.left_target,
+            &self.right_target,
+            &self.stereo_buffer,
+        );
+        self.aspect = config.width as f32 / config.height as f32;
+    }
+
+    // (re-)creates the pipeline for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Stereo Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+    }
+
+    /**
+    Uploads the cameras of both eyes and the composition mode. Call this every frame before rendering.
+     */
+    pub fn update(&mut self, queue: &Queue, stereo: &Stereo, camera: &Camera) {
+        let aspect = match stereo.mode {
+            StereoMode::Anaglyph => self.aspect,
+            StereoMode::SideBySide => self.aspect / 2.0,
+        };
+        let half_separation = stereo.eye_separation / 2.0;
+        let convergence = stereo.convergence.max(camera.znear);
+        let left = camera.eye_uniform(-half_separation, convergence, aspect);
+        let right = camera.eye_uniform(half_separation, convergence, aspect);
+        queue.write_buffer(&self.left_camera_buffer, 0, bytemuck::cast_slice(&[left]));
+        queue.write_buffer(&self.right_camera_buffer, 0, bytemuck::cast_slice(&[right]));
+        queue.write_buffer(
+            &self.stereo_buffer,
+            0,
+            bytemuck::cast_slice(&[StereoUniform::from(stereo)]),
+        );
+    }
+
+    /**
+    Renders both eyes and composites them into `view`.
+     */
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        clear_color: Color,
+        pbr_pipeline: &PBRPipeline,
+        inputs: &DrawInputs,
+    ) {
+        let pass = ClippedPass {
+            clear_color,
+            // a zero plane doesn't clip anything
+            clip_plane: Vec4::ZERO,
+            mirrored: false,
+        };
+        for (target, camera_bind_group) in [
+            (&self.left_target, &self.left_camera_bind_group),
+            (&self.right_target, &self.right_camera_bind_group),
+        ] {
+            let inputs = DrawInputs {
+                camera_bind_group,
+                ..*inputs
+            };
+            pbr_pipeline.render_clipped(device, encoder, target, pass, &inputs);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Stereo Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, inputs.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.targets_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl From<&Stereo> for StereoUniform {
+    fn from(stereo: &Stereo) -> Self {
+        Self {
+            mode: match stereo.mode {
+                StereoMode::Anaglyph => 0,
+                StereoMode::SideBySide => 1,
+            },
+            padding: [0; 3],
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3, Vec4};
+use wgpu::util::DeviceExt;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, Color, CommandEncoder, DepthStencilState, Device,
+    PipelineLayout, Queue, RenderPassDepthStencilAttachment, RenderPipeline, ShaderModule, SurfaceConfiguration,
+    TextureView,
+};
+
+use lib::scene::water::Water;
+use lib::shader_types::CameraUniform;
+use lib::texture::Texture;
+
+use crate::camera::{Camera, DepthMode};
+use crate::pipelines::pbr::{ClippedPass, DrawInputs, PBRPipeline, RenderLayerTarget};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct WaterUniform {
+    position: [f32; 4],
+    color: [f32; 4],
+    size: [f32; 2],
+    time: f32,
+    wave_strength: f32,
+    wave_speed: f32,
+    wave_scale: f32,
+    padding: [f32; 2],
+}
+
+impl WaterUniform {
+    fn new(water: &Water, time: f32) -> Self {
+        Self {
+            position: water.position.extend(1.0).to_array(),
+            color: Vec3::from(water.color).extend(1.0).to_array(),
+            size: water.size.to_array(),
+            time,
+            wave_strength: water.wave_strength,
+            wave_speed: water.wave_speed,
+            wave_scale: water.wave_scale,
+            padding: [0.0; 2],
+        }
+    }
+}
+
+/**
+Pipeline for planar water surfaces. The scene is first rendered into two offscreen targets, once mirrored at the water
+level (reflection) and once clipped to what's below the water (refraction). The surface then blends both, distorted
+by animated waves.
+ */
+pub struct WaterPipeline {
+    shader: ShaderModule,
+    pipeline: Option<RenderPipeline>,
+    pub pipeline_layout: PipelineLayout,
+    water_buffer: Buffer,
+    water_bind_group: BindGroup,
+    // camera mirrored at the water level, used for the reflection pass
+    reflection_camera_buffer: Buffer,
+    reflection_camera_bind_group: BindGroup,
+    targets_bind_group_layout: BindGroupLayout,
+    targets_bind_group: BindGroup,
+    reflection_target: RenderLayerTarget,
+    refraction_target: RenderLayerTarget,
+    // planes clipping the reflection and refraction to what's above and below the water of the last `update`
+    clip_planes: [Vec4; 2],
+}
+
+impl WaterPipeline {
+    pub fn new(device: &Device, config: &SurfaceConfiguration, camera: &Camera) -> Self {
+        let shader = Camera::create_shader(device, "Water Shader", include_str!("../shaders/water.wgsl"));
+
+        let water_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Water Bindgroup Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let water_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Water Buffer"),
+            contents: bytemuck::cast_slice(&[WaterUniform::new(&Water::default(), 0.0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let water_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Water Bindgroup"),
+            layout: &water_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: water_buffer.as_entire_binding(),
+            }],
+        });
+
+        let reflection_camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Water Reflection Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera.uniform()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let reflection_camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Water Reflection Camera Bindgroup"),
+            layout: &camera.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: reflection_camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let targets_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Water Targets Bindgroup Layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let reflection_target = RenderLayerTarget::new(device, config.width, config.height);
+        let refraction_target = RenderLayerTarget::new(device, config.width, config.height);
+        let targets_bind_group = Self::create_targets_bind_group(
+            device,
+            &targets_bind_group_layout,
+            &reflection_target,
+            &refraction_target,
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Water Pipeline Layout"),
+            bind_group_layouts: &[
+                &camera.bind_group_layout,
+                &water_bind_group_layout,
+                &targets_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            pipeline: None,
+            pipeline_layout,
+            water_buffer,
+            water_bind_group,
+            reflection_camera_buffer,
+            reflection_camera_bind_group,
+            targets_bind_group_layout,
+            targets_bind_group,
+            reflection_target,
+            refraction_target,
+            clip_planes: [Vec4::ZERO; 2],
+        }
+    }
+
+    fn create_targets_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        reflection_target: &RenderLayerTarget,
+        refraction_target: &RenderLayerTarget,
+    ) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Water Targets Bindgroup"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&reflection_target.color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&refraction_target.color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&reflection_target.color.sampler),
+                },
+            ],
+        })
+    }
+
+    pub(crate) fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        self.reflection_target = RenderLayerTarget::new(device, config.width, config.height);
+        self.refraction_target = RenderLayerTarget::new(device, config.width, config.height);
+        self.targets_bind_group = Self::create_targets_bind_group(
+            device,
+            &self.targets_bind_group_layout,
+            &self.reflection_target,
+            &self.refraction_target,
+        );
+    }
+
+    // (re-)creates the pipeline for color targets of `format`
+    pub(crate) fn create_pipeline(&mut self, device: &Device, format: wgpu::TextureFormat, depth_mode: DepthMode) {
+        self.pipeline = Some(device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Water Pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // visible from above and below
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: depth_mode.compare(),
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        }));
+    }
+
+    /**
+    Uploads the water settings, the wave time in seconds and the mirrored camera. Call this every frame before
+    rendering.
+     */
+    pub fn update(&mut self, queue: &Queue, water: &Water, camera: &Camera, time: f32) {
+        let uniform = WaterUniform::new(water, time);
+        queue.write_buffer(&self.water_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.clip_planes = [water.clip_plane_above(), water.clip_plane_below()];
+
+        // mirror the world at the water level before the camera's view is applied
+        let mirror = Mat4::from_translation(Vec3::new(0.0, water.level(), 0.0))
+            * Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0))
+            * Mat4::from_translation(Vec3::new(0.0, -water.level(), 0.0));
+        let camera_uniform = camera.uniform();
+        let scale = Mat4::from_scale(Vec3::splat(0.01));
+        let reflection_uniform = CameraUniform {
+            proj_view: (Mat4::from_cols_array_2d(&camera_uniform.proj_view) * mirror).to_cols_array_2d(),
+            unproj_view: (scale * mirror * scale.inverse() * Mat4::from_cols_array_2d(&camera_uniform.unproj_view))
+                .to_cols_array_2d(),
+            view_position: (scale * mirror * scale.inverse() * camera.eye.extend(1.0)).into(),
+            ..camera_uniform
+        };
+        queue.write_buffer(
+            &self.reflection_camera_buffer,
+            0,
+            bytemuck::cast_slice(&[reflection_uniform]),
+        );
+    }
+
+    /**
+    Renders the reflection and refraction targets and then the water surface of the last `update` on top of `view`.
+    Expects the meshes to already be rendered into `view`, with their depth in the PBR pipeline's depth texture.
+    The reflection and refraction get cleared to `clear_color`, so a procedural sky is not reflected yet.
+     */
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        clear_color: Color,
+        pbr_pipeline: &PBRPipeline,
+        inputs: &DrawInputs,
+    ) {
+        let [above, below] = self.clip_planes;
+        let reflection = ClippedPass {
+            clear_color,
+            clip_plane: above,
+            mirrored: true,
+        };
+        let reflection_inputs = DrawInputs {
+            camera_bind_group: &self.reflection_camera_bind_group,
+            ..*inputs
+        };
+        pbr_pipeline.render_clipped(device, encoder, &self.reflection_target, reflection, &reflection_inputs);
+        let refraction = ClippedPass {
+            clear_color,
+            clip_plane: below,
+            mirrored: false,
+        };
+        pbr_pipeline.render_clipped(device, encoder, &self.refraction_target, refraction, inputs);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Water Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &pbr_pipeline.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(self.pipeline.as_ref().unwrap());
+        render_pass.set_bind_group(0, inputs.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.water_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.targets_bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+
+use egui::{Color32, Response, Sense, Shape, Stroke, Ui, Widget};
+use log::warn;
+use web_time::Instant;
+
+use crate::gpu_timing::PassTiming;
+
+/**
+Measures the CPU time of scopes in a frame, like `GpuTimer` does for the GPU. Scopes are delimited with `begin` and
+`end` and can't be nested.
+ */
+#[derive(Default)]
+pub struct CpuProfiler {
+    current: Option<(&'static str, Instant)>,
+    scopes: Vec<PassTiming>,
+}
+
+impl CpuProfiler {
+    /**
+    Starts recording a frame, discarding the scopes of the previous one.
+     */
+    pub fn begin_frame(&mut self) {
+        self.current = None;
+        self.scopes.clear();
+    }
+
+    pub fn begin(&mut self, label: &'static str) {
+        if let Some((current, _)) = self.current {
+            warn!("CPU profiler scope {} begun before {} ended", label, current);
+        }
+        self.current = Some((label, Instant::now()));
+    }
+
+    /**
+    Ends the scope that was begun last.
+     */
+    pub fn end(&mut self) {
+        if let Some((label, start)) = self.current.take() {
+            self.scopes.push(PassTiming {
+                label,
+                milliseconds: start.elapsed().as_secs_f32() * 1000.0,
+            });
+        }
+    }
+
+    /**
+    The scopes of the frame so far.
+     */
+    pub fn scopes(&self) -> &[PassTiming] {
+        &self.scopes
+    }
+}
+
+/**
+Timings of one frame in a `FrameHistory`, in milliseconds.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameSample {
+    /// the same measure that `Meta::frame_time` averages
+    pub frame: f32,
+    /// sum of the frame's CPU profiler scopes
+    pub cpu: f32,
+    /// sum of the GPU timer scopes of a recent frame, zero without timestamp queries
+    pub gpu: f32,
+}
+
+/**
+Rolling history of the most recent frames' timings, e.g. for the `FrameGraph` widget.
+ */
+#[derive(Debug, Clone)]
+pub struct FrameHistory {
+    samples: VecDeque<FrameSample>,
+    capacity: usize,
+}
+
+impl Default for FrameHistory {
+    fn default() -> Self {
+        Self::new(240)
+    }
+}
+
+impl FrameHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&mut self, sample: FrameSample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /**
+    The samples from oldest to newest.
+     */
+    pub fn samples(&self) -> impl ExactSizeIterator<Item = &FrameSample> {
+        self.samples.iter()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn max_frame(&self) -> f32 {
+        self.samples.iter().map(|sample| sample.frame).fold(0.0, f32::max)
+    }
+}
+
+/**
+Bar graph of a `FrameHistory`: one bar per frame for the frame time, overlaid with the CPU and GPU time, and lines at
+the 60 and 30 FPS budgets. Hooks can embed it with `ui.add(FrameGraph::new(&meta.frame_history))`.
+ */
+pub struct FrameGraph<'a> {
+    history: &'a FrameHistory,
+    height: f32,
+}
+
+impl<'a> FrameGraph<'a> {
+    const FRAME_COLOR: Color32 = Color32::from_rgb(90, 90, 100);
+    const CPU_COLOR: Color32 = Color32::from_rgb(80, 160, 230);
+    const GPU_COLOR: Color32 = Color32::from_rgb(230, 140, 60);
+    // frame budgets in milliseconds
+    const BUDGETS: [f32; 2] = [1000.0 / 60.0, 1000.0 / 30.0];
+
+    pub fn new(history: &'a FrameHistory) -> Self {
+        Self { history, height: 80.0 }
+    }
+
+    pub fn height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+impl Widget for FrameGraph<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), self.height), Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+        // scale to the slowest frame, but show at least the 60 FPS budget
+        let max = self.history.max_frame().max(Self::BUDGETS[0]) * 1.1;
+        let y = |milliseconds: f32| rect.bottom() - rect.height() * (milliseconds / max).min(1.0);
+        let bar_width = rect.width() / self.history.capacity() as f32;
+        // newest frame on the right
+        let offset = self.history.capacity() - self.history.samples().len();
+        for (i, sample) in self.history.samples().enumerate() {
+            let left = rect.left() + (offset + i) as f32 * bar_width;
+            let bar = |milliseconds: f32, from: f32, to: f32| {
+                egui::Rect::from_x_y_ranges(
+                    left + from * bar_width..=left + to * bar_width,
+                    y(milliseconds)..=rect.bottom(),
+                )
+            };
+            painter.rect_filled(bar(sample.frame, 0.0, 1.0), 0.0, Self::FRAME_COLOR);
+            painter.rect_filled(bar(sample.cpu, 0.0, 0.5), 0.0, Self::CPU_COLOR);
+            painter.rect_filled(bar(sample.gpu, 0.5, 1.0), 0.0, Self::GPU_COLOR);
+        }
+        for budget in Self::BUDGETS.into_iter().filter(|budget| *budget < max) {
+            painter.add(Shape::dashed_line(
+                &[egui::pos2(rect.left(), y(budget)), egui::pos2(rect.right(), y(budget))],
+                Stroke::new(1.0, ui.visuals().weak_text_color()),
+                4.0,
+                4.0,
+            ));
+        }
+
+        // the frame under the cursor
+        let hovered = response
+            .hover_pos()
+            .and_then(|pos| (((pos.x - rect.left()) / bar_width).max(0.0) as usize).checked_sub(offset))
+            .and_then(|i| self.history.samples().nth(i));
+        response.on_hover_ui(|ui| {
+            if let Some(sample) = hovered {
+                ui.label(format!("Frame: {:.2} ms", sample.frame));
+                ui.colored_label(Self::CPU_COLOR, format!("CPU: {:.2} ms", sample.cpu));
+                ui.colored_label(Self::GPU_COLOR, format!("GPU: {:.2} ms", sample.gpu));
+            }
+        })
+    }
+}
@@ -0,0 +1,166 @@
+use log::warn;
+use wgpu::{Buffer, BufferAsyncError, CommandEncoder, Device};
+
+/**
+Staging buffer for reading data back from the GPU, e.g. picking results, screenshots or the results of compute
+shaders. Data is copied into it with `copy_from_buffer` or `copy_from_texture`, then `map` is called after the
+copy was submitted, and the data can be read with `try_read` without blocking or with `read` once it is needed.
+
+While a readback is in flight (see `is_pending`), nothing may be copied into the buffer.
+ */
+pub struct ReadbackBuffer {
+    buffer: Buffer,
+    // bytes per row of texture copies: the unpadded size of a row and the size including the alignment padding
+    row_bytes: Option<(u32, u32)>,
+    mapping: Option<flume::Receiver<Result<(), BufferAsyncError>>>,
+}
+
+impl ReadbackBuffer {
+    pub fn new(device: &Device, label: &str, size: u64) -> Self {
+        Self {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            row_bytes: None,
+            mapping: None,
+        }
+    }
+
+    /**
+    A buffer that fits a `width` by `height` region of a texture with `bytes_per_pixel`, with its rows padded to
+    `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT` as texture copies require.
+     */
+    pub fn for_texture(device: &Device, label: &str, width: u32, height: u32, bytes_per_pixel: u32) -> Self {
+        let unpadded = width * bytes_per_pixel;
+        let padded = padded_bytes_per_row(unpadded);
+        Self {
+            row_bytes: Some((unpadded, padded)),
+            ..Self::new(device, label, padded as u64 * height as u64)
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.buffer.size()
+    }
+
+    /**
+    Whether the buffer was mapped and hasn't been read yet.
+     */
+    pub fn is_pending(&self) -> bool {
+        self.mapping.is_some()
+    }
+
+    pub fn copy_from_buffer(&self, encoder: &mut CommandEncoder, source: &Buffer, offset: u64, size: u64) {
+        encoder.copy_buffer_to_buffer(source, offset, &self.buffer, 0, size);
+    }
+
+    /**
+    Copies the `width` by `height` region at `origin` of the first mip level of `texture`. Only for buffers created
+    with `for_texture`, and the region may not be larger than the one the buffer was created for.
+     */
+    pub fn copy_from_texture(
+        &self,
+        encoder: &mut CommandEncoder,
+        texture: &wgpu::Texture,
+        origin: wgpu::Origin3d,
+        width: u32,
+        height: u32,
+    ) {
+        let (_, padded) = self
+            .row_bytes
+            .expect("Texture copy into a readback buffer not created for textures");
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /**
+    Starts mapping the buffer, call this after the copy into it was submitted.
+     */
+    pub fn map(&mut self) {
+        let (sender, receiver) = flume::bounded(1);
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+        self.mapping = Some(receiver);
+    }
+
+    /**
+    Passes the buffer's contents to `f` if mapping it has finished, without blocking, and unmaps it afterwards.
+    Returns `None` while the mapping is still in progress, or if it failed.
+     */
+    pub fn try_read<R>(&mut self, device: &Device, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let mapping = self.mapping.as_ref()?;
+        device.poll(wgpu::Maintain::Poll);
+        let result = mapping.try_recv().ok()?;
+        self.finish_read(result, f)
+    }
+
+    /**
+    Like `try_read`, but blocks until all submitted work is done and the buffer is mapped. Returns `None` if the
+    buffer wasn't mapped or mapping it failed.
+
+    On the web, mapping only finishes once control returns to the browser, so this can't block and behaves like
+    `try_read`.
+     */
+    #[cfg(target_arch = "wasm32")]
+    pub fn read<R>(&mut self, device: &Device, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        self.try_read(device, f)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read<R>(&mut self, device: &Device, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let mapping = self.mapping.as_ref()?;
+        device.poll(wgpu::Maintain::Wait);
+        let result = mapping.recv().ok()?;
+        self.finish_read(result, f)
+    }
+
+    fn finish_read<R>(&mut self, result: Result<(), BufferAsyncError>, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        self.mapping = None;
+        if let Err(e) = result {
+            warn!("Couldn't map readback buffer: {}", e);
+            return None;
+        }
+        let value = f(&self.buffer.slice(..).get_mapped_range());
+        self.buffer.unmap();
+        Some(value)
+    }
+
+    /**
+    Splits the contents of a texture readback into its rows, without the padding.
+     */
+    pub fn rows<'a>(&self, data: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+        let (unpadded, padded) = self.row_bytes.unwrap_or((data.len() as u32, data.len() as u32));
+        data.chunks(padded.max(1) as usize)
+            .map(move |row| &row[..(unpadded as usize).min(row.len())])
+    }
+}
+
+/**
+The number of bytes per row that a texture copy into a buffer needs, for rows of `unpadded` bytes.
+ */
+pub fn padded_bytes_per_row(unpadded: u32) -> u32 {
+    unpadded.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
@@ -0,0 +1,233 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::{info, warn};
+use wgpu::{Device, PresentMode};
+
+use crate::commands::imposter_meshes;
+use crate::crash_report::CrashReporter;
+use crate::events::Event;
+use crate::gpu_timing::GpuTimer;
+use crate::pipelines::pbr::DrawInputs;
+use crate::{Pipelines, RenderState};
+
+/**
+How many frames in a row the surface texture may be lost before the device is considered lost. A lost surface is
+usually fixed by reconfiguring it, if that keeps failing, the device is gone.
+ */
+pub(crate) const MAX_SURFACE_LOSSES: u32 = 3;
+
+/**
+Handles the errors of `device` that no error scope captured. Returns a flag that is set once the device was lost, which
+the render loop checks to recover, see `recover`. Other errors are fatal, like with wgpu's default handler, and are
+recorded in the crash report.
+ */
+pub(crate) fn watch_device(device: &Device, crash_reporter: Option<CrashReporter>) -> Arc<AtomicBool> {
+    let lost = Arc::new(AtomicBool::new(false));
+    let handler_lost = lost.clone();
+    device.on_uncaptured_error(Box::new(move |error| {
+        if is_device_lost(&error) {
+            warn!("Graphics device lost: {}", error);
+            handler_lost.store(true, Ordering::Relaxed);
+            return;
+        }
+        if let Some(crash_reporter) = &crash_reporter {
+            crash_reporter.record_device_error(&error.to_string());
+        }
+        panic!("wgpu error: {}", error);
+    }));
+    lost
+}
+
+// wgpu 0.18 has no callback for device loss, operations on a lost device fail with a `DeviceError::Lost` somewhere in
+// their error's sources instead
+fn is_device_lost(error: &wgpu::Error) -> bool {
+    let mut source: Option<&dyn Error> = Some(error);
+    while let Some(error) = source {
+        if error.to_string().contains("device is lost") {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+/**
+Whether the device was lost since it was created, see `watch_device`.
+ */
+pub(crate) fn device_lost(state: &RenderState) -> bool {
+    state.device_lost.load(Ordering::Relaxed)
+}
+
+/**
+Requests a new device and recreates everything the renderer had on the old one: the pipelines, the camera, the
+textures and materials of the world, the meshes and lights of all scenes and the loaded LUT, from the copies the
+managers, scenes and the grading pipeline keep on the CPU. Imposters are baked again from their models as they are
+now. Picking queries that were in flight are lost, their commands fail. Afterwards, the hook is notified with
+`Event::DeviceRecovered`.
+
+Only supported natively, as requesting the device blocks.
+ */
+pub(crate) fn recover(state: &mut RenderState) -> Result<()> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = state;
+        anyhow::bail!("Recovering from a lost device isn't supported on the web");
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        warn!("Recreating the graphics device and all GPU resources");
+        let (adapter, device, queue) = pollster::block_on(crate::request_device(
+            &state.instance,
+            &state.surface,
+            &state.adapter_selection,
+        ))?;
+        state.meta.adapter = adapter.get_info();
+        // the new adapter may support other formats and present modes
+        let surface_caps = state.surface.get_capabilities(&adapter);
+        if !surface_caps.formats.contains(&state.surface_config.format) {
+            let format = crate::preferred_surface_format(&surface_caps);
+            warn!(
+                "The new adapter doesn't support the surface format, using {:?} instead",
+                format
+            );
+            state.surface_config.format = format;
+            state.meta.surface_format = format;
+        }
+        let present_mode = state.surface_config.present_mode;
+        let auto = matches!(present_mode, PresentMode::AutoVsync | PresentMode::AutoNoVsync);
+        if !auto && !surface_caps.present_modes.contains(&present_mode) {
+            warn!(
+                "The new adapter doesn't support present mode {:?}, using vsync",
+                present_mode
+            );
+            state.surface_config.present_mode = PresentMode::AutoVsync;
+            state.meta.present_mode = PresentMode::AutoVsync;
+            state.meta.vsync = true;
+        }
+        state.meta.supported_present_modes = surface_caps.present_modes;
+        state.meta.supported_surface_formats = surface_caps.formats;
+        if let Some(crash_reporter) = &state.crash_reporter {
+            crash_reporter.set_device(&adapter, &device);
+        }
+        state.device_lost = watch_device(&device, state.crash_reporter.clone());
+        state.device = device;
+        state.queue = queue;
+        state.surface_losses = 0;
+        state.surface.configure(&state.device, &state.surface_config);
+
+        // the queries are lost along with the old picking pipeline, but the hook still expects a result for its own
+        for command in state.object_picking_pipeline.cancel() {
+            let _ = state.event_channel.0.send(Event::CommandFailed {
+                command: format!("{:?}", command),
+                error: "The graphics device was lost before picking finished".to_string(),
+            });
+        }
+        // baked again once the scenes are recreated
+        let imposters = state.imposter_pipeline.model_ids().collect::<Vec<_>>();
+
+        state.camera.recreate(&state.device);
+        // uploaded again once the pipelines are recreated
+        let lut = state.grading_pipeline.lut().cloned();
+        let Pipelines {
+            pbr_pipeline,
+            depth_prepass_pipeline,
+            shadow_pipeline,
+            object_picking_pipeline,
+            outlining_pipeline,
+            jump_flood_pipeline,
+            grid_pipeline,
+            line_pipeline,
+            axes_gizmo_pipeline,
+            light_gizmo_pipeline,
+            sky_pipeline,
+            imposter_pipeline,
+            water_pipeline,
+            stereo_pipeline,
+            split_screen_pipeline,
+            material_preview_pipeline,
+            post_process_chain,
+            motion_blur_pipeline,
+            post_effects_pipeline,
+            grading_pipeline,
+        } = Pipelines::new(&state.device, &state.surface_config, &state.camera);
+        state.pbr_pipeline = pbr_pipeline;
+        state.depth_prepass_pipeline = depth_prepass_pipeline;
+        state.shadow_pipeline = shadow_pipeline;
+        state.object_picking_pipeline = object_picking_pipeline;
+        state.outlining_pipeline = outlining_pipeline;
+        state.jump_flood_pipeline = jump_flood_pipeline;
+        state.grid_pipeline = grid_pipeline;
+        state.line_pipeline = line_pipeline;
+        state.axes_gizmo_pipeline = axes_gizmo_pipeline;
+        state.light_gizmo_pipeline = light_gizmo_pipeline;
+        state.sky_pipeline = sky_pipeline;
+        state.imposter_pipeline = imposter_pipeline;
+        state.water_pipeline = water_pipeline;
+        state.stereo_pipeline = stereo_pipeline;
+        state.split_screen_pipeline = split_screen_pipeline;
+        state.material_preview_pipeline = material_preview_pipeline;
+        state.post_process_chain = post_process_chain;
+        state.motion_blur_pipeline = motion_blur_pipeline;
+        state.post_effects_pipeline = post_effects_pipeline;
+        state.grading_pipeline = grading_pipeline;
+        if let Some(lut) = lut {
+            if lut.size <= state.device.limits().max_texture_dimension_3d {
+                state.grading_pipeline.set_lut(&state.device, &state.queue, &lut);
+            } else {
+                warn!("The new device doesn't support LUTs of size {}, grading is disabled", lut.size);
+            }
+        }
+        for pass in state.hook.post_processes(&state.device, &state.post_process_chain) {
+            state.post_process_chain.push(pass);
+        }
+
+        let world = &mut state.world;
+        world.assets.textures.recreate(&state.device, &state.queue);
+        world.assets.materials.recreate(
+            &state.device,
+            &state.queue,
+            &state.pbr_pipeline.mat_bind_group_layout,
+            &state.pbr_pipeline.tex_bind_group_layout,
+            &world.assets.textures,
+        );
+        for scene in world.scenes.values_mut() {
+            scene.recreate(
+                &state.device,
+                &state.queue,
+                &world.assets.materials,
+                &state.pbr_pipeline.mesh_bind_group_layout,
+                &state.pbr_pipeline.light_bind_group_layout,
+            );
+        }
+
+        for model_id in imposters {
+            let found = world
+                .scenes
+                .values()
+                .find_map(|scene| Some((scene, scene.iter_models_deep().find(|model| model.id == model_id)?)));
+            let Some((scene, model)) = found else {
+                continue;
+            };
+            let meshes = imposter_meshes(scene, model);
+            state.imposter_pipeline.bake(
+                &state.device,
+                &state.queue,
+                model_id,
+                &state.pbr_pipeline,
+                &DrawInputs::scene(scene, &meshes, &world.assets.materials, &state.camera),
+                &state.camera,
+            );
+        }
+
+        state.gpu_timer = GpuTimer::new(&state.device, &state.queue);
+        state.recreate_egui();
+        state.resize(state.size);
+
+        info!("Recovered from the loss of the graphics device");
+        let _ = state.event_channel.0.send(Event::DeviceRecovered);
+        Ok(())
+    }
+}
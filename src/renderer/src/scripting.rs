@@ -0,0 +1,333 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{anyhow, Context, Result};
+use glam::{Mat4, Quat, Vec3};
+use hashbrown::HashMap;
+use log::{error, info};
+use rhai::{CallFnOptions, Dynamic, Engine, Scope, AST};
+
+use lib::scene::model::Model;
+use lib::Dirtyable;
+
+use crate::commands::Command;
+use crate::RenderState;
+
+// upper bound for the work of one call, so that an endless loop in a script can't freeze the engine
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/**
+Rhai scripts attached to models, see `Command::AttachScript`. Each frame, after `Hook::update`, the `update(delta_time)`
+function of the script of every model in the active scene is called, and an `init()` function once before the first
+`update`. Both run with `this` bound to an object map that the script can keep its state in, e.g. `this.speed = 2.0;`.
+
+Scripts can only reach the engine through these functions:
+- `model_id()`: the id of the model the script is attached to
+- `position()`, `set_position(v)`, `translate(v)`: the model's position relative to its parent
+- `rotate_x(angle)`, `rotate_y(angle)`, `rotate_z(angle)`: rotate the model around its own axes, in radians
+- `visible()`, `set_visible(visible)`
+- `light_color()`, `set_light_color(v)`, `light_intensity()`, `set_light_intensity(intensity)`, for models with a light
+- `find_model(name)`: the id of a model of the active scene by name, or `()`
+- `world_position(id)`: the position of a model of the active scene in world space, or `()`
+- `delete_model(id)`, `set_mesh_param(mesh_id, name, x, y, z, w)`: sent as commands
+- `vec3(x, y, z)` creates vectors, which have `x`, `y` and `z` and support `+`, `-` and `*` with numbers
+
+A script that fails is detached and its error logged.
+ */
+pub(crate) struct Scripts {
+    engine: Engine,
+    frame: Rc<RefCell<ScriptFrame>>,
+    attached: HashMap<u32, Script>,
+}
+
+struct Script {
+    path: PathBuf,
+    ast: AST,
+    // `this` of the script's functions
+    state: Dynamic,
+    initialized: bool,
+}
+
+// what the engine functions of the script that is currently running operate on
+#[derive(Default)]
+struct ScriptFrame {
+    model_id: u32,
+    local_transform: Mat4,
+    visible: bool,
+    light: Option<(Vec3, f32)>,
+    // names and world positions of the active scene's models
+    models: Vec<(u32, Option<Box<str>>, Vec3)>,
+    commands: Vec<Command>,
+}
+
+impl Scripts {
+    pub(crate) fn new() -> Self {
+        let frame = Rc::new(RefCell::new(ScriptFrame::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| info!("Script: {}", text));
+        engine.on_debug(|text, _, position| info!("Script ({}): {}", position, text));
+        register_vec3(&mut engine);
+        register_model_api(&mut engine, &frame);
+        Self {
+            engine,
+            frame,
+            attached: HashMap::new(),
+        }
+    }
+
+    /**
+    Compiles the script and attaches it to the model, replacing the model's current script.
+     */
+    pub(crate) fn attach(&mut self, model_id: u32, path: &Path) -> Result<()> {
+        let ast = self
+            .engine
+            .compile_file(path.to_path_buf())
+            .map_err(|err| anyhow!("{}", err))
+            .with_context(|| format!("Couldn't compile the script {}", path.display()))?;
+        self.attached.insert(
+            model_id,
+            Script {
+                path: path.to_path_buf(),
+                ast,
+                state: Dynamic::from_map(Default::default()),
+                initialized: false,
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn detach(&mut self, model_id: u32) -> bool {
+        self.attached.remove(&model_id).is_some()
+    }
+
+    fn run(&mut self, model_id: u32, delta_time: f32) -> Result<(), String> {
+        let script = self.attached.get_mut(&model_id).unwrap();
+        let has_function = |name: &str| script.ast.iter_functions().any(|function| function.name == name);
+        let mut calls = vec![];
+        if !script.initialized {
+            script.initialized = true;
+            if has_function("init") {
+                calls.push(("init", vec![]));
+            }
+        }
+        if has_function("update") {
+            calls.push(("update", vec![Dynamic::from_float(delta_time as rhai::FLOAT)]));
+        }
+        for (name, args) in calls {
+            let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut script.state);
+            // whatever the functions return is ignored
+            let _ = self
+                .engine
+                .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &script.ast, name, args)
+                .map_err(|err| format!("{}: {}", name, err))?;
+        }
+        Ok(())
+    }
+}
+
+/**
+Runs the scripts of the models in the active scene and applies their changes, see `Scripts`.
+ */
+pub(crate) fn update(state: &mut RenderState, delta_time: f32) {
+    let scripts = &mut state.scripts;
+    if scripts.attached.is_empty() {
+        return;
+    }
+    // scripts of deleted models go away with them, those of other scenes wait until their scene is active again
+    scripts
+        .attached
+        .retain(|id, _| state.world.scenes.values().any(|scene| scene.get_model(*id).is_some()));
+    let Some(scene) = state.world.scenes.get_mut(&state.world.active_scene) else {
+        return;
+    };
+    let mut models = vec![];
+    collect_positions(&scene.models, Mat4::IDENTITY, &mut models);
+    scripts.frame.borrow_mut().models = models;
+
+    let ids = scripts.attached.keys().copied().collect::<Vec<_>>();
+    for id in ids {
+        let Some((model, _, _)) = find_model(&mut scene.models, id, Mat4::IDENTITY, true) else {
+            continue;
+        };
+        {
+            let mut frame = scripts.frame.borrow_mut();
+            frame.model_id = id;
+            frame.local_transform = model.local_transform;
+            frame.visible = model.visible;
+            frame.light = model.light.as_ref().map(|light| (light.color, light.intensity));
+        }
+        if let Err(err) = scripts.run(id, delta_time) {
+            let path = scripts.attached.remove(&id).unwrap().path;
+            error!(
+                "Script {} of model {} failed and was detached: {}",
+                path.display(),
+                id,
+                err
+            );
+        }
+        // the script's changes, even those before an error
+        let (model, parent_transform, parent_visible) =
+            find_model(&mut scene.models, id, Mat4::IDENTITY, true).unwrap();
+        let frame = scripts.frame.borrow();
+        if frame.local_transform != model.local_transform {
+            model.local_transform = frame.local_transform;
+            model.update_transforms(parent_transform);
+        }
+        if frame.visible != model.visible {
+            model.visible = frame.visible;
+            model.update_visibility(parent_visible);
+        }
+        if let (Some(light), Some((color, intensity))) = (&mut model.light, frame.light) {
+            if light.color != color || light.intensity != intensity {
+                light.color = color;
+                light.intensity = intensity;
+                light.set_dirty(true);
+            }
+        }
+    }
+    for command in scripts.frame.borrow_mut().commands.drain(..) {
+        let _ = state.command_channel.0.send(command);
+    }
+}
+
+// the model with its parent's world transform and whether the parents are visible
+fn find_model(
+    models: &mut [Model],
+    id: u32,
+    parent_transform: Mat4,
+    parent_visible: bool,
+) -> Option<(&mut Model, Mat4, bool)> {
+    for model in models.iter_mut() {
+        if model.id == id {
+            return Some((model, parent_transform, parent_visible));
+        }
+        let transform = parent_transform * model.local_transform;
+        let visible = parent_visible && model.visible;
+        if let Some(found) = find_model(&mut model.children, id, transform, visible) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn collect_positions(models: &[Model], parent_transform: Mat4, positions: &mut Vec<(u32, Option<Box<str>>, Vec3)>) {
+    for model in models {
+        let transform = parent_transform * model.local_transform;
+        positions.push((model.id, model.name.clone(), transform.w_axis.truncate()));
+        collect_positions(&model.children, transform, positions);
+    }
+}
+
+fn register_vec3(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<Vec3>("Vec3")
+        .register_fn("vec3", |x: rhai::FLOAT, y: rhai::FLOAT, z: rhai::FLOAT| {
+            Vec3::new(x as f32, y as f32, z as f32)
+        })
+        .register_get_set(
+            "x",
+            |v: &mut Vec3| v.x as rhai::FLOAT,
+            |v: &mut Vec3, x: rhai::FLOAT| v.x = x as f32,
+        )
+        .register_get_set(
+            "y",
+            |v: &mut Vec3| v.y as rhai::FLOAT,
+            |v: &mut Vec3, y: rhai::FLOAT| v.y = y as f32,
+        )
+        .register_get_set(
+            "z",
+            |v: &mut Vec3| v.z as rhai::FLOAT,
+            |v: &mut Vec3, z: rhai::FLOAT| v.z = z as f32,
+        )
+        .register_fn("+", |a: Vec3, b: Vec3| a + b)
+        .register_fn("-", |a: Vec3, b: Vec3| a - b)
+        .register_fn("*", |v: Vec3, s: rhai::FLOAT| v * s as f32)
+        .register_fn("*", |s: rhai::FLOAT, v: Vec3| v * s as f32)
+        .register_fn("length", |v: Vec3| v.length() as rhai::FLOAT)
+        .register_fn("to_string", |v: &mut Vec3| format!("{}", v))
+        .register_fn("to_debug", |v: &mut Vec3| format!("{}", v));
+}
+
+fn register_model_api(engine: &mut Engine, frame: &Rc<RefCell<ScriptFrame>>) {
+    let f = frame.clone();
+    engine.register_fn("model_id", move || f.borrow().model_id as rhai::INT);
+
+    let f = frame.clone();
+    engine.register_fn("position", move || f.borrow().local_transform.w_axis.truncate());
+    let f = frame.clone();
+    engine.register_fn("set_position", move |position: Vec3| {
+        f.borrow_mut().local_transform.w_axis = position.extend(1.0);
+    });
+    let f = frame.clone();
+    engine.register_fn("translate", move |offset: Vec3| {
+        f.borrow_mut().local_transform.w_axis += offset.extend(0.0);
+    });
+    for (name, axis) in [("rotate_x", Vec3::X), ("rotate_y", Vec3::Y), ("rotate_z", Vec3::Z)] {
+        let f = frame.clone();
+        engine.register_fn(name, move |angle: rhai::FLOAT| {
+            let mut frame = f.borrow_mut();
+            frame.local_transform *= Mat4::from_quat(Quat::from_axis_angle(axis, angle as f32));
+        });
+    }
+
+    let f = frame.clone();
+    engine.register_fn("visible", move || f.borrow().visible);
+    let f = frame.clone();
+    engine.register_fn("set_visible", move |visible: bool| f.borrow_mut().visible = visible);
+
+    let f = frame.clone();
+    engine.register_fn("light_color", move || {
+        f.borrow().light.map_or(Vec3::ZERO, |(color, _)| color)
+    });
+    let f = frame.clone();
+    engine.register_fn("set_light_color", move |color: Vec3| {
+        if let Some(light) = &mut f.borrow_mut().light {
+            light.0 = color;
+        }
+    });
+    let f = frame.clone();
+    engine.register_fn("light_intensity", move || {
+        f.borrow().light.map_or(0.0, |(_, intensity)| intensity as rhai::FLOAT)
+    });
+    let f = frame.clone();
+    engine.register_fn("set_light_intensity", move |intensity: rhai::FLOAT| {
+        if let Some(light) = &mut f.borrow_mut().light {
+            light.1 = intensity as f32;
+        }
+    });
+
+    let f = frame.clone();
+    engine.register_fn("find_model", move |name: &str| {
+        f.borrow()
+            .models
+            .iter()
+            .find(|(_, model_name, _)| model_name.as_deref() == Some(name))
+            .map_or(Dynamic::UNIT, |(id, _, _)| Dynamic::from_int(*id as rhai::INT))
+    });
+    let f = frame.clone();
+    engine.register_fn("world_position", move |id: rhai::INT| {
+        f.borrow()
+            .models
+            .iter()
+            .find(|(model_id, _, _)| *model_id as rhai::INT == id)
+            .map_or(Dynamic::UNIT, |(_, _, position)| Dynamic::from(*position))
+    });
+
+    let f = frame.clone();
+    engine.register_fn("delete_model", move |id: rhai::INT| {
+        f.borrow_mut().commands.push(Command::DeleteModel(id as u32));
+    });
+    let f = frame.clone();
+    engine.register_fn(
+        "set_mesh_param",
+        move |mesh_id: rhai::INT, name: &str, x: rhai::FLOAT, y: rhai::FLOAT, z: rhai::FLOAT, w: rhai::FLOAT| {
+            f.borrow_mut().commands.push(Command::SetMeshParam {
+                mesh_id: mesh_id as u32,
+                name: name.to_string(),
+                value: glam::Vec4::new(x as f32, y as f32, z as f32, w as f32),
+            });
+        },
+    );
+}
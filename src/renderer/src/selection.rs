@@ -0,0 +1,135 @@
+use lib::scene::model::Model;
+use lib::scene::Scene;
+
+use crate::events::Modifiers;
+
+/**
+How `Command::Select` combines the given meshes with the current selection.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectionMode {
+    /// selects only the given meshes
+    #[default]
+    Replace,
+    /// adds the given meshes to the selection
+    Add,
+    /// selects the given meshes that aren't selected and deselects the others
+    Toggle,
+}
+
+impl SelectionMode {
+    /**
+    The mode editors commonly use for a click with these modifiers: ctrl toggles, shift adds, otherwise the click
+    replaces the selection.
+     */
+    pub fn from_modifiers(modifiers: Modifiers) -> Self {
+        if modifiers.ctrl {
+            Self::Toggle
+        } else if modifiers.shift {
+            Self::Add
+        } else {
+            Self::Replace
+        }
+    }
+}
+
+/**
+The selected meshes of the active scene, in the order they were selected, and the models that were selected on their
+own, like lights clicked through their gizmo. Selected meshes are outlined with the scene's `selection_outline`, and
+the `*Selection` commands operate on the models they belong to.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    meshes: Vec<u32>,
+    models: Vec<u32>,
+}
+
+impl Selection {
+    pub fn meshes(&self) -> &[u32] {
+        &self.meshes
+    }
+
+    /**
+    The models that are selected themselves rather than through their meshes, see `models` for all selected models.
+     */
+    pub fn direct_models(&self) -> &[u32] {
+        &self.models
+    }
+
+    pub fn contains(&self, mesh_id: u32) -> bool {
+        self.meshes.contains(&mesh_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty() && self.models.is_empty()
+    }
+
+    pub(crate) fn select(&mut self, mesh_ids: &[u32], mode: SelectionMode) {
+        if mode == SelectionMode::Replace {
+            self.models.clear();
+        }
+        Self::combine(&mut self.meshes, mesh_ids, mode);
+    }
+
+    pub(crate) fn select_models(&mut self, model_ids: &[u32], mode: SelectionMode) {
+        if mode == SelectionMode::Replace {
+            self.meshes.clear();
+        }
+        Self::combine(&mut self.models, model_ids, mode);
+    }
+
+    fn combine(selected: &mut Vec<u32>, ids: &[u32], mode: SelectionMode) {
+        if mode == SelectionMode::Replace {
+            selected.clear();
+        }
+        for &id in ids {
+            match selected.iter().position(|&other| other == id) {
+                Some(index) if mode == SelectionMode::Toggle => {
+                    selected.remove(index);
+                }
+                Some(_) => {}
+                None => selected.push(id),
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.meshes.clear();
+        self.models.clear();
+    }
+
+    /**
+    Drops meshes and models that aren't in `scene` anymore, e.g. because their model was deleted. Returns whether there
+    were any.
+     */
+    pub(crate) fn prune(&mut self, scene: &Scene) -> bool {
+        let len = self.meshes.len() + self.models.len();
+        self.meshes.retain(|mesh_id| {
+            scene
+                .iter_models_deep()
+                .any(|model| model.meshes.iter().any(|mesh| mesh.id == *mesh_id))
+        });
+        self.models
+            .retain(|model_id| scene.iter_models_deep().any(|model| model.id == *model_id));
+        self.meshes.len() + self.models.len() != len
+    }
+
+    /**
+    The selected models and the models that the selected meshes belong to, without models that are below another one
+    of them, as operating on a model already includes its children.
+     */
+    pub fn models(&self, scene: &Scene) -> Vec<u32> {
+        fn collect(models: &[Model], selection: &Selection, selected: &mut Vec<u32>) {
+            for model in models {
+                if selection.models.contains(&model.id) || model.meshes.iter().any(|mesh| selection.contains(mesh.id)) {
+                    selected.push(model.id);
+                } else {
+                    collect(&model.children, selection, selected);
+                }
+            }
+        }
+        let mut selected = vec![];
+        collect(&scene.models, self, &mut selected);
+        selected
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::pipelines::grid::Grid;
+use crate::snap::Snap;
+use crate::stats_overlay::StatsOverlay;
+use crate::Meta;
+
+/**
+The parts of `Meta` that are kept across runs, see `RunConfig::settings`. Settings that are missing from the file keep
+their defaults, so the file stays readable when settings are added.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Settings {
+    show_grid: bool,
+    grid: Grid,
+    snap: Snap,
+    stats_overlay: StatsOverlay,
+}
+
+impl Settings {
+    /**
+    Reads the settings from the file, or the defaults if there is none yet or it can't be read.
+     */
+    pub(crate) fn load(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+        match fs::read(path)
+            .context("Couldn't read the file")
+            .and_then(|bytes| serde_json::from_slice(&bytes).context("Couldn't parse the settings"))
+        {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!("Couldn't load the settings from {:?}, using defaults: {:#}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub(crate) fn from_meta(meta: &Meta) -> Self {
+        Self {
+            show_grid: meta.show_grid,
+            grid: meta.grid,
+            snap: meta.snap,
+            stats_overlay: meta.stats_overlay,
+        }
+    }
+
+    pub(crate) fn apply(&self, meta: &mut Meta) {
+        meta.show_grid = self.show_grid;
+        meta.grid = self.grid;
+        meta.snap = self.snap;
+        meta.stats_overlay = self.stats_overlay;
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).with_context(|| format!("Couldn't create the directory {:?}", directory))?;
+        }
+        let json = serde_json::to_string_pretty(self).context("Couldn't serialize the settings")?;
+        fs::write(path, json).with_context(|| format!("Couldn't write the settings to {:?}", path))?;
+        info!("Saved the settings to {:?}", path);
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+// shared by the shaders that tone map their output, prepended to their source; each declares its own `camera` binding
+struct Camera {
+    proj_view: mat4x4<f32>,
+    unproj_view: mat4x4<f32>,
+    view_position: vec4<f32>,
+    num_lights: u32,
+    gamma: f32,
+    brightness: f32,
+    near_depth: f32,  // 1 for reverse-Z, where the far plane is at depth 0
+    prev_proj_view: mat4x4<f32>,
+};
+
+// display calibration, reinhard tone mapping and gamma correction; mirrors Calibration::apply in calibration.rs
+fn tonemap(radiance: vec3<f32>) -> vec3<f32> {
+    let color = radiance * camera.brightness;
+    return pow(color / (color + vec3(1.0)), vec3(1.0 / camera.gamma));
+}
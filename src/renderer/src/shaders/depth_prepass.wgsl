@@ -0,0 +1,47 @@
+struct PushConstants {
+    mesh_index: u32,
+}
+var<push_constant> push: PushConstants;
+
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tangent: vec4<f32>,
+    @location(3) uv: vec2<f32>
+}
+
+struct MeshInfo {
+    material: u32,
+    model_transform: mat4x4<f32>,
+    normal_matrix: mat4x4<f32>,  // model_transform.inverse().transpose()
+    scale: vec3<f32>,
+    prev_model_transform: mat4x4<f32>,  // of the previous frame, including the scale
+    params: array<vec4<f32>, 4>,  // user-defined parameters, see ParamBlock
+}
+@group(0) @binding(0)
+var<storage, read> mesh_infos: array<MeshInfo>;
+
+struct Camera {
+    proj_view: mat4x4<f32>,
+    unproj_view: mat4x4<f32>,
+    view_position: vec4<f32>,
+    num_lights: u32,
+};
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+// has to compute the position exactly like vs_main in pbr.wgsl, so that the PBR pass finds the same depths
+@vertex
+fn vs_main(
+    in: VertexInput,
+) -> @invariant @builtin(position) vec4<f32> {
+    let model_transform = mesh_infos[push.mesh_index].model_transform;
+    let scale = mesh_infos[push.mesh_index].scale;
+
+    let scale_mat = mat4x4<f32>(scale.x, 0.0, 0.0, 0.0,
+                                0.0, scale.y, 0.0, 0.0,
+                                0.0, 0.0, scale.z, 0.0,
+                                0.0, 0.0, 0.0, 1.0);
+    return camera.proj_view * model_transform * scale_mat * vec4<f32>(in.position, 1.0);
+}
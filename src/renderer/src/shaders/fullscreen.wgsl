@@ -0,0 +1,21 @@
+// Shared vertex shader of the post-processing passes: a single triangle covering the screen, drawn with 3 vertices.
+// Fragment shaders take the VertexOutput below as input and sample the previous pass' output from group 0:
+//
+// @group(0) @binding(0)
+// var t_input: texture_2d<f32>;
+// @group(0) @binding(1)
+// var s_input: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
@@ -0,0 +1,44 @@
+// Color grading: looks up the tone mapped scene color in a 3D LUT
+
+struct LutSettings {
+    domain_min: vec3<f32>,
+    size: f32,
+    domain_max: vec3<f32>,
+}
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+@group(1) @binding(0)
+var t_lut: texture_3d<f32>;
+@group(1) @binding(1)
+var s_lut: sampler;
+@group(1) @binding(2)
+var<uniform> settings: LutSettings;
+
+// output of vs_main in fullscreen.wgsl
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the targets are sRGB, LUTs map the displayed (encoded) values
+    let color = linear_to_srgb(textureSampleLevel(t_input, s_input, in.uv, 0.0).rgb);
+    let normalized = clamp((color - settings.domain_min) / (settings.domain_max - settings.domain_min), vec3<f32>(0.0), vec3<f32>(1.0));
+    // sample at texel centers, so that 0 and 1 hit the first and last entry exactly
+    let coords = (normalized * (settings.size - 1.0) + 0.5) / settings.size;
+    let graded = textureSampleLevel(t_lut, s_lut, coords, 0.0).rgb;
+    return vec4<f32>(srgb_to_linear(graded), 1.0);
+}
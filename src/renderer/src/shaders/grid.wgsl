@@ -4,14 +4,17 @@ struct VertexInput {
     @location(0) position: vec3<f32>, // 3*4 = 12
 }
 
+// the grid is drawn on a quad covering the screen, each fragment casts a ray from the near to the far plane and draws
+// the grid where it hits the ground plane at y = 0, so the grid reaches the horizon in every direction
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) near_point: vec3<f32>,
     @location(1) far_point: vec3<f32>,
-    @location(2) proj_view_x: vec4<f32>,
-    @location(3) proj_view_y: vec4<f32>,
-    @location(4) proj_view_z: vec4<f32>,
-    @location(5) proj_view_w: vec4<f32>,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
 }
 
 struct Camera {
@@ -19,10 +22,27 @@ struct Camera {
     unproj_view: mat4x4<f32>,  // inverse of proj_view; e.g. used for rendering the grid
     view_position: vec4<f32>,
     num_lights: u32,
+    gamma: f32,
+    brightness: f32,
+    near_depth: f32,  // 1 for reverse-Z, where the far plane is at depth 0
 };
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
+// see Grid in grid.rs
+struct Grid {
+    minor_color: vec4<f32>,
+    major_color: vec4<f32>,
+    x_axis_color: vec4<f32>,
+    z_axis_color: vec4<f32>,
+    spacing: f32,
+    major_every: f32,
+    fade_start: f32,
+    fade_end: f32,
+}
+@group(1) @binding(0)
+var<uniform> grid: Grid;
+
 
 fn unproject_point(x: f32, y: f32, z: f32, unproj: mat4x4<f32>) -> vec3<f32> {
     let unproj_point = unproj * vec4(x, y, z, 1.0);
@@ -34,42 +54,48 @@ fn vs_main(
     in: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.near_point = unproject_point(in.position.x, in.position.y, 0.0, camera.unproj_view);
-    out.far_point = unproject_point(in.position.x, in.position.y, 1.0, camera.unproj_view);
-    let t = -out.near_point.y / (out.far_point.y - out.near_point.y);
-    let fragPos3D = out.near_point - t * (out.far_point - out.near_point);
-    let clip_space_pos = camera.proj_view * vec4<f32>(fragPos3D, 1.0);
-    let depth = clip_space_pos.z / clip_space_pos.w;
-    out.clip_position = vec4<f32>(in.position.xy, depth, 1.0);
+    out.near_point = unproject_point(in.position.x, in.position.y, camera.near_depth, camera.unproj_view);
+    out.far_point = unproject_point(in.position.x, in.position.y, 1.0 - camera.near_depth, camera.unproj_view);
+    // the depth is written by the fragment shader, the quad itself must not be clipped
+    out.clip_position = vec4<f32>(in.position.xy, 0.5, 1.0);
     return out;
 }
 
+// how much of the pixel is covered by a line of the grid with lines at integer coordinates. Lines fade out where the
+// cells get smaller than two pixels, instead of turning into moiré towards the horizon
+fn line_coverage(coord: vec2<f32>) -> f32 {
+    let derivative = fwidth(coord);
+    let lines = abs(fract(coord - 0.5) - 0.5) / derivative;
+    let density = clamp(max(derivative.x, derivative.y) * 2.0 - 1.0, 0.0, 1.0);
+    return (1.0 - min(min(lines.x, lines.y), 1.0)) * (1.0 - density);
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_main(in: VertexOutput) -> FragmentOutput {
     let t = -in.near_point.y / (in.far_point.y - in.near_point.y);
-    var flag = 0.0;
-    if (t > 0.0) {
-        flag = 1.0;
+    // the ray points away from the ground plane
+    if t <= 0.0 {
+        discard;
     }
     let fragPos3D = in.near_point + t * (in.far_point - in.near_point);
-    let scale = 20.0;
-    let coord = fragPos3D.xz * scale; // use the scale variable to set the distance between the lines
-    let derivative = fwidth(coord);
-    let grid = abs(fract(coord - 0.5) - 0.5) / derivative;
-    let grid_line = min(grid.x, grid.y);
-    let minimumz = min(derivative.y, 1.0);
-    let minimumx = min(derivative.x, 1.0);
-    var color = vec4(0.2, 0.2, 0.2, 1.0 - min(grid_line, 1.0));
+    let coord = fragPos3D.xz / grid.spacing;
+    let minor = line_coverage(coord);
+    let major = line_coverage(coord / grid.major_every);
+    var color = vec4(grid.minor_color.rgb, grid.minor_color.a * minor);
+    color = mix(color, vec4(grid.major_color.rgb, grid.major_color.a * major), major);
+    // the axes through the origin are about two pixels wide at any distance
+    let axes = 1.0 - min(abs(fragPos3D.xz) / fwidth(fragPos3D.xz), vec2(1.0));
     // z axis
-    if (fragPos3D.x > -0.1 * minimumx && fragPos3D.x < 0.1 * minimumx) {
-        color.z = 1.0;
-    }
+    color = mix(color, grid.z_axis_color, axes.x);
     // x axis
-    if (fragPos3D.z > -0.1 * minimumz && fragPos3D.z < 0.1 * minimumz) {
-        color.x = 1.0;
-    }
-
+    color = mix(color, grid.x_axis_color, axes.y);
+    let fade = 1.0 - smoothstep(grid.fade_start, grid.fade_end, distance(camera.view_position.xyz, fragPos3D));
+    color.a *= fade;
 
-    return color * flag;
-//    return vec4<f32>(in.clip_position.zzz, 1.0);
-}
\ No newline at end of file
+    var out: FragmentOutput;
+    out.color = color;
+    let clip_space_pos = camera.proj_view * vec4<f32>(fragPos3D, 1.0);
+    // beyond the far plane, the grid stays just in front of it instead of failing the depth test
+    out.depth = mix(camera.near_depth, clamp(clip_space_pos.z / clip_space_pos.w, 0.0, 1.0), 0.999999);
+    return out;
+}
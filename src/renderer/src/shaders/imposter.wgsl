@@ -0,0 +1,44 @@
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct Imposter {
+    // world space corners of the quad: bottom left, bottom right, top left, top right
+    corners: array<vec4<f32>, 4>,
+    // offset of the selected view in the atlas, and the size of a view, in uv units
+    tile_offset: vec2<f32>,
+    tile_size: f32,
+}
+@group(1) @binding(0)
+var<uniform> imposter: Imposter;
+@group(1) @binding(1)
+var atlas: texture_2d<f32>;
+@group(1) @binding(2)
+var atlas_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// two triangles spanning the quad, no vertex buffer needed
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var indices = array<u32, 6>(0u, 1u, 2u, 2u, 1u, 3u);
+    let corner = indices[index];
+    // the top of a view is at v = 0
+    let uv = vec2<f32>(f32(corner & 1u), 1.0 - f32(corner >> 1u));
+    var out: VertexOutput;
+    out.clip_position = camera.proj_view * imposter.corners[corner];
+    out.uv = imposter.tile_offset + uv * imposter.tile_size;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the views are baked as untonemapped radiance, with zero alpha where the model doesn't cover them
+    let texel = textureSample(atlas, atlas_sampler, in.uv);
+    if texel.a < 0.5 {
+        discard;
+    }
+    return vec4<f32>(tonemap(texel.rgb), 1.0);
+}
@@ -0,0 +1,133 @@
+// Outlines with a constant width in pixels: the meshes are drawn into a mask with their outline's color and width, the
+// position of the closest mask pixel is spread across the screen with the jump flooding algorithm, and pixels close
+// enough to the mask are outlined like the mask pixel.
+// The fullscreen passes use vs_main of fullscreen.wgsl, which is prepended to this file.
+
+struct PushConstants {
+    mesh_index: u32,
+    // distance in pixels that the seeds are looked up at, halved in every flood pass
+    step: i32,
+    // the drawn mesh's outline, rgb in the upper three bytes and the width in pixels in the lowest byte
+    outline: u32,
+}
+var<push_constant> push: PushConstants;
+
+
+struct VertexInput {
+    @location(0) position: vec3<f32>, // 3*4 = 12
+    @location(1) normal: vec3<f32>, // 12 + 3*4 = 24
+    @location(2) tangent: vec4<f32>,    // 24 + 4*4 = 40
+    @location(3) uv: vec2<f32> // 40 + 2*4 = 48
+}
+
+struct MeshInfo {
+    material: u32,
+    model_transform: mat4x4<f32>,
+    normal_matrix: mat4x4<f32>,  // model_transform.inverse().transpose()
+    scale: vec3<f32>,
+    prev_model_transform: mat4x4<f32>,  // of the previous frame, including the scale
+    params: array<vec4<f32>, 4>,  // user-defined parameters, see ParamBlock
+}
+
+struct Camera {
+    proj_view: mat4x4<f32>,
+    unproj_view: mat4x4<f32>,
+    view_position: vec4<f32>,
+    num_lights: u32,
+};
+
+// bind groups of the mask pass
+@group(0) @binding(0)
+var<storage, read> mesh_infos: array<MeshInfo>;
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+// bind groups of the fullscreen passes, at other bindings than those of the mask pass so that they don't collide
+@group(0) @binding(1)
+var t_seeds: texture_2d<f32>;
+@group(1) @binding(1)
+var t_mask: texture_2d<f32>;
+
+// seeds of pixels that haven't found a mask pixel (yet)
+const NO_SEED = vec2<f32>(-1.0, -1.0);
+
+@vertex
+fn vs_mask(in: VertexInput) -> @builtin(position) vec4<f32> {
+    let model_transform = mesh_infos[push.mesh_index].model_transform;
+    let scale = mesh_infos[push.mesh_index].scale;
+    let scale_mat = mat4x4<f32>(scale.x, 0.0, 0.0, 0.0,
+                                0.0, scale.y, 0.0, 0.0,
+                                0.0, 0.0, scale.z, 0.0,
+                                0.0, 0.0, 0.0, 1.0);
+    return camera.proj_view * model_transform * scale_mat * vec4<f32>(in.position, 1.0);
+}
+
+// the outline's color in rgb and its width in pixels divided by 255 in alpha, the width is at least 1
+@fragment
+fn fs_mask() -> @location(0) vec4<f32> {
+    return vec4<f32>(
+        f32((push.outline >> 24u) & 0xffu),
+        f32((push.outline >> 16u) & 0xffu),
+        f32((push.outline >> 8u) & 0xffu),
+        f32(max(push.outline & 0xffu, 1u)),
+    ) / 255.0;
+}
+
+fn in_mask(pixel: vec2<i32>) -> bool {
+    return textureLoad(t_mask, pixel, 0).a > 0.0;
+}
+
+// every mask pixel is its own seed
+@fragment
+fn fs_seed(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    if in_mask(pixel) {
+        return vec4<f32>(floor(in.clip_position.xy), 0.0, 0.0);
+    }
+    return vec4<f32>(NO_SEED, 0.0, 0.0);
+}
+
+// takes the closest of the seeds found by the pixel and its eight neighbours at `step` pixels distance
+@fragment
+fn fs_flood(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let size = vec2<i32>(textureDimensions(t_seeds));
+    let position = floor(in.clip_position.xy);
+    var closest = NO_SEED;
+    var closest_distance = 1e20;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = pixel + vec2<i32>(x, y) * push.step;
+            if any(neighbour < vec2<i32>(0)) || any(neighbour >= size) {
+                continue;
+            }
+            let seed = textureLoad(t_seeds, neighbour, 0).xy;
+            if seed.x < 0.0 {
+                continue;
+            }
+            let seed_distance = distance(position, seed);
+            if seed_distance < closest_distance {
+                closest = seed;
+                closest_distance = seed_distance;
+            }
+        }
+    }
+    return vec4<f32>(closest, 0.0, 0.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let seed = textureLoad(t_seeds, pixel, 0).xy;
+    if in_mask(pixel) || seed.x < 0.0 {
+        discard;
+    }
+    let outline = textureLoad(t_mask, vec2<i32>(seed), 0);
+    let width = round(outline.a * 255.0);
+    // fades out over the last pixel, which smoothes the outline's edge
+    let coverage = clamp(width + 0.5 - distance(floor(in.clip_position.xy), seed), 0.0, 1.0);
+    if coverage <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(outline.rgb, coverage);
+}
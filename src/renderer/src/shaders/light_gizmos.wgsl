@@ -0,0 +1,76 @@
+struct Camera {
+    proj_view: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// see IconUniform in light_gizmos.rs
+struct Icons {
+    // half the size of an icon in normalized device coordinates
+    extent: vec2<f32>,
+};
+@group(1) @binding(0)
+var<uniform> icons: Icons;
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+var<private> CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, 1.0),
+);
+
+const TAU: f32 = 6.283185307;
+const RAYS: f32 = 8.0;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, light: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = CORNERS[index];
+    let center = camera.proj_view * vec4<f32>(light.position, 1.0);
+    if center.w <= 0.0 {
+        // behind the camera, outside of the clip volume
+        out.clip_position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+    } else {
+        // halfway into the clip volume, so that icons beyond the far plane aren't clipped
+        out.clip_position = vec4<f32>(center.xy + corner * icons.extent * center.w, center.w * 0.5, center.w);
+    }
+    out.uv = corner;
+    out.color = light.color;
+    return out;
+}
+
+// 1 inside of the radius, 0 outside, with a smooth edge one pixel wide
+fn inside(distance: f32, radius: f32, aa: f32) -> f32 {
+    return 1.0 - smoothstep(radius - aa, radius + aa, distance);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let r = length(in.uv);
+    let aa = fwidth(r);
+    // a bulb in the light's color with a dark rim, surrounded by rays
+    let bulb = inside(r, 0.42, aa);
+    let rim = inside(r, 0.55, aa);
+    let segment = fract(atan2(in.uv.y, in.uv.x) / TAU * RAYS + 0.5) - 0.5;
+    let ray_distance = abs(segment) * TAU / RAYS * r;
+    let rays = inside(ray_distance, 0.07, fwidth(ray_distance)) * (1.0 - inside(r, 0.65, aa)) * inside(r, 0.95, aa);
+    let color = mix(vec3<f32>(0.05), in.color, max(bulb, rays));
+    let alpha = max(rim, rays);
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(color, alpha);
+}
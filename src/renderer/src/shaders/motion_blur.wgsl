@@ -0,0 +1,99 @@
+// velocity pass: screen-space motion of every visible surface since the previous frame
+
+struct PushConstants {
+    mesh_index: u32,
+}
+var<push_constant> push: PushConstants;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>, // 3*4 = 12
+    @location(1) normal: vec3<f32>, // 12 + 3*4 = 24
+    @location(2) tangent: vec4<f32>,    // 24 + 4*4 = 40
+    @location(3) uv: vec2<f32> // 40 + 2*4 = 48
+}
+
+struct MeshInfo {
+    material: u32,
+    model_transform: mat4x4<f32>,
+    normal_matrix: mat4x4<f32>,  // model_transform.inverse().transpose()
+    scale: vec3<f32>,
+    prev_model_transform: mat4x4<f32>,  // of the previous frame, including the scale
+    params: array<vec4<f32>, 4>,  // user-defined parameters, see ParamBlock
+}
+@group(0) @binding(0)
+var<storage, read> mesh_infos: array<MeshInfo>;
+
+struct Camera {
+    proj_view: mat4x4<f32>,
+    unproj_view: mat4x4<f32>,
+    view_position: vec4<f32>,
+    num_lights: u32,
+    gamma: f32,
+    brightness: f32,
+    prev_proj_view: mat4x4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+struct VelocityOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) current: vec4<f32>,
+    @location(1) previous: vec4<f32>,
+}
+
+@vertex
+fn vs_velocity(in: VertexInput) -> VelocityOutput {
+    let mesh = mesh_infos[push.mesh_index];
+    let scale_mat = mat4x4<f32>(mesh.scale.x, 0.0, 0.0, 0.0,
+                                0.0, mesh.scale.y, 0.0, 0.0,
+                                0.0, 0.0, mesh.scale.z, 0.0,
+                                0.0, 0.0, 0.0, 1.0);
+    var out: VelocityOutput;
+    out.clip_position = camera.proj_view * mesh.model_transform * scale_mat * vec4<f32>(in.position, 1.0);
+    out.current = out.clip_position;
+    out.previous = camera.prev_proj_view * mesh.prev_model_transform * vec4<f32>(in.position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_velocity(in: VelocityOutput) -> @location(0) vec4<f32> {
+    let current = in.current.xy / in.current.w;
+    let previous = in.previous.xy / in.previous.w;
+    // from normalized device coordinates to texture coordinates, which have y pointing down
+    return vec4<f32>((current - previous) * vec2<f32>(0.5, -0.5), 0.0, 0.0);
+}
+
+// blur pass: averages the scene color along each pixel's velocity
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+struct BlurSettings {
+    intensity: f32,
+    samples: u32,
+}
+@group(1) @binding(0)
+var t_velocity: texture_2d<f32>;
+@group(1) @binding(1)
+var<uniform> settings: BlurSettings;
+
+// output of vs_main in fullscreen.wgsl
+struct FullscreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@fragment
+fn fs_blur(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let velocity = textureSampleLevel(t_velocity, s_input, in.uv, 0.0).xy * settings.intensity;
+    let samples = max(settings.samples, 2u);
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < samples; i++) {
+        // centered on the pixel, so that the blur doesn't lag behind the object
+        let offset = f32(i) / f32(samples - 1u) - 0.5;
+        color += textureSampleLevel(t_input, s_input, in.uv - velocity * offset, 0.0).rgb;
+    }
+    return vec4<f32>(color / f32(samples), 1.0);
+}
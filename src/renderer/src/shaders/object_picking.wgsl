@@ -17,6 +17,13 @@ struct VertexOutput {
     @location(0) color: vec4<f32>,
     @location(1) frag_pos: vec3<f32>,
     @location(2) view_pos: vec3<f32>,
+    // distance from the camera plane in camera space
+    @location(3) view_depth: f32,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) view_depth: f32,
 }
 
 struct MeshInfo {
@@ -24,6 +31,8 @@ struct MeshInfo {
     model_transform: mat4x4<f32>,
     normal_matrix: mat4x4<f32>,  // model_transform.inverse().transpose()
     scale: vec3<f32>,
+    prev_model_transform: mat4x4<f32>,  // of the previous frame, including the scale
+    params: array<vec4<f32>, 4>,  // user-defined parameters, see ParamBlock
 }
 @group(0) @binding(0)
 var<storage, read> mesh_infos: array<MeshInfo>;
@@ -53,10 +62,14 @@ fn vs_main(
     out.frag_pos = (model_transform * vec4<f32>(in.position, 1.0)).xyz;
 
     out.view_pos = camera.view_position.xyz;
+    out.view_depth = out.clip_position.w;
     return out;
 }
 
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color;
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = in.color;
+    out.view_depth = in.view_depth;
+    return out;
 }
\ No newline at end of file
@@ -24,6 +24,8 @@ struct MeshInfo {
     model_transform: mat4x4<f32>,
     normal_matrix: mat4x4<f32>,  // model_transform.inverse().transpose()
     scale: vec3<f32>,
+    prev_model_transform: mat4x4<f32>,  // of the previous frame, including the scale
+    params: array<vec4<f32>, 4>,  // user-defined parameters, see ParamBlock
 }
 @group(0) @binding(0)
 var<storage, read> mesh_infos: array<MeshInfo>;
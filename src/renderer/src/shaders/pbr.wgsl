@@ -1,5 +1,7 @@
 struct PushConstants {
     mesh_index: u32,
+    layer: u32,  // see RenderLayer in pbr.rs
+    clip_plane: vec4<f32>,  // world space, all zeros if clipping is disabled
 }
 var<push_constant> push: PushConstants;
 
@@ -12,7 +14,8 @@ struct VertexInput {
 }
 
 struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
+    // invariant, so that the depths match those of the depth pre-pass
+    @invariant @builtin(position) clip_position: vec4<f32>,
     @location(0) @interpolate(flat) index: u32,
     @location(1) tex_coords: vec2<f32>,
     @location(2) frag_pos: vec3<f32>,
@@ -21,6 +24,8 @@ struct VertexOutput {
     @location(5) b: vec3<f32>,
     @location(6) n: vec3<f32>,
     @location(7) @interpolate(flat) num_lights: u32,
+    // unlike frag_pos including the mesh's scale, like the depths in the shadow maps
+    @location(8) world_pos: vec3<f32>,
 }
 
 struct MeshInfo {
@@ -28,16 +33,12 @@ struct MeshInfo {
     model_transform: mat4x4<f32>,
     normal_matrix: mat4x4<f32>,  // model_transform.inverse().transpose()
     scale: vec3<f32>,
+    prev_model_transform: mat4x4<f32>,  // of the previous frame, including the scale
+    params: array<vec4<f32>, 4>,  // user-defined parameters, see ParamBlock
 }
 @group(2) @binding(0)
 var<storage, read> mesh_infos: array<MeshInfo>;
 
-struct Camera {
-    proj_view: mat4x4<f32>,
-    unproj_view: mat4x4<f32>,
-    view_position: vec4<f32>,
-    num_lights: u32,
-};
 @group(3) @binding(0)
 var<uniform> camera: Camera;
 
@@ -53,7 +54,9 @@ fn vs_main(
                                 0.0, scale.y, 0.0, 0.0,
                                 0.0, 0.0, scale.z, 0.0,
                                 0.0, 0.0, 0.0, 1.0);
-    out.clip_position = camera.proj_view * model_transform * scale_mat * vec4<f32>(in.position, 1.0);
+    let world_pos = model_transform * scale_mat * vec4<f32>(in.position, 1.0);
+    out.clip_position = camera.proj_view * world_pos;
+    out.world_pos = world_pos.xyz;
 
     out.index = push.mesh_index;
     out.tex_coords = in.uv;
@@ -70,36 +73,16 @@ fn vs_main(
     return out;
 }
 
-@group(0) @binding(0)
-var t_albedo: texture_2d<f32>;
-@group(0) @binding(1)
-var s_albedo: sampler;
-
-@group(0) @binding(2)
-var t_normal: texture_2d<f32>;
-@group(0) @binding(3)
-var s_normal: sampler;
-
-@group(0) @binding(4)
-var t_metallic: texture_2d<f32>;
-@group(0) @binding(5)
-var s_metallic: sampler;
-
-@group(0) @binding(6)
-var t_occlusion: texture_2d<f32>;
-@group(0) @binding(7)
-var s_occlusion: sampler;
-
-@group(0) @binding(8)
-var t_emissive: texture_2d<f32>;
-@group(0) @binding(9)
-var s_emissive: sampler;
+// the material's textures are accessed through sample_albedo, sample_normal etc., which are defined in either
+// pbr_bound_textures.wgsl or pbr_bindless_textures.wgsl, depending on whether the device supports texture arrays
 
 struct Material {
     albedo: vec4<f32>, // 4*4 = 16
     emission_factors: vec3<f32>, // 16 + 3*4 = 28
     occlusion_factor: f32, // 28 + 4 = 32
     metal_roughness_factors: vec2<f32>, // 32 + 2*4 = 40
+    // slots of the albedo, normal, metal roughness, occlusion and emission textures, only used with bindless textures
+    texture_slots: array<u32, 5>, // 40 + 5*4 = 60
 };
 
 @group(1) @binding(0)
@@ -111,29 +94,176 @@ struct LightInfo {
     color: vec3<f32>,
     intensity: f32,
     range: f32,
+    shadow_map: i32,  // slot in the shadow maps, -1 if the light casts no shadows
 };
 @group(4) @binding(0)
 var<storage, read> lights: array<LightInfo>;
 
+// see ShadowMaps in shadows.rs, six faces per slot
+@group(5) @binding(0)
+var<storage, read> shadow_faces: array<mat4x4<f32>>;
+@group(5) @binding(1)
+var shadow_maps: texture_depth_2d_array;
+@group(5) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+// the scene's lighting settings, see LightingUniform in shadows.rs
+struct Lighting {
+    ambient_sky: vec3<f32>,
+    shadow_filter: u32,
+    ambient_ground: vec3<f32>,
+    kernel_size: u32,  // samples per axis
+    light_size: f32,  // in world units, only for PCSS
+    near: f32,  // of the shadow map faces
+    far: vec4<f32>,  // of the shadow map faces of each slot
+}
+@group(5) @binding(3)
+var<uniform> lighting: Lighting;
+
+const SHADOW_HARD = 0u;
+const SHADOW_PCF = 1u;
+const SHADOW_PCSS = 2u;
+// upper bound of the PCSS blocker search and penumbra radius, in texels
+const MAX_PENUMBRA = 16.0;
+
+// how much of the light reaches the position, 0 if it's occluded in the light's shadow map
+fn shadow(light: LightInfo, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if light.shadow_map < 0 {
+        return 1.0;
+    }
+    let light_pos = light.transform[3].xyz;
+    // moves the position off the surface by about a texel of the shadow map at its distance, against shadow acne
+    let texel = 2.0 * distance(position, light_pos) / f32(textureDimensions(shadow_maps).x);
+    let offset_pos = position + normal * texel * 1.5;
+    // the face whose frustum contains the position, by the major axis of the direction from the light
+    let dir = offset_pos - light_pos;
+    let a = abs(dir);
+    var face = 0u;
+    if a.x >= a.y && a.x >= a.z {
+        face = select(1u, 0u, dir.x > 0.0);
+    } else if a.y >= a.z {
+        face = select(3u, 2u, dir.y > 0.0);
+    } else {
+        face = select(5u, 4u, dir.z > 0.0);
+    }
+    let layer = u32(light.shadow_map) * 6u + face;
+    let clip = shadow_faces[layer] * vec4<f32>(offset_pos, 1.0);
+    let ndc = clip.xyz / clip.w;
+    if ndc.z > 1.0 {
+        // beyond the far plane of the face, where nothing was rendered
+        return 1.0;
+    }
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    switch lighting.shadow_filter {
+        case SHADOW_PCF: {
+            return pcf(uv, layer, ndc.z, f32(lighting.kernel_size - 1u) / 2.0);
+        }
+        case SHADOW_PCSS: {
+            // clip.w is the distance from the light along the face's axis
+            return pcss(uv, layer, ndc.z, clip.w, lighting.far[light.shadow_map]);
+        }
+        default: {
+            return textureSampleCompareLevel(shadow_maps, shadow_sampler, uv, layer, ndc.z);
+        }
+    }
+}
+
+// offset of a sample of the filter kernel from its center, between -1 and 1 on both axes
+fn kernel_offset(x: u32, y: u32) -> vec2<f32> {
+    let half = max(f32(lighting.kernel_size - 1u) / 2.0, 1.0);
+    return (vec2<f32>(f32(x), f32(y)) - f32(lighting.kernel_size - 1u) / 2.0) / half;
+}
+
+// percentage-closer filtering: the fraction of the samples within `radius` texels around uv that are lit
+fn pcf(uv: vec2<f32>, layer: u32, depth: f32, radius: f32) -> f32 {
+    let texel = 1.0 / f32(textureDimensions(shadow_maps).x);
+    var lit = 0.0;
+    for (var x = 0u; x < lighting.kernel_size; x++) {
+        for (var y = 0u; y < lighting.kernel_size; y++) {
+            let offset = kernel_offset(x, y) * radius * texel;
+            lit += textureSampleCompareLevel(shadow_maps, shadow_sampler, uv + offset, layer, depth);
+        }
+    }
+    return lit / f32(lighting.kernel_size * lighting.kernel_size);
+}
+
+// distance from the light along the face's axis of a depth in a shadow map whose faces end at `far`
+fn linear_shadow_depth(depth: f32, far: f32) -> f32 {
+    let near = lighting.near;
+    return near * far / (far - depth * (far - near));
+}
+
+// percentage-closer soft shadows: PCF whose radius is the penumbra the light's size casts, estimated from the
+// average distance of the occluders around uv
+fn pcss(uv: vec2<f32>, layer: u32, depth: f32, receiver: f32, far: f32) -> f32 {
+    let size = textureDimensions(shadow_maps).x;
+    // world size of a texel at the receiver, the faces have a field of view of 90°
+    let texel = 2.0 * receiver / f32(size);
+    // searches the penumbra that an occluder halfway to the light would cast
+    let search = clamp(lighting.light_size / texel, 1.0, MAX_PENUMBRA);
+    var occluders = 0.0;
+    var occluder_sum = 0.0;
+    for (var x = 0u; x < lighting.kernel_size; x++) {
+        for (var y = 0u; y < lighting.kernel_size; y++) {
+            let coords = vec2<i32>(uv * f32(size) + kernel_offset(x, y) * search);
+            let occluder = textureLoad(shadow_maps, clamp(coords, vec2(0), vec2(i32(size) - 1)), layer, 0);
+            if occluder < depth {
+                occluder_sum += linear_shadow_depth(occluder, far);
+                occluders += 1.0;
+            }
+        }
+    }
+    if occluders == 0.0 {
+        return 1.0;
+    }
+    let occluder = occluder_sum / occluders;
+    let penumbra = lighting.light_size * (receiver - occluder) / occluder / texel;
+    return pcf(uv, layer, depth, clamp(penumbra, 1.0, MAX_PENUMBRA));
+}
+
 const PI = 3.14159265359;
 
+fn clip(in: VertexOutput) {
+    if dot(vec4<f32>(in.frag_pos, 1.0), push.clip_plane) < 0.0 {
+        discard;
+    }
+}
+
+const LAYER_FULL = 0u;
+const LAYER_EMISSIVE = 1u;
+const LAYER_DIRECT_LIGHTING = 2u;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip(in);
+    return vec4<f32>(tonemap(shade(in, push.layer)), 1.0);
+}
+
+// writes the untonemapped radiance of a single layer, used for offscreen (HDR) layer targets
+@fragment
+fn fs_layer(in: VertexOutput) -> @location(0) vec4<f32> {
+    clip(in);
+    return vec4<f32>(shade(in, push.layer), 1.0);
+}
+
+// computes the linear radiance of the fragment, restricted to the contributions of the given layer
+fn shade(in: VertexOutput, layer: u32) -> vec3<f32> {
     let mat_id = mesh_infos[in.index].material;
     let material = materials[mat_id];
     let tbn = mat3x3<f32>(in.t, in.b, in.n);
 
 
     // load material values, if index 0, value will be 1 because of white default texture
-    var albedo = textureSample(t_albedo, s_albedo, in.tex_coords) * material.albedo;
-    var normal = textureSample(t_normal, s_normal, in.tex_coords).rgb * 2.0;
+    var albedo = sample_albedo(material, in.tex_coords) * material.albedo;
+    var normal = sample_normal(material, in.tex_coords).rgb * 2.0;
     normal = normal - vec3(1.0);
     normal = normalize(tbn * normal);
 
-    let metallic = textureSample(t_metallic, s_metallic, in.tex_coords).b * material.metal_roughness_factors.x;
-    let roughness = textureSample(t_metallic, s_metallic, in.tex_coords).g * material.metal_roughness_factors.y;
-    var occlusion = textureSample(t_occlusion, s_occlusion, in.tex_coords).r;
-    var emission = textureSample(t_emissive, s_emissive, in.tex_coords).rgb;
+    let metal_roughness = sample_metal_roughness(material, in.tex_coords);
+    let metallic = metal_roughness.b * material.metal_roughness_factors.x;
+    let roughness = metal_roughness.g * material.metal_roughness_factors.y;
+    var occlusion = sample_occlusion(material, in.tex_coords).r;
+    var emission = sample_emission(material, in.tex_coords).rgb;
     // convert to linear space
     albedo = pow(albedo, vec4(2.2));
     emission = pow(emission, vec3(2.2));
@@ -153,7 +283,8 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 
         let dist = length(light_pos - in.frag_pos);
         let attenuation = 1.0 / (dist * dist);
-        let radiance: vec3<f32> = light.color * (light.intensity / 10.0) * attenuation;
+        let radiance: vec3<f32> = light.color * (light.intensity / 10.0) * attenuation
+            * shadow(light, in.world_pos, normalize(in.n));
         // Fresnel equation F of DFG which is the specular part of BRDF
         let reflect_ratio = fresnel(max(dot(half_vec, view_dir), 0.0), f0);
         let normal_dist = distribution(normal, half_vec, roughness);
@@ -175,14 +306,21 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         lo += (diffuse_albedo_by_pi + specular) * radiance * normal_dot_light;
     }
 
-    let ambient = vec3(0.001) * albedo.rgb * occlusion;
-    var color = ambient + lo + emission * material.emission_factors;
-    // reinhard tone mapping
-    color = color / (color + vec3(1.0));
-    // gamma correction
-    color = pow(color, vec3(1.0 / 2.2));
-    return vec4<f32>(color, 1.0);
-
+    // the engine's up axis is -Y
+    let ambient_light = mix(lighting.ambient_ground, lighting.ambient_sky, 0.5 - normal.y * 0.5);
+    let ambient = ambient_light * albedo.rgb * occlusion;
+    let emissive = emission * material.emission_factors;
+    switch layer {
+        case LAYER_EMISSIVE: {
+            return emissive;
+        }
+        case LAYER_DIRECT_LIGHTING: {
+            return lo;
+        }
+        default: {
+            return ambient + lo + emissive;
+        }
+    }
 }
 
 // Fresnel-Schlick approximation
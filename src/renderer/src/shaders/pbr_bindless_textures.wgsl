@@ -0,0 +1,33 @@
+// all textures are bound once per pass, materials refer to them by their slot in the texture manager.
+// TEXTURE_CAPACITY (the length of both arrays) is prepended when the shader is assembled, see PBRPipeline::new
+
+@group(0) @binding(0)
+var textures: binding_array<texture_2d<f32>>;
+@group(0) @binding(1)
+var samplers: binding_array<sampler>;
+
+fn sample_slot(slot: u32, uv: vec2<f32>) -> vec4<f32> {
+    // slots beyond the capacity aren't bound, fall back to the first texture
+    let index = select(0u, slot, slot < TEXTURE_CAPACITY);
+    return textureSample(textures[index], samplers[index], uv);
+}
+
+fn sample_albedo(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return sample_slot(material.texture_slots[0], uv);
+}
+
+fn sample_normal(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return sample_slot(material.texture_slots[1], uv);
+}
+
+fn sample_metal_roughness(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return sample_slot(material.texture_slots[2], uv);
+}
+
+fn sample_occlusion(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return sample_slot(material.texture_slots[3], uv);
+}
+
+fn sample_emission(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return sample_slot(material.texture_slots[4], uv);
+}
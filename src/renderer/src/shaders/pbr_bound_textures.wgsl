@@ -0,0 +1,46 @@
+// fallback for devices without texture arrays: the textures of the drawn mesh's material get bound before each draw
+
+@group(0) @binding(0)
+var t_albedo: texture_2d<f32>;
+@group(0) @binding(1)
+var s_albedo: sampler;
+
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+
+@group(0) @binding(4)
+var t_metallic: texture_2d<f32>;
+@group(0) @binding(5)
+var s_metallic: sampler;
+
+@group(0) @binding(6)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(7)
+var s_occlusion: sampler;
+
+@group(0) @binding(8)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(9)
+var s_emissive: sampler;
+
+fn sample_albedo(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(t_albedo, s_albedo, uv);
+}
+
+fn sample_normal(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(t_normal, s_normal, uv);
+}
+
+fn sample_metal_roughness(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(t_metallic, s_metallic, uv);
+}
+
+fn sample_occlusion(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(t_occlusion, s_occlusion, uv);
+}
+
+fn sample_emission(material: Material, uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(t_emissive, s_emissive, uv);
+}
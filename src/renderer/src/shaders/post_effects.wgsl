@@ -0,0 +1,55 @@
+// Camera effects: chromatic aberration, vignette and film grain
+
+struct Settings {
+    ca_strength: f32,
+    vignette_intensity: f32,
+    vignette_radius: f32,
+    vignette_softness: f32,
+    grain_intensity: f32,
+    grain_size: f32,
+    time: f32,
+    padding: f32,
+}
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+@group(1) @binding(0)
+var<uniform> settings: Settings;
+
+// output of vs_main in fullscreen.wgsl
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+fn hash(p: vec2<f32>) -> f32 {
+    let q = fract(p * vec2<f32>(443.897, 441.423));
+    let r = q + dot(q, q.yx + 19.19);
+    return fract((r.x + r.y) * r.x);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // -1..1 from the center, 1 at the edges
+    let centered = in.uv * 2.0 - 1.0;
+
+    // the red and blue channels are scaled away from / towards the center
+    let offset = centered * settings.ca_strength;
+    let color = vec3<f32>(
+        textureSampleLevel(t_input, s_input, in.uv - offset, 0.0).r,
+        textureSampleLevel(t_input, s_input, in.uv, 0.0).g,
+        textureSampleLevel(t_input, s_input, in.uv + offset, 0.0).b,
+    );
+
+    let distance = length(centered) / sqrt(2.0);
+    let vignette = 1.0 - settings.vignette_intensity
+        * smoothstep(settings.vignette_radius, settings.vignette_radius + settings.vignette_softness, distance);
+
+    let cell = floor(in.clip_position.xy / max(settings.grain_size, 1.0));
+    let grain = (hash(cell + fract(settings.time) * 1000.0) - 0.5) * settings.grain_intensity;
+
+    return vec4<f32>(max(color * vignette + grain, vec3<f32>(0.0)), 1.0);
+}
@@ -0,0 +1,42 @@
+struct PushConstants {
+    mesh_index: u32,
+    face: u32,
+}
+var<push_constant> push: PushConstants;
+
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tangent: vec4<f32>,
+    @location(3) uv: vec2<f32>
+}
+
+struct MeshInfo {
+    material: u32,
+    model_transform: mat4x4<f32>,
+    normal_matrix: mat4x4<f32>,  // model_transform.inverse().transpose()
+    scale: vec3<f32>,
+    prev_model_transform: mat4x4<f32>,  // of the previous frame, including the scale
+    params: array<vec4<f32>, 4>,  // user-defined parameters, see ParamBlock
+}
+@group(0) @binding(0)
+var<storage, read> mesh_infos: array<MeshInfo>;
+
+// projection and view matrix of each face of the shadow maps
+@group(1) @binding(0)
+var<storage, read> faces: array<mat4x4<f32>>;
+
+@vertex
+fn vs_main(
+    in: VertexInput,
+) -> @builtin(position) vec4<f32> {
+    let model_transform = mesh_infos[push.mesh_index].model_transform;
+    let scale = mesh_infos[push.mesh_index].scale;
+
+    let scale_mat = mat4x4<f32>(scale.x, 0.0, 0.0, 0.0,
+                                0.0, scale.y, 0.0, 0.0,
+                                0.0, 0.0, scale.z, 0.0,
+                                0.0, 0.0, 0.0, 1.0);
+    return faces[push.face] * model_transform * scale_mat * vec4<f32>(in.position, 1.0);
+}
@@ -0,0 +1,108 @@
+// Preetham, Shirley, Smits: "A Practical Analytic Model for Daylight" (1999)
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) near_point: vec3<f32>,
+    @location(1) far_point: vec3<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct SkyUniform {
+    sun_direction: vec3<f32>,
+    turbidity: f32,
+    exposure: f32,
+}
+@group(1) @binding(0)
+var<uniform> sky: SkyUniform;
+
+const PI = 3.14159265359;
+const UP = vec3<f32>(0.0, -1.0, 0.0);
+const SUN_ANGULAR_RADIUS = 0.0093;
+
+fn unproject_point(x: f32, y: f32, z: f32, unproj: mat4x4<f32>) -> vec3<f32> {
+    let unproj_point = unproj * vec4(x, y, z, 1.0);
+    return unproj_point.xyz / unproj_point.w;
+}
+
+// fullscreen triangle, no vertex buffer needed
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let position = uv * 2.0 - 1.0;
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(position, 1.0, 1.0);
+    out.near_point = unproject_point(position.x, position.y, camera.near_depth, camera.unproj_view);
+    out.far_point = unproject_point(position.x, position.y, 1.0 - camera.near_depth, camera.unproj_view);
+    return out;
+}
+
+// Perez et al. luminance distribution
+fn perez(cos_theta: f32, gamma: f32, cos_gamma: f32, a: f32, b: f32, c: f32, d: f32, e: f32) -> f32 {
+    return (1.0 + a * exp(b / max(cos_theta, 0.01))) * (1.0 + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+}
+
+fn sky_xyy(view_dir: vec3<f32>, sun_dir: vec3<f32>, t: f32) -> vec3<f32> {
+    let cos_theta = max(dot(view_dir, UP), 0.0);
+    let cos_theta_s = max(dot(sun_dir, UP), 0.0);
+    let theta_s = acos(cos_theta_s);
+    let cos_gamma = clamp(dot(view_dir, sun_dir), -1.0, 1.0);
+    let gamma = acos(cos_gamma);
+
+    // zenith values
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+    let zenith_luminance = (4.0453 * t - 4.9710) * tan(chi) - 0.2155 * t + 2.4192;
+    let theta_s2 = theta_s * theta_s;
+    let theta_s3 = theta_s2 * theta_s;
+    let t2 = t * t;
+    let zenith_x = t2 * (0.00166 * theta_s3 - 0.00375 * theta_s2 + 0.00209 * theta_s)
+        + t * (-0.02903 * theta_s3 + 0.06377 * theta_s2 - 0.03202 * theta_s + 0.00394)
+        + (0.11693 * theta_s3 - 0.21196 * theta_s2 + 0.06052 * theta_s + 0.25886);
+    let zenith_y = t2 * (0.00275 * theta_s3 - 0.00610 * theta_s2 + 0.00317 * theta_s)
+        + t * (-0.04214 * theta_s3 + 0.08970 * theta_s2 - 0.04153 * theta_s + 0.00516)
+        + (0.15346 * theta_s3 - 0.26756 * theta_s2 + 0.06670 * theta_s + 0.26688);
+
+    // distribution coefficients
+    let luminance = zenith_luminance
+        * perez(cos_theta, gamma, cos_gamma, 0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703)
+        / perez(1.0, theta_s, cos_theta_s, 0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703);
+    let x = zenith_x
+        * perez(cos_theta, gamma, cos_gamma, -0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452)
+        / perez(1.0, theta_s, cos_theta_s, -0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452);
+    let y = zenith_y
+        * perez(cos_theta, gamma, cos_gamma, -0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529)
+        / perez(1.0, theta_s, cos_theta_s, -0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529);
+    return vec3<f32>(x, y, luminance);
+}
+
+fn xyy_to_linear_srgb(xyy: vec3<f32>) -> vec3<f32> {
+    let big_y = xyy.z;
+    let big_x = xyy.x / max(xyy.y, 0.0001) * big_y;
+    let big_z = (1.0 - xyy.x - xyy.y) / max(xyy.y, 0.0001) * big_y;
+    return vec3<f32>(
+        3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z,
+    );
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let view_dir = normalize(in.far_point - in.near_point);
+    let sun_dir = normalize(sky.sun_direction);
+
+    // fade the whole sky out while the sun is below the horizon
+    let daylight = smoothstep(-0.1, 0.05, dot(sun_dir, UP));
+    var color = max(xyy_to_linear_srgb(sky_xyy(view_dir, sun_dir, sky.turbidity)), vec3(0.0)) * daylight;
+
+    // sun disk
+    if acos(clamp(dot(view_dir, sun_dir), -1.0, 1.0)) < SUN_ANGULAR_RADIUS {
+        color += vec3(1000.0) * daylight;
+    }
+    // darken everything below the horizon towards the ground
+    let below = clamp(-dot(view_dir, UP) * 4.0, 0.0, 1.0);
+    color = mix(color, color * 0.2, below);
+
+    return vec4<f32>(tonemap(color * sky.exposure), 1.0);
+}
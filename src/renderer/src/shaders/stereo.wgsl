@@ -0,0 +1,57 @@
+// Composites the HDR renders of both eyes into a stereo preview
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct StereoUniform {
+    mode: u32,  // see StereoMode in stereo.rs
+}
+
+@group(1) @binding(0)
+var t_left: texture_2d<f32>;
+@group(1) @binding(1)
+var t_right: texture_2d<f32>;
+@group(1) @binding(2)
+var s_eye: sampler;
+@group(1) @binding(3)
+var<uniform> stereo: StereoUniform;
+
+const MODE_ANAGLYPH: u32 = 0u;
+const MODE_SIDE_BY_SIDE: u32 = 1u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if stereo.mode == MODE_SIDE_BY_SIDE {
+        // each eye was rendered with half the aspect ratio, so the full target maps to one half of the screen
+        let uv = vec2<f32>(fract(in.uv.x * 2.0), in.uv.y);
+        var color: vec3<f32>;
+        if in.uv.x < 0.5 {
+            color = textureSampleLevel(t_left, s_eye, uv, 0.0).rgb;
+        } else {
+            color = textureSampleLevel(t_right, s_eye, uv, 0.0).rgb;
+        }
+        return vec4<f32>(tonemap(color), 1.0);
+    }
+    // red-cyan anaglyph, using the left eye's luminance for red to reduce retinal rivalry
+    let left = tonemap(textureSampleLevel(t_left, s_eye, in.uv, 0.0).rgb);
+    let right = tonemap(textureSampleLevel(t_right, s_eye, in.uv, 0.0).rgb);
+    return vec4<f32>(luminance(left), right.g, right.b, 1.0);
+}
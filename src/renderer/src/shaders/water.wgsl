@@ -0,0 +1,89 @@
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct WaterUniform {
+    position: vec4<f32>,
+    color: vec4<f32>,
+    size: vec2<f32>,
+    time: f32,
+    wave_strength: f32,
+    wave_speed: f32,
+    wave_scale: f32,
+}
+@group(1) @binding(0)
+var<uniform> water: WaterUniform;
+
+@group(2) @binding(0)
+var t_reflection: texture_2d<f32>;
+@group(2) @binding(1)
+var t_refraction: texture_2d<f32>;
+@group(2) @binding(2)
+var s_screen: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+}
+
+const UP = vec3<f32>(0.0, -1.0, 0.0);
+// the camera works on world coordinates scaled by this factor, see Camera::build_projection
+const CAMERA_SCALE = 0.01;
+
+// two triangles spanning the surface, no vertex buffer needed
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, 0.5),
+    );
+    let corner = corners[index] * water.size;
+    let world_position = water.position.xyz + vec3<f32>(corner.x, 0.0, corner.y);
+
+    var out: VertexOutput;
+    out.clip_position = camera.proj_view * vec4<f32>(world_position, 1.0);
+    out.world_position = world_position;
+    return out;
+}
+
+// procedural normal map: the analytic derivative of a few directional sine waves
+fn wave_normal(position: vec2<f32>) -> vec3<f32> {
+    // xy: direction, z: frequency
+    var waves = array<vec3<f32>, 4>(
+        vec3<f32>(1.0, 0.0, 1.0),
+        vec3<f32>(0.6, 0.8, 1.7),
+        vec3<f32>(-0.7, 0.7, 2.3),
+        vec3<f32>(0.2, -0.98, 3.1),
+    );
+    let p = position / water.wave_scale;
+    let t = water.time * water.wave_speed;
+    var slope = vec2<f32>(0.0);
+    for (var i = 0; i < 4; i++) {
+        let wave = waves[i];
+        // the amplitude is 1 / frequency, so every wave contributes the same maximum slope
+        slope += wave.xy * cos(dot(wave.xy, p) * wave.z + t * (1.0 + 0.3 * f32(i)));
+    }
+    return normalize(UP + vec3<f32>(slope.x, 0.0, slope.y) * 0.05);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = wave_normal(in.world_position.xz);
+    let screen_uv = in.clip_position.xy / vec2<f32>(textureDimensions(t_refraction));
+    let distortion = normal.xz * water.wave_strength;
+    let uv = clamp(screen_uv + distortion, vec2<f32>(0.001), vec2<f32>(0.999));
+
+    let reflection = textureSample(t_reflection, s_screen, uv).rgb;
+    let refraction = mix(textureSample(t_refraction, s_screen, uv).rgb, water.color.rgb, 0.5);
+
+    // Schlick's approximation with the reflectance of water at normal incidence
+    let eye = camera.view_position.xyz / CAMERA_SCALE;
+    let view_dir = normalize(eye - in.world_position);
+    let fresnel = 0.02 + 0.98 * pow(1.0 - max(dot(view_dir, normal), 0.0), 5.0);
+
+    // the offscreen targets are HDR, so they are tone mapped just like the PBR pipeline's output
+    return vec4<f32>(tonemap(mix(refraction, reflection, fresnel)), 1.0);
+}
@@ -0,0 +1,63 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/**
+Steps that moving and rotating models snap to, see `Meta::snap`. `Command::MoveSelection` and dragging round their
+offsets to multiples of the translation step while snapping is enabled, and `Command::SnapModelToGrid` rounds a
+model's position to the translation step regardless.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Snap {
+    pub enabled: bool,
+    /// in world units, the distance between the grid's major lines by default
+    pub translation: f32,
+    /// in degrees
+    pub rotation: f32,
+}
+
+impl Default for Snap {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            translation: 0.5,
+            rotation: 15.0,
+        }
+    }
+}
+
+impl Snap {
+    /**
+    Rounds an offset to the translation step if snapping is enabled.
+     */
+    pub fn offset(&self, offset: Vec3) -> Vec3 {
+        if self.enabled {
+            self.position(offset)
+        } else {
+            offset
+        }
+    }
+
+    /**
+    Rounds an angle in radians to the rotation step if snapping is enabled.
+     */
+    pub fn angle(&self, angle: f32) -> f32 {
+        let step = self.rotation.to_radians();
+        if self.enabled && step > 0.0 {
+            (angle / step).round() * step
+        } else {
+            angle
+        }
+    }
+
+    /**
+    Rounds a position to the closest point on the grid of the translation step, whether snapping is enabled or not.
+     */
+    pub fn position(&self, position: Vec3) -> Vec3 {
+        if self.translation > 0.0 {
+            (position / self.translation).round() * self.translation
+        } else {
+            position
+        }
+    }
+}
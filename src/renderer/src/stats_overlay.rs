@@ -0,0 +1,83 @@
+use egui::{Align2, Color32, Context, RichText};
+use serde::{Deserialize, Serialize};
+
+use crate::profiling::FrameGraph;
+use crate::Meta;
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+/**
+Performance numbers drawn in the top left corner of the viewport by the renderer itself, on top of the hook's UI: the
+frame rate, a graph of the recent frame times, the draw calls and triangles of the main PBR pass and the GPU memory
+taken up by the world. Only shown while the GUI is.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsOverlay {
+    pub enabled: bool,
+    /// shows the `FrameGraph` below the frame rate
+    pub graph: bool,
+}
+
+impl Default for StatsOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            graph: true,
+        }
+    }
+}
+
+impl StatsOverlay {
+    const WIDTH: f32 = 200.0;
+
+    pub(crate) fn show(&self, ctx: &Context, meta: &Meta) {
+        if !self.enabled {
+            return;
+        }
+        egui::Area::new("stats_overlay")
+            .anchor(Align2::LEFT_TOP, egui::vec2(10.0, 10.0))
+            .order(egui::Order::Foreground)
+            .interactable(self.graph)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(Self::WIDTH);
+                    let fps = if meta.frame_time > 0.0 {
+                        1.0 / meta.frame_time
+                    } else {
+                        0.0
+                    };
+                    ui.label(
+                        RichText::new(format!("{:.0} FPS ({:.2} ms)", fps, meta.frame_time * 1000.0))
+                            .monospace()
+                            .strong(),
+                    );
+                    if self.graph {
+                        ui.add(FrameGraph::new(&meta.frame_history).height(50.0));
+                    }
+                    egui::Grid::new("stats_overlay_grid").num_columns(2).show(ui, |ui| {
+                        ui.label("Draw calls");
+                        ui.monospace(meta.draw_stats.draw_calls.to_string());
+                        ui.end_row();
+                        ui.label("Triangles");
+                        ui.monospace(meta.draw_stats.triangles.to_string());
+                        ui.end_row();
+                        ui.label("VRAM");
+                        let memory = &meta.gpu_memory;
+                        let text = match memory.budget {
+                            Some(budget) => {
+                                format!("{:.1} / {:.1} MiB", memory.total() as f64 / MIB, budget as f64 / MIB)
+                            }
+                            None => format!("{:.1} MiB", memory.total() as f64 / MIB),
+                        };
+                        if memory.over_budget() {
+                            ui.label(RichText::new(text).monospace().color(Color32::LIGHT_RED));
+                        } else {
+                            ui.monospace(text);
+                        }
+                        ui.end_row();
+                    });
+                });
+            });
+    }
+}
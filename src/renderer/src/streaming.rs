@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+
+use glam::{IVec2, Vec2, Vec3, Vec3Swizzles};
+use hashbrown::HashMap;
+use log::{error, info};
+
+use lib::scene::model::DeepIter;
+use systems::io::gltf_loader::{read_gltf, upload_gltf, GltfData};
+
+use crate::events::Event;
+use crate::RenderState;
+
+/**
+Settings for streaming the world in cells: the ground plane is divided into a grid of square cells, each of which is
+backed by its own glTF file. Cells near the camera are read on a background thread and then added to the active
+scene, cells that the camera moved away from are removed from it again. Cells are registered with
+`Command::AddStreamingCell`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Streaming {
+    pub enabled: bool,
+    /// edge length of the cells, in world units
+    pub cell_size: f32,
+    /// cells closer to the camera than this are loaded, in world units
+    pub load_distance: f32,
+    /// loaded cells farther from the camera than this are unloaded, in world units. Larger than `load_distance`, so
+    /// that cells at the border don't load and unload repeatedly
+    pub unload_distance: f32,
+}
+
+impl Default for Streaming {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cell_size: 1000.0,
+            load_distance: 1500.0,
+            unload_distance: 2000.0,
+        }
+    }
+}
+
+impl Streaming {
+    /**
+    The cell that contains `position`, which is in world space.
+     */
+    pub fn cell_at(&self, position: Vec3) -> IVec2 {
+        (position.xz() / self.cell_size).floor().as_ivec2()
+    }
+
+    // horizontal distance from position to the closest point of the cell
+    fn distance_to_cell(&self, position: Vec3, cell: IVec2) -> f32 {
+        let min = cell.as_vec2() * self.cell_size;
+        let max = min + Vec2::splat(self.cell_size);
+        let position = position.xz();
+        position.distance(position.clamp(min, max))
+    }
+}
+
+enum CellState {
+    Unloaded,
+    Loading(flume::Receiver<Option<GltfData>>),
+    // ids of the top-level models that the cell added to the scene
+    Loaded(Vec<u32>),
+    // reading the cell's file failed, it isn't retried until the cell is added again
+    Failed,
+}
+
+struct Cell {
+    path: PathBuf,
+    state: CellState,
+}
+
+/**
+The registered cells and their loading state.
+ */
+#[derive(Default)]
+pub(crate) struct CellStreamer {
+    cells: HashMap<IVec2, Cell>,
+}
+
+impl CellStreamer {
+    /** Sets the state of a cell, unless it was removed in the meantime. */
+    fn set_state(&mut self, cell: IVec2, state: CellState) {
+        if let Some(cell) = self.cells.get_mut(&cell) {
+            cell.state = state;
+        }
+    }
+
+    pub(crate) fn add_cell(&mut self, cell: IVec2, path: PathBuf) {
+        self.cells.insert(
+            cell,
+            Cell {
+                path,
+                state: CellState::Unloaded,
+            },
+        );
+    }
+
+    /**
+    Unregisters the cell and returns the ids of the models it added to the scene, if it was loaded.
+     */
+    pub(crate) fn remove_cell(&mut self, cell: IVec2) -> Vec<u32> {
+        match self.cells.remove(&cell).map(|cell| cell.state) {
+            Some(CellState::Loaded(model_ids)) => model_ids,
+            _ => vec![],
+        }
+    }
+
+    /**
+    Marks the loaded cells whose models were removed along with their scene as unloaded, so that they are loaded into
+    the active scene again once the camera is close. Returns the coordinates of those cells.
+     */
+    pub(crate) fn forget_models(&mut self, model_ids: &[u32]) -> Vec<IVec2> {
+        let mut forgotten = vec![];
+        for (coordinates, cell) in self.cells.iter_mut() {
+            if let CellState::Loaded(ids) = &cell.state {
+                if ids.iter().any(|id| model_ids.contains(id)) {
+                    cell.state = CellState::Unloaded;
+                    forgotten.push(*coordinates);
+                }
+            }
+        }
+        forgotten
+    }
+}
+
+/**
+Starts loading cells near the camera, adds the cells that finished loading to the active scene and removes the ones
+that are too far away.
+ */
+pub(crate) fn update(state: &mut RenderState) {
+    let settings = state.meta.streaming;
+    if !settings.enabled {
+        return;
+    }
+    let eye = state.camera.world_eye();
+    let mut loaded = vec![];
+    let mut unloaded = vec![];
+    for (coordinates, cell) in state.streamer.cells.iter_mut() {
+        let distance = settings.distance_to_cell(eye, *coordinates);
+        match &cell.state {
+            CellState::Unloaded if distance < settings.load_distance => {
+                let (sender, receiver) = flume::bounded(1);
+                let path = cell.path.clone();
+                state.world.assets.start_load(&path);
+                let load = move || {
+                    let data = read_gltf(&path)
+                        .map_err(|e| error!("Couldn't read streaming cell {:?}: {:#}", path, e))
+                        .ok();
+                    // the streamer may have been dropped in the meantime
+                    let _ = sender.send(data);
+                };
+                #[cfg(not(target_arch = "wasm32"))]
+                thread::spawn(load);
+                // there are no threads in the browser, nor a file system, so reading fails right away
+                #[cfg(target_arch = "wasm32")]
+                load();
+                cell.state = CellState::Loading(receiver);
+            }
+            CellState::Loading(receiver) => match receiver.try_recv() {
+                Ok(Some(data)) => loaded.push((*coordinates, cell.path.clone(), data)),
+                Ok(None) | Err(flume::TryRecvError::Disconnected) => {
+                    // the error was logged by the loading thread
+                    let error = "Couldn't read the file".to_string();
+                    state.world.assets.finish_load(&cell.path, Some(error));
+                    cell.state = CellState::Failed;
+                }
+                Err(flume::TryRecvError::Empty) => {}
+            },
+            CellState::Loaded(_) if distance > settings.unload_distance => {
+                if let CellState::Loaded(model_ids) = std::mem::replace(&mut cell.state, CellState::Unloaded) {
+                    unloaded.push((*coordinates, model_ids));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (coordinates, path, data) in loaded {
+        let scenes = upload_gltf(
+            data,
+            &state.device,
+            &state.queue,
+            state.pbr_pipeline.gltf_layouts(),
+            &mut state.world.assets.textures,
+            &mut state.world.assets.materials,
+        );
+        let scene = match scenes {
+            Ok(mut scenes) if !scenes.is_empty() => scenes.remove(0),
+            Ok(_) => {
+                error!("Streaming cell {} doesn't contain a scene", coordinates);
+                let error = "The file doesn't contain a scene".to_string();
+                state.world.assets.finish_load(&path, Some(error));
+                state.streamer.set_state(coordinates, CellState::Failed);
+                continue;
+            }
+            Err(err) => {
+                error!("Couldn't upload streaming cell {}: {:#}", coordinates, err);
+                state.world.assets.finish_load(&path, Some(format!("{:#}", err)));
+                state.streamer.set_state(coordinates, CellState::Failed);
+                continue;
+            }
+        };
+        state.world.assets.finish_load(&path, None);
+        let model_ids = scene.models.iter().map(|model| model.id).collect();
+        state.join_into_active_scene(scene);
+        state.streamer.set_state(coordinates, CellState::Loaded(model_ids));
+        info!("Streamed in cell {}", coordinates);
+        // the hook may have dropped its receiver
+        let _ = state.event_channel.0.send(Event::CellLoaded(coordinates));
+    }
+    for (coordinates, model_ids) in unloaded {
+        remove_models(state, &model_ids);
+        info!("Streamed out cell {}", coordinates);
+        let _ = state.event_channel.0.send(Event::CellUnloaded(coordinates));
+    }
+}
+
+/**
+Removes models that a cell added from the active scene, like `Command::DeleteModel` does.
+ */
+pub(crate) fn remove_models(state: &mut RenderState, model_ids: &[u32]) {
+    let Some(scene) = state.world.scenes.get_mut(&state.world.active_scene) else {
+        return;
+    };
+    for model_id in model_ids {
+        if let Some(removed) = scene.remove_model(*model_id, &state.device, &state.queue, &state.world.assets.materials)
+        {
+            for id in std::iter::once(removed.id).chain(removed.children.iter_deep().map(|child| child.id)) {
+                scene.remove_joints_to(id);
+            }
+        }
+    }
+    state.update_light_count();
+}
@@ -0,0 +1,90 @@
+use egui::load::SizedTexture;
+use egui::{Response, TextureId, Ui};
+use hashbrown::{HashMap, HashSet};
+use wgpu::{Device, FilterMode};
+
+use lib::managers::{TexId, TextureManager};
+use lib::texture::Texture;
+
+use crate::ui_resources::UiResources;
+
+/**
+Thumbnails of the world's textures for the GUI. Textures are registered with egui the first time their thumbnail is
+asked for, so they are available from the next frame on, and unregistered once they are removed from the world.
+ */
+#[derive(Debug, Default)]
+pub struct TextureThumbnails {
+    registered: HashMap<TexId, TextureId>,
+    requested: HashSet<TexId>,
+    // textures whose contents were replaced, registered again with their new view
+    replaced: HashSet<TexId>,
+}
+
+impl TextureThumbnails {
+    /**
+    The egui texture of the texture, or `None` if it isn't registered yet. It is then registered before the next frame.
+     */
+    pub fn get(&mut self, id: TexId) -> Option<TextureId> {
+        let registered = self.registered.get(&id).copied();
+        if registered.is_none() {
+            self.requested.insert(id);
+        }
+        registered
+    }
+
+    /**
+    Shows the thumbnail of the texture scaled to fit into a square of `size` points. Until it is registered, the space
+    is left empty.
+     */
+    pub fn show(&mut self, ui: &mut Ui, id: TexId, texture: &Texture, size: f32) -> Response {
+        let extent = texture.texture.size();
+        let scale = size / extent.width.max(extent.height).max(1) as f32;
+        let size = egui::vec2(extent.width as f32 * scale, extent.height as f32 * scale);
+        match self.get(id) {
+            Some(thumbnail) => ui.image(SizedTexture::new(thumbnail, size)),
+            None => ui.allocate_response(size, egui::Sense::hover()),
+        }
+    }
+
+    /**
+    Registers the requested textures and unregisters the removed ones.
+     */
+    pub(crate) fn update(&mut self, resources: &mut UiResources, device: &Device, textures: &TextureManager) {
+        let replaced = std::mem::take(&mut self.replaced);
+        self.registered.retain(|id, thumbnail| {
+            let exists = textures.contains(*id) && !replaced.contains(id);
+            if !exists {
+                resources.free_texture(*thumbnail);
+            }
+            exists
+        });
+        for id in self.requested.drain() {
+            if !textures.contains(id) || self.registered.contains_key(&id) {
+                continue;
+            }
+            // render targets have no sRGB view, they are shown as placeholders
+            let Some(view) = textures.get_texture(&id).srgb_view() else {
+                continue;
+            };
+            let thumbnail = resources.register_texture(device, &view, FilterMode::Linear);
+            self.registered.insert(id, thumbnail);
+        }
+    }
+
+    /**
+    Registers the texture again after it was replaced, see `TextureManager::replace_texture`.
+     */
+    pub(crate) fn replace(&mut self, id: TexId) {
+        if self.registered.contains_key(&id) {
+            self.replaced.insert(id);
+            self.requested.insert(id);
+        }
+    }
+
+    /**
+    Forgets all registrations, e.g. after egui was recreated on a new device. They are registered again on request.
+     */
+    pub(crate) fn clear(&mut self) {
+        self.registered.clear();
+    }
+}
@@ -0,0 +1,70 @@
+/**
+Pauses and scales the time that passes in the scene: the `delta_time` of `Hook::update`, scripts and physics, the `time`
+of bindings and the animation of water and post effects. The camera and the GUI keep running in real time, so that a
+paused scene can still be looked around in.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeControl {
+    pub paused: bool,
+    scale: f32,
+    step: bool,
+    elapsed: f32,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            scale: 1.0,
+            step: false,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl TimeControl {
+    pub const MIN_SCALE: f32 = 0.1;
+    pub const MAX_SCALE: f32 = 4.0;
+    /// the scene time that `step` advances a paused scene by
+    pub const STEP: f32 = 1.0 / 60.0;
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /**
+    Makes the scene run `scale` times as fast as real time, clamped to `MIN_SCALE` and `MAX_SCALE`.
+     */
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.clamp(Self::MIN_SCALE, Self::MAX_SCALE);
+    }
+
+    /**
+    Advances a paused scene by `STEP` in the next frame, e.g. to go through an animation frame by frame.
+     */
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+
+    /**
+    The scene time that has passed since the start, in seconds.
+     */
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /**
+    Returns the scene time that passes during a frame that took `real_delta` seconds.
+     */
+    pub(crate) fn advance(&mut self, real_delta: f32) -> f32 {
+        let delta = if !self.paused {
+            real_delta * self.scale
+        } else if std::mem::take(&mut self.step) {
+            Self::STEP
+        } else {
+            0.0
+        };
+        self.elapsed += delta;
+        delta
+    }
+}
@@ -0,0 +1,55 @@
+use egui::TextureId;
+use egui_wgpu::Renderer;
+use wgpu::{Device, FilterMode, TextureView};
+
+// for custom painting, see `UiResources::callback_resources`
+pub use egui_wgpu::{Callback, CallbackResources, CallbackTrait};
+
+/**
+The GPU side of the GUI that hooks can extend in `Hook::prepare_ui`: textures that egui can draw, and the shared
+resources of custom paint callbacks.
+
+Paint callbacks are added to a painter in `Hook::update_ui` with `Callback::new_paint_callback`, and render into the
+GUI's render pass with the pipelines and buffers they stored in `callback_resources`.
+
+Registrations don't survive the loss of the graphics device or a change of the surface format, hooks register their
+textures again after `Event::DeviceRecovered`.
+ */
+pub struct UiResources<'a> {
+    renderer: &'a mut Renderer,
+}
+
+impl<'a> UiResources<'a> {
+    pub(crate) fn new(renderer: &'a mut Renderer) -> Self {
+        Self { renderer }
+    }
+
+    /**
+    Makes the texture available to egui, e.g. for `Ui::image`. egui treats the sampled colors as linear, so textures
+    that hold gamma encoded colors should be viewed with an sRGB format. Render targets of the renderer work as well, as
+    long as they are registered again when they are recreated.
+     */
+    pub fn register_texture(&mut self, device: &Device, view: &TextureView, filter: FilterMode) -> TextureId {
+        self.renderer.register_native_texture(device, view, filter)
+    }
+
+    /**
+    Points a registered id at another texture, e.g. after a render target was resized.
+     */
+    pub fn update_texture(&mut self, device: &Device, view: &TextureView, filter: FilterMode, id: TextureId) {
+        self.renderer
+            .update_egui_texture_from_wgpu_texture(device, view, filter, id);
+    }
+
+    pub fn free_texture(&mut self, id: TextureId) {
+        self.renderer.free_texture(&id);
+    }
+
+    /**
+    Resources shared by paint callbacks, stored by their type. Callbacks get them in `CallbackTrait::prepare` and
+    `CallbackTrait::paint`.
+     */
+    pub fn callback_resources(&mut self) -> &mut CallbackResources {
+        &mut self.renderer.callback_resources
+    }
+}
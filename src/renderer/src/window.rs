@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use winit::dpi::LogicalSize;
+use winit::error::OsError;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::monitor::MonitorHandle;
+use winit::window::{CursorGrabMode, Fullscreen, Icon, Window, WindowBuilder};
+
+use crate::adapter::AdapterSelection;
+
+/**
+Whether the window covers a monitor. The monitor is the one the window is on, or the primary one when the window is
+created.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    /// a borderless window the size of the monitor
+    Borderless,
+    /// exclusive fullscreen in the monitor's video mode with the highest resolution and refresh rate
+    Fullscreen,
+}
+
+/**
+How the window is created, passed into `run`. Sizes are in logical pixels.
+ */
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    pub title: String,
+    /// initial size of the window's contents, chosen by the platform if `None`
+    pub size: Option<(u32, u32)>,
+    pub min_size: Option<(u32, u32)>,
+    pub resizable: bool,
+    pub decorations: bool,
+    /// image file for the window icon
+    pub icon: Option<PathBuf>,
+    pub window_mode: WindowMode,
+    /// keeps the cursor inside the window
+    pub cursor_grab: bool,
+    /// on the web, the id of the canvas element to render into. If `None`, a canvas is created and appended to the
+    /// document's body. Ignored on other platforms
+    pub canvas: Option<String>,
+    /// directory that a report with the adapter, device limits, recent commands and frame stats is written into when
+    /// the engine panics or the device reports an error. No reports are written if `None`
+    pub crash_reports: Option<PathBuf>,
+    /// offers to open the report of a crash during the last run in a dialog
+    pub crash_dialog: bool,
+    /// the graphics adapter to render with, overridden by the `ENGINE_ADAPTER` environment variable, see
+    /// `adapter::enumerate_adapters` for the adapters to choose from
+    pub adapter: AdapterSelection,
+    /// starts without rendering and with a hidden window, e.g. for procedural generation or server-style use, see
+    /// `Command::SetSimulationOnly`
+    pub simulation_only: bool,
+    /// file that settings like the grid's appearance and snapping are loaded from at startup and saved to on exit. Settings aren't
+    /// kept across runs if `None`
+    pub settings: Option<PathBuf>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            title: "engine-wgpu".into(),
+            size: None,
+            min_size: None,
+            resizable: true,
+            decorations: true,
+            icon: None,
+            window_mode: WindowMode::Windowed,
+            cursor_grab: false,
+            canvas: None,
+            // there is no file system to write reports to in the browser
+            crash_reports: (!cfg!(target_arch = "wasm32")).then(|| PathBuf::from("crash_reports")),
+            crash_dialog: true,
+            adapter: AdapterSelection::default(),
+            simulation_only: false,
+            settings: (!cfg!(target_arch = "wasm32")).then(|| PathBuf::from("settings.json")),
+        }
+    }
+}
+
+impl RunConfig {
+    pub(crate) fn build<T>(&self, event_loop: &EventLoopWindowTarget<T>) -> Result<Window, OsError> {
+        let mut builder = WindowBuilder::new()
+            .with_title(&self.title)
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_visible(!self.simulation_only)
+            .with_window_icon(self.icon.as_deref().and_then(load_icon))
+            .with_fullscreen(fullscreen(self.window_mode, event_loop.primary_monitor()));
+        if let Some((width, height)) = self.size {
+            builder = builder.with_inner_size(LogicalSize::new(width, height));
+        }
+        if let Some((width, height)) = self.min_size {
+            builder = builder.with_min_inner_size(LogicalSize::new(width, height));
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::WindowBuilderExtWebSys;
+            builder = builder.with_canvas(self.canvas.as_deref().and_then(find_canvas));
+        }
+        let window = builder.build(event_loop)?;
+        #[cfg(target_arch = "wasm32")]
+        if self.canvas.is_none() {
+            append_canvas(&window);
+        }
+        if self.cursor_grab {
+            grab_cursor(&window);
+        }
+        Ok(window)
+    }
+}
+
+pub(crate) fn fullscreen(mode: WindowMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match mode {
+        WindowMode::Windowed => None,
+        WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        WindowMode::Fullscreen => {
+            let video_mode = monitor.and_then(|monitor| {
+                monitor.video_modes().max_by_key(|video_mode| {
+                    let size = video_mode.size();
+                    (size.width * size.height, video_mode.refresh_rate_millihertz())
+                })
+            });
+            match video_mode {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                None => {
+                    warn!("No video mode for exclusive fullscreen, using a borderless window instead");
+                    Some(Fullscreen::Borderless(None))
+                }
+            }
+        }
+    }
+}
+
+fn grab_cursor(window: &Window) {
+    // not every platform supports both modes
+    if let Err(e) = window
+        .set_cursor_grab(CursorGrabMode::Confined)
+        .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
+    {
+        warn!("Couldn't grab the cursor: {}", e);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn find_canvas(id: &str) -> Option<web_sys::HtmlCanvasElement> {
+    use wasm_bindgen::JsCast;
+    let canvas = web_sys::window()?
+        .document()?
+        .get_element_by_id(id)
+        .and_then(|element| element.dyn_into().ok());
+    if canvas.is_none() {
+        warn!("No canvas with id {} found, creating one", id);
+    }
+    canvas
+}
+
+#[cfg(target_arch = "wasm32")]
+fn append_canvas(window: &Window) {
+    use winit::platform::web::WindowExtWebSys;
+    let body = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.body());
+    match body {
+        Some(body) => {
+            if let Err(e) = body.append_child(&window.canvas()) {
+                warn!("Couldn't add the canvas to the document: {:?}", e);
+            }
+        }
+        None => warn!("No document body to add the canvas to"),
+    }
+}
+
+fn load_icon(path: &Path) -> Option<Icon> {
+    let image = image::open(path)
+        .map_err(|e| warn!("Couldn't load window icon {:?}: {}", path, e))
+        .ok()?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height)
+        .map_err(|e| warn!("Invalid window icon {:?}: {}", path, e))
+        .ok()
+}
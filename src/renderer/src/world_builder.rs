@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use glam::Vec3;
+
+use lib::geometry::Geometry;
+use lib::scene::water::Water;
+
+use crate::commands::{Command, CreateModel};
+use crate::events::Event;
+use crate::RenderState;
+
+/**
+Collects the initial contents of the world during `Hook::setup`.
+
+Everything added here is applied directly after `setup` returns, before the first frame is rendered, so programmatic
+scenes don't have to go through the command channel. Models are added to the active scene, which is created if the
+world doesn't have one yet.
+ */
+#[derive(Debug, Default)]
+pub struct WorldBuilder {
+    commands: Vec<Command>,
+}
+
+impl WorldBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Imports all models of the first scene in the given glTF file. */
+    pub fn add_gltf(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.commands.push(Command::ImportFile(path.into()));
+        self
+    }
+
+    pub fn add_light(&mut self, position: Vec3, color: Vec3, intensity: f32) -> &mut Self {
+        self.commands.push(Command::CreateModel(
+            CreateModel::Light {
+                position,
+                color,
+                intensity,
+            },
+            None,
+        ));
+        self
+    }
+
+    /** Adds a primitive shape using the default material. */
+    pub fn add_primitive(&mut self, geometry: Geometry, position: Vec3) -> &mut Self {
+        self.commands.push(Command::CreateModel(
+            CreateModel::Primitive { geometry, position },
+            None,
+        ));
+        self
+    }
+
+    pub fn add_water(&mut self, water: Water) -> &mut Self {
+        self.commands.push(Command::CreateWater(water));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub(crate) fn build(self, state: &mut RenderState, event_sender: mpsc::Sender<Event>) {
+        for command in self.commands {
+            command.process(state, event_sender.clone());
+        }
+    }
+}
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::{fs, io};
 
+use anyhow::{anyhow, bail, Context, Result};
 use base64::{engine::general_purpose, Engine as _};
 use glam::{Mat4, Vec2, Vec3, Vec4};
 use gltf::buffer::Data;
@@ -20,7 +21,7 @@ use lib::scene::material::PbrMaterial;
 use lib::scene::mesh::Mesh;
 use lib::scene::model::Model;
 use lib::scene::Scene;
-use lib::texture::{Texture, TextureKind};
+use lib::texture::TextureKind;
 use lib::Material;
 
 fn read_to_end<P>(path: P) -> gltf::Result<Vec<u8>>
@@ -75,55 +76,59 @@ impl<'a> Scheme<'a> {
                 Scheme::Unsupported
             }
         } else {
-            Scheme::Relative(urlencoding::decode(uri).unwrap())
+            match urlencoding::decode(uri) {
+                Ok(path) => Scheme::Relative(path),
+                Err(_) => Scheme::Unsupported,
+            }
         }
     }
 
-    fn read(base: Option<&Path>, uri: &str) -> Vec<u8> {
-        match Scheme::parse(uri) {
+    fn read(base: Option<&Path>, uri: &str) -> Result<Vec<u8>> {
+        match (Scheme::parse(uri), base) {
             // The path may be unused in the Scheme::Data case
             // Example: "uri" : "data:application/octet-stream;base64,wsVHPgA...."
-            Scheme::Data(_, base64) => general_purpose::STANDARD.decode(base64).expect("Couldn't read b64"),
-            Scheme::File(path) if base.is_some() => read_to_end(path).expect("Couldn't read file at path"),
-            Scheme::Relative(path) if base.is_some() => {
-                read_to_end(base.unwrap().join(&*path)).expect("Couldn't read image from relative path")
+            (Scheme::Data(_, base64), _) => general_purpose::STANDARD.decode(base64).context("Couldn't read b64"),
+            (Scheme::File(path), Some(_)) => {
+                read_to_end(path).with_context(|| format!("Couldn't read file at path {}", path))
             }
-            Scheme::Unsupported => panic!("Unsupported scheme."),
-            _ => panic!("External references aren't supported."),
+            (Scheme::Relative(path), Some(base)) => read_to_end(base.join(&*path))
+                .with_context(|| format!("Couldn't read image from relative path {}", path)),
+            (Scheme::Unsupported, _) => bail!("Unsupported scheme: {}", uri),
+            _ => bail!("External references aren't supported."),
         }
     }
 }
 
-fn load_image(source: Source<'_>, base: Option<&Path>, buffer_data: &[Data]) -> DynamicImage {
+fn load_image(source: Source<'_>, base: Option<&Path>, buffer_data: &[Data]) -> Result<DynamicImage> {
     let (decoded_image, ..) = match source {
         Source::Uri { uri, mime_type } if base.is_some() => match Scheme::parse(uri) {
             Scheme::Data(Some(mime), base64) => {
-                let encoded_image = general_purpose::STANDARD.decode(base64).expect("Couldn't parse b64");
+                let encoded_image = general_purpose::STANDARD.decode(base64).context("Couldn't parse b64")?;
                 let encoded_format = match mime {
                     "image/png" => Png,
                     "image/jpeg" => Jpeg,
-                    _ => panic!("Couldn't determine format of b64-encoded image"),
+                    _ => bail!("Couldn't determine format of b64-encoded image"),
                 };
                 (
-                    image::load_from_memory(&encoded_image).expect("Couldn't load image"),
+                    image::load_from_memory(&encoded_image).context("Couldn't load image")?,
                     encoded_format,
                 )
             }
-            Scheme::Unsupported => panic!("Unsupported scheme."),
+            Scheme::Unsupported => bail!("Unsupported scheme: {}", uri),
             _ => {
-                let encoded_image = Scheme::read(base, uri);
+                let encoded_image = Scheme::read(base, uri)?;
                 let encoded_format = match mime_type {
                     Some("image/png") => Png,
                     Some("image/jpeg") => Jpeg,
                     None => match uri.rsplit('.').next() {
                         Some("png") => Png,
                         Some("jpg") | Some("jpeg") => Jpeg,
-                        _ => panic!("Couldn't determine format of image"),
+                        _ => bail!("Couldn't determine format of image {}", uri),
                     },
-                    _ => panic!("Couldn't determine format of image"),
+                    _ => bail!("Couldn't determine format of image {}", uri),
                 };
                 (
-                    image::load_from_memory(&encoded_image).expect("Couldn't load image"),
+                    image::load_from_memory(&encoded_image).context("Couldn't load image")?,
                     encoded_format,
                 )
             }
@@ -136,54 +141,110 @@ fn load_image(source: Source<'_>, base: Option<&Path>, buffer_data: &[Data]) ->
             let encoded_format = match mime_type {
                 "image/png" => Png,
                 "image/jpeg" => Jpeg,
-                _ => panic!("Couldn't determine format of image"),
+                _ => bail!("Couldn't determine format of image"),
             };
             (
-                image::load_from_memory(encoded_image).expect("Couldn't load image"),
+                image::load_from_memory(encoded_image).context("Couldn't load image")?,
                 encoded_format,
             )
         }
-        _ => panic!("Unsupported source"),
+        _ => bail!("Unsupported source"),
     };
 
-    decoded_image
+    Ok(decoded_image)
+}
+
+/**
+A glTF file that was read from disk, with its buffers and decoded images. Reading doesn't need the GPU and is the slow
+part of loading, so it can happen on another thread; the data is then uploaded with `upload_gltf`.
+ */
+pub struct GltfData {
+    document: gltf::Document,
+    buffers: Vec<Data>,
+    images: HashMap<u32, DynamicImage>,
+}
+
+pub fn read_gltf(path: &Path) -> Result<GltfData> {
+    // todo skip loading of images on gltf lib side
+    let (document, buffers, _) =
+        gltf::import(path).with_context(|| format!("Couldn't import glTF file {}", path.display()))?;
+    let images = document
+        .images()
+        .map(|image| {
+            let decoded = load_image(image.source(), Path::new(path).parent(), &buffers)
+                .with_context(|| format!("Couldn't load image {}", image.index()))?;
+            Ok((image.index() as u32, decoded))
+        })
+        .collect::<Result<_>>()?;
+    Ok(GltfData {
+        document,
+        buffers,
+        images,
+    })
+}
+
+/**
+The bind group layouts that the textures, materials, meshes and lights of a glTF file are created with.
+ */
+#[derive(Clone, Copy)]
+pub struct GltfLayouts<'a> {
+    pub texture: &'a BindGroupLayout,
+    pub material: &'a BindGroupLayout,
+    pub mesh: &'a BindGroupLayout,
+    pub light: &'a BindGroupLayout,
 }
 
 pub fn load_gltf(
     path: &Path,
     device: &Device,
     queue: &Queue,
-    texture_bind_group_layout: &BindGroupLayout,
-    material_bind_group_layout: &BindGroupLayout,
-    mesh_bind_group_layout: &BindGroupLayout,
-    light_bind_group_layout: &BindGroupLayout,
+    layouts: GltfLayouts,
+    texture_manager: &mut TextureManager,
+    material_manager: &mut MaterialManager,
+) -> Result<Vec<Scene>> {
+    upload_gltf(
+        read_gltf(path)?,
+        device,
+        queue,
+        layouts,
+        texture_manager,
+        material_manager,
+    )
+}
+
+/**
+Creates the textures, materials and scenes of a glTF file that was read with `read_gltf`.
+ */
+pub fn upload_gltf(
+    data: GltfData,
+    device: &Device,
+    queue: &Queue,
+    layouts: GltfLayouts,
     texture_manager: &mut TextureManager,
     material_manager: &mut MaterialManager,
-) -> Vec<Scene> {
-    let (gltf, buffers, _) = gltf::import(path).unwrap(); // todo skip loading of images on gltf lib side
+) -> Result<Vec<Scene>> {
+    let GltfData {
+        document: gltf,
+        buffers,
+        images,
+    } = data;
 
     info!("GLTF has {:?} scenes", gltf.scenes().len());
 
     let mut scenes: Vec<Scene> = vec![];
-    let mut images: HashMap<u32, DynamicImage> = HashMap::with_capacity(gltf.images().len());
-    for image in gltf.images() {
-        images.insert(
-            image.index() as u32,
-            load_image(image.source(), Path::new(path).parent(), &buffers),
-        );
-    }
     // because gltf texture IDs need not correspond to our global texture IDs, we have to keep track of them separately at first
     let local_textures = gltf
         .textures()
         .map(|gltf_texture| {
-            let img = images.remove(&(gltf_texture.source().index() as u32)).unwrap();
-            let texture = Texture::from_image(device, queue, &img, gltf_texture.name(), TextureKind::Other)
-                .expect("Couldn't create texture");
-
-            let global_id = texture_manager.add_texture(texture);
-            (gltf_texture.index(), global_id)
+            // several textures may share the same image
+            let img = images
+                .get(&(gltf_texture.source().index() as u32))
+                .ok_or_else(|| anyhow!("Texture {} has no image", gltf_texture.index()))?;
+            // images that were imported before, e.g. by another file, are reused
+            let global_id = texture_manager.add_image(device, queue, img, gltf_texture.name(), TextureKind::Other);
+            Ok((gltf_texture.index(), global_id))
         })
-        .collect::<HashMap<_, _>>();
+        .collect::<Result<HashMap<_, _>>>()?;
 
     let local_materials = gltf
         .materials()
@@ -203,17 +264,13 @@ pub fn load_gltf(
                     .pbr_metallic_roughness()
                     .base_color_texture()
                     .map(|t| t.texture().index())
-                    .map(|id| *local_textures.get(&id).expect("Couldn't find base texture")),
+                    .and_then(|id| local_textures.get(&id).copied()),
                 albedo: gltf_mat.pbr_metallic_roughness().base_color_factor().into(),
                 metallic_roughness_texture: gltf_mat
                     .pbr_metallic_roughness()
                     .metallic_roughness_texture()
                     .map(|t| t.texture().index())
-                    .map(|id| {
-                        *local_textures
-                            .get(&id)
-                            .expect("Couldn't find metallic roughness texture")
-                    }),
+                    .and_then(|id| local_textures.get(&id).copied()),
                 metallic_roughness_factors: Vec2::from((
                     gltf_mat.pbr_metallic_roughness().metallic_factor(),
                     gltf_mat.pbr_metallic_roughness().roughness_factor(),
@@ -221,22 +278,33 @@ pub fn load_gltf(
                 normal_texture: gltf_mat
                     .normal_texture()
                     .map(|t| t.texture().index())
-                    .map(|id| *local_textures.get(&id).expect("Couldn't find normal texture")),
+                    .and_then(|id| local_textures.get(&id).copied()),
                 occlusion_texture: gltf_mat
                     .occlusion_texture()
                     .map(|t| t.texture().index())
-                    .map(|id| *local_textures.get(&id).expect("Couldn't find occlusion texture")),
+                    .and_then(|id| local_textures.get(&id).copied()),
                 occlusion_factor: 1.0, // TODO: Impl: try to read strength from glTF
                 emissive_texture: gltf_mat
                     .emissive_texture()
                     .map(|t| t.texture().index())
-                    .map(|id| *local_textures.get(&id).expect("Couldn't find emissive texture")),
+                    .and_then(|id| local_textures.get(&id).copied()),
                 emissive_factors: gltf_mat.emissive_factor().into(),
                 texture_bind_group: None,
             }; // TODO move this into a function (automatically init texture_bind_group, buffer and MaterialInfo)
-            mat.create_texture_bind_group(device, texture_bind_group_layout, texture_manager);
+            if let Some(global_id) = material_manager.find_identical(&mat) {
+                // copies of the same asset, or files that share textures and factors, share their materials
+                debug!("Material {:?} is a duplicate of material {:?}", mat.name, global_id);
+                return (index, global_id);
+            }
+            mat.create_texture_bind_group(device, layouts.texture, texture_manager);
             let global_id =
-                material_manager.add_material(Material::Pbr(mat), device, queue, material_bind_group_layout);
+                material_manager.add_material(
+                    Material::Pbr(mat),
+                    device,
+                    queue,
+                    layouts.material,
+                    texture_manager,
+                );
             (index, global_id)
         })
         .collect::<HashMap<_, _>>();
@@ -248,7 +316,7 @@ pub fn load_gltf(
         let models: Vec<Model> = scene
             .nodes()
             .map(|n| {
-                load_node(
+                let mut model = load_node(
                     &n,
                     &buffers,
                     &local_materials,
@@ -256,7 +324,10 @@ pub fn load_gltf(
                     neutral,
                     &mut num_lights,
                     device,
-                )
+                );
+                // the models keep their world transforms, also those without meshes and lights
+                model.update_transforms(neutral);
+                model
             })
             .collect();
         scenes.push(Scene::from(
@@ -265,11 +336,11 @@ pub fn load_gltf(
             models,
             material_manager,
             scene.name().map(Box::from),
-            mesh_bind_group_layout,
-            light_bind_group_layout,
+            layouts.mesh,
+            layouts.light,
         ));
     }
-    scenes
+    Ok(scenes)
 }
 
 fn load_node(
@@ -324,7 +395,7 @@ fn load_node(
             let mat = gltf_primitive
                 .material()
                 .index()
-                .map(|i| *materials.get(&i).expect("Couldn't find material"));
+                .and_then(|i| materials.get(&i).copied());
             meshes.push(Mesh::from(
                 positions,
                 indices,
@@ -333,7 +404,6 @@ fn load_node(
                 mat.unwrap_or(material_manager.default_material),
                 uvs,
                 global_transform,
-                device,
             ));
         }
     }
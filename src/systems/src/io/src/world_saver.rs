@@ -5,8 +5,9 @@
 // use log::debug;
 //
 // use lib::scene_serde::WorldSerde;
+// use lib::util::cache::AssetCache;
 //
-// pub fn save(path: &Path, world: WorldSerde) -> Result<(), Box<dyn Error>> {
+// pub fn save(path: &Path, world: WorldSerde, cache: &AssetCache) -> Result<(), Box<dyn Error>> {
 //     debug!("Saving world to {}", path.to_str().unwrap());
 //     fs::create_dir_all(path.join("images"))?;
 //     for texture in world.textures.textures.as_slice() {
@@ -14,11 +15,7 @@
 //             "Texture has path {}",
 //             texture.img_path.clone().to_str().unwrap()
 //         );
-//         fs::copy(
-//             Path::new("run").join(texture.img_path.clone()),
-//             path.join("images")
-//                 .join(texture.img_path.clone().file_name().unwrap()),
-//         )?;
+//         cache.copy_to(&[texture.img_path.clone()], &path.join("images"))?;
 //     }
 //     let serialized = serde_json::to_string(&world)?;
 //     fs::write(path.join("world.json"), serialized)?;